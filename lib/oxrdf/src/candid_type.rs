@@ -0,0 +1,61 @@
+//! [Candid](https://github.com/dfinity/candid) encodings of the RDF terms.
+//!
+//! The IRIs, blank node identifiers and variable names are encoded as `text`.
+//! The literals are encoded as `record { value : text; datatype : text; language : opt text }`.
+//! The other terms, triples and quads derive their encoding from their definition.
+
+use crate::{BlankNode, Literal, NamedNode, Variable};
+use candid::types::{Serializer, Type};
+use candid::CandidType;
+
+impl CandidType for NamedNode {
+    fn _ty() -> Type {
+        String::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.as_str().idl_serialize(serializer)
+    }
+}
+
+impl CandidType for BlankNode {
+    fn _ty() -> Type {
+        String::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.as_str().idl_serialize(serializer)
+    }
+}
+
+impl CandidType for Variable {
+    fn _ty() -> Type {
+        String::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.as_str().idl_serialize(serializer)
+    }
+}
+
+#[derive(CandidType)]
+struct CandidLiteral {
+    value: String,
+    datatype: String,
+    language: Option<String>,
+}
+
+impl CandidType for Literal {
+    fn _ty() -> Type {
+        CandidLiteral::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        CandidLiteral {
+            value: self.value().into(),
+            datatype: self.datatype().as_str().into(),
+            language: self.language().map(Into::into),
+        }
+        .idl_serialize(serializer)
+    }
+}
@@ -534,6 +534,41 @@ impl Dataset {
         }
     }
 
+    /// Checks if the dataset is [isomorphic](https://www.w3.org/TR/rdf11-datasets/#dfn-isomorphic-datasets) to an other dataset,
+    /// i.e. if they are equal up to a renaming of their blank nodes.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxrdf::*;
+    ///
+    /// let iri = NamedNodeRef::new("http://example.com")?;
+    ///
+    /// let bnode1 = BlankNode::default();
+    /// let dataset1 = [QuadRef::new(iri, iri, &bnode1, &bnode1)].into_iter().collect::<Dataset>();
+    ///
+    /// let bnode2 = BlankNode::default();
+    /// let dataset2 = [QuadRef::new(iri, iri, &bnode2, &bnode2)].into_iter().collect::<Dataset>();
+    ///
+    /// assert_ne!(dataset1, dataset2);
+    /// assert!(dataset1.is_isomorphic(&dataset2));
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Warning: If the datasets are not equal, it [canonicalizes](Self::canonicalize) copies of them and has the same worst-case complexity.
+    pub fn is_isomorphic(&self, other: &Self) -> bool {
+        if self.len() != other.len() || self.blank_nodes().len() != other.blank_nodes().len() {
+            return false;
+        }
+        if self == other {
+            return true;
+        }
+        let mut a = self.iter().collect::<Self>();
+        let mut b = other.iter().collect::<Self>();
+        a.canonicalize();
+        b.canonicalize();
+        a == b
+    }
+
     fn blank_nodes(&self) -> HashSet<InternedBlankNode> {
         let mut bnodes = HashSet::new();
         for (g, s, _, o) in &self.gspo {
@@ -214,6 +214,34 @@ impl Graph {
     pub fn canonicalize(&mut self) {
         self.dataset.canonicalize()
     }
+
+    /// Checks if the graph is [isomorphic](https://www.w3.org/TR/rdf11-concepts/#dfn-graph-isomorphism) to an other graph,
+    /// i.e. if they are equal up to a renaming of their blank nodes.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxrdf::*;
+    ///
+    /// let iri = NamedNodeRef::new("http://example.com")?;
+    ///
+    /// let bnode1 = BlankNode::default();
+    /// let graph1 = [TripleRef::new(iri, iri, &bnode1), TripleRef::new(&bnode1, iri, iri)].into_iter().collect::<Graph>();
+    ///
+    /// let bnode2 = BlankNode::default();
+    /// let graph2 = [TripleRef::new(iri, iri, &bnode2), TripleRef::new(&bnode2, iri, iri)].into_iter().collect::<Graph>();
+    ///
+    /// assert_ne!(graph1, graph2);
+    /// assert!(graph1.is_isomorphic(&graph2));
+    ///
+    /// let graph3 = [TripleRef::new(iri, iri, &bnode1), TripleRef::new(&bnode2, iri, iri)].into_iter().collect::<Graph>();
+    /// assert!(!graph1.is_isomorphic(&graph3));
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Warning: If the graphs are not equal, it [canonicalizes](Self::canonicalize) copies of them and has the same worst-case complexity.
+    pub fn is_isomorphic(&self, other: &Self) -> bool {
+        self.dataset.is_isomorphic(&other.dataset)
+    }
 }
 
 impl PartialEq for Graph {
@@ -5,6 +5,8 @@
 #![doc(html_logo_url = "https://raw.githubusercontent.com/oxigraph/oxigraph/main/logo.svg")]
 
 mod blank_node;
+#[cfg(feature = "candid")]
+mod candid_type;
 pub mod dataset;
 pub mod graph;
 mod interning;
@@ -1,5 +1,59 @@
 //! Provides ready to use [`NamedNodeRef`](super::NamedNodeRef)s for basic RDF vocabularies.
 
+pub mod owl {
+    //! [OWL 2](https://www.w3.org/TR/owl2-syntax/) vocabulary.
+    use crate::named_node::NamedNodeRef;
+
+    /// The class of all the values of the property of the subject restriction.
+    pub const ALL_VALUES_FROM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#allValuesFrom");
+    /// The class of OWL classes.
+    pub const CLASS: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#Class");
+    /// The subject and object classes have the same instances.
+    pub const EQUIVALENT_CLASS: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#equivalentClass");
+    /// The subject and object properties relate the same individuals.
+    pub const EQUIVALENT_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#equivalentProperty");
+    /// The class of the properties with at most one value for each individual.
+    pub const FUNCTIONAL_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#FunctionalProperty");
+    /// The value that the individuals of the subject restriction have for its property.
+    pub const HAS_VALUE: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#hasValue");
+    /// The class of the properties whose values identify the individuals.
+    pub const INVERSE_FUNCTIONAL_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#InverseFunctionalProperty");
+    /// The subject property is the inverse of the object property.
+    pub const INVERSE_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#inverseOf");
+    /// The class of the properties relating individuals.
+    pub const OBJECT_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#ObjectProperty");
+    /// The property of the subject restriction.
+    pub const ON_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#onProperty");
+    /// The subject property is the composition of the properties of the object list.
+    pub const PROPERTY_CHAIN_AXIOM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#propertyChainAxiom");
+    /// The subject and object individuals are the same.
+    pub const SAME_AS: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#sameAs");
+    /// The class of at least one of the values of the property of the subject restriction.
+    pub const SOME_VALUES_FROM: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#someValuesFrom");
+    /// The class of symmetric properties.
+    pub const SYMMETRIC_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#SymmetricProperty");
+    /// The class of every individual.
+    pub const THING: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#Thing");
+    /// The class of transitive properties.
+    pub const TRANSITIVE_PROPERTY: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#TransitiveProperty");
+}
+
 pub mod rdf {
     //! [RDF](https://www.w3.org/TR/rdf11-concepts/) vocabulary.
     use crate::named_node::NamedNodeRef;
@@ -109,6 +163,42 @@ pub mod rdfs {
         NamedNodeRef::new_unchecked("http://www.w3.org/2000/01/rdf-schema#subPropertyOf");
 }
 
+pub mod skos {
+    //! [SKOS](https://www.w3.org/TR/skos-reference/) vocabulary.
+    use crate::named_node::NamedNodeRef;
+
+    /// Relates a concept to a concept that is more general in meaning.
+    pub const BROADER: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#broader");
+    /// The transitive closure of `broader`.
+    pub const BROADER_TRANSITIVE: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#broaderTransitive");
+    /// The class of SKOS concepts.
+    pub const CONCEPT: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#Concept");
+    /// The class of SKOS concept schemes.
+    pub const CONCEPT_SCHEME: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#ConceptScheme");
+    /// Relates a concept scheme to a concept which is topmost in the broader/narrower hierarchy.
+    pub const HAS_TOP_CONCEPT: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#hasTopConcept");
+    /// Relates a resource to a concept scheme in which it is included.
+    pub const IN_SCHEME: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#inScheme");
+    /// Relates a concept to a concept that is more specific in meaning.
+    pub const NARROWER: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#narrower");
+    /// The transitive closure of `narrower`.
+    pub const NARROWER_TRANSITIVE: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#narrowerTransitive");
+    /// The preferred lexical label for a resource, in a given language.
+    pub const PREF_LABEL: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#prefLabel");
+    /// Relates a concept to the concept scheme that it is a top level concept of.
+    pub const TOP_CONCEPT_OF: NamedNodeRef<'_> =
+        NamedNodeRef::new_unchecked("http://www.w3.org/2004/02/skos/core#topConceptOf");
+}
+
 pub mod xsd {
     //! [RDF compatible XSD datatypes](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-compatible-xsd-types).
     use crate::named_node::NamedNodeRef;
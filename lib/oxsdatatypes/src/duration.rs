@@ -301,6 +301,32 @@ impl YearMonthDuration {
         })
     }
 
+    /// [op:multiply-yearMonthDuration](https://www.w3.org/TR/xpath-functions/#func-multiply-yearMonthDuration)
+    #[inline]
+    pub fn checked_mul(self, rhs: impl Into<Decimal>) -> Option<Self> {
+        Self::from_decimal_months(Decimal::from(self.months).checked_mul(rhs)?)
+    }
+
+    /// [op:divide-yearMonthDuration](https://www.w3.org/TR/xpath-functions/#func-divide-yearMonthDuration)
+    #[inline]
+    pub fn checked_div(self, rhs: impl Into<Decimal>) -> Option<Self> {
+        Self::from_decimal_months(Decimal::from(self.months).checked_div(rhs)?)
+    }
+
+    /// [op:divide-yearMonthDuration-by-yearMonthDuration](https://www.w3.org/TR/xpath-functions/#func-divide-yearMonthDuration-by-yearMonthDuration)
+    #[inline]
+    pub fn checked_div_duration(self, rhs: Self) -> Option<Decimal> {
+        Decimal::from(self.months).checked_div(rhs.months)
+    }
+
+    /// The months are rounded to the nearest integer
+    #[inline]
+    fn from_decimal_months(months: Decimal) -> Option<Self> {
+        Some(Self {
+            months: months.round().as_i128().try_into().ok()?,
+        })
+    }
+
     /// Checks if the two values are [identical](https://www.w3.org/TR/xmlschema11-2/#identity).
     #[inline]
     pub fn is_identical_with(&self, other: &Self) -> bool {
@@ -467,6 +493,28 @@ impl DayTimeDuration {
         })
     }
 
+    /// [op:multiply-dayTimeDuration](https://www.w3.org/TR/xpath-functions/#func-multiply-dayTimeDuration)
+    #[inline]
+    pub fn checked_mul(&self, rhs: impl Into<Decimal>) -> Option<Self> {
+        Some(Self {
+            seconds: self.seconds.checked_mul(rhs)?,
+        })
+    }
+
+    /// [op:divide-dayTimeDuration](https://www.w3.org/TR/xpath-functions/#func-divide-dayTimeDuration)
+    #[inline]
+    pub fn checked_div(&self, rhs: impl Into<Decimal>) -> Option<Self> {
+        Some(Self {
+            seconds: self.seconds.checked_div(rhs)?,
+        })
+    }
+
+    /// [op:divide-dayTimeDuration-by-dayTimeDuration](https://www.w3.org/TR/xpath-functions/#func-divide-dayTimeDuration-by-dayTimeDuration)
+    #[inline]
+    pub fn checked_div_duration(&self, rhs: Self) -> Option<Decimal> {
+        self.seconds.checked_div(rhs.seconds)
+    }
+
     /// Checks if the two values are [identical](https://www.w3.org/TR/xmlschema11-2/#identity).
     #[inline]
     pub fn is_identical_with(&self, other: &Self) -> bool {
@@ -596,6 +644,7 @@ impl Neg for DayTimeDuration {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::error::Error;
 
     #[test]
     fn from_str() -> Result<(), XsdParseError> {
@@ -774,6 +823,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mul() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            YearMonthDuration::from_str("P2Y11M")?.checked_mul(Decimal::from_str("2.3")?),
+            Some(YearMonthDuration::from_str("P6Y9M")?)
+        );
+        assert_eq!(
+            DayTimeDuration::from_str("PT2H10M")?.checked_mul(Decimal::from_str("2.1")?),
+            Some(DayTimeDuration::from_str("PT4H33M")?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn div() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            YearMonthDuration::from_str("P2Y11M")?.checked_div(Decimal::from_str("1.5")?),
+            Some(YearMonthDuration::from_str("P1Y11M")?)
+        );
+        assert_eq!(
+            YearMonthDuration::from_str("P3Y4M")?
+                .checked_div_duration(YearMonthDuration::from_str("-P1Y4M")?),
+            Some(Decimal::from_str("-2.5")?)
+        );
+        assert_eq!(
+            DayTimeDuration::from_str("P1DT2H30M10.5S")?.checked_div(Decimal::from_str("1.5")?),
+            Some(DayTimeDuration::from_str("PT17H40M7S")?)
+        );
+        assert_eq!(
+            DayTimeDuration::from_str("PT1H")?
+                .checked_div_duration(DayTimeDuration::from_str("PT30M")?),
+            Some(Decimal::from(2))
+        );
+        assert_eq!(
+            DayTimeDuration::from_str("PT1H")?.checked_div(Decimal::from(0)),
+            None
+        );
+        Ok(())
+    }
+
     #[test]
     fn minimally_conformant() -> Result<(), XsdParseError> {
         // All minimally conforming processors must support fractional-second duration values
@@ -1,5 +1,9 @@
 //! Definition of [`QuerySolution`] structure and associated utility constructions.
 
+#[cfg(feature = "candid")]
+use candid::types::{Serializer, Type};
+#[cfg(feature = "candid")]
+use candid::CandidType;
 use oxrdf::{Term, Variable, VariableRef};
 use std::fmt;
 use std::iter::Zip;
@@ -209,6 +213,31 @@ impl fmt::Debug for QuerySolution {
     }
 }
 
+/// The solution is encoded as the Candid `vec record { variable : text; value : Term }` of its bound variables.
+#[cfg(feature = "candid")]
+impl CandidType for QuerySolution {
+    fn _ty() -> Type {
+        Vec::<CandidBinding>::ty()
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.iter()
+            .map(|(variable, value)| CandidBinding {
+                variable: variable.clone(),
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>()
+            .idl_serialize(serializer)
+    }
+}
+
+#[cfg(feature = "candid")]
+#[derive(CandidType)]
+struct CandidBinding {
+    variable: Variable,
+    value: Term,
+}
+
 /// An iterator over [`QuerySolution`] bound variables.
 ///
 /// ```
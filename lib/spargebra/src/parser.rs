@@ -16,8 +16,13 @@ use std::str::Chars;
 use std::str::FromStr;
 use std::{char, fmt};
 
-/// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query.
-pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseError> {
+/// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query
+/// and some prefixes declared before the query prologue.
+pub fn parse_query(
+    query: &str,
+    base_iri: Option<&str>,
+    prefixes: HashMap<String, String>,
+) -> Result<Query, ParseError> {
     let mut state = ParserState {
         base_iri: if let Some(base_iri) = base_iri {
             Some(Iri::parse(base_iri.to_owned()).map_err(|e| ParseError {
@@ -26,7 +31,7 @@ pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseEr
         } else {
             None
         },
-        namespaces: HashMap::default(),
+        namespaces: prefixes,
         used_bnodes: HashSet::default(),
         currently_used_bnodes: HashSet::default(),
         aggregates: Vec::new(),
@@ -37,8 +42,13 @@ pub fn parse_query(query: &str, base_iri: Option<&str>) -> Result<Query, ParseEr
     })
 }
 
-/// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query.
-pub fn parse_update(update: &str, base_iri: Option<&str>) -> Result<Update, ParseError> {
+/// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query
+/// and some prefixes declared before the update prologue.
+pub fn parse_update(
+    update: &str,
+    base_iri: Option<&str>,
+    prefixes: HashMap<String, String>,
+) -> Result<Update, ParseError> {
     let mut state = ParserState {
         base_iri: if let Some(base_iri) = base_iri {
             Some(Iri::parse(base_iri.to_owned()).map_err(|e| ParseError {
@@ -47,7 +57,7 @@ pub fn parse_update(update: &str, base_iri: Option<&str>) -> Result<Update, Pars
         } else {
             None
         },
-        namespaces: HashMap::default(),
+        namespaces: prefixes,
         used_bnodes: HashSet::default(),
         currently_used_bnodes: HashSet::default(),
         aggregates: Vec::new(),
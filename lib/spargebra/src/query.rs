@@ -2,6 +2,7 @@ use crate::algebra::*;
 use crate::parser::{parse_query, ParseError};
 use crate::term::*;
 use oxiri::Iri;
+use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -61,7 +62,27 @@ pub enum Query {
 impl Query {
     /// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query.
     pub fn parse(query: &str, base_iri: Option<&str>) -> Result<Self, ParseError> {
-        parse_query(query, base_iri)
+        parse_query(query, base_iri, HashMap::new())
+    }
+
+    /// Parses a SPARQL query with some prefixes declared implicitly, as if they were in the query prologue.
+    ///
+    /// The `PREFIX` declarations of the query override them.
+    ///
+    /// ```
+    /// use spargebra::Query;
+    ///
+    /// let prefixes = [("ex".to_owned(), "http://example.com/".to_owned())];
+    /// let query = Query::parse_with_prefixes("SELECT ?s WHERE { ?s ex:p ?o }", None, prefixes)?;
+    /// assert_eq!(query.to_string(), "SELECT ?s WHERE { ?s <http://example.com/p> ?o . }");
+    /// # Ok::<_, spargebra::ParseError>(())
+    /// ```
+    pub fn parse_with_prefixes(
+        query: &str,
+        base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ParseError> {
+        parse_query(query, base_iri, prefixes.into_iter().collect())
     }
 
     /// Formats using the [SPARQL S-Expression syntax](https://jena.apache.org/documentation/notes/sse.html).
@@ -2,6 +2,7 @@ use crate::algebra::*;
 use crate::parser::{parse_update, ParseError};
 use crate::term::*;
 use oxiri::Iri;
+use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -27,7 +28,18 @@ pub struct Update {
 impl Update {
     /// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query.
     pub fn parse(update: &str, base_iri: Option<&str>) -> Result<Self, ParseError> {
-        parse_update(update, base_iri)
+        parse_update(update, base_iri, HashMap::new())
+    }
+
+    /// Parses a SPARQL update with some prefixes declared implicitly, as if they were in the update prologue.
+    ///
+    /// The `PREFIX` declarations of the update override them.
+    pub fn parse_with_prefixes(
+        update: &str,
+        base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ParseError> {
+        parse_update(update, base_iri, prefixes.into_iter().collect())
     }
 
     /// Formats using the [SPARQL S-Expression syntax](https://jena.apache.org/documentation/notes/sse.html).
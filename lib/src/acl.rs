@@ -0,0 +1,262 @@
+//! Access control of the graphs by caller principal.
+//!
+//! The access control list is stored as RDF in the [`ACL_GRAPH`] named graph of the store:
+//! * `<graph> acl:read <agent>` allows the agent to read the graph,
+//! * `<graph> acl:write <agent>` allows the agent to read and write the graph,
+//! * `<principal> acl:role <role>` gives to the principal the access of the role.
+//!
+//! An agent is either a principal, identified by its [`principal_iri`], or a role IRI.
+//! The default graph is identified by [`DEFAULT_GRAPH`].
+//! A graph without any `acl:read` or `acl:write` triple is readable and writable by everyone,
+//! the other graphs are only readable and writable by the agents they are granted to.
+//!
+//! The access control list is enforced by [`Store::query_as`] and [`Store::update_as`]:
+//! the graphs the caller can not read are hidden from the evaluation
+//! and the writes into the graphs it can not write fail with [`StorageError::AccessDenied`].
+//! The [`ACL_GRAPH`] itself is neither readable nor writable through them.
+//! The other [`Store`] methods ignore the access control list.
+//!
+//! Usage example:
+//! ```
+//! use ic_cdk::export::candid::Principal;
+//! use oxigraph::acl::{grant, principal_iri, Access};
+//! use oxigraph::model::*;
+//! use oxigraph::sparql::QueryResults;
+//! use oxigraph::store::Store;
+//!
+//! let store = Store::new()?;
+//! let graph = NamedNodeRef::new("http://example.com/private")?;
+//! let alice = Principal::management_canister();
+//! let bob = Principal::anonymous();
+//! store.update("INSERT DATA { GRAPH <http://example.com/private> { <http://example.com/s> <http://example.com/p> 1 } }")?;
+//! grant(&store, graph.into(), principal_iri(alice).as_ref(), Access::Write)?;
+//!
+//! if let QueryResults::Boolean(found) = store.query_as(bob, "ASK { GRAPH ?g { ?s ?p ?o } }")? {
+//!     assert!(!found);
+//! }
+//! assert!(store.update_as(bob, "INSERT DATA { GRAPH <http://example.com/private> { <http://example.com/s> <http://example.com/p> 2 } }").is_err());
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::model::*;
+use crate::store::{StorageError, Store};
+use ic_cdk::export::candid::Principal;
+use std::collections::{HashMap, HashSet};
+
+/// The named graph storing the access control list.
+pub const ACL_GRAPH: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:ic-oxigraph:acl");
+/// The predicate granting the read access to a graph.
+pub const READ: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:ic-oxigraph:acl#read");
+/// The predicate granting the read and write access to a graph.
+pub const WRITE: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:ic-oxigraph:acl#write");
+/// The predicate giving a role to a principal.
+pub const ROLE: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:ic-oxigraph:acl#role");
+/// The IRI identifying the default graph in the access control list.
+pub const DEFAULT_GRAPH: NamedNodeRef<'static> =
+    NamedNodeRef::new_unchecked("urn:ic-oxigraph:acl#defaultGraph");
+
+/// An access to a graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// The graph can be read.
+    Read,
+    /// The graph can be read and written.
+    Write,
+}
+
+impl Access {
+    fn predicate(self) -> NamedNodeRef<'static> {
+        match self {
+            Self::Read => READ,
+            Self::Write => WRITE,
+        }
+    }
+}
+
+/// The IRI identifying the principal in the access control list, `urn:ic-principal:` followed by its textual form.
+///
+/// ```
+/// use ic_cdk::export::candid::Principal;
+/// use oxigraph::acl::principal_iri;
+///
+/// assert_eq!(principal_iri(Principal::anonymous()).as_str(), "urn:ic-principal:2vxsx-fae");
+/// ```
+pub fn principal_iri(principal: Principal) -> NamedNode {
+    NamedNode::new_unchecked(format!("urn:ic-principal:{principal}"))
+}
+
+/// Grants the access to the graph to the agent, a principal IRI or a role.
+pub fn grant(
+    store: &Store,
+    graph_name: GraphNameRef<'_>,
+    agent: NamedNodeRef<'_>,
+    access: Access,
+) -> Result<(), StorageError> {
+    store.insert(QuadRef::new(
+        graph_subject(graph_name),
+        access.predicate(),
+        agent,
+        ACL_GRAPH,
+    ))?;
+    Ok(())
+}
+
+/// Revokes the access to the graph granted to the agent.
+///
+/// The graph becomes readable and writable by everyone if no access to it is granted anymore.
+pub fn revoke(
+    store: &Store,
+    graph_name: GraphNameRef<'_>,
+    agent: NamedNodeRef<'_>,
+    access: Access,
+) -> Result<(), StorageError> {
+    store.remove(QuadRef::new(
+        graph_subject(graph_name),
+        access.predicate(),
+        agent,
+        ACL_GRAPH,
+    ))?;
+    Ok(())
+}
+
+/// Gives the role to the principal.
+pub fn assign_role(
+    store: &Store,
+    principal: Principal,
+    role: NamedNodeRef<'_>,
+) -> Result<(), StorageError> {
+    store.insert(QuadRef::new(
+        &principal_iri(principal),
+        ROLE,
+        role,
+        ACL_GRAPH,
+    ))?;
+    Ok(())
+}
+
+/// Takes the role back from the principal.
+pub fn unassign_role(
+    store: &Store,
+    principal: Principal,
+    role: NamedNodeRef<'_>,
+) -> Result<(), StorageError> {
+    store.remove(QuadRef::new(
+        &principal_iri(principal),
+        ROLE,
+        role,
+        ACL_GRAPH,
+    ))?;
+    Ok(())
+}
+
+fn graph_subject(graph_name: GraphNameRef<'_>) -> SubjectRef<'_> {
+    match graph_name {
+        GraphNameRef::NamedNode(graph_name) => graph_name.into(),
+        GraphNameRef::BlankNode(graph_name) => graph_name.into(),
+        GraphNameRef::DefaultGraph => DEFAULT_GRAPH.into(),
+    }
+}
+
+/// The graphs a principal can read and write according to the access control list.
+///
+/// ```
+/// use ic_cdk::export::candid::Principal;
+/// use oxigraph::acl::{assign_role, grant, Access, GraphAccess};
+/// use oxigraph::model::*;
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let graph = NamedNodeRef::new("http://example.com/g")?;
+/// let editor = NamedNodeRef::new("http://example.com/editor")?;
+/// grant(&store, graph.into(), editor, Access::Write)?;
+/// assign_role(&store, Principal::anonymous(), editor)?;
+///
+/// let access = GraphAccess::for_principal(&store, Principal::anonymous())?;
+/// assert!(access.can_write(graph.into()));
+/// assert!(access.can_write(GraphNameRef::DefaultGraph));
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct GraphAccess {
+    hidden: HashSet<GraphName>,
+    read_only: HashSet<GraphName>,
+}
+
+impl GraphAccess {
+    /// Reads the access of the principal, directly or through its roles, from the access control list of the store.
+    pub fn for_principal(store: &Store, principal: Principal) -> Result<Self, StorageError> {
+        let principal = principal_iri(principal);
+        let mut agents = HashSet::new();
+        for quad in store.quads_for_pattern(
+            Some(principal.as_ref().into()),
+            Some(ROLE),
+            None,
+            Some(ACL_GRAPH.into()),
+        ) {
+            if let Term::NamedNode(role) = quad?.object {
+                agents.insert(role);
+            }
+        }
+        agents.insert(principal);
+
+        // The access granted to the agents of each graph having an access control
+        let mut granted = HashMap::<GraphName, Option<Access>>::new();
+        for quad in store.quads_for_pattern(None, None, None, Some(ACL_GRAPH.into())) {
+            let quad = quad?;
+            let access = if quad.predicate == READ {
+                Access::Read
+            } else if quad.predicate == WRITE {
+                Access::Write
+            } else {
+                continue;
+            };
+            let graph_name = match quad.subject {
+                Subject::NamedNode(node) if node == DEFAULT_GRAPH => GraphName::DefaultGraph,
+                Subject::NamedNode(node) => node.into(),
+                Subject::BlankNode(node) => node.into(),
+                Subject::Triple(_) => continue,
+            };
+            let entry = granted.entry(graph_name).or_default();
+            if matches!(&quad.object, Term::NamedNode(agent) if agents.contains(agent))
+                && *entry != Some(Access::Write)
+            {
+                *entry = Some(access);
+            }
+        }
+
+        let mut hidden = HashSet::from([GraphName::from(ACL_GRAPH.into_owned())]);
+        let mut read_only = HashSet::new();
+        for (graph_name, access) in granted {
+            match access {
+                None => {
+                    hidden.insert(graph_name);
+                }
+                Some(Access::Read) => {
+                    read_only.insert(graph_name);
+                }
+                Some(Access::Write) => (),
+            }
+        }
+        Ok(Self { hidden, read_only })
+    }
+
+    /// Checks if the graph can be read.
+    pub fn can_read(&self, graph_name: GraphNameRef<'_>) -> bool {
+        !self.hidden.contains(&graph_name.into_owned())
+    }
+
+    /// Checks if the graph can be written.
+    pub fn can_write(&self, graph_name: GraphNameRef<'_>) -> bool {
+        let graph_name = graph_name.into_owned();
+        !self.hidden.contains(&graph_name) && !self.read_only.contains(&graph_name)
+    }
+
+    /// The graphs that can not be read
+    pub(crate) fn hidden_graphs(&self) -> impl Iterator<Item = &GraphName> {
+        self.hidden.iter()
+    }
+
+    /// The graphs that can not be written
+    pub(crate) fn denied_graphs(&self) -> impl Iterator<Item = &GraphName> {
+        self.hidden.iter().chain(&self.read_only)
+    }
+}
@@ -0,0 +1,218 @@
+//! Cache of serialized SPARQL query results.
+//!
+//! Read-heavy canisters often serve the same queries again and again, for example the ones behind a dashboard.
+//! A [`QueryCache`] memoizes the serialization of their results, keyed by the query text and the results format,
+//! for the [commit version](Store::commit_version) of the store they have been computed against.
+//!
+//! The cache is invalidated automatically: all its entries are dropped as soon as a write is committed to the store.
+//! It is kept in memory and is lost on canister upgrade.
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::cache::QueryCache;
+//! use oxigraph::model::*;
+//! use oxigraph::sparql::QueryResultsFormat;
+//! use oxigraph::store::Store;
+//!
+//! let store = Store::new()?;
+//! let cache = QueryCache::new(store.clone());
+//! let query = "SELECT (COUNT(*) AS ?c) WHERE { ?s ?p ?o }";
+//!
+//! let results = cache.query(query, QueryResultsFormat::Csv)?;
+//! assert_eq!(&*results, b"c\r\n0\r\n");
+//! cache.query(query, QueryResultsFormat::Csv)?;
+//! assert_eq!((cache.hits(), cache.misses()), (1, 1));
+//!
+//! // The write invalidates the cache
+//! let ex = NamedNodeRef::new("http://example.com")?;
+//! store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+//! let results = cache.query(query, QueryResultsFormat::Csv)?;
+//! assert_eq!(&*results, b"c\r\n1\r\n");
+//! assert_eq!((cache.hits(), cache.misses()), (1, 2));
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::io::GraphFormat;
+use crate::sparql::{EvaluationError, QueryOptions, QueryResults, QueryResultsFormat};
+use crate::store::Store;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Default maximal number of entries of a cache.
+const DEFAULT_CAPACITY: usize = 100;
+
+/// A cache of serialized query results over a [`Store`].
+///
+/// All the queries are evaluated with the same [`QueryOptions`], given by [`with_options`](Self::with_options).
+/// Once the cache is full, the least recently used entry is dropped.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct QueryCache {
+    store: Store,
+    options: QueryOptions,
+    capacity: usize,
+    state: Rc<RefCell<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// The commit version of the store the entries have been computed against
+    version: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Incremented on each lookup to find the least recently used entry
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Eq, PartialEq, Hash)]
+struct CacheKey {
+    query: String,
+    media_type: &'static str,
+}
+
+struct CacheEntry {
+    results: Rc<[u8]>,
+    last_used: u64,
+}
+
+impl QueryCache {
+    /// Builds an empty cache of the results of the queries against the given store.
+    #[inline]
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            options: QueryOptions::default(),
+            capacity: DEFAULT_CAPACITY,
+            state: Rc::default(),
+        }
+    }
+
+    /// Evaluates the queries with the given options.
+    #[inline]
+    #[must_use]
+    pub fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the maximal number of cached results (100 by default).
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Returns the results of a `SELECT` or an `ASK` query serialized in the given format,
+    /// evaluating the query only if they are not in the cache.
+    pub fn query(
+        &self,
+        query: &str,
+        format: QueryResultsFormat,
+    ) -> Result<Rc<[u8]>, EvaluationError> {
+        self.cached(query, format.media_type(), |results, buffer| {
+            results.write(buffer, format)
+        })
+    }
+
+    /// Returns the results of a `CONSTRUCT` or a `DESCRIBE` query serialized in the given format,
+    /// evaluating the query only if they are not in the cache.
+    pub fn query_graph(
+        &self,
+        query: &str,
+        format: GraphFormat,
+    ) -> Result<Rc<[u8]>, EvaluationError> {
+        self.cached(query, format.media_type(), |results, buffer| {
+            results.write_graph(buffer, format)
+        })
+    }
+
+    /// Returns the number of queries answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.state.borrow().hits
+    }
+
+    /// Returns the number of queries that have been evaluated.
+    pub fn misses(&self) -> u64 {
+        self.state.borrow().misses
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.state.borrow().entries.len()
+    }
+
+    /// Returns if no result is cached.
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().entries.is_empty()
+    }
+
+    /// Drops all the cached results.
+    pub fn clear(&self) {
+        self.state.borrow_mut().entries.clear();
+    }
+
+    fn cached(
+        &self,
+        query: &str,
+        media_type: &'static str,
+        write: impl FnOnce(QueryResults, &mut Vec<u8>) -> Result<(), EvaluationError>,
+    ) -> Result<Rc<[u8]>, EvaluationError> {
+        let key = CacheKey {
+            query: query.into(),
+            media_type,
+        };
+        let version = self.store.commit_version()?;
+        {
+            let mut state = self.state.borrow_mut();
+            let state = &mut *state;
+            if state.version != version {
+                state.entries.clear();
+                state.version = version;
+            }
+            state.clock += 1;
+            if let Some(entry) = state.entries.get_mut(&key) {
+                entry.last_used = state.clock;
+                state.hits += 1;
+                return Ok(Rc::clone(&entry.results));
+            }
+            state.misses += 1;
+        }
+
+        let mut buffer = Vec::new();
+        write(
+            self.store.query_opt(query, self.options.clone())?,
+            &mut buffer,
+        )?;
+        let results = Rc::<[u8]>::from(buffer);
+
+        let mut state = self.state.borrow_mut();
+        // The results are not cached if the store has been written during the evaluation
+        if self.capacity > 0 && state.version == self.store.commit_version()? {
+            if state.entries.len() >= self.capacity {
+                let least_recently_used = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| CacheKey {
+                        query: key.query.clone(),
+                        media_type: key.media_type,
+                    });
+                if let Some(least_recently_used) = least_recently_used {
+                    state.entries.remove(&least_recently_used);
+                }
+            }
+            let last_used = state.clock;
+            state.entries.insert(
+                key,
+                CacheEntry {
+                    results: Rc::clone(&results),
+                    last_used,
+                },
+            );
+        }
+        Ok(results)
+    }
+}
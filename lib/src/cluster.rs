@@ -0,0 +1,647 @@
+//! A dataset sharded across several canisters running Oxigraph.
+//!
+//! A [`ShardRouter`] sends each quad to the shard canister selected by the hash of its subject,
+//! so all the quads about a subject are stored in the same shard.
+//! The patterns are sent to all the shards, except if their subject is known,
+//! and the SPARQL queries are evaluated by gathering from the shards the quads matching their triple patterns.
+//!
+//! The shard canisters expose the [`INSERT_METHOD`] and [`QUADS_FOR_PATTERN_METHOD`] update methods
+//! wrapping [`handle_insert`] and [`handle_quads_for_pattern`],
+//! that only accept the calls of the canisters running the router:
+//! ```no_run
+//! use oxigraph::cluster::{handle_insert, handle_quads_for_pattern, ShardPattern};
+//! use oxigraph::store::Store;
+//! use ic_cdk::export::Principal;
+//!
+//! thread_local! {
+//!     static STORE: Store = Store::new().unwrap();
+//!     static ROUTERS: Vec<Principal> = vec![Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap()];
+//! }
+//!
+//! // #[update]
+//! fn cluster_insert(quads: Vec<String>) -> Result<u64, String> {
+//!     ROUTERS.with(|routers| STORE.with(|store| handle_insert(store, routers, &quads)))
+//! }
+//!
+//! // #[update]
+//! fn cluster_quads_for_pattern(pattern: ShardPattern) -> Result<Vec<String>, String> {
+//!     ROUTERS.with(|routers| STORE.with(|store| handle_quads_for_pattern(store, routers, &pattern)))
+//! }
+//! # let _ = (cluster_insert, cluster_quads_for_pattern);
+//! ```
+//!
+//! The shard of a quad depends on the number of shards, so the quads must be moved if shards are added or removed.
+//!
+//! The blank nodes are identified by their labels in the exchanged statements and patterns:
+//! the shards store them with these labels as ids, so `_:b` is the same blank node in all the calls.
+//!
+//! This module is only available with the `cluster` feature and must be used inside a canister.
+use crate::io::read::ParseError;
+use crate::io::{DatasetFormat, DatasetParser};
+use crate::model::*;
+use crate::sparql::{EvaluationError, Query, QueryResults};
+use crate::store::{StorageError, Store};
+use ic_cdk::api::call::{call, RejectionCode};
+use ic_cdk::api::caller;
+use ic_cdk::export::candid::{CandidType, Deserialize, Principal};
+use siphasher::sip128::{Hasher128, SipHasher24};
+use spargebra::algebra::{
+    AggregateExpression, Expression, GraphPattern, OrderExpression, PropertyPathExpression,
+};
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hasher;
+use std::str::FromStr;
+
+/// The shard canister method inserting quads, see [`handle_insert`].
+pub const INSERT_METHOD: &str = "cluster_insert";
+/// The shard canister method returning the quads matching a pattern, see [`handle_quads_for_pattern`].
+pub const QUADS_FOR_PATTERN_METHOD: &str = "cluster_quads_for_pattern";
+
+/// Routes the quads and the queries to the shard canisters of a dataset.
+///
+/// ```no_run
+/// use oxigraph::cluster::ShardRouter;
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::QueryResults;
+/// use ic_cdk::export::Principal;
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let router = ShardRouter::new(vec![
+///         Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai")?,
+///         Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai")?,
+///     ]);
+///     let ex = NamedNodeRef::new("http://example.com")?;
+///     router.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph)).await?;
+///     if let QueryResults::Solutions(solutions) = router.query("SELECT * WHERE { ?s ?p ?o }").await? {
+///         assert_eq!(solutions.count(), 1);
+///     }
+///     Ok(())
+/// }
+/// # let _ = example;
+/// ```
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ShardRouter {
+    shards: Vec<Principal>,
+}
+
+impl ShardRouter {
+    /// Builds a router for the given shard canisters.
+    ///
+    /// The order of the shards must stay the same to find back the quads.
+    ///
+    /// Panics if `shards` is empty.
+    pub fn new(shards: Vec<Principal>) -> Self {
+        assert!(!shards.is_empty(), "A cluster needs at least one shard");
+        Self { shards }
+    }
+
+    /// The shard canisters.
+    #[inline]
+    pub fn shards(&self) -> &[Principal] {
+        &self.shards
+    }
+
+    /// Returns the shard storing the quads with the given subject.
+    pub fn shard_for<'a>(&self, subject: impl Into<SubjectRef<'a>>) -> Principal {
+        self.shards[self.shard_index(subject.into())]
+    }
+
+    fn shard_index(&self, subject: SubjectRef<'_>) -> usize {
+        let mut hasher = SipHasher24::new();
+        hasher.write(subject.to_string().as_bytes());
+        // The number of shards always fits in u128 and the remainder in usize
+        let shard_count = u128::try_from(self.shards.len()).unwrap_or(u128::MAX);
+        usize::try_from(hasher.finish128().as_u128() % shard_count).unwrap_or(0)
+    }
+
+    /// Adds a quad to its shard.
+    ///
+    /// Returns `true` if the quad was not already in the shard.
+    pub async fn insert<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, ClusterError> {
+        let quad = quad.into();
+        let shard = self.shard_for(quad.subject);
+        Ok(call_insert(shard, vec![format!("{quad} .")]).await? > 0)
+    }
+
+    /// Adds a set of quads, each shard receiving its quads in a single call.
+    ///
+    /// Returns the number of quads that were not already in the shards.
+    pub async fn extend(
+        &self,
+        quads: impl IntoIterator<Item = impl Into<Quad>>,
+    ) -> Result<u64, ClusterError> {
+        let mut batches = vec![Vec::new(); self.shards.len()];
+        for quad in quads {
+            let quad = quad.into();
+            batches[self.shard_index(quad.subject.as_ref())].push(format!("{quad} ."));
+        }
+        // The calls are sent when they are created so the shards are written concurrently
+        let calls = self
+            .shards
+            .iter()
+            .zip(batches)
+            .filter(|(_, batch)| !batch.is_empty())
+            .map(|(shard, batch)| call_insert(*shard, batch))
+            .collect::<Vec<_>>();
+        let mut inserted = 0;
+        for call in calls {
+            inserted += call.await?;
+        }
+        Ok(inserted)
+    }
+
+    /// Returns all the quads matching a given quad pattern.
+    ///
+    /// Only the shard of the subject is called if the subject is set.
+    pub async fn quads_for_pattern(
+        &self,
+        subject: Option<SubjectRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Result<Vec<Quad>, ClusterError> {
+        self.fetch(&ShardPattern {
+            subject: subject.map(|s| s.to_string()),
+            predicate: predicate.map(|p| p.to_string()),
+            object: object.map(|o| o.to_string()),
+            graph: match graph_name {
+                None => ShardGraph::Any,
+                Some(GraphNameRef::DefaultGraph) => ShardGraph::Default,
+                Some(graph_name) => ShardGraph::Graph(graph_name.to_string()),
+            },
+        })
+        .await
+    }
+
+    /// Evaluates a SPARQL query over the whole dataset.
+    ///
+    /// The quads matching the query triple patterns and property paths are gathered from the shards
+    /// in a temporary store in which the query is then evaluated.
+    /// The queries should be selective enough for these quads to fit in the router canister heap
+    /// and in the responses of the shards.
+    ///
+    /// The queries with `SERVICE` calls are not supported.
+    pub async fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, ClusterError> {
+        let query = query
+            .try_into()
+            .map_err(|e| ClusterError::Evaluation(e.into()))?;
+        // With a custom dataset, the default graph patterns might match quads from any graph
+        let default_graph = if query.dataset().is_default_dataset() {
+            ShardGraph::Default
+        } else {
+            ShardGraph::Any
+        };
+        let mut collector = PatternCollector::default();
+        collector.add_pattern(query.pattern(), &default_graph)?;
+        let fetches = collector
+            .patterns
+            .iter()
+            .map(|pattern| self.fetch(pattern))
+            .collect::<Vec<_>>();
+        let store = Store::new().map_err(EvaluationError::from)?;
+        for fetch in fetches {
+            store.extend(fetch.await?).map_err(EvaluationError::from)?;
+        }
+        Ok(store.query(query)?)
+    }
+
+    async fn fetch(&self, pattern: &ShardPattern) -> Result<Vec<Quad>, ClusterError> {
+        let shards = match &pattern.subject {
+            Some(subject) => {
+                vec![self.shard_for(&parse_subject(subject).map_err(EvaluationError::msg)?)]
+            }
+            None => self.shards.clone(),
+        };
+        // The calls are sent when they are created so the shards are read concurrently
+        let calls = shards
+            .into_iter()
+            .map(|shard| {
+                let call = call::<_, (Result<Vec<String>, String>,)>(
+                    shard,
+                    QUADS_FOR_PATTERN_METHOD,
+                    (pattern.clone(),),
+                );
+                async move { (shard, call.await) }
+            })
+            .collect::<Vec<_>>();
+        let mut quads = Vec::new();
+        for call in calls {
+            let (shard, result) = call.await;
+            let (result,) = result.map_err(|(code, message)| ClusterError::Call {
+                shard,
+                code,
+                message,
+            })?;
+            let statements = result.map_err(|message| ClusterError::Shard { shard, message })?;
+            quads.extend(parse_quads(&statements).map_err(|e| ClusterError::Shard {
+                shard,
+                message: format!("Invalid quads returned: {e}"),
+            })?);
+        }
+        Ok(quads)
+    }
+}
+
+/// A quad pattern sent to the shard canisters.
+///
+/// The terms are serialized using the [N-Triples](https://www.w3.org/TR/n-triples/) syntax, `None` matching any term.
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone, Hash)]
+#[candid_path("ic_cdk::export::candid")]
+#[serde(crate = "ic_cdk::export::serde")]
+pub struct ShardPattern {
+    pub subject: Option<String>,
+    pub predicate: Option<String>,
+    pub object: Option<String>,
+    pub graph: ShardGraph,
+}
+
+/// The graphs matched by a [`ShardPattern`].
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone, Hash)]
+#[candid_path("ic_cdk::export::candid")]
+#[serde(crate = "ic_cdk::export::serde")]
+pub enum ShardGraph {
+    /// All the graphs.
+    Any,
+    /// The default graph.
+    Default,
+    /// All the named graphs.
+    Named,
+    /// The named graph with the given name.
+    Graph(String),
+}
+
+/// Inserts the quads sent by a [`ShardRouter`], serialized as [N-Quads](https://www.w3.org/TR/n-quads/) statements.
+///
+/// Returns the number of quads that were not already in the store.
+/// Fails if the caller is not one of the `routers` canisters.
+pub fn handle_insert(
+    store: &Store,
+    routers: &[Principal],
+    quads: &[String],
+) -> Result<u64, String> {
+    check_caller(routers)?;
+    let quads = parse_quads(quads).map_err(|e| e.to_string())?;
+    store
+        .transaction(|mut transaction| {
+            let mut inserted = 0;
+            for quad in &quads {
+                if transaction.insert(quad)? {
+                    inserted += 1;
+                }
+            }
+            Ok::<_, StorageError>(inserted)
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the quads matching a pattern sent by a [`ShardRouter`], serialized as [N-Quads](https://www.w3.org/TR/n-quads/) statements.
+///
+/// Fails if the caller is not one of the `routers` canisters.
+pub fn handle_quads_for_pattern(
+    store: &Store,
+    routers: &[Principal],
+    pattern: &ShardPattern,
+) -> Result<Vec<String>, String> {
+    check_caller(routers)?;
+    let subject = pattern.subject.as_deref().map(parse_subject).transpose()?;
+    let predicate = pattern
+        .predicate
+        .as_deref()
+        .map(NamedNode::from_str)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let object = pattern
+        .object
+        .as_deref()
+        .map(Term::from_str)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let graph_name = match &pattern.graph {
+        ShardGraph::Any | ShardGraph::Named => None,
+        ShardGraph::Default => Some(GraphName::DefaultGraph),
+        ShardGraph::Graph(graph_name) => {
+            match Term::from_str(graph_name).map_err(|e| e.to_string())? {
+                Term::NamedNode(node) => Some(node.into()),
+                Term::BlankNode(node) => Some(node.into()),
+                term => return Err(format!("{term} can not be a graph name")),
+            }
+        }
+    };
+    let mut statements = Vec::new();
+    for quad in store.quads_for_pattern(
+        subject.as_ref().map(Subject::as_ref),
+        predicate.as_ref().map(NamedNode::as_ref),
+        object.as_ref().map(Term::as_ref),
+        graph_name.as_ref().map(GraphName::as_ref),
+    ) {
+        let quad = quad.map_err(|e| e.to_string())?;
+        if pattern.graph == ShardGraph::Named && quad.graph_name.is_default_graph() {
+            continue;
+        }
+        statements.push(format!("{quad} ."));
+    }
+    Ok(statements)
+}
+
+/// An error raised by a [`ShardRouter`].
+#[derive(Debug)]
+pub enum ClusterError {
+    /// The call to a shard canister failed.
+    Call {
+        shard: Principal,
+        code: RejectionCode,
+        message: String,
+    },
+    /// A shard canister returned an error.
+    Shard { shard: Principal, message: String },
+    /// The query is invalid or its evaluation failed.
+    Evaluation(EvaluationError),
+}
+
+impl fmt::Display for ClusterError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Call {
+                shard,
+                code,
+                message,
+            } => write!(
+                f,
+                "The call to the shard {shard} failed: {code:?}: {message}"
+            ),
+            Self::Shard { shard, message } => write!(f, "The shard {shard} failed: {message}"),
+            Self::Evaluation(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ClusterError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Call { .. } | Self::Shard { .. } => None,
+            Self::Evaluation(e) => Some(e),
+        }
+    }
+}
+
+impl From<EvaluationError> for ClusterError {
+    #[inline]
+    fn from(error: EvaluationError) -> Self {
+        Self::Evaluation(error)
+    }
+}
+
+async fn call_insert(shard: Principal, quads: Vec<String>) -> Result<u64, ClusterError> {
+    let (result,): (Result<u64, String>,) =
+        call(shard, INSERT_METHOD, (quads,))
+            .await
+            .map_err(|(code, message)| ClusterError::Call {
+                shard,
+                code,
+                message,
+            })?;
+    result.map_err(|message| ClusterError::Shard { shard, message })
+}
+
+/// Parses N-Quads statements, the blank node labels being kept as ids so that they are the same in all the statements and calls
+/// Rejects the calls that are not done by one of the router canisters.
+fn check_caller(routers: &[Principal]) -> Result<(), String> {
+    let caller = caller();
+    if routers.contains(&caller) {
+        Ok(())
+    } else {
+        Err(format!("{caller} is not allowed to call the shard"))
+    }
+}
+
+fn parse_quads(statements: &[String]) -> Result<Vec<Quad>, ParseError> {
+    let parser = DatasetParser::from_format(DatasetFormat::NQuads).with_blank_node_labels();
+    let mut quads = Vec::with_capacity(statements.len());
+    for statement in statements {
+        for quad in parser.read_quads(statement.as_bytes())? {
+            quads.push(quad?);
+        }
+    }
+    Ok(quads)
+}
+
+fn parse_subject(subject: &str) -> Result<Subject, String> {
+    match Term::from_str(subject).map_err(|e| e.to_string())? {
+        Term::NamedNode(node) => Ok(node.into()),
+        Term::BlankNode(node) => Ok(node.into()),
+        Term::Triple(triple) => Ok(triple.into()),
+        Term::Literal(literal) => Err(format!("The literal {literal} can not be a subject")),
+    }
+}
+
+/// Collects the quad patterns to gather from the shards to evaluate a graph pattern
+#[derive(Default)]
+struct PatternCollector {
+    patterns: Vec<ShardPattern>,
+}
+
+impl PatternCollector {
+    fn add(&mut self, pattern: ShardPattern) {
+        if !self.patterns.contains(&pattern) {
+            self.patterns.push(pattern);
+        }
+    }
+
+    fn add_pattern(
+        &mut self,
+        pattern: &GraphPattern,
+        graph: &ShardGraph,
+    ) -> Result<(), EvaluationError> {
+        match pattern {
+            GraphPattern::Bgp { patterns } => {
+                for TriplePattern {
+                    subject,
+                    predicate,
+                    object,
+                } in patterns
+                {
+                    if let TermPattern::Literal(_) = subject {
+                        continue; // No quad matches
+                    }
+                    self.add(ShardPattern {
+                        subject: ground_term(subject),
+                        predicate: match predicate {
+                            NamedNodePattern::NamedNode(p) => Some(p.to_string()),
+                            NamedNodePattern::Variable(_) => None,
+                        },
+                        object: ground_term(object),
+                        graph: graph.clone(),
+                    });
+                }
+                Ok(())
+            }
+            GraphPattern::Path { path, .. } => {
+                self.add_path(path, graph);
+                Ok(())
+            }
+            GraphPattern::Values { .. } => Ok(()),
+            GraphPattern::Join { left, right }
+            | GraphPattern::Lateral { left, right }
+            | GraphPattern::Union { left, right }
+            | GraphPattern::Minus { left, right } => {
+                self.add_pattern(left, graph)?;
+                self.add_pattern(right, graph)
+            }
+            GraphPattern::LeftJoin {
+                left,
+                right,
+                expression,
+            } => {
+                self.add_pattern(left, graph)?;
+                self.add_pattern(right, graph)?;
+                if let Some(expression) = expression {
+                    self.add_expression(expression, graph)?;
+                }
+                Ok(())
+            }
+            GraphPattern::Filter { expr, inner } => {
+                self.add_expression(expr, graph)?;
+                self.add_pattern(inner, graph)
+            }
+            GraphPattern::Extend {
+                inner, expression, ..
+            } => {
+                self.add_expression(expression, graph)?;
+                self.add_pattern(inner, graph)
+            }
+            GraphPattern::OrderBy { inner, expression } => {
+                for expression in expression {
+                    match expression {
+                        OrderExpression::Asc(e) | OrderExpression::Desc(e) => {
+                            self.add_expression(e, graph)?
+                        }
+                    }
+                }
+                self.add_pattern(inner, graph)
+            }
+            GraphPattern::Graph { name, inner } => self.add_pattern(
+                inner,
+                &match name {
+                    NamedNodePattern::NamedNode(name) => ShardGraph::Graph(name.to_string()),
+                    NamedNodePattern::Variable(_) => ShardGraph::Named,
+                },
+            ),
+            GraphPattern::Project { inner, .. }
+            | GraphPattern::Distinct { inner }
+            | GraphPattern::Reduced { inner }
+            | GraphPattern::Slice { inner, .. } => self.add_pattern(inner, graph),
+            GraphPattern::Group {
+                inner, aggregates, ..
+            } => {
+                for (_, aggregate) in aggregates {
+                    match aggregate {
+                        AggregateExpression::Count { expr, .. } => {
+                            if let Some(expr) = expr {
+                                self.add_expression(expr, graph)?;
+                            }
+                        }
+                        AggregateExpression::Sum { expr, .. }
+                        | AggregateExpression::Avg { expr, .. }
+                        | AggregateExpression::Min { expr, .. }
+                        | AggregateExpression::Max { expr, .. }
+                        | AggregateExpression::GroupConcat { expr, .. }
+                        | AggregateExpression::Sample { expr, .. }
+                        | AggregateExpression::Custom { expr, .. } => {
+                            self.add_expression(expr, graph)?
+                        }
+                    }
+                }
+                self.add_pattern(inner, graph)
+            }
+            GraphPattern::Service { name, .. } => Err(EvaluationError::msg(format!(
+                "SERVICE calls are not supported by the cluster queries (service {name})"
+            ))),
+        }
+    }
+
+    /// The paths might go through any subject, so all the triples with the path predicates are gathered
+    fn add_path(&mut self, path: &PropertyPathExpression, graph: &ShardGraph) {
+        match path {
+            PropertyPathExpression::NamedNode(p) => self.add(ShardPattern {
+                subject: None,
+                predicate: Some(p.to_string()),
+                object: None,
+                graph: graph.clone(),
+            }),
+            PropertyPathExpression::NegatedPropertySet(_) => self.add(ShardPattern {
+                subject: None,
+                predicate: None,
+                object: None,
+                graph: graph.clone(),
+            }),
+            PropertyPathExpression::Reverse(p)
+            | PropertyPathExpression::ZeroOrOne(p)
+            | PropertyPathExpression::ZeroOrMore(p)
+            | PropertyPathExpression::OneOrMore(p) => self.add_path(p, graph),
+            PropertyPathExpression::Sequence(a, b) | PropertyPathExpression::Alternative(a, b) => {
+                self.add_path(a, graph);
+                self.add_path(b, graph);
+            }
+        }
+    }
+
+    fn add_expression(
+        &mut self,
+        expression: &Expression,
+        graph: &ShardGraph,
+    ) -> Result<(), EvaluationError> {
+        match expression {
+            Expression::NamedNode(_)
+            | Expression::Literal(_)
+            | Expression::Variable(_)
+            | Expression::Bound(_) => Ok(()),
+            Expression::Or(a, b)
+            | Expression::And(a, b)
+            | Expression::Equal(a, b)
+            | Expression::SameTerm(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterOrEqual(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessOrEqual(a, b)
+            | Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b) => {
+                self.add_expression(a, graph)?;
+                self.add_expression(b, graph)
+            }
+            Expression::UnaryPlus(e) | Expression::UnaryMinus(e) | Expression::Not(e) => {
+                self.add_expression(e, graph)
+            }
+            Expression::In(a, b) => {
+                self.add_expression(a, graph)?;
+                b.iter().try_for_each(|e| self.add_expression(e, graph))
+            }
+            Expression::Exists(p) => self.add_pattern(p, graph),
+            Expression::If(a, b, c) => {
+                self.add_expression(a, graph)?;
+                self.add_expression(b, graph)?;
+                self.add_expression(c, graph)
+            }
+            Expression::Coalesce(l) | Expression::FunctionCall(_, l) => {
+                l.iter().try_for_each(|e| self.add_expression(e, graph))
+            }
+        }
+    }
+}
+
+/// The constant term of a pattern, the blank nodes being variables
+fn ground_term(term: &TermPattern) -> Option<String> {
+    match term {
+        TermPattern::NamedNode(node) => Some(node.to_string()),
+        TermPattern::Literal(literal) => Some(literal.to_string()),
+        TermPattern::BlankNode(_) | TermPattern::Triple(_) | TermPattern::Variable(_) => None,
+    }
+}
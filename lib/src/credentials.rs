@@ -0,0 +1,627 @@
+//! Verification of the [Data Integrity](https://www.w3.org/TR/vc-data-integrity/) proofs of [Verifiable Credentials](https://www.w3.org/TR/vc-data-model-2.0/) stored as RDF.
+//!
+//! A credential is stored in a named graph, linked by `https://w3id.org/security#proof` triples to the graphs containing its proofs,
+//! which is the shape given by the conversion of a JSON-LD credential to RDF.
+//! [`Store::verify_credential`] checks each proof:
+//! * the credential triples without the proof links and the proof triples without the proof value are canonicalized
+//!   with [RDFC-1.0](https://www.w3.org/TR/rdf-canon/) and hashed with SHA-256.
+//! * the signature of the concatenation of the proof hash and of the credential hash is checked with the public key of the verification method.
+//!
+//! The supported cryptosuites are `eddsa-rdfc-2022` with Ed25519 keys and `ecdsa-rdfc-2019` with P-256 keys.
+//! The public key is the `https://w3id.org/security#publicKeyMultibase` of the verification method if the store contains it,
+//! and is else read from the verification method IRI if it is a `did:key`.
+//!
+//! Only the signatures are checked: the proof purpose, the validity period and the status of the credential are left to the caller.
+//! Credentials are not parsed from JSON-LD yet, they must be loaded from an other RDF serialization into a store
+//! not [canonicalizing the literals](crate::store::StorageConfig::with_literal_canonicalization), to keep their signed lexical forms.
+//!
+//! Usage example:
+//! ```
+//! use ed25519_dalek::{Signer, SigningKey};
+//! use oxigraph::model::vocab::rdf;
+//! use oxigraph::model::*;
+//! use oxigraph::store::Store;
+//! use sha2::{Digest, Sha256};
+//!
+//! // Without blank nodes, the canonical form of a graph is its sorted N-Triples lines
+//! fn canonical_hash(triples: &[Triple]) -> Vec<u8> {
+//!     let mut lines = triples.iter().map(|t| format!("{t} .\n")).collect::<Vec<_>>();
+//!     lines.sort();
+//!     Sha256::digest(lines.concat()).to_vec()
+//! }
+//!
+//! let sec = |name: &str| NamedNode::new_unchecked(format!("https://w3id.org/security#{name}"));
+//! let credential = NamedNode::new("http://example.com/credentials/1")?;
+//! let proof = NamedNode::new("http://example.com/credentials/1#proof")?;
+//! let method = NamedNode::new("http://example.com/issuer#key-1")?;
+//! let key = SigningKey::from_bytes(&[7; 32]);
+//!
+//! // The issuer signs the credential
+//! let document = [Triple::new(
+//!     credential.clone(),
+//!     rdf::TYPE,
+//!     NamedNode::new("https://www.w3.org/2018/credentials#VerifiableCredential")?,
+//! )];
+//! let config = [
+//!     Triple::new(proof.clone(), rdf::TYPE, sec("DataIntegrityProof")),
+//!     Triple::new(proof.clone(), sec("cryptosuite"), Literal::new_simple_literal("eddsa-rdfc-2022")),
+//!     Triple::new(proof.clone(), sec("verificationMethod"), method.clone()),
+//! ];
+//! let signature = key.sign(&[canonical_hash(&config), canonical_hash(&document)].concat());
+//! let proof_value = format!("z{}", bs58::encode(signature.to_bytes()).into_string());
+//! let public_key = format!(
+//!     "z{}",
+//!     bs58::encode([&[0xed, 0x01][..], key.verifying_key().as_bytes()].concat()).into_string()
+//! );
+//!
+//! // The store keeps the credential, its proof and the issuer key
+//! let store = Store::new()?;
+//! for triple in &document {
+//!     store.insert(triple.as_ref().in_graph(&credential))?;
+//! }
+//! store.insert(QuadRef::new(&credential, &sec("proof"), &proof, &credential))?;
+//! for triple in &config {
+//!     store.insert(triple.as_ref().in_graph(&proof))?;
+//! }
+//! store.insert(QuadRef::new(&proof, &sec("proofValue"), LiteralRef::new_simple_literal(&proof_value), &proof))?;
+//! store.insert(QuadRef::new(&method, &sec("publicKeyMultibase"), LiteralRef::new_simple_literal(&public_key), GraphNameRef::DefaultGraph))?;
+//! assert!(store.verify_credential(&credential)?);
+//!
+//! // Any change of the credential invalidates the proof
+//! store.insert(QuadRef::new(&credential, rdf::TYPE, &sec("Forged"), &credential))?;
+//! assert!(!store.verify_credential(&credential)?);
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::model::*;
+use crate::store::{StorageError, Store};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+const PROOF: NamedNodeRef<'_> = NamedNodeRef::new_unchecked("https://w3id.org/security#proof");
+const CRYPTOSUITE: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("https://w3id.org/security#cryptosuite");
+const VERIFICATION_METHOD: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("https://w3id.org/security#verificationMethod");
+const PROOF_VALUE: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("https://w3id.org/security#proofValue");
+const PUBLIC_KEY_MULTIBASE: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("https://w3id.org/security#publicKeyMultibase");
+
+/// The multicodec prefix of the Ed25519 public keys
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+/// The multicodec prefix of the compressed P-256 public keys
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// Maximal number of blank node paths explored by a canonicalization.
+///
+/// The graphs with many indistinguishable blank nodes need a factorial number of them and are rejected.
+const MAX_CANONICALIZATION_STEPS: usize = 10_000;
+
+pub(crate) fn verify_credential(
+    store: &Store,
+    graph: NamedOrBlankNodeRef<'_>,
+) -> Result<bool, CredentialError> {
+    let mut document = Vec::new();
+    let mut proofs = Vec::new();
+    for quad in store.quads_for_pattern(None, None, None, Some(graph.into())) {
+        let quad = quad?;
+        if quad.predicate == PROOF {
+            proofs.push(quad.object);
+        } else {
+            document.push(Triple::from(quad));
+        }
+    }
+    if proofs.is_empty() {
+        return Err(CredentialError::MissingProof);
+    }
+    let document_hash = Sha256::digest(canonicalize(&document)?);
+    for proof in &proofs {
+        if !verify_proof(store, proof, &document_hash)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn verify_proof(
+    store: &Store,
+    proof: &Term,
+    document_hash: &[u8],
+) -> Result<bool, CredentialError> {
+    let proof_graph = match proof {
+        Term::NamedNode(proof) => GraphNameRef::from(proof),
+        Term::BlankNode(proof) => GraphNameRef::from(proof),
+        _ => {
+            return Err(CredentialError::InvalidProof(format!(
+                "The proof {proof} is not a graph name"
+            )))
+        }
+    };
+    let mut config = Vec::new();
+    let mut proof_value = None;
+    for quad in store.quads_for_pattern(None, None, None, Some(proof_graph)) {
+        let triple = Triple::from(quad?);
+        if triple.predicate == PROOF_VALUE {
+            proof_value = Some(triple.object);
+        } else {
+            config.push(triple);
+        }
+    }
+    let signature = match &proof_value {
+        Some(Term::Literal(value)) => decode_multibase(value.value())?,
+        _ => {
+            return Err(CredentialError::InvalidProof(format!(
+                "The proof {proof} has no proof value"
+            )))
+        }
+    };
+    let cryptosuite = match object(&config, CRYPTOSUITE) {
+        Some(Term::Literal(cryptosuite)) => cryptosuite.value(),
+        _ => {
+            return Err(CredentialError::InvalidProof(format!(
+                "The proof {proof} has no cryptosuite"
+            )))
+        }
+    };
+    let method = match object(&config, VERIFICATION_METHOD) {
+        Some(Term::NamedNode(method)) => method.as_ref(),
+        _ => {
+            return Err(CredentialError::InvalidProof(format!(
+                "The proof {proof} has no verification method"
+            )))
+        }
+    };
+    let public_key = public_key(store, method)?;
+    let mut data = Sha256::digest(canonicalize(&config)?).to_vec();
+    data.extend_from_slice(document_hash);
+    match cryptosuite {
+        "eddsa-rdfc-2022" => {
+            let key = public_key
+                .strip_prefix(&ED25519_PUB)
+                .and_then(|key| <&[u8; 32]>::try_from(key).ok())
+                .and_then(|key| ed25519_dalek::VerifyingKey::from_bytes(key).ok())
+                .ok_or_else(|| {
+                    CredentialError::InvalidProof(format!(
+                        "The verification method {method} is not an Ed25519 key"
+                    ))
+                })?;
+            let signature = ed25519_dalek::Signature::from_slice(&signature).map_err(|_| {
+                CredentialError::InvalidProof(format!(
+                    "The proof value of {proof} is not an Ed25519 signature"
+                ))
+            })?;
+            Ok(key.verify_strict(&data, &signature).is_ok())
+        }
+        "ecdsa-rdfc-2019" => {
+            let key = public_key
+                .strip_prefix(&P256_PUB)
+                .and_then(|key| p256::ecdsa::VerifyingKey::from_sec1_bytes(key).ok())
+                .ok_or_else(|| {
+                    CredentialError::InvalidProof(format!(
+                        "The verification method {method} is not a P-256 key"
+                    ))
+                })?;
+            let signature =
+                p256::ecdsa::Signature::try_from(signature.as_slice()).map_err(|_| {
+                    CredentialError::InvalidProof(format!(
+                        "The proof value of {proof} is not a P-256 signature"
+                    ))
+                })?;
+            // The data is hashed again with SHA-256 by the ECDSA verification
+            Ok(p256::ecdsa::signature::Verifier::verify(&key, &data, &signature).is_ok())
+        }
+        _ => Err(CredentialError::InvalidProof(format!(
+            "The cryptosuite {cryptosuite} is not supported"
+        ))),
+    }
+}
+
+fn object<'a>(triples: &'a [Triple], predicate: NamedNodeRef<'_>) -> Option<&'a Term> {
+    triples
+        .iter()
+        .find(|triple| triple.predicate == predicate)
+        .map(|triple| &triple.object)
+}
+
+/// Returns the multicodec encoded public key of the verification method
+fn public_key(store: &Store, method: NamedNodeRef<'_>) -> Result<Vec<u8>, CredentialError> {
+    for quad in store.quads_for_pattern(Some(method.into()), Some(PUBLIC_KEY_MULTIBASE), None, None)
+    {
+        if let Term::Literal(key) = quad?.object {
+            return decode_multibase(key.value());
+        }
+    }
+    if let Some(key) = method.as_str().strip_prefix("did:key:") {
+        // The fragment repeats the key
+        return decode_multibase(key.split_once('#').map_or(key, |(key, _)| key));
+    }
+    Err(CredentialError::UnknownVerificationMethod(
+        method.into_owned(),
+    ))
+}
+
+/// Decodes a base58-btc [multibase](https://www.w3.org/TR/controller-document/#multibase-0) value
+fn decode_multibase(value: &str) -> Result<Vec<u8>, CredentialError> {
+    value
+        .strip_prefix('z')
+        .and_then(|value| bs58::decode(value).into_vec().ok())
+        .ok_or_else(|| {
+            CredentialError::InvalidProof(format!("{value} is not a base58-btc multibase value"))
+        })
+}
+
+/// Serializes the triples as the canonical N-Quads document of [RDFC-1.0](https://www.w3.org/TR/rdf-canon/) of the dataset with the triples in its default graph
+fn canonicalize(triples: &[Triple]) -> Result<String, CredentialError> {
+    let mut canonicalizer = Canonicalizer::new(triples)?;
+    let labels = canonicalizer.label()?;
+    let mut lines = canonicalizer
+        .triples
+        .iter()
+        .map(|triple| triple.to_nquad(&|node| labels[node].clone()))
+        .collect::<Vec<_>>();
+    lines.sort();
+    Ok(lines.concat())
+}
+
+/// A term of a triple to canonicalize
+enum CanonicalTerm {
+    /// A named node or a literal, in its N-Quads serialization
+    Ground(String),
+    /// The index of a blank node
+    BlankNode(usize),
+}
+
+impl CanonicalTerm {
+    fn to_nquad(&self, label: &dyn Fn(usize) -> String) -> String {
+        match self {
+            Self::Ground(term) => term.clone(),
+            Self::BlankNode(node) => label(*node),
+        }
+    }
+}
+
+struct CanonicalTriple {
+    subject: CanonicalTerm,
+    /// The N-Quads serialization of the predicate
+    predicate: String,
+    object: CanonicalTerm,
+}
+
+impl CanonicalTriple {
+    fn to_nquad(&self, label: &dyn Fn(usize) -> String) -> String {
+        format!(
+            "{} {} {} .\n",
+            self.subject.to_nquad(label),
+            self.predicate,
+            self.object.to_nquad(label)
+        )
+    }
+}
+
+/// Issues the blank node identifiers `{prefix}0`, `{prefix}1`... in order
+#[derive(Clone)]
+struct IdentifierIssuer {
+    prefix: &'static str,
+    issued: HashMap<usize, String>,
+    order: Vec<usize>,
+}
+
+impl IdentifierIssuer {
+    fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            issued: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    fn get(&self, node: usize) -> Option<&str> {
+        self.issued.get(&node).map(String::as_str)
+    }
+
+    fn issue(&mut self, node: usize) -> String {
+        if let Some(id) = self.issued.get(&node) {
+            return id.clone();
+        }
+        let id = format!("{}{}", self.prefix, self.order.len());
+        self.issued.insert(node, id.clone());
+        self.order.push(node);
+        id
+    }
+}
+
+/// The state of the [RDFC-1.0](https://www.w3.org/TR/rdf-canon/) algorithm
+struct Canonicalizer {
+    triples: Vec<CanonicalTriple>,
+    /// The indexes of the triples mentioning each blank node
+    mentions: Vec<Vec<usize>>,
+    first_degree_hashes: Vec<String>,
+    canonical: IdentifierIssuer,
+    steps: usize,
+}
+
+impl Canonicalizer {
+    fn new(triples: &[Triple]) -> Result<Self, CredentialError> {
+        let mut blank_nodes = HashMap::new();
+        let mut mentions = Vec::new();
+        let mut blank_node = |id: &str, triple: usize| {
+            let node = *blank_nodes.entry(id.to_owned()).or_insert_with(|| {
+                mentions.push(Vec::new());
+                mentions.len() - 1
+            });
+            if mentions[node].last() != Some(&triple) {
+                mentions[node].push(triple);
+            }
+            CanonicalTerm::BlankNode(node)
+        };
+        let mut canonical_triples = Vec::with_capacity(triples.len());
+        for (i, triple) in triples.iter().enumerate() {
+            let subject = match &triple.subject {
+                Subject::NamedNode(node) => CanonicalTerm::Ground(node.to_string()),
+                Subject::BlankNode(node) => blank_node(node.as_str(), i),
+                Subject::Triple(_) => return Err(CredentialError::QuotedTriple),
+            };
+            let object = match &triple.object {
+                Term::NamedNode(node) => CanonicalTerm::Ground(node.to_string()),
+                Term::BlankNode(node) => blank_node(node.as_str(), i),
+                Term::Literal(literal) => CanonicalTerm::Ground(literal.to_string()),
+                Term::Triple(_) => return Err(CredentialError::QuotedTriple),
+            };
+            canonical_triples.push(CanonicalTriple {
+                subject,
+                predicate: triple.predicate.to_string(),
+                object,
+            });
+        }
+        let mut canonicalizer = Self {
+            triples: canonical_triples,
+            mentions,
+            first_degree_hashes: Vec::new(),
+            canonical: IdentifierIssuer::new("_:c14n"),
+            steps: 0,
+        };
+        canonicalizer.first_degree_hashes = (0..canonicalizer.mentions.len())
+            .map(|node| canonicalizer.hash_first_degree_quads(node))
+            .collect();
+        Ok(canonicalizer)
+    }
+
+    /// Returns the canonical label of each blank node
+    fn label(&mut self) -> Result<Vec<String>, CredentialError> {
+        let mut hash_to_nodes = BTreeMap::<_, Vec<_>>::new();
+        for (node, hash) in self.first_degree_hashes.iter().enumerate() {
+            hash_to_nodes.entry(hash.clone()).or_default().push(node);
+        }
+        let mut shared_hashes = Vec::new();
+        for nodes in hash_to_nodes.into_values() {
+            if nodes.len() == 1 {
+                self.canonical.issue(nodes[0]);
+            } else {
+                shared_hashes.push(nodes);
+            }
+        }
+        for nodes in shared_hashes {
+            let mut paths = Vec::new();
+            for node in nodes {
+                if self.canonical.get(node).is_some() {
+                    continue;
+                }
+                let mut issuer = IdentifierIssuer::new("_:b");
+                issuer.issue(node);
+                paths.push(self.hash_n_degree_quads(node, issuer)?);
+            }
+            paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, issuer) in paths {
+                for node in issuer.order {
+                    self.canonical.issue(node);
+                }
+            }
+        }
+        Ok((0..self.mentions.len())
+            .map(|node| self.canonical.issue(node))
+            .collect())
+    }
+
+    fn hash_first_degree_quads(&self, node: usize) -> String {
+        let mut lines = self.mentions[node]
+            .iter()
+            .map(|triple| {
+                self.triples[*triple]
+                    .to_nquad(&|other| (if other == node { "_:a" } else { "_:z" }).to_owned())
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        hex::encode(Sha256::digest(lines.concat()))
+    }
+
+    fn hash_related_blank_node(
+        &self,
+        related: usize,
+        triple: &CanonicalTriple,
+        issuer: &IdentifierIssuer,
+        position: char,
+    ) -> String {
+        let id = self
+            .canonical
+            .get(related)
+            .or_else(|| issuer.get(related))
+            .unwrap_or_else(|| self.first_degree_hashes[related].as_str());
+        hex::encode(Sha256::digest(format!(
+            "{position}{}{id}",
+            triple.predicate
+        )))
+    }
+
+    fn hash_n_degree_quads(
+        &mut self,
+        node: usize,
+        mut issuer: IdentifierIssuer,
+    ) -> Result<(String, IdentifierIssuer), CredentialError> {
+        self.step()?;
+        let mut related_by_hash = BTreeMap::<_, Vec<_>>::new();
+        for triple in &self.mentions[node] {
+            let triple = &self.triples[*triple];
+            for (term, position) in [(&triple.subject, 's'), (&triple.object, 'o')] {
+                if let CanonicalTerm::BlankNode(related) = *term {
+                    if related != node {
+                        related_by_hash
+                            .entry(self.hash_related_blank_node(related, triple, &issuer, position))
+                            .or_default()
+                            .push(related);
+                    }
+                }
+            }
+        }
+        let mut data = String::new();
+        for (hash, mut related) in related_by_hash {
+            data.push_str(&hash);
+            let mut chosen: Option<(String, IdentifierIssuer)> = None;
+            related.sort_unstable();
+            loop {
+                if let Some(path) = self.hash_path(
+                    &related,
+                    &issuer,
+                    chosen.as_ref().map(|(path, _)| path.as_str()),
+                )? {
+                    chosen = Some(path);
+                }
+                if !next_permutation(&mut related) {
+                    break;
+                }
+            }
+            if let Some((path, chosen_issuer)) = chosen {
+                data.push_str(&path);
+                issuer = chosen_issuer;
+            }
+        }
+        Ok((hex::encode(Sha256::digest(data)), issuer))
+    }
+
+    /// Returns the path of a permutation of the related blank nodes with its issuer, `None` if it is not smaller than the chosen one
+    fn hash_path(
+        &mut self,
+        permutation: &[usize],
+        issuer: &IdentifierIssuer,
+        chosen: Option<&str>,
+    ) -> Result<Option<(String, IdentifierIssuer)>, CredentialError> {
+        self.step()?;
+        let mut issuer = issuer.clone();
+        let mut path = String::new();
+        let mut recursion = Vec::new();
+        for related in permutation {
+            if let Some(id) = self.canonical.get(*related) {
+                path.push_str(id);
+            } else {
+                if issuer.get(*related).is_none() {
+                    recursion.push(*related);
+                }
+                path.push_str(&issuer.issue(*related));
+            }
+            if chosen.map_or(false, |chosen| path.as_str() > chosen) {
+                return Ok(None);
+            }
+        }
+        for related in recursion {
+            let (hash, related_issuer) = self.hash_n_degree_quads(related, issuer)?;
+            issuer = related_issuer;
+            path.push_str(&issuer.issue(related));
+            path.push('<');
+            path.push_str(&hash);
+            path.push('>');
+            if chosen.map_or(false, |chosen| path.as_str() > chosen) {
+                return Ok(None);
+            }
+        }
+        Ok(if chosen.map_or(true, |chosen| path.as_str() < chosen) {
+            Some((path, issuer))
+        } else {
+            None
+        })
+    }
+
+    fn step(&mut self) -> Result<(), CredentialError> {
+        self.steps += 1;
+        if self.steps > MAX_CANONICALIZATION_STEPS {
+            return Err(CredentialError::TooComplex);
+        }
+        Ok(())
+    }
+}
+
+/// Rearranges the values into their next permutation in lexicographic order, returns `false` if they were in the last one
+fn next_permutation(values: &mut [usize]) -> bool {
+    let mut i = values.len();
+    while i > 1 && values[i - 2] >= values[i - 1] {
+        i -= 1;
+    }
+    if i <= 1 {
+        return false;
+    }
+    let mut j = values.len() - 1;
+    while values[j] <= values[i - 2] {
+        j -= 1;
+    }
+    values.swap(i - 2, j);
+    values[i - 1..].reverse();
+    true
+}
+
+/// An error raised while verifying a credential with [`Store::verify_credential`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CredentialError {
+    /// The credential graph has no proof.
+    MissingProof,
+    /// The proof is malformed or uses an unsupported cryptosuite.
+    InvalidProof(String),
+    /// The public key of the verification method is neither in the store nor in its IRI.
+    UnknownVerificationMethod(NamedNode),
+    /// The credential or its proof contains quoted triples, that RDFC-1.0 does not canonicalize.
+    QuotedTriple,
+    /// The credential or its proof has too many indistinguishable blank nodes to be canonicalized.
+    TooComplex,
+    /// An error raised while accessing the store.
+    Storage(StorageError),
+}
+
+impl fmt::Display for CredentialError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingProof => write!(f, "The credential has no proof"),
+            Self::InvalidProof(message) => write!(f, "{message}"),
+            Self::UnknownVerificationMethod(method) => {
+                write!(f, "The public key of {method} is not known")
+            }
+            Self::QuotedTriple => write!(f, "Quoted triples can not be canonicalized"),
+            Self::TooComplex => write!(
+                f,
+                "The credential has too many similar blank nodes to be canonicalized"
+            ),
+            Self::Storage(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CredentialError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::MissingProof
+            | Self::InvalidProof(_)
+            | Self::UnknownVerificationMethod(_)
+            | Self::QuotedTriple
+            | Self::TooComplex => None,
+            Self::Storage(e) => Some(e),
+        }
+    }
+}
+
+impl From<StorageError> for CredentialError {
+    #[inline]
+    fn from(error: StorageError) -> Self {
+        Self::Storage(error)
+    }
+}
@@ -0,0 +1,405 @@
+//! A [SPARQL 1.1 Protocol](https://www.w3.org/TR/sparql11-protocol/) endpoint for the canister `http_request` methods.
+//!
+//! [`handle_http_request`] supports:
+//! * queries using `GET` with a `query` parameter, `POST` with an `application/x-www-form-urlencoded` body or `POST` with an `application/sparql-query` body,
+//! * updates using `POST` with an `application/x-www-form-urlencoded` body or `POST` with an `application/sparql-update` body,
+//! * the `default-graph-uri`, `named-graph-uri`, `using-graph-uri` and `using-named-graph-uri` parameters,
+//! * content negotiation of the results using the `Accept` header.
+//!
+//! The HTTP query calls can not modify the canister state.
+//! The `http_request` method should return [`HttpResponse::upgrade`] for the update requests
+//! so that the HTTP gateway calls `http_request_update` instead:
+//! ```no_run
+//! use oxigraph::endpoint::{handle_http_request, HttpRequest, HttpResponse};
+//! use oxigraph::store::Store;
+//!
+//! thread_local! {
+//!     static STORE: Store = Store::new().unwrap();
+//! }
+//!
+//! // #[query]
+//! fn http_request(request: HttpRequest) -> HttpResponse {
+//!     if request.is_update() {
+//!         return HttpResponse::upgrade();
+//!     }
+//!     STORE.with(|store| handle_http_request(store, request))
+//! }
+//!
+//! // #[update]
+//! fn http_request_update(request: HttpRequest) -> HttpResponse {
+//!     STORE.with(|store| handle_http_request(store, request))
+//! }
+//! # let _ = (http_request, http_request_update);
+//! ```
+use crate::io::{negotiate, GraphFormat};
+use crate::model::{GraphName, NamedNode, NamedOrBlankNode};
+use crate::sparql::{
+    EvaluationError, Query, QueryDataset, QueryResults, QueryResultsFormat, Update,
+};
+use crate::store::{StorageError, Store};
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use std::str;
+
+/// An HTTP request received by the canister `http_request` and `http_request_update` methods.
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone)]
+#[candid_path("ic_cdk::export::candid")]
+#[serde(crate = "ic_cdk::export::serde")]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Returns the value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Checks if the request is a SPARQL update, which must be executed in an update call.
+    pub fn is_update(&self) -> bool {
+        if !self.method.eq_ignore_ascii_case("POST") {
+            return false;
+        }
+        match self.content_type() {
+            Some("application/sparql-update") => true,
+            Some("application/x-www-form-urlencoded") => parse_form(&self.body)
+                .map_or(false, |params| params.iter().any(|(k, _)| k == "update")),
+            _ => false,
+        }
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        Some(self.header("Content-Type")?.split(';').next()?.trim())
+    }
+
+    fn query_string(&self) -> &str {
+        self.url.split_once('?').map_or("", |(_, query)| query)
+    }
+}
+
+/// An HTTP response returned by the canister `http_request` and `http_request_update` methods.
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone)]
+#[candid_path("ic_cdk::export::candid")]
+#[serde(crate = "ic_cdk::export::serde")]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Asks the HTTP gateway to replay the request as an update call.
+    pub upgrade: Option<bool>,
+}
+
+impl HttpResponse {
+    /// Builds a response asking the HTTP gateway to replay the request with `http_request_update`.
+    pub fn upgrade() -> Self {
+        Self {
+            status_code: 200,
+            headers: Vec::new(),
+            body: Vec::new(),
+            upgrade: Some(true),
+        }
+    }
+
+    fn ok(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status_code: 200,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body,
+            upgrade: None,
+        }
+    }
+
+    fn no_content() -> Self {
+        Self {
+            status_code: 204,
+            headers: Vec::new(),
+            body: Vec::new(),
+            upgrade: None,
+        }
+    }
+
+    fn error(status_code: u16, message: impl ToString) -> Self {
+        Self {
+            status_code,
+            headers: vec![("Content-Type".into(), "text/plain; charset=utf-8".into())],
+            body: message.to_string().into_bytes(),
+            upgrade: None,
+        }
+    }
+}
+
+/// Executes a SPARQL query or update received using the [SPARQL 1.1 Protocol](https://www.w3.org/TR/sparql11-protocol/).
+///
+/// See the [module documentation](self) for the supported requests.
+///
+/// Usage example:
+/// ```
+/// use oxigraph::endpoint::{handle_http_request, HttpRequest};
+/// use oxigraph::model::*;
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+///
+/// let response = handle_http_request(
+///     &store,
+///     HttpRequest {
+///         method: "GET".into(),
+///         url: "/sparql?query=ASK%20%7B%20%3Fs%20%3Fp%20%3Fo%20%7D".into(),
+///         headers: vec![("Accept".into(), "application/sparql-results+json".into())],
+///         body: Vec::new(),
+///     },
+/// );
+/// assert_eq!(response.status_code, 200);
+/// assert_eq!(response.body, b"{\"head\":{},\"boolean\":true}");
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[allow(clippy::needless_pass_by_value)]
+pub fn handle_http_request(store: &Store, request: HttpRequest) -> HttpResponse {
+    let url_params = match parse_form(request.query_string().as_bytes()) {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+    if request.method.eq_ignore_ascii_case("GET") {
+        if url_params.iter().any(|(k, _)| k == "update") {
+            return HttpResponse::error(400, "SPARQL updates must be sent using POST");
+        }
+        match single_param(&url_params, "query") {
+            Ok(Some(query)) => evaluate_query(store, &request, query, &url_params),
+            Ok(None) => HttpResponse::error(400, "The query parameter is required"),
+            Err(response) => response,
+        }
+    } else if request.method.eq_ignore_ascii_case("POST") {
+        match request.content_type() {
+            Some("application/sparql-query") => match str::from_utf8(&request.body) {
+                Ok(query) => evaluate_query(store, &request, query, &url_params),
+                Err(e) => HttpResponse::error(400, e),
+            },
+            Some("application/sparql-update") => match str::from_utf8(&request.body) {
+                Ok(update) => evaluate_update(store, update, &url_params),
+                Err(e) => HttpResponse::error(400, e),
+            },
+            Some("application/x-www-form-urlencoded") => {
+                let params = match parse_form(&request.body) {
+                    Ok(params) => params,
+                    Err(response) => return response,
+                };
+                match (
+                    single_param(&params, "query"),
+                    single_param(&params, "update"),
+                ) {
+                    (Ok(Some(query)), Ok(None)) => evaluate_query(store, &request, query, &params),
+                    (Ok(None), Ok(Some(update))) => evaluate_update(store, update, &params),
+                    (Err(response), _) | (_, Err(response)) => response,
+                    _ => HttpResponse::error(
+                        400,
+                        "Exactly one query or update parameter is required",
+                    ),
+                }
+            }
+            Some(content_type) => HttpResponse::error(
+                415,
+                format!("Unsupported request content type: {content_type}"),
+            ),
+            None => HttpResponse::error(400, "The Content-Type header is required"),
+        }
+    } else {
+        let mut response =
+            HttpResponse::error(405, format!("Unsupported method: {}", request.method));
+        response.headers.push(("Allow".into(), "GET, POST".into()));
+        response
+    }
+}
+
+fn evaluate_query(
+    store: &Store,
+    request: &HttpRequest,
+    query: &str,
+    params: &[(String, String)],
+) -> HttpResponse {
+    let mut query = match Query::parse(query, None) {
+        Ok(query) => query,
+        Err(e) => return HttpResponse::error(400, e),
+    };
+    if let Err(response) = set_dataset(
+        query.dataset_mut(),
+        params,
+        "default-graph-uri",
+        "named-graph-uri",
+    ) {
+        return response;
+    }
+    let accept = request.header("Accept").unwrap_or("*/*");
+    match store.query(query) {
+        Ok(QueryResults::Graph(triples)) => {
+            let format = match GraphFormat::from_accept_header(accept) {
+                Some(format) => format,
+                None => {
+                    return HttpResponse::error(
+                        406,
+                        format!("No supported graph format in {accept}"),
+                    )
+                }
+            };
+            let mut body = Vec::new();
+            match QueryResults::Graph(triples).write_graph(&mut body, format) {
+                Ok(()) => HttpResponse::ok(format.media_type(), body),
+                Err(e) => evaluation_error(e),
+            }
+        }
+        Ok(results) => {
+            let format = match negotiate(
+                accept,
+                QueryResultsFormat::Json,
+                QueryResultsFormat::from_media_type,
+            ) {
+                Some(format) => format,
+                None => {
+                    return HttpResponse::error(
+                        406,
+                        format!("No supported results format in {accept}"),
+                    )
+                }
+            };
+            let mut body = Vec::new();
+            match results.write(&mut body, format) {
+                Ok(()) => HttpResponse::ok(format.media_type(), body),
+                Err(e) => evaluation_error(e),
+            }
+        }
+        Err(e) => evaluation_error(e),
+    }
+}
+
+fn evaluate_update(store: &Store, update: &str, params: &[(String, String)]) -> HttpResponse {
+    let mut update = match Update::parse(update, None) {
+        Ok(update) => update,
+        Err(e) => return HttpResponse::error(400, e),
+    };
+    for dataset in update.using_datasets_mut() {
+        if let Err(response) =
+            set_dataset(dataset, params, "using-graph-uri", "using-named-graph-uri")
+        {
+            return response;
+        }
+    }
+    match store.update(update) {
+        Ok(()) => HttpResponse::no_content(),
+        Err(e) => evaluation_error(e),
+    }
+}
+
+fn set_dataset(
+    dataset: &mut QueryDataset,
+    params: &[(String, String)],
+    default_graph_param: &str,
+    named_graph_param: &str,
+) -> Result<(), HttpResponse> {
+    let default_graphs = named_nodes(params, default_graph_param)?;
+    let named_graphs = named_nodes(params, named_graph_param)?;
+    if !default_graphs.is_empty() || !named_graphs.is_empty() {
+        dataset.set_default_graph(default_graphs.into_iter().map(GraphName::from).collect());
+        dataset.set_available_named_graphs(
+            named_graphs
+                .into_iter()
+                .map(NamedOrBlankNode::from)
+                .collect(),
+        );
+    }
+    Ok(())
+}
+
+fn named_nodes(params: &[(String, String)], name: &str) -> Result<Vec<NamedNode>, HttpResponse> {
+    params
+        .iter()
+        .filter(|(k, _)| k == name)
+        .map(|(_, v)| {
+            NamedNode::new(v.as_str()).map_err(|e| HttpResponse::error(400, format!("{v}: {e}")))
+        })
+        .collect()
+}
+
+fn single_param<'a>(
+    params: &'a [(String, String)],
+    name: &str,
+) -> Result<Option<&'a str>, HttpResponse> {
+    let mut values = params.iter().filter(|(k, _)| k == name);
+    let value = values.next().map(|(_, v)| v.as_str());
+    if values.next().is_some() {
+        return Err(HttpResponse::error(
+            400,
+            format!("The {name} parameter must be given only once"),
+        ));
+    }
+    Ok(value)
+}
+
+fn evaluation_error(error: EvaluationError) -> HttpResponse {
+    match error {
+        EvaluationError::Parsing(e) => HttpResponse::error(400, e),
+        EvaluationError::Storage(StorageError::ReadOnly) => {
+            HttpResponse::error(403, StorageError::ReadOnly)
+        }
+        EvaluationError::Storage(StorageError::AccessDenied) => {
+            HttpResponse::error(403, StorageError::AccessDenied)
+        }
+        EvaluationError::Storage(e @ StorageError::InvalidTerm { .. }) => {
+            HttpResponse::error(400, e)
+        }
+        EvaluationError::Storage(StorageError::GraphLocked) => {
+            HttpResponse::error(409, StorageError::GraphLocked)
+        }
+        EvaluationError::Storage(StorageError::UnderMaintenance { retry_after }) => {
+            let mut response =
+                HttpResponse::error(503, StorageError::UnderMaintenance { retry_after });
+            response.headers.push((
+                "Retry-After".into(),
+                retry_after.as_secs().max(1).to_string(),
+            ));
+            response
+        }
+        e => HttpResponse::error(500, e),
+    }
+}
+
+/// Parses an `application/x-www-form-urlencoded` content.
+fn parse_form(input: &[u8]) -> Result<Vec<(String, String)>, HttpResponse> {
+    input
+        .split(|b| *b == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, |b| *b == b'=');
+            let name = percent_decode(parts.next().unwrap_or_default())?;
+            let value = percent_decode(parts.next().unwrap_or_default())?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn percent_decode(input: &[u8]) -> Result<String, HttpResponse> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' => output.push(b' '),
+            b'%' => {
+                let byte = input
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| HttpResponse::error(400, "Invalid percent encoding"))?;
+                output.push(byte);
+                i += 2;
+            }
+            b => output.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(output).map_err(|e| HttpResponse::error(400, e))
+}
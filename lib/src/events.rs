@@ -0,0 +1,100 @@
+//! Notifications of the [`Store`](crate::store::Store) changes to subscriber canisters.
+//!
+//! A canister registered with [`Store::subscribe_canister`](crate::store::Store::subscribe_canister)
+//! receives after each commit a one-way call to its notification method
+//! with a [`ChangeNotification`] of the inserted and removed quads matching its pattern.
+//!
+//! The notifications are best effort: they are not retried if the call can not be enqueued,
+//! and as one-way calls the subscriber errors are not reported back.
+//!
+//! The host canister exposes the registration to the other canisters:
+//! ```no_run
+//! use oxigraph::store::{QuadPattern, Store};
+//!
+//! thread_local! {
+//!     static STORE: Store = Store::new().unwrap();
+//! }
+//!
+//! // #[update]
+//! fn subscribe(graph: String) -> Result<u64, String> {
+//!     let graph = oxigraph::model::NamedNode::new(graph).map_err(|e| e.to_string())?;
+//!     let pattern = QuadPattern::new(None, None, None, Some(graph.as_ref().into()));
+//!     Ok(STORE
+//!         .with(|store| store.subscribe_canister(pattern, ic_cdk::caller(), "on_changes"))
+//!         .into())
+//! }
+//! # let _ = subscribe;
+//! ```
+//! and the subscriber canister handles the notifications:
+//! ```no_run
+//! use oxigraph::events::ChangeNotification;
+//! use oxigraph::store::QuadChange;
+//!
+//! // #[update]
+//! fn on_changes(notification: ChangeNotification) {
+//!     for change in notification.changes().unwrap() {
+//!         if let QuadChange::Inserted(quad) = change {
+//!             ic_cdk::println!("New quad {quad}");
+//!         }
+//!     }
+//! }
+//! # let _ = on_changes;
+//! ```
+use crate::io::read::ParseError;
+use crate::io::{DatasetFormat, DatasetParser};
+use crate::store::{QuadChange, SubscriptionId};
+use ic_cdk::api::call::notify;
+use ic_cdk::export::candid::{CandidType, Deserialize, Principal};
+
+/// The changes matching a subscription committed into the store.
+///
+/// The quads are serialized as [N-Quads](https://www.w3.org/TR/n-quads/) statements.
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone)]
+#[candid_path("ic_cdk::export::candid")]
+#[serde(crate = "ic_cdk::export::serde")]
+pub struct ChangeNotification {
+    /// The identifier of the subscription, returned by [`Store::subscribe_canister`](crate::store::Store::subscribe_canister).
+    pub subscription: u64,
+    /// The changed quads in the order of the changes, with `true` for the insertions and `false` for the removals.
+    pub quads: Vec<(bool, String)>,
+}
+
+impl ChangeNotification {
+    pub(crate) fn new(subscription: SubscriptionId, changes: &[QuadChange]) -> Self {
+        Self {
+            subscription: subscription.into(),
+            quads: changes
+                .iter()
+                .map(|change| {
+                    (
+                        matches!(change, QuadChange::Inserted(_)),
+                        format!("{} .", change.quad()),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Parses the notified changes.
+    pub fn changes(&self) -> Result<Vec<QuadChange>, ParseError> {
+        let parser = DatasetParser::from_format(DatasetFormat::NQuads);
+        let mut changes = Vec::with_capacity(self.quads.len());
+        for (inserted, statement) in &self.quads {
+            for quad in parser.read_quads(statement.as_bytes())? {
+                let quad = quad?;
+                changes.push(if *inserted {
+                    QuadChange::Inserted(quad)
+                } else {
+                    QuadChange::Removed(quad)
+                });
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// Sends a notification with a one-way call.
+pub(crate) fn notify_canister(canister: Principal, method: &str, notification: ChangeNotification) {
+    // The notifications are best effort, so the calls that can not be enqueued are dropped
+    let _ = notify(canister, method, (notification,));
+}
@@ -0,0 +1,421 @@
+//! Scheduled harvesting of external RDF sources into named graphs.
+//!
+//! A [`Harvester`] periodically fetches the registered sources using [HTTPS outcalls](https://internetcomputer.org/https-outcalls)
+//! and atomically replaces the content of the named graph associated to each of them.
+//! The status of each source and a short history of its failures are kept in memory.
+//!
+//! The replicas must agree on the responses, so the canister should expose [`transform_harvest_response`]
+//! as a query method and give its name to [`Harvester::with_transform`].
+//!
+//! This module is only available with the `harvester` feature and must be used inside a canister.
+//!
+//! Usage example:
+//! ```no_run
+//! use oxigraph::harvest::{HarvestSource, Harvester};
+//! use oxigraph::io::GraphFormat;
+//! use oxigraph::model::NamedNode;
+//! use oxigraph::store::Store;
+//! use std::time::Duration;
+//!
+//! let harvester = Harvester::new(Store::new()?).with_transform("transform_harvest");
+//! harvester.register(HarvestSource::new(
+//!     "https://example.com/data.ttl",
+//!     NamedNode::new("https://example.com/data")?,
+//!     GraphFormat::Turtle,
+//!     Duration::from_secs(3600),
+//! ));
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::io::read::ParseError;
+use crate::io::{GraphFormat, GraphParser};
+use crate::model::{NamedOrBlankNode, Triple};
+use crate::store::{LoaderError, StorageError, Store};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext, TransformFunc,
+};
+use ic_cdk::api::time;
+use ic_cdk::export::candid::{Func, Nat};
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Number of failures kept in the history of each source.
+const MAX_FAILURE_HISTORY: usize = 16;
+/// Maximal size of a fetched document.
+const MAX_RESPONSE_BYTES: u64 = 2_000_000;
+
+/// A source to harvest: an URL fetched periodically into a named graph.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct HarvestSource {
+    url: String,
+    graph_name: NamedOrBlankNode,
+    format: GraphFormat,
+    interval: Duration,
+}
+
+impl HarvestSource {
+    /// Builds a new source.
+    ///
+    /// `format` is used to parse the fetched document if the server does not return a supported `Content-Type`.
+    #[inline]
+    pub fn new(
+        url: impl Into<String>,
+        graph_name: impl Into<NamedOrBlankNode>,
+        format: GraphFormat,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            url: url.into(),
+            graph_name: graph_name.into(),
+            format,
+            interval,
+        }
+    }
+
+    /// The URL of the source.
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The named graph replaced by the source content.
+    #[inline]
+    pub fn graph_name(&self) -> &NamedOrBlankNode {
+        &self.graph_name
+    }
+
+    /// The default format of the source.
+    #[inline]
+    pub fn format(&self) -> GraphFormat {
+        self.format
+    }
+
+    /// The delay between two fetches.
+    #[inline]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// The harvesting status of a [`HarvestSource`].
+#[derive(Debug, Clone, Default)]
+pub struct HarvestStatus {
+    last_attempt: Option<u64>,
+    last_success: Option<u64>,
+    triples_count: usize,
+    failures: VecDeque<HarvestFailure>,
+}
+
+impl HarvestStatus {
+    /// The time of the last fetch attempt in nanoseconds since the epoch.
+    #[inline]
+    pub fn last_attempt(&self) -> Option<u64> {
+        self.last_attempt
+    }
+
+    /// The time of the last successful harvest in nanoseconds since the epoch.
+    #[inline]
+    pub fn last_success(&self) -> Option<u64> {
+        self.last_success
+    }
+
+    /// The number of triples loaded by the last successful harvest.
+    #[inline]
+    pub fn triples_count(&self) -> usize {
+        self.triples_count
+    }
+
+    /// The most recent failures, the oldest first.
+    #[inline]
+    pub fn failures(&self) -> impl Iterator<Item = &HarvestFailure> {
+        self.failures.iter()
+    }
+
+    fn record_failure(&mut self, time: u64, message: String) {
+        if self.failures.len() == MAX_FAILURE_HISTORY {
+            self.failures.pop_front();
+        }
+        self.failures.push_back(HarvestFailure { time, message });
+    }
+}
+
+/// A failed harvest recorded in a [`HarvestStatus`].
+#[derive(Debug, Clone)]
+pub struct HarvestFailure {
+    time: u64,
+    message: String,
+}
+
+impl HarvestFailure {
+    /// The time of the failure in nanoseconds since the epoch.
+    #[inline]
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// The error message.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+struct SourceState {
+    source: HarvestSource,
+    status: HarvestStatus,
+    timer: TimerId,
+}
+
+/// Periodically fetches [`HarvestSource`]s into a [`Store`].
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct Harvester {
+    store: Store,
+    transform: Option<String>,
+    sources: Rc<RefCell<HashMap<String, SourceState>>>,
+}
+
+impl Harvester {
+    /// Builds a harvester writing into the given store.
+    #[inline]
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            transform: None,
+            sources: Rc::default(),
+        }
+    }
+
+    /// Sets the name of the canister query method transforming the responses,
+    /// usually a wrapper around [`transform_harvest_response`].
+    #[inline]
+    #[must_use]
+    pub fn with_transform(mut self, method: impl Into<String>) -> Self {
+        self.transform = Some(method.into());
+        self
+    }
+
+    /// Registers a source and schedules its periodic harvesting.
+    ///
+    /// A source already registered with the same URL is replaced.
+    pub fn register(&self, source: HarvestSource) {
+        let harvester = self.clone();
+        let url = source.url.clone();
+        let timer = set_timer_interval(source.interval, move || {
+            let harvester = harvester.clone();
+            let url = url.clone();
+            ic_cdk::spawn(async move {
+                // The failure is recorded in the source status
+                let _ = harvester.harvest_now(&url).await;
+            });
+        });
+        let previous = self.sources.borrow_mut().insert(
+            source.url.clone(),
+            SourceState {
+                source,
+                status: HarvestStatus::default(),
+                timer,
+            },
+        );
+        if let Some(previous) = previous {
+            clear_timer(previous.timer);
+        }
+    }
+
+    /// Unregisters a source and stops its harvesting.
+    ///
+    /// The already harvested named graph is kept in the store.
+    ///
+    /// Returns `true` if the source was registered.
+    pub fn unregister(&self, url: &str) -> bool {
+        if let Some(state) = self.sources.borrow_mut().remove(url) {
+            clear_timer(state.timer);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the registered sources.
+    pub fn sources(&self) -> Vec<HarvestSource> {
+        self.sources
+            .borrow()
+            .values()
+            .map(|state| state.source.clone())
+            .collect()
+    }
+
+    /// Returns the status of a registered source.
+    pub fn status(&self, url: &str) -> Option<HarvestStatus> {
+        self.sources
+            .borrow()
+            .get(url)
+            .map(|state| state.status.clone())
+    }
+
+    /// Fetches a registered source now and replaces its named graph content.
+    ///
+    /// Returns the number of loaded triples.
+    pub async fn harvest_now(&self, url: &str) -> Result<usize, HarvestError> {
+        let source = self
+            .sources
+            .borrow()
+            .get(url)
+            .map(|state| state.source.clone())
+            .ok_or_else(|| HarvestError::UnknownSource(url.into()))?;
+        let attempt_time = time();
+        let result = self.harvest(&source).await;
+        if let Some(state) = self.sources.borrow_mut().get_mut(url) {
+            state.status.last_attempt = Some(attempt_time);
+            match &result {
+                Ok(count) => {
+                    state.status.last_success = Some(attempt_time);
+                    state.status.triples_count = *count;
+                }
+                Err(error) => state.status.record_failure(attempt_time, error.to_string()),
+            }
+        }
+        result
+    }
+
+    async fn harvest(&self, source: &HarvestSource) -> Result<usize, HarvestError> {
+        let (body, content_type) = fetch(source, self.transform.as_deref()).await?;
+        let format = content_type
+            .as_deref()
+            .and_then(GraphFormat::from_media_type)
+            .unwrap_or(source.format);
+        let triples = GraphParser::from_format(format)
+            .with_base_iri(&source.url)
+            .map_err(|e| ParseError::invalid_base_iri(&source.url, e))?
+            .read_triples(body.as_slice())?
+            .collect::<Result<Vec<Triple>, _>>()?;
+        let graph_name = source.graph_name.as_ref();
+        self.store.transaction(|mut t| {
+            t.clear_graph(graph_name)?;
+            t.insert_named_graph(graph_name)?;
+            t.extend(
+                triples
+                    .iter()
+                    .map(|triple| triple.as_ref().in_graph(graph_name)),
+            )
+        })?;
+        Ok(triples.len())
+    }
+}
+
+async fn fetch(
+    source: &HarvestSource,
+    transform: Option<&str>,
+) -> Result<(Vec<u8>, Option<String>), HarvestError> {
+    let request = CanisterHttpRequestArgument {
+        url: source.url.clone(),
+        max_response_bytes: Some(MAX_RESPONSE_BYTES),
+        method: HttpMethod::GET,
+        headers: vec![HttpHeader {
+            name: "Accept".into(),
+            value: source.format.media_type().into(),
+        }],
+        body: None,
+        transform: transform.map(|method| TransformContext {
+            function: TransformFunc(Func {
+                principal: ic_cdk::api::id(),
+                method: method.into(),
+            }),
+            context: Vec::new(),
+        }),
+    };
+    let (response,) = http_request(request)
+        .await
+        .map_err(|(code, message)| HarvestError::Http(format!("{code:?}: {message}")))?;
+    if response.status < Nat::from(200_u64) || response.status >= Nat::from(300_u64) {
+        return Err(HarvestError::Http(format!(
+            "{} returned the HTTP status {}",
+            source.url, response.status
+        )));
+    }
+    let content_type = response
+        .headers
+        .into_iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-type"))
+        .map(|header| header.value);
+    Ok((response.body, content_type))
+}
+
+/// Normalizes a harvested response so that all the replicas agree on it.
+///
+/// The headers are dropped except `Content-Type`, which is used to pick the parser.
+///
+/// It should be wrapped in a canister query method given to [`Harvester::with_transform`]:
+/// ```ignore
+/// #[ic_cdk_macros::query]
+/// fn transform_harvest(args: TransformArgs) -> HttpResponse {
+///     oxigraph::harvest::transform_harvest_response(args)
+/// }
+/// ```
+pub fn transform_harvest_response(args: TransformArgs) -> HttpResponse {
+    let mut response = args.response;
+    response
+        .headers
+        .retain(|header| header.name.eq_ignore_ascii_case("content-type"));
+    response
+}
+
+/// An error raised while harvesting a [`HarvestSource`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HarvestError {
+    /// The source is not registered.
+    UnknownSource(String),
+    /// The HTTPS outcall failed or returned a non successful status.
+    Http(String),
+    /// An error raised while parsing or loading the fetched document.
+    Loader(LoaderError),
+}
+
+impl fmt::Display for HarvestError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSource(url) => write!(f, "The source {url} is not registered"),
+            Self::Http(e) => e.fmt(f),
+            Self::Loader(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for HarvestError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::UnknownSource(_) | Self::Http(_) => None,
+            Self::Loader(e) => Some(e),
+        }
+    }
+}
+
+impl From<LoaderError> for HarvestError {
+    #[inline]
+    fn from(error: LoaderError) -> Self {
+        Self::Loader(error)
+    }
+}
+
+impl From<ParseError> for HarvestError {
+    #[inline]
+    fn from(error: ParseError) -> Self {
+        Self::Loader(error.into())
+    }
+}
+
+impl From<StorageError> for HarvestError {
+    #[inline]
+    fn from(error: StorageError) -> Self {
+        Self::Loader(error.into())
+    }
+}
@@ -0,0 +1,291 @@
+//! A compact binary serialization of RDF datasets.
+//!
+//! A file is made of:
+//! * the `OXBQ` magic bytes followed by the format version,
+//! * a dictionary section: the number of terms followed by the terms,
+//!   each term being only allowed to reference the terms written before it,
+//! * a quads section: the number of quads followed by the quads,
+//!   each quad being the dictionary ids of its subject, predicate, object and graph name.
+//!
+//! The dictionary ids start at 1, the id 0 being used for the default graph.
+//! The numbers are written as unsigned LEB128 variable length integers.
+
+use crate::io::read::ParseError;
+use crate::model::*;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+const MAGIC_BYTES: &[u8] = b"OXBQ";
+const VERSION: u8 = 1;
+
+const TYPE_NAMED_NODE: u8 = 1;
+const TYPE_BLANK_NODE: u8 = 2;
+const TYPE_SIMPLE_LITERAL: u8 = 3;
+const TYPE_LANG_STRING_LITERAL: u8 = 4;
+const TYPE_TYPED_LITERAL: u8 = 5;
+const TYPE_TRIPLE: u8 = 6;
+
+/// Writes the binary serialization.
+///
+/// The dictionary and the quads are buffered until [`finish`](Self::finish) is called.
+pub struct BinaryQuadWriter<W: Write> {
+    writer: W,
+    ids: HashMap<Term, u64>,
+    dictionary: Vec<u8>,
+    quads: Vec<u8>,
+    quads_len: u64,
+}
+
+impl<W: Write> BinaryQuadWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            ids: HashMap::new(),
+            dictionary: Vec::new(),
+            quads: Vec::new(),
+            quads_len: 0,
+        }
+    }
+
+    pub fn write(&mut self, quad: QuadRef<'_>) {
+        let subject = self.id(quad.subject.into());
+        let predicate = self.id(quad.predicate.into());
+        let object = self.id(quad.object);
+        let graph_name = match quad.graph_name {
+            GraphNameRef::NamedNode(graph_name) => self.id(graph_name.into()),
+            GraphNameRef::BlankNode(graph_name) => self.id(graph_name.into()),
+            GraphNameRef::DefaultGraph => 0,
+        };
+        for id in [subject, predicate, object, graph_name] {
+            write_varint(&mut self.quads, id);
+        }
+        self.quads_len += 1;
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut header = MAGIC_BYTES.to_vec();
+        header.push(VERSION);
+        write_varint(&mut header, self.ids.len() as u64);
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&self.dictionary)?;
+        let mut header = Vec::new();
+        write_varint(&mut header, self.quads_len);
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&self.quads)?;
+        Ok(self.writer)
+    }
+
+    /// Returns the dictionary id of the term, adding it to the dictionary if needed
+    fn id(&mut self, term: TermRef<'_>) -> u64 {
+        let term = term.into_owned();
+        if let Some(id) = self.ids.get(&term) {
+            return *id;
+        }
+        match &term {
+            Term::NamedNode(node) => {
+                self.dictionary.push(TYPE_NAMED_NODE);
+                write_str(&mut self.dictionary, node.as_str());
+            }
+            Term::BlankNode(node) => {
+                self.dictionary.push(TYPE_BLANK_NODE);
+                write_str(&mut self.dictionary, node.as_str());
+            }
+            Term::Literal(literal) => {
+                if let Some(language) = literal.language() {
+                    self.dictionary.push(TYPE_LANG_STRING_LITERAL);
+                    write_str(&mut self.dictionary, literal.value());
+                    write_str(&mut self.dictionary, language);
+                } else if literal.is_plain() {
+                    self.dictionary.push(TYPE_SIMPLE_LITERAL);
+                    write_str(&mut self.dictionary, literal.value());
+                } else {
+                    let datatype = self.id(literal.datatype().into());
+                    self.dictionary.push(TYPE_TYPED_LITERAL);
+                    write_str(&mut self.dictionary, literal.value());
+                    write_varint(&mut self.dictionary, datatype);
+                }
+            }
+            Term::Triple(triple) => {
+                let subject = self.id(triple.subject.as_ref().into());
+                let predicate = self.id(triple.predicate.as_ref().into());
+                let object = self.id(triple.object.as_ref());
+                self.dictionary.push(TYPE_TRIPLE);
+                for id in [subject, predicate, object] {
+                    write_varint(&mut self.dictionary, id);
+                }
+            }
+        }
+        let id = self.ids.len() as u64 + 1;
+        self.ids.insert(term, id);
+        id
+    }
+}
+
+/// Reads the binary serialization.
+///
+/// The dictionary is read when the reader is built and the quads are then read one by one.
+pub struct BinaryQuadReader<R: Read> {
+    reader: R,
+    terms: Vec<Term>,
+    remaining_quads: u64,
+}
+
+impl<R: Read> BinaryQuadReader<R> {
+    /// Reads the file header and dictionary, the blank nodes being built from their labels with `blank_node`.
+    pub fn new(
+        mut reader: R,
+        mut blank_node: impl FnMut(&str) -> BlankNode,
+    ) -> Result<Self, ParseError> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if !header.starts_with(MAGIC_BYTES) {
+            return Err(ParseError::binary("The file is not a binary RDF dataset"));
+        }
+        if header[4] != VERSION {
+            return Err(ParseError::binary(format!(
+                "Unsupported binary format version {}",
+                header[4]
+            )));
+        }
+        let terms_len = read_varint(&mut reader)?;
+        let mut terms = Vec::new();
+        for _ in 0..terms_len {
+            let mut kind = [0];
+            reader.read_exact(&mut kind)?;
+            let term = match kind[0] {
+                TYPE_NAMED_NODE => NamedNode::new_unchecked(read_str(&mut reader)?).into(),
+                TYPE_BLANK_NODE => blank_node(&read_str(&mut reader)?).into(),
+                TYPE_SIMPLE_LITERAL => Literal::new_simple_literal(read_str(&mut reader)?).into(),
+                TYPE_LANG_STRING_LITERAL => {
+                    let value = read_str(&mut reader)?;
+                    let language = read_str(&mut reader)?;
+                    Literal::new_language_tagged_literal_unchecked(value, language).into()
+                }
+                TYPE_TYPED_LITERAL => {
+                    let value = read_str(&mut reader)?;
+                    let datatype = named_node(read_term(&mut reader, &terms)?)?;
+                    Literal::new_typed_literal(value, datatype).into()
+                }
+                TYPE_TRIPLE => {
+                    let subject = subject(read_term(&mut reader, &terms)?)?;
+                    let predicate = named_node(read_term(&mut reader, &terms)?)?;
+                    let object = read_term(&mut reader, &terms)?.clone();
+                    Triple::new(subject, predicate, object).into()
+                }
+                kind => {
+                    return Err(ParseError::binary(format!(
+                        "Invalid term kind {kind} in the dictionary"
+                    )))
+                }
+            };
+            terms.push(term);
+        }
+        let remaining_quads = read_varint(&mut reader)?;
+        Ok(Self {
+            reader,
+            terms,
+            remaining_quads,
+        })
+    }
+
+    fn read_quad(&mut self) -> Result<Quad, ParseError> {
+        let subject = subject(read_term(&mut self.reader, &self.terms)?)?;
+        let predicate = named_node(read_term(&mut self.reader, &self.terms)?)?;
+        let object = read_term(&mut self.reader, &self.terms)?.clone();
+        let graph_name = match read_varint(&mut self.reader)? {
+            0 => GraphName::DefaultGraph,
+            id => match dictionary_term(&self.terms, id)? {
+                Term::NamedNode(node) => node.clone().into(),
+                Term::BlankNode(node) => node.clone().into(),
+                _ => return Err(ParseError::binary("Invalid graph name")),
+            },
+        };
+        Ok(Quad::new(subject, predicate, object, graph_name))
+    }
+}
+
+impl<R: Read> Iterator for BinaryQuadReader<R> {
+    type Item = Result<Quad, ParseError>;
+
+    fn next(&mut self) -> Option<Result<Quad, ParseError>> {
+        if self.remaining_quads == 0 {
+            return None;
+        }
+        let quad = self.read_quad();
+        self.remaining_quads = if quad.is_ok() {
+            self.remaining_quads - 1
+        } else {
+            0
+        };
+        Some(quad)
+    }
+}
+
+fn read_term<'a>(reader: &mut impl Read, terms: &'a [Term]) -> Result<&'a Term, ParseError> {
+    dictionary_term(terms, read_varint(reader)?)
+}
+
+fn dictionary_term(terms: &[Term], id: u64) -> Result<&Term, ParseError> {
+    usize::try_from(id)
+        .ok()
+        .and_then(|id| terms.get(id.checked_sub(1)?))
+        .ok_or_else(|| ParseError::binary(format!("The term id {id} is not in the dictionary")))
+}
+
+fn subject(term: &Term) -> Result<Subject, ParseError> {
+    match term {
+        Term::NamedNode(node) => Ok(node.clone().into()),
+        Term::BlankNode(node) => Ok(node.clone().into()),
+        Term::Triple(triple) => Ok(triple.clone().into()),
+        Term::Literal(_) => Err(ParseError::binary("A literal can not be a subject")),
+    }
+}
+
+fn named_node(term: &Term) -> Result<NamedNode, ParseError> {
+    if let Term::NamedNode(node) = term {
+        Ok(node.clone())
+    } else {
+        Err(ParseError::binary("An IRI was expected"))
+    }
+}
+
+fn write_varint(sink: &mut Vec<u8>, mut value: u64) {
+    loop {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            sink.push(byte);
+            return;
+        }
+        sink.push(byte | 0x80);
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, ParseError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ParseError::binary("Too long variable length integer"))
+}
+
+fn write_str(sink: &mut Vec<u8>, value: &str) {
+    write_varint(sink, value.len() as u64);
+    sink.extend_from_slice(value.as_bytes());
+}
+
+fn read_str(reader: &mut impl Read) -> Result<String, ParseError> {
+    let len = read_varint(reader)?;
+    let mut buffer = Vec::new();
+    reader.take(len).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    String::from_utf8(buffer).map_err(|e| ParseError::binary(e.to_string()))
+}
@@ -0,0 +1,275 @@
+//! Transparent compression and decompression of the serialized RDF.
+
+#[cfg(feature = "compression")]
+use flate2::read::MultiGzDecoder;
+#[cfg(feature = "compression")]
+use flate2::write::GzEncoder;
+#[cfg(feature = "compression")]
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+#[cfg(feature = "compression")]
+use ruzstd::encoding::{compress, CompressionLevel};
+#[cfg(feature = "compression")]
+use std::io::BufReader;
+use std::io::{self, BufRead, Read, Write};
+
+const GZIP_MAGIC_BYTES: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC_BYTES: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+/// Compression formats of the serialized RDF.
+///
+/// The parsers detect the compressed files from their first bytes
+/// and the serializers compress their output if asked to with `with_compression`.
+///
+/// The compression and the decompression require the `compression` feature.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    /// [gzip](https://www.rfc-editor.org/rfc/rfc1952)
+    Gzip,
+    /// [Zstandard](https://www.rfc-editor.org/rfc/rfc8878)
+    Zstd,
+}
+
+impl Compression {
+    /// The format usual file extension.
+    ///
+    /// ```
+    /// use oxigraph::io::Compression;
+    ///
+    /// assert_eq!(Compression::Gzip.file_extension(), "gz")
+    /// ```
+    #[inline]
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+
+    /// The format [HTTP content coding](https://www.iana.org/assignments/http-parameters/http-parameters.xhtml#content-coding),
+    /// used in the `Content-Encoding` header.
+    ///
+    /// ```
+    /// use oxigraph::io::Compression;
+    ///
+    /// assert_eq!(Compression::Zstd.content_encoding(), "zstd")
+    /// ```
+    #[inline]
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Looks for a known format from an extension.
+    ///
+    /// ```
+    /// use oxigraph::io::Compression;
+    ///
+    /// assert_eq!(Compression::from_extension("gz"), Some(Compression::Gzip))
+    /// ```
+    #[inline]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Looks for a known format from an HTTP content coding.
+    ///
+    /// ```
+    /// use oxigraph::io::Compression;
+    ///
+    /// assert_eq!(Compression::from_content_encoding("x-gzip"), Some(Compression::Gzip))
+    /// ```
+    #[inline]
+    pub fn from_content_encoding(content_encoding: &str) -> Option<Self> {
+        match content_encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Detects the format from the first bytes of a file.
+    ///
+    /// ```
+    /// use oxigraph::io::Compression;
+    ///
+    /// assert_eq!(Compression::from_magic_bytes(&[0x28, 0xB5, 0x2F, 0xFD, 0x04]), Some(Compression::Zstd));
+    /// assert_eq!(Compression::from_magic_bytes(b"<http://example.com/s>"), None);
+    /// ```
+    #[inline]
+    pub fn from_magic_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(GZIP_MAGIC_BYTES) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(ZSTD_MAGIC_BYTES) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// A reader decompressing its input if it is compressed.
+pub struct DecompressingReader<R: BufRead> {
+    inner: DecompressingReaderKind<R>,
+}
+
+enum DecompressingReaderKind<R: BufRead> {
+    Plain(R),
+    #[cfg(feature = "compression")]
+    Gzip(BufReader<MultiGzDecoder<R>>),
+    #[cfg(feature = "compression")]
+    Zstd(BufReader<StreamingDecoder<R, FrameDecoder>>),
+}
+
+impl<R: BufRead> DecompressingReader<R> {
+    /// Wraps `reader`, the compression being detected from its first bytes if not given.
+    pub fn new(mut reader: R, compression: Option<Compression>) -> io::Result<Self> {
+        let compression = if compression.is_some() {
+            compression
+        } else {
+            Compression::from_magic_bytes(reader.fill_buf()?)
+        };
+        Ok(Self {
+            inner: match compression {
+                None => DecompressingReaderKind::Plain(reader),
+                #[cfg(feature = "compression")]
+                Some(Compression::Gzip) => {
+                    DecompressingReaderKind::Gzip(BufReader::new(MultiGzDecoder::new(reader)))
+                }
+                #[cfg(feature = "compression")]
+                Some(Compression::Zstd) => DecompressingReaderKind::Zstd(BufReader::new(
+                    StreamingDecoder::new(reader)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                )),
+                #[cfg(not(feature = "compression"))]
+                Some(compression) => return Err(unsupported(compression)),
+            },
+        })
+    }
+}
+
+impl<R: BufRead> Read for DecompressingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            DecompressingReaderKind::Plain(reader) => reader.read(buf),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Gzip(reader) => reader.read(buf),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Zstd(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for DecompressingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match &mut self.inner {
+            DecompressingReaderKind::Plain(reader) => reader.fill_buf(),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Gzip(reader) => reader.fill_buf(),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Zstd(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match &mut self.inner {
+            DecompressingReaderKind::Plain(reader) => reader.consume(amt),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Gzip(reader) => reader.consume(amt),
+            #[cfg(feature = "compression")]
+            DecompressingReaderKind::Zstd(reader) => reader.consume(amt),
+        }
+    }
+}
+
+/// A writer compressing its output if asked to.
+///
+/// Warning: Do not forget to run the [`finish`](CompressingWriter::finish()) method to write the end of the compressed stream.
+pub struct CompressingWriter<W: Write> {
+    inner: CompressingWriterKind<W>,
+}
+
+enum CompressingWriterKind<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compression")]
+    Gzip(GzEncoder<W>),
+    /// The Zstandard frame is compressed at once when the writing is finished
+    #[cfg(feature = "compression")]
+    Zstd {
+        writer: W,
+        buffer: Vec<u8>,
+    },
+}
+
+impl<W: Write> CompressingWriter<W> {
+    /// Wraps `writer`, nothing being compressed if `compression` is `None`.
+    pub fn new(writer: W, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(Self {
+            inner: match compression {
+                None => CompressingWriterKind::Plain(writer),
+                #[cfg(feature = "compression")]
+                Some(Compression::Gzip) => CompressingWriterKind::Gzip(GzEncoder::new(
+                    writer,
+                    flate2::Compression::default(),
+                )),
+                #[cfg(feature = "compression")]
+                Some(Compression::Zstd) => CompressingWriterKind::Zstd {
+                    writer,
+                    buffer: Vec::new(),
+                },
+                #[cfg(not(feature = "compression"))]
+                Some(compression) => return Err(unsupported(compression)),
+            },
+        })
+    }
+
+    /// Writes the end of the compressed stream and returns the wrapped writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            CompressingWriterKind::Plain(writer) => Ok(writer),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Zstd { mut writer, buffer } => {
+                compress(buffer.as_slice(), &mut writer, CompressionLevel::Fastest);
+                Ok(writer)
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for CompressingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            CompressingWriterKind::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Zstd { buffer, .. } => buffer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            CompressingWriterKind::Plain(writer) => writer.flush(),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            CompressingWriterKind::Zstd { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+fn unsupported(compression: Compression) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("The {compression:?} compression requires the compression feature"),
+    )
+}
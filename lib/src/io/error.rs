@@ -23,6 +23,24 @@ impl ParseError {
             },
         })
     }
+
+    #[inline]
+    pub(crate) fn binary(message: impl Into<String>) -> Self {
+        Self::Syntax(SyntaxError {
+            inner: SyntaxErrorKind::Binary {
+                msg: message.into(),
+            },
+        })
+    }
+
+    #[inline]
+    pub(crate) fn n3(line: u64, column: u64, message: impl Into<String>) -> Self {
+        Self::Syntax(SyntaxError {
+            inner: SyntaxErrorKind::N3 {
+                msg: format!("error at line {line} column {column}: {}", message.into()),
+            },
+        })
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -113,6 +131,8 @@ pub struct SyntaxError {
 enum SyntaxErrorKind {
     Turtle(TurtleError),
     RdfXml(RdfXmlError),
+    N3 { msg: String },
+    Binary { msg: String },
     InvalidBaseIri { iri: String, error: IriParseError },
 }
 
@@ -122,6 +142,7 @@ impl fmt::Display for SyntaxError {
         match &self.inner {
             SyntaxErrorKind::Turtle(e) => e.fmt(f),
             SyntaxErrorKind::RdfXml(e) => e.fmt(f),
+            SyntaxErrorKind::N3 { msg } | SyntaxErrorKind::Binary { msg } => write!(f, "{msg}"),
             SyntaxErrorKind::InvalidBaseIri { iri, error } => {
                 write!(f, "Invalid base IRI '{iri}': {error}")
             }
@@ -135,7 +156,9 @@ impl Error for SyntaxError {
         match &self.inner {
             SyntaxErrorKind::Turtle(e) => Some(e),
             SyntaxErrorKind::RdfXml(e) => Some(e),
-            SyntaxErrorKind::InvalidBaseIri { .. } => None,
+            SyntaxErrorKind::N3 { .. }
+            | SyntaxErrorKind::Binary { .. }
+            | SyntaxErrorKind::InvalidBaseIri { .. } => None,
         }
     }
 }
@@ -146,6 +169,9 @@ impl From<SyntaxError> for io::Error {
         match error.inner {
             SyntaxErrorKind::Turtle(error) => error.into(),
             SyntaxErrorKind::RdfXml(error) => error.into(),
+            SyntaxErrorKind::N3 { msg } | SyntaxErrorKind::Binary { msg } => {
+                Self::new(io::ErrorKind::InvalidData, msg)
+            }
             SyntaxErrorKind::InvalidBaseIri { iri, error } => Self::new(
                 io::ErrorKind::InvalidInput,
                 format!("Invalid IRI '{iri}': {error}"),
@@ -10,6 +10,11 @@ pub enum GraphFormat {
     Turtle,
     /// [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/)
     RdfXml,
+    /// [Notation3](https://w3c.github.io/N3/spec/)
+    ///
+    /// Only the N3 documents that are RDF graphs are supported: the formulae, the quick variables and the quantifiers are rejected.
+    /// The graphs are serialized in N-Triples, a subset of N3.
+    N3,
 }
 
 impl GraphFormat {
@@ -26,6 +31,7 @@ impl GraphFormat {
             Self::NTriples => "http://www.w3.org/ns/formats/N-Triples",
             Self::Turtle => "http://www.w3.org/ns/formats/Turtle",
             Self::RdfXml => "http://www.w3.org/ns/formats/RDF_XML",
+            Self::N3 => "http://www.w3.org/ns/formats/N3",
         }
     }
 
@@ -42,6 +48,7 @@ impl GraphFormat {
             Self::NTriples => "application/n-triples",
             Self::Turtle => "text/turtle",
             Self::RdfXml => "application/rdf+xml",
+            Self::N3 => "text/n3",
         }
     }
 
@@ -58,29 +65,59 @@ impl GraphFormat {
             Self::NTriples => "nt",
             Self::Turtle => "ttl",
             Self::RdfXml => "rdf",
+            Self::N3 => "n3",
         }
     }
     /// Looks for a known format from a media type.
     ///
     /// It supports some media type aliases.
     /// For example, "application/xml" is going to return `GraphFormat::RdfXml` even if it is not its canonical media type.
+    /// The media type parameters are ignored and the comparison is case-insensitive.
     ///
     /// Example:
     /// ```
     /// use oxigraph::io::GraphFormat;
     ///
-    /// assert_eq!(GraphFormat::from_media_type("text/turtle; charset=utf-8"), Some(GraphFormat::Turtle))
+    /// assert_eq!(GraphFormat::from_media_type("text/turtle; charset=utf-8"), Some(GraphFormat::Turtle));
+    /// assert_eq!(GraphFormat::from_media_type("Application/RDF+XML"), Some(GraphFormat::RdfXml));
     /// ```
     #[inline]
     pub fn from_media_type(media_type: &str) -> Option<Self> {
-        match media_type.split(';').next()?.trim() {
+        match media_type_essence(media_type)?.as_str() {
             "application/n-triples" | "text/plain" => Some(Self::NTriples),
             "text/turtle" | "application/turtle" | "application/x-turtle" => Some(Self::Turtle),
             "application/rdf+xml" | "application/xml" | "text/xml" => Some(Self::RdfXml),
+            "text/n3" | "text/rdf+n3" => Some(Self::N3),
             _ => None,
         }
     }
 
+    /// Picks the preferred supported format of an HTTP `Accept` header value.
+    ///
+    /// The media ranges are weighted by their `q` parameter and the first one wins in case of tie.
+    /// `*/*` is resolved to Turtle, `text/*` to Turtle and `application/*` to N-Triples.
+    /// Returns `None` if no media range matches a supported format.
+    ///
+    /// Example:
+    /// ```
+    /// use oxigraph::io::GraphFormat;
+    ///
+    /// assert_eq!(GraphFormat::from_accept_header("application/ld+json, application/n-triples;q=0.5, text/turtle;q=0.8"), Some(GraphFormat::Turtle));
+    /// assert_eq!(GraphFormat::from_accept_header("application/*"), Some(GraphFormat::NTriples));
+    /// assert_eq!(GraphFormat::from_accept_header("application/ld+json"), None);
+    /// ```
+    pub fn from_accept_header(accept: &str) -> Option<Self> {
+        negotiate(
+            accept,
+            Self::Turtle,
+            |media_range| match media_type_essence(media_range)?.as_str() {
+                "text/*" => Some(Self::Turtle),
+                "application/*" => Some(Self::NTriples),
+                _ => Self::from_media_type(media_range),
+            },
+        )
+    }
+
     /// Looks for a known format from an extension.
     ///
     /// It supports some aliases.
@@ -93,13 +130,55 @@ impl GraphFormat {
     /// ```
     #[inline]
     pub fn from_extension(extension: &str) -> Option<Self> {
-        match extension {
+        match extension
+            .trim_start_matches('.')
+            .to_ascii_lowercase()
+            .as_str()
+        {
             "nt" | "txt" => Some(Self::NTriples),
             "ttl" => Some(Self::Turtle),
-            "rdf" | "xml" => Some(Self::RdfXml),
+            "rdf" | "xml" | "owl" => Some(Self::RdfXml),
+            "n3" => Some(Self::N3),
             _ => None,
         }
     }
+
+    /// Guesses the format from the first bytes of a file.
+    ///
+    /// RDF/XML is detected from its XML declaration, document type or `rdf:` root element,
+    /// Turtle from its prefix and base declarations
+    /// and N-Triples from a first statement made of full IRIs and blank nodes.
+    /// The other files starting with a statement are assumed to be Turtle.
+    /// The compressed files should be decompressed first.
+    ///
+    /// Example:
+    /// ```
+    /// use oxigraph::io::GraphFormat;
+    ///
+    /// assert_eq!(GraphFormat::from_leading_bytes(b"# comment\n<http://example.com/s> <http://example.com/p> \"o\" ."), Some(GraphFormat::NTriples));
+    /// assert_eq!(GraphFormat::from_leading_bytes(b"@prefix ex: <http://example.com/> ."), Some(GraphFormat::Turtle));
+    /// assert_eq!(GraphFormat::from_leading_bytes(b"<?xml version=\"1.0\"?>"), Some(GraphFormat::RdfXml));
+    /// assert_eq!(GraphFormat::from_leading_bytes(b"{\"@context\": {}}"), None);
+    /// ```
+    pub fn from_leading_bytes(bytes: &[u8]) -> Option<Self> {
+        let statement = first_statement(bytes)?;
+        if statement.starts_with("<?xml")
+            || statement.starts_with("<!")
+            || statement.starts_with("<rdf:")
+        {
+            Some(Self::RdfXml)
+        } else if is_turtle_directive(&statement) {
+            Some(Self::Turtle)
+        } else if is_n_statement(&statement, false) {
+            Some(Self::NTriples)
+        } else if statement.starts_with(['<', '_', '[', '('])
+            || starts_with_prefixed_name(&statement)
+        {
+            Some(Self::Turtle)
+        } else {
+            None
+        }
+    }
 }
 
 /// [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset) serialization formats.
@@ -112,6 +191,31 @@ pub enum DatasetFormat {
     NQuads,
     /// [TriG](https://www.w3.org/TR/trig/)
     TriG,
+    /// A compact binary serialization specific to this library:
+    /// a dictionary of the terms followed by the quads written as term ids.
+    ///
+    /// It is much faster to write and to read than the textual formats because the terms are written once
+    /// and nothing has to be parsed or validated, for example to back up the store content during upgrades.
+    /// The whole file is buffered by the serializer until the end of the writing.
+    ///
+    /// ```
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, &Literal::from(1), ex))?;
+    ///
+    /// let mut dump = Vec::new();
+    /// store.dump_dataset(&mut dump, DatasetFormat::Binary)?;
+    ///
+    /// let restored = Store::new()?;
+    /// restored.load_dataset(dump.as_slice(), DatasetFormat::Binary, None)?;
+    /// assert!(restored.contains(QuadRef::new(ex, ex, &Literal::from(1), ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    Binary,
 }
 
 impl DatasetFormat {
@@ -127,6 +231,7 @@ impl DatasetFormat {
         match self {
             Self::NQuads => "http://www.w3.org/ns/formats/N-Quads",
             Self::TriG => "http://www.w3.org/ns/formats/TriG",
+            Self::Binary => "urn:ic-oxigraph:formats:binary",
         }
     }
 
@@ -142,6 +247,7 @@ impl DatasetFormat {
         match self {
             Self::NQuads => "application/n-quads",
             Self::TriG => "application/trig",
+            Self::Binary => "application/x-oxigraph-binary",
         }
     }
 
@@ -157,11 +263,13 @@ impl DatasetFormat {
         match self {
             Self::NQuads => "nq",
             Self::TriG => "trig",
+            Self::Binary => "oxb",
         }
     }
     /// Looks for a known format from a media type.
     ///
     /// It supports some media type aliases.
+    /// The media type parameters are ignored and the comparison is case-insensitive.
     ///
     /// Example:
     /// ```
@@ -171,9 +279,10 @@ impl DatasetFormat {
     /// ```
     #[inline]
     pub fn from_media_type(media_type: &str) -> Option<Self> {
-        match media_type.split(';').next()?.trim() {
+        match media_type_essence(media_type)?.as_str() {
             "application/n-quads" | "text/x-nquads" | "text/nquads" => Some(Self::NQuads),
             "application/trig" | "application/x-trig" => Some(Self::TriG),
+            "application/x-oxigraph-binary" => Some(Self::Binary),
             _ => None,
         }
     }
@@ -190,12 +299,78 @@ impl DatasetFormat {
     /// ```
     #[inline]
     pub fn from_extension(extension: &str) -> Option<Self> {
-        match extension {
+        match extension
+            .trim_start_matches('.')
+            .to_ascii_lowercase()
+            .as_str()
+        {
             "nq" | "txt" => Some(Self::NQuads),
             "trig" => Some(Self::TriG),
+            "oxb" => Some(Self::Binary),
             _ => None,
         }
     }
+
+    /// Picks the preferred supported format of an HTTP `Accept` header value.
+    ///
+    /// The media ranges are weighted by their `q` parameter and the first one wins in case of tie.
+    /// `*/*` is resolved to TriG and `application/*` to N-Quads.
+    /// Returns `None` if no media range matches a supported format.
+    ///
+    /// Example:
+    /// ```
+    /// use oxigraph::io::DatasetFormat;
+    ///
+    /// assert_eq!(DatasetFormat::from_accept_header("application/trig;q=0.9, application/n-quads"), Some(DatasetFormat::NQuads));
+    /// assert_eq!(DatasetFormat::from_accept_header("text/turtle, */*;q=0.1"), Some(DatasetFormat::TriG));
+    /// ```
+    pub fn from_accept_header(accept: &str) -> Option<Self> {
+        negotiate(accept, Self::TriG, |media_range| {
+            if media_type_essence(media_range)? == "application/*" {
+                Some(Self::NQuads)
+            } else {
+                Self::from_media_type(media_range)
+            }
+        })
+    }
+
+    /// Guesses the format from the first bytes of a file.
+    ///
+    /// The [binary format](Self::Binary) is detected from its magic bytes,
+    /// TriG from its prefix and base declarations or graph blocks
+    /// and N-Quads from a first statement made of full IRIs and blank nodes.
+    /// The other files starting with a statement are assumed to be TriG.
+    /// The compressed files should be decompressed first.
+    ///
+    /// Example:
+    /// ```
+    /// use oxigraph::io::DatasetFormat;
+    ///
+    /// assert_eq!(DatasetFormat::from_leading_bytes(b"<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g> ."), Some(DatasetFormat::NQuads));
+    /// assert_eq!(DatasetFormat::from_leading_bytes(b"<http://example.com/g> { <http://example.com/s> <http://example.com/p> 1 }"), Some(DatasetFormat::TriG));
+    /// ```
+    pub fn from_leading_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"OXBQ") {
+            return Some(Self::Binary);
+        }
+        let statement = first_statement(bytes)?;
+        if is_turtle_directive(&statement)
+            || statement.starts_with('{')
+            || statement
+                .get(..5)
+                .map_or(false, |start| start.eq_ignore_ascii_case("GRAPH"))
+        {
+            Some(Self::TriG)
+        } else if is_n_statement(&statement, true) {
+            Some(Self::NQuads)
+        } else if statement.starts_with(['<', '_', '[', '('])
+            || starts_with_prefixed_name(&statement)
+        {
+            Some(Self::TriG)
+        } else {
+            None
+        }
+    }
 }
 
 impl TryFrom<DatasetFormat> for GraphFormat {
@@ -207,6 +382,7 @@ impl TryFrom<DatasetFormat> for GraphFormat {
         match value {
             DatasetFormat::NQuads => Ok(Self::NTriples),
             DatasetFormat::TriG => Ok(Self::Turtle),
+            DatasetFormat::Binary => Err(()),
         }
     }
 }
@@ -220,7 +396,119 @@ impl TryFrom<GraphFormat> for DatasetFormat {
         match value {
             GraphFormat::NTriples => Ok(Self::NQuads),
             GraphFormat::Turtle => Ok(Self::TriG),
-            GraphFormat::RdfXml => Err(()),
+            GraphFormat::RdfXml | GraphFormat::N3 => Err(()),
+        }
+    }
+}
+
+/// Returns the lowercase media type without its parameters.
+fn media_type_essence(media_type: &str) -> Option<String> {
+    let essence = media_type.split(';').next()?.trim();
+    (!essence.is_empty()).then(|| essence.to_ascii_lowercase())
+}
+
+/// Picks the format with the highest quality in an `Accept` header value.
+pub(crate) fn negotiate<F: Copy>(
+    accept: &str,
+    default: F,
+    parse: impl Fn(&str) -> Option<F>,
+) -> Option<F> {
+    let mut best: Option<(F, f32)> = None;
+    for media_range in accept.split(',') {
+        let mut parts = media_range.split(';');
+        let media_type = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.);
+        let format = if media_type == "*/*" {
+            Some(default)
+        } else {
+            parse(media_type)
+        };
+        if let Some(format) = format {
+            if quality > 0. && best.map_or(true, |(_, q)| quality > q) {
+                best = Some((format, quality));
+            }
         }
     }
+    best.map(|(format, _)| format)
+}
+
+/// Returns the first line of a file that is not empty or a comment.
+fn first_statement(bytes: &[u8]) -> Option<String> {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ToOwned::to_owned)
+}
+
+fn is_turtle_directive(statement: &str) -> bool {
+    statement.starts_with("@prefix")
+        || statement.starts_with("@base")
+        || ["PREFIX", "BASE"].iter().any(|keyword| {
+            statement
+                .get(..keyword.len())
+                .map_or(false, |start| start.eq_ignore_ascii_case(keyword))
+        })
+}
+
+/// Checks if the statement is only made of full IRIs, blank nodes and literals, like in N-Triples,
+/// or in N-Quads if `with_graph_name` is set.
+fn is_n_statement(statement: &str, with_graph_name: bool) -> bool {
+    let max_terms = if with_graph_name { 4 } else { 3 };
+    let mut rest = statement;
+    let mut terms = 0;
+    loop {
+        rest = rest.trim_start();
+        if let Some(end) = rest.strip_prefix('.') {
+            let end = end.trim_start();
+            return terms >= 3 && (end.is_empty() || end.starts_with('#'));
+        }
+        rest = if rest.starts_with('<') {
+            match rest.find('>') {
+                Some(end) => &rest[end + 1..],
+                None => return false,
+            }
+        } else if rest.starts_with("_:") && terms != 1 {
+            rest.trim_start_matches(|c: char| !c.is_whitespace())
+        } else if rest.starts_with('"') && terms == 2 {
+            match skip_literal(rest) {
+                Some(end) => end,
+                None => return false,
+            }
+        } else {
+            return false;
+        };
+        terms += 1;
+        if terms > max_terms {
+            return false;
+        }
+    }
+}
+
+/// Skips a quoted literal with its optional language tag or datatype.
+fn skip_literal(literal: &str) -> Option<&str> {
+    let mut escaped = false;
+    let (end, _) = literal.char_indices().skip(1).find(|(_, c)| {
+        let is_end = !escaped && *c == '"';
+        escaped = !escaped && *c == '\\';
+        is_end
+    })?;
+    let rest = &literal[end + 1..];
+    if let Some(language) = rest.strip_prefix('@') {
+        Some(language.trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '-'))
+    } else if let Some(datatype) = rest.strip_prefix("^^<") {
+        Some(&datatype[datatype.find('>')? + 1..])
+    } else {
+        Some(rest)
+    }
+}
+
+fn starts_with_prefixed_name(statement: &str) -> bool {
+    statement.split_whitespace().next().map_or(false, |token| {
+        token.starts_with(|c: char| c.is_alphabetic() || c == ':') && token.contains(':')
+    })
 }
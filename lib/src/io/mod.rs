@@ -1,10 +1,16 @@
 //! Utilities to read and write RDF graphs and datasets.
 
+mod binary;
+mod compression;
 mod error;
 mod format;
+mod n3;
+mod pretty;
 pub mod read;
 pub mod write;
 
+pub use self::compression::Compression;
+pub(crate) use self::format::negotiate;
 pub use self::format::DatasetFormat;
 pub use self::format::GraphFormat;
 pub use self::read::DatasetParser;
@@ -0,0 +1,715 @@
+//! A parser for the RDF compatible subset of [Notation3](https://w3c.github.io/N3/spec/).
+
+use crate::io::read::ParseError;
+use crate::model::vocab::{rdf, xsd};
+use crate::model::*;
+use oxiri::Iri;
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+
+const OWL_SAME_AS: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("http://www.w3.org/2002/07/owl#sameAs");
+const LOG_IMPLIES: NamedNodeRef<'_> =
+    NamedNodeRef::new_unchecked("http://www.w3.org/2000/10/swap/log#implies");
+
+/// Parses the N3 documents that are plain RDF graphs.
+///
+/// It supports the Turtle syntax extended with the N3 paths (`!` and `^`), the `is ... of`, `has`,
+/// `=`, `=>` and `<=` verbs and the `@`-prefixed keywords.
+/// The formulae, the quick variables and the quantifiers are rejected with an error.
+pub struct N3Parser<R: BufRead> {
+    reader: CharReader<R>,
+    base_iri: Option<Iri<String>>,
+    prefixes: HashMap<String, String>,
+    bnodes: HashMap<String, BlankNode>,
+    is_end: bool,
+}
+
+/// The direction of a verb
+enum Verb {
+    /// `subject predicate object`
+    Forward(Term),
+    /// `object predicate subject`, like for `is predicate of`
+    Backward(Term),
+}
+
+impl<R: BufRead> N3Parser<R> {
+    pub fn new(reader: R, base_iri: Option<Iri<String>>) -> Self {
+        Self {
+            reader: CharReader {
+                reader,
+                buffer: VecDeque::new(),
+                line: 1,
+                column: 1,
+                eof: false,
+            },
+            base_iri,
+            prefixes: HashMap::new(),
+            bnodes: HashMap::new(),
+            is_end: false,
+        }
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.is_end
+    }
+
+    /// Parses the next statement and adds its triples to `buffer`
+    pub fn parse_step(&mut self, buffer: &mut Vec<Triple>) -> Result<(), ParseError> {
+        let result = self.parse_statement(buffer);
+        if result.is_err() {
+            // The parser is not able to recover from a syntax error
+            self.is_end = true;
+        }
+        result
+    }
+
+    fn parse_statement(&mut self, buffer: &mut Vec<Triple>) -> Result<(), ParseError> {
+        self.skip_whitespaces()?;
+        match self.reader.peek()? {
+            None => {
+                self.is_end = true;
+                Ok(())
+            }
+            Some('@') => {
+                self.reader.next()?;
+                let keyword = self.read_word()?;
+                match keyword.as_str() {
+                    "prefix" => self.parse_prefix()?,
+                    "base" => self.parse_base()?,
+                    "keywords" => return Err(self.error("@keywords is not supported")),
+                    "forAll" | "forSome" => {
+                        return Err(self.error(format!(
+                            "@{keyword} is not supported, only the RDF compatible subset of N3 is"
+                        )))
+                    }
+                    _ => return Err(self.error(format!("Unexpected @{keyword}"))),
+                }
+                self.expect('.')
+            }
+            Some(_) => {
+                let (word, next) = self.reader.peek_word()?;
+                if next != Some(':') && word.eq_ignore_ascii_case("prefix") {
+                    self.read_word()?;
+                    self.parse_prefix()
+                } else if next != Some(':') && word.eq_ignore_ascii_case("base") {
+                    self.read_word()?;
+                    self.parse_base()
+                } else {
+                    let subject = self.parse_expression(buffer)?;
+                    self.skip_whitespaces()?;
+                    if self.reader.peek()? != Some('.') {
+                        self.parse_property_list(&subject, buffer)?;
+                    }
+                    self.expect('.')
+                }
+            }
+        }
+    }
+
+    fn parse_prefix(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespaces()?;
+        let (prefix, _) = self.reader.peek_word()?;
+        for _ in prefix.chars() {
+            self.reader.next()?;
+        }
+        self.expect(':')?;
+        self.skip_whitespaces()?;
+        let iri = self.parse_iri()?;
+        self.prefixes.insert(prefix, iri.into_string());
+        Ok(())
+    }
+
+    fn parse_base(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespaces()?;
+        let iri = self.parse_iri()?;
+        self.base_iri = Some(Iri::parse(iri.into_string()).map_err(|e| self.error(e.to_string()))?);
+        Ok(())
+    }
+
+    fn parse_property_list(
+        &mut self,
+        subject: &Term,
+        buffer: &mut Vec<Triple>,
+    ) -> Result<(), ParseError> {
+        loop {
+            let verb = self.parse_verb(buffer)?;
+            loop {
+                let object = self.parse_expression(buffer)?;
+                match &verb {
+                    Verb::Forward(predicate) => {
+                        self.emit(subject.clone(), predicate.clone(), object, buffer)?;
+                    }
+                    Verb::Backward(predicate) => {
+                        self.emit(object, predicate.clone(), subject.clone(), buffer)?;
+                    }
+                }
+                self.skip_whitespaces()?;
+                if self.reader.peek()? != Some(',') {
+                    break;
+                }
+                self.reader.next()?;
+            }
+            if self.reader.peek()? != Some(';') {
+                return Ok(());
+            }
+            while self.reader.peek()? == Some(';') {
+                self.reader.next()?;
+                self.skip_whitespaces()?;
+            }
+            if matches!(self.reader.peek()?, None | Some('.' | ']')) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_verb(&mut self, buffer: &mut Vec<Triple>) -> Result<Verb, ParseError> {
+        self.skip_whitespaces()?;
+        match (self.reader.peek()?, self.reader.peek_at(1)?) {
+            (Some('='), Some('>')) => {
+                self.reader.next()?;
+                self.reader.next()?;
+                return Ok(Verb::Forward(LOG_IMPLIES.into_owned().into()));
+            }
+            (Some('='), _) => {
+                self.reader.next()?;
+                return Ok(Verb::Forward(OWL_SAME_AS.into_owned().into()));
+            }
+            (Some('<'), Some('=')) => {
+                self.reader.next()?;
+                self.reader.next()?;
+                return Ok(Verb::Backward(LOG_IMPLIES.into_owned().into()));
+            }
+            (Some('<'), Some('-')) => {
+                self.reader.next()?;
+                self.reader.next()?;
+                return Ok(Verb::Backward(self.parse_expression(buffer)?));
+            }
+            _ => (),
+        }
+        let with_at = self.reader.peek()? == Some('@');
+        let (word, next) = self.reader.peek_word_at(usize::from(with_at))?;
+        if next == Some(':') || !(with_at || matches!(word.as_str(), "a" | "has" | "is")) {
+            return Ok(Verb::Forward(self.parse_expression(buffer)?));
+        }
+        if with_at {
+            self.reader.next()?;
+        }
+        self.read_word()?;
+        match word.as_str() {
+            "a" => Ok(Verb::Forward(rdf::TYPE.into_owned().into())),
+            "has" => Ok(Verb::Forward(self.parse_expression(buffer)?)),
+            "is" => {
+                let predicate = self.parse_expression(buffer)?;
+                self.skip_whitespaces()?;
+                if self.reader.peek()? == Some('@') {
+                    self.reader.next()?;
+                }
+                if self.read_word()? != "of" {
+                    return Err(self.error("'of' expected after 'is' and the predicate"));
+                }
+                Ok(Verb::Backward(predicate))
+            }
+            _ => Err(self.error(format!("Unexpected @{word}"))),
+        }
+    }
+
+    /// Parses a node and the path following it, if any
+    fn parse_expression(&mut self, buffer: &mut Vec<Triple>) -> Result<Term, ParseError> {
+        let mut node = self.parse_path_item(buffer)?;
+        loop {
+            self.skip_whitespaces()?;
+            match (self.reader.peek()?, self.reader.peek_at(1)?) {
+                (Some('!'), _) => {
+                    self.reader.next()?;
+                    let predicate = self.parse_path_item(buffer)?;
+                    let object = Term::from(BlankNode::default());
+                    self.emit(node, predicate, object.clone(), buffer)?;
+                    node = object;
+                }
+                (Some('^'), next) if next != Some('^') => {
+                    self.reader.next()?;
+                    let predicate = self.parse_path_item(buffer)?;
+                    let subject = Term::from(BlankNode::default());
+                    self.emit(subject.clone(), predicate, node, buffer)?;
+                    node = subject;
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    fn parse_path_item(&mut self, buffer: &mut Vec<Triple>) -> Result<Term, ParseError> {
+        self.skip_whitespaces()?;
+        match self.reader.peek()? {
+            None => Err(self.error("Unexpected end of file")),
+            Some('<') => Ok(self.parse_iri()?.into()),
+            Some('_') if self.reader.peek_at(1)? == Some(':') => {
+                self.reader.next()?;
+                self.reader.next()?;
+                let label = self.read_local_name()?;
+                if label.is_empty() {
+                    return Err(self.error("Empty blank node label"));
+                }
+                Ok(self
+                    .bnodes
+                    .entry(label)
+                    .or_insert_with(BlankNode::default)
+                    .clone()
+                    .into())
+            }
+            Some('[') => {
+                self.reader.next()?;
+                let node = Term::from(BlankNode::default());
+                self.skip_whitespaces()?;
+                if self.reader.peek()? != Some(']') {
+                    self.parse_property_list(&node, buffer)?;
+                }
+                self.expect(']')?;
+                Ok(node)
+            }
+            Some('(') => {
+                self.reader.next()?;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespaces()?;
+                    if self.reader.peek()? == Some(')') {
+                        self.reader.next()?;
+                        break;
+                    }
+                    items.push(self.parse_expression(buffer)?);
+                }
+                let mut list = Term::from(rdf::NIL.into_owned());
+                for item in items.into_iter().rev() {
+                    let cell = Term::from(BlankNode::default());
+                    self.emit(cell.clone(), rdf::FIRST.into_owned().into(), item, buffer)?;
+                    self.emit(cell.clone(), rdf::REST.into_owned().into(), list, buffer)?;
+                    list = cell;
+                }
+                Ok(list)
+            }
+            Some('{') => {
+                Err(self
+                    .error("Formulae are not supported, only the RDF compatible subset of N3 is"))
+            }
+            Some('?') => Err(self.error(
+                "Quick variables are not supported, only the RDF compatible subset of N3 is",
+            )),
+            Some('"' | '\'') => self.parse_literal(buffer),
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.parse_number(),
+            Some('.')
+                if self
+                    .reader
+                    .peek_at(1)?
+                    .map_or(false, |c| c.is_ascii_digit()) =>
+            {
+                self.parse_number()
+            }
+            Some('@') => {
+                self.reader.next()?;
+                let keyword = self.read_word()?;
+                self.parse_boolean(&keyword)
+            }
+            Some(c) if c == ':' || is_name_start_char(c) => {
+                let (word, next) = self.reader.peek_word()?;
+                if next == Some(':') {
+                    Ok(self.parse_prefixed_name()?.into())
+                } else {
+                    self.read_word()?;
+                    self.parse_boolean(&word)
+                }
+            }
+            Some(c) => Err(self.error(format!("Unexpected character '{c}'"))),
+        }
+    }
+
+    fn parse_boolean(&self, keyword: &str) -> Result<Term, ParseError> {
+        match keyword {
+            "true" | "false" => Ok(Literal::new_typed_literal(keyword, xsd::BOOLEAN).into()),
+            _ => Err(self.error(format!("Unexpected keyword '{keyword}'"))),
+        }
+    }
+
+    fn parse_iri(&mut self) -> Result<NamedNode, ParseError> {
+        self.expect('<')?;
+        let mut iri = String::new();
+        loop {
+            match self.reader.next()? {
+                None => return Err(self.error("Unexpected end of file in an IRI")),
+                Some('>') => break,
+                Some('\\') => iri.push(self.parse_unicode_escape()?),
+                Some(c)
+                    if c.is_whitespace()
+                        || matches!(c, '<' | '"' | '{' | '}' | '|' | '^' | '`') =>
+                {
+                    return Err(self.error(format!("Invalid character '{c}' in an IRI")))
+                }
+                Some(c) => iri.push(c),
+            }
+        }
+        self.resolve(&iri)
+    }
+
+    fn parse_prefixed_name(&mut self) -> Result<NamedNode, ParseError> {
+        let (prefix, _) = self.reader.peek_word()?;
+        for _ in prefix.chars() {
+            self.reader.next()?;
+        }
+        self.expect(':')?;
+        let local = self.read_local_name()?;
+        let namespace = self
+            .prefixes
+            .get(&prefix)
+            .ok_or_else(|| self.error(format!("The prefix {prefix}: has not been declared")))?;
+        let iri = format!("{namespace}{local}");
+        Iri::parse(iri.as_str()).map_err(|e| self.error(format!("Invalid IRI <{iri}>: {e}")))?;
+        Ok(NamedNode::new_unchecked(iri))
+    }
+
+    fn parse_literal(&mut self, buffer: &mut Vec<Triple>) -> Result<Term, ParseError> {
+        let quote = self.reader.next()?.unwrap_or('"');
+        let is_long = self.reader.peek()? == Some(quote) && self.reader.peek_at(1)? == Some(quote);
+        if is_long {
+            self.reader.next()?;
+            self.reader.next()?;
+        }
+        let mut value = String::new();
+        loop {
+            match self.reader.next()? {
+                None => return Err(self.error("Unexpected end of file in a string")),
+                Some('\\') => value.push(self.parse_string_escape()?),
+                Some(c) if c == quote => {
+                    if !is_long {
+                        break;
+                    }
+                    if self.reader.peek()? == Some(quote) && self.reader.peek_at(1)? == Some(quote)
+                    {
+                        self.reader.next()?;
+                        self.reader.next()?;
+                        // The string may end with quotes: """a"""" is 'a"'
+                        while self.reader.peek()? == Some(quote) {
+                            self.reader.next()?;
+                            value.push(quote);
+                        }
+                        break;
+                    }
+                    value.push(c);
+                }
+                Some('\n' | '\r') if !is_long => {
+                    return Err(self.error("Line jumps are not allowed in short strings"))
+                }
+                Some(c) => value.push(c),
+            }
+        }
+        match (self.reader.peek()?, self.reader.peek_at(1)?) {
+            (Some('@'), _) => {
+                self.reader.next()?;
+                let mut language = String::new();
+                while let Some(c) = self.reader.peek()? {
+                    if !(c.is_ascii_alphanumeric() || c == '-') {
+                        break;
+                    }
+                    language.push(c);
+                    self.reader.next()?;
+                }
+                Ok(Literal::new_language_tagged_literal(value, &language)
+                    .map_err(|e| self.error(format!("Invalid language tag '{language}': {e}")))?
+                    .into())
+            }
+            (Some('^'), Some('^')) => {
+                self.reader.next()?;
+                self.reader.next()?;
+                match self.parse_path_item(buffer)? {
+                    Term::NamedNode(datatype) => {
+                        Ok(Literal::new_typed_literal(value, datatype).into())
+                    }
+                    _ => Err(self.error("The datatype of a literal must be an IRI")),
+                }
+            }
+            _ => Ok(Literal::new_simple_literal(value).into()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Term, ParseError> {
+        let mut value = String::new();
+        if let Some(sign @ ('+' | '-')) = self.reader.peek()? {
+            value.push(sign);
+            self.reader.next()?;
+        }
+        self.read_digits(&mut value)?;
+        let mut datatype = xsd::INTEGER;
+        if self.reader.peek()? == Some('.')
+            && self
+                .reader
+                .peek_at(1)?
+                .map_or(false, |c| c.is_ascii_digit())
+        {
+            value.push('.');
+            self.reader.next()?;
+            self.read_digits(&mut value)?;
+            datatype = xsd::DECIMAL;
+        }
+        if let Some(e @ ('e' | 'E')) = self.reader.peek()? {
+            value.push(e);
+            self.reader.next()?;
+            if let Some(sign @ ('+' | '-')) = self.reader.peek()? {
+                value.push(sign);
+                self.reader.next()?;
+            }
+            if !self.read_digits(&mut value)? {
+                return Err(self.error("Digits expected in the exponent of a double"));
+            }
+            datatype = xsd::DOUBLE;
+        }
+        if !value.contains(|c: char| c.is_ascii_digit()) {
+            return Err(self.error("Digits expected in a number"));
+        }
+        Ok(Literal::new_typed_literal(value, datatype).into())
+    }
+
+    /// Reads a sequence of digits and returns if it is not empty
+    fn read_digits(&mut self, value: &mut String) -> Result<bool, ParseError> {
+        let mut found = false;
+        while let Some(c) = self.reader.peek()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            value.push(c);
+            self.reader.next()?;
+            found = true;
+        }
+        Ok(found)
+    }
+
+    fn parse_string_escape(&mut self) -> Result<char, ParseError> {
+        match self.reader.peek()? {
+            Some('u' | 'U') => self.parse_unicode_escape(),
+            _ => match self.reader.next()? {
+                Some('t') => Ok('\t'),
+                Some('b') => Ok('\u{8}'),
+                Some('n') => Ok('\n'),
+                Some('r') => Ok('\r'),
+                Some('f') => Ok('\u{C}'),
+                Some(c @ ('"' | '\'' | '\\')) => Ok(c),
+                Some(c) => Err(self.error(format!("Invalid escape sequence '\\{c}'"))),
+                None => Err(self.error("Unexpected end of file in an escape sequence")),
+            },
+        }
+    }
+
+    /// Parses the `uXXXX` or `UXXXXXXXX` part of an escape sequence
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let length = match self.reader.next()? {
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        let mut code = 0;
+        for _ in 0..length {
+            let digit = self
+                .reader
+                .next()?
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("Invalid hexadecimal digit in an escape sequence"))?;
+            code = code * 16 + digit;
+        }
+        char::from_u32(code).ok_or_else(|| self.error("Invalid code point in an escape sequence"))
+    }
+
+    /// Reads the local part of a prefixed name or a blank node label
+    fn read_local_name(&mut self) -> Result<String, ParseError> {
+        let mut local = String::new();
+        while let Some(c) = self.reader.peek()? {
+            if c == '\\' {
+                self.reader.next()?;
+                match self.reader.next()? {
+                    Some(c) if "_~.-!$&'()*+,;=/?#@%".contains(c) => local.push(c),
+                    _ => return Err(self.error("Invalid escape sequence in a local name")),
+                }
+            } else if c == '%' {
+                local.push(c);
+                self.reader.next()?;
+                for _ in 0..2 {
+                    match self.reader.next()? {
+                        Some(c) if c.is_ascii_hexdigit() => local.push(c),
+                        _ => return Err(self.error("Invalid percent encoding in a local name")),
+                    }
+                }
+            } else if c == '.' {
+                // A local name can't end with a dot, it is the end of the statement
+                if !self
+                    .reader
+                    .peek_at(1)?
+                    .map_or(false, |c| is_name_char(c) || c == ':')
+                {
+                    break;
+                }
+                local.push(c);
+                self.reader.next()?;
+            } else if is_name_char(c) || c == ':' {
+                local.push(c);
+                self.reader.next()?;
+            } else {
+                break;
+            }
+        }
+        Ok(local)
+    }
+
+    fn read_word(&mut self) -> Result<String, ParseError> {
+        let mut word = String::new();
+        while let Some(c) = self.reader.peek()? {
+            if !is_name_char(c) {
+                break;
+            }
+            word.push(c);
+            self.reader.next()?;
+        }
+        Ok(word)
+    }
+
+    fn skip_whitespaces(&mut self) -> Result<(), ParseError> {
+        while let Some(c) = self.reader.peek()? {
+            if c == '#' {
+                while !matches!(self.reader.next()?, None | Some('\n' | '\r')) {}
+            } else if c.is_whitespace() {
+                self.reader.next()?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        self.skip_whitespaces()?;
+        match self.reader.next()? {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(format!("'{expected}' expected, found '{c}'"))),
+            None => Err(self.error(format!("'{expected}' expected, found the end of file"))),
+        }
+    }
+
+    fn resolve(&self, iri: &str) -> Result<NamedNode, ParseError> {
+        Ok(NamedNode::new_unchecked(
+            if let Some(base_iri) = &self.base_iri {
+                base_iri.resolve(iri)
+            } else {
+                Iri::parse(iri.to_owned())
+            }
+            .map_err(|e| self.error(format!("Invalid IRI <{iri}>: {e}")))?
+            .into_inner(),
+        ))
+    }
+
+    fn emit(
+        &self,
+        subject: Term,
+        predicate: Term,
+        object: Term,
+        buffer: &mut Vec<Triple>,
+    ) -> Result<(), ParseError> {
+        let subject = match subject {
+            Term::NamedNode(node) => Subject::from(node),
+            Term::BlankNode(node) => node.into(),
+            Term::Triple(triple) => Subject::Triple(triple),
+            Term::Literal(literal) => {
+                return Err(self.error(format!(
+                    "The literal {literal} can't be the subject of an RDF triple"
+                )))
+            }
+        };
+        let predicate = match predicate {
+            Term::NamedNode(node) => node,
+            _ => {
+                return Err(self.error(format!(
+                    "{predicate} can't be the predicate of an RDF triple, only IRIs can"
+                )))
+            }
+        };
+        buffer.push(Triple::new(subject, predicate, object));
+        Ok(())
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::n3(self.reader.line, self.reader.column, message)
+    }
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '\u{B7}')
+}
+
+/// Reads the characters of a [`BufRead`] line by line, with a lookahead
+struct CharReader<R: BufRead> {
+    reader: R,
+    buffer: VecDeque<char>,
+    line: u64,
+    column: u64,
+    eof: bool,
+}
+
+impl<R: BufRead> CharReader<R> {
+    fn fill(&mut self, len: usize) -> Result<(), ParseError> {
+        while self.buffer.len() < len && !self.eof {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                self.eof = true;
+            } else {
+                self.buffer.extend(line.chars());
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, ParseError> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&mut self, position: usize) -> Result<Option<char>, ParseError> {
+        self.fill(position + 1)?;
+        Ok(self.buffer.get(position).copied())
+    }
+
+    /// Returns the name starting at `position` without consuming it and the character following it
+    fn peek_word_at(&mut self, position: usize) -> Result<(String, Option<char>), ParseError> {
+        let mut word = String::new();
+        let mut position = position;
+        loop {
+            let next = self.peek_at(position)?;
+            let is_word_char = match next {
+                // A dot is only part of the word if it is followed by another character of the word
+                Some('.') => {
+                    !word.is_empty() && self.peek_at(position + 1)?.map_or(false, is_name_char)
+                }
+                Some(c) => is_name_char(c),
+                None => false,
+            };
+            if !is_word_char {
+                return Ok((word, next));
+            }
+            word.extend(next);
+            position += 1;
+        }
+    }
+
+    fn peek_word(&mut self) -> Result<(String, Option<char>), ParseError> {
+        self.peek_word_at(0)
+    }
+
+    fn next(&mut self) -> Result<Option<char>, ParseError> {
+        self.fill(1)?;
+        let c = self.buffer.pop_front();
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else if c.is_some() {
+            self.column += 1;
+        }
+        Ok(c)
+    }
+}
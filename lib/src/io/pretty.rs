@@ -0,0 +1,385 @@
+//! [Turtle](https://www.w3.org/TR/turtle/) and [TriG](https://www.w3.org/TR/trig/) serialization with prefixes.
+
+use crate::model::vocab::{rdf, xsd};
+use crate::model::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Writes the quads in TriG, which is plain Turtle if they are all in the default graph.
+///
+/// The prefixes are declared first and used to abbreviate the IRIs.
+/// The statements are grouped by graph and subject,
+/// the blank nodes used once as object are written inline and the well-formed RDF lists as collections.
+pub fn write_pretty(
+    writer: &mut impl Write,
+    prefixes: &BTreeMap<String, String>,
+    quads: &[Quad],
+) -> io::Result<()> {
+    write_prefixes(writer, prefixes)?;
+    PrettyFormatter::new(prefixes, quads).write(writer)
+}
+
+/// Writes the prefix declarations followed by an empty line
+pub fn write_prefixes(
+    writer: &mut impl Write,
+    prefixes: &BTreeMap<String, String>,
+) -> io::Result<()> {
+    for (name, iri) in prefixes {
+        writeln!(writer, "@prefix {name}: <{iri}> .")?;
+    }
+    if !prefixes.is_empty() {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+type Description<'a> = Vec<(NamedNodeRef<'a>, TermRef<'a>)>;
+
+struct PrettyFormatter<'a> {
+    terms: TermFormatter<'a>,
+    /// The graphs with their subjects in order of first appearance, the default graph first
+    graphs: Vec<(GraphNameRef<'a>, Vec<SubjectRef<'a>>)>,
+    descriptions: HashMap<(GraphNameRef<'a>, SubjectRef<'a>), Description<'a>>,
+    /// The blank nodes used exactly once as object and in a single graph
+    inlinable: HashSet<BlankNodeRef<'a>>,
+}
+
+impl<'a> PrettyFormatter<'a> {
+    fn new(prefixes: &'a BTreeMap<String, String>, quads: &'a [Quad]) -> Self {
+        let mut graphs = Vec::<(GraphNameRef<'a>, Vec<SubjectRef<'a>>)>::new();
+        let mut descriptions = HashMap::<_, Description<'a>>::new();
+        let mut object_counts = HashMap::<BlankNodeRef<'a>, usize>::new();
+        // The graph each blank node is used in, None if it is used in multiple graphs, as graph name or in a quoted triple
+        let mut blank_node_graphs = HashMap::new();
+        for quad in quads {
+            let quad = quad.as_ref();
+            let key = (quad.graph_name, quad.subject);
+            if !descriptions.contains_key(&key) {
+                if let Some((_, subjects)) = graphs.iter_mut().find(|(g, _)| *g == quad.graph_name)
+                {
+                    subjects.push(quad.subject);
+                } else {
+                    graphs.push((quad.graph_name, vec![quad.subject]));
+                }
+            }
+            descriptions
+                .entry(key)
+                .or_default()
+                .push((quad.predicate, quad.object));
+            if let GraphNameRef::BlankNode(node) = quad.graph_name {
+                track_blank_node(&mut blank_node_graphs, node, None);
+            }
+            match quad.subject {
+                SubjectRef::NamedNode(_) => (),
+                SubjectRef::BlankNode(node) => {
+                    track_blank_node(&mut blank_node_graphs, node, Some(quad.graph_name));
+                }
+                SubjectRef::Triple(triple) => track_quoted_triple(&mut blank_node_graphs, triple),
+            }
+            match quad.object {
+                TermRef::NamedNode(_) | TermRef::Literal(_) => (),
+                TermRef::BlankNode(node) => {
+                    *object_counts.entry(node).or_insert(0) += 1;
+                    track_blank_node(&mut blank_node_graphs, node, Some(quad.graph_name));
+                }
+                TermRef::Triple(triple) => track_quoted_triple(&mut blank_node_graphs, triple),
+            }
+        }
+        graphs.sort_by_key(|(graph_name, _)| !graph_name.is_default_graph());
+        let inlinable = object_counts
+            .into_iter()
+            .filter(|(node, count)| *count == 1 && blank_node_graphs[node].is_some())
+            .map(|(node, _)| node)
+            .collect();
+        Self {
+            terms: TermFormatter::new(prefixes),
+            graphs,
+            descriptions,
+            inlinable,
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut written = HashSet::new();
+        for (i, (graph_name, subjects)) in self.graphs.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            let indent = match graph_name {
+                GraphNameRef::NamedNode(node) => {
+                    writeln!(writer, "{} {{", self.terms.named_node(*node))?;
+                    "    "
+                }
+                GraphNameRef::BlankNode(node) => {
+                    writeln!(writer, "{node} {{")?;
+                    "    "
+                }
+                GraphNameRef::DefaultGraph => "",
+            };
+            for subject in subjects {
+                if !matches!(subject, SubjectRef::BlankNode(node) if self.inlinable.contains(node))
+                {
+                    self.write_statement(writer, *graph_name, *subject, indent, &mut written)?;
+                }
+            }
+            // The blank nodes only referring to each other are not reachable from the other subjects
+            for subject in subjects {
+                if let SubjectRef::BlankNode(node) = subject {
+                    if self.inlinable.contains(node) && !written.contains(node) {
+                        self.write_statement(writer, *graph_name, *subject, indent, &mut written)?;
+                    }
+                }
+            }
+            if !graph_name.is_default_graph() {
+                writeln!(writer, "}}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_statement(
+        &self,
+        writer: &mut impl Write,
+        graph_name: GraphNameRef<'a>,
+        subject: SubjectRef<'a>,
+        indent: &str,
+        written: &mut HashSet<BlankNodeRef<'a>>,
+    ) -> io::Result<()> {
+        if let SubjectRef::BlankNode(node) = subject {
+            written.insert(node);
+        }
+        write!(writer, "{indent}{}", self.terms.subject(subject))?;
+        let indent = format!("{indent}    ");
+        self.write_predicate_objects(writer, graph_name, subject, Some(&indent), written)?;
+        writeln!(writer, " .")
+    }
+
+    /// Writes the description of the subject, on a single line if there is no indentation
+    fn write_predicate_objects(
+        &self,
+        writer: &mut impl Write,
+        graph_name: GraphNameRef<'a>,
+        subject: SubjectRef<'a>,
+        indent: Option<&str>,
+        written: &mut HashSet<BlankNodeRef<'a>>,
+    ) -> io::Result<()> {
+        let mut predicates = Vec::<(NamedNodeRef<'a>, Vec<TermRef<'a>>)>::new();
+        for (predicate, object) in &self.descriptions[&(graph_name, subject)] {
+            if let Some((_, objects)) = predicates.iter_mut().find(|(p, _)| p == predicate) {
+                objects.push(*object);
+            } else {
+                predicates.push((*predicate, vec![*object]));
+            }
+        }
+        for (i, (predicate, objects)) in predicates.into_iter().enumerate() {
+            if i > 0 {
+                write!(writer, " ;")?;
+            }
+            match indent {
+                Some(indent) if i > 0 => write!(writer, "\n{indent}")?,
+                _ => write!(writer, " ")?,
+            }
+            if predicate == rdf::TYPE {
+                write!(writer, "a")?;
+            } else {
+                write!(writer, "{}", self.terms.named_node(predicate))?;
+            }
+            for (j, object) in objects.into_iter().enumerate() {
+                if j > 0 {
+                    write!(writer, " ,")?;
+                }
+                write!(writer, " ")?;
+                self.write_object(writer, graph_name, object, written)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_object(
+        &self,
+        writer: &mut impl Write,
+        graph_name: GraphNameRef<'a>,
+        object: TermRef<'a>,
+        written: &mut HashSet<BlankNodeRef<'a>>,
+    ) -> io::Result<()> {
+        if let TermRef::BlankNode(node) = object {
+            if self.inlinable.contains(&node) && !written.contains(&node) {
+                if let Some((items, nodes)) = self.collection(graph_name, node, written) {
+                    written.extend(nodes);
+                    write!(writer, "(")?;
+                    for item in items {
+                        write!(writer, " ")?;
+                        self.write_object(writer, graph_name, item, written)?;
+                    }
+                    return write!(writer, " )");
+                }
+                written.insert(node);
+                let subject = SubjectRef::BlankNode(node);
+                if !self.descriptions.contains_key(&(graph_name, subject)) {
+                    return write!(writer, "[]");
+                }
+                write!(writer, "[")?;
+                self.write_predicate_objects(writer, graph_name, subject, None, written)?;
+                return write!(writer, " ]");
+            }
+        }
+        write!(writer, "{}", self.terms.term(object))
+    }
+
+    /// Returns the items and the nodes of the list starting at `head` if it is a well-formed list that could be written inline
+    fn collection(
+        &self,
+        graph_name: GraphNameRef<'a>,
+        head: BlankNodeRef<'a>,
+        written: &HashSet<BlankNodeRef<'a>>,
+    ) -> Option<(Vec<TermRef<'a>>, Vec<BlankNodeRef<'a>>)> {
+        let mut items = Vec::new();
+        let mut nodes = Vec::new();
+        let mut current = head;
+        loop {
+            if !self.inlinable.contains(&current)
+                || written.contains(&current)
+                || nodes.contains(&current)
+            {
+                return None;
+            }
+            let description = self
+                .descriptions
+                .get(&(graph_name, SubjectRef::BlankNode(current)))?;
+            if description.len() != 2 {
+                return None;
+            }
+            let first = description.iter().find(|(p, _)| *p == rdf::FIRST)?.1;
+            let rest = description.iter().find(|(p, _)| *p == rdf::REST)?.1;
+            items.push(first);
+            nodes.push(current);
+            match rest {
+                TermRef::NamedNode(node) if node == rdf::NIL => return Some((items, nodes)),
+                TermRef::BlankNode(node) => current = node,
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Formats the terms in Turtle, abbreviating the IRIs with the prefixes
+pub struct TermFormatter<'a> {
+    prefixes: &'a BTreeMap<String, String>,
+}
+
+impl<'a> TermFormatter<'a> {
+    pub fn new(prefixes: &'a BTreeMap<String, String>) -> Self {
+        Self { prefixes }
+    }
+
+    pub fn subject(&self, subject: SubjectRef<'_>) -> String {
+        match subject {
+            SubjectRef::NamedNode(node) => self.named_node(node),
+            SubjectRef::BlankNode(node) => node.to_string(),
+            SubjectRef::Triple(triple) => self.triple(triple),
+        }
+    }
+
+    pub fn term(&self, term: TermRef<'_>) -> String {
+        match term {
+            TermRef::NamedNode(node) => self.named_node(node),
+            TermRef::BlankNode(node) => node.to_string(),
+            TermRef::Literal(literal) => self.literal(literal),
+            TermRef::Triple(triple) => self.triple(triple),
+        }
+    }
+
+    fn triple(&self, triple: &Triple) -> String {
+        format!(
+            "<< {} {} {} >>",
+            self.subject(triple.subject.as_ref()),
+            self.named_node(triple.predicate.as_ref()),
+            self.term(triple.object.as_ref())
+        )
+    }
+
+    /// Abbreviates the IRI with the longest matching prefix
+    pub fn named_node(&self, node: NamedNodeRef<'_>) -> String {
+        let iri = node.as_str();
+        self.prefixes
+            .iter()
+            .filter(|(_, prefix_iri)| {
+                iri.starts_with(prefix_iri.as_str()) && is_local_name(&iri[prefix_iri.len()..])
+            })
+            .max_by_key(|(_, prefix_iri)| prefix_iri.len())
+            .map_or_else(
+                || node.to_string(),
+                |(name, prefix_iri)| format!("{name}:{}", &iri[prefix_iri.len()..]),
+            )
+    }
+
+    fn literal(&self, literal: LiteralRef<'_>) -> String {
+        let value = literal.value();
+        let datatype = literal.datatype();
+        if literal.is_plain() {
+            literal.to_string()
+        } else if (datatype == xsd::BOOLEAN && matches!(value, "true" | "false"))
+            || (datatype == xsd::INTEGER && is_integer(value))
+            || (datatype == xsd::DECIMAL && is_decimal(value))
+        {
+            value.to_owned()
+        } else {
+            format!(
+                "{}^^{}",
+                LiteralRef::new_simple_literal(value),
+                self.named_node(datatype)
+            )
+        }
+    }
+}
+
+fn track_blank_node<'a>(
+    graphs: &mut HashMap<BlankNodeRef<'a>, Option<GraphNameRef<'a>>>,
+    node: BlankNodeRef<'a>,
+    graph_name: Option<GraphNameRef<'a>>,
+) {
+    let current = graphs.entry(node).or_insert(graph_name);
+    if *current != graph_name {
+        *current = None;
+    }
+}
+
+fn track_quoted_triple<'a>(
+    graphs: &mut HashMap<BlankNodeRef<'a>, Option<GraphNameRef<'a>>>,
+    triple: &'a Triple,
+) {
+    match &triple.subject {
+        Subject::NamedNode(_) => (),
+        Subject::BlankNode(node) => track_blank_node(graphs, node.as_ref(), None),
+        Subject::Triple(triple) => track_quoted_triple(graphs, triple),
+    }
+    match &triple.object {
+        Term::NamedNode(_) | Term::Literal(_) => (),
+        Term::BlankNode(node) => track_blank_node(graphs, node.as_ref(), None),
+        Term::Triple(triple) => track_quoted_triple(graphs, triple),
+    }
+}
+
+/// Conservative check of the Turtle `PN_LOCAL` rule
+fn is_local_name(local: &str) -> bool {
+    let mut chars = local.chars();
+    chars.next().map_or(true, |first| {
+        (first.is_alphanumeric() || matches!(first, '_' | ':'))
+            && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'))
+            && !local.ends_with('.')
+    })
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(&['+', '-'][..]).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_decimal(value: &str) -> bool {
+    let digits = value.strip_prefix(&['+', '-'][..]).unwrap_or(value);
+    digits.split_once('.').map_or(false, |(integer, fraction)| {
+        !fraction.is_empty()
+            && integer.bytes().all(|b| b.is_ascii_digit())
+            && fraction.bytes().all(|b| b.is_ascii_digit())
+    })
+}
@@ -1,15 +1,21 @@
 //! Utilities to read RDF graphs and datasets.
 
+use crate::io::binary::BinaryQuadReader;
+use crate::io::compression::DecompressingReader;
 pub use crate::io::error::{ParseError, SyntaxError};
-use crate::io::{DatasetFormat, GraphFormat};
+use crate::io::n3::N3Parser;
+use crate::io::{Compression, DatasetFormat, GraphFormat};
 use crate::model::*;
 use oxiri::{Iri, IriParseError};
 use rio_api::model as rio;
 use rio_api::parser::{QuadsParser, TriplesParser};
 use rio_turtle::{NQuadsParser, NTriplesParser, TriGParser, TurtleParser};
 use rio_xml::RdfXmlParser;
+use siphasher::sip128::{Hasher128, SipHasher24};
 use std::collections::HashMap;
+use std::hash::Hasher;
 use std::io::BufRead;
+use std::mem::take;
 
 /// Parsers for RDF graph serialization formats.
 ///
@@ -17,6 +23,7 @@ use std::io::BufRead;
 /// * [N-Triples](https://www.w3.org/TR/n-triples/) ([`GraphFormat::NTriples`](super::GraphFormat::NTriples))
 /// * [Turtle](https://www.w3.org/TR/turtle/) ([`GraphFormat::Turtle`](super::GraphFormat::Turtle))
 /// * [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/) ([`GraphFormat::RdfXml`](super::GraphFormat::RdfXml))
+/// * [Notation3](https://w3c.github.io/N3/spec/) ([`GraphFormat::N3`](super::GraphFormat::N3)), only the documents that are RDF graphs
 ///
 /// ```
 /// use oxigraph::io::{GraphFormat, GraphParser};
@@ -31,9 +38,30 @@ use std::io::BufRead;
 ///assert_eq!(triples[0].subject.to_string(), "<http://example.com/s>");
 /// # std::io::Result::Ok(())
 /// ```
+///
+/// The N3 paths are expanded into triples with blank nodes, the formulae are rejected:
+/// ```
+/// use oxigraph::io::{GraphFormat, GraphParser};
+/// use std::io::Cursor;
+///
+/// let file = "@prefix : <http://example.com/> .
+/// :alice!:mother :name \"Eve\" .
+/// :bob is :knows of :alice ; = :robert .";
+///
+/// let parser = GraphParser::from_format(GraphFormat::N3);
+/// let triples = parser.read_triples(Cursor::new(file))?.collect::<Result<Vec<_>,_>>()?;
+/// assert_eq!(triples.len(), 4);
+///
+/// let formula = "{ <http://example.com/s> <http://example.com/p> <http://example.com/o> } => { <http://example.com/s> <http://example.com/q> <http://example.com/o> } .";
+/// assert!(parser.read_triples(Cursor::new(formula))?.next().unwrap().is_err());
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Clone)]
 pub struct GraphParser {
     format: GraphFormat,
     base_iri: Option<Iri<String>>,
+    blank_node_ids: BlankNodeIds,
+    compression: Option<Compression>,
 }
 
 impl GraphParser {
@@ -43,6 +71,8 @@ impl GraphParser {
         Self {
             format,
             base_iri: None,
+            blank_node_ids: BlankNodeIds::Random,
+            compression: None,
         }
     }
 
@@ -67,11 +97,32 @@ impl GraphParser {
         Ok(self)
     }
 
+    /// Derives the blank nodes from their label and `namespace` instead of generating random ones.
+    ///
+    /// It allows to parse a file in several parts while keeping the blank nodes identities.
+    pub(crate) fn with_blank_node_namespace(mut self, namespace: u128) -> Self {
+        self.blank_node_ids = BlankNodeIds::Namespace(namespace);
+        self
+    }
+
+    /// Decompresses the file with the given compression.
+    ///
+    /// By default the gzip and Zstandard files are detected from their first bytes.
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of triples.
-    #[allow(clippy::unnecessary_wraps)]
     pub fn read_triples<R: BufRead>(&self, reader: R) -> Result<TripleReader<R>, ParseError> {
+        let reader = DecompressingReader::new(reader, self.compression)?;
         Ok(TripleReader {
-            mapper: RioMapper::default(),
+            mapper: RioMapper {
+                bnode_map: HashMap::new(),
+                blank_node_ids: self.blank_node_ids,
+            },
             parser: match self.format {
                 GraphFormat::NTriples => TripleReaderKind::NTriples(NTriplesParser::new(reader)),
                 GraphFormat::Turtle => {
@@ -80,10 +131,121 @@ impl GraphParser {
                 GraphFormat::RdfXml => {
                     TripleReaderKind::RdfXml(RdfXmlParser::new(reader, self.base_iri.clone()))
                 }
+                GraphFormat::N3 => {
+                    TripleReaderKind::N3(N3Parser::new(reader, self.base_iri.clone()))
+                }
             },
             buffer: Vec::new(),
         })
     }
+
+    /// Returns a [`PushTripleParser`] to which the bytes of the file are fed incrementally, the triples being sent to `sink`.
+    ///
+    /// It allows to parse a file received in several messages without a [`BufRead`](std::io::BufRead) implementation over the whole file.
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphParser};
+    ///
+    /// let mut parser = GraphParser::from_format(GraphFormat::NTriples).push_triples(Vec::new());
+    /// parser.feed(b"<http://example.com/s> <http://example.com/p> ")?;
+    /// parser.feed(b"<http://example.com/o> .\n<http://example.com/s> <http://example.com/p> _:b .")?;
+    /// let triples = parser.finish()?;
+    ///
+    /// assert_eq!(triples.len(), 2);
+    /// assert_eq!(triples[0].object.to_string(), "<http://example.com/o>");
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn push_triples<S: TripleSink>(&self, sink: S) -> PushTripleParser<S> {
+        PushTripleParser {
+            parser: self.clone(),
+            sink,
+            buffer: Vec::new(),
+            mapper: RioMapper {
+                bnode_map: HashMap::new(),
+                blank_node_ids: self.blank_node_ids,
+            },
+            state: PushState::new(self.format == GraphFormat::NTriples, self.compression),
+        }
+    }
+}
+
+/// A parser to which the bytes of a file are pushed incrementally.
+/// Could be built using [`GraphParser::push_triples`].
+///
+/// The N-Triples files are parsed line by line while they are fed, each triple being sent to the sink as soon as its line is complete.
+/// The other formats and the compressed files are buffered and parsed when [`finish`](PushTripleParser::finish()) is called.
+///
+/// Warning: Do not forget to run the [`finish`](PushTripleParser::finish()) method to parse the end of the file.
+#[must_use]
+pub struct PushTripleParser<S: TripleSink> {
+    parser: GraphParser,
+    sink: S,
+    buffer: Vec<u8>,
+    mapper: RioMapper,
+    state: PushState,
+}
+
+impl<S: TripleSink> PushTripleParser<S> {
+    /// Feeds the next bytes of the file.
+    ///
+    /// The parser should not be used anymore after an error.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ParseError> {
+        self.buffer.extend_from_slice(chunk);
+        if let Some(lines) = self.state.take_lines(&mut self.buffer, false) {
+            self.parse_lines(&lines)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the end of the file and returns the sink.
+    pub fn finish(mut self) -> Result<S, ParseError> {
+        if let Some(lines) = self.state.take_lines(&mut self.buffer, true) {
+            self.parse_lines(&lines)?;
+        } else {
+            for triple in self
+                .parser
+                .read_triples(take(&mut self.buffer).as_slice())?
+            {
+                self.sink.push_triple(triple?);
+            }
+        }
+        Ok(self.sink)
+    }
+
+    fn parse_lines(&mut self, lines: &[u8]) -> Result<(), ParseError> {
+        let (sink, mapper) = (&mut self.sink, &mut self.mapper);
+        NTriplesParser::new(lines).parse_all(&mut |t| {
+            sink.push_triple(mapper.triple(&t));
+            Ok::<_, ParseError>(())
+        })
+    }
+}
+
+/// Receives the triples parsed by a [`PushTripleParser`].
+pub trait TripleSink {
+    /// Receives a parsed triple.
+    fn push_triple(&mut self, triple: Triple);
+}
+
+impl TripleSink for Vec<Triple> {
+    #[inline]
+    fn push_triple(&mut self, triple: Triple) {
+        self.push(triple);
+    }
+}
+
+impl TripleSink for Graph {
+    #[inline]
+    fn push_triple(&mut self, triple: Triple) {
+        self.insert(&triple);
+    }
+}
+
+impl<S: TripleSink + ?Sized> TripleSink for &mut S {
+    #[inline]
+    fn push_triple(&mut self, triple: Triple) {
+        (**self).push_triple(triple);
+    }
 }
 
 /// An iterator yielding read triples.
@@ -111,9 +273,10 @@ pub struct TripleReader<R: BufRead> {
 
 #[allow(clippy::large_enum_variant)]
 enum TripleReaderKind<R: BufRead> {
-    NTriples(NTriplesParser<R>),
-    Turtle(TurtleParser<R>),
-    RdfXml(RdfXmlParser<R>),
+    NTriples(NTriplesParser<DecompressingReader<R>>),
+    Turtle(TurtleParser<DecompressingReader<R>>),
+    RdfXml(RdfXmlParser<DecompressingReader<R>>),
+    N3(N3Parser<DecompressingReader<R>>),
 }
 
 impl<R: BufRead> Iterator for TripleReader<R> {
@@ -135,6 +298,13 @@ impl<R: BufRead> Iterator for TripleReader<R> {
                 TripleReaderKind::RdfXml(parser) => {
                     Self::read(parser, &mut self.buffer, &mut self.mapper)
                 }
+                TripleReaderKind::N3(parser) => {
+                    if parser.is_end() {
+                        None
+                    } else {
+                        Some(parser.parse_step(&mut self.buffer))
+                    }
+                }
             }? {
                 return Some(Err(error));
             }
@@ -169,6 +339,7 @@ impl<R: BufRead> TripleReader<R> {
 /// It currently supports the following formats:
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`DatasetFormat::NQuads`](super::DatasetFormat::NQuads))
 /// * [TriG](https://www.w3.org/TR/trig/) ([`DatasetFormat::TriG`](super::DatasetFormat::TriG))
+/// * The binary format of this library ([`DatasetFormat::Binary`](super::DatasetFormat::Binary))
 ///
 /// ```
 /// use oxigraph::io::{DatasetFormat, DatasetParser};
@@ -183,9 +354,12 @@ impl<R: BufRead> TripleReader<R> {
 ///assert_eq!(quads[0].subject.to_string(), "<http://example.com/s>");
 /// # std::io::Result::Ok(())
 /// ```
+#[derive(Clone)]
 pub struct DatasetParser {
     format: DatasetFormat,
     base_iri: Option<Iri<String>>,
+    blank_node_ids: BlankNodeIds,
+    compression: Option<Compression>,
 }
 
 impl DatasetParser {
@@ -195,6 +369,8 @@ impl DatasetParser {
         Self {
             format,
             base_iri: None,
+            blank_node_ids: BlankNodeIds::Random,
+            compression: None,
         }
     }
 
@@ -219,22 +395,204 @@ impl DatasetParser {
         Ok(self)
     }
 
+    /// Derives the blank nodes from their label and `namespace` instead of generating random ones.
+    ///
+    /// It allows to parse a file in several parts while keeping the blank nodes identities.
+    pub(crate) fn with_blank_node_namespace(mut self, namespace: u128) -> Self {
+        self.blank_node_ids = BlankNodeIds::Namespace(namespace);
+        self
+    }
+
+    /// Uses the blank node labels as the blank node ids instead of generating random ones.
+    ///
+    /// It allows to exchange blank nodes between stores that identify them by their labels.
+    #[cfg(feature = "cluster")]
+    pub(crate) fn with_blank_node_labels(mut self) -> Self {
+        self.blank_node_ids = BlankNodeIds::Labels;
+        self
+    }
+
+    /// Decompresses the file with the given compression.
+    ///
+    /// By default the gzip and Zstandard files are detected from their first bytes.
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of quads.
-    #[allow(clippy::unnecessary_wraps)]
     pub fn read_quads<R: BufRead>(&self, reader: R) -> Result<QuadReader<R>, ParseError> {
+        let reader = DecompressingReader::new(reader, self.compression)?;
+        let mut mapper = RioMapper {
+            bnode_map: HashMap::new(),
+            blank_node_ids: self.blank_node_ids,
+        };
+        let parser = match self.format {
+            DatasetFormat::NQuads => QuadReaderKind::NQuads(NQuadsParser::new(reader)),
+            DatasetFormat::TriG => {
+                QuadReaderKind::TriG(TriGParser::new(reader, self.base_iri.clone()))
+            }
+            DatasetFormat::Binary => QuadReaderKind::Binary(BinaryQuadReader::new(reader, |id| {
+                mapper.blank_node(rio::BlankNode { id })
+            })?),
+        };
         Ok(QuadReader {
-            mapper: RioMapper::default(),
-            parser: match self.format {
-                DatasetFormat::NQuads => QuadReaderKind::NQuads(NQuadsParser::new(reader)),
-                DatasetFormat::TriG => {
-                    QuadReaderKind::TriG(TriGParser::new(reader, self.base_iri.clone()))
-                }
-            },
+            mapper,
+            parser,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Returns a [`PushQuadParser`] to which the bytes of the file are fed incrementally, the quads being sent to `sink`.
+    ///
+    /// It allows to parse a file received in several messages without a [`BufRead`](std::io::BufRead) implementation over the whole file.
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetParser};
+    /// use oxigraph::model::Dataset;
+    ///
+    /// let mut parser = DatasetParser::from_format(DatasetFormat::TriG).push_quads(Dataset::new());
+    /// parser.feed(b"@prefix ex: <http://example.com/> .\nex:g { ex:s ex:p ")?;
+    /// parser.feed(b"ex:o1 , ex:o2 }")?;
+    /// let dataset = parser.finish()?;
+    ///
+    /// assert_eq!(dataset.len(), 2);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn push_quads<S: QuadSink>(&self, sink: S) -> PushQuadParser<S> {
+        PushQuadParser {
+            parser: self.clone(),
+            sink,
             buffer: Vec::new(),
+            mapper: RioMapper {
+                bnode_map: HashMap::new(),
+                blank_node_ids: self.blank_node_ids,
+            },
+            state: PushState::new(self.format == DatasetFormat::NQuads, self.compression),
+        }
+    }
+}
+
+/// A parser to which the bytes of a file are pushed incrementally.
+/// Could be built using [`DatasetParser::push_quads`].
+///
+/// The N-Quads files are parsed line by line while they are fed, each quad being sent to the sink as soon as its line is complete.
+/// The other formats and the compressed files are buffered and parsed when [`finish`](PushQuadParser::finish()) is called.
+///
+/// Warning: Do not forget to run the [`finish`](PushQuadParser::finish()) method to parse the end of the file.
+#[must_use]
+pub struct PushQuadParser<S: QuadSink> {
+    parser: DatasetParser,
+    sink: S,
+    buffer: Vec<u8>,
+    mapper: RioMapper,
+    state: PushState,
+}
+
+impl<S: QuadSink> PushQuadParser<S> {
+    /// Feeds the next bytes of the file.
+    ///
+    /// The parser should not be used anymore after an error.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ParseError> {
+        self.buffer.extend_from_slice(chunk);
+        if let Some(lines) = self.state.take_lines(&mut self.buffer, false) {
+            self.parse_lines(&lines)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the end of the file and returns the sink.
+    pub fn finish(mut self) -> Result<S, ParseError> {
+        if let Some(lines) = self.state.take_lines(&mut self.buffer, true) {
+            self.parse_lines(&lines)?;
+        } else {
+            for quad in self.parser.read_quads(take(&mut self.buffer).as_slice())? {
+                self.sink.push_quad(quad?);
+            }
+        }
+        Ok(self.sink)
+    }
+
+    fn parse_lines(&mut self, lines: &[u8]) -> Result<(), ParseError> {
+        let (sink, mapper) = (&mut self.sink, &mut self.mapper);
+        NQuadsParser::new(lines).parse_all(&mut |q| {
+            sink.push_quad(mapper.quad(&q));
+            Ok::<_, ParseError>(())
         })
     }
 }
 
+/// Receives the quads parsed by a [`PushQuadParser`].
+pub trait QuadSink {
+    /// Receives a parsed quad.
+    fn push_quad(&mut self, quad: Quad);
+}
+
+impl QuadSink for Vec<Quad> {
+    #[inline]
+    fn push_quad(&mut self, quad: Quad) {
+        self.push(quad);
+    }
+}
+
+impl QuadSink for Dataset {
+    #[inline]
+    fn push_quad(&mut self, quad: Quad) {
+        self.insert(&quad);
+    }
+}
+
+impl<S: QuadSink + ?Sized> QuadSink for &mut S {
+    #[inline]
+    fn push_quad(&mut self, quad: Quad) {
+        (**self).push_quad(quad);
+    }
+}
+
+/// How a push parser handles the fed bytes
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum PushState {
+    /// The file might be parsed line by line if it is not compressed
+    Start,
+    /// The complete lines are parsed as soon as they are fed
+    Lines,
+    /// The file is parsed at once at the end
+    Buffered,
+}
+
+impl PushState {
+    fn new(line_based: bool, compression: Option<Compression>) -> Self {
+        if line_based && compression.is_none() {
+            Self::Start
+        } else {
+            Self::Buffered
+        }
+    }
+
+    /// Removes the complete lines from the buffer if the file is parsed line by line
+    fn take_lines(&mut self, buffer: &mut Vec<u8>, end: bool) -> Option<Vec<u8>> {
+        // The compression magic bytes are at most 4 bytes long
+        if *self == Self::Start && (buffer.len() >= 4 || end) {
+            *self = if Compression::from_magic_bytes(buffer).is_some() {
+                Self::Buffered
+            } else {
+                Self::Lines
+            };
+        }
+        if *self != Self::Lines {
+            return None;
+        }
+        let len = if end {
+            buffer.len()
+        } else {
+            buffer.iter().rposition(|b| *b == b'\n')? + 1
+        };
+        Some(buffer.drain(..len).collect())
+    }
+}
+
 /// An iterator yielding read quads.
 /// Could be built using a [`DatasetParser`].
 ///
@@ -259,8 +617,9 @@ pub struct QuadReader<R: BufRead> {
 }
 
 enum QuadReaderKind<R: BufRead> {
-    NQuads(NQuadsParser<R>),
-    TriG(TriGParser<R>),
+    NQuads(NQuadsParser<DecompressingReader<R>>),
+    TriG(TriGParser<DecompressingReader<R>>),
+    Binary(BinaryQuadReader<DecompressingReader<R>>),
 }
 
 impl<R: BufRead> Iterator for QuadReader<R> {
@@ -279,6 +638,7 @@ impl<R: BufRead> Iterator for QuadReader<R> {
                 QuadReaderKind::TriG(parser) => {
                     Self::read(parser, &mut self.buffer, &mut self.mapper)
                 }
+                QuadReaderKind::Binary(reader) => return reader.next(),
             }? {
                 return Some(Err(error));
             }
@@ -311,6 +671,24 @@ impl<R: BufRead> QuadReader<R> {
 #[derive(Default)]
 struct RioMapper {
     bnode_map: HashMap<String, BlankNode>,
+    blank_node_ids: BlankNodeIds,
+}
+
+/// How the ids of the parsed blank nodes are chosen
+#[derive(Clone, Copy)]
+enum BlankNodeIds {
+    Random,
+    /// Derived from the label and a namespace
+    Namespace(u128),
+    /// The label itself
+    #[cfg(feature = "cluster")]
+    Labels,
+}
+
+impl Default for BlankNodeIds {
+    fn default() -> Self {
+        Self::Random
+    }
 }
 
 impl<'a> RioMapper {
@@ -319,9 +697,20 @@ impl<'a> RioMapper {
     }
 
     fn blank_node(&mut self, node: rio::BlankNode<'a>) -> BlankNode {
+        let ids = self.blank_node_ids;
         self.bnode_map
             .entry(node.id.to_owned())
-            .or_insert_with(BlankNode::default)
+            .or_insert_with(|| match ids {
+                BlankNodeIds::Random => BlankNode::default(),
+                BlankNodeIds::Namespace(namespace) => {
+                    let mut hasher = SipHasher24::new();
+                    hasher.write(&namespace.to_be_bytes());
+                    hasher.write(node.id.as_bytes());
+                    BlankNode::new_from_unique_id(hasher.finish128().into())
+                }
+                #[cfg(feature = "cluster")]
+                BlankNodeIds::Labels => BlankNode::new_unchecked(node.id),
+            })
             .clone()
     }
 
@@ -1,10 +1,16 @@
 //! Utilities to write RDF graphs and datasets.
 
+use crate::io::binary::BinaryQuadWriter;
+use crate::io::compression::CompressingWriter;
+use crate::io::pretty::{write_prefixes, write_pretty, TermFormatter};
+use crate::io::Compression;
 use crate::io::{DatasetFormat, GraphFormat};
 use crate::model::*;
+use oxiri::{Iri, IriParseError};
 use rio_api::formatter::TriplesFormatter;
 use rio_api::model as rio;
 use rio_xml::RdfXmlFormatter;
+use std::collections::BTreeMap;
 use std::io::{self, Write};
 
 /// A serializer for RDF graph serialization formats.
@@ -13,6 +19,7 @@ use std::io::{self, Write};
 /// * [N-Triples](https://www.w3.org/TR/n-triples/) ([`GraphFormat::NTriples`](super::GraphFormat::NTriples))
 /// * [Turtle](https://www.w3.org/TR/turtle/) ([`GraphFormat::Turtle`](super::GraphFormat::Turtle))
 /// * [RDF/XML](https://www.w3.org/TR/rdf-syntax-grammar/) ([`GraphFormat::RdfXml`](super::GraphFormat::RdfXml))
+/// * [Notation3](https://w3c.github.io/N3/spec/) ([`GraphFormat::N3`](super::GraphFormat::N3)), written in N-Triples
 ///
 /// ```
 /// use oxigraph::io::{GraphFormat, GraphSerializer};
@@ -32,26 +39,157 @@ use std::io::{self, Write};
 /// ```
 pub struct GraphSerializer {
     format: GraphFormat,
+    prefixes: BTreeMap<String, String>,
+    pretty: bool,
+    compression: Option<Compression>,
 }
 
 impl GraphSerializer {
     /// Builds a serializer for the given format
     #[inline]
     pub fn from_format(format: GraphFormat) -> Self {
-        Self { format }
+        Self {
+            format,
+            prefixes: BTreeMap::new(),
+            pretty: false,
+            compression: None,
+        }
+    }
+
+    /// Adds a prefix to declare and to abbreviate the IRIs with in Turtle.
+    ///
+    /// [`Store::dump_graph`](crate::store::Store::dump_graph) adds the prefixes registered in the store automatically.
+    #[inline]
+    pub fn with_prefix(
+        mut self,
+        prefix_name: impl Into<String>,
+        prefix_iri: impl Into<String>,
+    ) -> Result<Self, IriParseError> {
+        self.prefixes.insert(
+            prefix_name.into(),
+            Iri::parse(prefix_iri.into())?.into_inner(),
+        );
+        Ok(self)
+    }
+
+    pub(crate) fn with_prefixes(mut self, prefixes: Vec<(String, NamedNode)>) -> Self {
+        for (prefix_name, prefix_iri) in prefixes {
+            self.prefixes
+                .entry(prefix_name)
+                .or_insert_with(|| prefix_iri.into_string());
+        }
+        self
+    }
+
+    /// Writes [Turtle](https://www.w3.org/TR/turtle/) with the statements grouped by subject,
+    /// the blank nodes only used once inline and the RDF lists as collections,
+    /// instead of a statement per line.
+    ///
+    /// The triples are buffered until [`TripleWriter::finish`] is called.
+    /// This has no effect on the other formats.
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphSerializer};
+    /// use oxigraph::model::vocab::rdf;
+    /// use oxigraph::model::*;
+    ///
+    /// let s = NamedNodeRef::new("http://example.com/s")?;
+    /// let p = NamedNodeRef::new("http://example.com/p")?;
+    /// let bnode = BlankNode::default();
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = GraphSerializer::from_format(GraphFormat::Turtle)
+    ///     .with_prefix("ex", "http://example.com/")?
+    ///     .pretty()
+    ///     .triple_writer(&mut buffer)?;
+    /// writer.write(TripleRef::new(s, rdf::TYPE, NamedNodeRef::new("http://example.com/C")?))?;
+    /// writer.write(TripleRef::new(s, p, LiteralRef::new_simple_literal("foo")))?;
+    /// writer.write(TripleRef::new(s, p, &bnode))?;
+    /// writer.write(TripleRef::new(&bnode, p, &Literal::from(1)))?;
+    /// writer.finish()?;
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(buffer)?,
+    ///     "@prefix ex: <http://example.com/> .\n\nex:s a ex:C ;\n    ex:p \"foo\" , [ ex:p 1 ] .\n"
+    /// );
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn pretty(mut self) -> Self {
+        self.pretty = true;
+        self
+    }
+
+    /// Compresses the output with the given compression format.
+    ///
+    /// It requires the `compression` feature, [`GraphSerializer::triple_writer`] returns an error otherwise.
+    ///
+    /// ```
+    /// # #[cfg(feature = "compression")] {
+    /// use oxigraph::io::{Compression, GraphFormat, GraphParser, GraphSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let triple = Triple::new(
+    ///     NamedNode::new("http://example.com/s")?,
+    ///     NamedNode::new("http://example.com/p")?,
+    ///     NamedNode::new("http://example.com/o")?,
+    /// );
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = GraphSerializer::from_format(GraphFormat::NTriples)
+    ///     .with_compression(Compression::Gzip)
+    ///     .triple_writer(&mut buffer)?;
+    /// writer.write(&triple)?;
+    /// writer.finish()?;
+    ///
+    /// // The compression is detected by the parser
+    /// let triples = GraphParser::from_format(GraphFormat::NTriples)
+    ///     .read_triples(buffer.as_slice())?
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(triples, vec![triple]);
+    /// # }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
     }
 
     /// Returns a [`TripleWriter`] allowing writing triples into the given [`Write`](std::io::Write) implementation
     pub fn triple_writer<W: Write>(&self, writer: W) -> io::Result<TripleWriter<W>> {
+        let mut writer = CompressingWriter::new(writer, self.compression)?;
         Ok(TripleWriter {
             formatter: match self.format {
-                GraphFormat::NTriples | GraphFormat::Turtle => TripleWriterKind::NTriples(writer),
+                GraphFormat::Turtle if self.pretty => TripleWriterKind::Pretty {
+                    writer,
+                    prefixes: self.prefixes.clone(),
+                    quads: Vec::new(),
+                },
+                GraphFormat::Turtle if !self.prefixes.is_empty() => {
+                    write_prefixes(&mut writer, &self.prefixes)?;
+                    TripleWriterKind::Prefixed {
+                        writer,
+                        prefixes: self.prefixes.clone(),
+                    }
+                }
+                GraphFormat::NTriples | GraphFormat::Turtle | GraphFormat::N3 => {
+                    TripleWriterKind::NTriples(writer)
+                }
                 GraphFormat::RdfXml => TripleWriterKind::RdfXml(RdfXmlFormatter::new(writer)?),
             },
         })
     }
 }
 
+impl From<GraphFormat> for GraphSerializer {
+    #[inline]
+    fn from(format: GraphFormat) -> Self {
+        Self::from_format(format)
+    }
+}
+
 /// Allows writing triples.
 /// Could be built using a [`GraphSerializer`].
 ///
@@ -79,8 +217,17 @@ pub struct TripleWriter<W: Write> {
 }
 
 enum TripleWriterKind<W: Write> {
-    NTriples(W),
-    RdfXml(RdfXmlFormatter<W>),
+    NTriples(CompressingWriter<W>),
+    RdfXml(RdfXmlFormatter<CompressingWriter<W>>),
+    Prefixed {
+        writer: CompressingWriter<W>,
+        prefixes: BTreeMap<String, String>,
+    },
+    Pretty {
+        writer: CompressingWriter<W>,
+        prefixes: BTreeMap<String, String>,
+        quads: Vec<Quad>,
+    },
 }
 
 impl<W: Write> TripleWriter<W> {
@@ -91,6 +238,19 @@ impl<W: Write> TripleWriter<W> {
             TripleWriterKind::NTriples(writer) => {
                 writeln!(writer, "{triple} .")?;
             }
+            TripleWriterKind::Prefixed { writer, prefixes } => {
+                let formatter = TermFormatter::new(prefixes);
+                writeln!(
+                    writer,
+                    "{} {} {} .",
+                    formatter.subject(triple.subject),
+                    formatter.named_node(triple.predicate),
+                    formatter.term(triple.object)
+                )?;
+            }
+            TripleWriterKind::Pretty { quads, .. } => {
+                quads.push(triple.in_graph(GraphNameRef::DefaultGraph).into_owned());
+            }
             TripleWriterKind::RdfXml(formatter) => formatter.format(&rio::Triple {
                 subject: match triple.subject {
                     SubjectRef::NamedNode(node) => rio::NamedNode { iri: node.as_str() }.into(),
@@ -143,8 +303,18 @@ impl<W: Write> TripleWriter<W> {
     /// Writes the last bytes of the file
     pub fn finish(self) -> io::Result<()> {
         match self.formatter {
-            TripleWriterKind::NTriples(mut writer) => writer.flush(),
-            TripleWriterKind::RdfXml(formatter) => formatter.finish()?.flush(), //TODO: remove flush when the next version of Rio is going to be released
+            TripleWriterKind::NTriples(writer) | TripleWriterKind::Prefixed { writer, .. } => {
+                writer.finish()?.flush()
+            }
+            TripleWriterKind::RdfXml(formatter) => formatter.finish()?.finish()?.flush(), //TODO: remove flush when the next version of Rio is going to be released
+            TripleWriterKind::Pretty {
+                mut writer,
+                prefixes,
+                quads,
+            } => {
+                write_pretty(&mut writer, &prefixes, &quads)?;
+                writer.finish()?.flush()
+            }
         }
     }
 }
@@ -154,6 +324,7 @@ impl<W: Write> TripleWriter<W> {
 /// It currently supports the following formats:
 /// * [N-Quads](https://www.w3.org/TR/n-quads/) ([`DatasetFormat::NQuads`](super::DatasetFormat::NQuads))
 /// * [TriG](https://www.w3.org/TR/trig/) ([`DatasetFormat::TriG`](super::DatasetFormat::TriG))
+/// * The binary format of this library ([`DatasetFormat::Binary`](super::DatasetFormat::Binary))
 ///
 /// ```
 /// use oxigraph::io::{DatasetFormat, DatasetSerializer};
@@ -174,27 +345,159 @@ impl<W: Write> TripleWriter<W> {
 /// ```
 pub struct DatasetSerializer {
     format: DatasetFormat,
+    prefixes: BTreeMap<String, String>,
+    pretty: bool,
+    compression: Option<Compression>,
 }
 
 impl DatasetSerializer {
     /// Builds a serializer for the given format
     #[inline]
     pub fn from_format(format: DatasetFormat) -> Self {
-        Self { format }
+        Self {
+            format,
+            prefixes: BTreeMap::new(),
+            pretty: false,
+            compression: None,
+        }
+    }
+
+    /// Adds a prefix to declare and to abbreviate the IRIs with in TriG.
+    ///
+    /// [`Store::dump_dataset`](crate::store::Store::dump_dataset) adds the prefixes registered in the store automatically.
+    #[inline]
+    pub fn with_prefix(
+        mut self,
+        prefix_name: impl Into<String>,
+        prefix_iri: impl Into<String>,
+    ) -> Result<Self, IriParseError> {
+        self.prefixes.insert(
+            prefix_name.into(),
+            Iri::parse(prefix_iri.into())?.into_inner(),
+        );
+        Ok(self)
+    }
+
+    pub(crate) fn with_prefixes(mut self, prefixes: Vec<(String, NamedNode)>) -> Self {
+        for (prefix_name, prefix_iri) in prefixes {
+            self.prefixes
+                .entry(prefix_name)
+                .or_insert_with(|| prefix_iri.into_string());
+        }
+        self
+    }
+
+    /// Writes [TriG](https://www.w3.org/TR/trig/) with the statements grouped by graph and subject,
+    /// the blank nodes only used once inline and the RDF lists as collections,
+    /// instead of a statement per line.
+    ///
+    /// The quads are buffered until [`QuadWriter::finish`] is called.
+    /// This has no effect on the other formats.
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetSerializer};
+    /// use oxigraph::model::vocab::rdf;
+    /// use oxigraph::model::*;
+    ///
+    /// let s = NamedNodeRef::new("http://example.com/s")?;
+    /// let p = NamedNodeRef::new("http://example.com/p")?;
+    /// let g = NamedNodeRef::new("http://example.com/g")?;
+    /// let list = [BlankNode::default(), BlankNode::default()];
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = DatasetSerializer::from_format(DatasetFormat::TriG)
+    ///     .with_prefix("ex", "http://example.com/")?
+    ///     .pretty()
+    ///     .quad_writer(&mut buffer)?;
+    /// writer.write(QuadRef::new(s, p, &list[0], g))?;
+    /// writer.write(QuadRef::new(&list[0], rdf::FIRST, LiteralRef::new_simple_literal("a"), g))?;
+    /// writer.write(QuadRef::new(&list[0], rdf::REST, &list[1], g))?;
+    /// writer.write(QuadRef::new(&list[1], rdf::FIRST, LiteralRef::new_simple_literal("b"), g))?;
+    /// writer.write(QuadRef::new(&list[1], rdf::REST, rdf::NIL, g))?;
+    /// writer.finish()?;
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(buffer)?,
+    ///     "@prefix ex: <http://example.com/> .\n\nex:g {\n    ex:s ex:p ( \"a\" \"b\" ) .\n}\n"
+    /// );
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn pretty(mut self) -> Self {
+        self.pretty = true;
+        self
+    }
+
+    /// Compresses the output with the given compression format.
+    ///
+    /// It requires the `compression` feature, [`DatasetSerializer::quad_writer`] returns an error otherwise.
+    ///
+    /// ```
+    /// # #[cfg(feature = "compression")] {
+    /// use oxigraph::io::{Compression, DatasetFormat, DatasetParser, DatasetSerializer};
+    /// use oxigraph::model::*;
+    ///
+    /// let quad = Quad::new(
+    ///     NamedNode::new("http://example.com/s")?,
+    ///     NamedNode::new("http://example.com/p")?,
+    ///     NamedNode::new("http://example.com/o")?,
+    ///     NamedNode::new("http://example.com/g")?,
+    /// );
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = DatasetSerializer::from_format(DatasetFormat::NQuads)
+    ///     .with_compression(Compression::Zstd)
+    ///     .quad_writer(&mut buffer)?;
+    /// writer.write(&quad)?;
+    /// writer.finish()?;
+    ///
+    /// // The compression is detected by the parser
+    /// let quads = DatasetParser::from_format(DatasetFormat::NQuads)
+    ///     .read_quads(buffer.as_slice())?
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(quads, vec![quad]);
+    /// # }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
     }
 
     /// Returns a [`QuadWriter`] allowing writing triples into the given [`Write`](std::io::Write) implementation
-    #[allow(clippy::unnecessary_wraps)]
     pub fn quad_writer<W: Write>(&self, writer: W) -> io::Result<QuadWriter<W>> {
+        let mut writer = CompressingWriter::new(writer, self.compression)?;
         Ok(QuadWriter {
             formatter: match self.format {
                 DatasetFormat::NQuads => QuadWriterKind::NQuads(writer),
+                DatasetFormat::TriG if self.pretty => QuadWriterKind::Pretty {
+                    writer,
+                    prefixes: self.prefixes.clone(),
+                    quads: Vec::new(),
+                },
+                DatasetFormat::TriG if !self.prefixes.is_empty() => {
+                    write_prefixes(&mut writer, &self.prefixes)?;
+                    QuadWriterKind::Prefixed {
+                        writer,
+                        prefixes: self.prefixes.clone(),
+                    }
+                }
                 DatasetFormat::TriG => QuadWriterKind::TriG(writer),
+                DatasetFormat::Binary => QuadWriterKind::Binary(BinaryQuadWriter::new(writer)),
             },
         })
     }
 }
 
+impl From<DatasetFormat> for DatasetSerializer {
+    #[inline]
+    fn from(format: DatasetFormat) -> Self {
+        Self::from_format(format)
+    }
+}
+
 /// Allows writing triples.
 /// Could be built using a [`DatasetSerializer`].
 ///
@@ -223,8 +526,18 @@ pub struct QuadWriter<W: Write> {
 }
 
 enum QuadWriterKind<W: Write> {
-    NQuads(W),
-    TriG(W),
+    NQuads(CompressingWriter<W>),
+    TriG(CompressingWriter<W>),
+    Binary(BinaryQuadWriter<CompressingWriter<W>>),
+    Prefixed {
+        writer: CompressingWriter<W>,
+        prefixes: BTreeMap<String, String>,
+    },
+    Pretty {
+        writer: CompressingWriter<W>,
+        prefixes: BTreeMap<String, String>,
+        quads: Vec<Quad>,
+    },
 }
 
 impl<W: Write> QuadWriter<W> {
@@ -247,15 +560,49 @@ impl<W: Write> QuadWriter<W> {
                     )
                 }?;
             }
+            QuadWriterKind::Prefixed { writer, prefixes } => {
+                let formatter = TermFormatter::new(prefixes);
+                let triple = format!(
+                    "{} {} {}",
+                    formatter.subject(quad.subject),
+                    formatter.named_node(quad.predicate),
+                    formatter.term(quad.object)
+                );
+                match quad.graph_name {
+                    GraphNameRef::NamedNode(graph_name) => {
+                        writeln!(
+                            writer,
+                            "{} {{ {triple} }}",
+                            formatter.named_node(graph_name)
+                        )
+                    }
+                    GraphNameRef::BlankNode(graph_name) => {
+                        writeln!(writer, "{graph_name} {{ {triple} }}")
+                    }
+                    GraphNameRef::DefaultGraph => writeln!(writer, "{triple} ."),
+                }?;
+            }
+            QuadWriterKind::Pretty { quads, .. } => quads.push(quad.into_owned()),
+            QuadWriterKind::Binary(writer) => writer.write(quad),
         }
         Ok(())
     }
 
     /// Writes the last bytes of the file
-    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
     pub fn finish(self) -> io::Result<()> {
         match self.formatter {
-            QuadWriterKind::NQuads(mut writer) | QuadWriterKind::TriG(mut writer) => writer.flush(),
+            QuadWriterKind::NQuads(writer)
+            | QuadWriterKind::TriG(writer)
+            | QuadWriterKind::Prefixed { writer, .. } => writer.finish()?.flush(),
+            QuadWriterKind::Binary(writer) => writer.finish()?.finish()?.flush(),
+            QuadWriterKind::Pretty {
+                mut writer,
+                prefixes,
+                quads,
+            } => {
+                write_pretty(&mut writer, &prefixes, &quads)?;
+                writer.finish()?.flush()
+            }
         }
     }
 }
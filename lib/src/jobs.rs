@@ -0,0 +1,1260 @@
+//! Long running jobs split over several calls.
+//!
+//! The size and the number of instructions of a canister message are bounded,
+//! so big files can not be loaded with a single call to [`Store::load_dataset`].
+//! [`ImportJobs`] allows to send them in chunks and to follow the progress of the import
+//! and [`ExportJobs`] allows to download a dump chunk by chunk.
+//!
+//! The state of the jobs is persisted in the store, so they survive canister upgrades as long as the store data does.
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::io::GraphFormat;
+//! use oxigraph::jobs::{ExportJobs, ImportJobs, ImportOptions, JobStatus};
+//! use oxigraph::store::Store;
+//!
+//! let store = Store::new()?;
+//! let jobs = ImportJobs::new(store.clone());
+//! let job = jobs.start_import(GraphFormat::NTriples, ImportOptions::default())?;
+//! jobs.append_chunk(job, b"<http://example.com/s> <http://example.com/p> <http://exam")?;
+//! jobs.append_chunk(job, b"ple.com/o> .\n<http://example.com/s> <http://example.com/p> \"o\" .\n")?;
+//! let progress = jobs.finish(job)?;
+//! assert_eq!(progress.status(), JobStatus::Finished);
+//! assert_eq!(progress.quads_inserted(), 2);
+//! assert_eq!(store.len()?, 2);
+//!
+//! let exports = ExportJobs::new(store);
+//! let job = exports.start_export(GraphFormat::NTriples, None)?;
+//! let mut manifest = exports.continue_export(job)?;
+//! while manifest.status() == JobStatus::Running {
+//!     manifest = exports.continue_export(job)?;
+//! }
+//! assert_eq!(manifest.status(), JobStatus::Finished);
+//! let mut dump = Vec::new();
+//! for i in 0..manifest.chunk_count() {
+//!     dump.extend(exports.get_chunk(job, i)?.unwrap());
+//! }
+//! assert_eq!(dump.len() as u64, manifest.total_size());
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::io::read::ParseError;
+use crate::io::{
+    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+};
+use crate::model::*;
+use crate::store::{CorruptionError, LoaderError, StorageError, Store, Transaction};
+use oxiri::{Iri, IriParseError};
+use rand::random;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::{fmt, io};
+
+/// Number of syntax errors kept in the progress of each import.
+const MAX_RECORDED_ERRORS: usize = 16;
+const JOB_COUNTER_KEY: &[u8] = b"job_counter";
+const IMPORT_JOB_PREFIX: &[u8] = b"import_job/";
+const IMPORT_CHUNK_PREFIX: &[u8] = b"import_chunk/";
+const EXPORT_JOB_PREFIX: &[u8] = b"export_job/";
+/// Default size of the export chunks, small enough to fit in a canister reply.
+const DEFAULT_EXPORT_CHUNK_SIZE: usize = 1_000_000;
+/// Default number of export chunks built by each call.
+const DEFAULT_EXPORT_CHUNKS_PER_CALL: usize = 4;
+/// Number of quads read at once by the exports.
+const EXPORT_BATCH_SIZE: usize = 1000;
+
+/// The identifier of a job.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct JobId(u64);
+
+impl fmt::Display for JobId {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<u64> for JobId {
+    #[inline]
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<JobId> for u64 {
+    #[inline]
+    fn from(id: JobId) -> Self {
+        id.0
+    }
+}
+
+/// The status of a job.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub enum JobStatus {
+    /// The job is waiting for more calls.
+    Running,
+    /// The job has succeeded.
+    Finished,
+    /// The job has been stopped by an error.
+    Failed,
+}
+
+/// A serialization format accepted by the jobs.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub enum JobFormat {
+    /// A graph format. The triples are put in the target graph.
+    Graph(GraphFormat),
+    /// A dataset format.
+    Dataset(DatasetFormat),
+}
+
+impl JobFormat {
+    /// Returns if the format could be processed line by line.
+    fn is_line_based(self) -> bool {
+        matches!(
+            self,
+            Self::Graph(GraphFormat::NTriples) | Self::Dataset(DatasetFormat::NQuads)
+        )
+    }
+
+    /// Returns if the serializations of the statements could be concatenated.
+    fn is_streamable(self) -> bool {
+        !matches!(
+            self,
+            Self::Graph(GraphFormat::RdfXml) | Self::Dataset(DatasetFormat::Binary)
+        )
+    }
+}
+
+impl From<GraphFormat> for JobFormat {
+    #[inline]
+    fn from(format: GraphFormat) -> Self {
+        Self::Graph(format)
+    }
+}
+
+impl From<DatasetFormat> for JobFormat {
+    #[inline]
+    fn from(format: DatasetFormat) -> Self {
+        Self::Dataset(format)
+    }
+}
+
+/// Options of an import job.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ImportOptions {
+    base_iri: Option<String>,
+    to_graph_name: GraphName,
+}
+
+impl Default for ImportOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            base_iri: None,
+            to_graph_name: GraphName::DefaultGraph,
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Provides an IRI that could be used to resolve the file relative IRIs.
+    #[inline]
+    pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
+        self.base_iri = Some(Iri::parse(base_iri.into())?.into_inner());
+        Ok(self)
+    }
+
+    /// Sets the graph in which the triples of a graph format and the default graph quads of a dataset format are loaded.
+    ///
+    /// By default the default graph is used.
+    #[inline]
+    #[must_use]
+    pub fn with_to_graph_name(mut self, to_graph_name: impl Into<GraphName>) -> Self {
+        self.to_graph_name = to_graph_name.into();
+        self
+    }
+}
+
+/// The progress of an import job.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ImportProgress {
+    status: JobStatus,
+    bytes_received: u64,
+    bytes_parsed: u64,
+    quads_inserted: u64,
+    error_count: u64,
+    errors: Vec<String>,
+}
+
+impl ImportProgress {
+    /// The status of the job.
+    #[inline]
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    /// The number of bytes received using [`ImportJobs::append_chunk`].
+    #[inline]
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// The number of bytes already parsed.
+    #[inline]
+    pub fn bytes_parsed(&self) -> u64 {
+        self.bytes_parsed
+    }
+
+    /// The number of quads already inserted into the store.
+    #[inline]
+    pub fn quads_inserted(&self) -> u64 {
+        self.quads_inserted
+    }
+
+    /// The number of errors raised while parsing.
+    #[inline]
+    pub fn error_count(&self) -> u64 {
+        self.error_count
+    }
+
+    /// The first errors raised while parsing.
+    #[inline]
+    pub fn errors(&self) -> impl Iterator<Item = &str> {
+        self.errors.iter().map(String::as_str)
+    }
+
+    fn record_error(&mut self, error: String) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_RECORDED_ERRORS {
+            self.errors.push(error);
+        }
+    }
+}
+
+struct ImportJob {
+    format: JobFormat,
+    options: ImportOptions,
+    blank_node_namespace: u128,
+    lines_parsed: u64,
+    chunk_count: u64,
+    progress: ImportProgress,
+    pending: Vec<u8>,
+}
+
+/// Manages the import jobs of a [`Store`].
+///
+/// The line based formats ([N-Triples](https://www.w3.org/TR/n-triples/) and [N-Quads](https://www.w3.org/TR/n-quads/))
+/// are parsed and inserted as soon as complete lines are received, only the incomplete last line being kept in the job.
+/// Their invalid lines are recorded as errors and skipped.
+/// The chunks of the other formats are stored as they are received and loaded atomically by [`finish`](Self::finish),
+/// an invalid file making the job fail.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct ImportJobs {
+    store: Store,
+}
+
+impl ImportJobs {
+    /// Builds a job manager for the given store.
+    #[inline]
+    pub fn new(store: Store) -> Self {
+        Self { store }
+    }
+
+    /// Starts a new import job.
+    pub fn start_import(
+        &self,
+        format: impl Into<JobFormat>,
+        options: ImportOptions,
+    ) -> Result<JobId, StorageError> {
+        let job = ImportJob {
+            format: format.into(),
+            options,
+            blank_node_namespace: random(),
+            lines_parsed: 0,
+            chunk_count: 0,
+            progress: ImportProgress {
+                status: JobStatus::Running,
+                bytes_received: 0,
+                bytes_parsed: 0,
+                quads_inserted: 0,
+                error_count: 0,
+                errors: Vec::new(),
+            },
+            pending: Vec::new(),
+        };
+        let encoded = encode_import_job(&job);
+        self.store.transaction(|mut t| {
+            let id = allocate_job_id(&t)?;
+            t.insert_metadata(&import_job_key(id), &encoded)?;
+            t.insert_metadata(JOB_COUNTER_KEY, &(u64::from(id) + 1).to_be_bytes())?;
+            Ok(id)
+        })
+    }
+
+    /// Appends a chunk of the file to a running job.
+    ///
+    /// The chunk is parsed with the format of the job:
+    /// ```
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::jobs::{ImportJobs, ImportOptions};
+    /// use oxigraph::store::Store;
+    ///
+    /// let jobs = ImportJobs::new(Store::new()?);
+    /// let job = jobs.start_import(GraphFormat::NTriples, ImportOptions::default())?;
+    /// let progress = jobs.append_chunk(
+    ///     job,
+    ///     b"<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g> .\n",
+    /// )?;
+    /// assert_eq!(progress.quads_inserted(), 0);
+    /// assert_eq!(progress.error_count(), 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn append_chunk(&self, id: JobId, chunk: &[u8]) -> Result<ImportProgress, JobError> {
+        let mut job = self.running_job(id)?;
+        job.progress.bytes_received += chunk.len() as u64;
+        if job.format.is_line_based() {
+            job.pending.extend_from_slice(chunk);
+            let mut quads = Vec::new();
+            if let Some(end) = job.pending.iter().rposition(|b| *b == b'\n') {
+                let lines = job.pending.drain(..=end).collect::<Vec<_>>();
+                parse_lines(&mut job, &lines, &mut quads);
+            }
+            self.save(id, &job, &quads)?;
+        } else {
+            let chunk_key = import_chunk_key(id, job.chunk_count);
+            job.chunk_count += 1;
+            let encoded = encode_import_job(&job);
+            self.store.transaction(|mut t| {
+                t.insert_metadata(&chunk_key, chunk)?;
+                t.insert_metadata(&import_job_key(id), &encoded)
+            })?;
+        }
+        Ok(job.progress)
+    }
+
+    /// Parses the remaining data of a running job and marks it as finished.
+    pub fn finish(&self, id: JobId) -> Result<ImportProgress, JobError> {
+        let mut job = self.running_job(id)?;
+        let mut quads = Vec::new();
+        if job.format.is_line_based() {
+            let pending = std::mem::take(&mut job.pending);
+            parse_lines(&mut job, &pending, &mut quads);
+            job.progress.status = JobStatus::Finished;
+        } else {
+            let mut pending = Vec::new();
+            for n in 0..job.chunk_count {
+                let chunk = self
+                    .store
+                    .metadata(&import_chunk_key(id, n))?
+                    .ok_or_else(|| {
+                        StorageError::from(CorruptionError::msg("An import job chunk is missing"))
+                    })?;
+                pending.extend_from_slice(&chunk);
+            }
+            match parse_file(&job, &pending) {
+                Ok(parsed) => {
+                    job.progress.bytes_parsed += pending.len() as u64;
+                    job.progress.quads_inserted += parsed.len() as u64;
+                    quads = parsed;
+                    job.progress.status = JobStatus::Finished;
+                }
+                Err(error) => {
+                    job.progress.record_error(error.to_string());
+                    job.progress.status = JobStatus::Failed;
+                }
+            }
+        }
+        self.save(id, &job, &quads)?;
+        Ok(job.progress)
+    }
+
+    /// Returns the progress of a job.
+    pub fn progress(&self, id: JobId) -> Result<Option<ImportProgress>, StorageError> {
+        Ok(self.job(id)?.map(|job| job.progress))
+    }
+
+    /// Returns all the import jobs with their progress.
+    pub fn jobs(&self) -> Result<Vec<(JobId, ImportProgress)>, StorageError> {
+        self.store
+            .metadata_with_prefix(IMPORT_JOB_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| {
+                Ok((
+                    decode_job_id(&key[IMPORT_JOB_PREFIX.len()..])?,
+                    decode_import_job(&value)?.progress,
+                ))
+            })
+            .collect()
+    }
+
+    /// Removes a job, its progress and its received chunks.
+    ///
+    /// The already inserted quads are kept in the store.
+    ///
+    /// Returns `true` if the job existed.
+    pub fn remove(&self, id: JobId) -> Result<bool, StorageError> {
+        let key = import_job_key(id);
+        self.store.transaction(|mut t| {
+            let job = if let Some(value) = t.metadata(&key)? {
+                decode_import_job(&value)?
+            } else {
+                return Ok(false);
+            };
+            for n in 0..job.chunk_count {
+                t.remove_metadata(&import_chunk_key(id, n))?;
+            }
+            t.remove_metadata(&key)?;
+            Ok(true)
+        })
+    }
+
+    fn job(&self, id: JobId) -> Result<Option<ImportJob>, StorageError> {
+        self.store
+            .metadata(&import_job_key(id))?
+            .map(|value| decode_import_job(&value))
+            .transpose()
+    }
+
+    fn running_job(&self, id: JobId) -> Result<ImportJob, JobError> {
+        let job = self.job(id)?.ok_or(JobError::UnknownJob(id))?;
+        if job.progress.status != JobStatus::Running {
+            return Err(JobError::NotRunning(id));
+        }
+        Ok(job)
+    }
+
+    fn save(&self, id: JobId, job: &ImportJob, quads: &[Quad]) -> Result<(), StorageError> {
+        let key = import_job_key(id);
+        let encoded = encode_import_job(job);
+        self.store.transaction(|mut t| {
+            for quad in quads {
+                t.insert(quad)?;
+            }
+            if job.progress.status != JobStatus::Running {
+                for n in 0..job.chunk_count {
+                    t.remove_metadata(&import_chunk_key(id, n))?;
+                }
+            }
+            t.insert_metadata(&key, &encoded)
+        })
+    }
+}
+
+/// The description of a dump built by an export job.
+///
+/// It is complete once the job is [finished](JobStatus::Finished).
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ExportManifest {
+    format: JobFormat,
+    status: JobStatus,
+    total_size: u64,
+    chunk_checksums: Vec<[u8; 32]>,
+}
+
+impl ExportManifest {
+    /// The format of the dump.
+    #[inline]
+    pub fn format(&self) -> JobFormat {
+        self.format
+    }
+
+    /// The status of the job.
+    #[inline]
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    /// The size of the dump in bytes.
+    #[inline]
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// The number of chunks.
+    #[inline]
+    pub fn chunk_count(&self) -> u64 {
+        self.chunk_checksums.len() as u64
+    }
+
+    /// The hex encoded SHA-256 checksum of the concatenation of the chunk checksums.
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.chunk_checksums.concat()))
+    }
+
+    /// The hex encoded SHA-256 checksum of the chunk `n`.
+    #[inline]
+    pub fn chunk_checksum(&self, n: u64) -> Option<String> {
+        Some(hex::encode(
+            self.chunk_checksums.get(usize::try_from(n).ok()?)?,
+        ))
+    }
+}
+
+struct ExportJob {
+    graph_name: Option<GraphName>,
+    dataset_version: u64,
+    /// The storage position of the first statement not in a chunk yet
+    next_position: Vec<u8>,
+    /// The storage position of the first statement of each chunk
+    chunk_positions: Vec<Vec<u8>>,
+    manifest: ExportManifest,
+}
+
+/// Manages the export jobs of a [`Store`].
+///
+/// [`start_export`](Self::start_export) records the exported graph and the current [dataset version](Store::dataset_version).
+/// The dump is then split into chunks by [`continue_export`](Self::continue_export), a few chunks per call,
+/// only their boundaries and checksums being kept in the job manifest.
+/// Each chunk is serialized again when it is retrieved using [`get_chunk`](Self::get_chunk),
+/// starting with a lookup of the storage position of its first statement.
+/// Retrieving a chunk does not write anything, so it could be done from a query call.
+///
+/// The state of the jobs is not part of the dataset: the exports can run while the store is
+/// [read-only](Store::set_read_only) or in maintenance mode.
+///
+/// The job fails if the store content is modified before the dump is fully retrieved.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct ExportJobs {
+    store: Store,
+    chunk_size: usize,
+    chunks_per_call: usize,
+}
+
+impl ExportJobs {
+    /// Builds a job manager for the given store.
+    #[inline]
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            chunk_size: DEFAULT_EXPORT_CHUNK_SIZE,
+            chunks_per_call: DEFAULT_EXPORT_CHUNKS_PER_CALL,
+        }
+    }
+
+    /// Sets the maximal size of the chunks in bytes.
+    ///
+    /// A chunk exceeds it only if a single statement does.
+    ///
+    /// By default 1MB.
+    #[inline]
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sets the maximal number of chunks built by each call to [`continue_export`](Self::continue_export).
+    ///
+    /// By default 4.
+    #[inline]
+    #[must_use]
+    pub fn with_chunks_per_call(mut self, chunks_per_call: usize) -> Self {
+        self.chunks_per_call = chunks_per_call.max(1);
+        self
+    }
+
+    /// Starts a new export job.
+    ///
+    /// With a graph format, the triples of the given graph are exported (by default the default graph).
+    /// With a dataset format, the quads of the given graph are exported (by default all the quads).
+    ///
+    /// The formats that can not be split between statements, [RDF/XML](GraphFormat::RdfXml)
+    /// and the [binary format](DatasetFormat::Binary), are not supported.
+    pub fn start_export(
+        &self,
+        format: impl Into<JobFormat>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Result<JobId, JobError> {
+        let format = format.into();
+        if !format.is_streamable() {
+            return Err(JobError::UnsupportedFormat(format));
+        }
+        let job = ExportJob {
+            graph_name: match format {
+                JobFormat::Graph(_) => Some(
+                    graph_name
+                        .unwrap_or(GraphNameRef::DefaultGraph)
+                        .into_owned(),
+                ),
+                JobFormat::Dataset(_) => graph_name.map(GraphNameRef::into_owned),
+            },
+            dataset_version: self.store.dataset_version()?,
+            next_position: Vec::new(),
+            chunk_positions: Vec::new(),
+            manifest: ExportManifest {
+                format,
+                status: JobStatus::Running,
+                total_size: 0,
+                chunk_checksums: Vec::new(),
+            },
+        };
+        let id = if let Some(value) = self.store.metadata(JOB_COUNTER_KEY)? {
+            decode_job_id(&value)?
+        } else {
+            JobId(0)
+        };
+        self.store
+            .set_metadata(JOB_COUNTER_KEY, Some(&(id.0 + 1).to_be_bytes()))?;
+        self.store
+            .set_metadata(&export_job_key(id), Some(&encode_export_job(&job)))?;
+        Ok(id)
+    }
+
+    /// Builds the next chunks of a running export job and returns its updated manifest.
+    ///
+    /// The job is finished once all the statements are in a chunk
+    /// and fails if the store content has been modified since the job started.
+    ///
+    /// The statements of each call are read from the position in the storage indexes where the previous call stopped.
+    ///
+    /// ```
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::jobs::{ExportJobs, JobError, JobStatus};
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let store = Store::new()?;
+    /// for i in 0..10 {
+    ///     let ex = NamedNode::new(format!("http://example.com/{i}"))?;
+    ///     store.insert(QuadRef::new(&ex, &ex, &ex, GraphNameRef::DefaultGraph))?;
+    /// }
+    /// store.set_read_only(true)?;
+    /// let exports = ExportJobs::new(store).with_chunk_size(1).with_chunks_per_call(3);
+    /// let job = exports.start_export(GraphFormat::NTriples, None)?;
+    /// let mut manifest = exports.continue_export(job)?;
+    /// assert_eq!(manifest.chunk_count(), 3);
+    /// while manifest.status() == JobStatus::Running {
+    ///     manifest = exports.continue_export(job)?;
+    /// }
+    /// assert_eq!(manifest.chunk_count(), 10);
+    /// let mut chunks = (0..10)
+    ///     .map(|i| Ok(exports.get_chunk(job, i)?.unwrap()))
+    ///     .collect::<Result<Vec<_>, JobError>>()?;
+    /// chunks.sort();
+    /// chunks.dedup();
+    /// assert_eq!(chunks.len(), 10);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn continue_export(&self, id: JobId) -> Result<ExportManifest, JobError> {
+        let mut job = self.job(id)?.ok_or(JobError::UnknownJob(id))?;
+        if job.manifest.status != JobStatus::Running {
+            return Err(JobError::NotRunning(id));
+        }
+        if self.store.dataset_version()? == job.dataset_version {
+            let mut statements = self.statements(&job, &job.next_position).peekable();
+            for _ in 0..self.chunks_per_call {
+                let mut chunk = Vec::new();
+                let mut start = None;
+                while let Some(statement) = statements.next_if(|statement| {
+                    chunk.is_empty()
+                        || statement.as_ref().map_or(true, |(_, statement)| {
+                            chunk.len() + statement.len() <= self.chunk_size
+                        })
+                }) {
+                    let (position, statement) = statement?;
+                    start.get_or_insert(position);
+                    chunk.extend_from_slice(&statement);
+                }
+                let start = if let Some(start) = start {
+                    start
+                } else {
+                    break;
+                };
+                job.chunk_positions.push(start);
+                job.manifest.total_size += chunk.len() as u64;
+                job.manifest
+                    .chunk_checksums
+                    .push(Sha256::digest(&chunk).into());
+            }
+            match statements.next() {
+                Some(statement) => job.next_position = statement?.0,
+                None => job.manifest.status = JobStatus::Finished,
+            }
+        } else {
+            job.manifest.status = JobStatus::Failed;
+        }
+        self.store
+            .set_metadata(&export_job_key(id), Some(&encode_export_job(&job)))?;
+        Ok(job.manifest)
+    }
+
+    /// Returns the manifest of an export job.
+    pub fn manifest(&self, id: JobId) -> Result<Option<ExportManifest>, StorageError> {
+        Ok(self.job(id)?.map(|job| job.manifest))
+    }
+
+    /// Returns the chunk `n` of an export job.
+    ///
+    /// Fails if the store content has been modified since the job started.
+    ///
+    /// ```
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::jobs::{ExportJobs, JobError};
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// let exports = ExportJobs::new(store.clone());
+    /// let job = exports.start_export(DatasetFormat::NQuads, None)?;
+    /// exports.continue_export(job)?;
+    /// assert_eq!(
+    ///     exports.get_chunk(job, 0)?.unwrap(),
+    ///     b"<http://example.com> <http://example.com> <http://example.com> .\n"
+    /// );
+    ///
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// assert!(matches!(exports.get_chunk(job, 0), Err(JobError::Outdated(_))));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn get_chunk(&self, id: JobId, n: u64) -> Result<Option<Vec<u8>>, JobError> {
+        let job = if let Some(job) = self.job(id)? {
+            job
+        } else {
+            return Ok(None);
+        };
+        let index = if let Ok(index) = usize::try_from(n) {
+            index
+        } else {
+            return Ok(None);
+        };
+        let start = if let Some(start) = job.chunk_positions.get(index) {
+            start
+        } else {
+            return Ok(None);
+        };
+        // The last chunk of a finished job goes until the end
+        let end =
+            job.chunk_positions
+                .get(index + 1)
+                .or(if job.manifest.status == JobStatus::Finished {
+                    None
+                } else {
+                    Some(&job.next_position)
+                });
+        if self.store.dataset_version()? != job.dataset_version {
+            return Err(JobError::Outdated(id));
+        }
+        let mut chunk = Vec::new();
+        for statement in self.statements(&job, start) {
+            let (position, statement) = statement?;
+            if end.map_or(false, |end| position >= *end) {
+                break;
+            }
+            chunk.extend_from_slice(&statement);
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Returns all the export jobs with their manifest.
+    pub fn jobs(&self) -> Result<Vec<(JobId, ExportManifest)>, StorageError> {
+        self.store
+            .metadata_with_prefix(EXPORT_JOB_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| {
+                Ok((
+                    decode_job_id(&key[EXPORT_JOB_PREFIX.len()..])?,
+                    decode_export_job(&value)?.manifest,
+                ))
+            })
+            .collect()
+    }
+
+    /// Removes a job.
+    ///
+    /// Returns `true` if the job existed.
+    pub fn remove(&self, id: JobId) -> Result<bool, StorageError> {
+        let key = export_job_key(id);
+        if self.store.metadata(&key)?.is_none() {
+            return Ok(false);
+        }
+        self.store.set_metadata(&key, None)?;
+        Ok(true)
+    }
+
+    fn job(&self, id: JobId) -> Result<Option<ExportJob>, StorageError> {
+        self.store
+            .metadata(&export_job_key(id))?
+            .map(|value| decode_export_job(&value))
+            .transpose()
+    }
+
+    /// Serializes one by one the exported statements from the storage position `from`, each with its position.
+    fn statements(&self, job: &ExportJob, from: &[u8]) -> ExportStatements {
+        ExportStatements {
+            store: self.store.clone(),
+            graph_name: job.graph_name.clone(),
+            format: job.manifest.format,
+            batch: Vec::new().into_iter(),
+            next_position: Some(from.to_vec()),
+        }
+    }
+}
+
+/// Reads the exported quads by batches with range lookups from a storage position and serializes them.
+struct ExportStatements {
+    store: Store,
+    graph_name: Option<GraphName>,
+    format: JobFormat,
+    batch: std::vec::IntoIter<(Vec<u8>, Quad)>,
+    /// The position to read the next batch from, `None` if all the quads have been read
+    next_position: Option<Vec<u8>>,
+}
+
+impl Iterator for ExportStatements {
+    type Item = Result<(Vec<u8>, Vec<u8>), JobError>;
+
+    fn next(&mut self) -> Option<Result<(Vec<u8>, Vec<u8>), JobError>> {
+        loop {
+            if let Some((position, quad)) = self.batch.next() {
+                return Some(
+                    serialize_statement(self.format, quad.as_ref())
+                        .map(|statement| (position, statement))
+                        .map_err(JobError::from),
+                );
+            }
+            let from = self.next_position.take()?;
+            let quads = match self.store.quads_from_position(
+                self.graph_name.as_ref().map(GraphName::as_ref),
+                &from,
+                EXPORT_BATCH_SIZE,
+            ) {
+                Ok(quads) => quads,
+                Err(error) => return Some(Err(error.into())),
+            };
+            if quads.len() == EXPORT_BATCH_SIZE {
+                self.next_position = quads.last().map(|(position, _)| {
+                    let mut next = position.clone();
+                    next.push(0);
+                    next
+                });
+            }
+            self.batch = quads.into_iter();
+        }
+    }
+}
+
+/// Parses complete N-Triples or N-Quads lines, recording and skipping the invalid ones.
+fn parse_lines(job: &mut ImportJob, data: &[u8], quads: &mut Vec<Quad>) {
+    let start = quads.len();
+    if let Ok(parsed) = parse_file(job, data) {
+        quads.extend(parsed);
+    } else {
+        // We parse line by line to only skip the invalid ones
+        for (i, line) in data.split(|b| *b == b'\n').enumerate() {
+            match parse_file(job, line) {
+                Ok(parsed) => quads.extend(parsed),
+                Err(error) => job
+                    .progress
+                    .record_error(format!("Line {}: {error}", job.lines_parsed + i as u64 + 1)),
+            }
+        }
+    }
+    job.lines_parsed += data.iter().filter(|b| **b == b'\n').count() as u64;
+    job.progress.bytes_parsed += data.len() as u64;
+    job.progress.quads_inserted += (quads.len() - start) as u64;
+}
+
+fn parse_file(job: &ImportJob, data: &[u8]) -> Result<Vec<Quad>, LoaderError> {
+    let to_graph_name = &job.options.to_graph_name;
+    match job.format {
+        JobFormat::Graph(format) => {
+            let mut parser = GraphParser::from_format(format)
+                .with_blank_node_namespace(job.blank_node_namespace);
+            if let Some(base_iri) = &job.options.base_iri {
+                parser = parser
+                    .with_base_iri(base_iri)
+                    .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+            }
+            parser
+                .read_triples(data)?
+                .map(|triple| Ok(triple?.in_graph(to_graph_name.clone())))
+                .collect()
+        }
+        JobFormat::Dataset(format) => {
+            let mut parser = DatasetParser::from_format(format)
+                .with_blank_node_namespace(job.blank_node_namespace);
+            if let Some(base_iri) = &job.options.base_iri {
+                parser = parser
+                    .with_base_iri(base_iri)
+                    .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+            }
+            parser
+                .read_quads(data)?
+                .map(|quad| {
+                    let mut quad = quad?;
+                    if quad.graph_name.is_default_graph() {
+                        quad.graph_name = to_graph_name.clone();
+                    }
+                    Ok(quad)
+                })
+                .collect()
+        }
+    }
+}
+
+fn allocate_job_id(t: &Transaction<'_>) -> Result<JobId, StorageError> {
+    Ok(JobId(if let Some(value) = t.metadata(JOB_COUNTER_KEY)? {
+        decode_job_id(&value)?.0
+    } else {
+        0
+    }))
+}
+
+fn import_job_key(id: JobId) -> Vec<u8> {
+    let mut key = IMPORT_JOB_PREFIX.to_vec();
+    key.extend_from_slice(&id.0.to_be_bytes());
+    key
+}
+
+fn import_chunk_key(id: JobId, n: u64) -> Vec<u8> {
+    let mut key = IMPORT_CHUNK_PREFIX.to_vec();
+    key.extend_from_slice(&id.0.to_be_bytes());
+    key.extend_from_slice(&n.to_be_bytes());
+    key
+}
+
+fn export_job_key(id: JobId) -> Vec<u8> {
+    let mut key = EXPORT_JOB_PREFIX.to_vec();
+    key.extend_from_slice(&id.0.to_be_bytes());
+    key
+}
+
+fn decode_job_id(value: &[u8]) -> Result<JobId, StorageError> {
+    Ok(JobId(u64::from_be_bytes(value.try_into().map_err(
+        |_| CorruptionError::msg("Invalid job id encoding"),
+    )?)))
+}
+
+fn encode_import_job(job: &ImportJob) -> Vec<u8> {
+    let mut buffer = vec![
+        encode_format(job.format),
+        encode_status(job.progress.status),
+    ];
+    write_bytes(
+        &mut buffer,
+        job.options.base_iri.as_deref().unwrap_or("").as_bytes(),
+    );
+    write_bytes(
+        &mut buffer,
+        encode_graph_name(&job.options.to_graph_name).as_bytes(),
+    );
+    buffer.extend_from_slice(&job.blank_node_namespace.to_be_bytes());
+    for value in [
+        job.lines_parsed,
+        job.chunk_count,
+        job.progress.bytes_received,
+        job.progress.bytes_parsed,
+        job.progress.quads_inserted,
+        job.progress.error_count,
+        job.progress.errors.len() as u64,
+    ] {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+    for error in &job.progress.errors {
+        write_bytes(&mut buffer, error.as_bytes());
+    }
+    write_bytes(&mut buffer, &job.pending);
+    buffer
+}
+
+fn decode_import_job(mut data: &[u8]) -> Result<ImportJob, StorageError> {
+    let format = decode_format(read_u8(&mut data)?)?;
+    let status = decode_status(read_u8(&mut data)?)?;
+    let base_iri = read_string(&mut data)?;
+    let to_graph_name = decode_graph_name(&read_string(&mut data)?)?;
+    let blank_node_namespace = u128::from_be_bytes(
+        read_slice(&mut data, 16)?
+            .try_into()
+            .map_err(|_| CorruptionError::msg("Invalid import job encoding"))?,
+    );
+    let lines_parsed = read_u64(&mut data)?;
+    let chunk_count = read_u64(&mut data)?;
+    let bytes_received = read_u64(&mut data)?;
+    let bytes_parsed = read_u64(&mut data)?;
+    let quads_inserted = read_u64(&mut data)?;
+    let error_count = read_u64(&mut data)?;
+    let errors = (0..read_u64(&mut data)?)
+        .map(|_| read_string(&mut data))
+        .collect::<Result<_, _>>()?;
+    let pending = read_bytes(&mut data)?.to_vec();
+    Ok(ImportJob {
+        format,
+        options: ImportOptions {
+            base_iri: if base_iri.is_empty() {
+                None
+            } else {
+                Some(base_iri)
+            },
+            to_graph_name,
+        },
+        blank_node_namespace,
+        lines_parsed,
+        chunk_count,
+        progress: ImportProgress {
+            status,
+            bytes_received,
+            bytes_parsed,
+            quads_inserted,
+            error_count,
+            errors,
+        },
+        pending,
+    })
+}
+
+fn encode_export_job(job: &ExportJob) -> Vec<u8> {
+    let mut buffer = vec![
+        encode_format(job.manifest.format),
+        encode_status(job.manifest.status),
+    ];
+    if let Some(graph_name) = &job.graph_name {
+        buffer.push(1);
+        write_bytes(&mut buffer, encode_graph_name(graph_name).as_bytes());
+    } else {
+        buffer.push(0);
+    }
+    for value in [job.dataset_version, job.manifest.total_size] {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+    write_bytes(&mut buffer, &job.next_position);
+    for (position, checksum) in job
+        .chunk_positions
+        .iter()
+        .zip(&job.manifest.chunk_checksums)
+    {
+        write_bytes(&mut buffer, position);
+        buffer.extend_from_slice(checksum);
+    }
+    buffer
+}
+
+fn decode_export_job(mut data: &[u8]) -> Result<ExportJob, StorageError> {
+    let format = decode_format(read_u8(&mut data)?)?;
+    let status = decode_status(read_u8(&mut data)?)?;
+    let graph_name = match read_u8(&mut data)? {
+        0 => None,
+        1 => Some(decode_graph_name(&read_string(&mut data)?)?),
+        _ => return Err(CorruptionError::msg("Invalid export job graph name").into()),
+    };
+    let dataset_version = read_u64(&mut data)?;
+    let total_size = read_u64(&mut data)?;
+    let next_position = read_bytes(&mut data)?.to_vec();
+    let mut chunk_positions = Vec::new();
+    let mut chunk_checksums = Vec::new();
+    while !data.is_empty() {
+        chunk_positions.push(read_bytes(&mut data)?.to_vec());
+        chunk_checksums.push(read_checksum(&mut data)?);
+    }
+    Ok(ExportJob {
+        graph_name,
+        dataset_version,
+        next_position,
+        chunk_positions,
+        manifest: ExportManifest {
+            format,
+            status,
+            total_size,
+            chunk_checksums,
+        },
+    })
+}
+
+fn serialize_statement(format: JobFormat, quad: QuadRef<'_>) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    match format {
+        JobFormat::Graph(format) => {
+            let mut writer = GraphSerializer::from_format(format).triple_writer(&mut buffer)?;
+            writer.write(TripleRef::from(quad))?;
+            writer.finish()?;
+        }
+        JobFormat::Dataset(format) => {
+            let mut writer = DatasetSerializer::from_format(format).quad_writer(&mut buffer)?;
+            writer.write(quad)?;
+            writer.finish()?;
+        }
+    }
+    Ok(buffer)
+}
+
+fn encode_status(status: JobStatus) -> u8 {
+    match status {
+        JobStatus::Running => 0,
+        JobStatus::Finished => 1,
+        JobStatus::Failed => 2,
+    }
+}
+
+fn decode_status(value: u8) -> Result<JobStatus, StorageError> {
+    Ok(match value {
+        0 => JobStatus::Running,
+        1 => JobStatus::Finished,
+        2 => JobStatus::Failed,
+        _ => return Err(CorruptionError::msg("Invalid job status").into()),
+    })
+}
+
+fn encode_format(format: JobFormat) -> u8 {
+    match format {
+        JobFormat::Graph(GraphFormat::NTriples) => 0,
+        JobFormat::Graph(GraphFormat::Turtle) => 1,
+        JobFormat::Graph(GraphFormat::RdfXml) => 2,
+        JobFormat::Dataset(DatasetFormat::NQuads) => 3,
+        JobFormat::Dataset(DatasetFormat::TriG) => 4,
+        JobFormat::Graph(GraphFormat::N3) => 5,
+        JobFormat::Dataset(DatasetFormat::Binary) => 6,
+    }
+}
+
+fn decode_format(value: u8) -> Result<JobFormat, StorageError> {
+    Ok(match value {
+        0 => JobFormat::Graph(GraphFormat::NTriples),
+        1 => JobFormat::Graph(GraphFormat::Turtle),
+        2 => JobFormat::Graph(GraphFormat::RdfXml),
+        3 => JobFormat::Dataset(DatasetFormat::NQuads),
+        4 => JobFormat::Dataset(DatasetFormat::TriG),
+        5 => JobFormat::Graph(GraphFormat::N3),
+        6 => JobFormat::Dataset(DatasetFormat::Binary),
+        _ => return Err(CorruptionError::msg("Invalid job format").into()),
+    })
+}
+
+fn encode_graph_name(graph_name: &GraphName) -> String {
+    match graph_name {
+        GraphName::NamedNode(node) => node.to_string(),
+        GraphName::BlankNode(node) => node.to_string(),
+        GraphName::DefaultGraph => String::new(),
+    }
+}
+
+fn decode_graph_name(value: &str) -> Result<GraphName, StorageError> {
+    Ok(if value.is_empty() {
+        GraphName::DefaultGraph
+    } else if let Some(id) = value.strip_prefix("_:") {
+        BlankNode::new(id)
+            .map_err(|_| CorruptionError::msg("Invalid import job graph name"))?
+            .into()
+    } else {
+        NamedNode::new(
+            value
+                .strip_prefix('<')
+                .and_then(|v| v.strip_suffix('>'))
+                .ok_or_else(|| CorruptionError::msg("Invalid import job graph name"))?,
+        )
+        .map_err(|_| CorruptionError::msg("Invalid import job graph name"))?
+        .into()
+    })
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u64).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn read_slice<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], StorageError> {
+    if data.len() < len {
+        return Err(CorruptionError::msg("Unexpected end of a job encoding").into());
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, StorageError> {
+    Ok(read_slice(data, 1)?[0])
+}
+
+fn read_u64(data: &mut &[u8]) -> Result<u64, StorageError> {
+    Ok(u64::from_be_bytes(
+        read_slice(data, 8)?
+            .try_into()
+            .map_err(|_| CorruptionError::msg("Invalid job encoding"))?,
+    ))
+}
+
+fn read_checksum(data: &mut &[u8]) -> Result<[u8; 32], StorageError> {
+    read_slice(data, 32)?
+        .try_into()
+        .map_err(|_| CorruptionError::msg("Invalid job checksum encoding").into())
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], StorageError> {
+    let len = read_u64(data)?
+        .try_into()
+        .map_err(|_| CorruptionError::msg("Invalid job encoding"))?;
+    read_slice(data, len)
+}
+
+fn read_string(data: &mut &[u8]) -> Result<String, StorageError> {
+    String::from_utf8(read_bytes(data)?.to_vec())
+        .map_err(|_| CorruptionError::msg("Invalid job string encoding").into())
+}
+
+/// An error raised while running a job.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JobError {
+    /// The job does not exist.
+    UnknownJob(JobId),
+    /// The job is already finished or failed.
+    NotRunning(JobId),
+    /// The format is not supported by export jobs.
+    UnsupportedFormat(JobFormat),
+    /// The store content has been modified since the export job started.
+    Outdated(JobId),
+    /// An error raised while serializing the exported statements.
+    Io(io::Error),
+    /// An error raised while accessing the store.
+    Storage(StorageError),
+}
+
+impl fmt::Display for JobError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownJob(id) => write!(f, "The job {id} does not exist"),
+            Self::NotRunning(id) => write!(f, "The job {id} is not running anymore"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "The format {format:?} is not supported by export jobs")
+            }
+            Self::Outdated(id) => {
+                write!(f, "The store has been modified since the job {id} started")
+            }
+            Self::Io(e) => e.fmt(f),
+            Self::Storage(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for JobError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::UnknownJob(_)
+            | Self::NotRunning(_)
+            | Self::UnsupportedFormat(_)
+            | Self::Outdated(_) => None,
+            Self::Io(e) => Some(e),
+            Self::Storage(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for JobError {
+    #[inline]
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<StorageError> for JobError {
+    #[inline]
+    fn from(error: StorageError) -> Self {
+        Self::Storage(error)
+    }
+}
@@ -5,10 +5,29 @@
 #![doc(test(attr(deny(warnings))))]
 #![allow(clippy::return_self_not_must_use)]
 
+pub mod acl;
+pub mod cache;
+#[cfg(feature = "cluster")]
+pub mod cluster;
+#[cfg(feature = "credentials")]
+pub mod credentials;
+pub mod endpoint;
+pub mod events;
+#[cfg(feature = "harvester")]
+pub mod harvest;
 pub mod io;
+pub mod jobs;
+#[cfg(feature = "maintenance")]
+pub mod maintenance;
+pub mod reasoner;
+pub mod registry;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
 pub mod sparql;
 mod storage;
 pub mod store;
+pub mod summary;
+pub mod views;
 
 pub mod model {
     //! Implements data structures for [RDF 1.1 Concepts](https://www.w3.org/TR/rdf11-concepts/) using [OxRDF](https://crates.io/crates/oxrdf).
@@ -48,22 +67,30 @@ fn custom_getrandom(buf: &mut [u8]) -> Result<(), Error> {
     Ok(())
 }
 
+/// Delay before retrying a failed `raw_rand` call.
+#[cfg(feature = "internal-rng")]
+const RNG_SEED_RETRY_DELAY: Duration = Duration::from_secs(10);
+
 #[cfg(feature = "internal-rng")]
 fn rng_seed() {
     ic_cdk::spawn(async move {
         let result: ic_cdk::api::call::CallResult<(Vec<u8>,)> =
             ic_cdk::api::call::call(candid::Principal::management_canister(), "raw_rand", ()).await;
 
-        RNG_REF_CELL.with(|rng_ref_cell| {
-            let mut rng = rng_ref_cell.borrow_mut();
-
-            match result {
-                Ok(randomness) => {
-                    *rng = SeedableRng::from_seed(randomness.0[..].try_into().unwrap())
-                }
-                Err(err) => panic!("{:?}", err),
-            };
-        });
+        match result {
+            Ok((randomness,)) => match randomness.try_into() {
+                Ok(seed) => seed_rng(seed),
+                Err(randomness) => ic_cdk::println!(
+                    "raw_rand returned {} bytes instead of 32, the RNG is not reseeded",
+                    randomness.len()
+                ),
+            },
+            Err((code, message)) => {
+                // The previous RNG state is kept and the call is retried later
+                ic_cdk::println!("The raw_rand call failed, retrying later: {code:?}: {message}");
+                ic_cdk_timers::set_timer(RNG_SEED_RETRY_DELAY, rng_seed);
+            }
+        }
     });
 }
 
@@ -104,8 +131,32 @@ pub fn init(rng: &RefCell<StdRng>) {
     });
 }
 
+/// Seeds the **Random Number Generator** directly, for example with randomness obtained by the canister itself.
+///
+/// ```no_run
+/// // #[update]
+/// async fn reseed() {
+///     let (randomness,): (Vec<u8>,) = ic_cdk::api::call::call(
+///         ic_cdk::export::Principal::management_canister(),
+///         "raw_rand",
+///         (),
+///     )
+///     .await
+///     .unwrap();
+///     ic_oxigraph::seed_rng(randomness.try_into().unwrap());
+/// }
+/// # let _ = reseed;
+/// ```
+pub fn seed_rng(seed: [u8; 32]) {
+    RNG_REF_CELL.with(|rng_ref_cell| {
+        *rng_ref_cell.borrow_mut() = SeedableRng::from_seed(seed);
+    });
+}
+
 /// Initializes the **Random Number Generator** by asynchronously calling the management canister to obtain a random seed.
 ///
+/// If the call fails, the previous seed is kept and the call is retried 10 seconds later.
+///
 /// This function **must** be called in the `init` and `post_upgrade` functions of the canister that imports this library.
 /// 
 /// # Example
@@ -129,3 +180,13 @@ pub fn init(rng: &RefCell<StdRng>) {
 pub fn init() {
     ic_cdk_timers::set_timer(Duration::new(0, 0), rng_seed);
 }
+
+/// Initializes the **Random Number Generator** like [`init`] and reseeds it every `interval`.
+///
+/// This function **must** be called in the `init` and `post_upgrade` functions of the canister that imports this library,
+/// instead of [`init`].
+#[cfg(feature = "internal-rng")]
+pub fn init_with_reseed(interval: Duration) {
+    init();
+    ic_cdk_timers::set_timer_interval(interval, rng_seed);
+}
@@ -0,0 +1,292 @@
+//! Background maintenance of a [`Store`] run by canister timers.
+//!
+//! A [`BackgroundMaintenance`] runs on each timer tick a bounded part of the enabled tasks:
+//! * the collection of the dictionary strings not used anymore, see [`Store::collect_strings`].
+//! * the refresh of the dataset statistics, see [`Store::export_summary_incrementally`].
+//! * the compaction of the write-ahead log, see [`Store::compact_write_ahead_log`].
+//! * the removal of the expired quads, see [`Store::remove_expired_quads`].
+//!
+//! Each task stops its work of a tick once it has executed more instructions than the budget given on construction,
+//! so the ticks never reach the instruction limit of a canister message.
+//! The string collection and the statistics refresh are started again a configurable period after their last run.
+//!
+//! Nothing is done while the store is in [maintenance mode](Store::begin_maintenance).
+//! The state of the tasks is kept in memory and is lost on canister upgrade.
+//!
+//! This module is only available with the `maintenance` feature and must be used inside a canister.
+//!
+//! Usage example:
+//! ```no_run
+//! use oxigraph::maintenance::BackgroundMaintenance;
+//! use oxigraph::store::Store;
+//! use std::time::Duration;
+//!
+//! let maintenance = BackgroundMaintenance::new(Store::new()?, Duration::from_secs(10), 1_000_000_000)
+//!     .with_string_collection(Duration::from_secs(24 * 3600))
+//!     .with_statistics_refresh(Duration::from_secs(3600));
+//! maintenance.start();
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::store::{LogCompaction, StorageError, Store, StringCollection, SummaryExport};
+use crate::summary::DatasetSummary;
+use ic_cdk::api::{performance_counter, time};
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Number of keys touched by each step of a task, the budget being checked between the steps.
+const STEP_BATCH_SIZE: usize = 100;
+
+/// Runs incremental maintenance tasks of a store on a canister timer.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct BackgroundMaintenance {
+    store: Store,
+    interval: Duration,
+    instruction_budget: u64,
+    state: Rc<RefCell<MaintenanceState>>,
+}
+
+#[derive(Default)]
+struct MaintenanceState {
+    timer: Option<TimerId>,
+    string_collection: Option<PeriodicTask<StringCollection>>,
+    statistics_refresh: Option<PeriodicTask<SummaryExport>>,
+    log_compaction: Option<LogCompactionTask>,
+    expiration_sweep: bool,
+    strings_removed: u64,
+    expired_quads_removed: u64,
+    statistics: Option<DatasetSummary>,
+    last_error: Option<String>,
+}
+
+/// A task started again `period` nanoseconds after the end of its last run.
+struct PeriodicTask<T> {
+    period: u64,
+    next_run: u64,
+    running: Option<T>,
+}
+
+impl<T> PeriodicTask<T> {
+    fn new(period: Duration) -> Self {
+        Self {
+            period: u64::try_from(period.as_nanos()).unwrap_or(u64::MAX),
+            next_run: 0,
+            running: None,
+        }
+    }
+
+    /// Returns the running task, starting a new one with `start` if it is time to.
+    fn current(&mut self, now: u64, start: impl FnOnce() -> T) -> Option<&mut T> {
+        if self.running.is_none() && now >= self.next_run {
+            self.running = Some(start());
+        }
+        self.running.as_mut()
+    }
+
+    fn finish(&mut self, now: u64) -> Option<T> {
+        self.next_run = now.saturating_add(self.period);
+        self.running.take()
+    }
+}
+
+type NextLog = Box<dyn FnMut() -> Option<Box<dyn Write + Send>>>;
+
+struct LogCompactionTask {
+    next_log: NextLog,
+    compacted: Box<dyn FnMut()>,
+    running: Option<LogCompaction>,
+}
+
+impl BackgroundMaintenance {
+    /// Builds a maintenance of the given store without any task enabled.
+    ///
+    /// A tick is done every `interval` and each task executes at most around `instruction_budget` instructions per tick.
+    #[inline]
+    pub fn new(store: Store, interval: Duration, instruction_budget: u64) -> Self {
+        Self {
+            store,
+            interval,
+            instruction_budget,
+            state: Rc::default(),
+        }
+    }
+
+    /// Enables the collection of the unused dictionary strings, started again `period` after the end of each run.
+    #[must_use]
+    pub fn with_string_collection(self, period: Duration) -> Self {
+        self.state.borrow_mut().string_collection = Some(PeriodicTask::new(period));
+        self
+    }
+
+    /// Enables the refresh of the dataset [statistics](Self::statistics), started again `period` after the end of each run.
+    #[must_use]
+    pub fn with_statistics_refresh(self, period: Duration) -> Self {
+        self.state.borrow_mut().statistics_refresh = Some(PeriodicTask::new(period));
+        self
+    }
+
+    /// Enables the compaction of the write-ahead log.
+    ///
+    /// When no compaction is running, `next_log` is called on each tick
+    /// and a compaction into the returned log is started if it returns one,
+    /// for example when the current log has grown too much.
+    /// `compacted` is called once the compaction is finished, the previous log and checkpoint are then not needed anymore.
+    #[must_use]
+    pub fn with_log_compaction(
+        self,
+        next_log: impl FnMut() -> Option<Box<dyn Write + Send>> + 'static,
+        compacted: impl FnMut() + 'static,
+    ) -> Self {
+        self.state.borrow_mut().log_compaction = Some(LogCompactionTask {
+            next_log: Box::new(next_log),
+            compacted: Box::new(compacted),
+            running: None,
+        });
+        self
+    }
+
+    /// Enables the removal of the quads whose time to live has elapsed, done on each tick.
+    #[must_use]
+    pub fn with_expiration_sweep(self) -> Self {
+        self.state.borrow_mut().expiration_sweep = true;
+        self
+    }
+
+    /// Starts the timer doing the ticks.
+    ///
+    /// It does nothing if the timer is already started.
+    pub fn start(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.timer.is_none() {
+            let maintenance = self.clone();
+            state.timer = Some(set_timer_interval(self.interval, move || {
+                maintenance.run_tick();
+            }));
+        }
+    }
+
+    /// Stops the timer doing the ticks.
+    ///
+    /// The running tasks are resumed on the next [`start`](Self::start).
+    pub fn stop(&self) {
+        if let Some(timer) = self.state.borrow_mut().timer.take() {
+            clear_timer(timer);
+        }
+    }
+
+    /// Does a bounded part of each enabled task.
+    ///
+    /// It is called by the maintenance timer and does nothing while the store is in maintenance mode.
+    pub fn run_tick(&self) {
+        if self.store.is_in_maintenance() {
+            return;
+        }
+        let now = time();
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        if let Some(task) = &mut state.string_collection {
+            if let Some(collection) = task.current(now, || self.store.collect_strings()) {
+                let result = self.run_with_budget(|batch_size| collection.step(batch_size));
+                if !matches!(result, Ok(false)) {
+                    if let Some(collection) = task.finish(now) {
+                        state.strings_removed += collection.strings_removed();
+                    }
+                    record_error(&mut state.last_error, "string collection", result);
+                }
+            }
+        }
+
+        if let Some(task) = &mut state.statistics_refresh {
+            if let Some(export) = task.current(now, || self.store.export_summary_incrementally()) {
+                let result = self.run_with_budget(|batch_size| export.step(batch_size));
+                if !matches!(result, Ok(false)) {
+                    if let Some(summary) = task.finish(now).and_then(SummaryExport::into_summary) {
+                        state.statistics = Some(summary);
+                    }
+                    record_error(&mut state.last_error, "statistics refresh", result);
+                }
+            }
+        }
+
+        if let Some(task) = &mut state.log_compaction {
+            if task.running.is_none() {
+                if let Some(log) = (task.next_log)() {
+                    match self.store.compact_write_ahead_log(log) {
+                        Ok(compaction) => task.running = Some(compaction),
+                        Err(error) => {
+                            record_error(&mut state.last_error, "log compaction", Err(error));
+                        }
+                    }
+                }
+            }
+            if let Some(compaction) = &mut task.running {
+                let result = self.run_with_budget(|batch_size| compaction.step(batch_size));
+                if !matches!(result, Ok(false)) {
+                    task.running = None;
+                    if result.is_ok() {
+                        (task.compacted)();
+                    }
+                    record_error(&mut state.last_error, "log compaction", result);
+                }
+            }
+        }
+
+        if state.expiration_sweep {
+            let removed = &mut state.expired_quads_removed;
+            let result = self.run_with_budget(|batch_size| {
+                let count = self.store.remove_expired_quads(batch_size)?;
+                *removed += count as u64;
+                Ok(count < batch_size)
+            });
+            record_error(&mut state.last_error, "expiration sweep", result);
+        }
+    }
+
+    /// The statistics computed by the last finished refresh, if any.
+    pub fn statistics(&self) -> Option<DatasetSummary> {
+        self.state.borrow().statistics.clone()
+    }
+
+    /// The number of strings removed by the finished string collections.
+    pub fn strings_removed(&self) -> u64 {
+        self.state.borrow().strings_removed
+    }
+
+    /// The number of expired quads removed by the expiration sweep.
+    pub fn expired_quads_removed(&self) -> u64 {
+        self.state.borrow().expired_quads_removed
+    }
+
+    /// The last error that stopped a task run, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.state.borrow().last_error.clone()
+    }
+
+    /// Calls `step` until it returns `true` or the instruction budget is exhausted.
+    fn run_with_budget(
+        &self,
+        mut step: impl FnMut(usize) -> Result<bool, StorageError>,
+    ) -> Result<bool, StorageError> {
+        let start = performance_counter(0);
+        loop {
+            if step(STEP_BATCH_SIZE)? {
+                return Ok(true);
+            }
+            if performance_counter(0).saturating_sub(start) >= self.instruction_budget {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+fn record_error(last_error: &mut Option<String>, task: &str, result: Result<bool, StorageError>) {
+    if let Err(error) = result {
+        ic_cdk::println!("The {task} has failed: {error}");
+        *last_error = Some(format!("The {task} has failed: {error}"));
+    }
+}
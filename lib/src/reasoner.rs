@@ -0,0 +1,970 @@
+//! Forward chaining reasoning: the triples entailed by the store content stored and kept up to date when the store changes.
+//!
+//! A [`Reasoner`] materializes in a named graph of the store the triples entailed by the union of the other graphs
+//! according to the [OWL 2 RL](https://www.w3.org/TR/owl2-profiles/#Reasoning_in_OWL_2_RL_and_RDF_Graphs_using_Rules) rules about:
+//! * equality: `owl:sameAs` symmetry, transitivity and replacement (`eq-sym`, `eq-trans`, `eq-rep-s`, `eq-rep-p` and `eq-rep-o`).
+//! * properties: domains and ranges, functional and inverse functional properties, symmetric and transitive properties,
+//!   sub-properties, equivalent properties, inverse properties and property chains (`prp-dom`, `prp-rng`, `prp-fp`, `prp-ifp`,
+//!   `prp-symp`, `prp-trp`, `prp-spo1`, `prp-spo2`, `prp-eqp1`, `prp-eqp2`, `prp-inv1` and `prp-inv2`).
+//! * classes: sub-classes, equivalent classes and `owl:hasValue`, `owl:someValuesFrom` and `owl:allValuesFrom` restrictions
+//!   (`cax-sco`, `cax-eqc1`, `cax-eqc2`, `scm-sco`, `scm-spo`, `scm-eqc1`, `scm-eqp1`, `cls-hv1`, `cls-hv2`, `cls-svf1` and `cls-avf`).
+//!
+//! The reflexivity of `owl:sameAs` (`eq-ref`) is not materialized and the inconsistencies are not reported.
+//! The graph also contains the entailed triples that are asserted in other graphs.
+//!
+//! The entailed triples are maintained from the [change feed](Store::subscribe) of the store:
+//! the consequences of the insertions are added with a semi-naive evaluation of the rules
+//! and the removals are applied with the DRed algorithm:
+//! all the triples derived from the removed ones are deleted then the ones that are still entailed are derived again.
+//! A change of the property chain axioms leads to a full recomputation.
+//!
+//! The application specific rules defined with [`Store::define_rule`] are applied in the same way.
+//! Each of them stores the quads it derives in its own graph, so the provenance of the derived triples is kept.
+//! A reasoner only applying them is built with [`Reasoner::with_defined_rules`].
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::model::vocab::owl;
+//! use oxigraph::model::*;
+//! use oxigraph::reasoner::Reasoner;
+//! use oxigraph::store::Store;
+//!
+//! let store = Store::new()?;
+//! let a = NamedNodeRef::new("http://example.com/a")?;
+//! let b = NamedNodeRef::new("http://example.com/b")?;
+//! let c = NamedNodeRef::new("http://example.com/c")?;
+//! let ancestor = NamedNodeRef::new("http://example.com/ancestor")?;
+//! let descendant = NamedNodeRef::new("http://example.com/descendant")?;
+//! let inferred = NamedNodeRef::new("http://example.com/inferred")?;
+//! let reasoner = Reasoner::new(store.clone(), inferred)?;
+//!
+//! store.insert(QuadRef::new(ancestor, owl::INVERSE_OF, descendant, GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(ancestor, vocab::rdf::TYPE, owl::TRANSITIVE_PROPERTY, GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(a, ancestor, b, GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(b, ancestor, c, GraphNameRef::DefaultGraph))?;
+//! assert!(store.contains(QuadRef::new(a, ancestor, c, inferred))?);
+//! assert!(store.contains(QuadRef::new(c, descendant, a, inferred))?);
+//!
+//! // The triples that are not entailed anymore are removed
+//! store.remove(QuadRef::new(b, ancestor, c, GraphNameRef::DefaultGraph))?;
+//! assert!(!store.contains(QuadRef::new(a, ancestor, c, inferred))?);
+//! assert!(store.contains(QuadRef::new(b, descendant, a, inferred))?);
+//! assert!(!reasoner.is_stale());
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::model::vocab::{owl, rdf, rdfs};
+use crate::model::*;
+use crate::sparql::{EvaluationError, Query};
+use crate::store::{CorruptionError, QuadChange, QuadPattern, StorageError, Store, SubscriptionId};
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Maintains the triples entailed by the content of a [`Store`] in some of its named graphs.
+///
+/// The entailed triples are maintained as long as this object is alive.
+/// The reasoner is not persisted and should be created again after a canister upgrade.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct Reasoner {
+    inner: Arc<ReasonerInner>,
+}
+
+impl Reasoner {
+    /// Starts to track the changes of the store and materializes the entailed triples in the graph `graph_name`.
+    ///
+    /// The rules defined with [`Store::define_rule`] are also applied.
+    /// The current content of the graph and of the graphs of the rules is replaced.
+    pub fn new<'a>(
+        store: Store,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<Self, StorageError> {
+        Self::build(store, Some(graph_name.into().into_owned()))
+    }
+
+    /// Starts to track the changes of the store and only applies the rules defined with [`Store::define_rule`].
+    ///
+    /// The current content of the graphs of the rules is replaced.
+    pub fn with_defined_rules(store: Store) -> Result<Self, StorageError> {
+        Self::build(store, None)
+    }
+
+    fn build(store: Store, graph_name: Option<NamedOrBlankNode>) -> Result<Self, StorageError> {
+        let inner = Arc::new_cyclic(|inner| {
+            let inner = inner.clone();
+            let subscription = store.subscribe(QuadPattern::default(), move |changes| {
+                if let Some(inner) = inner.upgrade() {
+                    inner.apply_changes(changes);
+                }
+            });
+            ReasonerInner {
+                store,
+                graphs: RwLock::new(graph_name.iter().cloned().map(GraphName::from).collect()),
+                graph_name,
+                state: Mutex::new(ReasonerState {
+                    chains: BTreeSet::new(),
+                    rules: Vec::new(),
+                    stale: true,
+                }),
+                pending: Mutex::default(),
+                subscription,
+            }
+        });
+        let reasoner = Self { inner };
+        reasoner.refresh()?;
+        Ok(reasoner)
+    }
+
+    /// The graph in which the OWL 2 RL entailments are stored, `None` if only the defined rules are applied.
+    #[inline]
+    pub fn graph_name(&self) -> Option<NamedOrBlankNodeRef<'_>> {
+        self.inner.graph_name.as_ref().map(NamedOrBlankNode::as_ref)
+    }
+
+    /// Returns if the entailed triples are outdated because the maintenance failed.
+    pub fn is_stale(&self) -> bool {
+        self.inner.state.lock().unwrap().stale
+    }
+
+    /// Recomputes the entailed triples from scratch.
+    ///
+    /// The rules defined with [`Store::define_rule`] are read again.
+    pub fn refresh(&self) -> Result<(), StorageError> {
+        let mut state = self.inner.state.lock().unwrap();
+        self.inner.pending.lock().unwrap().clear();
+        state.stale = true;
+        self.inner.rematerialize(&mut state)?;
+        state.stale = false;
+        drop(state);
+        // The changes notified during the recomputation
+        self.inner.apply_pending();
+        Ok(())
+    }
+}
+
+struct ReasonerInner {
+    store: Store,
+    graph_name: Option<NamedOrBlankNode>,
+    /// The graphs written by the reasoner, whose changes are ignored
+    graphs: RwLock<HashSet<GraphName>>,
+    state: Mutex<ReasonerState>,
+    /// The changes waiting to be applied, the writes done during the maintenance notifying new changes
+    pending: Mutex<Vec<QuadChange>>,
+    subscription: SubscriptionId,
+}
+
+struct ReasonerState {
+    /// The property chain axioms, as the property and its chain
+    chains: BTreeSet<(NamedNode, Vec<NamedNode>)>,
+    rules: Vec<Rule>,
+    stale: bool,
+}
+
+impl ReasonerInner {
+    fn apply_changes(&self, changes: &[QuadChange]) {
+        {
+            let graphs = self.graphs.read().unwrap();
+            let mut pending = self.pending.lock().unwrap();
+            pending.extend(
+                changes
+                    .iter()
+                    .filter(|change| !graphs.contains(&change.quad().graph_name))
+                    .cloned(),
+            );
+            if pending.is_empty() {
+                return;
+            }
+        }
+        self.apply_pending();
+    }
+
+    fn apply_pending(&self) {
+        // The changes are applied by the caller that owns the state, even if they were notified during its own writes
+        while let Ok(mut state) = self.state.try_lock() {
+            let changes = std::mem::take(&mut *self.pending.lock().unwrap());
+            if !state.stale && !changes.is_empty() && self.apply(&mut state, &changes).is_err() {
+                // The graph is fully recomputed by the next refresh
+                state.stale = true;
+            }
+            drop(state);
+            if self.pending.lock().unwrap().is_empty() {
+                return;
+            }
+        }
+    }
+
+    fn apply(&self, state: &mut ReasonerState, changes: &[QuadChange]) -> Result<(), StorageError> {
+        if self.graph_name.is_some()
+            && changes.iter().any(|change| {
+                let predicate = change.quad().predicate.as_ref();
+                predicate == owl::PROPERTY_CHAIN_AXIOM
+                    || predicate == rdf::FIRST
+                    || predicate == rdf::REST
+            })
+            && self.property_chains()? != state.chains
+        {
+            return self.rematerialize(state);
+        }
+        let mut removed = Vec::new();
+        let mut inserted = Vec::new();
+        for change in changes {
+            let triple = Triple::from(change.quad().clone());
+            if self.is_asserted(&triple)? {
+                if matches!(change, QuadChange::Inserted(_)) {
+                    inserted.push(triple);
+                }
+            } else if matches!(change, QuadChange::Removed(_)) {
+                removed.push(triple);
+            }
+        }
+        if !removed.is_empty() {
+            self.remove(&state.rules, removed)?;
+        }
+        self.propagate(&state.rules, inserted)
+    }
+
+    fn rematerialize(&self, state: &mut ReasonerState) -> Result<(), StorageError> {
+        state.rules.clear();
+        if let Some(graph_name) = &self.graph_name {
+            state.chains = self.property_chains()?;
+            state.rules.extend(
+                owl2_rl_rules()
+                    .into_iter()
+                    .chain(
+                        state.chains.iter().map(|(property, chain)| {
+                            Rule::property_chain(property.as_ref(), chain)
+                        }),
+                    )
+                    .map(|rule| rule.in_graph(graph_name.clone())),
+            );
+        }
+        for (_, definition) in self.store.rules()? {
+            let rule = Query::parse(definition.query(), None)
+                .ok()
+                .and_then(|query| Rule::from_query(&query).ok())
+                .ok_or_else(|| CorruptionError::msg("Invalid rule query"))?;
+            state
+                .rules
+                .push(rule.in_graph(definition.graph_name().into_owned()));
+        }
+        let graphs = self
+            .graph_name
+            .iter()
+            .cloned()
+            .map(GraphName::from)
+            .chain(state.rules.iter().map(|rule| rule.graph_name.clone()))
+            .collect::<HashSet<_>>();
+        *self.graphs.write().unwrap() = graphs.clone();
+        for graph_name in &graphs {
+            self.store.clear_graph(graph_name)?;
+        }
+        let mut asserted = HashSet::new();
+        for quad in self.store.iter() {
+            let quad = quad?;
+            if !graphs.contains(&quad.graph_name) {
+                asserted.insert(Triple::from(quad));
+            }
+        }
+        self.propagate(&state.rules, asserted.into_iter().collect())
+    }
+
+    /// Adds the consequences of the `delta` triples until a fixpoint is reached.
+    fn propagate(&self, rules: &[Rule], mut delta: Vec<Triple>) -> Result<(), StorageError> {
+        while !delta.is_empty() {
+            let mut quads = Vec::new();
+            let mut next = HashSet::new();
+            for quad in self.consequences(rules, &delta, None)? {
+                if !self.store.contains(&quad)? {
+                    let triple = Triple::from(quad.clone());
+                    if !self.is_known(&triple)? {
+                        next.insert(triple);
+                    }
+                    quads.push(quad);
+                }
+            }
+            self.store.extend(quads)?;
+            delta = next.into_iter().collect();
+        }
+        Ok(())
+    }
+
+    /// Applies the removal of the `removed` triples with the DRed algorithm.
+    fn remove(&self, rules: &[Rule], removed: Vec<Triple>) -> Result<(), StorageError> {
+        // We delete all the triples that might have been derived from the removed ones
+        let overlay = removed.iter().cloned().collect::<HashSet<_>>();
+        let mut deleted = HashSet::new();
+        let mut delta = removed;
+        while !delta.is_empty() {
+            let mut next = HashSet::new();
+            for quad in self.consequences(rules, &delta, Some(&overlay))? {
+                if !deleted.contains(&quad) && self.store.contains(&quad)? {
+                    let triple = Triple::from(quad.clone());
+                    if !self.is_asserted(&triple)? {
+                        next.insert(triple);
+                    }
+                    deleted.insert(quad);
+                }
+            }
+            delta = next.into_iter().collect();
+        }
+        self.store.remove_all(deleted.iter().cloned())?;
+
+        // We derive again the ones that are still entailed
+        let mut rederived = Vec::new();
+        for quad in deleted {
+            if self.is_derivable(rules, &quad)? {
+                rederived.push(quad);
+            }
+        }
+        self.store.extend(rederived.iter().cloned())?;
+        let mut delta = HashSet::new();
+        for quad in rederived {
+            let triple = Triple::from(quad);
+            if !self.is_asserted(&triple)? {
+                delta.insert(triple);
+            }
+        }
+        self.propagate(rules, delta.into_iter().collect())
+    }
+
+    /// Returns the quads derived by the rules using at least one of the `delta` triples.
+    ///
+    /// The `removed` triples are considered as still in the store.
+    fn consequences(
+        &self,
+        rules: &[Rule],
+        delta: &[Triple],
+        removed: Option<&HashSet<Triple>>,
+    ) -> Result<HashSet<Quad>, StorageError> {
+        let mut derived = HashSet::new();
+        for rule in rules {
+            for (i, atom) in rule.body.iter().enumerate() {
+                let others = rule
+                    .body
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, atom)| atom)
+                    .collect::<Vec<_>>();
+                for triple in delta {
+                    if let Some(binding) = bind(atom, triple, &vec![None; rule.variables]) {
+                        for solution in self.join(&others, binding, removed)? {
+                            derived.extend(rule.instantiate(&solution));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(derived)
+    }
+
+    /// Checks if the quad is derived by a rule from the current content of the store.
+    fn is_derivable(&self, rules: &[Rule], quad: &Quad) -> Result<bool, StorageError> {
+        let triple = Triple::from(quad.clone());
+        for rule in rules {
+            if rule.graph_name != quad.graph_name {
+                continue;
+            }
+            let body = rule.body.iter().collect::<Vec<_>>();
+            for atom in &rule.head {
+                if let Some(binding) = bind(atom, &triple, &vec![None; rule.variables]) {
+                    if self
+                        .join(&body, binding, None)?
+                        .iter()
+                        .any(|solution| rule.accepts(solution))
+                    {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Evaluates the conjunction of the `atoms` from the `binding`, the most bound atom first.
+    fn join(
+        &self,
+        atoms: &[&Atom],
+        binding: Vec<Option<Term>>,
+        removed: Option<&HashSet<Triple>>,
+    ) -> Result<Vec<Vec<Option<Term>>>, StorageError> {
+        let position = if let Some((position, _)) =
+            atoms.iter().enumerate().max_by_key(|(i, atom)| {
+                (
+                    atom.iter()
+                        .filter(|t| t.resolve(&binding).is_some())
+                        .count(),
+                    usize::MAX - i,
+                )
+            }) {
+            position
+        } else {
+            return Ok(vec![binding]);
+        };
+        let atom = atoms[position];
+        let others = atoms
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != position)
+            .map(|(_, atom)| *atom)
+            .collect::<Vec<_>>();
+        let mut solutions = Vec::new();
+        for triple in self.matching(
+            atom[0].resolve(&binding),
+            atom[1].resolve(&binding),
+            atom[2].resolve(&binding),
+            removed,
+        )? {
+            if let Some(binding) = bind(atom, &triple, &binding) {
+                solutions.extend(self.join(&others, binding, removed)?);
+            }
+        }
+        Ok(solutions)
+    }
+
+    /// Returns the triples of all the graphs of the store matching the pattern, and the `removed` ones.
+    fn matching(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        removed: Option<&HashSet<Triple>>,
+    ) -> Result<Vec<Triple>, StorageError> {
+        let subject_ref = match subject {
+            Some(Term::NamedNode(subject)) => Some(SubjectRef::from(subject)),
+            Some(Term::BlankNode(subject)) => Some(subject.into()),
+            Some(Term::Triple(subject)) => Some(SubjectRef::Triple(subject)),
+            Some(Term::Literal(_)) => return Ok(Vec::new()),
+            None => None,
+        };
+        let predicate_ref = match predicate {
+            Some(Term::NamedNode(predicate)) => Some(predicate.as_ref()),
+            Some(_) => return Ok(Vec::new()),
+            None => None,
+        };
+        let mut triples = self
+            .store
+            .quads_for_pattern(subject_ref, predicate_ref, object.map(Term::as_ref), None)
+            .map(|quad| Ok(quad?.into()))
+            .collect::<Result<Vec<Triple>, StorageError>>()?;
+        if let Some(removed) = removed {
+            triples.extend(
+                removed
+                    .iter()
+                    .filter(|triple| {
+                        subject.map_or(true, |s| *s == Term::from(triple.subject.clone()))
+                            && predicate
+                                .map_or(true, |p| *p == Term::from(triple.predicate.clone()))
+                            && object.map_or(true, |o| *o == triple.object)
+                    })
+                    .cloned(),
+            );
+        }
+        Ok(triples)
+    }
+
+    /// Checks if the triple is in a graph of the store not written by the reasoner.
+    fn is_asserted(&self, triple: &Triple) -> Result<bool, StorageError> {
+        let graphs = self.graphs.read().unwrap();
+        for quad in self.store.quads_for_pattern(
+            Some(triple.subject.as_ref()),
+            Some(triple.predicate.as_ref()),
+            Some(triple.object.as_ref()),
+            None,
+        ) {
+            if !graphs.contains(&quad?.graph_name) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Checks if the triple is in any graph of the store.
+    fn is_known(&self, triple: &Triple) -> Result<bool, StorageError> {
+        Ok(self
+            .store
+            .quads_for_pattern(
+                Some(triple.subject.as_ref()),
+                Some(triple.predicate.as_ref()),
+                Some(triple.object.as_ref()),
+                None,
+            )
+            .next()
+            .transpose()?
+            .is_some())
+    }
+
+    /// Reads the `owl:propertyChainAxiom` triples and their lists.
+    fn property_chains(&self) -> Result<BTreeSet<(NamedNode, Vec<NamedNode>)>, StorageError> {
+        let mut chains = BTreeSet::new();
+        for quad in self
+            .store
+            .quads_for_pattern(None, Some(owl::PROPERTY_CHAIN_AXIOM), None, None)
+        {
+            let quad = quad?;
+            if let Subject::NamedNode(property) = quad.subject {
+                if let Some(chain) = self.list(quad.object)? {
+                    if !chain.is_empty() {
+                        chains.insert((property, chain));
+                    }
+                }
+            }
+        }
+        Ok(chains)
+    }
+
+    /// Reads a RDF list of IRIs, `None` if it is not a well formed list.
+    fn list(&self, mut node: Term) -> Result<Option<Vec<NamedNode>>, StorageError> {
+        let mut elements = Vec::new();
+        let mut visited = HashSet::new();
+        while node.as_ref() != TermRef::from(rdf::NIL) {
+            let subject = match &node {
+                Term::NamedNode(node) => SubjectRef::from(node),
+                Term::BlankNode(node) => node.into(),
+                _ => return Ok(None),
+            };
+            if !visited.insert(node.clone()) {
+                return Ok(None);
+            }
+            let first = self
+                .store
+                .quads_for_pattern(Some(subject), Some(rdf::FIRST), None, None)
+                .next()
+                .transpose()?;
+            let rest = self
+                .store
+                .quads_for_pattern(Some(subject), Some(rdf::REST), None, None)
+                .next()
+                .transpose()?;
+            match (first, rest) {
+                (Some(first), Some(rest)) => {
+                    if let Term::NamedNode(element) = first.object {
+                        elements.push(element);
+                    } else {
+                        return Ok(None);
+                    }
+                    node = rest.object;
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(elements))
+    }
+}
+
+impl Drop for ReasonerInner {
+    fn drop(&mut self) {
+        self.store.unsubscribe(self.subscription);
+    }
+}
+
+/// A triple pattern of a rule.
+type Atom = [RuleTerm; 3];
+
+#[derive(Clone)]
+enum RuleTerm {
+    Variable(usize),
+    Constant(Term),
+}
+
+impl RuleTerm {
+    fn resolve<'a>(&'a self, binding: &'a [Option<Term>]) -> Option<&'a Term> {
+        match self {
+            Self::Variable(variable) => binding[*variable].as_ref(),
+            Self::Constant(term) => Some(term),
+        }
+    }
+}
+
+impl From<NamedNodeRef<'_>> for RuleTerm {
+    fn from(node: NamedNodeRef<'_>) -> Self {
+        Self::Constant(node.into_owned().into())
+    }
+}
+
+/// A rule deriving the `head` triples in the graph `graph_name` for each solution of the `body` triple patterns.
+struct Rule {
+    body: Vec<Atom>,
+    head: Vec<Atom>,
+    graph_name: GraphName,
+    /// The number of variables
+    variables: usize,
+    /// Two variables that must not be bound to the same term
+    different: Option<(usize, usize)>,
+}
+
+impl Rule {
+    fn new(body: Vec<Atom>, head: Vec<Atom>) -> Self {
+        let variables = body
+            .iter()
+            .flatten()
+            .filter_map(|term| {
+                if let RuleTerm::Variable(variable) = term {
+                    Some(variable + 1)
+                } else {
+                    None
+                }
+            })
+            .max()
+            .unwrap_or(0);
+        Self {
+            body,
+            head,
+            graph_name: GraphName::DefaultGraph,
+            variables,
+            different: None,
+        }
+    }
+
+    /// Builds a rule from a `CONSTRUCT` query whose `WHERE` clause is a basic graph pattern.
+    fn from_query(query: &Query) -> Result<Self, EvaluationError> {
+        let (template, patterns) =
+            match (query.construct_template(), query.pattern()) {
+                (Some(template), GraphPattern::Bgp { patterns })
+                    if query.dataset().is_default_dataset() =>
+                {
+                    (template, patterns)
+                }
+                _ => return Err(EvaluationError::msg(
+                    "A rule must be a CONSTRUCT query whose WHERE clause is a basic graph pattern",
+                )),
+            };
+        let mut variables = HashMap::new();
+        let body = patterns
+            .iter()
+            .map(|pattern| rule_atom(pattern, &mut variables, true))
+            .collect::<Result<_, _>>()?;
+        let head = template
+            .iter()
+            .map(|pattern| rule_atom(pattern, &mut variables, false))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(body, head))
+    }
+
+    fn in_graph(mut self, graph_name: impl Into<GraphName>) -> Self {
+        self.graph_name = graph_name.into();
+        self
+    }
+
+    fn with_different(mut self, a: usize, b: usize) -> Self {
+        self.different = Some((a, b));
+        self
+    }
+
+    /// `prp-spo2` for a given chain: `?x0 p1 ?x1 ... ?xn-1 pn ?xn` derives `?x0 property ?xn`.
+    fn property_chain(property: NamedNodeRef<'_>, chain: &[NamedNode]) -> Self {
+        Self::new(
+            chain
+                .iter()
+                .enumerate()
+                .map(|(i, element)| {
+                    [
+                        RuleTerm::Variable(i),
+                        element.as_ref().into(),
+                        RuleTerm::Variable(i + 1),
+                    ]
+                })
+                .collect(),
+            vec![[
+                RuleTerm::Variable(0),
+                property.into(),
+                RuleTerm::Variable(chain.len()),
+            ]],
+        )
+    }
+
+    fn accepts(&self, binding: &[Option<Term>]) -> bool {
+        self.different
+            .map_or(true, |(a, b)| binding[a] != binding[b])
+    }
+
+    fn instantiate(&self, binding: &[Option<Term>]) -> Vec<Quad> {
+        if !self.accepts(binding) {
+            return Vec::new();
+        }
+        self.head
+            .iter()
+            .filter_map(|atom| {
+                let subject = match atom[0].resolve(binding)?.clone() {
+                    Term::NamedNode(node) => Subject::from(node),
+                    Term::BlankNode(node) => node.into(),
+                    Term::Triple(triple) => triple.into(),
+                    Term::Literal(_) => return None,
+                };
+                let predicate = if let Term::NamedNode(node) = atom[1].resolve(binding)? {
+                    node.clone()
+                } else {
+                    return None;
+                };
+                Some(Quad::new(
+                    subject,
+                    predicate,
+                    atom[2].resolve(binding)?.clone(),
+                    self.graph_name.clone(),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Converts a triple pattern of a rule, the variables and the blank nodes of the `WHERE` clause being numbered in `variables`.
+fn rule_atom(
+    pattern: &TriplePattern,
+    variables: &mut HashMap<String, usize>,
+    in_body: bool,
+) -> Result<Atom, EvaluationError> {
+    let mut term = |pattern: &TermPattern| match pattern {
+        TermPattern::NamedNode(node) => Ok(RuleTerm::Constant(node.clone().into())),
+        TermPattern::Literal(literal) => Ok(RuleTerm::Constant(literal.clone().into())),
+        TermPattern::Variable(variable) => rule_variable(variable.as_str(), variables, in_body),
+        TermPattern::BlankNode(node) if in_body => {
+            rule_variable(&node.to_string(), variables, in_body)
+        }
+        TermPattern::BlankNode(_) => Err(EvaluationError::msg(
+            "The blank nodes are not allowed in the template of a rule",
+        )),
+        TermPattern::Triple(_) => Err(EvaluationError::msg(
+            "The quoted triple patterns are not supported in rules",
+        )),
+    };
+    let subject = term(&pattern.subject)?;
+    let object = term(&pattern.object)?;
+    let predicate = match &pattern.predicate {
+        NamedNodePattern::NamedNode(node) => RuleTerm::Constant(node.clone().into()),
+        NamedNodePattern::Variable(variable) => {
+            rule_variable(variable.as_str(), variables, in_body)?
+        }
+    };
+    Ok([subject, predicate, object])
+}
+
+fn rule_variable(
+    name: &str,
+    variables: &mut HashMap<String, usize>,
+    in_body: bool,
+) -> Result<RuleTerm, EvaluationError> {
+    if let Some(id) = variables.get(name) {
+        Ok(RuleTerm::Variable(*id))
+    } else if in_body {
+        let id = variables.len();
+        variables.insert(name.to_owned(), id);
+        Ok(RuleTerm::Variable(id))
+    } else {
+        Err(EvaluationError::msg(format!(
+            "The variable ?{name} of the template of a rule is not in its WHERE clause"
+        )))
+    }
+}
+
+/// Checks that a query is a valid rule for [`Store::define_rule`].
+pub(crate) fn validate_rule(query: &Query) -> Result<(), EvaluationError> {
+    Rule::from_query(query).map(|_| ())
+}
+
+/// Extends the `binding` to match the atom with the triple, `None` if it is not possible.
+fn bind(atom: &Atom, triple: &Triple, binding: &[Option<Term>]) -> Option<Vec<Option<Term>>> {
+    let mut binding = binding.to_vec();
+    for (pattern, term) in atom.iter().zip([
+        Term::from(triple.subject.clone()),
+        triple.predicate.clone().into(),
+        triple.object.clone(),
+    ]) {
+        match pattern {
+            RuleTerm::Constant(constant) => {
+                if *constant != term {
+                    return None;
+                }
+            }
+            RuleTerm::Variable(variable) => {
+                if let Some(value) = &binding[*variable] {
+                    if *value != term {
+                        return None;
+                    }
+                } else {
+                    binding[*variable] = Some(term);
+                }
+            }
+        }
+    }
+    Some(binding)
+}
+
+/// The OWL 2 RL rules supported by the reasoner, except `prp-spo2` that depends on the property chain axioms.
+fn owl2_rl_rules() -> Vec<Rule> {
+    const X: RuleTerm = RuleTerm::Variable(0);
+    const Y: RuleTerm = RuleTerm::Variable(1);
+    const Z: RuleTerm = RuleTerm::Variable(2);
+    const P: RuleTerm = RuleTerm::Variable(3);
+    const Q: RuleTerm = RuleTerm::Variable(4);
+    const R: RuleTerm = RuleTerm::Variable(5);
+    const C: RuleTerm = RuleTerm::Variable(6);
+    const D: RuleTerm = RuleTerm::Variable(7);
+    const E: RuleTerm = RuleTerm::Variable(8);
+    let same_as = RuleTerm::from(owl::SAME_AS);
+    let type_ = RuleTerm::from(rdf::TYPE);
+    let sub_class_of = RuleTerm::from(rdfs::SUB_CLASS_OF);
+    let sub_property_of = RuleTerm::from(rdfs::SUB_PROPERTY_OF);
+    let equivalent_class = RuleTerm::from(owl::EQUIVALENT_CLASS);
+    let equivalent_property = RuleTerm::from(owl::EQUIVALENT_PROPERTY);
+    let inverse_of = RuleTerm::from(owl::INVERSE_OF);
+    let on_property = RuleTerm::from(owl::ON_PROPERTY);
+    vec![
+        // eq-sym
+        Rule::new(vec![[X, same_as.clone(), Y]], vec![[Y, same_as.clone(), X]]),
+        // eq-trans
+        Rule::new(
+            vec![[X, same_as.clone(), Y], [Y, same_as.clone(), Z]],
+            vec![[X, same_as.clone(), Z]],
+        ),
+        // eq-rep-s
+        Rule::new(vec![[X, same_as.clone(), Y], [X, P, Z]], vec![[Y, P, Z]]),
+        // eq-rep-p
+        Rule::new(vec![[P, same_as.clone(), Q], [X, P, Y]], vec![[X, Q, Y]]),
+        // eq-rep-o
+        Rule::new(vec![[Y, same_as.clone(), Z], [X, P, Y]], vec![[X, P, Z]]),
+        // prp-dom
+        Rule::new(
+            vec![[P, rdfs::DOMAIN.into(), C], [X, P, Y]],
+            vec![[X, type_.clone(), C]],
+        ),
+        // prp-rng
+        Rule::new(
+            vec![[P, rdfs::RANGE.into(), C], [X, P, Y]],
+            vec![[Y, type_.clone(), C]],
+        ),
+        // prp-fp
+        Rule::new(
+            vec![
+                [P, type_.clone(), owl::FUNCTIONAL_PROPERTY.into()],
+                [X, P, Y],
+                [X, P, Z],
+            ],
+            vec![[Y, same_as.clone(), Z]],
+        )
+        .with_different(1, 2),
+        // prp-ifp
+        Rule::new(
+            vec![
+                [P, type_.clone(), owl::INVERSE_FUNCTIONAL_PROPERTY.into()],
+                [X, P, Z],
+                [Y, P, Z],
+            ],
+            vec![[X, same_as, Y]],
+        )
+        .with_different(0, 1),
+        // prp-symp
+        Rule::new(
+            vec![
+                [P, type_.clone(), owl::SYMMETRIC_PROPERTY.into()],
+                [X, P, Y],
+            ],
+            vec![[Y, P, X]],
+        ),
+        // prp-trp
+        Rule::new(
+            vec![
+                [P, type_.clone(), owl::TRANSITIVE_PROPERTY.into()],
+                [X, P, Y],
+                [Y, P, Z],
+            ],
+            vec![[X, P, Z]],
+        ),
+        // prp-spo1
+        Rule::new(
+            vec![[P, sub_property_of.clone(), Q], [X, P, Y]],
+            vec![[X, Q, Y]],
+        ),
+        // prp-eqp1
+        Rule::new(
+            vec![[P, equivalent_property.clone(), Q], [X, P, Y]],
+            vec![[X, Q, Y]],
+        ),
+        // prp-eqp2
+        Rule::new(
+            vec![[P, equivalent_property.clone(), Q], [X, Q, Y]],
+            vec![[X, P, Y]],
+        ),
+        // prp-inv1
+        Rule::new(vec![[P, inverse_of.clone(), Q], [X, P, Y]], vec![[Y, Q, X]]),
+        // prp-inv2
+        Rule::new(vec![[P, inverse_of, Q], [X, Q, Y]], vec![[Y, P, X]]),
+        // cax-sco
+        Rule::new(
+            vec![[C, sub_class_of.clone(), D], [X, type_.clone(), C]],
+            vec![[X, type_.clone(), D]],
+        ),
+        // cax-eqc1
+        Rule::new(
+            vec![[C, equivalent_class.clone(), D], [X, type_.clone(), C]],
+            vec![[X, type_.clone(), D]],
+        ),
+        // cax-eqc2
+        Rule::new(
+            vec![[C, equivalent_class.clone(), D], [X, type_.clone(), D]],
+            vec![[X, type_.clone(), C]],
+        ),
+        // scm-sco
+        Rule::new(
+            vec![[C, sub_class_of.clone(), D], [D, sub_class_of.clone(), E]],
+            vec![[C, sub_class_of.clone(), E]],
+        ),
+        // scm-spo
+        Rule::new(
+            vec![
+                [P, sub_property_of.clone(), Q],
+                [Q, sub_property_of.clone(), R],
+            ],
+            vec![[P, sub_property_of.clone(), R]],
+        ),
+        // scm-eqc1
+        Rule::new(
+            vec![[C, equivalent_class, D]],
+            vec![[C, sub_class_of.clone(), D], [D, sub_class_of, C]],
+        ),
+        // scm-eqp1
+        Rule::new(
+            vec![[P, equivalent_property, Q]],
+            vec![[P, sub_property_of.clone(), Q], [Q, sub_property_of, P]],
+        ),
+        // cls-hv1
+        Rule::new(
+            vec![
+                [C, owl::HAS_VALUE.into(), Y],
+                [C, on_property.clone(), P],
+                [X, type_.clone(), C],
+            ],
+            vec![[X, P, Y]],
+        ),
+        // cls-hv2
+        Rule::new(
+            vec![
+                [C, owl::HAS_VALUE.into(), Y],
+                [C, on_property.clone(), P],
+                [X, P, Y],
+            ],
+            vec![[X, type_.clone(), C]],
+        ),
+        // cls-svf1
+        Rule::new(
+            vec![
+                [C, owl::SOME_VALUES_FROM.into(), D],
+                [C, on_property.clone(), P],
+                [X, P, Y],
+                [Y, type_.clone(), D],
+            ],
+            vec![[X, type_.clone(), C]],
+        ),
+        // cls-avf
+        Rule::new(
+            vec![
+                [C, owl::ALL_VALUES_FROM.into(), D],
+                [C, on_property, P],
+                [X, type_.clone(), C],
+                [X, P, Y],
+            ],
+            vec![[Y, type_, D]],
+        ),
+    ]
+}
@@ -0,0 +1,157 @@
+//! Several isolated RDF datasets hosted in the same canister.
+//!
+//! A [`StoreRegistry`] opens a [`Store`] per dataset name, each with its own column families,
+//! so the quads, the dictionary, the statistics and the versions of a dataset are never visible from the other ones.
+//! It avoids faking the isolation of the tenants of an application with named graphs.
+//!
+//! The configuration of each dataset is built from its name.
+//! With the `stable-memory` feature, it must give to each dataset its own stable memory regions:
+//! opening a dataset whose regions are already used by another open dataset fails.
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::model::*;
+//! use oxigraph::registry::StoreRegistry;
+//!
+//! let registry = StoreRegistry::new();
+//! let ex = NamedNodeRef::new("http://example.com")?;
+//! registry.open("tenant-a")?.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+//!
+//! // The datasets do not share their content
+//! assert_eq!(registry.open("tenant-a")?.len()?, 1);
+//! assert_eq!(registry.open("tenant-b")?.len()?, 0);
+//! assert_eq!(registry.names(), ["tenant-a", "tenant-b"]);
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::store::{StorageConfig, StorageError, Store};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+type ConfigFactory = Arc<dyn Fn(&str) -> StorageConfig + Send + Sync>;
+
+/// A set of named [`Store`]s opened on demand.
+///
+/// The clones of a registry share the same datasets.
+/// The registry only keeps the datasets in memory: the datasets on the heap are lost on canister upgrade
+/// and the ones in stable memory must be opened again after it.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct StoreRegistry {
+    config: ConfigFactory,
+    datasets: Arc<RwLock<BTreeMap<String, Dataset>>>,
+}
+
+struct Dataset {
+    store: Store,
+    stable_memory_ids: Vec<u8>,
+}
+
+impl StoreRegistry {
+    /// Builds a registry opening each dataset with the default configuration, keeping it on the heap.
+    #[inline]
+    pub fn new() -> Self {
+        Self::new_with_config(|_| StorageConfig::default())
+    }
+
+    /// Builds a registry opening each dataset with the configuration returned by `config` for its name.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::registry::StoreRegistry;
+    /// use oxigraph::store::{StorageConfig, StorageError};
+    ///
+    /// // The datasets of the free tier are limited to one quad
+    /// let registry = StoreRegistry::new_with_config(|name| {
+    ///     if name.starts_with("free/") {
+    ///         StorageConfig::new().with_max_quads(1)
+    ///     } else {
+    ///         StorageConfig::new()
+    ///     }
+    /// });
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = registry.open("free/tenant-a")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// assert!(matches!(
+    ///     store.insert(QuadRef::new(ex, ex, ex, ex)),
+    ///     Err(StorageError::QuotaExceeded { .. })
+    /// ));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn new_with_config(config: impl Fn(&str) -> StorageConfig + Send + Sync + 'static) -> Self {
+        Self {
+            config: Arc::new(config),
+            datasets: Arc::default(),
+        }
+    }
+
+    /// Returns the store of the dataset `name`, opening it if it is not open yet.
+    ///
+    /// Fails if its configuration uses a stable memory region of another open dataset.
+    #[allow(clippy::unwrap_in_result)]
+    pub fn open(&self, name: &str) -> Result<Store, StorageError> {
+        if let Some(store) = self.get(name) {
+            return Ok(store);
+        }
+        let mut datasets = self.datasets.write().unwrap();
+        if let Some(dataset) = datasets.get(name) {
+            return Ok(dataset.store.clone()); // Opened in between
+        }
+        let config = (self.config)(name);
+        let stable_memory_ids = config.stable_memory_ids();
+        for (other, dataset) in datasets.iter() {
+            if let Some(id) = stable_memory_ids
+                .iter()
+                .find(|id| dataset.stable_memory_ids.contains(*id))
+            {
+                return Err(StorageError::Other(
+                    format!("The stable memory region {id} is already used by the dataset {other}")
+                        .into(),
+                ));
+            }
+        }
+        let store = Store::new_with_config(&config)?;
+        datasets.insert(
+            name.into(),
+            Dataset {
+                store: store.clone(),
+                stable_memory_ids,
+            },
+        );
+        Ok(store)
+    }
+
+    /// Returns the store of the dataset `name` if it is open.
+    pub fn get(&self, name: &str) -> Option<Store> {
+        self.datasets
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|dataset| dataset.store.clone())
+    }
+
+    /// Closes the dataset `name` and returns its store if it was open.
+    ///
+    /// The content of a dataset on the heap is dropped with the last clone of its store.
+    /// The stable memory regions of a dataset are left untouched and can be used by another dataset.
+    pub fn close(&self, name: &str) -> Option<Store> {
+        self.datasets
+            .write()
+            .unwrap()
+            .remove(name)
+            .map(|dataset| dataset.store)
+    }
+
+    /// The names of the open datasets in lexicographic order.
+    pub fn names(&self) -> Vec<String> {
+        self.datasets.read().unwrap().keys().cloned().collect()
+    }
+}
+
+impl Default for StoreRegistry {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
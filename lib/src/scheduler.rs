@@ -0,0 +1,240 @@
+//! Fair scheduling of long SPARQL updates over several timer callbacks.
+//!
+//! The number of instructions of a canister message is bounded,
+//! so an update touching millions of quads can not be executed by a single call to [`Store::update`].
+//! An [`UpdateScheduler`] splits the submitted updates into bounded batches
+//! and executes one batch of each running update per round, a round being done on each timer tick.
+//!
+//! While an update is running, the graphs it writes are locked:
+//! the other writes into them fail with [`StorageError::GraphLocked`](crate::store::StorageError::GraphLocked)
+//! and an update writing into an already locked graph is rejected on submission.
+//! The `WHERE` clause of each `DELETE`/`INSERT` operation is fully evaluated before its changes are written.
+//! The graph management operations (`LOAD`, `CLEAR`, `CREATE`, `DROP`) are executed in a single batch.
+//!
+//! The scheduled updates are kept in memory and are lost on canister upgrade.
+//!
+//! This module is only available with the `scheduler` feature and must be used inside a canister.
+//!
+//! Usage example:
+//! ```no_run
+//! use oxigraph::jobs::JobStatus;
+//! use oxigraph::scheduler::UpdateScheduler;
+//! use oxigraph::store::Store;
+//! use std::time::Duration;
+//!
+//! let scheduler = UpdateScheduler::new(Store::new()?, 10_000, Duration::from_secs(1));
+//! let job = scheduler.submit(
+//!     "DELETE { ?s <http://example.com/p> ?o } INSERT { ?s <http://example.com/q> ?o } WHERE { ?s <http://example.com/p> ?o }",
+//! )?;
+//! let progress = scheduler.progress(job).unwrap();
+//! assert_eq!(progress.status(), JobStatus::Running);
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::jobs::{JobId, JobStatus};
+use crate::sparql::{BatchedUpdate, EvaluationError, Update, UpdateOptions};
+use crate::store::Store;
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Executes long SPARQL updates in bounded batches, round-robin between the running updates.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct UpdateScheduler {
+    store: Store,
+    batch_size: usize,
+    interval: Duration,
+    state: Rc<RefCell<SchedulerState>>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    next_id: u64,
+    jobs: BTreeMap<JobId, ScheduledUpdate>,
+    timer: Option<TimerId>,
+}
+
+struct ScheduledUpdate {
+    update: BatchedUpdate,
+    lock_owner: u64,
+    status: JobStatus,
+    error: Option<String>,
+}
+
+impl UpdateScheduler {
+    /// Builds a scheduler writing into the given store.
+    ///
+    /// Each round is done `interval` after the previous one and evaluates at most `batch_size` solutions
+    /// or writes at most `batch_size` quads for each running update.
+    #[inline]
+    pub fn new(store: Store, batch_size: usize, interval: Duration) -> Self {
+        Self {
+            store,
+            batch_size: batch_size.max(1),
+            interval,
+            state: Rc::default(),
+        }
+    }
+
+    /// Submits an update to execute in the next rounds.
+    ///
+    /// Fails with [`StorageError::GraphLocked`](crate::store::StorageError::GraphLocked)
+    /// if the update writes into a graph locked by another running update.
+    pub fn submit(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+    ) -> Result<JobId, EvaluationError> {
+        self.submit_opt(update, UpdateOptions::default())
+    }
+
+    /// Submits an update with some options to execute in the next rounds.
+    pub fn submit_opt(
+        &self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: impl Into<UpdateOptions>,
+    ) -> Result<JobId, EvaluationError> {
+        let update = BatchedUpdate::new(&update.try_into().map_err(Into::into)?, options.into());
+        let lock_owner = self.store.lock_graphs(update.written_graphs())?;
+        let mut state = self.state.borrow_mut();
+        let id = JobId::from(state.next_id);
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            ScheduledUpdate {
+                update,
+                lock_owner,
+                status: JobStatus::Running,
+                error: None,
+            },
+        );
+        if state.timer.is_none() {
+            let scheduler = self.clone();
+            state.timer = Some(set_timer_interval(self.interval, move || {
+                scheduler.run_round();
+            }));
+        }
+        Ok(id)
+    }
+
+    /// Executes a batch of each running update.
+    ///
+    /// It is called by the scheduler timer and does nothing while the store is in maintenance mode.
+    pub fn run_round(&self) {
+        if self.store.is_in_maintenance() {
+            return;
+        }
+        let mut state = self.state.borrow_mut();
+        for job in state.jobs.values_mut() {
+            if job.status != JobStatus::Running {
+                continue;
+            }
+            match self
+                .store
+                .step_batched_update(&mut job.update, job.lock_owner, self.batch_size)
+            {
+                Ok(()) => {
+                    if job.update.is_finished() {
+                        job.status = JobStatus::Finished;
+                        self.store.unlock_graphs(job.lock_owner);
+                    }
+                }
+                Err(error) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(error.to_string());
+                    self.store.unlock_graphs(job.lock_owner);
+                }
+            }
+        }
+        if !state
+            .jobs
+            .values()
+            .any(|job| job.status == JobStatus::Running)
+        {
+            if let Some(timer) = state.timer.take() {
+                clear_timer(timer);
+            }
+        }
+    }
+
+    /// Returns the progress of a submitted update.
+    pub fn progress(&self, id: JobId) -> Option<UpdateProgress> {
+        let state = self.state.borrow();
+        let job = state.jobs.get(&id)?;
+        Some(UpdateProgress {
+            status: job.status,
+            operation_count: job.update.operation_count(),
+            operations_done: job.update.operations_done(),
+            solutions_evaluated: job.update.solutions_evaluated(),
+            quads_written: job.update.quads_written(),
+            error: job.error.clone(),
+        })
+    }
+
+    /// Forgets a submitted update, cancelling it if it is still running.
+    ///
+    /// The batches already written are kept in the store.
+    ///
+    /// Returns `true` if the update existed.
+    pub fn remove(&self, id: JobId) -> bool {
+        if let Some(job) = self.state.borrow_mut().jobs.remove(&id) {
+            if job.status == JobStatus::Running {
+                self.store.unlock_graphs(job.lock_owner);
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The progress of an update submitted to an [`UpdateScheduler`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct UpdateProgress {
+    status: JobStatus,
+    operation_count: usize,
+    operations_done: usize,
+    solutions_evaluated: u64,
+    quads_written: u64,
+    error: Option<String>,
+}
+
+impl UpdateProgress {
+    /// The status of the update.
+    #[inline]
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    /// The number of operations of the update.
+    #[inline]
+    pub fn operation_count(&self) -> usize {
+        self.operation_count
+    }
+
+    /// The number of fully executed operations.
+    #[inline]
+    pub fn operations_done(&self) -> usize {
+        self.operations_done
+    }
+
+    /// The number of `WHERE` clause solutions evaluated so far.
+    #[inline]
+    pub fn solutions_evaluated(&self) -> u64 {
+        self.solutions_evaluated
+    }
+
+    /// The number of quad insertions and removals applied so far.
+    #[inline]
+    pub fn quads_written(&self) -> u64 {
+        self.quads_written
+    }
+
+    /// The error that stopped the update if it [failed](JobStatus::Failed).
+    #[inline]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
@@ -6,6 +6,8 @@
 
 use crate::model::*;
 use crate::sparql::eval::Timer;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::GroundTerm;
 use spargebra::GraphUpdateOperation;
 use std::fmt;
 use std::str::FromStr;
@@ -38,8 +40,21 @@ pub struct Query {
 impl Query {
     /// Parses a SPARQL query with an optional base IRI to resolve relative IRIs in the query.
     pub fn parse(query: &str, base_iri: Option<&str>) -> Result<Self, spargebra::ParseError> {
+        Self::parse_with_prefixes(query, base_iri, [])
+    }
+
+    /// Parses a SPARQL query with some prefixes declared implicitly, as if they were in the query prologue.
+    ///
+    /// The `PREFIX` declarations of the query override them.
+    pub fn parse_with_prefixes(
+        query: &str,
+        base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, spargebra::ParseError> {
         let start = Timer::now();
-        let query = Self::from(spargebra::Query::parse(query, base_iri)?);
+        let query = Self::from(spargebra::Query::parse_with_prefixes(
+            query, base_iri, prefixes,
+        )?);
         Ok(Self {
             dataset: query.dataset,
             inner: query.inner,
@@ -56,6 +71,27 @@ impl Query {
     pub fn dataset_mut(&mut self) -> &mut QueryDataset {
         &mut self.dataset
     }
+
+    pub(crate) fn is_construct(&self) -> bool {
+        matches!(self.inner, spargebra::Query::Construct { .. })
+    }
+
+    pub(crate) fn construct_template(&self) -> Option<&[spargebra::term::TriplePattern]> {
+        if let spargebra::Query::Construct { template, .. } = &self.inner {
+            Some(template)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn pattern(&self) -> &spargebra::algebra::GraphPattern {
+        match &self.inner {
+            spargebra::Query::Select { pattern, .. }
+            | spargebra::Query::Construct { pattern, .. }
+            | spargebra::Query::Describe { pattern, .. }
+            | spargebra::Query::Ask { pattern, .. } => pattern,
+        }
+    }
 }
 
 impl fmt::Display for Query {
@@ -123,7 +159,18 @@ pub struct Update {
 impl Update {
     /// Parses a SPARQL update with an optional base IRI to resolve relative IRIs in the query.
     pub fn parse(update: &str, base_iri: Option<&str>) -> Result<Self, spargebra::ParseError> {
-        let update = spargebra::Update::parse(update, base_iri)?;
+        Self::parse_with_prefixes(update, base_iri, [])
+    }
+
+    /// Parses a SPARQL update with some prefixes declared implicitly, as if they were in the update prologue.
+    ///
+    /// The `PREFIX` declarations of the update override them.
+    pub fn parse_with_prefixes(
+        update: &str,
+        base_iri: Option<&str>,
+        prefixes: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, spargebra::ParseError> {
+        let update = spargebra::Update::parse_with_prefixes(update, base_iri, prefixes)?;
         Ok(Self {
             using_datasets: update
                 .operations
@@ -149,6 +196,40 @@ impl Update {
     pub fn using_datasets_mut(&mut self) -> impl Iterator<Item = &mut QueryDataset> {
         self.using_datasets.iter_mut().filter_map(Option::as_mut)
     }
+
+    /// Joins the `WHERE` clause of the DELETE/INSERT operations with a `VALUES` clause binding the variables to the rows
+    ///
+    /// The rows containing a blank node are skipped, `VALUES` not allowing them.
+    pub(crate) fn with_values(&self, variables: &[Variable], rows: &[Vec<Option<Term>>]) -> Self {
+        let bindings = rows
+            .iter()
+            .filter_map(|row| {
+                row.iter()
+                    .map(|term| term.clone().map(GroundTerm::try_from).transpose())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        let mut update = self.clone();
+        for operation in &mut update.inner.operations {
+            if let GraphUpdateOperation::DeleteInsert { pattern, .. } = operation {
+                let where_pattern = std::mem::replace(
+                    pattern.as_mut(),
+                    GraphPattern::Bgp {
+                        patterns: Vec::new(),
+                    },
+                );
+                **pattern = GraphPattern::Join {
+                    left: Box::new(GraphPattern::Values {
+                        variables: variables.to_vec(),
+                        bindings: bindings.clone(),
+                    }),
+                    right: Box::new(where_pattern),
+                };
+            }
+        }
+        update
+    }
 }
 
 impl fmt::Display for Update {
@@ -0,0 +1,514 @@
+use crate::model::{GraphName, NamedNode, NamedOrBlankNode};
+use crate::sparql::algebra::{Query, QueryDataset, Update};
+use crate::sparql::policy::top_level_limit;
+use oxrdf::Variable;
+use spargebra::algebra::{
+    AggregateExpression, Expression, GraphPattern, GraphTarget, OrderExpression,
+    PropertyPathExpression,
+};
+use spargebra::term::{
+    GraphNamePattern, GroundTermPattern, NamedNodePattern, TermPattern, TriplePattern,
+};
+use spargebra::GraphUpdateOperation;
+use std::collections::BTreeSet;
+
+/// What a query or an update touches and how expensive it may be, computed without evaluating it.
+///
+/// It allows to reject or to route a request before paying its evaluation cost.
+/// It is built with [`Query::analyze`] or [`Update::analyze`].
+///
+/// ```
+/// use oxigraph::model::NamedNode;
+/// use oxigraph::sparql::{CostBand, Query};
+///
+/// let analysis = Query::parse(
+///     "SELECT ?s WHERE { GRAPH <http://example.com/g> { ?s <http://example.com/p> ?o } }",
+///     None,
+/// )?
+/// .analyze();
+/// assert_eq!(analysis.graphs(), [NamedNode::new("http://example.com/g")?]);
+/// assert_eq!(analysis.predicates(), [NamedNode::new("http://example.com/p")?]);
+/// assert!(analysis.is_read_only());
+/// assert_eq!(analysis.cost(), CostBand::Low);
+///
+/// // Reading all the quads of the store is expensive
+/// let analysis = Query::parse("SELECT * WHERE { ?s ?p ?o }", None)?.analyze();
+/// assert_eq!(analysis.cost(), CostBand::High);
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QueryAnalysis {
+    graphs: Vec<NamedNode>,
+    predicates: Vec<NamedNode>,
+    variables: Vec<Variable>,
+    is_read_only: bool,
+    cost: CostBand,
+}
+
+impl QueryAnalysis {
+    /// The named graphs explicitly referenced, by the dataset specification, `GRAPH` clauses or update operations, in lexicographic order.
+    ///
+    /// Graphs selected through a variable are not listed.
+    #[inline]
+    pub fn graphs(&self) -> &[NamedNode] {
+        &self.graphs
+    }
+
+    /// The predicates of the triple patterns and property paths, in lexicographic order.
+    #[inline]
+    pub fn predicates(&self) -> &[NamedNode] {
+        &self.predicates
+    }
+
+    /// The variables used anywhere in the request, in lexicographic order.
+    #[inline]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// If the request never modifies the store, i.e. if it is a query.
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.is_read_only
+    }
+
+    /// A rough estimation of the evaluation cost of the request.
+    #[inline]
+    pub fn cost(&self) -> CostBand {
+        self.cost
+    }
+}
+
+/// A rough estimation of the evaluation cost of a request, from its syntax only.
+///
+/// The bands are ordered from the cheapest to the most expensive.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub enum CostBand {
+    /// Only index lookups with at least one constant term per pattern, like `?s rdf:type ex:Person`.
+    Low,
+    /// Unbounded scans cut by a `LIMIT`, or sorts, groups and `DISTINCT` keeping solutions in memory, or clearing a single graph.
+    Medium,
+    /// Scans of all the quads or unbounded property paths without an effective `LIMIT`, `SERVICE` calls, `LOAD` operations
+    /// or clearing several graphs.
+    High,
+}
+
+impl Query {
+    /// Computes the graphs, the predicates and the variables used by the query and an estimation of its cost, without evaluating it.
+    ///
+    /// See [`QueryAnalysis`] for an example.
+    pub fn analyze(&self) -> QueryAnalysis {
+        let mut analyzer = Analyzer::default();
+        analyzer.add_dataset(&self.dataset);
+        let pattern = self.pattern();
+        analyzer.add_pattern(pattern);
+        let is_limited = matches!(self.inner, spargebra::Query::Ask { .. })
+            || top_level_limit(pattern).is_some();
+        let cost = analyzer.cost(is_limited);
+        analyzer.build(true, cost)
+    }
+}
+
+impl Update {
+    /// Computes the graphs, the predicates and the variables used by the update and an estimation of its cost, without evaluating it.
+    ///
+    /// ```
+    /// use oxigraph::sparql::{CostBand, Update};
+    ///
+    /// let analysis = Update::parse("DELETE WHERE { ?s ?p ?o }", None)?.analyze();
+    /// assert!(!analysis.is_read_only());
+    /// assert_eq!(analysis.cost(), CostBand::High);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn analyze(&self) -> QueryAnalysis {
+        let mut analyzer = Analyzer::default();
+        let mut cost = CostBand::Low;
+        for (operation, using) in self.inner.operations.iter().zip(&self.using_datasets) {
+            if let Some(using) = using {
+                analyzer.add_dataset(using);
+            }
+            let operation_cost = match operation {
+                GraphUpdateOperation::InsertData { data } => {
+                    for quad in data {
+                        analyzer.predicates.insert(quad.predicate.clone());
+                        analyzer.add_graph_name(&quad.graph_name);
+                    }
+                    CostBand::Low
+                }
+                GraphUpdateOperation::DeleteData { data } => {
+                    for quad in data {
+                        analyzer.predicates.insert(quad.predicate.clone());
+                        analyzer.add_graph_name(&quad.graph_name);
+                    }
+                    CostBand::Low
+                }
+                GraphUpdateOperation::DeleteInsert {
+                    delete,
+                    insert,
+                    pattern,
+                    ..
+                } => {
+                    for quad in delete {
+                        analyzer.add_ground_term_pattern(&quad.subject);
+                        analyzer.add_predicate_pattern(&quad.predicate);
+                        analyzer.add_ground_term_pattern(&quad.object);
+                        analyzer.add_graph_name_pattern(&quad.graph_name);
+                    }
+                    for quad in insert {
+                        analyzer.add_term_pattern(&quad.subject);
+                        analyzer.add_predicate_pattern(&quad.predicate);
+                        analyzer.add_term_pattern(&quad.object);
+                        analyzer.add_graph_name_pattern(&quad.graph_name);
+                    }
+                    let mut pattern_analyzer = Analyzer::default();
+                    pattern_analyzer.add_pattern(pattern);
+                    let cost = pattern_analyzer.cost(false);
+                    analyzer.merge(pattern_analyzer);
+                    cost
+                }
+                GraphUpdateOperation::Load { destination, .. } => {
+                    // LOAD fetches a remote document
+                    analyzer.add_graph_name(destination);
+                    CostBand::High
+                }
+                GraphUpdateOperation::Clear { graph, .. }
+                | GraphUpdateOperation::Drop { graph, .. } => {
+                    if let GraphTarget::NamedNode(graph) = graph {
+                        analyzer.graphs.insert(graph.clone());
+                        CostBand::Medium
+                    } else {
+                        CostBand::High
+                    }
+                }
+                GraphUpdateOperation::Create { graph, .. } => {
+                    analyzer.graphs.insert(graph.clone());
+                    CostBand::Low
+                }
+            };
+            cost = cost.max(operation_cost);
+        }
+        analyzer.build(false, cost)
+    }
+}
+
+#[derive(Default)]
+struct Analyzer {
+    graphs: BTreeSet<NamedNode>,
+    predicates: BTreeSet<NamedNode>,
+    variables: BTreeSet<Variable>,
+    /// A triple pattern without any constant term or an unbounded property path
+    has_unbounded_scan: bool,
+    /// A sort, a group or a DISTINCT
+    has_materialization: bool,
+    has_service: bool,
+}
+
+impl Analyzer {
+    fn build(self, is_read_only: bool, cost: CostBand) -> QueryAnalysis {
+        QueryAnalysis {
+            graphs: self.graphs.into_iter().collect(),
+            predicates: self.predicates.into_iter().collect(),
+            variables: self.variables.into_iter().collect(),
+            is_read_only,
+            cost,
+        }
+    }
+
+    /// The cost of the patterns added so far, `is_limited` being true if the solutions are cut by a `LIMIT`
+    fn cost(&self, is_limited: bool) -> CostBand {
+        if self.has_service
+            || (self.has_unbounded_scan && (!is_limited || self.has_materialization))
+        {
+            CostBand::High
+        } else if self.has_unbounded_scan || self.has_materialization {
+            CostBand::Medium
+        } else {
+            CostBand::Low
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.graphs.extend(other.graphs);
+        self.predicates.extend(other.predicates);
+        self.variables.extend(other.variables);
+        self.has_unbounded_scan |= other.has_unbounded_scan;
+        self.has_materialization |= other.has_materialization;
+        self.has_service |= other.has_service;
+    }
+
+    fn add_dataset(&mut self, dataset: &QueryDataset) {
+        for graph in dataset.default_graph_graphs().unwrap_or_default() {
+            self.add_graph_name(graph);
+        }
+        for graph in dataset.available_named_graphs().unwrap_or_default() {
+            if let NamedOrBlankNode::NamedNode(graph) = graph {
+                self.graphs.insert(graph.clone());
+            }
+        }
+    }
+
+    fn add_graph_name(&mut self, graph_name: &GraphName) {
+        if let GraphName::NamedNode(graph_name) = graph_name {
+            self.graphs.insert(graph_name.clone());
+        }
+    }
+
+    fn add_pattern(&mut self, pattern: &GraphPattern) {
+        match pattern {
+            GraphPattern::Bgp { patterns } => {
+                for pattern in patterns {
+                    if is_unbounded_triple_pattern(pattern) {
+                        self.has_unbounded_scan = true;
+                    }
+                    self.add_triple_pattern(pattern);
+                }
+            }
+            GraphPattern::Path {
+                subject,
+                path,
+                object,
+            } => {
+                self.add_term_pattern(subject);
+                self.add_path(path);
+                self.add_term_pattern(object);
+            }
+            GraphPattern::Join { left, right }
+            | GraphPattern::Lateral { left, right }
+            | GraphPattern::Union { left, right }
+            | GraphPattern::Minus { left, right } => {
+                self.add_pattern(left);
+                self.add_pattern(right);
+            }
+            GraphPattern::LeftJoin {
+                left,
+                right,
+                expression,
+            } => {
+                self.add_pattern(left);
+                self.add_pattern(right);
+                if let Some(expression) = expression {
+                    self.add_expression(expression);
+                }
+            }
+            GraphPattern::Filter { expr, inner } => {
+                self.add_expression(expr);
+                self.add_pattern(inner);
+            }
+            GraphPattern::Graph { name, inner } => {
+                self.add_graph_pattern(name);
+                self.add_pattern(inner);
+            }
+            GraphPattern::Extend {
+                inner,
+                variable,
+                expression,
+            } => {
+                self.variables.insert(variable.clone());
+                self.add_expression(expression);
+                self.add_pattern(inner);
+            }
+            GraphPattern::Values { variables, .. } => {
+                self.variables.extend(variables.iter().cloned());
+            }
+            GraphPattern::OrderBy { inner, expression } => {
+                self.has_materialization = true;
+                for expression in expression {
+                    match expression {
+                        OrderExpression::Asc(e) | OrderExpression::Desc(e) => {
+                            self.add_expression(e)
+                        }
+                    }
+                }
+                self.add_pattern(inner);
+            }
+            GraphPattern::Project { inner, variables } => {
+                self.variables.extend(variables.iter().cloned());
+                self.add_pattern(inner);
+            }
+            GraphPattern::Distinct { inner } => {
+                self.has_materialization = true;
+                self.add_pattern(inner);
+            }
+            GraphPattern::Reduced { inner } | GraphPattern::Slice { inner, .. } => {
+                self.add_pattern(inner)
+            }
+            GraphPattern::Group {
+                inner,
+                variables,
+                aggregates,
+            } => {
+                self.has_materialization = true;
+                self.variables.extend(variables.iter().cloned());
+                for (variable, aggregate) in aggregates {
+                    self.variables.insert(variable.clone());
+                    match aggregate {
+                        AggregateExpression::Count { expr, .. } => {
+                            if let Some(expr) = expr {
+                                self.add_expression(expr);
+                            }
+                        }
+                        AggregateExpression::Sum { expr, .. }
+                        | AggregateExpression::Avg { expr, .. }
+                        | AggregateExpression::Min { expr, .. }
+                        | AggregateExpression::Max { expr, .. }
+                        | AggregateExpression::GroupConcat { expr, .. }
+                        | AggregateExpression::Sample { expr, .. }
+                        | AggregateExpression::Custom { expr, .. } => self.add_expression(expr),
+                    }
+                }
+                self.add_pattern(inner);
+            }
+            GraphPattern::Service { name, inner, .. } => {
+                self.has_service = true;
+                if let NamedNodePattern::Variable(v) = name {
+                    self.variables.insert(v.clone());
+                }
+                self.add_pattern(inner);
+            }
+        }
+    }
+
+    fn add_path(&mut self, path: &PropertyPathExpression) {
+        match path {
+            PropertyPathExpression::NamedNode(p) => {
+                self.predicates.insert(p.clone());
+            }
+            PropertyPathExpression::NegatedPropertySet(p) => {
+                // All the other predicates may be read
+                self.has_unbounded_scan = true;
+                self.predicates.extend(p.iter().cloned());
+            }
+            PropertyPathExpression::Reverse(p) | PropertyPathExpression::ZeroOrOne(p) => {
+                self.add_path(p)
+            }
+            PropertyPathExpression::Sequence(a, b) | PropertyPathExpression::Alternative(a, b) => {
+                self.add_path(a);
+                self.add_path(b);
+            }
+            PropertyPathExpression::ZeroOrMore(p) | PropertyPathExpression::OneOrMore(p) => {
+                self.has_unbounded_scan = true;
+                self.add_path(p);
+            }
+        }
+    }
+
+    fn add_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::NamedNode(_) | Expression::Literal(_) => (),
+            Expression::Variable(v) | Expression::Bound(v) => {
+                self.variables.insert(v.clone());
+            }
+            Expression::Or(a, b)
+            | Expression::And(a, b)
+            | Expression::Equal(a, b)
+            | Expression::SameTerm(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterOrEqual(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessOrEqual(a, b)
+            | Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b) => {
+                self.add_expression(a);
+                self.add_expression(b);
+            }
+            Expression::UnaryPlus(e) | Expression::UnaryMinus(e) | Expression::Not(e) => {
+                self.add_expression(e)
+            }
+            Expression::In(a, b) => {
+                self.add_expression(a);
+                for e in b {
+                    self.add_expression(e);
+                }
+            }
+            Expression::Exists(p) => self.add_pattern(p),
+            Expression::If(a, b, c) => {
+                self.add_expression(a);
+                self.add_expression(b);
+                self.add_expression(c);
+            }
+            Expression::Coalesce(l) | Expression::FunctionCall(_, l) => {
+                for e in l {
+                    self.add_expression(e);
+                }
+            }
+        }
+    }
+
+    fn add_triple_pattern(&mut self, pattern: &TriplePattern) {
+        self.add_term_pattern(&pattern.subject);
+        self.add_predicate_pattern(&pattern.predicate);
+        self.add_term_pattern(&pattern.object);
+    }
+
+    fn add_term_pattern(&mut self, pattern: &TermPattern) {
+        match pattern {
+            TermPattern::NamedNode(_) | TermPattern::BlankNode(_) | TermPattern::Literal(_) => (),
+            TermPattern::Triple(triple) => self.add_triple_pattern(triple),
+            TermPattern::Variable(v) => {
+                self.variables.insert(v.clone());
+            }
+        }
+    }
+
+    fn add_ground_term_pattern(&mut self, pattern: &GroundTermPattern) {
+        match pattern {
+            GroundTermPattern::NamedNode(_) | GroundTermPattern::Literal(_) => (),
+            GroundTermPattern::Variable(v) => {
+                self.variables.insert(v.clone());
+            }
+            GroundTermPattern::Triple(triple) => {
+                self.add_ground_term_pattern(&triple.subject);
+                self.add_predicate_pattern(&triple.predicate);
+                self.add_ground_term_pattern(&triple.object);
+            }
+        }
+    }
+
+    fn add_predicate_pattern(&mut self, pattern: &NamedNodePattern) {
+        match pattern {
+            NamedNodePattern::NamedNode(p) => {
+                self.predicates.insert(p.clone());
+            }
+            NamedNodePattern::Variable(v) => {
+                self.variables.insert(v.clone());
+            }
+        }
+    }
+
+    fn add_graph_pattern(&mut self, pattern: &NamedNodePattern) {
+        match pattern {
+            NamedNodePattern::NamedNode(g) => {
+                self.graphs.insert(g.clone());
+            }
+            NamedNodePattern::Variable(v) => {
+                self.variables.insert(v.clone());
+            }
+        }
+    }
+
+    fn add_graph_name_pattern(&mut self, pattern: &GraphNamePattern) {
+        match pattern {
+            GraphNamePattern::NamedNode(g) => {
+                self.graphs.insert(g.clone());
+            }
+            GraphNamePattern::DefaultGraph => (),
+            GraphNamePattern::Variable(v) => {
+                self.variables.insert(v.clone());
+            }
+        }
+    }
+}
+
+/// A triple pattern without any constant term, that requires to read all the quads of the graphs
+fn is_unbounded_triple_pattern(pattern: &TriplePattern) -> bool {
+    !matches!(
+        pattern.subject,
+        TermPattern::NamedNode(_) | TermPattern::Literal(_)
+    ) && matches!(pattern.predicate, NamedNodePattern::Variable(_))
+        && !matches!(
+            pattern.object,
+            TermPattern::NamedNode(_) | TermPattern::Literal(_)
+        )
+}
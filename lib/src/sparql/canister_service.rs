@@ -0,0 +1,164 @@
+//! [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) `SERVICE` calls to other canisters.
+
+use crate::model::NamedNode;
+use crate::sparql::algebra::Query;
+use crate::sparql::error::EvaluationError;
+use crate::sparql::model::QueryResults;
+use crate::sparql::service::{prefetchable_services, ServiceHandler};
+use crate::sparql::QueryOptions;
+use crate::store::Store;
+use ic_cdk::api::call::call;
+use ic_cdk::export::Principal;
+use sparesults::QueryResultsFormat;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The IRI scheme of the services hosted by canisters.
+const CANISTER_SCHEME: &str = "icp://";
+
+/// A [`ServiceHandler`] executing the `SERVICE <icp://{canister id}/{method}>` calls
+/// with inter-canister calls to other canisters running Oxigraph.
+///
+/// The subquery is sent as a string to the `method` of the canister `canister id`
+/// that must answer it with [`evaluate_service_query`],
+/// so several canisters can share the parts of one logical dataset.
+/// The `SERVICE` calls with another IRI scheme are rejected.
+///
+/// The inter-canister calls are asynchronous while the query evaluation is not,
+/// so the `SERVICE` calls of a query must be fetched with [`prefetch`](Self::prefetch) before evaluating it.
+/// Only the `SERVICE` calls with a constant IRI can be prefetched.
+/// The evaluation fails on the `SERVICE` calls that have not been prefetched, except if they are `SILENT`.
+///
+/// This handler is only available with the `canister-service` feature and must be used inside a canister.
+///
+/// ```no_run
+/// use oxigraph::sparql::{CanisterServiceHandler, Query, QueryOptions, QueryResults};
+/// use oxigraph::store::Store;
+///
+/// async fn federated_query(store: &Store) -> Result<usize, Box<dyn std::error::Error>> {
+///     let query = Query::parse(
+///         "SELECT * WHERE { ?s ?p ?o SERVICE <icp://rrkah-fqaaa-aaaaa-aaaaq-cai/sparql_query> { ?o ?p2 ?o2 } }",
+///         None,
+///     )?;
+///     let handler = CanisterServiceHandler::new();
+///     handler.prefetch(&query).await?;
+///     let options = QueryOptions::default().with_canister_service_handler(handler);
+///     if let QueryResults::Solutions(solutions) = store.query_opt(query, options)? {
+///         return Ok(solutions.count());
+///     }
+///     Ok(0)
+/// }
+/// # let _ = federated_query;
+/// ```
+#[derive(Clone, Default)]
+pub struct CanisterServiceHandler {
+    responses: Rc<RefCell<HashMap<(NamedNode, Query), Vec<u8>>>>,
+}
+
+impl CanisterServiceHandler {
+    /// Builds a handler without any prefetched results.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetches the results of the `SERVICE` calls of the query.
+    ///
+    /// The failures of the `SILENT` calls are ignored.
+    pub async fn prefetch(&self, query: &Query) -> Result<(), EvaluationError> {
+        for (name, service_query, silent) in prefetchable_services(query) {
+            let key = (name, service_query);
+            if self.responses.borrow().contains_key(&key) {
+                continue;
+            }
+            match fetch(&key.0, &key.1).await {
+                Ok(response) => {
+                    self.responses.borrow_mut().insert(key, response);
+                }
+                Err(_) if silent => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServiceHandler for CanisterServiceHandler {
+    type Error = EvaluationError;
+
+    fn handle(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+    ) -> Result<QueryResults, EvaluationError> {
+        let body = self
+            .responses
+            .borrow()
+            .get(&(service_name, query))
+            .cloned()
+            .ok_or_else(|| {
+                EvaluationError::msg(
+                    "The SERVICE call has not been prefetched with CanisterServiceHandler::prefetch",
+                )
+            })?;
+        Ok(QueryResults::read(
+            std::io::Cursor::new(body),
+            QueryResultsFormat::Json,
+        )?)
+    }
+}
+
+/// Answers a subquery sent by a [`CanisterServiceHandler`] of another canister.
+///
+/// The results are returned in the SPARQL JSON format.
+/// The `SERVICE` calls nested in the subquery are not supported.
+///
+/// It should be wrapped in a canister update method named as in the `SERVICE` IRIs:
+/// ```ignore
+/// #[ic_cdk_macros::update]
+/// fn sparql_query(query: String) -> Result<Vec<u8>, String> {
+///     STORE.with(|store| oxigraph::sparql::evaluate_service_query(store, &query))
+/// }
+/// ```
+pub fn evaluate_service_query(store: &Store, query: &str) -> Result<Vec<u8>, String> {
+    let results = store
+        .query_opt(query, QueryOptions::default().without_service_handler())
+        .map_err(|e| e.to_string())?;
+    let mut body = Vec::new();
+    results
+        .write(&mut body, QueryResultsFormat::Json)
+        .map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+async fn fetch(service_name: &NamedNode, query: &Query) -> Result<Vec<u8>, EvaluationError> {
+    let (canister, method) = parse_service_name(service_name)?;
+    let (result,): (Result<Vec<u8>, String>,) = call(canister, method, (query.to_string(),))
+        .await
+        .map_err(|(code, message)| {
+            EvaluationError::msg(format!(
+                "The SERVICE call to {service_name} failed: {code:?}: {message}"
+            ))
+        })?;
+    result.map_err(|message| {
+        EvaluationError::msg(format!("{service_name} returned an error: {message}"))
+    })
+}
+
+/// Splits an `icp://{canister id}/{method}` IRI.
+fn parse_service_name(service_name: &NamedNode) -> Result<(Principal, &str), EvaluationError> {
+    let (canister, method) = service_name
+        .as_str()
+        .strip_prefix(CANISTER_SCHEME)
+        .and_then(|rest| rest.split_once('/'))
+        .filter(|(_, method)| !method.is_empty())
+        .ok_or_else(|| {
+            EvaluationError::msg(format!(
+                "{service_name} is not a canister service IRI of the form icp://{{canister id}}/{{method}}"
+            ))
+        })?;
+    let canister = Principal::from_text(canister)
+        .map_err(|e| EvaluationError::msg(format!("Invalid canister id in {service_name}: {e}")))?;
+    Ok((canister, method))
+}
@@ -1,17 +1,20 @@
-use crate::model::TermRef;
+use crate::model::{GraphName, TermRef};
 use crate::sparql::algebra::QueryDataset;
 use crate::sparql::EvaluationError;
 use crate::storage::numeric_encoder::{insert_term, EncodedQuad, EncodedTerm, StrHash, StrLookup};
-use crate::storage::{StorageError, StorageReader};
+use crate::storage::{ObjectClass, ScanStats, StorageError, StorageReader};
 use std::cell::RefCell;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::empty;
+use std::ops::Bound;
+use std::rc::Rc;
 
 pub struct DatasetView {
     reader: StorageReader,
     extra: RefCell<HashMap<StrHash, String>>,
     dataset: EncodedDatasetSpec,
+    hidden: Rc<HashSet<EncodedTerm>>,
 }
 
 impl DatasetView {
@@ -28,9 +31,27 @@ impl DatasetView {
             reader,
             extra: RefCell::new(HashMap::default()),
             dataset,
+            hidden: Rc::default(),
         }
     }
 
+    /// Hides the given graphs: their quads are never returned, even if the query dataset explicitly lists them
+    #[must_use]
+    pub fn with_hidden_graphs(mut self, graph_names: &[GraphName]) -> Self {
+        let hidden = graph_names
+            .iter()
+            .map(|g| g.as_ref().into())
+            .collect::<HashSet<EncodedTerm>>();
+        if let Some(default) = &mut self.dataset.default {
+            default.retain(|g| !hidden.contains(g));
+        }
+        if let Some(named) = &mut self.dataset.named {
+            named.retain(|g| !hidden.contains(g));
+        }
+        self.hidden = Rc::new(hidden);
+        self
+    }
+
     fn store_encoded_quads_for_pattern(
         &self,
         subject: Option<&EncodedTerm>,
@@ -38,12 +59,43 @@ impl DatasetView {
         object: Option<&EncodedTerm>,
         graph_name: Option<&EncodedTerm>,
     ) -> impl Iterator<Item = Result<EncodedQuad, EvaluationError>> + 'static {
+        let hidden = Rc::clone(&self.hidden);
         self.reader
             .quads_for_pattern(subject, predicate, object, graph_name)
+            .filter(move |quad| match quad {
+                Err(_) => true,
+                Ok(quad) => !hidden.contains(&quad.graph_name),
+            })
+            .map(|t| t.map_err(Into::into))
+    }
+
+    /// Returns the quads matching the pattern in the given graphs, each graph being only scanned
+    /// after the previous ones have been exhausted
+    fn store_encoded_quads_for_pattern_in_graphs(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_names: &[EncodedTerm],
+    ) -> impl Iterator<Item = Result<EncodedQuad, EvaluationError>> + 'static {
+        let reader = self.reader.clone();
+        let subject = subject.cloned();
+        let predicate = predicate.cloned();
+        let object = object.cloned();
+        graph_names
+            .to_vec()
+            .into_iter()
+            .flat_map(move |graph_name| {
+                reader.quads_for_pattern(
+                    subject.as_ref(),
+                    predicate.as_ref(),
+                    object.as_ref(),
+                    Some(&graph_name),
+                )
+            })
             .map(|t| t.map_err(Into::into))
     }
 
-    #[allow(clippy::needless_collect)]
     pub fn encoded_quads_for_pattern(
         &self,
         subject: Option<&EncodedTerm>,
@@ -74,26 +126,23 @@ impl DatasetView {
                             }),
                         )
                     } else {
-                        let iters = default_graph_graphs
-                            .iter()
-                            .map(|graph_name| {
-                                self.store_encoded_quads_for_pattern(
-                                    subject,
-                                    predicate,
-                                    object,
-                                    Some(graph_name),
-                                )
-                            })
-                            .collect::<Vec<_>>();
-                        Box::new(iters.into_iter().flatten().map(|quad| {
-                            let quad = quad?;
-                            Ok(EncodedQuad::new(
-                                quad.subject,
-                                quad.predicate,
-                                quad.object,
-                                EncodedTerm::DefaultGraph,
-                            ))
-                        }))
+                        Box::new(
+                            self.store_encoded_quads_for_pattern_in_graphs(
+                                subject,
+                                predicate,
+                                object,
+                                default_graph_graphs,
+                            )
+                            .map(|quad| {
+                                let quad = quad?;
+                                Ok(EncodedQuad::new(
+                                    quad.subject,
+                                    quad.predicate,
+                                    quad.object,
+                                    EncodedTerm::DefaultGraph,
+                                ))
+                            }),
+                        )
                     }
                 } else {
                     Box::new(
@@ -109,11 +158,12 @@ impl DatasetView {
                             }),
                     )
                 }
-            } else if self
-                .dataset
-                .named
-                .as_ref()
-                .map_or(true, |d| d.contains(graph_name))
+            } else if !self.hidden.contains(graph_name)
+                && self
+                    .dataset
+                    .named
+                    .as_ref()
+                    .map_or(true, |d| d.contains(graph_name))
             {
                 Box::new(self.store_encoded_quads_for_pattern(
                     subject,
@@ -125,18 +175,12 @@ impl DatasetView {
                 Box::new(empty())
             }
         } else if let Some(named_graphs) = &self.dataset.named {
-            let iters = named_graphs
-                .iter()
-                .map(|graph_name| {
-                    self.store_encoded_quads_for_pattern(
-                        subject,
-                        predicate,
-                        object,
-                        Some(graph_name),
-                    )
-                })
-                .collect::<Vec<_>>();
-            Box::new(iters.into_iter().flatten())
+            Box::new(self.store_encoded_quads_for_pattern_in_graphs(
+                subject,
+                predicate,
+                object,
+                named_graphs,
+            ))
         } else {
             Box::new(
                 self.store_encoded_quads_for_pattern(subject, predicate, object, None)
@@ -148,6 +192,196 @@ impl DatasetView {
         }
     }
 
+    /// Returns the number of quads [`encoded_quads_for_pattern`](Self::encoded_quads_for_pattern) would return
+    /// without decoding them
+    pub fn count_quads_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<usize, EvaluationError> {
+        if !self.hidden.is_empty() {
+            // The store counts include the quads of the hidden graphs
+            return self
+                .encoded_quads_for_pattern(subject, predicate, object, graph_name)
+                .try_fold(0, |count, quad| quad.map(|_| count + 1));
+        }
+        let count = |graph_name: Option<&EncodedTerm>| {
+            self.reader
+                .count_quads_for_pattern(subject, predicate, object, graph_name)
+        };
+        Ok(if let Some(graph_name) = graph_name {
+            if graph_name.is_default_graph() {
+                if let Some(default_graph_graphs) = &self.dataset.default {
+                    default_graph_graphs
+                        .iter()
+                        .map(|graph_name| count(Some(graph_name)))
+                        .sum::<Result<usize, _>>()?
+                } else {
+                    count(None)?
+                }
+            } else if self
+                .dataset
+                .named
+                .as_ref()
+                .map_or(true, |d| d.contains(graph_name))
+            {
+                count(Some(graph_name))?
+            } else {
+                0
+            }
+        } else if let Some(named_graphs) = &self.dataset.named {
+            named_graphs
+                .iter()
+                .map(|graph_name| count(Some(graph_name)))
+                .sum::<Result<usize, _>>()?
+        } else {
+            // The quads of the named graphs are all the quads but the ones of the default graph
+            count(None)?.saturating_sub(count(Some(&EncodedTerm::DefaultGraph))?)
+        })
+    }
+
+    /// Returns the distinct terms at `position` (`0` for the subject, `1` for the predicate and `2` for the object)
+    /// of the quads [`encoded_quads_for_pattern`](Self::encoded_quads_for_pattern) would return
+    ///
+    /// Returns `None` if they can't be read from a store index without scanning all the quads.
+    pub fn distinct_terms_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+        position: usize,
+    ) -> Option<impl Iterator<Item = Result<EncodedTerm, EvaluationError>>> {
+        let graph_name = graph_name?;
+        let graph_name = if graph_name.is_default_graph() {
+            // The terms of several graphs would have to be deduplicated
+            if let Some([graph_name]) = self.dataset.default.as_deref() {
+                graph_name
+            } else {
+                return None;
+            }
+        } else if !self.hidden.contains(graph_name)
+            && self
+                .dataset
+                .named
+                .as_ref()
+                .map_or(true, |d| d.contains(graph_name))
+        {
+            graph_name
+        } else {
+            return None;
+        };
+        Some(
+            self.reader
+                .distinct_terms_for_pattern(subject, predicate, object, graph_name, position)?
+                .map(|t| t.map_err(Into::into)),
+        )
+    }
+
+    /// Returns the number of distinct graph names of the quads
+    /// [`encoded_quads_for_pattern`](Self::encoded_quads_for_pattern) would return
+    ///
+    /// The named graphs are listed from the graph index and each of them is only probed for a matching quad.
+    pub fn count_graphs_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<usize, EvaluationError> {
+        if graph_name.is_some() {
+            return Ok(usize::from(
+                self.encoded_quads_for_pattern(subject, predicate, object, graph_name)
+                    .next()
+                    .transpose()?
+                    .is_some(),
+            ));
+        }
+        let contains = |graph_name: &EncodedTerm| -> Result<bool, EvaluationError> {
+            Ok(
+                if subject.is_none()
+                    && predicate.is_none()
+                    && object.is_none()
+                    && !self.reader.has_read_filter()
+                {
+                    self.reader.graph_len(graph_name)? > 0
+                } else {
+                    self.reader
+                        .quads_for_pattern(subject, predicate, object, Some(graph_name))
+                        .next()
+                        .transpose()?
+                        .is_some()
+                },
+            )
+        };
+        let mut count = 0;
+        if let Some(named_graphs) = &self.dataset.named {
+            for graph_name in named_graphs {
+                if contains(graph_name)? {
+                    count += 1;
+                }
+            }
+        } else {
+            for graph_name in self.reader.named_graphs() {
+                let graph_name = graph_name?;
+                if !self.hidden.contains(&graph_name) && contains(&graph_name)? {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// The number of quads of the store
+    ///
+    /// It ignores the dataset restrictions.
+    pub fn store_len(&self) -> Result<usize, StorageError> {
+        self.reader.len()
+    }
+
+    /// The number of quads of the store with the predicate and, if set, an object of the given class
+    ///
+    /// It is read from the store statistics and ignores the dataset restrictions.
+    pub fn predicate_cardinality(
+        &self,
+        predicate: &EncodedTerm,
+        object_class: Option<ObjectClass>,
+    ) -> Result<u64, StorageError> {
+        self.reader.predicate_count(predicate, object_class)
+    }
+
+    /// Returns the literals of the store with the given datatype, `None` if the literal index is not available
+    pub fn literals_with_datatype(&self, datatype: &EncodedTerm) -> Option<Vec<EncodedTerm>> {
+        self.reader.literals_with_datatype(datatype).ok().flatten()
+    }
+
+    /// Returns the literals of the store with the given language tag, `None` if the literal index is not available
+    pub fn literals_with_language(&self, language: &str) -> Option<Vec<EncodedTerm>> {
+        self.reader.literals_with_language(language).ok().flatten()
+    }
+
+    /// Returns the distinct objects of the predicate in the range, `None` if the range index is not available
+    /// or if there are more than `limit` of them
+    pub fn objects_in_range(
+        &self,
+        predicate: &EncodedTerm,
+        start: Bound<&EncodedTerm>,
+        end: Bound<&EncodedTerm>,
+        limit: usize,
+    ) -> Option<Vec<EncodedTerm>> {
+        self.reader
+            .objects_in_range(predicate, start, end, limit)
+            .ok()
+            .flatten()
+    }
+
+    /// The work done by the index scans of the query
+    pub fn scan_stats(&self) -> Rc<ScanStats> {
+        self.reader.scan_stats()
+    }
+
     pub fn encode_term<'a>(&self, term: impl Into<TermRef<'a>>) -> EncodedTerm {
         let term = term.into();
         let encoded = term.into();
@@ -21,6 +21,18 @@ pub enum EvaluationError {
     Io(io::Error),
     /// An error returned during the query evaluation itself (not supported custom function...).
     Query(QueryError),
+    /// The evaluation has been cancelled because it has exceeded its instruction or time limit.
+    ///
+    /// See [`QueryOptions::with_max_instructions`](super::QueryOptions::with_max_instructions).
+    Cancelled,
+    /// The query has returned more results than allowed.
+    ///
+    /// See [`QueryOptions::with_max_results`](super::QueryOptions::with_max_results).
+    TooManyResults,
+    /// The joins and aggregations of the evaluation have produced more bindings than allowed.
+    ///
+    /// See [`QueryOptions::with_max_intermediate_bindings`](super::QueryOptions::with_max_intermediate_bindings).
+    TooManyIntermediateBindings,
 }
 
 /// An error returned during the query evaluation itself (not supported custom function...).
@@ -45,6 +57,15 @@ impl fmt::Display for EvaluationError {
             Self::ResultsParsing(error) => error.fmt(f),
             Self::Io(error) => error.fmt(f),
             Self::Query(error) => error.fmt(f),
+            Self::Cancelled => write!(
+                f,
+                "The evaluation has been cancelled because it has exceeded its instruction or time limit"
+            ),
+            Self::TooManyResults => write!(f, "The query has returned more results than allowed"),
+            Self::TooManyIntermediateBindings => write!(
+                f,
+                "The evaluation has produced more intermediate bindings than allowed"
+            ),
         }
     }
 }
@@ -69,6 +90,7 @@ impl error::Error for EvaluationError {
             Self::ResultsParsing(e) => Some(e),
             Self::Io(e) => Some(e),
             Self::Query(e) => Some(e),
+            Self::Cancelled | Self::TooManyResults | Self::TooManyIntermediateBindings => None,
         }
     }
 }
@@ -153,6 +175,10 @@ impl From<EvaluationError> for io::Error {
             EvaluationError::Io(error) => error,
             EvaluationError::Storage(error) => error.into(),
             EvaluationError::Query(error) => Self::new(io::ErrorKind::Other, error),
+            EvaluationError::Cancelled => Self::new(io::ErrorKind::TimedOut, error),
+            EvaluationError::TooManyResults | EvaluationError::TooManyIntermediateBindings => {
+                Self::new(io::ErrorKind::Other, error)
+            }
         }
     }
 }
@@ -0,0 +1,361 @@
+use crate::sparql::algebra::Query;
+use crate::sparql::dataset::DatasetView;
+use crate::sparql::error::EvaluationError;
+use crate::sparql::plan::{PatternValue, PlanNode, PlanPropertyPath};
+use crate::sparql::plan_builder::PlanBuilder;
+use crate::storage::{ObjectClass, StorageReader};
+use std::collections::{BTreeSet, HashMap};
+
+/// Instructions spent to read and decode an index entry, a rough average measured on the heap storage
+const INSTRUCTIONS_PER_INDEX_ENTRY: f64 = 5_000.;
+/// Instructions spent by an operator to handle a solution: joining, filtering, hashing...
+const INSTRUCTIONS_PER_SOLUTION: f64 = 2_000.;
+/// Fraction of the solutions kept by a filter
+const FILTER_SELECTIVITY: f64 = 0.5;
+
+/// A prediction of the number of results of a query and of the instructions needed to evaluate it.
+///
+/// It is computed by [`Store::estimate_query_cost`](crate::store::Store::estimate_query_cost) from the query plan
+/// and the store statistics, without evaluating the query.
+/// It is only an order of magnitude: the statistics do not describe the correlations between the quads
+/// and the calls to `SERVICE` are not taken into account.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub struct CostEstimate {
+    results: u64,
+    instructions: u64,
+}
+
+impl CostEstimate {
+    /// The estimated number of results of the query.
+    #[inline]
+    pub fn results(&self) -> u64 {
+        self.results
+    }
+
+    /// The estimated number of instructions, as counted by `ic_cdk::api::performance_counter`, to evaluate the query and read all its results.
+    #[inline]
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+}
+
+pub(crate) fn estimate_query_cost(
+    reader: StorageReader,
+    query: &Query,
+) -> Result<CostEstimate, EvaluationError> {
+    let dataset = DatasetView::new(reader, &query.dataset);
+    let (plan, _) = PlanBuilder::build(&dataset, query.pattern(), true, &HashMap::new(), false)?;
+    let estimator = Estimator {
+        quads: to_float(u64::try_from(dataset.store_len()?).unwrap_or(u64::MAX)),
+        dataset: &dataset,
+    };
+    let estimate = estimator.estimate(&plan, &BTreeSet::new())?;
+    Ok(CostEstimate {
+        results: to_integer(estimate.results),
+        instructions: to_integer(estimate.instructions),
+    })
+}
+
+/// The estimated cost of a single evaluation of a plan node
+struct NodeEstimate {
+    results: f64,
+    instructions: f64,
+}
+
+struct Estimator<'a> {
+    dataset: &'a DatasetView,
+    quads: f64,
+}
+
+impl Estimator<'_> {
+    /// Estimates a node evaluated with the `bound` variables already set
+    fn estimate(
+        &self,
+        node: &PlanNode,
+        bound: &BTreeSet<usize>,
+    ) -> Result<NodeEstimate, EvaluationError> {
+        Ok(match node {
+            PlanNode::StaticBindings { encoded_tuples, .. } => {
+                let results = to_float(encoded_tuples.len() as u64);
+                NodeEstimate {
+                    results,
+                    instructions: results * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::Service { .. } => NodeEstimate {
+                results: 1.,
+                instructions: 0.,
+            },
+            PlanNode::QuadPattern {
+                subject,
+                predicate,
+                object,
+                ..
+            } => {
+                let results = self.pattern_cardinality(subject, predicate, object, bound)?;
+                NodeEstimate {
+                    results,
+                    instructions: results * INSTRUCTIONS_PER_INDEX_ENTRY,
+                }
+            }
+            PlanNode::DistinctTerms {
+                subject,
+                predicate,
+                object,
+                ..
+            } => {
+                // Each distinct term is read with a seek
+                let results = self
+                    .pattern_cardinality(subject, predicate, object, bound)?
+                    .sqrt();
+                NodeEstimate {
+                    results,
+                    instructions: results * INSTRUCTIONS_PER_INDEX_ENTRY,
+                }
+            }
+            PlanNode::CountQuads {
+                subject,
+                predicate,
+                object,
+                ..
+            } => NodeEstimate {
+                results: 1.,
+                // The keys are counted without being decoded
+                instructions: self.pattern_cardinality(subject, predicate, object, bound)?
+                    * INSTRUCTIONS_PER_INDEX_ENTRY
+                    / 4.,
+            },
+            PlanNode::PathPattern {
+                subject,
+                path,
+                object,
+                ..
+            } => {
+                let mut results = self.path_cardinality(path)?;
+                for value in [subject, object] {
+                    if is_bound(value, bound) {
+                        results = results.sqrt();
+                    }
+                }
+                NodeEstimate {
+                    results,
+                    instructions: results * INSTRUCTIONS_PER_INDEX_ENTRY * 2.,
+                }
+            }
+            PlanNode::HashJoin { left, right } => {
+                let left_estimate = self.estimate(left, bound)?;
+                let right_estimate = self.estimate(right, bound)?;
+                let results = if left
+                    .used_variables()
+                    .intersection(&right.used_variables())
+                    .next()
+                    .is_some()
+                {
+                    left_estimate.results.min(right_estimate.results)
+                } else {
+                    left_estimate.results * right_estimate.results
+                };
+                NodeEstimate {
+                    results,
+                    instructions: left_estimate.instructions
+                        + right_estimate.instructions
+                        + (left_estimate.results + right_estimate.results)
+                            * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::ForLoopJoin { left, right } => {
+                let left_estimate = self.estimate(left, bound)?;
+                let right_estimate = self.estimate(right, &with_bound(bound, left))?;
+                NodeEstimate {
+                    results: left_estimate.results * right_estimate.results,
+                    instructions: left_estimate.instructions
+                        + left_estimate.results * right_estimate.instructions,
+                }
+            }
+            PlanNode::AntiJoin { left, right } | PlanNode::HashLeftJoin { left, right, .. } => {
+                let left_estimate = self.estimate(left, bound)?;
+                let right_estimate = self.estimate(right, bound)?;
+                NodeEstimate {
+                    results: left_estimate.results,
+                    instructions: left_estimate.instructions
+                        + right_estimate.instructions
+                        + (left_estimate.results + right_estimate.results)
+                            * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::ForLoopLeftJoin { left, right, .. } => {
+                let left_estimate = self.estimate(left, bound)?;
+                let right_estimate = self.estimate(right, &with_bound(bound, left))?;
+                NodeEstimate {
+                    results: left_estimate.results * right_estimate.results.max(1.),
+                    instructions: left_estimate.instructions
+                        + left_estimate.results * right_estimate.instructions,
+                }
+            }
+            PlanNode::Filter { child, .. } => {
+                let child_estimate = self.estimate(child, bound)?;
+                NodeEstimate {
+                    results: child_estimate.results * FILTER_SELECTIVITY,
+                    instructions: child_estimate.instructions
+                        + child_estimate.results * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::Union { children } => {
+                let mut estimate = NodeEstimate {
+                    results: 0.,
+                    instructions: 0.,
+                };
+                for child in children {
+                    let child_estimate = self.estimate(child, bound)?;
+                    estimate.results += child_estimate.results;
+                    estimate.instructions += child_estimate.instructions;
+                }
+                estimate
+            }
+            PlanNode::Extend { child, .. }
+            | PlanNode::HashDeduplicate { child }
+            | PlanNode::Reduced { child } => {
+                let child_estimate = self.estimate(child, bound)?;
+                NodeEstimate {
+                    results: child_estimate.results,
+                    instructions: child_estimate.instructions
+                        + child_estimate.results * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::Sort { child, .. } => {
+                let child_estimate = self.estimate(child, bound)?;
+                NodeEstimate {
+                    results: child_estimate.results,
+                    instructions: child_estimate.instructions
+                        + child_estimate.results
+                            * child_estimate.results.max(1.).log2().max(1.)
+                            * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+            PlanNode::Skip { child, count } => {
+                let child_estimate = self.estimate(child, bound)?;
+                NodeEstimate {
+                    results: (child_estimate.results - to_float(*count as u64)).max(0.),
+                    instructions: child_estimate.instructions,
+                }
+            }
+            PlanNode::Limit { child, count } => {
+                let child_estimate = self.estimate(child, bound)?;
+                let results = child_estimate.results.min(to_float(*count as u64));
+                // The child is evaluated lazily and stops with the limit
+                let read_fraction = if child_estimate.results > 0. {
+                    results / child_estimate.results
+                } else {
+                    1.
+                };
+                NodeEstimate {
+                    results,
+                    instructions: child_estimate.instructions * read_fraction,
+                }
+            }
+            PlanNode::Project { child, mapping } => {
+                let child_bound = mapping
+                    .iter()
+                    .filter(|(_, output)| bound.contains(&output.encoded))
+                    .map(|(input, _)| input.encoded)
+                    .collect();
+                self.estimate(child, &child_bound)?
+            }
+            PlanNode::Aggregate {
+                child,
+                key_variables,
+                ..
+            } => {
+                let child_estimate = self.estimate(child, bound)?;
+                NodeEstimate {
+                    results: if key_variables.is_empty() {
+                        1.
+                    } else {
+                        child_estimate.results.sqrt()
+                    },
+                    instructions: child_estimate.instructions
+                        + child_estimate.results * INSTRUCTIONS_PER_SOLUTION,
+                }
+            }
+        })
+    }
+
+    /// The number of quads matching a pattern: the statistics give it for a constant predicate
+    /// and each bound subject or object is assumed to divide it by its square root
+    fn pattern_cardinality(
+        &self,
+        subject: &PatternValue,
+        predicate: &PatternValue,
+        object: &PatternValue,
+        bound: &BTreeSet<usize>,
+    ) -> Result<f64, EvaluationError> {
+        let mut cardinality = if let PatternValue::Constant(predicate) = predicate {
+            let object_class = if let PatternValue::Constant(object) = object {
+                Some(ObjectClass::of(&object.encoded))
+            } else {
+                None
+            };
+            to_float(
+                self.dataset
+                    .predicate_cardinality(&predicate.encoded, object_class)?,
+            )
+        } else if is_bound(predicate, bound) {
+            self.quads.sqrt()
+        } else {
+            self.quads
+        };
+        for value in [subject, object] {
+            if is_bound(value, bound) {
+                cardinality = cardinality.sqrt();
+            }
+        }
+        Ok(cardinality)
+    }
+
+    /// The number of pairs of nodes linked by a path
+    fn path_cardinality(&self, path: &PlanPropertyPath) -> Result<f64, EvaluationError> {
+        Ok(match path {
+            PlanPropertyPath::Path(predicate) => to_float(
+                self.dataset
+                    .predicate_cardinality(&predicate.encoded, None)?,
+            ),
+            PlanPropertyPath::Reverse(path) | PlanPropertyPath::ZeroOrOne(path) => {
+                self.path_cardinality(path)?
+            }
+            PlanPropertyPath::Sequence(a, b) => {
+                self.path_cardinality(a)?.max(self.path_cardinality(b)?)
+            }
+            PlanPropertyPath::Alternative(a, b) => {
+                self.path_cardinality(a)? + self.path_cardinality(b)?
+            }
+            PlanPropertyPath::ZeroOrMore(_)
+            | PlanPropertyPath::OneOrMore(_)
+            | PlanPropertyPath::NegatedPropertySet(_) => self.quads,
+        })
+    }
+}
+
+fn is_bound(value: &PatternValue, bound: &BTreeSet<usize>) -> bool {
+    match value {
+        PatternValue::Constant(_) => true,
+        PatternValue::Variable(v) => bound.contains(&v.encoded),
+        PatternValue::TriplePattern(_) => false,
+    }
+}
+
+/// The variables bound when evaluating the right side of a for loop join
+fn with_bound(bound: &BTreeSet<usize>, left: &PlanNode) -> BTreeSet<usize> {
+    let mut bound = bound.clone();
+    bound.extend(left.always_bound_variables());
+    bound
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn to_float(value: u64) -> f64 {
+    value as f64
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_integer(value: f64) -> u64 {
+    value.round() as u64 // Saturating cast
+}
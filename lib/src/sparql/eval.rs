@@ -5,11 +5,14 @@ use crate::sparql::dataset::DatasetView;
 use crate::sparql::error::EvaluationError;
 use crate::sparql::model::*;
 use crate::sparql::plan::*;
+use crate::sparql::regex_cache::{RegexCache, RegexCacheStats};
 use crate::sparql::time::now;
 use crate::sparql::service::ServiceHandler;
 use crate::storage::numeric_encoder::*;
 use crate::storage::small_string::SmallString;
+use crate::storage::{ObjectClass, ScanStats};
 use digest::Digest;
+use ic_cdk::api::performance_counter;
 use md5::Md5;
 use oxilangtag::LanguageTag;
 use oxiri::Iri;
@@ -19,14 +22,16 @@ use rand::random;
 use regex::{Regex, RegexBuilder};
 use sha1::Sha1;
 use sha2::{Sha256, Sha384, Sha512};
+use siphasher::sip128::{Hasher128, SipHasher24};
 use spargebra::algebra::GraphPattern;
 use std::cell::Cell;
 use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::iter::Iterator;
 use std::iter::{empty, once};
+use std::mem::replace;
 use std::rc::Rc;
 use std::str;
 use std::time::Duration as StdDuration;
@@ -44,6 +49,12 @@ pub struct SimpleEvaluator {
     service_handler: Rc<dyn ServiceHandler<Error = EvaluationError>>,
     custom_functions: Rc<CustomFunctionRegistry>,
     run_stats: bool,
+    deadline: Option<Rc<Deadline>>,
+    max_results: Option<usize>,
+    bindings_budget: Option<Rc<BindingsBudget>>,
+    blank_nodes: Rc<BlankNodeGenerator>,
+    regex_cache: RegexCache,
+    regex_cache_stats: Rc<RegexCacheStats>,
 }
 
 impl SimpleEvaluator {
@@ -61,9 +72,52 @@ impl SimpleEvaluator {
             service_handler,
             custom_functions,
             run_stats,
+            deadline: None,
+            max_results: None,
+            bindings_budget: None,
+            blank_nodes: Rc::new(BlankNodeGenerator::new(None)),
+            regex_cache: RegexCache::default(),
+            regex_cache_stats: Rc::default(),
         }
     }
 
+    /// Uses the given cache for the regular expressions compiled during the evaluation
+    /// and counts the cache lookups in `stats`.
+    #[must_use]
+    pub fn with_regex_cache(mut self, cache: RegexCache, stats: Rc<RegexCacheStats>) -> Self {
+        self.regex_cache = cache;
+        self.regex_cache_stats = stats;
+        self
+    }
+
+    /// Uses the given generator for the blank nodes returned by `BNODE()`.
+    #[must_use]
+    pub fn with_blank_node_generator(mut self, blank_nodes: Rc<BlankNodeGenerator>) -> Self {
+        self.blank_nodes = blank_nodes;
+        self
+    }
+
+    /// Cancels the evaluation when the deadline is exceeded.
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Option<Rc<Deadline>>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Fails the evaluation when it returns more than `max_results` results
+    /// or when its joins and aggregations produce more than `max_intermediate_bindings` bindings.
+    #[must_use]
+    pub fn with_limits(
+        mut self,
+        max_results: Option<usize>,
+        max_intermediate_bindings: Option<usize>,
+    ) -> Self {
+        self.max_results = max_results;
+        self.bindings_budget =
+            max_intermediate_bindings.map(|max| Rc::new(BindingsBudget::new(max)));
+        self
+    }
+
     #[allow(clippy::rc_buffer)]
     pub fn evaluate_select_plan(
         &self,
@@ -74,7 +128,7 @@ impl SimpleEvaluator {
         (
             QueryResults::Solutions(decode_bindings(
                 Rc::clone(&self.dataset),
-                eval(EncodedTuple::with_capacity(variables.len())),
+                self.limit_results(eval(EncodedTuple::with_capacity(variables.len()))),
                 variables,
             )),
             stats,
@@ -106,13 +160,13 @@ impl SimpleEvaluator {
         let (eval, stats) = self.plan_evaluator(plan);
         (
             QueryResults::Graph(QueryTripleIter {
-                iter: Box::new(ConstructIterator {
+                iter: self.limit_results(Box::new(ConstructIterator {
                     eval: self.clone(),
                     iter: eval(from),
                     template,
                     buffered_results: Vec::default(),
                     bnodes: Vec::default(),
-                }),
+                })),
             }),
             stats,
         )
@@ -126,11 +180,11 @@ impl SimpleEvaluator {
         let (eval, stats) = self.plan_evaluator(plan);
         (
             QueryResults::Graph(QueryTripleIter {
-                iter: Box::new(DescribeIterator {
+                iter: self.limit_results(Box::new(DescribeIterator {
                     eval: self.clone(),
                     iter: eval(from),
                     quads: Box::new(empty()),
-                }),
+                })),
             }),
             stats,
         )
@@ -144,6 +198,7 @@ impl SimpleEvaluator {
         Rc<PlanNodeWithStats>,
     ) {
         let mut stat_children = Vec::new();
+        let visited_nodes = Rc::new(Cell::new(0));
         let mut evaluator: Rc<dyn Fn(EncodedTuple) -> EncodedTuplesIterator> = match node.as_ref() {
             PlanNode::StaticBindings { encoded_tuples, .. } => {
                 let tuples = encoded_tuples.clone();
@@ -226,6 +281,118 @@ impl SimpleEvaluator {
                     }))
                 })
             }
+            PlanNode::CountQuads {
+                subject,
+                predicate,
+                object,
+                graph_name,
+                distinct_graphs,
+                variable,
+            } => {
+                let subject = TupleSelector::from(subject);
+                let predicate = TupleSelector::from(predicate);
+                let object = TupleSelector::from(object);
+                let graph_name = TupleSelector::from(graph_name);
+                let distinct_graphs = *distinct_graphs;
+                let variable = variable.encoded;
+                let dataset = Rc::clone(&self.dataset);
+                Rc::new(move |from| {
+                    let subject = get_pattern_value(&subject, &from);
+                    let predicate = get_pattern_value(&predicate, &from);
+                    let object = get_pattern_value(&object, &from);
+                    let graph_name = get_pattern_value(&graph_name, &from);
+                    let count = if distinct_graphs {
+                        dataset.count_graphs_for_pattern(
+                            subject.as_ref(),
+                            predicate.as_ref(),
+                            object.as_ref(),
+                            graph_name.as_ref(),
+                        )
+                    } else {
+                        dataset.count_quads_for_pattern(
+                            subject.as_ref(),
+                            predicate.as_ref(),
+                            object.as_ref(),
+                            graph_name.as_ref(),
+                        )
+                    };
+                    Box::new(once(count.and_then(|count| {
+                        let count = i64::try_from(count).map_err(|_| {
+                            EvaluationError::msg("The count does not fit in a 64 bits integer")
+                        })?;
+                        let mut result = EncodedTuple::with_capacity(from.capacity());
+                        result.set(variable, count.into());
+                        Ok(result)
+                    })))
+                })
+            }
+            PlanNode::DistinctTerms {
+                subject,
+                predicate,
+                object,
+                graph_name,
+                variable,
+            } => {
+                let variable = variable.encoded;
+                let position = [subject, predicate, object].iter().position(
+                    |value| matches!(value, PatternValue::Variable(v) if v.encoded == variable),
+                );
+                let subject = TupleSelector::from(subject);
+                let predicate = TupleSelector::from(predicate);
+                let object = TupleSelector::from(object);
+                let graph_name = TupleSelector::from(graph_name);
+                let dataset = Rc::clone(&self.dataset);
+                Rc::new(move |from| {
+                    let input_subject = get_pattern_value(&subject, &from);
+                    let input_predicate = get_pattern_value(&predicate, &from);
+                    let input_object = get_pattern_value(&object, &from);
+                    let input_graph_name = get_pattern_value(&graph_name, &from);
+                    if let Some(terms) = position.and_then(|position| {
+                        dataset.distinct_terms_for_pattern(
+                            input_subject.as_ref(),
+                            input_predicate.as_ref(),
+                            input_object.as_ref(),
+                            input_graph_name.as_ref(),
+                            position,
+                        )
+                    }) {
+                        return Box::new(terms.map(move |term| {
+                            let mut new_tuple = from.clone();
+                            new_tuple.set(variable, term?);
+                            Ok(new_tuple)
+                        }));
+                    }
+                    // No index sorted by the variable: we deduplicate its values
+                    let iter = dataset.encoded_quads_for_pattern(
+                        input_subject.as_ref(),
+                        input_predicate.as_ref(),
+                        input_object.as_ref(),
+                        input_graph_name.as_ref(),
+                    );
+                    let subject = subject.clone();
+                    let predicate = predicate.clone();
+                    let object = object.clone();
+                    let graph_name = graph_name.clone();
+                    let mut already_seen = HashSet::new();
+                    Box::new(iter.filter_map(move |quad| match quad {
+                        Ok(quad) => {
+                            let mut quad_tuple = from.clone();
+                            put_pattern_value(&subject, quad.subject, &mut quad_tuple)?;
+                            put_pattern_value(&predicate, quad.predicate, &mut quad_tuple)?;
+                            put_pattern_value(&object, quad.object, &mut quad_tuple)?;
+                            put_pattern_value(&graph_name, quad.graph_name, &mut quad_tuple)?;
+                            let value = quad_tuple.get(variable)?;
+                            if !already_seen.insert(value.clone()) {
+                                return None;
+                            }
+                            let mut new_tuple = from.clone();
+                            new_tuple.set(variable, value.clone());
+                            Some(Ok(new_tuple))
+                        }
+                        Err(error) => Some(Err(error)),
+                    }))
+                })
+            }
             PlanNode::PathPattern {
                 subject,
                 path,
@@ -237,12 +404,14 @@ impl SimpleEvaluator {
                 let object = TupleSelector::from(object);
                 let graph_name = TupleSelector::from(graph_name);
                 let dataset = Rc::clone(&self.dataset);
+                let visited_nodes = Rc::clone(&visited_nodes);
                 Rc::new(move |from| {
                     let input_subject = get_pattern_value(&subject, &from);
                     let input_object = get_pattern_value(&object, &from);
                     let input_graph_name = get_pattern_value(&graph_name, &from);
                     let path_eval = PathEvaluator {
                         dataset: Rc::clone(&dataset),
+                        visited_nodes: Rc::clone(&visited_nodes),
                     };
                     match (input_subject, input_object, input_graph_name) {
                         (Some(input_subject), Some(input_object), Some(input_graph_name)) => {
@@ -612,6 +781,7 @@ impl SimpleEvaluator {
                         ),
                     })
                     .collect();
+                let child = self.count_bindings(child);
                 let dataset = Rc::clone(&self.dataset);
                 Rc::new(move |from| {
                     let mut errors = Vec::default();
@@ -659,6 +829,7 @@ impl SimpleEvaluator {
             PlanNode::HashDeduplicate { child } => {
                 let (child, child_stats) = self.plan_evaluator(Rc::clone(child));
                 stat_children.push(child_stats);
+                let child = self.count_bindings(child);
                 Rc::new(move |from| Box::new(hash_deduplicate(child(from))))
             }
             PlanNode::Reduced { child } => {
@@ -748,6 +919,7 @@ impl SimpleEvaluator {
                     .collect();
                 let accumulator_variables: Vec<_> =
                     aggregates.iter().map(|(_, var)| var.encoded).collect();
+                let child = self.count_bindings(child);
                 Rc::new(move |from| {
                     let tuple_size = from.capacity();
                     let key_variables = Rc::clone(&key_variables);
@@ -818,29 +990,118 @@ impl SimpleEvaluator {
                 })
             }
         };
+        let estimated_cardinality = self.estimate_cardinality(&node);
         let stats = Rc::new(PlanNodeWithStats {
             node,
             children: stat_children,
             exec_count: Cell::new(0),
-            exec_duration: Cell::new(std::time::Duration::from_secs(0)),
+            exec_evaluations: Cell::new(0),
+            exec_instructions: Cell::new(0),
+            visited_nodes,
+            read_entries: Cell::new(0),
+            stopped_scans: Cell::new(0),
+            estimated_cardinality,
         });
         if self.run_stats {
             let stats = Rc::clone(&stats);
+            let scan_stats = self.dataset.scan_stats();
             evaluator = Rc::new(move |tuple| {
-                let start = Timer::now();
-                let inner = evaluator(tuple);
-                stats
-                    .exec_duration
-                    .set(stats.exec_duration.get() + start.elapsed());
+                let start_instructions = performance_counter(0);
+                let inner = track_scans(&stats, &scan_stats, || evaluator(tuple));
+                stats.exec_evaluations.set(stats.exec_evaluations.get() + 1);
+                stats.exec_instructions.set(
+                    stats.exec_instructions.get()
+                        + performance_counter(0).saturating_sub(start_instructions),
+                );
                 Box::new(StatsIterator {
                     inner,
                     stats: Rc::clone(&stats),
+                    scan_stats: Rc::clone(&scan_stats),
                 })
             })
         }
+        if let Some(deadline) = &self.deadline {
+            let deadline = Rc::clone(deadline);
+            evaluator = Rc::new(move |tuple| {
+                Box::new(DeadlineIterator {
+                    inner: evaluator(tuple),
+                    deadline: Rc::clone(&deadline),
+                    cancelled: false,
+                })
+            })
+        }
+        if matches!(
+            *stats.node,
+            PlanNode::HashJoin { .. }
+                | PlanNode::ForLoopJoin { .. }
+                | PlanNode::HashLeftJoin { .. }
+                | PlanNode::ForLoopLeftJoin { .. }
+        ) {
+            evaluator = self.count_bindings(evaluator);
+        }
         (evaluator, stats)
     }
 
+    /// Counts the bindings returned by the evaluator against the budget of intermediate bindings, if any
+    fn count_bindings(
+        &self,
+        evaluator: Rc<dyn Fn(EncodedTuple) -> EncodedTuplesIterator>,
+    ) -> Rc<dyn Fn(EncodedTuple) -> EncodedTuplesIterator> {
+        if let Some(budget) = &self.bindings_budget {
+            let budget = Rc::clone(budget);
+            Rc::new(move |tuple| {
+                Box::new(BindingsBudgetIterator {
+                    inner: evaluator(tuple),
+                    budget: Rc::clone(&budget),
+                    exceeded: false,
+                })
+            })
+        } else {
+            evaluator
+        }
+    }
+
+    /// Fails after `max_results` results, if set
+    fn limit_results<T: 'static>(
+        &self,
+        iter: Box<dyn Iterator<Item = Result<T, EvaluationError>>>,
+    ) -> Box<dyn Iterator<Item = Result<T, EvaluationError>>> {
+        if let Some(max_results) = self.max_results {
+            Box::new(MaxResultsIterator {
+                inner: iter,
+                remaining: max_results,
+                exceeded: false,
+            })
+        } else {
+            iter
+        }
+    }
+
+    /// The number of results of a node estimated from the store statistics, if known
+    fn estimate_cardinality(&self, node: &PlanNode) -> Option<u64> {
+        match node {
+            PlanNode::StaticBindings { encoded_tuples, .. } => {
+                u64::try_from(encoded_tuples.len()).ok()
+            }
+            PlanNode::CountQuads { .. } => Some(1),
+            PlanNode::QuadPattern {
+                predicate: PatternValue::Constant(predicate),
+                object,
+                ..
+            } => {
+                let object_class = if let PatternValue::Constant(object) = object {
+                    Some(ObjectClass::of(&object.encoded))
+                } else {
+                    None
+                };
+                self.dataset
+                    .predicate_cardinality(&predicate.encoded, object_class)
+                    .ok()
+            }
+            _ => None,
+        }
+    }
+
     fn evaluate_service(
         &self,
         service_name: &PatternValue,
@@ -1050,8 +1311,15 @@ impl SimpleEvaluator {
             PlanExpression::Add(a, b) => {
                 let a = self.expression_evaluator(a, stat_children);
                 let b = self.expression_evaluator(b, stat_children);
-                Rc::new(
-                    move |tuple| match NumericBinaryOperands::new(a(tuple)?, b(tuple)?)? {
+                Rc::new(move |tuple| {
+                    let (a, b) = (a(tuple)?, b(tuple)?);
+                    // A duration can also be added before the date or the time it is added to
+                    let (a, b) = if is_duration(&a) && !is_duration(&b) {
+                        (b, a)
+                    } else {
+                        (a, b)
+                    };
+                    match NumericBinaryOperands::new(a, b)? {
                         NumericBinaryOperands::Float(v1, v2) => Some((v1 + v2).into()),
                         NumericBinaryOperands::Double(v1, v2) => Some((v1 + v2).into()),
                         NumericBinaryOperands::Integer(v1, v2) => Some(v1.checked_add(v2)?.into()),
@@ -1088,8 +1356,8 @@ impl SimpleEvaluator {
                             Some(v1.checked_add_day_time_duration(v2)?.into())
                         }
                         _ => None,
-                    },
-                )
+                    }
+                })
             }
             PlanExpression::Subtract(a, b) => {
                 let a = self.expression_evaluator(a, stat_children);
@@ -1146,21 +1414,29 @@ impl SimpleEvaluator {
             PlanExpression::Multiply(a, b) => {
                 let a = self.expression_evaluator(a, stat_children);
                 let b = self.expression_evaluator(b, stat_children);
-                Rc::new(
-                    move |tuple| match NumericBinaryOperands::new(a(tuple)?, b(tuple)?)? {
+                Rc::new(move |tuple| {
+                    let (a, b) = (a(tuple)?, b(tuple)?);
+                    if is_duration(&a) || is_duration(&b) {
+                        return multiply_duration(&a, &b);
+                    }
+                    match NumericBinaryOperands::new(a, b)? {
                         NumericBinaryOperands::Float(v1, v2) => Some((v1 * v2).into()),
                         NumericBinaryOperands::Double(v1, v2) => Some((v1 * v2).into()),
                         NumericBinaryOperands::Integer(v1, v2) => Some(v1.checked_mul(v2)?.into()),
                         NumericBinaryOperands::Decimal(v1, v2) => Some(v1.checked_mul(v2)?.into()),
                         _ => None,
-                    },
-                )
+                    }
+                })
             }
             PlanExpression::Divide(a, b) => {
                 let a = self.expression_evaluator(a, stat_children);
                 let b = self.expression_evaluator(b, stat_children);
-                Rc::new(
-                    move |tuple| match NumericBinaryOperands::new(a(tuple)?, b(tuple)?)? {
+                Rc::new(move |tuple| {
+                    let (a, b) = (a(tuple)?, b(tuple)?);
+                    if is_duration(&a) {
+                        return divide_duration(&a, &b);
+                    }
+                    match NumericBinaryOperands::new(a, b)? {
                         NumericBinaryOperands::Float(v1, v2) => Some((v1 / v2).into()),
                         NumericBinaryOperands::Double(v1, v2) => Some((v1 / v2).into()),
                         NumericBinaryOperands::Integer(v1, v2) => {
@@ -1168,8 +1444,8 @@ impl SimpleEvaluator {
                         }
                         NumericBinaryOperands::Decimal(v1, v2) => Some(v1.checked_div(v2)?.into()),
                         _ => None,
-                    },
-                )
+                    }
+                })
             }
             PlanExpression::UnaryPlus(e) => {
                 let e = self.expression_evaluator(e, stat_children);
@@ -1299,11 +1575,14 @@ impl SimpleEvaluator {
                         )
                     })
                 }
-                None => Rc::new(|_| {
-                    Some(EncodedTerm::NumericalBlankNode {
-                        id: random::<u128>(),
+                None => {
+                    let blank_nodes = Rc::clone(&self.blank_nodes);
+                    Rc::new(move |_| {
+                        Some(EncodedTerm::NumericalBlankNode {
+                            id: blank_nodes.next_id(),
+                        })
                     })
-                }),
+                }
             },
             PlanExpression::Rand => Rc::new(|_| Some(random::<f64>().into())),
             PlanExpression::Abs(e) => {
@@ -1435,7 +1714,10 @@ impl SimpleEvaluator {
                 let dataset = Rc::clone(&self.dataset);
                 Rc::new(move |tuple| {
                     let (text, language) = to_string_and_language(&dataset, &arg(tuple)?)?;
-                    let replacement = to_simple_string(&dataset, &replacement(tuple)?)?;
+                    let replacement = xpath_replacement(
+                        &to_simple_string(&dataset, &replacement(tuple)?)?,
+                        regex.captures_len() - 1,
+                    )?;
                     Some(build_plain_literal(
                         &dataset,
                         &regex.replace_all(&text, replacement.as_str()),
@@ -1451,6 +1733,8 @@ impl SimpleEvaluator {
                     .as_ref()
                     .map(|flags| self.expression_evaluator(flags, stat_children));
                 let dataset = Rc::clone(&self.dataset);
+                let regex_cache = self.regex_cache.clone();
+                let regex_cache_stats = Rc::clone(&self.regex_cache_stats);
                 Rc::new(move |tuple| {
                     let pattern = to_simple_string(&dataset, &pattern(tuple)?)?;
                     let options = if let Some(flags) = &flags {
@@ -1458,9 +1742,13 @@ impl SimpleEvaluator {
                     } else {
                         None
                     };
-                    let regex = compile_pattern(&pattern, options.as_deref())?;
+                    let regex =
+                        regex_cache.get(&pattern, options.as_deref(), &regex_cache_stats)?;
                     let (text, language) = to_string_and_language(&dataset, &arg(tuple)?)?;
-                    let replacement = to_simple_string(&dataset, &replacement(tuple)?)?;
+                    let replacement = xpath_replacement(
+                        &to_simple_string(&dataset, &replacement(tuple)?)?,
+                        regex.captures_len() - 1,
+                    )?;
                     Some(build_plain_literal(
                         &dataset,
                         &regex.replace_all(&text, replacement.as_str()),
@@ -1682,6 +1970,18 @@ impl SimpleEvaluator {
                 })
             }
 
+            PlanExpression::FormatNumber(value, picture) => {
+                let value = self.expression_evaluator(value, stat_children);
+                let picture = self.expression_evaluator(picture, stat_children);
+                let dataset = Rc::clone(&self.dataset);
+                Rc::new(move |tuple| {
+                    let picture = to_simple_string(&dataset, &picture(tuple)?)?;
+                    Some(build_string_literal(
+                        &dataset,
+                        &format_number(&value(tuple)?, &picture)?,
+                    ))
+                })
+            }
             PlanExpression::Adjust(dt, tz) => {
                 let dt = self.expression_evaluator(dt, stat_children);
                 let tz = self.expression_evaluator(tz, stat_children);
@@ -1841,6 +2141,8 @@ impl SimpleEvaluator {
                     .as_ref()
                     .map(|flags| self.expression_evaluator(flags, stat_children));
                 let dataset = Rc::clone(&self.dataset);
+                let regex_cache = self.regex_cache.clone();
+                let regex_cache_stats = Rc::clone(&self.regex_cache_stats);
                 Rc::new(move |tuple| {
                     let pattern = to_simple_string(&dataset, &pattern(tuple)?)?;
                     let options = if let Some(flags) = &flags {
@@ -1848,7 +2150,8 @@ impl SimpleEvaluator {
                     } else {
                         None
                     };
-                    let regex = compile_pattern(&pattern, options.as_deref())?;
+                    let regex =
+                        regex_cache.get(&pattern, options.as_deref(), &regex_cache_stats)?;
                     let text = to_string(&dataset, &text(tuple)?)?;
                     Some(regex.is_match(&text).into())
                 })
@@ -2341,6 +2644,294 @@ pub(super) fn compile_pattern(pattern: &str, flags: Option<&str>) -> Option<Rege
     regex_builder.build().ok()
 }
 
+/// Converts an [XPath replacement string](https://www.w3.org/TR/xpath-functions-31/#func-replace) to the syntax of the regex crate
+///
+/// `$N` refers to the N-th group, `\$` and `\\` are the escaped `$` and `\`.
+fn xpath_replacement(replacement: &str, groups: usize) -> Option<String> {
+    let mut output = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => output.push('\\'),
+                '$' => output.push_str("$$"),
+                _ => return None,
+            },
+            '$' => {
+                let mut group = usize::try_from(chars.next()?.to_digit(10)?).ok()?;
+                // The longest sequence of digits that is a group number is used
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    let extended = group * 10 + usize::try_from(digit).ok()?;
+                    if extended > groups {
+                        break;
+                    }
+                    group = extended;
+                    chars.next();
+                }
+                output.push_str("${");
+                output.push_str(&group.to_string());
+                output.push('}');
+            }
+            _ => output.push(c),
+        }
+    }
+    Some(output)
+}
+
+fn is_duration(term: &EncodedTerm) -> bool {
+    matches!(
+        term,
+        EncodedTerm::DurationLiteral(_)
+            | EncodedTerm::YearMonthDurationLiteral(_)
+            | EncodedTerm::DayTimeDurationLiteral(_)
+    )
+}
+
+fn to_decimal(term: &EncodedTerm) -> Option<Decimal> {
+    match term {
+        EncodedTerm::IntegerLiteral(value) => Some(Decimal::from(*value)),
+        EncodedTerm::DecimalLiteral(value) => Some(*value),
+        EncodedTerm::FloatLiteral(value) => Decimal::try_from(*value).ok(),
+        EncodedTerm::DoubleLiteral(value) => Decimal::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+/// [op:multiply-yearMonthDuration](https://www.w3.org/TR/xpath-functions-31/#func-multiply-yearMonthDuration) and [op:multiply-dayTimeDuration](https://www.w3.org/TR/xpath-functions-31/#func-multiply-dayTimeDuration), the number being on any side
+fn multiply_duration(a: &EncodedTerm, b: &EncodedTerm) -> Option<EncodedTerm> {
+    match (a, b) {
+        (EncodedTerm::YearMonthDurationLiteral(duration), number)
+        | (number, EncodedTerm::YearMonthDurationLiteral(duration)) => {
+            Some(duration.checked_mul(to_decimal(number)?)?.into())
+        }
+        (EncodedTerm::DayTimeDurationLiteral(duration), number)
+        | (number, EncodedTerm::DayTimeDurationLiteral(duration)) => {
+            Some(duration.checked_mul(to_decimal(number)?)?.into())
+        }
+        _ => None,
+    }
+}
+
+/// [op:divide-yearMonthDuration](https://www.w3.org/TR/xpath-functions-31/#func-divide-yearMonthDuration) and [op:divide-yearMonthDuration-by-yearMonthDuration](https://www.w3.org/TR/xpath-functions-31/#func-divide-yearMonthDuration-by-yearMonthDuration) and their `xsd:dayTimeDuration` equivalents
+fn divide_duration(a: &EncodedTerm, b: &EncodedTerm) -> Option<EncodedTerm> {
+    match (a, b) {
+        (EncodedTerm::YearMonthDurationLiteral(a), EncodedTerm::YearMonthDurationLiteral(b)) => {
+            Some(a.checked_div_duration(*b)?.into())
+        }
+        (EncodedTerm::DayTimeDurationLiteral(a), EncodedTerm::DayTimeDurationLiteral(b)) => {
+            Some(a.checked_div_duration(*b)?.into())
+        }
+        (EncodedTerm::YearMonthDurationLiteral(a), b) => {
+            Some(a.checked_div(to_decimal(b)?)?.into())
+        }
+        (EncodedTerm::DayTimeDurationLiteral(a), b) => Some(a.checked_div(to_decimal(b)?)?.into()),
+        _ => None,
+    }
+}
+
+/// [fn:format-number](https://www.w3.org/TR/xpath-functions-31/#func-format-number) with the default decimal format
+fn format_number(value: &EncodedTerm, picture: &str) -> Option<String> {
+    let float = match value {
+        EncodedTerm::FloatLiteral(value) => Some(f64::from(f32::from(*value))),
+        EncodedTerm::DoubleLiteral(value) => Some(f64::from(*value)),
+        _ => None,
+    };
+    if float.map_or(false, f64::is_nan) {
+        return Some("NaN".into());
+    }
+    let is_negative = if let Some(float) = float {
+        float < 0.
+    } else {
+        to_decimal(value)?.is_negative()
+    };
+    let (positive, negative) = match picture.split_once(';') {
+        Some((positive, negative)) => (positive, Some(negative)),
+        None => (picture, None),
+    };
+    let (sub_picture, minus) = match (is_negative, negative) {
+        (true, Some(negative)) => (negative, ""),
+        (true, None) => (positive, "-"),
+        (false, _) => (positive, ""),
+    };
+    let picture = NumberPicture::parse(sub_picture)?;
+    if negative.map_or(false, |negative| NumberPicture::parse(negative).is_none()) {
+        return None;
+    }
+    let digits = if float.map_or(false, f64::is_infinite) {
+        "Infinity".into()
+    } else {
+        picture.format(to_decimal(value)?.abs().checked_mul(picture.scale)?)
+    };
+    Some(format!(
+        "{minus}{}{digits}{}",
+        picture.prefix, picture.suffix
+    ))
+}
+
+/// A sub-picture of [fn:format-number](https://www.w3.org/TR/xpath-functions-31/#func-format-number)
+struct NumberPicture<'a> {
+    prefix: &'a str,
+    suffix: &'a str,
+    scale: u32,
+    min_integer_digits: usize,
+    /// Positions of the grouping separators counted from the decimal separator
+    grouping_positions: Vec<usize>,
+    min_fraction_digits: usize,
+    max_fraction_digits: usize,
+}
+
+impl<'a> NumberPicture<'a> {
+    fn parse(picture: &'a str) -> Option<Self> {
+        let is_active = |c: char| c.is_ascii_digit() || matches!(c, '#' | '.' | ',');
+        let start = picture.find(is_active)?;
+        let end = picture.rfind(is_active)? + 1;
+        let (prefix, mantissa, suffix) = (&picture[..start], &picture[start..end], &picture[end..]);
+        let passive = || prefix.chars().chain(suffix.chars());
+        let scale = match (
+            passive().filter(|c| *c == '%').count(),
+            passive().filter(|c| *c == '‰').count(),
+        ) {
+            (0, 0) => 1,
+            (1, 0) => 100,
+            (0, 1) => 1000,
+            _ => return None,
+        };
+        if !mantissa.chars().all(is_active)
+            || !mantissa.contains(|c: char| c == '#' || c.is_ascii_digit())
+        {
+            return None;
+        }
+        let (integer, fraction) = match mantissa.split_once('.') {
+            Some((integer, fraction)) => (integer, fraction),
+            None => (mantissa, ""),
+        };
+        if fraction.contains('.')
+            || integer.ends_with(',')
+            || fraction.starts_with(',')
+            || mantissa.contains(",,")
+        {
+            return None;
+        }
+        // A mandatory digit can't be followed by an optional one in the integer part and the opposite in the fractional part
+        if integer
+            .trim_start_matches(|c| c == '#' || c == ',')
+            .contains('#')
+            || fraction
+                .trim_end_matches(|c| c == '#' || c == ',')
+                .contains('#')
+        {
+            return None;
+        }
+        let mut grouping_positions = Vec::new();
+        let mut digits = 0;
+        for c in integer.chars().rev() {
+            if c == ',' {
+                grouping_positions.push(digits);
+            } else {
+                digits += 1;
+            }
+        }
+        let mut min_integer_digits = integer.chars().filter(char::is_ascii_digit).count();
+        let min_fraction_digits = fraction.chars().filter(char::is_ascii_digit).count();
+        let max_fraction_digits = fraction.chars().filter(|c| *c != ',').count();
+        if min_integer_digits == 0 && max_fraction_digits == 0 {
+            min_integer_digits = 1;
+        }
+        Some(Self {
+            prefix,
+            suffix,
+            scale,
+            min_integer_digits,
+            grouping_positions,
+            min_fraction_digits,
+            max_fraction_digits,
+        })
+    }
+
+    /// Formats a positive number
+    fn format(&self, value: Decimal) -> String {
+        let value = value.to_string();
+        let (integer, fraction) = value.split_once('.').unwrap_or((&value, ""));
+        let mut digits = integer.bytes().chain(fraction.bytes()).collect::<Vec<_>>();
+        let mut integer_len = integer.len();
+        if fraction.len() > self.max_fraction_digits {
+            // Rounding half to even
+            let kept = integer_len + self.max_fraction_digits;
+            let round_up = match digits[kept] {
+                b'6'..=b'9' => true,
+                b'5' => digits[kept + 1..].iter().any(|d| *d != b'0') || digits[kept - 1] % 2 == 1,
+                _ => false,
+            };
+            digits.truncate(kept);
+            if round_up {
+                let mut carry = true;
+                for digit in digits.iter_mut().rev() {
+                    if *digit == b'9' {
+                        *digit = b'0';
+                    } else {
+                        *digit += 1;
+                        carry = false;
+                        break;
+                    }
+                }
+                if carry {
+                    digits.insert(0, b'1');
+                    integer_len += 1;
+                }
+            }
+        }
+        let (integer, fraction) = digits.split_at(integer_len);
+        let integer = integer
+            .iter()
+            .position(|d| *d != b'0')
+            .map_or(&[][..], |start| &integer[start..]);
+        let mut fraction = fraction.to_vec();
+        while fraction.len() > self.min_fraction_digits && fraction.last() == Some(&b'0') {
+            fraction.pop();
+        }
+        fraction.resize(fraction.len().max(self.min_fraction_digits), b'0');
+
+        let mut integer = integer.iter().map(|d| char::from(*d)).collect::<String>();
+        while integer.len() < self.min_integer_digits {
+            integer.insert(0, '0');
+        }
+        if integer.is_empty() && fraction.is_empty() {
+            integer.push('0');
+        }
+        let mut output = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            let position = integer.len() - i;
+            if i > 0 && self.is_grouping_position(position) {
+                output.push(',');
+            }
+            output.push(digit);
+        }
+        if !fraction.is_empty() {
+            output.push('.');
+            output.extend(fraction.iter().map(|d| char::from(*d)));
+        }
+        output
+    }
+
+    fn is_grouping_position(&self, position: usize) -> bool {
+        if self.grouping_positions.contains(&position) {
+            return true;
+        }
+        // The grouping is repeated if the separators are at the multiples of the first position
+        if let Some(first) = self.grouping_positions.first() {
+            *first > 0
+                && self
+                    .grouping_positions
+                    .iter()
+                    .enumerate()
+                    .all(|(i, p)| *p == first * (i + 1))
+                && position % first == 0
+        } else {
+            false
+        }
+    }
+}
+
 #[allow(clippy::rc_buffer)]
 fn decode_bindings(
     dataset: Rc<DatasetView>,
@@ -3130,6 +3721,8 @@ pub fn are_compatible_and_not_disjointed(a: &EncodedTuple, b: &EncodedTuple) ->
 #[derive(Clone)]
 struct PathEvaluator {
     dataset: Rc<DatasetView>,
+    /// The number of nodes visited by the transitive closures, reported in the query explanation
+    visited_nodes: Rc<Cell<usize>>,
 }
 
 impl PathEvaluator {
@@ -3178,6 +3771,7 @@ impl PathEvaluator {
                         self.eval_from_in_graph(p, start, graph_name),
                         move |e| self.eval_from_in_graph(p, &e, graph_name),
                         end,
+                        &self.visited_nodes,
                     )?
                 }
             }
@@ -3185,6 +3779,7 @@ impl PathEvaluator {
                 self.eval_from_in_graph(p, start, graph_name),
                 move |e| self.eval_from_in_graph(p, &e, graph_name),
                 end,
+                &self.visited_nodes,
             )?,
             PlanPropertyPath::ZeroOrOne(p) => {
                 if start == end {
@@ -3236,7 +3831,7 @@ impl PathEvaluator {
                     },
                 ))
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_closed_in_unknown_graph(a, start, end)
                     .chain(self.eval_closed_in_unknown_graph(b, start, end)),
             )),
@@ -3250,6 +3845,7 @@ impl PathEvaluator {
                         Some(Ok(start2.clone())),
                         |e| eval.eval_from_in_graph(&p, &e, &graph_name),
                         &end,
+                        &eval.visited_nodes,
                     )
                     .map(|is_found| is_found.then(|| graph_name))
                     .transpose()
@@ -3267,6 +3863,7 @@ impl PathEvaluator {
                                     Some(Ok(start)),
                                     |e| eval.eval_from_in_graph(&p, &e, &graph_name),
                                     &end,
+                                    &eval.visited_nodes,
                                 )
                                 .map(|is_found| is_found.then(|| graph_name))
                             })
@@ -3338,7 +3935,7 @@ impl PathEvaluator {
                         }),
                 )
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_from_in_graph(a, start, graph_name)
                     .chain(self.eval_from_in_graph(b, start, graph_name)),
             )),
@@ -3347,9 +3944,12 @@ impl PathEvaluator {
                     let eval = self.clone();
                     let p = Rc::clone(p);
                     let graph_name2 = graph_name.clone();
-                    transitive_closure(Some(Ok(start.clone())), move |e| {
-                        eval.eval_from_in_graph(&p, &e, &graph_name2)
-                    })
+                    let visited_nodes = Rc::clone(&eval.visited_nodes);
+                    transitive_closure(
+                        Some(Ok(start.clone())),
+                        move |e| eval.eval_from_in_graph(&p, &e, &graph_name2),
+                        visited_nodes,
+                    )
                 })
             }
             PlanPropertyPath::OneOrMore(p) => {
@@ -3359,11 +3959,12 @@ impl PathEvaluator {
                 Box::new(transitive_closure(
                     self.eval_from_in_graph(&p, start, graph_name),
                     move |e| eval.eval_from_in_graph(&p, &e, &graph_name2),
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::ZeroOrOne(p) => {
                 self.run_if_term_is_a_graph_node(start, graph_name, || {
-                    hash_deduplicate(
+                    deduplicate_nodes(
                         once(Ok(start.clone()))
                             .chain(self.eval_from_in_graph(p, start, graph_name)),
                     )
@@ -3414,7 +4015,7 @@ impl PathEvaluator {
                     },
                 ))
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_from_in_unknown_graph(a, start)
                     .chain(self.eval_from_in_unknown_graph(b, start)),
             )),
@@ -3426,9 +4027,12 @@ impl PathEvaluator {
                     let eval = eval.clone();
                     let p = Rc::clone(&p);
                     let graph_name2 = graph_name.clone();
-                    transitive_closure(Some(Ok(start2.clone())), move |e| {
-                        eval.eval_from_in_graph(&p, &e, &graph_name2)
-                    })
+                    let visited_nodes = Rc::clone(&eval.visited_nodes);
+                    transitive_closure(
+                        Some(Ok(start2.clone())),
+                        move |e| eval.eval_from_in_graph(&p, &e, &graph_name2),
+                        visited_nodes,
+                    )
                     .map(move |e| Ok((e?, graph_name.clone())))
                 })
             }
@@ -3441,6 +4045,7 @@ impl PathEvaluator {
                         eval.eval_from_in_graph(&p, &e, &graph_name)
                             .map(move |e| Ok((e?, graph_name.clone())))
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::ZeroOrOne(p) => {
@@ -3448,7 +4053,7 @@ impl PathEvaluator {
                 let start2 = start.clone();
                 let p = Rc::clone(p);
                 self.run_if_term_is_a_dataset_node(start, move |graph_name| {
-                    hash_deduplicate(once(Ok(start2.clone())).chain(eval.eval_from_in_graph(
+                    deduplicate_nodes(once(Ok(start2.clone())).chain(eval.eval_from_in_graph(
                         &p,
                         &start2,
                         &graph_name,
@@ -3500,7 +4105,7 @@ impl PathEvaluator {
                         }),
                 )
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_to_in_graph(a, end, graph_name)
                     .chain(self.eval_to_in_graph(b, end, graph_name)),
             )),
@@ -3509,9 +4114,12 @@ impl PathEvaluator {
                     let eval = self.clone();
                     let p = Rc::clone(p);
                     let graph_name2 = graph_name.clone();
-                    transitive_closure(Some(Ok(end.clone())), move |e| {
-                        eval.eval_to_in_graph(&p, &e, &graph_name2)
-                    })
+                    let visited_nodes = Rc::clone(&eval.visited_nodes);
+                    transitive_closure(
+                        Some(Ok(end.clone())),
+                        move |e| eval.eval_to_in_graph(&p, &e, &graph_name2),
+                        visited_nodes,
+                    )
                 })
             }
             PlanPropertyPath::OneOrMore(p) => {
@@ -3521,11 +4129,12 @@ impl PathEvaluator {
                 Box::new(transitive_closure(
                     self.eval_to_in_graph(&p, end, graph_name),
                     move |e| eval.eval_to_in_graph(&p, &e, &graph_name2),
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::ZeroOrOne(p) => {
                 self.run_if_term_is_a_graph_node(end, graph_name, || {
-                    hash_deduplicate(
+                    deduplicate_nodes(
                         once(Ok(end.clone())).chain(self.eval_to_in_graph(p, end, graph_name)),
                     )
                 })
@@ -3574,7 +4183,7 @@ impl PathEvaluator {
                     },
                 ))
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_to_in_unknown_graph(a, end)
                     .chain(self.eval_to_in_unknown_graph(b, end)),
             )),
@@ -3586,9 +4195,12 @@ impl PathEvaluator {
                     let eval = eval.clone();
                     let p = Rc::clone(&p);
                     let graph_name2 = graph_name.clone();
-                    transitive_closure(Some(Ok(end2.clone())), move |e| {
-                        eval.eval_to_in_graph(&p, &e, &graph_name2)
-                    })
+                    let visited_nodes = Rc::clone(&eval.visited_nodes);
+                    transitive_closure(
+                        Some(Ok(end2.clone())),
+                        move |e| eval.eval_to_in_graph(&p, &e, &graph_name2),
+                        visited_nodes,
+                    )
                     .map(move |e| Ok((e?, graph_name.clone())))
                 })
             }
@@ -3601,6 +4213,7 @@ impl PathEvaluator {
                         eval.eval_to_in_graph(&p, &e, &graph_name)
                             .map(move |e| Ok((e?, graph_name.clone())))
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::ZeroOrOne(p) => {
@@ -3608,7 +4221,7 @@ impl PathEvaluator {
                 let end2 = end.clone();
                 let p = Rc::clone(p);
                 self.run_if_term_is_a_dataset_node(end, move |graph_name| {
-                    hash_deduplicate(once(Ok(end2.clone())).chain(eval.eval_to_in_graph(
+                    deduplicate_nodes(once(Ok(end2.clone())).chain(eval.eval_to_in_graph(
                         &p,
                         &end2,
                         &graph_name,
@@ -3662,7 +4275,7 @@ impl PathEvaluator {
                     },
                 ))
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_open_in_graph(a, graph_name)
                     .chain(self.eval_open_in_graph(b, graph_name)),
             )),
@@ -3670,27 +4283,39 @@ impl PathEvaluator {
                 let eval = self.clone();
                 let p = Rc::clone(p);
                 let graph_name2 = graph_name.clone();
+                let mut steps = HashMap::new();
                 Box::new(transitive_closure(
                     self.get_subject_or_object_identity_pairs_in_graph(graph_name),
                     move |(start, middle)| {
-                        eval.eval_from_in_graph(&p, &middle, &graph_name2)
-                            .map(move |end| Ok((start.clone(), end?)))
+                        cached_path_step(&mut steps, &middle, || {
+                            eval.eval_from_in_graph(&p, &middle, &graph_name2)
+                        })
+                        .map(|end| Ok((start.clone(), end?)))
+                        .collect::<Vec<_>>()
+                        .into_iter()
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::OneOrMore(p) => {
                 let eval = self.clone();
                 let p = Rc::clone(p);
                 let graph_name2 = graph_name.clone();
+                let mut steps = HashMap::new();
                 Box::new(transitive_closure(
                     self.eval_open_in_graph(&p, graph_name),
                     move |(start, middle)| {
-                        eval.eval_from_in_graph(&p, &middle, &graph_name2)
-                            .map(move |end| Ok((start.clone(), end?)))
+                        cached_path_step(&mut steps, &middle, || {
+                            eval.eval_from_in_graph(&p, &middle, &graph_name2)
+                        })
+                        .map(|end| Ok((start.clone(), end?)))
+                        .collect::<Vec<_>>()
+                        .into_iter()
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
-            PlanPropertyPath::ZeroOrOne(p) => Box::new(hash_deduplicate(
+            PlanPropertyPath::ZeroOrOne(p) => Box::new(deduplicate_nodes(
                 self.get_subject_or_object_identity_pairs_in_graph(graph_name)
                     .chain(self.eval_open_in_graph(p, graph_name)),
             )),
@@ -3739,33 +4364,45 @@ impl PathEvaluator {
                     },
                 ))
             }
-            PlanPropertyPath::Alternative(a, b) => Box::new(hash_deduplicate(
+            PlanPropertyPath::Alternative(a, b) => Box::new(deduplicate_nodes(
                 self.eval_open_in_unknown_graph(a)
                     .chain(self.eval_open_in_unknown_graph(b)),
             )),
             PlanPropertyPath::ZeroOrMore(p) => {
                 let eval = self.clone();
                 let p = Rc::clone(p);
+                let mut steps = HashMap::new();
                 Box::new(transitive_closure(
                     self.get_subject_or_object_identity_pairs_in_dataset(),
                     move |(start, middle, graph_name)| {
-                        eval.eval_from_in_graph(&p, &middle, &graph_name)
-                            .map(move |end| Ok((start.clone(), end?, graph_name.clone())))
+                        cached_path_step(&mut steps, &(&middle, &graph_name), || {
+                            eval.eval_from_in_graph(&p, &middle, &graph_name)
+                        })
+                        .map(|end| Ok((start.clone(), end?, graph_name.clone())))
+                        .collect::<Vec<_>>()
+                        .into_iter()
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
             PlanPropertyPath::OneOrMore(p) => {
                 let eval = self.clone();
                 let p = Rc::clone(p);
+                let mut steps = HashMap::new();
                 Box::new(transitive_closure(
                     self.eval_open_in_unknown_graph(&p),
                     move |(start, middle, graph_name)| {
-                        eval.eval_from_in_graph(&p, &middle, &graph_name)
-                            .map(move |end| Ok((start.clone(), end?, graph_name.clone())))
+                        cached_path_step(&mut steps, &(&middle, &graph_name), || {
+                            eval.eval_from_in_graph(&p, &middle, &graph_name)
+                        })
+                        .map(|end| Ok((start.clone(), end?, graph_name.clone())))
+                        .collect::<Vec<_>>()
+                        .into_iter()
                     },
+                    Rc::clone(&self.visited_nodes),
                 ))
             }
-            PlanPropertyPath::ZeroOrOne(p) => Box::new(hash_deduplicate(
+            PlanPropertyPath::ZeroOrOne(p) => Box::new(deduplicate_nodes(
                 self.get_subject_or_object_identity_pairs_in_dataset()
                     .chain(self.eval_open_in_unknown_graph(p)),
             )),
@@ -4306,61 +4943,148 @@ impl<T1, T2, I1: Iterator<Item = T1>, I2: Iterator<Item = T2>> Iterator
     }
 }
 
-fn transitive_closure<T: Clone + Eq + Hash, NI: Iterator<Item = Result<T, EvaluationError>>>(
+/// Returns the nodes reachable from `start` with `next`, visiting them in breadth-first order
+///
+/// The visited nodes are only recorded by their 128-bit hash, they are returned as soon as they are reached.
+fn transitive_closure<T: Clone + Hash, NI: Iterator<Item = Result<T, EvaluationError>>>(
     start: impl IntoIterator<Item = Result<T, EvaluationError>>,
-    mut next: impl FnMut(T) -> NI,
+    next: impl FnMut(T) -> NI,
+    visited_nodes: Rc<Cell<usize>>,
 ) -> impl Iterator<Item = Result<T, EvaluationError>> {
-    let mut errors = Vec::new();
-    let mut todo = start
-        .into_iter()
-        .filter_map(|e| match e {
-            Ok(e) => Some(e),
-            Err(e) => {
-                errors.push(e);
-                None
-            }
-        })
-        .collect::<Vec<_>>();
-    let mut all = todo.iter().cloned().collect::<HashSet<_>>();
-    while let Some(e) = todo.pop() {
-        for e in next(e) {
-            match e {
-                Ok(e) => {
-                    if all.insert(e.clone()) {
-                        todo.push(e)
+    TransitiveClosure {
+        start: start.into_iter().collect::<Vec<_>>().into_iter(),
+        frontier: VecDeque::new(),
+        current: None,
+        visited: HashSet::new(),
+        next,
+        visited_nodes,
+    }
+}
+
+struct TransitiveClosure<T, NI, F> {
+    start: std::vec::IntoIter<Result<T, EvaluationError>>,
+    /// The reached nodes whose successors have not been looked for yet
+    frontier: VecDeque<T>,
+    /// The successors of the node being expanded
+    current: Option<NI>,
+    visited: HashSet<u128>,
+    next: F,
+    visited_nodes: Rc<Cell<usize>>,
+}
+
+impl<T: Clone + Hash, NI: Iterator<Item = Result<T, EvaluationError>>, F: FnMut(T) -> NI> Iterator
+    for TransitiveClosure<T, NI, F>
+{
+    type Item = Result<T, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<T, EvaluationError>> {
+        loop {
+            let node = if let Some(node) = self.start.next() {
+                node
+            } else if let Some(node) = self.current.as_mut().and_then(Iterator::next) {
+                node
+            } else {
+                let node = self.frontier.pop_front()?;
+                self.current = Some((self.next)(node));
+                continue;
+            };
+            match node {
+                Ok(node) => {
+                    if self.visited.insert(node_id(&node)) {
+                        self.visited_nodes.set(self.visited_nodes.get() + 1);
+                        self.frontier.push_back(node.clone());
+                        return Some(Ok(node));
                     }
                 }
-                Err(e) => errors.push(e),
+                Err(error) => return Some(Err(error)),
             }
         }
     }
-    errors.into_iter().map(Err).chain(all.into_iter().map(Ok))
 }
 
-fn look_in_transitive_closure<
-    T: Clone + Eq + Hash,
-    NI: Iterator<Item = Result<T, EvaluationError>>,
->(
+/// Looks for `target` in the nodes reachable from `start` with `next` with a breadth-first search
+///
+/// The search stops as soon as `target` is reached, each frontier being fully expanded before the next one.
+fn look_in_transitive_closure<T: Eq + Hash, NI: Iterator<Item = Result<T, EvaluationError>>>(
     start: impl IntoIterator<Item = Result<T, EvaluationError>>,
     mut next: impl FnMut(T) -> NI,
     target: &T,
+    visited_nodes: &Cell<usize>,
 ) -> Result<bool, EvaluationError> {
-    let mut todo = start.into_iter().collect::<Result<Vec<_>, _>>()?;
-    let mut all = todo.iter().cloned().collect::<HashSet<_>>();
-    while let Some(e) = todo.pop() {
-        if e == *target {
+    let mut visited = HashSet::new();
+    let mut frontier = Vec::new();
+    for node in start {
+        let node = node?;
+        if node == *target {
             return Ok(true);
         }
-        for e in next(e) {
-            let e = e?;
-            if all.insert(e.clone()) {
-                todo.push(e);
+        if visited.insert(node_id(&node)) {
+            frontier.push(node);
+        }
+    }
+    while !frontier.is_empty() {
+        visited_nodes.set(visited_nodes.get() + frontier.len());
+        let mut next_frontier = Vec::new();
+        for node in frontier {
+            for node in next(node) {
+                let node = node?;
+                if node == *target {
+                    return Ok(true);
+                }
+                if visited.insert(node_id(&node)) {
+                    next_frontier.push(node);
+                }
             }
         }
+        frontier = next_frontier;
     }
     Ok(false)
 }
 
+/// Returns the nodes reached by one step of a path from `node`, only computed with `step` on the first call for this node
+///
+/// The closures of the paths without fixed ends reach the same nodes from many starts,
+/// so this avoids scanning again the successors of each of them.
+fn cached_path_step<I: Iterator<Item = Result<EncodedTerm, EvaluationError>>>(
+    cache: &mut HashMap<u128, Vec<EncodedTerm>>,
+    node: &impl Hash,
+    step: impl FnOnce() -> I,
+) -> std::vec::IntoIter<Result<EncodedTerm, EvaluationError>> {
+    let id = node_id(node);
+    if !cache.contains_key(&id) {
+        match step().collect::<Result<Vec<_>, _>>() {
+            Ok(targets) => {
+                cache.insert(id, targets);
+            }
+            Err(error) => return vec![Err(error)].into_iter(),
+        }
+    }
+    cache[&id]
+        .iter()
+        .cloned()
+        .map(Ok)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Removes the duplicated nodes, comparing them with their 128-bit hash
+fn deduplicate_nodes<T: Hash>(
+    iter: impl Iterator<Item = Result<T, EvaluationError>>,
+) -> impl Iterator<Item = Result<T, EvaluationError>> {
+    let mut already_seen = HashSet::with_capacity(iter.size_hint().0);
+    iter.filter(move |node| {
+        node.as_ref()
+            .map_or(true, |node| already_seen.insert(node_id(node)))
+    })
+}
+
+/// The 128-bit hash identifying a node in the sets of visited nodes of the property path evaluation
+fn node_id(node: &impl Hash) -> u128 {
+    let mut hasher = SipHasher24::new();
+    node.hash(&mut hasher);
+    hasher.finish128().into()
+}
+
 fn hash_deduplicate<T: Eq + Hash + Clone>(
     iter: impl Iterator<Item = Result<T, EvaluationError>>,
 ) -> impl Iterator<Item = Result<T, EvaluationError>> {
@@ -4784,17 +5508,19 @@ impl Extend<EncodedTuple> for EncodedTupleSet {
 struct StatsIterator {
     inner: EncodedTuplesIterator,
     stats: Rc<PlanNodeWithStats>,
+    scan_stats: Rc<ScanStats>,
 }
 
 impl Iterator for StatsIterator {
     type Item = Result<EncodedTuple, EvaluationError>;
 
     fn next(&mut self) -> Option<Result<EncodedTuple, EvaluationError>> {
-        let start = Timer::now();
-        let result = self.inner.next();
-        self.stats
-            .exec_duration
-            .set(self.stats.exec_duration.get() + start.elapsed());
+        let start_instructions = performance_counter(0);
+        let result = track_scans(&self.stats, &self.scan_stats, || self.inner.next());
+        self.stats.exec_instructions.set(
+            self.stats.exec_instructions.get()
+                + performance_counter(0).saturating_sub(start_instructions),
+        );
         if matches!(result, Some(Ok(_))) {
             self.stats.exec_count.set(self.stats.exec_count.get() + 1);
         }
@@ -4802,6 +5528,203 @@ impl Iterator for StatsIterator {
     }
 }
 
+impl Drop for StatsIterator {
+    fn drop(&mut self) {
+        // The scans stopped before their end are only counted when they are dropped
+        let inner = replace(&mut self.inner, Box::new(empty()));
+        track_scans(&self.stats, &self.scan_stats, || drop(inner));
+    }
+}
+
+/// Adds to the node statistics the index work done by `f`
+fn track_scans<T>(stats: &PlanNodeWithStats, scan_stats: &ScanStats, f: impl FnOnce() -> T) -> T {
+    let read_entries = scan_stats.read_entries.get();
+    let stopped_scans = scan_stats.stopped_scans.get();
+    let result = f();
+    stats
+        .read_entries
+        .set(stats.read_entries.get() + scan_stats.read_entries.get() - read_entries);
+    stats
+        .stopped_scans
+        .set(stats.stopped_scans.get() + scan_stats.stopped_scans.get() - stopped_scans);
+    result
+}
+
+/// Cancels the evaluation after the first error returned by the deadline
+struct DeadlineIterator {
+    inner: EncodedTuplesIterator,
+    deadline: Rc<Deadline>,
+    cancelled: bool,
+}
+
+impl Iterator for DeadlineIterator {
+    type Item = Result<EncodedTuple, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple, EvaluationError>> {
+        if self.cancelled {
+            return None;
+        }
+        if let Err(error) = self.deadline.check() {
+            self.cancelled = true;
+            return Some(Err(error));
+        }
+        self.inner.next()
+    }
+}
+
+/// Fails the evaluation once the bindings budget is exhausted
+struct BindingsBudgetIterator {
+    inner: EncodedTuplesIterator,
+    budget: Rc<BindingsBudget>,
+    exceeded: bool,
+}
+
+impl Iterator for BindingsBudgetIterator {
+    type Item = Result<EncodedTuple, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTuple, EvaluationError>> {
+        if self.exceeded {
+            return None;
+        }
+        if self.budget.is_exhausted() {
+            self.exceeded = true;
+            return Some(Err(EvaluationError::TooManyIntermediateBindings));
+        }
+        let result = self.inner.next()?;
+        if result.is_ok() {
+            self.budget.consume();
+            if self.budget.is_exhausted() {
+                self.exceeded = true;
+                return Some(Err(EvaluationError::TooManyIntermediateBindings));
+            }
+        }
+        Some(result)
+    }
+}
+
+/// Fails the evaluation when it returns more than a given number of results
+struct MaxResultsIterator<T> {
+    inner: Box<dyn Iterator<Item = Result<T, EvaluationError>>>,
+    remaining: usize,
+    exceeded: bool,
+}
+
+impl<T> Iterator for MaxResultsIterator<T> {
+    type Item = Result<T, EvaluationError>;
+
+    fn next(&mut self) -> Option<Result<T, EvaluationError>> {
+        if self.exceeded {
+            return None;
+        }
+        let result = self.inner.next()?;
+        if result.is_ok() {
+            if self.remaining == 0 {
+                self.exceeded = true;
+                return Some(Err(EvaluationError::TooManyResults));
+            }
+            self.remaining -= 1;
+        }
+        Some(result)
+    }
+}
+
+/// Maximal number of bindings the joins and aggregations of an evaluation are allowed to produce
+struct BindingsBudget {
+    max: usize,
+    used: Cell<usize>,
+}
+
+impl BindingsBudget {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            used: Cell::new(0),
+        }
+    }
+
+    fn consume(&self) {
+        self.used.set(self.used.get().saturating_add(1));
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.used.get() > self.max
+    }
+}
+
+/// Generator of the ids of the new blank nodes
+///
+/// The ids are random or, if a seed is given, derived from the seed and a counter
+/// to make the evaluation reproducible.
+pub struct BlankNodeGenerator {
+    seed: Option<u64>,
+    counter: Cell<u64>,
+}
+
+impl BlankNodeGenerator {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            counter: Cell::new(0),
+        }
+    }
+
+    pub fn next_id(&self) -> u128 {
+        if let Some(seed) = self.seed {
+            let counter = self.counter.get();
+            self.counter.set(counter.wrapping_add(1));
+            let mut hasher = SipHasher24::new_with_keys(seed, 0);
+            hasher.write(&counter.to_be_bytes());
+            hasher.finish128().into()
+        } else {
+            random()
+        }
+    }
+}
+
+/// Instruction and time limits of an evaluation
+pub struct Deadline {
+    max_instructions: Option<u64>,
+    timeout: Option<(Timer, StdDuration)>,
+    checks: Cell<u32>,
+    exceeded: Cell<bool>,
+}
+
+impl Deadline {
+    /// The counters are only read once every `CHECK_PERIOD` checks to keep the checks cheap
+    const CHECK_PERIOD: u32 = 64;
+
+    pub fn new(max_instructions: Option<u64>, timeout: Option<StdDuration>) -> Self {
+        Self {
+            max_instructions,
+            timeout: timeout.map(|timeout| (Timer::now(), timeout)),
+            checks: Cell::new(0),
+            exceeded: Cell::new(false),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), EvaluationError> {
+        if !self.exceeded.get() {
+            let checks = self.checks.get().wrapping_add(1);
+            self.checks.set(checks);
+            if checks % Self::CHECK_PERIOD != 0 {
+                return Ok(());
+            }
+            let instructions_exceeded = self
+                .max_instructions
+                .map_or(false, |max| performance_counter(0) >= max);
+            let time_exceeded = self
+                .timeout
+                .as_ref()
+                .map_or(false, |(start, timeout)| start.elapsed() >= *timeout);
+            if !instructions_exceeded && !time_exceeded {
+                return Ok(());
+            }
+            self.exceeded.set(true);
+        }
+        Err(EvaluationError::Cancelled)
+    }
+}
+
 pub struct Timer {
     timestamp_ms: f64,
 }
@@ -0,0 +1,409 @@
+use crate::sparql::plan::{self, PlanNodeWithStats};
+use json_event_parser::{JsonEvent, JsonWriter};
+use std::{fmt, io};
+
+/// A node of the plan of an [explained query](super::QueryExplanation::plan()).
+///
+/// The tree of nodes can be traversed to build dashboards or to find the operators of a query worth rewriting.
+///
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{PlanOperator, QueryOptions, QueryResults};
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+/// let (results, explanation) = store.explain_query_opt(
+///     "SELECT ?s WHERE { ?s <http://example.com> ?o }",
+///     QueryOptions::default(),
+///     true,
+/// )?;
+/// if let QueryResults::Solutions(solutions) = results? {
+///     // The statistics are only complete once all the solutions have been read
+///     for _ in solutions {}
+/// }
+/// let plan = explanation.plan();
+/// let mut patterns = Vec::new();
+/// let mut stack = vec![&plan];
+/// while let Some(node) = stack.pop() {
+///     if node.operator() == PlanOperator::QuadPattern {
+///         patterns.push(node);
+///     }
+///     stack.extend(node.children());
+/// }
+/// assert_eq!(patterns.len(), 1);
+/// assert_eq!(patterns[0].estimated_cardinality(), Some(1));
+/// assert_eq!(patterns[0].stats().map(|s| s.results()), Some(1));
+/// assert_eq!(patterns[0].stats().map(|s| s.evaluations()), Some(1));
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct PlanNode {
+    operator: PlanOperator,
+    label: String,
+    estimated_cardinality: Option<u64>,
+    stats: Option<PlanNodeStats>,
+    children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    pub(super) fn new(node: &PlanNodeWithStats, with_stats: bool) -> Self {
+        let children: Vec<_> = node
+            .children
+            .iter()
+            .map(|child| Self::new(child, with_stats))
+            .collect();
+        let stats = with_stats.then(|| {
+            let instructions = node.exec_instructions.get();
+            PlanNodeStats {
+                results: node.exec_count.get(),
+                evaluations: node.exec_evaluations.get(),
+                instructions,
+                own_instructions: children
+                    .iter()
+                    .filter_map(|child| child.stats.as_ref())
+                    .fold(instructions, |instructions, child| {
+                        instructions.saturating_sub(child.instructions)
+                    }),
+                read_index_entries: node.read_entries.get(),
+                stopped_index_scans: node.stopped_scans.get(),
+                visited_nodes: matches!(node.node.as_ref(), plan::PlanNode::PathPattern { .. })
+                    .then(|| node.visited_nodes.get()),
+            }
+        });
+        Self {
+            operator: PlanOperator::of(&node.node),
+            label: node.node_label(),
+            estimated_cardinality: node.estimated_cardinality,
+            stats,
+            children,
+        }
+    }
+
+    /// The kind of operator of the node.
+    #[inline]
+    pub fn operator(&self) -> PlanOperator {
+        self.operator
+    }
+
+    /// A human readable description of the node, like `QuadPattern(?s <http://example.com> ?o DEFAULT)`.
+    ///
+    /// Its format is not stable.
+    #[inline]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The number of results of the node estimated from the store statistics before the evaluation.
+    ///
+    /// It is only known for the quad patterns with a constant predicate and for the nodes with a fixed number of results.
+    /// It is an estimation for a single evaluation of the node and ignores the `FROM` and `FROM NAMED` restrictions.
+    #[inline]
+    pub fn estimated_cardinality(&self) -> Option<u64> {
+        self.estimated_cardinality
+    }
+
+    /// The statistics of the evaluation of the node, `None` if they have not been requested.
+    #[inline]
+    pub fn stats(&self) -> Option<&PlanNodeStats> {
+        self.stats.as_ref()
+    }
+
+    /// The input nodes of the node.
+    #[inline]
+    pub fn children(&self) -> &[PlanNode] {
+        &self.children
+    }
+
+    pub(super) fn write_in_json(&self, writer: &mut JsonWriter<impl io::Write>) -> io::Result<()> {
+        writer.write_event(JsonEvent::StartObject)?;
+        writer.write_event(JsonEvent::ObjectKey("name"))?;
+        writer.write_event(JsonEvent::String(&self.label))?;
+        if let Some(estimated_cardinality) = self.estimated_cardinality {
+            writer.write_event(JsonEvent::ObjectKey("estimated number of results"))?;
+            writer.write_event(JsonEvent::Number(&estimated_cardinality.to_string()))?;
+        }
+        if let Some(stats) = &self.stats {
+            writer.write_event(JsonEvent::ObjectKey("number of results"))?;
+            writer.write_event(JsonEvent::Number(&stats.results.to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("evaluations"))?;
+            writer.write_event(JsonEvent::Number(&stats.evaluations.to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("instructions"))?;
+            writer.write_event(JsonEvent::Number(&stats.instructions.to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("own instructions"))?;
+            writer.write_event(JsonEvent::Number(&stats.own_instructions.to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("read index entries"))?;
+            writer.write_event(JsonEvent::Number(&stats.read_index_entries.to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("stopped index scans"))?;
+            writer.write_event(JsonEvent::Number(&stats.stopped_index_scans.to_string()))?;
+            if let Some(visited_nodes) = stats.visited_nodes {
+                writer.write_event(JsonEvent::ObjectKey("visited nodes"))?;
+                writer.write_event(JsonEvent::Number(&visited_nodes.to_string()))?;
+            }
+        }
+        writer.write_event(JsonEvent::ObjectKey("children"))?;
+        writer.write_event(JsonEvent::StartArray)?;
+        for child in &self.children {
+            child.write_in_json(writer)?;
+        }
+        writer.write_event(JsonEvent::EndArray)?;
+        writer.write_event(JsonEvent::EndObject)
+    }
+
+    pub(super) fn write_in_dot(&self, output: &mut impl io::Write) -> io::Result<()> {
+        writeln!(output, "digraph plan {{")?;
+        writeln!(output, "  node [shape=box, style=filled, fillcolor=white];")?;
+        let total_instructions = self.stats.as_ref().map_or(0, |stats| stats.instructions);
+        self.write_dot_node(output, &mut 0, total_instructions)?;
+        writeln!(output, "}}")
+    }
+
+    /// Writes the node and its children as Graphviz nodes and returns the id of the node
+    fn write_dot_node(
+        &self,
+        output: &mut impl io::Write,
+        next_id: &mut usize,
+        total_instructions: u64,
+    ) -> io::Result<usize> {
+        let id = *next_id;
+        *next_id += 1;
+        let mut label = escape_dot(&self.label);
+        for annotation in self.annotations() {
+            label.push_str("\\n");
+            label.push_str(&escape_dot(&annotation));
+        }
+        write!(output, "  n{id} [label=\"{label}\"")?;
+        if let Some(stats) = &self.stats {
+            if total_instructions > 0 {
+                // The more instructions the node executes itself, the darker it is
+                let shade = 1 + stats.own_instructions.saturating_mul(5) / total_instructions;
+                write!(output, ", fillcolor=\"/reds9/{}\"", shade.min(6))?;
+            }
+        }
+        writeln!(output, "];")?;
+        for child in &self.children {
+            let child_id = child.write_dot_node(output, next_id, total_instructions)?;
+            writeln!(output, "  n{id} -> n{child_id};")?;
+        }
+        Ok(id)
+    }
+
+    fn fmt_tree(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        first_prefix: &str,
+        prefix: &str,
+    ) -> fmt::Result {
+        write!(f, "{first_prefix}{}", self.label)?;
+        let annotations = self.annotations();
+        if !annotations.is_empty() {
+            write!(f, " [{}]", annotations.join(", "))?;
+        }
+        writeln!(f)?;
+        for (i, child) in self.children.iter().enumerate() {
+            if i + 1 == self.children.len() {
+                child.fmt_tree(f, &format!("{prefix}`- "), &format!("{prefix}   "))?;
+            } else {
+                child.fmt_tree(f, &format!("{prefix}|- "), &format!("{prefix}|  "))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The estimated cardinality and the statistics of the node in a short human readable form
+    fn annotations(&self) -> Vec<String> {
+        let mut annotations = Vec::new();
+        if let Some(estimated_cardinality) = self.estimated_cardinality {
+            annotations.push(format!("estimated: {estimated_cardinality}"));
+        }
+        if let Some(stats) = &self.stats {
+            annotations.push(format!("results: {}", stats.results));
+            if stats.evaluations != 1 {
+                annotations.push(format!("evaluations: {}", stats.evaluations));
+            }
+            annotations.push(format!(
+                "instructions: {} (own: {})",
+                stats.instructions, stats.own_instructions
+            ));
+            if stats.read_index_entries > 0 {
+                annotations.push(format!("read index entries: {}", stats.read_index_entries));
+            }
+            if stats.stopped_index_scans > 0 {
+                annotations.push(format!(
+                    "stopped index scans: {}",
+                    stats.stopped_index_scans
+                ));
+            }
+            if let Some(visited_nodes) = stats.visited_nodes {
+                annotations.push(format!("visited nodes: {visited_nodes}"));
+            }
+        }
+        annotations
+    }
+}
+
+/// Writes the plan as an ASCII tree, one node per line with its estimated cardinality and its statistics.
+///
+/// ```
+/// use oxigraph::sparql::QueryOptions;
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let (_, explanation) = store.explain_query_opt(
+///     "SELECT ?s WHERE { ?s ?p ?o FILTER(?o > 2) }",
+///     QueryOptions::default(),
+///     false,
+/// )?;
+/// let tree = explanation.plan().to_string();
+/// assert!(tree.contains("`- Filter("));
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+impl fmt::Display for PlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, "", "")
+    }
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The statistics of the evaluation of a [`PlanNode`].
+///
+/// The node may be evaluated several times, for example once per solution of the left side of a `ForLoopJoin`:
+/// the statistics are the sums over all the evaluations.
+/// Except [`own_instructions`](Self::own_instructions()), they include the work done by the children of the node.
+///
+/// The cost of the nodes is measured in executed instructions and not in wall time:
+/// the time of the Internet Computer does not change during the execution of a message.
+#[derive(Clone, Debug)]
+pub struct PlanNodeStats {
+    results: usize,
+    evaluations: usize,
+    instructions: u64,
+    own_instructions: u64,
+    read_index_entries: usize,
+    stopped_index_scans: usize,
+    visited_nodes: Option<usize>,
+}
+
+impl PlanNodeStats {
+    /// The number of results returned by the node.
+    #[inline]
+    pub fn results(&self) -> usize {
+        self.results
+    }
+
+    /// The number of times the node has been evaluated.
+    #[inline]
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// The number of instructions executed by the node, measured with `ic_cdk::api::performance_counter`.
+    #[inline]
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// The number of instructions executed by the node itself, without the ones of its children.
+    #[inline]
+    pub fn own_instructions(&self) -> u64 {
+        self.own_instructions
+    }
+
+    /// The number of index entries read from the store.
+    #[inline]
+    pub fn read_index_entries(&self) -> usize {
+        self.read_index_entries
+    }
+
+    /// The number of index scans stopped before having read all their entries.
+    #[inline]
+    pub fn stopped_index_scans(&self) -> usize {
+        self.stopped_index_scans
+    }
+
+    /// The number of nodes visited by the transitive closures of a [`PathPattern`](PlanOperator::PathPattern) node.
+    #[inline]
+    pub fn visited_nodes(&self) -> Option<usize> {
+        self.visited_nodes
+    }
+}
+
+/// The kind of operator of a [`PlanNode`].
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum PlanOperator {
+    /// Fixed solutions, like the ones of a `VALUES` clause
+    StaticBindings,
+    /// A call to a [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) service
+    Service,
+    /// The quads matching a pattern
+    QuadPattern,
+    /// The distinct values of a variable of a quad pattern read by skipping over the index entries
+    DistinctTerms,
+    /// The number of quads matching a pattern counted in the store
+    CountQuads,
+    /// The quads matching a property path
+    PathPattern,
+    /// A join building a hash table of its right side
+    HashJoin,
+    /// A join evaluating its right side once per solution of its left side
+    ForLoopJoin,
+    /// The solutions of the left side not compatible with any of the right side, like for `MINUS`
+    AntiJoin,
+    /// A `FILTER`
+    Filter,
+    /// A `UNION`
+    Union,
+    /// An `OPTIONAL` building a hash table of its right side
+    HashLeftJoin,
+    /// An `OPTIONAL` evaluating its right side once per solution of its left side
+    ForLoopLeftJoin,
+    /// A `BIND`
+    Extend,
+    /// An `ORDER BY`
+    Sort,
+    /// A `DISTINCT` keeping the already returned solutions in memory
+    HashDeduplicate,
+    /// A `REDUCED`
+    Reduced,
+    /// An `OFFSET`
+    Skip,
+    /// A `LIMIT`
+    Limit,
+    /// A projection of the solutions on some variables
+    Project,
+    /// A `GROUP BY` and its aggregates
+    Aggregate,
+}
+
+impl PlanOperator {
+    fn of(node: &plan::PlanNode) -> Self {
+        match node {
+            plan::PlanNode::StaticBindings { .. } => Self::StaticBindings,
+            plan::PlanNode::Service { .. } => Self::Service,
+            plan::PlanNode::QuadPattern { .. } => Self::QuadPattern,
+            plan::PlanNode::DistinctTerms { .. } => Self::DistinctTerms,
+            plan::PlanNode::CountQuads { .. } => Self::CountQuads,
+            plan::PlanNode::PathPattern { .. } => Self::PathPattern,
+            plan::PlanNode::HashJoin { .. } => Self::HashJoin,
+            plan::PlanNode::ForLoopJoin { .. } => Self::ForLoopJoin,
+            plan::PlanNode::AntiJoin { .. } => Self::AntiJoin,
+            plan::PlanNode::Filter { .. } => Self::Filter,
+            plan::PlanNode::Union { .. } => Self::Union,
+            plan::PlanNode::HashLeftJoin { .. } => Self::HashLeftJoin,
+            plan::PlanNode::ForLoopLeftJoin { .. } => Self::ForLoopLeftJoin,
+            plan::PlanNode::Extend { .. } => Self::Extend,
+            plan::PlanNode::Sort { .. } => Self::Sort,
+            plan::PlanNode::HashDeduplicate { .. } => Self::HashDeduplicate,
+            plan::PlanNode::Reduced { .. } => Self::Reduced,
+            plan::PlanNode::Skip { .. } => Self::Skip,
+            plan::PlanNode::Limit { .. } => Self::Limit,
+            plan::PlanNode::Project { .. } => Self::Project,
+            plan::PlanNode::Aggregate { .. } => Self::Aggregate,
+        }
+    }
+}
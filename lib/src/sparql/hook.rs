@@ -0,0 +1,96 @@
+use crate::model::Quad;
+use crate::sparql::error::EvaluationError;
+use std::error::Error;
+
+/// Listener of the quads written by a SPARQL update.
+///
+/// Should be given to [`UpdateOptions`](super::UpdateOptions::with_hooks()) before evaluating the update.
+/// It allows to validate, to audit or to maintain data derived from the changes made by the update.
+///
+/// Each operation of the update is surrounded by a call to [`before_write`](UpdateHook::before_write),
+/// that is able to veto the operation, and a call to [`after_write`](UpdateHook::after_write).
+/// A veto aborts the whole update: the operations already done are rolled back,
+/// except for the batches already written by the update scheduler, each batch being its own transaction.
+/// The quads given to them are the ones the operation asks to remove and insert:
+/// they may contain quads already removed from or already inserted into the store.
+///
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{UpdateHook, UpdateOptions};
+/// use oxigraph::store::Store;
+/// use std::cell::Cell;
+/// use std::io;
+/// use std::rc::Rc;
+///
+/// struct ReadOnlyGraphHook {
+///     graph: NamedNode,
+///     inserted: Rc<Cell<usize>>,
+/// }
+///
+/// impl UpdateHook for ReadOnlyGraphHook {
+///     type Error = io::Error;
+///
+///     fn before_write(&self, deletions: &[Quad], insertions: &[Quad]) -> Result<(), io::Error> {
+///         if deletions.iter().chain(insertions).any(|q| q.graph_name == self.graph.clone().into()) {
+///             return Err(io::Error::new(io::ErrorKind::PermissionDenied, "The graph is read-only"));
+///         }
+///         Ok(())
+///     }
+///
+///     fn after_write(&self, _: &[Quad], insertions: &[Quad]) {
+///         self.inserted.set(self.inserted.get() + insertions.len());
+///     }
+/// }
+///
+/// let store = Store::new()?;
+/// let inserted = Rc::new(Cell::new(0));
+/// let options = UpdateOptions::default().with_hooks(ReadOnlyGraphHook {
+///     graph: NamedNode::new("http://example.com/g")?,
+///     inserted: Rc::clone(&inserted),
+/// });
+///
+/// store.update_opt("INSERT DATA { <http://example.com/s> <http://example.com/p> 1 }", options.clone())?;
+/// assert_eq!(inserted.get(), 1);
+///
+/// // The write in the read-only graph is vetoed and the first operation is rolled back
+/// assert!(store.update_opt("INSERT DATA { <http://example.com/s> <http://example.com/p> 2 } ; INSERT DATA { GRAPH <http://example.com/g> { <http://example.com/s> <http://example.com/p> 2 } }", options).is_err());
+/// assert_eq!(store.len()?, 1);
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub trait UpdateHook {
+    type Error: Error + Send + Sync + 'static;
+
+    /// Called with the quads an operation is about to remove and to insert.
+    ///
+    /// Returning an error vetoes the operation and aborts the whole update with this error.
+    fn before_write(&self, _deletions: &[Quad], _insertions: &[Quad]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called with the quads an operation has removed and inserted.
+    fn after_write(&self, _deletions: &[Quad], _insertions: &[Quad]) {}
+}
+
+pub struct ErrorConversionUpdateHook<H: UpdateHook> {
+    hook: H,
+}
+
+impl<H: UpdateHook> ErrorConversionUpdateHook<H> {
+    pub fn wrap(hook: H) -> Self {
+        Self { hook }
+    }
+}
+
+impl<H: UpdateHook> UpdateHook for ErrorConversionUpdateHook<H> {
+    type Error = EvaluationError;
+
+    fn before_write(&self, deletions: &[Quad], insertions: &[Quad]) -> Result<(), EvaluationError> {
+        self.hook
+            .before_write(deletions, insertions)
+            .map_err(EvaluationError::wrap)
+    }
+
+    fn after_write(&self, deletions: &[Quad], insertions: &[Quad]) {
+        self.hook.after_write(deletions, insertions);
+    }
+}
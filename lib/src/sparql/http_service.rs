@@ -0,0 +1,287 @@
+//! [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) `SERVICE` calls using [HTTPS outcalls](https://internetcomputer.org/https-outcalls).
+
+use crate::model::NamedNode;
+use crate::sparql::algebra::Query;
+use crate::sparql::error::EvaluationError;
+use crate::sparql::model::QueryResults;
+use crate::sparql::service::{prefetchable_services, ServiceHandler};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext, TransformFunc,
+};
+use ic_cdk::export::candid::{Func, Nat};
+use sparesults::{
+    QueryResultsFormat, QueryResultsParser, QueryResultsReader, QueryResultsSerializer,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Default maximal size of a service response.
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 2_000_000;
+/// Number of nodes of the subnet used to estimate the cost of an outcall.
+const SUBNET_SIZE: u128 = 13;
+
+/// A [`ServiceHandler`] executing the `SERVICE` calls with HTTPS outcalls.
+///
+/// The outcalls are asynchronous while the query evaluation is not,
+/// so the `SERVICE` calls of a query must be fetched with [`prefetch`](Self::prefetch) before evaluating it.
+/// Only the `SERVICE` calls with a constant IRI can be prefetched.
+/// The evaluation fails on the `SERVICE` calls that have not been prefetched, except if they are `SILENT`.
+///
+/// The cycles of each outcall are estimated before it is sent and the outcalls exceeding the cycles budget are not sent.
+///
+/// The replicas must agree on the responses, so the canister should expose [`transform_service_response`]
+/// as a query method and give its name to [`with_transform`](Self::with_transform).
+///
+/// This handler is only available with the `http-service` feature and must be used inside a canister.
+///
+/// ```no_run
+/// use oxigraph::sparql::{HttpServiceHandler, Query, QueryOptions, QueryResults};
+/// use oxigraph::store::Store;
+///
+/// async fn federated_query(store: &Store) -> Result<usize, Box<dyn std::error::Error>> {
+///     let query = Query::parse(
+///         "SELECT * WHERE { SERVICE <https://query.wikidata.org/sparql> { ?s ?p ?o } } LIMIT 10",
+///         None,
+///     )?;
+///     let handler = HttpServiceHandler::new(100_000_000_000).with_transform("transform");
+///     handler.prefetch(&query).await?;
+///     let options = QueryOptions::default().with_http_service_handler(handler);
+///     if let QueryResults::Solutions(solutions) = store.query_opt(query, options)? {
+///         return Ok(solutions.count());
+///     }
+///     Ok(0)
+/// }
+/// # let _ = federated_query;
+/// ```
+#[derive(Clone)]
+pub struct HttpServiceHandler {
+    cycles_budget: u128,
+    max_response_bytes: u64,
+    transform: Option<String>,
+    state: Rc<RefCell<HttpServiceState>>,
+}
+
+#[derive(Default)]
+struct HttpServiceState {
+    cycles_spent: u128,
+    responses: HashMap<(NamedNode, Query), (QueryResultsFormat, Vec<u8>)>,
+}
+
+impl HttpServiceHandler {
+    /// Builds a handler allowed to spend at most `cycles_budget` cycles in outcalls.
+    pub fn new(cycles_budget: u128) -> Self {
+        Self {
+            cycles_budget,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            transform: None,
+            state: Rc::default(),
+        }
+    }
+
+    /// Sets the maximal size of a service response, 2MB by default.
+    ///
+    /// The cost of an outcall grows with this size.
+    #[inline]
+    #[must_use]
+    pub fn with_max_response_bytes(mut self, max_response_bytes: u64) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Sets the name of the canister query method transforming the responses,
+    /// usually a wrapper around [`transform_service_response`].
+    #[inline]
+    #[must_use]
+    pub fn with_transform(mut self, method: impl Into<String>) -> Self {
+        self.transform = Some(method.into());
+        self
+    }
+
+    /// The estimated number of cycles spent in outcalls so far.
+    pub fn cycles_spent(&self) -> u128 {
+        self.state.borrow().cycles_spent
+    }
+
+    /// Fetches the results of the `SERVICE` calls of the query.
+    ///
+    /// The failures of the `SILENT` calls are ignored.
+    pub async fn prefetch(&self, query: &Query) -> Result<(), EvaluationError> {
+        for (name, service_query, silent) in prefetchable_services(query) {
+            let key = (name, service_query);
+            if self.state.borrow().responses.contains_key(&key) {
+                continue;
+            }
+            match self.fetch(&key.0, &key.1).await {
+                Ok(response) => {
+                    self.state.borrow_mut().responses.insert(key, response);
+                }
+                Err(_) if silent => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch(
+        &self,
+        service_name: &NamedNode,
+        query: &Query,
+    ) -> Result<(QueryResultsFormat, Vec<u8>), EvaluationError> {
+        let body = query.to_string().into_bytes();
+        let headers = vec![
+            HttpHeader {
+                name: "Accept".into(),
+                value: "application/sparql-results+json, application/sparql-results+xml".into(),
+            },
+            HttpHeader {
+                name: "Content-Type".into(),
+                value: "application/sparql-query".into(),
+            },
+        ];
+        let request_bytes = service_name.as_str().len()
+            + body.len()
+            + headers
+                .iter()
+                .map(|h| h.name.len() + h.value.len())
+                .sum::<usize>()
+            + self.transform.as_ref().map_or(0, String::len);
+        let cycles = outcall_cycles(request_bytes, self.max_response_bytes);
+        {
+            let mut state = self.state.borrow_mut();
+            if state.cycles_spent + cycles > self.cycles_budget {
+                return Err(EvaluationError::msg(format!(
+                    "The SERVICE call to {service_name} would exceed the outcall cycles budget of {}",
+                    self.cycles_budget
+                )));
+            }
+            state.cycles_spent += cycles;
+        }
+        let request = CanisterHttpRequestArgument {
+            url: service_name.as_str().into(),
+            max_response_bytes: Some(self.max_response_bytes),
+            method: HttpMethod::POST,
+            headers,
+            body: Some(body),
+            transform: self.transform.as_ref().map(|method| TransformContext {
+                function: TransformFunc(Func {
+                    principal: ic_cdk::api::id(),
+                    method: method.clone(),
+                }),
+                context: Vec::new(),
+            }),
+        };
+        let (response,) = http_request(request).await.map_err(|(code, message)| {
+            EvaluationError::msg(format!(
+                "The SERVICE call to {service_name} failed: {code:?}: {message}"
+            ))
+        })?;
+        if response.status < Nat::from(200_u64) || response.status >= Nat::from(300_u64) {
+            return Err(EvaluationError::msg(format!(
+                "{service_name} returned the HTTP status {}",
+                response.status
+            )));
+        }
+        let format = response_format(&response).ok_or_else(|| {
+            EvaluationError::msg(format!(
+                "Unsupported Content-Type returned by {service_name}"
+            ))
+        })?;
+        Ok((format, response.body))
+    }
+}
+
+impl ServiceHandler for HttpServiceHandler {
+    type Error = EvaluationError;
+
+    fn handle(
+        &self,
+        service_name: NamedNode,
+        query: Query,
+    ) -> Result<QueryResults, EvaluationError> {
+        let (format, body) = self
+            .state
+            .borrow()
+            .responses
+            .get(&(service_name, query))
+            .cloned()
+            .ok_or_else(|| {
+                EvaluationError::msg(
+                    "The SERVICE call has not been prefetched with HttpServiceHandler::prefetch",
+                )
+            })?;
+        Ok(QueryResults::read(std::io::Cursor::new(body), format)?)
+    }
+}
+
+/// Normalizes a SPARQL service response so that all the replicas agree on it.
+///
+/// The headers are dropped except `Content-Type`, and the results are serialized again
+/// in the SPARQL JSON format with the solutions sorted.
+/// The responses that are not valid SPARQL results are only stripped of their headers.
+///
+/// It should be wrapped in a canister query method given to [`HttpServiceHandler::with_transform`]:
+/// ```ignore
+/// #[ic_cdk_macros::query]
+/// fn transform(args: TransformArgs) -> HttpResponse {
+///     oxigraph::sparql::transform_service_response(args)
+/// }
+/// ```
+pub fn transform_service_response(args: TransformArgs) -> HttpResponse {
+    let mut response = args.response;
+    let format = response_format(&response);
+    response
+        .headers
+        .retain(|header| header.name.eq_ignore_ascii_case("content-type"));
+    if let Some(body) = format.and_then(|format| canonicalize(&response.body, format)) {
+        response.body = body;
+        response.headers = vec![HttpHeader {
+            name: "Content-Type".into(),
+            value: QueryResultsFormat::Json.media_type().into(),
+        }];
+    }
+    response
+}
+
+fn canonicalize(body: &[u8], format: QueryResultsFormat) -> Option<Vec<u8>> {
+    let serializer = QueryResultsSerializer::from_format(QueryResultsFormat::Json);
+    match QueryResultsParser::from_format(format)
+        .read_results(body)
+        .ok()?
+    {
+        QueryResultsReader::Boolean(value) => {
+            serializer.write_boolean_result(Vec::new(), value).ok()
+        }
+        QueryResultsReader::Solutions(solutions) => {
+            let variables = solutions.variables().to_vec();
+            let mut solutions = solutions.collect::<Result<Vec<_>, _>>().ok()?;
+            solutions.sort_by_cached_key(|solution| {
+                solution
+                    .iter()
+                    .map(|(variable, value)| format!("{variable}={value}"))
+                    .collect::<Vec<_>>()
+            });
+            let mut writer = serializer.solutions_writer(Vec::new(), variables).ok()?;
+            for solution in &solutions {
+                writer.write(solution).ok()?;
+            }
+            writer.finish().ok()
+        }
+    }
+}
+
+fn response_format(response: &HttpResponse) -> Option<QueryResultsFormat> {
+    response
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-type"))
+        .and_then(|header| QueryResultsFormat::from_media_type(&header.value))
+}
+
+/// Estimates the cycles charged for an outcall, following the published pricing for a 13 nodes subnet.
+fn outcall_cycles(request_bytes: usize, max_response_bytes: u64) -> u128 {
+    (3_000_000 + 60_000 * SUBNET_SIZE) * SUBNET_SIZE
+        + 400 * SUBNET_SIZE * request_bytes as u128
+        + 800 * SUBNET_SIZE * u128::from(max_response_bytes)
+}
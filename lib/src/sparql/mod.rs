@@ -3,32 +3,60 @@
 //! Stores execute SPARQL. See [`Store`](crate::store::Store::query()) for an example.
 
 mod algebra;
+mod analysis;
+#[cfg(feature = "canister-service")]
+mod canister_service;
 mod dataset;
 mod error;
+mod estimate;
 mod eval;
+mod explanation;
+mod hook;
 mod http;
+#[cfg(feature = "http-service")]
+mod http_service;
 mod model;
 mod plan;
 mod plan_builder;
+mod policy;
+mod regex_cache;
 mod service;
 mod time;
 mod update;
+mod view;
 
-use crate::model::{NamedNode, Term};
+use crate::model::{GraphName, NamedNode, Quad, Term};
 pub use crate::sparql::algebra::{Query, QueryDataset, Update};
+pub use crate::sparql::analysis::{CostBand, QueryAnalysis};
+#[cfg(feature = "canister-service")]
+pub use crate::sparql::canister_service::{evaluate_service_query, CanisterServiceHandler};
 use crate::sparql::dataset::DatasetView;
 pub use crate::sparql::error::{EvaluationError, QueryError};
-use crate::sparql::eval::{SimpleEvaluator, Timer};
+pub(crate) use crate::sparql::estimate::estimate_query_cost;
+pub use crate::sparql::estimate::CostEstimate;
+use crate::sparql::eval::{BlankNodeGenerator, Deadline, SimpleEvaluator, Timer};
+pub use crate::sparql::explanation::{PlanNode, PlanNodeStats, PlanOperator};
+use crate::sparql::hook::ErrorConversionUpdateHook;
+pub use crate::sparql::hook::UpdateHook;
+#[cfg(feature = "http-service")]
+pub use crate::sparql::http_service::{transform_service_response, HttpServiceHandler};
+#[cfg(feature = "candid")]
+pub use crate::sparql::model::CandidQueryResults;
 pub use crate::sparql::model::{QueryResults, QuerySolution, QuerySolutionIter, QueryTripleIter};
 use crate::sparql::plan::PlanNodeWithStats;
 use crate::sparql::plan_builder::PlanBuilder;
+pub use crate::sparql::policy::QueryPolicy;
+pub use crate::sparql::regex_cache::RegexCache;
+use crate::sparql::regex_cache::RegexCacheStats;
 pub use crate::sparql::service::ServiceHandler;
 use crate::sparql::service::{EmptyServiceHandler, ErrorConversionServiceHandler};
-pub(crate) use crate::sparql::update::evaluate_update;
+pub(crate) use crate::sparql::update::{evaluate_update, BatchedUpdate};
+pub(crate) use crate::sparql::view::{ChangeEffect, IncrementalQuery};
 use crate::storage::StorageReader;
 use json_event_parser::{JsonEvent, JsonWriter};
 pub use oxrdf::{Variable, VariableNameParseError};
 pub use sparesults::QueryResultsFormat;
+pub use spargebra::algebra::PropertyPathExpression;
 pub use spargebra::ParseError;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -43,7 +71,17 @@ pub(crate) fn evaluate_query(
     run_stats: bool,
 ) -> Result<(Result<QueryResults, EvaluationError>, QueryExplanation), EvaluationError> {
     let query = query.try_into().map_err(Into::into)?;
-    let dataset = DatasetView::new(reader, &query.dataset);
+    options.policy.check(&query)?;
+    let deadline = options.deadline();
+    #[cfg(feature = "http-service")]
+    let outcall_cycles = options
+        .http_service_handler
+        .as_ref()
+        .map(HttpServiceHandler::cycles_spent);
+    let regex_cache = options.regex_cache.clone().unwrap_or_default();
+    let regex_cache_stats = Rc::new(RegexCacheStats::default());
+    let dataset =
+        DatasetView::new(reader, &query.dataset).with_hidden_graphs(&options.hidden_graphs);
     let start_planning = Timer::now();
     let (results, plan_node_with_stats, planning_duration) = match query.inner {
         spargebra::Query::Select {
@@ -64,6 +102,9 @@ pub(crate) fn evaluate_query(
                 Rc::new(options.custom_functions),
                 run_stats,
             )
+            .with_deadline(deadline)
+            .with_limits(options.max_results, options.max_intermediate_bindings)
+            .with_regex_cache(regex_cache, Rc::clone(&regex_cache_stats))
             .evaluate_select_plan(Rc::new(plan), Rc::new(variables));
             (Ok(results), explanation, planning_duration)
         }
@@ -85,6 +126,9 @@ pub(crate) fn evaluate_query(
                 Rc::new(options.custom_functions),
                 run_stats,
             )
+            .with_deadline(deadline)
+            .with_limits(options.max_results, options.max_intermediate_bindings)
+            .with_regex_cache(regex_cache, Rc::clone(&regex_cache_stats))
             .evaluate_ask_plan(Rc::new(plan));
             (results, explanation, planning_duration)
         }
@@ -116,6 +160,9 @@ pub(crate) fn evaluate_query(
                 Rc::new(options.custom_functions),
                 run_stats,
             )
+            .with_deadline(deadline)
+            .with_limits(options.max_results, options.max_intermediate_bindings)
+            .with_regex_cache(regex_cache, Rc::clone(&regex_cache_stats))
             .evaluate_construct_plan(Rc::new(plan), construct);
             (Ok(results), explanation, planning_duration)
         }
@@ -137,6 +184,9 @@ pub(crate) fn evaluate_query(
                 Rc::new(options.custom_functions),
                 run_stats,
             )
+            .with_deadline(deadline)
+            .with_limits(options.max_results, options.max_intermediate_bindings)
+            .with_regex_cache(regex_cache, Rc::clone(&regex_cache_stats))
             .evaluate_describe_plan(Rc::new(plan));
             (Ok(results), explanation, planning_duration)
         }
@@ -146,6 +196,9 @@ pub(crate) fn evaluate_query(
         with_stats: run_stats,
         parsing_duration: query.parsing_duration,
         planning_duration,
+        regex_cache_stats,
+        #[cfg(feature = "http-service")]
+        outcall_cycles,
     };
     Ok((results, explanation))
 }
@@ -175,6 +228,16 @@ pub struct QueryOptions {
     http_timeout: Option<Duration>,
     http_redirection_limit: usize,
     without_optimizations: bool,
+    policy: QueryPolicy,
+    max_instructions: Option<u64>,
+    timeout: Option<Duration>,
+    max_results: Option<usize>,
+    max_intermediate_bindings: Option<usize>,
+    as_of: Option<u64>,
+    regex_cache: Option<RegexCache>,
+    hidden_graphs: Vec<GraphName>,
+    #[cfg(feature = "http-service")]
+    http_service_handler: Option<HttpServiceHandler>,
 }
 
 impl QueryOptions {
@@ -182,20 +245,51 @@ impl QueryOptions {
     #[inline]
     #[must_use]
     pub fn with_service_handler(mut self, service_handler: impl ServiceHandler + 'static) -> Self {
-        self.service_handler = Some(Rc::new(ErrorConversionServiceHandler::wrap(
+        self.set_service_handler(Rc::new(ErrorConversionServiceHandler::wrap(
             service_handler,
         )));
         self
     }
 
+    /// Executes the `SERVICE` calls with HTTPS outcalls using a prefetched [`HttpServiceHandler`].
+    ///
+    /// This method is only available with the `http-service` feature.
+    #[cfg(feature = "http-service")]
+    #[inline]
+    #[must_use]
+    pub fn with_http_service_handler(mut self, handler: HttpServiceHandler) -> Self {
+        self.set_service_handler(Rc::new(handler.clone()));
+        self.http_service_handler = Some(handler);
+        self
+    }
+
+    /// Executes the `SERVICE <icp://...>` calls with inter-canister calls using a prefetched [`CanisterServiceHandler`].
+    ///
+    /// This method is only available with the `canister-service` feature.
+    #[cfg(feature = "canister-service")]
+    #[inline]
+    #[must_use]
+    pub fn with_canister_service_handler(mut self, handler: CanisterServiceHandler) -> Self {
+        self.set_service_handler(Rc::new(handler));
+        self
+    }
+
     /// Disables the `SERVICE` calls
     #[inline]
     #[must_use]
     pub fn without_service_handler(mut self) -> Self {
-        self.service_handler = Some(Rc::new(EmptyServiceHandler));
+        self.set_service_handler(Rc::new(EmptyServiceHandler));
         self
     }
 
+    fn set_service_handler(&mut self, handler: Rc<dyn ServiceHandler<Error = EvaluationError>>) {
+        self.service_handler = Some(handler);
+        #[cfg(feature = "http-service")]
+        {
+            self.http_service_handler = None;
+        }
+    }
+
     /// Sets a timeout for HTTP requests done during SPARQL evaluation.
     #[cfg(feature = "http_client")]
     #[inline]
@@ -248,6 +342,186 @@ impl QueryOptions {
         self
     }
 
+    /// Restricts the SPARQL features the query is allowed to use.
+    ///
+    /// See [`QueryPolicy`] for an example.
+    #[inline]
+    #[must_use]
+    pub fn with_policy(mut self, policy: QueryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Cancels the evaluation with [`EvaluationError::Cancelled`] once the current canister message
+    /// has executed `max_instructions` instructions.
+    ///
+    /// It allows to fail cleanly instead of trapping when the instruction limit of the message is reached.
+    /// The limit also applies to the `WHERE` clauses of the updates.
+    ///
+    /// The number of executed instructions is given by `ic_cdk::api::performance_counter`,
+    /// so the query must be evaluated inside a canister.
+    ///
+    /// ```no_run
+    /// use oxigraph::store::Store;
+    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
+    ///
+    /// let store = Store::new()?;
+    /// if let QueryResults::Solutions(solutions) = store.query_opt(
+    ///     "SELECT * WHERE { ?s ?p ?o }",
+    ///     QueryOptions::default().with_max_instructions(4_000_000_000),
+    /// )? {
+    ///     for solution in solutions {
+    ///         if let Err(EvaluationError::Cancelled) = solution {
+    ///             // The query is too expensive
+    ///         }
+    ///     }
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_max_instructions(mut self, max_instructions: u64) -> Self {
+        self.max_instructions = Some(max_instructions);
+        self
+    }
+
+    /// Cancels the evaluation with [`EvaluationError::Cancelled`] once it has lasted more than `timeout`.
+    ///
+    /// The time of the Internet Computer does not change during the execution of a message,
+    /// so inside a canister this timeout only bounds the evaluations spanning several messages.
+    /// Use [`with_max_instructions`](Self::with_max_instructions) to bound the evaluation in a single message.
+    #[inline]
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails the evaluation with [`EvaluationError::TooManyResults`] once the query has returned `max_results` results
+    /// (solutions for the `SELECT` queries, triples for the `CONSTRUCT` and `DESCRIBE` queries).
+    ///
+    /// The results returned before the error are valid but the results are incomplete.
+    ///
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// for i in 0..3 {
+    ///     store.insert(QuadRef::new(ex, ex, &Literal::from(i), GraphNameRef::DefaultGraph))?;
+    /// }
+    /// if let QueryResults::Solutions(solutions) = store.query_opt(
+    ///     "SELECT ?o WHERE { ?s ?p ?o }",
+    ///     QueryOptions::default().with_max_results(2),
+    /// )? {
+    ///     let solutions = solutions.collect::<Vec<_>>();
+    ///     assert_eq!(solutions.len(), 3);
+    ///     assert!(matches!(solutions[2], Err(EvaluationError::TooManyResults)));
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Fails the evaluation with [`EvaluationError::TooManyIntermediateBindings`] once its joins and aggregations
+    /// have produced more than `max_intermediate_bindings` bindings.
+    ///
+    /// The bindings counted are the ones returned by the joins and the ones buffered by the aggregations,
+    /// the `ORDER BY` and the `DISTINCT` before returning their results.
+    /// It protects the canister heap from the queries with an accidental cross product,
+    /// that may exhaust the memory before the [instruction limit](Self::with_max_instructions) is reached.
+    /// The limit also applies to the `WHERE` clauses of the updates.
+    ///
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// for i in 0..10 {
+    ///     store.insert(QuadRef::new(ex, ex, &Literal::from(i), GraphNameRef::DefaultGraph))?;
+    /// }
+    /// // The cross product produces 100 bindings
+    /// if let QueryResults::Solutions(mut solutions) = store.query_opt(
+    ///     "SELECT (COUNT(*) AS ?c) WHERE { ?s1 ?p1 ?o1 . ?s2 ?p2 ?o2 }",
+    ///     QueryOptions::default().with_max_intermediate_bindings(50),
+    /// )? {
+    ///     assert!(matches!(solutions.next(), Some(Err(EvaluationError::TooManyIntermediateBindings))));
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_max_intermediate_bindings(mut self, max_intermediate_bindings: usize) -> Self {
+        self.max_intermediate_bindings = Some(max_intermediate_bindings);
+        self
+    }
+
+    /// Evaluates the query against the store as it was at the given [dataset version](crate::store::Store::dataset_version).
+    ///
+    /// The store must keep the version in its history (see [`StorageConfig::with_history`](crate::store::StorageConfig::with_history)),
+    /// the evaluation fails with [`StorageError::VersionNotAvailable`](crate::store::StorageError::VersionNotAvailable) otherwise.
+    /// It is not supported by the queries evaluated inside a transaction.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{QueryOptions, QueryResults};
+    /// use oxigraph::store::{StorageConfig, Store};
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_history(10))?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// let version = store.dataset_version()?;
+    /// store.remove(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///
+    /// if let QueryResults::Boolean(found) = store.query_opt("ASK { ?s ?p ?o }", QueryOptions::default().with_as_of(version))? {
+    ///     assert!(found);
+    /// }
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_as_of(mut self, version: u64) -> Self {
+        self.as_of = Some(version);
+        self
+    }
+
+    /// Keeps the regular expressions compiled by `REGEX` and `REPLACE` in the given cache,
+    /// that can be shared by several query evaluations.
+    ///
+    /// By default each evaluation uses its own cache.
+    /// See [`RegexCache`] for an example.
+    #[inline]
+    #[must_use]
+    pub fn with_regex_cache(mut self, cache: RegexCache) -> Self {
+        self.regex_cache = Some(cache);
+        self
+    }
+
+    pub(crate) fn as_of(&self) -> Option<u64> {
+        self.as_of
+    }
+
+    /// Hides the given graphs from the evaluation, as if they were not in the store
+    #[must_use]
+    pub(crate) fn with_hidden_graphs(mut self, graph_names: Vec<GraphName>) -> Self {
+        self.hidden_graphs = graph_names;
+        self
+    }
+
+    fn deadline(&self) -> Option<Rc<Deadline>> {
+        (self.max_instructions.is_some() || self.timeout.is_some())
+            .then(|| Rc::new(Deadline::new(self.max_instructions, self.timeout)))
+    }
+
     fn service_handler(&self) -> Rc<dyn ServiceHandler<Error = EvaluationError>> {
         self.service_handler.clone().unwrap_or_else(|| {
             if cfg!(feature = "http_client") {
@@ -274,12 +548,86 @@ impl QueryOptions {
 #[derive(Clone, Default)]
 pub struct UpdateOptions {
     query_options: QueryOptions,
+    hooks: Vec<Rc<dyn UpdateHook<Error = EvaluationError>>>,
+    blank_node_seed: Option<u64>,
+}
+
+impl UpdateOptions {
+    /// Notifies an [`UpdateHook`] of the quads written by each operation of the update.
+    ///
+    /// Several hooks can be added: they are called in the order they have been added.
+    #[inline]
+    #[must_use]
+    pub fn with_hooks(mut self, hook: impl UpdateHook + 'static) -> Self {
+        self.hooks
+            .push(Rc::new(ErrorConversionUpdateHook::wrap(hook)));
+        self
+    }
+
+    /// Derives the blank nodes created by the update, by `BNODE()` or by the blank nodes of the `INSERT` templates,
+    /// from `seed` and a counter instead of picking them at random.
+    ///
+    /// Evaluating the same update with the same seed against the same store then gives the same results,
+    /// which is useful to replay updates or to write tests.
+    /// A different seed should be used for each update: the blank nodes created by two updates
+    /// evaluated with the same seed are the same.
+    ///
+    /// ```
+    /// use oxigraph::sparql::UpdateOptions;
+    /// use oxigraph::store::Store;
+    ///
+    /// let update = "INSERT { ?b <http://example.com/p> 1 } WHERE { BIND(BNODE() AS ?b) }";
+    /// let store1 = Store::new()?;
+    /// store1.update_opt(update, UpdateOptions::default().with_deterministic_bnodes(42))?;
+    /// let store2 = Store::new()?;
+    /// store2.update_opt(update, UpdateOptions::default().with_deterministic_bnodes(42))?;
+    /// assert_eq!(
+    ///     store1.iter().collect::<Result<Vec<_>, _>>()?,
+    ///     store2.iter().collect::<Result<Vec<_>, _>>()?
+    /// );
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_deterministic_bnodes(mut self, seed: u64) -> Self {
+        self.blank_node_seed = Some(seed);
+        self
+    }
+
+    pub(crate) fn blank_node_generator(&self) -> Rc<BlankNodeGenerator> {
+        Rc::new(BlankNodeGenerator::new(self.blank_node_seed))
+    }
+
+    pub(crate) fn has_hooks(&self) -> bool {
+        !self.hooks.is_empty()
+    }
+
+    pub(crate) fn before_write(
+        &self,
+        deletions: &[Quad],
+        insertions: &[Quad],
+    ) -> Result<(), EvaluationError> {
+        for hook in &self.hooks {
+            hook.before_write(deletions, insertions)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn after_write(&self, deletions: &[Quad], insertions: &[Quad]) {
+        for hook in &self.hooks {
+            hook.after_write(deletions, insertions);
+        }
+    }
 }
 
 impl From<QueryOptions> for UpdateOptions {
     #[inline]
     fn from(query_options: QueryOptions) -> Self {
-        Self { query_options }
+        Self {
+            query_options,
+            hooks: Vec::new(),
+            blank_node_seed: None,
+        }
     }
 }
 
@@ -290,9 +638,56 @@ pub struct QueryExplanation {
     with_stats: bool,
     parsing_duration: Option<Duration>,
     planning_duration: Duration,
+    regex_cache_stats: Rc<RegexCacheStats>,
+    #[cfg(feature = "http-service")]
+    outcall_cycles: Option<u128>,
 }
 
 impl QueryExplanation {
+    /// The cycles spent in outcalls by the [`HttpServiceHandler`] of the query, including its prefetching.
+    ///
+    /// This method is only available with the `http-service` feature.
+    #[cfg(feature = "http-service")]
+    #[inline]
+    pub fn outcall_cycles(&self) -> Option<u128> {
+        self.outcall_cycles
+    }
+
+    /// The time spent parsing the query, `None` if it was given already parsed.
+    #[inline]
+    pub fn parsing_duration(&self) -> Option<Duration> {
+        self.parsing_duration
+    }
+
+    /// The time spent building the query plan.
+    #[inline]
+    pub fn planning_duration(&self) -> Duration {
+        self.planning_duration
+    }
+
+    /// The number of regular expressions found in the [`RegexCache`] by the evaluation.
+    ///
+    /// Like the statistics, it only counts the results read so far.
+    #[inline]
+    pub fn regex_cache_hits(&self) -> u64 {
+        self.regex_cache_stats.hits.get()
+    }
+
+    /// The number of regular expressions compiled by the evaluation because they were not in the [`RegexCache`].
+    #[inline]
+    pub fn regex_cache_misses(&self) -> u64 {
+        self.regex_cache_stats.misses.get()
+    }
+
+    /// The plan of the query with, if they have been requested, its evaluation statistics.
+    ///
+    /// The statistics are a snapshot: the plan must be fetched again after having read more results.
+    ///
+    /// See [`PlanNode`] for an example.
+    pub fn plan(&self) -> PlanNode {
+        PlanNode::new(&self.inner, self.with_stats)
+    }
+
     /// Writes the explanation as JSON.
     pub fn write_in_json(&self, output: impl io::Write) -> io::Result<()> {
         let mut writer = JsonWriter::from_writer(output);
@@ -307,10 +702,52 @@ impl QueryExplanation {
         writer.write_event(JsonEvent::Number(
             &self.planning_duration.as_secs_f32().to_string(),
         ))?;
+        if self.regex_cache_hits() > 0 || self.regex_cache_misses() > 0 {
+            writer.write_event(JsonEvent::ObjectKey("regex cache hits"))?;
+            writer.write_event(JsonEvent::Number(&self.regex_cache_hits().to_string()))?;
+            writer.write_event(JsonEvent::ObjectKey("regex cache misses"))?;
+            writer.write_event(JsonEvent::Number(&self.regex_cache_misses().to_string()))?;
+        }
+        #[cfg(feature = "http-service")]
+        if let Some(outcall_cycles) = self.outcall_cycles {
+            writer.write_event(JsonEvent::ObjectKey("outcall cycles"))?;
+            writer.write_event(JsonEvent::Number(&outcall_cycles.to_string()))?;
+        }
         writer.write_event(JsonEvent::ObjectKey("plan"))?;
-        self.inner.json_node(&mut writer, self.with_stats)?;
+        self.plan().write_in_json(&mut writer)?;
         writer.write_event(JsonEvent::EndObject)
     }
+
+    /// Writes the plan as a [Graphviz](https://graphviz.org/) DOT graph.
+    ///
+    /// Each box is an operator with its estimated cardinality and, if they have been requested, its statistics.
+    /// The more instructions an operator executes itself, the darker its box is.
+    ///
+    /// ```
+    /// use oxigraph::sparql::QueryOptions;
+    /// use oxigraph::store::Store;
+    ///
+    /// let store = Store::new()?;
+    /// let (_, explanation) = store.explain_query_opt(
+    ///     "SELECT ?s WHERE { ?s ?p ?o }",
+    ///     QueryOptions::default(),
+    ///     false,
+    /// )?;
+    /// let mut dot = Vec::new();
+    /// explanation.write_in_dot(&mut dot)?;
+    /// assert!(String::from_utf8(dot)?.starts_with("digraph plan {"));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn write_in_dot(&self, mut output: impl io::Write) -> io::Result<()> {
+        self.plan().write_in_dot(&mut output)
+    }
+}
+
+/// Writes the plan as an ASCII tree, like [`PlanNode`] does.
+impl fmt::Display for QueryExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.plan())
+    }
 }
 
 impl fmt::Debug for QueryExplanation {
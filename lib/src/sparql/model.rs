@@ -146,6 +146,56 @@ impl<R: BufRead + 'static> From<QueryResultsReader<R>> for QueryResults {
     }
 }
 
+/// [`QueryResults`] loaded in memory to be returned by a canister method using [Candid](https://github.com/dfinity/candid).
+///
+/// This type is only available with the `candid` feature.
+///
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::CandidQueryResults;
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+///
+/// let results = CandidQueryResults::try_from(store.query("SELECT ?s WHERE { ?s ?p ?o }")?)?;
+/// if let CandidQueryResults::Solutions { variables, solutions } = results {
+///     assert_eq!(variables, vec![Variable::new("s")?]);
+///     assert_eq!(solutions[0].get("s"), Some(&ex.into_owned().into()));
+/// }
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[cfg(feature = "candid")]
+#[derive(candid::CandidType, Debug)]
+pub enum CandidQueryResults {
+    /// Results of a [SELECT](https://www.w3.org/TR/sparql11-query/#select) query.
+    Solutions {
+        variables: Vec<Variable>,
+        solutions: Vec<QuerySolution>,
+    },
+    /// Result of a [ASK](https://www.w3.org/TR/sparql11-query/#ask) query.
+    Boolean(bool),
+    /// Results of a [CONSTRUCT](https://www.w3.org/TR/sparql11-query/#construct) or [DESCRIBE](https://www.w3.org/TR/sparql11-query/#describe) query.
+    Graph(Vec<Triple>),
+}
+
+#[cfg(feature = "candid")]
+impl TryFrom<QueryResults> for CandidQueryResults {
+    type Error = EvaluationError;
+
+    fn try_from(results: QueryResults) -> Result<Self, EvaluationError> {
+        Ok(match results {
+            QueryResults::Solutions(solutions) => Self::Solutions {
+                variables: solutions.variables().to_vec(),
+                solutions: solutions.collect::<Result<_, _>>()?,
+            },
+            QueryResults::Boolean(value) => Self::Boolean(value),
+            QueryResults::Graph(triples) => Self::Graph(triples.collect::<Result<_, _>>()?),
+        })
+    }
+}
+
 /// An iterator over [`QuerySolution`]s.
 ///
 /// ```
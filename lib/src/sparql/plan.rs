@@ -1,7 +1,6 @@
 use crate::model::{BlankNode, Literal, NamedNode, Term, Triple};
 use crate::sparql::Variable;
 use crate::storage::numeric_encoder::EncodedTerm;
-use json_event_parser::{JsonEvent, JsonWriter};
 use regex::Regex;
 use spargebra::algebra::GraphPattern;
 use spargebra::term::GroundTerm;
@@ -9,9 +8,8 @@ use std::cell::Cell;
 use std::cmp::max;
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::rc::Rc;
-use std::time::Duration;
-use std::{fmt, io};
 
 #[derive(Debug, Clone)]
 pub enum PlanNode {
@@ -33,6 +31,24 @@ pub enum PlanNode {
         object: PatternValue,
         graph_name: PatternValue,
     },
+    /// Returns the distinct values of `variable` in the quads matching the pattern
+    DistinctTerms {
+        subject: PatternValue,
+        predicate: PatternValue,
+        object: PatternValue,
+        graph_name: PatternValue,
+        variable: PlanVariable,
+    },
+    /// Counts the quads matching the pattern without decoding them
+    CountQuads {
+        subject: PatternValue,
+        predicate: PatternValue,
+        object: PatternValue,
+        graph_name: PatternValue,
+        /// Counts the distinct graph names of the matching quads instead
+        distinct_graphs: bool,
+        variable: PlanVariable,
+    },
     PathPattern {
         subject: PatternValue,
         path: Rc<PlanPropertyPath>,
@@ -141,6 +157,9 @@ impl PlanNode {
                 object.lookup_variables(callback);
                 graph_name.lookup_variables(callback);
             }
+            Self::CountQuads { variable, .. } | Self::DistinctTerms { variable, .. } => {
+                callback(variable.encoded)
+            }
             Self::PathPattern {
                 subject,
                 object,
@@ -271,6 +290,9 @@ impl PlanNode {
                 object.lookup_variables(callback);
                 graph_name.lookup_variables(callback);
             }
+            Self::CountQuads { variable, .. } | Self::DistinctTerms { variable, .. } => {
+                callback(variable.encoded)
+            }
             Self::PathPattern {
                 subject,
                 object,
@@ -519,6 +541,7 @@ pub enum PlanExpression {
     Object(Box<Self>),
     IsTriple(Box<Self>),
     Adjust(Box<Self>, Box<Self>),
+    FormatNumber(Box<Self>, Box<Self>),
     BooleanCast(Box<Self>),
     DoubleCast(Box<Self>),
     FloatCast(Box<Self>),
@@ -618,7 +641,8 @@ impl PlanExpression {
             | Self::SameTerm(a, b)
             | Self::SubStr(a, b, None)
             | Self::DynamicRegex(a, b, None)
-            | Self::Adjust(a, b) => {
+            | Self::Adjust(a, b)
+            | Self::FormatNumber(a, b) => {
                 a.lookup_used_variables(callback);
                 b.lookup_used_variables(callback);
             }
@@ -767,6 +791,7 @@ impl fmt::Display for PlanExpression {
             Self::SubStr(a, b, None) => write!(f, "SubStr({a}, {b})"),
             Self::DynamicRegex(a, b, None) => write!(f, "DynamicRegex({a}, {b})"),
             Self::Adjust(a, b) => write!(f, "Adjust({a}, {b})"),
+            Self::FormatNumber(a, b) => write!(f, "FormatNumber({a}, {b})"),
             Self::If(a, b, c) => write!(f, "If({a}, {b}, {c})"),
             Self::SubStr(a, b, Some(c)) => write!(f, "SubStr({a}, {b}, {c})"),
             Self::DynamicRegex(a, b, Some(c)) => write!(f, "DynamicRegex({a}, {b}, {c})"),
@@ -1031,36 +1056,22 @@ pub struct PlanNodeWithStats {
     pub node: Rc<PlanNode>,
     pub children: Vec<Rc<PlanNodeWithStats>>,
     pub exec_count: Cell<usize>,
-    pub exec_duration: Cell<Duration>,
+    /// The number of times the node has been evaluated
+    pub exec_evaluations: Cell<usize>,
+    /// The instructions executed by the node and its children
+    pub exec_instructions: Cell<u64>,
+    /// The nodes visited by the transitive closures of a property path node
+    pub visited_nodes: Rc<Cell<usize>>,
+    /// The index entries read by the node and its children
+    pub read_entries: Cell<usize>,
+    /// The index scans stopped by the node and its children before having read all their entries
+    pub stopped_scans: Cell<usize>,
+    /// The number of results estimated from the store statistics
+    pub estimated_cardinality: Option<u64>,
 }
 
 impl PlanNodeWithStats {
-    pub fn json_node(
-        &self,
-        writer: &mut JsonWriter<impl io::Write>,
-        with_stats: bool,
-    ) -> io::Result<()> {
-        writer.write_event(JsonEvent::StartObject)?;
-        writer.write_event(JsonEvent::ObjectKey("name"))?;
-        writer.write_event(JsonEvent::String(&self.node_label()))?;
-        if with_stats {
-            writer.write_event(JsonEvent::ObjectKey("number of results"))?;
-            writer.write_event(JsonEvent::Number(&self.exec_count.get().to_string()))?;
-            writer.write_event(JsonEvent::ObjectKey("duration in seconds"))?;
-            writer.write_event(JsonEvent::Number(
-                &self.exec_duration.get().as_secs_f32().to_string(),
-            ))?;
-        }
-        writer.write_event(JsonEvent::ObjectKey("children"))?;
-        writer.write_event(JsonEvent::StartArray)?;
-        for child in &self.children {
-            child.json_node(writer, with_stats)?;
-        }
-        writer.write_event(JsonEvent::EndArray)?;
-        writer.write_event(JsonEvent::EndObject)
-    }
-
-    fn node_label(&self) -> String {
+    pub fn node_label(&self) -> String {
         match self.node.as_ref() {
             PlanNode::Aggregate {
                 key_variables,
@@ -1076,6 +1087,30 @@ impl PlanNodeWithStats {
                     .join(", ")
             ),
             PlanNode::AntiJoin { .. } => "AntiJoin".to_owned(),
+            PlanNode::CountQuads {
+                subject,
+                predicate,
+                object,
+                graph_name,
+                distinct_graphs,
+                variable,
+            } => {
+                let name = if *distinct_graphs {
+                    "CountGraphs"
+                } else {
+                    "CountQuads"
+                };
+                format!("{name}({subject} {predicate} {object} {graph_name} -> {variable})")
+            }
+            PlanNode::DistinctTerms {
+                subject,
+                predicate,
+                object,
+                graph_name,
+                variable,
+            } => {
+                format!("DistinctTerms({subject} {predicate} {object} {graph_name} -> {variable})")
+            }
             PlanNode::Extend {
                 expression,
                 variable,
@@ -1155,9 +1190,15 @@ impl fmt::Debug for PlanNodeWithStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut obj = f.debug_struct("Node");
         obj.field("name", &self.node_label());
-        if self.exec_duration.get() > Duration::default() {
+        if self.exec_evaluations.get() > 0 {
             obj.field("number of results", &self.exec_count.get());
-            obj.field("duration in seconds", &self.exec_duration.get());
+            obj.field("evaluations", &self.exec_evaluations.get());
+            obj.field("instructions", &self.exec_instructions.get());
+            obj.field("read index entries", &self.read_entries.get());
+            obj.field("stopped index scans", &self.stopped_scans.get());
+            if matches!(self.node.as_ref(), PlanNode::PathPattern { .. }) {
+                obj.field("visited nodes", &self.visited_nodes.get());
+            }
         }
         if !self.children.is_empty() {
             obj.field("children", &self.children);
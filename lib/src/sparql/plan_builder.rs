@@ -4,6 +4,7 @@ use crate::sparql::error::EvaluationError;
 use crate::sparql::eval::compile_pattern;
 use crate::sparql::plan::*;
 use crate::storage::numeric_encoder::{EncodedTerm, EncodedTriple};
+use crate::storage::ObjectClass;
 use oxrdf::vocab::xsd;
 use oxrdf::TermRef;
 use rand::random;
@@ -12,8 +13,14 @@ use spargebra::algebra::*;
 use spargebra::term::*;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::mem::swap;
+use std::ops::Bound;
 use std::rc::Rc;
 
+/// Maximal number of values bound from the literal or range index in place of the evaluation of a filter
+const MAX_INDEX_BINDINGS: usize = 1000;
+
+const XPATH_FUNCTIONS_NAMESPACE: &str = "http://www.w3.org/2005/xpath-functions#";
+
 pub struct PlanBuilder<'a> {
     dataset: &'a DatasetView,
     custom_functions: &'a HashMap<NamedNode, Rc<dyn Fn(&[OxTerm]) -> Option<OxTerm>>>,
@@ -78,7 +85,7 @@ impl<'a> PlanBuilder<'a> {
         Ok(match pattern {
             GraphPattern::Bgp { patterns } => {
                 if self.with_optimizations {
-                    self.build_for_bgp(sort_bgp(patterns), variables, graph_name)
+                    self.build_for_bgp(sort_bgp(patterns, self.dataset), variables, graph_name)
                 } else {
                     self.build_for_bgp(patterns, variables, graph_name)
                 }
@@ -201,13 +208,13 @@ impl<'a> PlanBuilder<'a> {
                 inner,
                 variables: by,
                 aggregates,
-            } => PlanNode::Aggregate {
-                child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
-                key_variables: by
+            } => {
+                let child = self.build_for_graph_pattern(inner, variables, graph_name)?;
+                let key_variables = by
                     .iter()
                     .map(|k| build_plan_variable(variables, k))
-                    .collect(),
-                aggregates: aggregates
+                    .collect::<Rc<[_]>>();
+                let aggregates = aggregates
                     .iter()
                     .map(|(v, a)| {
                         Ok((
@@ -215,8 +222,17 @@ impl<'a> PlanBuilder<'a> {
                             build_plan_variable(variables, v),
                         ))
                     })
-                    .collect::<Result<_, EvaluationError>>()?,
-            },
+                    .collect::<Result<Rc<[_]>, EvaluationError>>()?;
+                if let Some(count) = self.build_count_quads(&child, &key_variables, &aggregates) {
+                    count
+                } else {
+                    PlanNode::Aggregate {
+                        child: Rc::new(child),
+                        key_variables,
+                        aggregates,
+                    }
+                }
+            }
             GraphPattern::Values {
                 variables: table_variables,
                 bindings,
@@ -295,9 +311,13 @@ impl<'a> PlanBuilder<'a> {
                         .collect(),
                 }
             }
-            GraphPattern::Distinct { inner } => PlanNode::HashDeduplicate {
-                child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
-            },
+            GraphPattern::Distinct { inner } => {
+                let child = self.build_for_graph_pattern(inner, variables, graph_name)?;
+                self.build_distinct_terms(&child)
+                    .unwrap_or_else(|| PlanNode::HashDeduplicate {
+                        child: Rc::new(child),
+                    })
+            }
             GraphPattern::Reduced { inner } => PlanNode::Reduced {
                 child: Rc::new(self.build_for_graph_pattern(inner, variables, graph_name)?),
             },
@@ -347,6 +367,111 @@ impl<'a> PlanBuilder<'a> {
             })
     }
 
+    /// Answers `COUNT(*)` and `COUNT(DISTINCT ?g)` without `GROUP BY` on a single quad pattern,
+    /// `?g` being the graph name, by counting in the storage instead of materializing the solutions
+    fn build_count_quads(
+        &self,
+        child: &PlanNode,
+        key_variables: &[PlanVariable],
+        aggregates: &[(PlanAggregation, PlanVariable)],
+    ) -> Option<PlanNode> {
+        if !self.with_optimizations || !key_variables.is_empty() {
+            return None;
+        }
+        if let (
+            PlanNode::QuadPattern {
+                subject,
+                predicate,
+                object,
+                graph_name,
+            },
+            [(aggregate, variable)],
+        ) = (child, aggregates)
+        {
+            if !matches!(aggregate.function, PlanAggregationFunction::Count) {
+                return None;
+            }
+            let distinct_graphs = match (&aggregate.parameter, aggregate.distinct) {
+                (None, false) => false,
+                (Some(PlanExpression::Variable(parameter)), true) => match graph_name {
+                    PatternValue::Variable(g) if g.encoded == parameter.encoded => true,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            // The quads must be counted as they are: no repeated variable to check or quoted triple to match
+            let mut pattern_variables = HashSet::new();
+            for value in [subject, predicate, object, graph_name] {
+                match value {
+                    PatternValue::Constant(_) => (),
+                    PatternValue::Variable(v) => {
+                        if !pattern_variables.insert(v.encoded) {
+                            return None;
+                        }
+                    }
+                    PatternValue::TriplePattern(_) => return None,
+                }
+            }
+            Some(PlanNode::CountQuads {
+                subject: subject.clone(),
+                predicate: predicate.clone(),
+                object: object.clone(),
+                graph_name: graph_name.clone(),
+                distinct_graphs,
+                variable: variable.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Plans the distinct values of a variable of a single quad pattern to be read by skipping over the index entries sharing them
+    fn build_distinct_terms(&self, child: &PlanNode) -> Option<PlanNode> {
+        if !self.with_optimizations {
+            return None;
+        }
+        if let PlanNode::Project { child, mapping } = child {
+            if let (
+                PlanNode::QuadPattern {
+                    subject,
+                    predicate,
+                    object,
+                    graph_name,
+                },
+                [(variable, _)],
+            ) = (child.as_ref(), mapping.as_ref())
+            {
+                // The values must be the ones of the quads: no repeated variable to check or quoted triple to match
+                let mut pattern_variables = HashSet::new();
+                for value in [subject, predicate, object, graph_name] {
+                    match value {
+                        PatternValue::Constant(_) => (),
+                        PatternValue::Variable(v) => {
+                            if !pattern_variables.insert(v.encoded) {
+                                return None;
+                            }
+                        }
+                        PatternValue::TriplePattern(_) => return None,
+                    }
+                }
+                if !pattern_variables.contains(&variable.encoded) {
+                    return None;
+                }
+                return Some(PlanNode::Project {
+                    child: Rc::new(PlanNode::DistinctTerms {
+                        subject: subject.clone(),
+                        predicate: predicate.clone(),
+                        object: object.clone(),
+                        graph_name: graph_name.clone(),
+                        variable: variable.clone(),
+                    }),
+                    mapping: Rc::clone(mapping),
+                });
+            }
+        }
+        None
+    }
+
     fn build_for_path(&self, path: &PropertyPathExpression) -> PlanPropertyPath {
         match path {
             PropertyPathExpression::NamedNode(p) => PlanPropertyPath::Path(PlanTerm {
@@ -873,6 +998,10 @@ impl<'a> PlanBuilder<'a> {
                             graph_name,
                             "string",
                         )?
+                    } else if let Some(function) =
+                        name.as_str().strip_prefix(XPATH_FUNCTIONS_NAMESPACE)
+                    {
+                        self.build_xpath_function(function, parameters, variables, graph_name)?
                     } else {
                         return Err(EvaluationError::msg(format!(
                             "Not supported custom function {expression}"
@@ -921,6 +1050,50 @@ impl<'a> PlanBuilder<'a> {
         }
     }
 
+    /// Builds the [XPath functions](https://www.w3.org/TR/xpath-functions-31/) without a SPARQL builtin equivalent
+    fn build_xpath_function(
+        &self,
+        function: &str,
+        parameters: &[Expression],
+        variables: &mut Vec<Variable>,
+        graph_name: &PatternValue,
+    ) -> Result<PlanExpression, EvaluationError> {
+        let parameters = self.expression_list(parameters, variables, graph_name)?;
+        Ok(match (function, parameters.as_slice()) {
+            (
+                "adjust-dateTime-to-timezone"
+                | "adjust-date-to-timezone"
+                | "adjust-time-to-timezone",
+                [value],
+            ) => {
+                // The implicit timezone of a canister is UTC
+                let utc = Literal::new_typed_literal("PT0S", xsd::DAY_TIME_DURATION);
+                PlanExpression::Adjust(
+                    Box::new(value.clone()),
+                    Box::new(PlanExpression::Literal(PlanTerm {
+                        encoded: self.build_term(&utc),
+                        plain: utc,
+                    })),
+                )
+            }
+            (
+                "adjust-dateTime-to-timezone"
+                | "adjust-date-to-timezone"
+                | "adjust-time-to-timezone",
+                [value, timezone],
+            ) => PlanExpression::Adjust(Box::new(value.clone()), Box::new(timezone.clone())),
+            ("format-number", [value, picture]) => {
+                PlanExpression::FormatNumber(Box::new(value.clone()), Box::new(picture.clone()))
+            }
+            _ => {
+                return Err(EvaluationError::msg(format!(
+                    "Not supported XPath function fn:{function} with {} parameters",
+                    parameters.len()
+                )))
+            }
+        })
+    }
+
     fn expression_list(
         &self,
         l: &[Expression],
@@ -1232,6 +1405,8 @@ impl<'a> PlanBuilder<'a> {
         match node {
             PlanNode::StaticBindings { .. }
             | PlanNode::QuadPattern { .. }
+            | PlanNode::CountQuads { .. }
+            | PlanNode::DistinctTerms { .. }
             | PlanNode::PathPattern { .. } => (),
             PlanNode::Filter { child, expression } => {
                 let always_already_bound = child.always_bound_variables();
@@ -1391,7 +1566,9 @@ impl<'a> PlanBuilder<'a> {
             | PlanNode::Skip { .. }
             | PlanNode::Limit { .. }
             | PlanNode::Project { .. }
-            | PlanNode::Aggregate { .. } => false,
+            | PlanNode::Aggregate { .. }
+            | PlanNode::CountQuads { .. }
+            | PlanNode::DistinctTerms { .. } => false,
         }
     }
 
@@ -1409,6 +1586,15 @@ impl<'a> PlanBuilder<'a> {
                     self.push_filter(Rc::new(acc), Box::new(f))
                 });
         }
+        if let Some(bindings) = self
+            .literal_index_bindings(&node, &filter)
+            .or_else(|| self.range_index_bindings(&node, &filter))
+        {
+            return PlanNode::Filter {
+                child: Rc::new(self.new_join(bindings, (*node).clone())),
+                expression: filter,
+            };
+        }
         let mut filter_variables = BTreeSet::new();
         filter.lookup_used_variables(&mut |v| {
             filter_variables.insert(v);
@@ -1517,6 +1703,125 @@ impl<'a> PlanBuilder<'a> {
         }
     }
 
+    /// Binds the variable of a `DATATYPE(?v) = iri` or `LANG(?v) = "tag"` filter to the matching literals of the literal index
+    ///
+    /// Returns `None` if the filter has not this shape, if the variable is not bound by `node`
+    /// or if the literal index is disabled or returns too many literals.
+    fn literal_index_bindings(&self, node: &PlanNode, filter: &PlanExpression) -> Option<PlanNode> {
+        let (a, b) = if let PlanExpression::Equal(a, b) = filter {
+            (a.as_ref(), b.as_ref())
+        } else {
+            return None;
+        };
+        let (variable, literals) = match (a, b) {
+            (PlanExpression::Datatype(v), PlanExpression::NamedNode(datatype))
+            | (PlanExpression::NamedNode(datatype), PlanExpression::Datatype(v)) => {
+                let variable = if let PlanExpression::Variable(variable) = v.as_ref() {
+                    variable
+                } else {
+                    return None;
+                };
+                (
+                    variable,
+                    self.dataset.literals_with_datatype(&datatype.encoded)?,
+                )
+            }
+            (PlanExpression::Lang(v), PlanExpression::Literal(language))
+            | (PlanExpression::Literal(language), PlanExpression::Lang(v)) => {
+                let variable = if let PlanExpression::Variable(variable) = v.as_ref() {
+                    variable
+                } else {
+                    return None;
+                };
+                if language.plain.datatype() != xsd::STRING || language.plain.value().is_empty() {
+                    return None;
+                }
+                (
+                    variable,
+                    self.dataset
+                        .literals_with_language(language.plain.value())?,
+                )
+            }
+            _ => return None,
+        };
+        if literals.len() > MAX_INDEX_BINDINGS || !node.is_variable_bound(variable.encoded) {
+            return None;
+        }
+        Some(Self::variable_bindings(variable, literals))
+    }
+
+    /// Binds the variable of a `?v < value` like filter to the matching objects of the range index
+    ///
+    /// Returns `None` if the filter has not this shape, if the variable is not the object of a triple pattern
+    /// of `node` with a constant predicate or if the range index is disabled or returns too many objects.
+    fn range_index_bindings(&self, node: &PlanNode, filter: &PlanExpression) -> Option<PlanNode> {
+        let (a, b, is_less, is_inclusive) = match filter {
+            PlanExpression::Less(a, b) => (a, b, true, false),
+            PlanExpression::LessOrEqual(a, b) => (a, b, true, true),
+            PlanExpression::Greater(a, b) => (a, b, false, false),
+            PlanExpression::GreaterOrEqual(a, b) => (a, b, false, true),
+            _ => return None,
+        };
+        let (variable, value, is_less) = match (a.as_ref(), b.as_ref()) {
+            (PlanExpression::Variable(variable), PlanExpression::Literal(value)) => {
+                (variable, value, is_less)
+            }
+            (PlanExpression::Literal(value), PlanExpression::Variable(variable)) => {
+                (variable, value, !is_less)
+            }
+            _ => return None,
+        };
+        let bound = if is_inclusive {
+            Bound::Included(&value.encoded)
+        } else {
+            Bound::Excluded(&value.encoded)
+        };
+        let (start, end) = if is_less {
+            (Bound::Unbounded, bound)
+        } else {
+            (bound, Bound::Unbounded)
+        };
+        let predicate = Self::object_predicate(node, variable.encoded)?;
+        let objects = self
+            .dataset
+            .objects_in_range(predicate, start, end, MAX_INDEX_BINDINGS)?;
+        Some(Self::variable_bindings(variable, objects))
+    }
+
+    /// Returns the constant predicate of a triple pattern of `node` with `variable` as object
+    fn object_predicate(node: &PlanNode, variable: usize) -> Option<&EncodedTerm> {
+        match node {
+            PlanNode::QuadPattern {
+                predicate: PatternValue::Constant(predicate),
+                object: PatternValue::Variable(object),
+                ..
+            } if object.encoded == variable => Some(&predicate.encoded),
+            PlanNode::HashJoin { left, right } | PlanNode::ForLoopJoin { left, right } => {
+                Self::object_predicate(left, variable)
+                    .or_else(|| Self::object_predicate(right, variable))
+            }
+            PlanNode::Filter { child, .. } | PlanNode::Extend { child, .. } => {
+                Self::object_predicate(child, variable)
+            }
+            _ => None,
+        }
+    }
+
+    fn variable_bindings(variable: &PlanVariable, values: Vec<EncodedTerm>) -> PlanNode {
+        PlanNode::StaticBindings {
+            plain_bindings: vec![vec![None]; values.len()],
+            encoded_tuples: values
+                .into_iter()
+                .map(|value| {
+                    let mut tuple = EncodedTuple::with_capacity(variable.encoded + 1);
+                    tuple.set(variable.encoded, value);
+                    tuple
+                })
+                .collect(),
+            variables: vec![variable.clone()],
+        }
+    }
+
     fn build_term<'b>(&self, term: impl Into<TermRef<'b>>) -> EncodedTerm {
         self.dataset.encode_term(term)
     }
@@ -1569,21 +1874,50 @@ fn slice_key<T: Eq>(slice: &[T], element: &T) -> Option<usize> {
     None
 }
 
-fn sort_bgp(p: &[TriplePattern]) -> Vec<&TriplePattern> {
+fn sort_bgp<'a>(p: &'a [TriplePattern], dataset: &DatasetView) -> Vec<&'a TriplePattern> {
     let mut assigned_variables = HashSet::default();
     let mut assigned_blank_nodes = HashSet::default();
-    let mut new_p: Vec<_> = p.iter().collect();
+    // The cardinalities break the ties between the patterns with the same cost
+    let mut new_p: Vec<_> = p
+        .iter()
+        .map(|p| (p, estimate_pattern_cardinality(p, dataset)))
+        .collect();
 
     for i in 0..new_p.len() {
-        new_p[i..].sort_by(|p1, p2| {
-            estimate_pattern_cost(p1, &assigned_variables, &assigned_blank_nodes).cmp(
-                &estimate_pattern_cost(p2, &assigned_variables, &assigned_blank_nodes),
-            )
+        new_p[i..].sort_by(|(p1, c1), (p2, c2)| {
+            estimate_pattern_cost(p1, &assigned_variables, &assigned_blank_nodes)
+                .cmp(&estimate_pattern_cost(
+                    p2,
+                    &assigned_variables,
+                    &assigned_blank_nodes,
+                ))
+                .then(c1.cmp(c2))
         });
-        add_pattern_variables(new_p[i], &mut assigned_variables, &mut assigned_blank_nodes);
+        add_pattern_variables(
+            new_p[i].0,
+            &mut assigned_variables,
+            &mut assigned_blank_nodes,
+        );
     }
 
-    new_p
+    new_p.into_iter().map(|(p, _)| p).collect()
+}
+
+/// The number of quads of the store with the pattern predicate and object class according to the statistics
+fn estimate_pattern_cardinality(pattern: &TriplePattern, dataset: &DatasetView) -> u64 {
+    if let NamedNodePattern::NamedNode(predicate) = &pattern.predicate {
+        let object_class = match &pattern.object {
+            TermPattern::NamedNode(_) => Some(ObjectClass::NamedNode),
+            TermPattern::Literal(_) => Some(ObjectClass::Literal),
+            TermPattern::Triple(_) => Some(ObjectClass::Triple),
+            TermPattern::BlankNode(_) | TermPattern::Variable(_) => None,
+        };
+        dataset
+            .predicate_cardinality(&predicate.as_ref().into(), object_class)
+            .unwrap_or(u64::MAX)
+    } else {
+        u64::MAX
+    }
 }
 
 fn estimate_pattern_cost(
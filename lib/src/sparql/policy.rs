@@ -0,0 +1,257 @@
+use crate::sparql::algebra::Query;
+use crate::sparql::error::EvaluationError;
+use spargebra::algebra::{
+    AggregateExpression, Expression, Function, GraphPattern, OrderExpression,
+    PropertyPathExpression,
+};
+
+/// Restrictions on the SPARQL features a query is allowed to use.
+///
+/// The policy is checked just after parsing, before any evaluation,
+/// so that public endpoints can limit the cost of the queries sent by untrusted callers.
+///
+/// Should be given to [`QueryOptions`](super::QueryOptions::with_policy()).
+///
+/// ```
+/// use oxigraph::store::Store;
+/// use oxigraph::sparql::{QueryOptions, QueryPolicy};
+///
+/// let store = Store::new()?;
+/// let options = QueryOptions::default().with_policy(
+///     QueryPolicy::default().without_service().without_regex().with_required_limit(100)
+/// );
+/// assert!(store.query_opt("SELECT * WHERE { ?s ?p ?o }", options.clone()).is_err());
+/// assert!(store.query_opt("SELECT * WHERE { ?s ?p ?o } LIMIT 10", options).is_ok());
+/// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct QueryPolicy {
+    deny_service: bool,
+    deny_regex: bool,
+    deny_unbounded_property_paths: bool,
+    max_limit: Option<usize>,
+}
+
+impl QueryPolicy {
+    /// Forbids [SPARQL 1.1 Federated Query](https://www.w3.org/TR/sparql11-federated-query/) `SERVICE` calls.
+    #[inline]
+    #[must_use]
+    pub fn without_service(mut self) -> Self {
+        self.deny_service = true;
+        self
+    }
+
+    /// Forbids the regular expression based functions `REGEX` and `REPLACE`.
+    #[inline]
+    #[must_use]
+    pub fn without_regex(mut self) -> Self {
+        self.deny_regex = true;
+        self
+    }
+
+    /// Forbids the unbounded property paths operators `*` and `+`.
+    #[inline]
+    #[must_use]
+    pub fn without_unbounded_property_paths(mut self) -> Self {
+        self.deny_unbounded_property_paths = true;
+        self
+    }
+
+    /// Requires `SELECT`, `CONSTRUCT` and `DESCRIBE` queries to have a `LIMIT` clause not greater than `max_limit`.
+    #[inline]
+    #[must_use]
+    pub fn with_required_limit(mut self, max_limit: usize) -> Self {
+        self.max_limit = Some(max_limit);
+        self
+    }
+
+    /// Checks that a query complies with the policy.
+    pub fn check(&self, query: &Query) -> Result<(), EvaluationError> {
+        match &query.inner {
+            spargebra::Query::Select { pattern, .. }
+            | spargebra::Query::Construct { pattern, .. }
+            | spargebra::Query::Describe { pattern, .. } => {
+                if let Some(max_limit) = self.max_limit {
+                    match top_level_limit(pattern) {
+                        Some(limit) if limit <= max_limit => (),
+                        Some(limit) => {
+                            return Err(EvaluationError::msg(format!(
+                                "The query LIMIT {limit} is greater than the allowed maximum of {max_limit}"
+                            )))
+                        }
+                        None => {
+                            return Err(EvaluationError::msg(format!(
+                                "The query must have a LIMIT clause not greater than {max_limit}"
+                            )))
+                        }
+                    }
+                }
+                self.check_pattern(pattern)
+            }
+            spargebra::Query::Ask { pattern, .. } => self.check_pattern(pattern),
+        }
+    }
+
+    /// Checks that a graph pattern only uses allowed features.
+    pub(crate) fn check_pattern(&self, pattern: &GraphPattern) -> Result<(), EvaluationError> {
+        match pattern {
+            GraphPattern::Bgp { .. } | GraphPattern::Values { .. } => Ok(()),
+            GraphPattern::Path { path, .. } => self.check_path(path),
+            GraphPattern::Join { left, right }
+            | GraphPattern::Lateral { left, right }
+            | GraphPattern::Union { left, right }
+            | GraphPattern::Minus { left, right } => {
+                self.check_pattern(left)?;
+                self.check_pattern(right)
+            }
+            GraphPattern::LeftJoin {
+                left,
+                right,
+                expression,
+            } => {
+                self.check_pattern(left)?;
+                self.check_pattern(right)?;
+                if let Some(expression) = expression {
+                    self.check_expression(expression)?;
+                }
+                Ok(())
+            }
+            GraphPattern::Filter { expr, inner } => {
+                self.check_expression(expr)?;
+                self.check_pattern(inner)
+            }
+            GraphPattern::Extend {
+                inner, expression, ..
+            } => {
+                self.check_expression(expression)?;
+                self.check_pattern(inner)
+            }
+            GraphPattern::OrderBy { inner, expression } => {
+                for expression in expression {
+                    match expression {
+                        OrderExpression::Asc(e) | OrderExpression::Desc(e) => {
+                            self.check_expression(e)?
+                        }
+                    }
+                }
+                self.check_pattern(inner)
+            }
+            GraphPattern::Graph { inner, .. }
+            | GraphPattern::Project { inner, .. }
+            | GraphPattern::Distinct { inner }
+            | GraphPattern::Reduced { inner }
+            | GraphPattern::Slice { inner, .. } => self.check_pattern(inner),
+            GraphPattern::Group {
+                inner, aggregates, ..
+            } => {
+                for (_, aggregate) in aggregates {
+                    match aggregate {
+                        AggregateExpression::Count { expr, .. } => {
+                            if let Some(expr) = expr {
+                                self.check_expression(expr)?;
+                            }
+                        }
+                        AggregateExpression::Sum { expr, .. }
+                        | AggregateExpression::Avg { expr, .. }
+                        | AggregateExpression::Min { expr, .. }
+                        | AggregateExpression::Max { expr, .. }
+                        | AggregateExpression::GroupConcat { expr, .. }
+                        | AggregateExpression::Sample { expr, .. }
+                        | AggregateExpression::Custom { expr, .. } => {
+                            self.check_expression(expr)?
+                        }
+                    }
+                }
+                self.check_pattern(inner)
+            }
+            GraphPattern::Service { name, inner, .. } => {
+                if self.deny_service {
+                    return Err(EvaluationError::msg(format!(
+                        "SERVICE calls are not allowed (service {name})"
+                    )));
+                }
+                self.check_pattern(inner)
+            }
+        }
+    }
+
+    fn check_path(&self, path: &PropertyPathExpression) -> Result<(), EvaluationError> {
+        match path {
+            PropertyPathExpression::NamedNode(_)
+            | PropertyPathExpression::NegatedPropertySet(_) => Ok(()),
+            PropertyPathExpression::Reverse(p) | PropertyPathExpression::ZeroOrOne(p) => {
+                self.check_path(p)
+            }
+            PropertyPathExpression::Sequence(a, b) | PropertyPathExpression::Alternative(a, b) => {
+                self.check_path(a)?;
+                self.check_path(b)
+            }
+            PropertyPathExpression::ZeroOrMore(p) | PropertyPathExpression::OneOrMore(p) => {
+                if self.deny_unbounded_property_paths {
+                    return Err(EvaluationError::msg(format!(
+                        "Unbounded property paths are not allowed (path {path})"
+                    )));
+                }
+                self.check_path(p)
+            }
+        }
+    }
+
+    fn check_expression(&self, expression: &Expression) -> Result<(), EvaluationError> {
+        match expression {
+            Expression::NamedNode(_)
+            | Expression::Literal(_)
+            | Expression::Variable(_)
+            | Expression::Bound(_) => Ok(()),
+            Expression::Or(a, b)
+            | Expression::And(a, b)
+            | Expression::Equal(a, b)
+            | Expression::SameTerm(a, b)
+            | Expression::Greater(a, b)
+            | Expression::GreaterOrEqual(a, b)
+            | Expression::Less(a, b)
+            | Expression::LessOrEqual(a, b)
+            | Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b) => {
+                self.check_expression(a)?;
+                self.check_expression(b)
+            }
+            Expression::UnaryPlus(e) | Expression::UnaryMinus(e) | Expression::Not(e) => {
+                self.check_expression(e)
+            }
+            Expression::In(a, b) => {
+                self.check_expression(a)?;
+                b.iter().try_for_each(|e| self.check_expression(e))
+            }
+            Expression::Exists(p) => self.check_pattern(p),
+            Expression::If(a, b, c) => {
+                self.check_expression(a)?;
+                self.check_expression(b)?;
+                self.check_expression(c)
+            }
+            Expression::Coalesce(l) => l.iter().try_for_each(|e| self.check_expression(e)),
+            Expression::FunctionCall(function, args) => {
+                if self.deny_regex && matches!(function, Function::Regex | Function::Replace) {
+                    return Err(EvaluationError::msg(format!(
+                        "The {function} function is not allowed"
+                    )));
+                }
+                args.iter().try_for_each(|e| self.check_expression(e))
+            }
+        }
+    }
+}
+
+/// Returns the `LIMIT` of the query if it applies to the whole result set.
+pub(crate) fn top_level_limit(pattern: &GraphPattern) -> Option<usize> {
+    match pattern {
+        GraphPattern::Slice { length, .. } => *length,
+        GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::OrderBy { inner, .. } => top_level_limit(inner),
+        _ => None,
+    }
+}
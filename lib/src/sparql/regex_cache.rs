@@ -0,0 +1,116 @@
+use crate::sparql::eval::compile_pattern;
+use regex::Regex;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Default maximal number of regular expressions kept by a cache
+const DEFAULT_CAPACITY: usize = 256;
+
+/// A cache of the regular expressions compiled by the `REGEX` and `REPLACE` functions, keyed by pattern and flags.
+///
+/// The patterns that are constant in the query are compiled once when the query is planned.
+/// The other ones, for example the patterns bound to a variable, are compiled on evaluation
+/// and kept in a cache, so a pattern is only compiled once even if it is used with many solutions.
+///
+/// Each query evaluation uses its own cache by default.
+/// A cache can also be shared by the evaluations of several queries, for example one per store,
+/// using [`QueryOptions::with_regex_cache`](super::QueryOptions::with_regex_cache).
+/// The cache is emptied when it is full.
+///
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::sparql::{QueryOptions, QueryResults, RegexCache};
+/// use oxigraph::store::Store;
+///
+/// let store = Store::new()?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// store.insert(QuadRef::new(ex, ex, LiteralRef::new_simple_literal("^ex"), GraphNameRef::DefaultGraph))?;
+///
+/// let cache = RegexCache::default();
+/// let (results, explanation) = store.explain_query_opt(
+///     "SELECT ?s WHERE { ?s ?p ?pattern FILTER(REGEX('example', ?pattern)) }",
+///     QueryOptions::default().with_regex_cache(cache.clone()),
+///     false,
+/// )?;
+/// if let QueryResults::Solutions(solutions) = results? {
+///     assert_eq!(solutions.count(), 1);
+/// }
+/// assert_eq!(explanation.regex_cache_misses(), 1);
+/// assert_eq!(cache.len(), 1);
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct RegexCache {
+    inner: Rc<RegexCacheInner>,
+}
+
+struct RegexCacheInner {
+    capacity: usize,
+    /// The invalid patterns are kept too, with `None`
+    regexes: RefCell<HashMap<(String, Option<String>), Option<Regex>>>,
+}
+
+impl RegexCache {
+    /// Builds an empty cache keeping at most `capacity` regular expressions.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Rc::new(RegexCacheInner {
+                capacity,
+                regexes: RefCell::default(),
+            }),
+        }
+    }
+
+    /// Returns the number of regular expressions in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.regexes.borrow().len()
+    }
+
+    /// Returns if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.regexes.borrow().is_empty()
+    }
+
+    /// Removes all the regular expressions from the cache.
+    pub fn clear(&self) {
+        self.inner.regexes.borrow_mut().clear();
+    }
+
+    pub(crate) fn get(
+        &self,
+        pattern: &str,
+        flags: Option<&str>,
+        stats: &RegexCacheStats,
+    ) -> Option<Regex> {
+        let key = (pattern.to_owned(), flags.map(ToOwned::to_owned));
+        if let Some(regex) = self.inner.regexes.borrow().get(&key) {
+            stats.hits.set(stats.hits.get() + 1);
+            return regex.clone();
+        }
+        stats.misses.set(stats.misses.get() + 1);
+        let regex = compile_pattern(pattern, flags);
+        let mut regexes = self.inner.regexes.borrow_mut();
+        if regexes.len() >= self.inner.capacity {
+            regexes.clear();
+        }
+        if self.inner.capacity > 0 {
+            regexes.insert(key, regex.clone());
+        }
+        regex
+    }
+}
+
+impl Default for RegexCache {
+    #[inline]
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// The lookups done in a [`RegexCache`] by an evaluation
+#[derive(Default)]
+pub(crate) struct RegexCacheStats {
+    pub hits: Cell<u64>,
+    pub misses: Cell<u64>,
+}
@@ -1,9 +1,10 @@
 use crate::model::NamedNode;
-use crate::sparql::algebra::Query;
+use crate::sparql::algebra::{Query, QueryDataset};
 use crate::sparql::error::EvaluationError;
 use crate::sparql::http::Client;
 use crate::sparql::model::QueryResults;
 use crate::sparql::QueryResultsFormat;
+use spargebra::algebra::{GraphPattern, NamedNodePattern};
 use std::error::Error;
 use std::io::BufReader;
 use std::time::Duration;
@@ -61,10 +62,11 @@ pub struct EmptyServiceHandler;
 impl ServiceHandler for EmptyServiceHandler {
     type Error = EvaluationError;
 
-    fn handle(&self, _: NamedNode, _: Query) -> Result<QueryResults, EvaluationError> {
-        Err(EvaluationError::msg(
-            "The SERVICE feature is not implemented",
-        ))
+    fn handle(&self, service_name: NamedNode, _: Query) -> Result<QueryResults, EvaluationError> {
+        Err(EvaluationError::msg(format!(
+            "No handler is registered for the SERVICE call to {service_name}, \
+             use QueryOptions::with_service_handler to provide one"
+        )))
     }
 }
 
@@ -126,3 +128,76 @@ impl ServiceHandler for SimpleServiceHandler {
         Ok(QueryResults::read(BufReader::new(body), format)?)
     }
 }
+
+/// Returns the SERVICE calls of a query that can be resolved before its evaluation,
+/// with the query the evaluator gives to [`ServiceHandler::handle`] for each of them and if they are `SILENT`.
+pub(crate) fn prefetchable_services(query: &Query) -> Vec<(NamedNode, Query, bool)> {
+    let (pattern, base_iri) = match &query.inner {
+        spargebra::Query::Select {
+            pattern, base_iri, ..
+        }
+        | spargebra::Query::Construct {
+            pattern, base_iri, ..
+        }
+        | spargebra::Query::Describe {
+            pattern, base_iri, ..
+        }
+        | spargebra::Query::Ask {
+            pattern, base_iri, ..
+        } => (pattern, base_iri),
+    };
+    let mut services = Vec::new();
+    collect_services(pattern, &mut services);
+    services
+        .into_iter()
+        .map(|(name, inner, silent)| {
+            // Must be kept in sync with the query built by the evaluator for the SERVICE calls
+            let query = Query {
+                inner: spargebra::Query::Select {
+                    dataset: None,
+                    pattern: inner.clone(),
+                    base_iri: base_iri.clone(),
+                },
+                dataset: QueryDataset::new(),
+                parsing_duration: None,
+            };
+            (name, query, silent)
+        })
+        .collect()
+}
+
+/// Collects the SERVICE calls with a constant name that are not nested in other SERVICE calls
+fn collect_services<'a>(
+    pattern: &'a GraphPattern,
+    services: &mut Vec<(NamedNode, &'a GraphPattern, bool)>,
+) {
+    match pattern {
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } | GraphPattern::Values { .. } => (),
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Lateral { left, right }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            collect_services(left, services);
+            collect_services(right, services);
+        }
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::Graph { inner, .. }
+        | GraphPattern::Extend { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. }
+        | GraphPattern::Group { inner, .. } => collect_services(inner, services),
+        GraphPattern::Service {
+            name,
+            inner,
+            silent,
+        } => {
+            if let NamedNodePattern::NamedNode(name) = name {
+                services.push((name.clone(), inner, *silent));
+            }
+        }
+    }
+}
@@ -3,13 +3,13 @@ use crate::io::{GraphFormat, GraphParser};
 use crate::model::{GraphName as OxGraphName, GraphNameRef, Quad as OxQuad};
 use crate::sparql::algebra::QueryDataset;
 use crate::sparql::dataset::DatasetView;
-use crate::sparql::eval::SimpleEvaluator;
+use crate::sparql::eval::{BlankNodeGenerator, SimpleEvaluator};
 use crate::sparql::http::Client;
 use crate::sparql::plan::EncodedTuple;
 use crate::sparql::plan_builder::PlanBuilder;
 use crate::sparql::{EvaluationError, Update, UpdateOptions};
 use crate::storage::numeric_encoder::{Decoder, EncodedTerm};
-use crate::storage::StorageWriter;
+use crate::storage::{LockedGraphs, Storage, StorageError, StorageWriter};
 use oxiri::Iri;
 use spargebra::algebra::{GraphPattern, GraphTarget};
 use spargebra::term::{
@@ -18,8 +18,9 @@ use spargebra::term::{
     Quad, QuadPattern, Subject, Term, TermPattern, Triple, TriplePattern, Variable,
 };
 use spargebra::GraphUpdateOperation;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::BufReader;
+use std::mem::replace;
 use std::rc::Rc;
 
 pub fn evaluate_update<'a, 'b: 'a>(
@@ -35,15 +36,354 @@ pub fn evaluate_update<'a, 'b: 'a>(
             options.query_options.http_timeout,
             options.query_options.http_redirection_limit,
         ),
+        blank_nodes: options.blank_node_generator(),
     }
     .eval_all(&update.inner.operations, &update.using_datasets)
 }
 
+/// A SPARQL update evaluated in bounded steps, so that a huge update can be spread over several calls.
+///
+/// The `WHERE` clause of a `DELETE`/`INSERT` operation is fully evaluated before its changes are applied,
+/// the deletions before the insertions.
+/// The graph management operations (`LOAD`, `CLEAR`, `CREATE`, `DROP`) are evaluated in a single step.
+pub(crate) struct BatchedUpdate {
+    operations: VecDeque<(GraphUpdateOperation, Option<QueryDataset>)>,
+    operation_count: usize,
+    base_iri: Option<Rc<Iri<String>>>,
+    options: UpdateOptions,
+    blank_nodes: Rc<BlankNodeGenerator>,
+    state: BatchState,
+    solutions_evaluated: u64,
+    quads_written: u64,
+}
+
+enum BatchState {
+    /// Waiting for the next operation
+    Idle,
+    Evaluating {
+        solutions: Box<dyn Iterator<Item = Result<EncodedTuple, EvaluationError>>>,
+        variables: Vec<Variable>,
+        dataset: Rc<DatasetView>,
+        delete: Vec<GroundQuadPattern>,
+        insert: Vec<QuadPattern>,
+        deletions: Vec<OxQuad>,
+        insertions: Vec<OxQuad>,
+    },
+    Applying {
+        deletions: Vec<OxQuad>,
+        insertions: Vec<OxQuad>,
+    },
+}
+
+impl BatchedUpdate {
+    pub fn new(update: &Update, options: UpdateOptions) -> Self {
+        let operations = update
+            .inner
+            .operations
+            .iter()
+            .cloned()
+            .zip(update.using_datasets.iter().cloned())
+            .collect::<VecDeque<_>>();
+        Self {
+            operation_count: operations.len(),
+            operations,
+            base_iri: update.inner.base_iri.clone().map(Rc::new),
+            blank_nodes: options.blank_node_generator(),
+            options,
+            state: BatchState::Idle,
+            solutions_evaluated: 0,
+            quads_written: 0,
+        }
+    }
+
+    /// The graphs the update may write into
+    pub fn written_graphs(&self) -> LockedGraphs {
+        let mut graphs = Vec::new();
+        for (operation, _) in &self.operations {
+            match operation {
+                GraphUpdateOperation::InsertData { data } => {
+                    graphs.extend(data.iter().map(|quad| convert_graph_name(&quad.graph_name)));
+                }
+                GraphUpdateOperation::DeleteData { data } => {
+                    graphs.extend(data.iter().map(|quad| convert_graph_name(&quad.graph_name)));
+                }
+                GraphUpdateOperation::DeleteInsert { delete, insert, .. } => {
+                    for graph_name in delete
+                        .iter()
+                        .map(|quad| &quad.graph_name)
+                        .chain(insert.iter().map(|quad| &quad.graph_name))
+                    {
+                        graphs.push(match graph_name {
+                            GraphNamePattern::NamedNode(graph_name) => graph_name.as_ref().into(),
+                            GraphNamePattern::DefaultGraph => EncodedTerm::DefaultGraph,
+                            GraphNamePattern::Variable(_) => return LockedGraphs::All,
+                        });
+                    }
+                }
+                GraphUpdateOperation::Load { destination, .. } => {
+                    graphs.push(convert_graph_name(destination));
+                }
+                GraphUpdateOperation::Clear { graph, .. }
+                | GraphUpdateOperation::Drop { graph, .. } => graphs.push(match graph {
+                    GraphTarget::NamedNode(graph_name) => graph_name.as_ref().into(),
+                    GraphTarget::DefaultGraph => EncodedTerm::DefaultGraph,
+                    GraphTarget::NamedGraphs | GraphTarget::AllGraphs => return LockedGraphs::All,
+                }),
+                GraphUpdateOperation::Create { graph, .. } => graphs.push(graph.as_ref().into()),
+            }
+        }
+        LockedGraphs::Graphs(graphs)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, BatchState::Idle) && self.operations.is_empty()
+    }
+
+    pub fn operation_count(&self) -> usize {
+        self.operation_count
+    }
+
+    pub fn operations_done(&self) -> usize {
+        let running = usize::from(!matches!(self.state, BatchState::Idle));
+        self.operation_count - self.operations.len() - running
+    }
+
+    pub fn solutions_evaluated(&self) -> u64 {
+        self.solutions_evaluated
+    }
+
+    pub fn quads_written(&self) -> u64 {
+        self.quads_written
+    }
+
+    /// Evaluates at most `batch_size` solutions or writes at most `batch_size` quads.
+    ///
+    /// The writes are done in transactions owning the `lock_owner` graph locks.
+    pub fn step(
+        &mut self,
+        storage: &Storage,
+        lock_owner: u64,
+        batch_size: usize,
+    ) -> Result<(), EvaluationError> {
+        match replace(&mut self.state, BatchState::Idle) {
+            BatchState::Idle => {
+                if let Some((operation, using_dataset)) = self.operations.pop_front() {
+                    self.state =
+                        self.start_operation(storage, lock_owner, &operation, &using_dataset)?;
+                }
+            }
+            BatchState::Evaluating {
+                mut solutions,
+                variables,
+                dataset,
+                delete,
+                insert,
+                mut deletions,
+                mut insertions,
+            } => {
+                let mut bnodes = BlankNodeMapping::new(Rc::clone(&self.blank_nodes));
+                let mut exhausted = false;
+                for _ in 0..batch_size {
+                    let tuple = if let Some(tuple) = solutions.next() {
+                        tuple?
+                    } else {
+                        exhausted = true;
+                        break;
+                    };
+                    self.solutions_evaluated += 1;
+                    for quad in &delete {
+                        if let Some(quad) = SimpleUpdateEvaluator::convert_ground_quad_pattern(
+                            quad, &variables, &tuple, &dataset,
+                        )? {
+                            deletions.push(quad);
+                        }
+                    }
+                    for quad in &insert {
+                        if let Some(quad) = SimpleUpdateEvaluator::convert_quad_pattern(
+                            quad,
+                            &variables,
+                            &tuple,
+                            &dataset,
+                            &mut bnodes,
+                        )? {
+                            insertions.push(quad);
+                        }
+                    }
+                    bnodes.clear();
+                }
+                self.state = if exhausted {
+                    BatchState::Applying {
+                        deletions,
+                        insertions,
+                    }
+                } else {
+                    BatchState::Evaluating {
+                        solutions,
+                        variables,
+                        dataset,
+                        delete,
+                        insert,
+                        deletions,
+                        insertions,
+                    }
+                };
+            }
+            BatchState::Applying {
+                mut deletions,
+                mut insertions,
+            } => {
+                // The insertions only start once all the deletions are done
+                let deleted =
+                    deletions.split_off(deletions.len() - batch_size.min(deletions.len()));
+                let inserted = insertions.split_off(
+                    insertions.len() - (batch_size - deleted.len()).min(insertions.len()),
+                );
+                self.options.before_write(&deleted, &inserted)?;
+                storage.transaction_with_lock_owner(lock_owner, |mut writer| {
+                    for quad in &deleted {
+                        writer.remove(quad.as_ref())?;
+                    }
+                    for quad in &inserted {
+                        writer.insert(quad.as_ref())?;
+                    }
+                    Result::<_, StorageError>::Ok(())
+                })?;
+                self.options.after_write(&deleted, &inserted);
+                self.quads_written += (deleted.len() + inserted.len()) as u64;
+                if !deletions.is_empty() || !insertions.is_empty() {
+                    self.state = BatchState::Applying {
+                        deletions,
+                        insertions,
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn start_operation(
+        &self,
+        storage: &Storage,
+        lock_owner: u64,
+        operation: &GraphUpdateOperation,
+        using_dataset: &Option<QueryDataset>,
+    ) -> Result<BatchState, EvaluationError> {
+        Ok(match operation {
+            GraphUpdateOperation::InsertData { data } => {
+                let mut bnodes = BlankNodeMapping::new(Rc::clone(&self.blank_nodes));
+                BatchState::Applying {
+                    deletions: Vec::new(),
+                    insertions: data
+                        .iter()
+                        .map(|quad| SimpleUpdateEvaluator::convert_quad(quad, &mut bnodes))
+                        .collect(),
+                }
+            }
+            GraphUpdateOperation::DeleteData { data } => BatchState::Applying {
+                deletions: data
+                    .iter()
+                    .map(SimpleUpdateEvaluator::convert_ground_quad)
+                    .collect(),
+                insertions: Vec::new(),
+            },
+            GraphUpdateOperation::DeleteInsert {
+                delete,
+                insert,
+                pattern,
+                ..
+            } => {
+                self.options.query_options.policy.check_pattern(pattern)?;
+                let dataset = Rc::new(
+                    DatasetView::new(
+                        storage.snapshot(),
+                        using_dataset
+                            .as_ref()
+                            .ok_or_else(|| EvaluationError::msg("No dataset"))?,
+                    )
+                    .with_hidden_graphs(&self.options.query_options.hidden_graphs),
+                );
+                let (plan, variables) = PlanBuilder::build(
+                    &dataset,
+                    pattern,
+                    false,
+                    &self.options.query_options.custom_functions,
+                    !self.options.query_options.without_optimizations,
+                )?;
+                let evaluator = SimpleEvaluator::new(
+                    Rc::clone(&dataset),
+                    self.base_iri.clone(),
+                    self.options.query_options.service_handler(),
+                    Rc::new(self.options.query_options.custom_functions.clone()),
+                    false,
+                )
+                .with_blank_node_generator(Rc::clone(&self.blank_nodes));
+                let (eval, _) = evaluator.plan_evaluator(Rc::new(plan));
+                BatchState::Evaluating {
+                    solutions: eval(EncodedTuple::with_capacity(variables.len())),
+                    variables,
+                    dataset,
+                    delete: delete.clone(),
+                    insert: insert.clone(),
+                    deletions: Vec::new(),
+                    insertions: Vec::new(),
+                }
+            }
+            GraphUpdateOperation::Load { .. }
+            | GraphUpdateOperation::Clear { .. }
+            | GraphUpdateOperation::Create { .. }
+            | GraphUpdateOperation::Drop { .. } => {
+                storage.transaction_with_lock_owner(lock_owner, |mut writer| {
+                    SimpleUpdateEvaluator {
+                        transaction: &mut writer,
+                        base_iri: self.base_iri.clone(),
+                        options: self.options.clone(),
+                        client: Client::new(
+                            self.options.query_options.http_timeout,
+                            self.options.query_options.http_redirection_limit,
+                        ),
+                        blank_nodes: Rc::clone(&self.blank_nodes),
+                    }
+                    .eval(operation, using_dataset)
+                })?;
+                BatchState::Idle
+            }
+        })
+    }
+}
+
+/// The blank nodes created for the blank nodes of a template
+struct BlankNodeMapping {
+    generator: Rc<BlankNodeGenerator>,
+    nodes: HashMap<BlankNode, BlankNode>,
+}
+
+impl BlankNodeMapping {
+    fn new(generator: Rc<BlankNodeGenerator>) -> Self {
+        Self {
+            generator,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Forgets the mapping, so that the template creates new blank nodes for the next solution
+    fn clear(&mut self) {
+        self.nodes.clear();
+    }
+}
+
+fn convert_graph_name(graph_name: &GraphName) -> EncodedTerm {
+    match graph_name {
+        GraphName::NamedNode(graph_name) => graph_name.as_ref().into(),
+        GraphName::DefaultGraph => EncodedTerm::DefaultGraph,
+    }
+}
+
 struct SimpleUpdateEvaluator<'a, 'b> {
     transaction: &'a mut StorageWriter<'b>,
     base_iri: Option<Rc<Iri<String>>>,
     options: UpdateOptions,
     client: Client,
+    blank_nodes: Rc<BlankNodeGenerator>,
 }
 
 impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
@@ -52,10 +392,19 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         updates: &[GraphUpdateOperation],
         using_datasets: &[Option<QueryDataset>],
     ) -> Result<(), EvaluationError> {
-        for (update, using_dataset) in updates.iter().zip(using_datasets) {
-            self.eval(update, using_dataset)?;
+        // The operations already done are undone if a later one fails, for example if a hook vetoes it,
+        // even when the update is evaluated inside a transaction that goes on after the error
+        let savepoint = self.transaction.savepoint()?;
+        let result = updates
+            .iter()
+            .zip(using_datasets)
+            .try_for_each(|(update, using_dataset)| self.eval(update, using_dataset));
+        if result.is_err() {
+            self.transaction.rollback_to_savepoint(&savepoint)?;
+        } else {
+            self.transaction.release_savepoint(&savepoint);
         }
-        Ok(())
+        result
     }
 
     fn eval(
@@ -94,27 +443,48 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
                     Ok(())
                 }
             }
-            GraphUpdateOperation::Clear { graph, silent } => self.eval_clear(graph, *silent),
+            GraphUpdateOperation::Clear { graph, silent } => {
+                self.eval_graph_removal(graph, |e| e.eval_clear(graph, *silent))
+            }
             GraphUpdateOperation::Create { graph, silent } => self.eval_create(graph, *silent),
-            GraphUpdateOperation::Drop { graph, silent } => self.eval_drop(graph, *silent),
+            GraphUpdateOperation::Drop { graph, silent } => {
+                self.eval_graph_removal(graph, |e| e.eval_drop(graph, *silent))
+            }
         }
     }
 
-    fn eval_insert_data(&mut self, data: &[Quad]) -> Result<(), EvaluationError> {
-        let mut bnodes = HashMap::new();
-        for quad in data {
-            let quad = Self::convert_quad(quad, &mut bnodes);
+    /// Removes then inserts quads, notifying the update hooks
+    fn write(
+        &mut self,
+        deletions: &[OxQuad],
+        insertions: &[OxQuad],
+    ) -> Result<(), EvaluationError> {
+        self.options.before_write(deletions, insertions)?;
+        for quad in deletions {
+            self.transaction.remove(quad.as_ref())?;
+        }
+        for quad in insertions {
             self.transaction.insert(quad.as_ref())?;
         }
+        self.options.after_write(deletions, insertions);
         Ok(())
     }
 
+    fn eval_insert_data(&mut self, data: &[Quad]) -> Result<(), EvaluationError> {
+        let mut bnodes = BlankNodeMapping::new(Rc::clone(&self.blank_nodes));
+        let insertions = data
+            .iter()
+            .map(|quad| Self::convert_quad(quad, &mut bnodes))
+            .collect::<Vec<_>>();
+        self.write(&[], &insertions)
+    }
+
     fn eval_delete_data(&mut self, data: &[GroundQuad]) -> Result<(), EvaluationError> {
-        for quad in data {
-            let quad = Self::convert_ground_quad(quad);
-            self.transaction.remove(quad.as_ref())?;
-        }
-        Ok(())
+        let deletions = data
+            .iter()
+            .map(Self::convert_ground_quad)
+            .collect::<Vec<_>>();
+        self.write(&deletions, &[])
     }
 
     fn eval_delete_insert(
@@ -124,7 +494,11 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         using: &QueryDataset,
         algebra: &GraphPattern,
     ) -> Result<(), EvaluationError> {
-        let dataset = Rc::new(DatasetView::new(self.transaction.reader(), using));
+        self.options.query_options.policy.check_pattern(algebra)?;
+        let dataset = Rc::new(
+            DatasetView::new(self.transaction.reader(), using)
+                .with_hidden_graphs(&self.options.query_options.hidden_graphs),
+        );
         let (plan, variables) = PlanBuilder::build(
             &dataset,
             algebra,
@@ -138,29 +512,34 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
             self.options.query_options.service_handler(),
             Rc::new(self.options.query_options.custom_functions.clone()),
             false,
-        );
-        let mut bnodes = HashMap::new();
+        )
+        .with_deadline(self.options.query_options.deadline())
+        .with_limits(None, self.options.query_options.max_intermediate_bindings)
+        .with_blank_node_generator(Rc::clone(&self.blank_nodes));
+        let mut bnodes = BlankNodeMapping::new(Rc::clone(&self.blank_nodes));
         let (eval, _) = evaluator.plan_evaluator(Rc::new(plan));
         let tuples =
             eval(EncodedTuple::with_capacity(variables.len())).collect::<Result<Vec<_>, _>>()?; //TODO: would be much better to stream
+        let mut deletions = Vec::new();
+        let mut insertions = Vec::new();
         for tuple in tuples {
             for quad in delete {
                 if let Some(quad) =
                     Self::convert_ground_quad_pattern(quad, &variables, &tuple, &dataset)?
                 {
-                    self.transaction.remove(quad.as_ref())?;
+                    deletions.push(quad);
                 }
             }
             for quad in insert {
                 if let Some(quad) =
                     Self::convert_quad_pattern(quad, &variables, &tuple, &dataset, &mut bnodes)?
                 {
-                    self.transaction.insert(quad.as_ref())?;
+                    insertions.push(quad);
                 }
             }
             bnodes.clear();
         }
-        Ok(())
+        self.write(&deletions, &insertions)
     }
 
     fn eval_load(&mut self, from: &NamedNode, to: &GraphName) -> Result<(), EvaluationError> {
@@ -183,13 +562,51 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
                 .with_base_iri(base_iri.as_str())
                 .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
         }
-        for t in parser.read_triples(BufReader::new(body))? {
-            self.transaction
-                .insert(t?.as_ref().in_graph(to_graph_name))?;
+        let insertions = parser
+            .read_triples(BufReader::new(body))?
+            .map(|t| Ok(t?.in_graph(to_graph_name.into_owned())))
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        self.write(&[], &insertions)
+    }
+
+    /// Evaluates a `CLEAR` or a `DROP`, notifying the update hooks of the removal of the quads of the target
+    fn eval_graph_removal(
+        &mut self,
+        graph: &GraphTarget,
+        eval: impl FnOnce(&mut Self) -> Result<(), EvaluationError>,
+    ) -> Result<(), EvaluationError> {
+        if !self.options.has_hooks() {
+            return eval(self);
         }
+        let deletions = self.graph_target_quads(graph)?;
+        self.options.before_write(&deletions, &[])?;
+        eval(self)?;
+        self.options.after_write(&deletions, &[]);
         Ok(())
     }
 
+    fn graph_target_quads(&self, graph: &GraphTarget) -> Result<Vec<OxQuad>, EvaluationError> {
+        let reader = self.transaction.reader();
+        let quads = match graph {
+            GraphTarget::NamedNode(graph_name) => {
+                reader.quads_for_pattern(None, None, None, Some(&graph_name.as_ref().into()))
+            }
+            GraphTarget::DefaultGraph => {
+                reader.quads_for_pattern(None, None, None, Some(&EncodedTerm::DefaultGraph))
+            }
+            GraphTarget::NamedGraphs | GraphTarget::AllGraphs => reader.quads(),
+        };
+        let mut result = Vec::new();
+        for quad in quads {
+            let quad = quad?;
+            if matches!(graph, GraphTarget::NamedGraphs) && quad.graph_name.is_default_graph() {
+                continue;
+            }
+            result.push(reader.decode_quad(&quad)?);
+        }
+        Ok(result)
+    }
+
     fn eval_create(&mut self, graph_name: &NamedNode, silent: bool) -> Result<(), EvaluationError> {
         if self.transaction.insert_named_graph(graph_name.into())? || silent {
             Ok(())
@@ -245,7 +662,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         }
     }
 
-    fn convert_quad(quad: &Quad, bnodes: &mut HashMap<BlankNode, BlankNode>) -> OxQuad {
+    fn convert_quad(quad: &Quad, bnodes: &mut BlankNodeMapping) -> OxQuad {
         OxQuad {
             subject: match &quad.subject {
                 Subject::NamedNode(subject) => subject.clone().into(),
@@ -266,7 +683,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         }
     }
 
-    fn convert_triple(triple: &Triple, bnodes: &mut HashMap<BlankNode, BlankNode>) -> Triple {
+    fn convert_triple(triple: &Triple, bnodes: &mut BlankNodeMapping) -> Triple {
         Triple {
             subject: match &triple.subject {
                 Subject::NamedNode(subject) => subject.clone().into(),
@@ -283,11 +700,13 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         }
     }
 
-    fn convert_blank_node(
-        node: &BlankNode,
-        bnodes: &mut HashMap<BlankNode, BlankNode>,
-    ) -> BlankNode {
-        bnodes.entry(node.clone()).or_default().clone()
+    fn convert_blank_node(node: &BlankNode, bnodes: &mut BlankNodeMapping) -> BlankNode {
+        let generator = &bnodes.generator;
+        bnodes
+            .nodes
+            .entry(node.clone())
+            .or_insert_with(|| BlankNode::new_from_unique_id(generator.next_id()))
+            .clone()
     }
 
     fn convert_ground_quad(quad: &GroundQuad) -> OxQuad {
@@ -329,7 +748,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         variables: &[Variable],
         values: &EncodedTuple,
         dataset: &DatasetView,
-        bnodes: &mut HashMap<BlankNode, BlankNode>,
+        bnodes: &mut BlankNodeMapping,
     ) -> Result<Option<OxQuad>, EvaluationError> {
         Ok(Some(OxQuad {
             subject: match Self::convert_term_or_var(
@@ -373,7 +792,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         variables: &[Variable],
         values: &EncodedTuple,
         dataset: &DatasetView,
-        bnodes: &mut HashMap<BlankNode, BlankNode>,
+        bnodes: &mut BlankNodeMapping,
     ) -> Result<Option<Term>, EvaluationError> {
         Ok(match term {
             TermPattern::NamedNode(term) => Some(term.clone().into()),
@@ -429,7 +848,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
         variables: &[Variable],
         values: &EncodedTuple,
         dataset: &DatasetView,
-        bnodes: &mut HashMap<BlankNode, BlankNode>,
+        bnodes: &mut BlankNodeMapping,
     ) -> Result<Option<Triple>, EvaluationError> {
         Ok(Some(Triple {
             subject: match Self::convert_term_or_var(
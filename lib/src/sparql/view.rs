@@ -0,0 +1,435 @@
+//! Analysis of the queries of the [materialized views](crate::views)
+//! to find how a quad insertion or removal changes their results.
+
+use crate::model::*;
+use crate::sparql::algebra::Query;
+use spargebra::algebra::{
+    AggregateExpression, Expression, GraphPattern, OrderExpression, PropertyPathExpression,
+};
+use spargebra::term::{
+    GroundSubject, GroundTerm, GroundTriple, NamedNodePattern, TermPattern, TriplePattern,
+};
+
+/// The effect of a quad change on the results of a query.
+pub(crate) enum ChangeEffect {
+    /// The change does not modify the results.
+    Unchanged,
+    /// The new results are the current ones plus the results of these queries.
+    Additions(Vec<Query>),
+    /// The results may have changed in a way that requires a full evaluation.
+    Unknown,
+}
+
+/// A query with the information required to maintain its results from the quad changes.
+pub(crate) struct IncrementalQuery {
+    query: Query,
+    /// The quads read by the query, `None` if the query reads data in a way that is not tracked.
+    patterns: Option<Vec<ReadPattern>>,
+    /// If the query is a `CONSTRUCT` whose results only grow on insertions
+    /// and each new result can be found by binding one of its triple patterns to the inserted quad.
+    additive: bool,
+}
+
+impl IncrementalQuery {
+    pub fn new(query: Query) -> Self {
+        let (pattern, template) = match &query.inner {
+            spargebra::Query::Construct {
+                pattern, template, ..
+            } => (pattern, Some(template)),
+            spargebra::Query::Select { pattern, .. }
+            | spargebra::Query::Describe { pattern, .. }
+            | spargebra::Query::Ask { pattern, .. } => (pattern, None),
+        };
+        let mut patterns = Vec::new();
+        let tracked =
+            query.dataset.is_default_dataset() && collect_patterns(pattern, None, &mut patterns);
+        // The blank nodes of the template are fresh at each evaluation so the results would be duplicated
+        let additive = tracked
+            && template.map_or(false, |template| {
+                !template.iter().any(|triple| {
+                    contains_blank_node(&triple.subject) || contains_blank_node(&triple.object)
+                })
+            })
+            && is_additive(pattern);
+        Self {
+            patterns: tracked.then(|| patterns),
+            additive,
+            query,
+        }
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    pub fn is_construct(&self) -> bool {
+        self.query.is_construct()
+    }
+
+    pub fn is_select(&self) -> bool {
+        matches!(self.query.inner, spargebra::Query::Select { .. })
+    }
+
+    /// Returns how the insertion (if `inserted`) or the removal of a quad changes the query results.
+    pub fn effect(&self, quad: QuadRef<'_>, inserted: bool) -> ChangeEffect {
+        let patterns = if let Some(patterns) = &self.patterns {
+            patterns
+        } else {
+            return ChangeEffect::Unknown;
+        };
+        let bindings = patterns
+            .iter()
+            .filter_map(|pattern| pattern.bind(quad))
+            .collect::<Vec<_>>();
+        if bindings.is_empty() {
+            ChangeEffect::Unchanged
+        } else if inserted && self.additive {
+            ChangeEffect::Additions(
+                bindings
+                    .into_iter()
+                    .map(|binding| self.restricted(binding))
+                    .collect(),
+            )
+        } else {
+            ChangeEffect::Unknown
+        }
+    }
+
+    /// Returns the query restricted to the solutions compatible with the binding.
+    fn restricted(&self, binding: Vec<(Variable, Term)>) -> Query {
+        // The variables bound to blank nodes are left free, the results are then a bigger subset of the query results
+        let (variables, values): (Vec<_>, Vec<_>) = binding
+            .into_iter()
+            .filter_map(|(variable, term)| Some((variable, Some(ground_term(term)?))))
+            .unzip();
+        if let spargebra::Query::Construct {
+            template,
+            dataset,
+            pattern,
+            base_iri,
+        } = &self.query.inner
+        {
+            spargebra::Query::Construct {
+                template: template.clone(),
+                dataset: dataset.clone(),
+                pattern: GraphPattern::Join {
+                    left: Box::new(GraphPattern::Values {
+                        variables,
+                        bindings: vec![values],
+                    }),
+                    right: Box::new(pattern.clone()),
+                },
+                base_iri: base_iri.clone(),
+            }
+            .into()
+        } else {
+            self.query.clone()
+        }
+    }
+}
+
+/// A pattern read by a query with the graph it is evaluated against (`None` for the default graph).
+struct ReadPattern {
+    graph_name: Option<NamedNodePattern>,
+    read: Read,
+}
+
+enum Read {
+    Triple(TriplePattern),
+    /// A property path with the predicates it may follow, `None` if it may follow any predicate.
+    Path(Option<Vec<NamedNode>>),
+}
+
+impl ReadPattern {
+    /// Returns the variable binding making the pattern match the quad, if any.
+    fn bind(&self, quad: QuadRef<'_>) -> Option<Vec<(Variable, Term)>> {
+        let mut binding = Vec::new();
+        match (&self.graph_name, quad.graph_name) {
+            (None, GraphNameRef::DefaultGraph)
+            | (Some(NamedNodePattern::Variable(_)), GraphNameRef::BlankNode(_)) => (),
+            (Some(NamedNodePattern::NamedNode(name)), GraphNameRef::NamedNode(graph_name))
+                if name.as_ref() == graph_name =>
+            {
+                ()
+            }
+            (Some(NamedNodePattern::Variable(variable)), GraphNameRef::NamedNode(graph_name)) => {
+                bind_variable(&mut binding, variable, graph_name.into())?;
+            }
+            _ => return None,
+        }
+        match &self.read {
+            Read::Triple(pattern) => {
+                bind_triple_pattern(
+                    &mut binding,
+                    pattern,
+                    quad.subject.into(),
+                    quad.predicate,
+                    quad.object,
+                )?;
+            }
+            Read::Path(predicates) => {
+                if let Some(predicates) = predicates {
+                    if !predicates.iter().any(|p| p.as_ref() == quad.predicate) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(binding)
+    }
+}
+
+fn bind_triple_pattern(
+    binding: &mut Vec<(Variable, Term)>,
+    pattern: &TriplePattern,
+    subject: TermRef<'_>,
+    predicate: NamedNodeRef<'_>,
+    object: TermRef<'_>,
+) -> Option<()> {
+    bind_term_pattern(binding, &pattern.subject, subject)?;
+    match &pattern.predicate {
+        NamedNodePattern::NamedNode(p) => {
+            if p.as_ref() != predicate {
+                return None;
+            }
+        }
+        NamedNodePattern::Variable(variable) => {
+            bind_variable(binding, variable, predicate.into())?;
+        }
+    }
+    bind_term_pattern(binding, &pattern.object, object)
+}
+
+fn bind_term_pattern(
+    binding: &mut Vec<(Variable, Term)>,
+    pattern: &TermPattern,
+    term: TermRef<'_>,
+) -> Option<()> {
+    match pattern {
+        TermPattern::NamedNode(node) => matches_term(node.as_ref().into(), term),
+        TermPattern::Literal(literal) => matches_term(literal.as_ref().into(), term),
+        // Blank nodes behave like variables that can not be bound
+        TermPattern::BlankNode(_) => Some(()),
+        TermPattern::Triple(pattern) => {
+            if let TermRef::Triple(triple) = term {
+                bind_triple_pattern(
+                    binding,
+                    pattern,
+                    triple.subject.as_ref().into(),
+                    triple.predicate.as_ref(),
+                    triple.object.as_ref(),
+                )
+            } else {
+                None
+            }
+        }
+        TermPattern::Variable(variable) => bind_variable(binding, variable, term),
+    }
+}
+
+fn bind_variable(
+    binding: &mut Vec<(Variable, Term)>,
+    variable: &Variable,
+    term: TermRef<'_>,
+) -> Option<()> {
+    if let Some((_, value)) = binding.iter().find(|(v, _)| v == variable) {
+        matches_term(value.as_ref(), term)
+    } else {
+        binding.push((variable.clone(), term.into_owned()));
+        Some(())
+    }
+}
+
+fn contains_blank_node(pattern: &TermPattern) -> bool {
+    match pattern {
+        TermPattern::BlankNode(_) => true,
+        TermPattern::Triple(triple) => {
+            contains_blank_node(&triple.subject) || contains_blank_node(&triple.object)
+        }
+        TermPattern::NamedNode(_) | TermPattern::Literal(_) | TermPattern::Variable(_) => false,
+    }
+}
+
+fn matches_term(expected: TermRef<'_>, term: TermRef<'_>) -> Option<()> {
+    if expected == term {
+        Some(())
+    } else {
+        None
+    }
+}
+
+fn ground_term(term: Term) -> Option<GroundTerm> {
+    match term {
+        Term::NamedNode(node) => Some(node.into()),
+        Term::BlankNode(_) => None,
+        Term::Literal(literal) => Some(literal.into()),
+        Term::Triple(triple) => Some(GroundTerm::Triple(Box::new(GroundTriple {
+            subject: match triple.subject {
+                Subject::NamedNode(node) => GroundSubject::NamedNode(node),
+                Subject::BlankNode(_) => return None,
+                Subject::Triple(t) => match ground_term(Term::Triple(t))? {
+                    GroundTerm::Triple(t) => GroundSubject::Triple(t),
+                    GroundTerm::NamedNode(_) | GroundTerm::Literal(_) => return None,
+                },
+            },
+            predicate: triple.predicate,
+            object: ground_term(triple.object)?,
+        }))),
+    }
+}
+
+/// Collects the patterns read by a graph pattern.
+///
+/// Returns `false` if the graph pattern reads data in a way that is not tracked (`SERVICE` calls and `EXISTS` filters).
+fn collect_patterns(
+    pattern: &GraphPattern,
+    graph_name: Option<&NamedNodePattern>,
+    patterns: &mut Vec<ReadPattern>,
+) -> bool {
+    match pattern {
+        GraphPattern::Bgp { patterns: triples } => {
+            patterns.extend(triples.iter().map(|triple| ReadPattern {
+                graph_name: graph_name.cloned(),
+                read: Read::Triple(triple.clone()),
+            }));
+            true
+        }
+        GraphPattern::Path { path, .. } => {
+            let mut predicates = Some(Vec::new());
+            collect_path_predicates(path, &mut predicates);
+            patterns.push(ReadPattern {
+                graph_name: graph_name.cloned(),
+                read: Read::Path(predicates),
+            });
+            true
+        }
+        GraphPattern::Join { left, right }
+        | GraphPattern::Lateral { left, right }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            collect_patterns(left, graph_name, patterns)
+                && collect_patterns(right, graph_name, patterns)
+        }
+        GraphPattern::LeftJoin {
+            left,
+            right,
+            expression,
+        } => {
+            expression.as_ref().map_or(true, |e| !contains_exists(e))
+                && collect_patterns(left, graph_name, patterns)
+                && collect_patterns(right, graph_name, patterns)
+        }
+        GraphPattern::Filter { expr, inner }
+        | GraphPattern::Extend {
+            inner,
+            expression: expr,
+            ..
+        } => !contains_exists(expr) && collect_patterns(inner, graph_name, patterns),
+        GraphPattern::OrderBy { inner, expression } => {
+            expression.iter().all(|e| match e {
+                OrderExpression::Asc(e) | OrderExpression::Desc(e) => !contains_exists(e),
+            }) && collect_patterns(inner, graph_name, patterns)
+        }
+        GraphPattern::Graph { name, inner } => collect_patterns(inner, Some(name), patterns),
+        GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. } => collect_patterns(inner, graph_name, patterns),
+        GraphPattern::Group {
+            inner, aggregates, ..
+        } => {
+            aggregates.iter().all(|(_, aggregate)| match aggregate {
+                AggregateExpression::Count { expr, .. } => {
+                    expr.as_ref().map_or(true, |e| !contains_exists(e))
+                }
+                AggregateExpression::Sum { expr, .. }
+                | AggregateExpression::Avg { expr, .. }
+                | AggregateExpression::Min { expr, .. }
+                | AggregateExpression::Max { expr, .. }
+                | AggregateExpression::GroupConcat { expr, .. }
+                | AggregateExpression::Sample { expr, .. }
+                | AggregateExpression::Custom { expr, .. } => !contains_exists(expr),
+            }) && collect_patterns(inner, graph_name, patterns)
+        }
+        GraphPattern::Values { .. } => true,
+        GraphPattern::Service { .. } => false,
+    }
+}
+
+/// Collects the predicates a property path may follow, setting `predicates` to `None` if it may follow any predicate.
+fn collect_path_predicates(path: &PropertyPathExpression, predicates: &mut Option<Vec<NamedNode>>) {
+    match path {
+        PropertyPathExpression::NamedNode(p) => {
+            if let Some(predicates) = predicates {
+                predicates.push(p.clone());
+            }
+        }
+        PropertyPathExpression::NegatedPropertySet(_) => *predicates = None,
+        PropertyPathExpression::Reverse(p)
+        | PropertyPathExpression::ZeroOrMore(p)
+        | PropertyPathExpression::OneOrMore(p)
+        | PropertyPathExpression::ZeroOrOne(p) => collect_path_predicates(p, predicates),
+        PropertyPathExpression::Sequence(a, b) | PropertyPathExpression::Alternative(a, b) => {
+            collect_path_predicates(a, predicates);
+            collect_path_predicates(b, predicates);
+        }
+    }
+}
+
+/// Checks if the solutions of a graph pattern only grow when quads are inserted
+/// and each new solution matches one of its triple patterns with the new quad.
+fn is_additive(pattern: &GraphPattern) -> bool {
+    match pattern {
+        GraphPattern::Bgp { .. } | GraphPattern::Values { .. } => true,
+        GraphPattern::Join { left, right } | GraphPattern::Union { left, right } => {
+            is_additive(left) && is_additive(right)
+        }
+        GraphPattern::Filter { expr, inner }
+        | GraphPattern::Extend {
+            inner,
+            expression: expr,
+            ..
+        } => !contains_exists(expr) && is_additive(inner),
+        GraphPattern::Graph { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner } => is_additive(inner),
+        // Projections are excluded because they hide variables that can not be bound from outside
+        GraphPattern::Path { .. }
+        | GraphPattern::LeftJoin { .. }
+        | GraphPattern::Lateral { .. }
+        | GraphPattern::Minus { .. }
+        | GraphPattern::OrderBy { .. }
+        | GraphPattern::Project { .. }
+        | GraphPattern::Slice { .. }
+        | GraphPattern::Group { .. }
+        | GraphPattern::Service { .. } => false,
+    }
+}
+
+fn contains_exists(expression: &Expression) -> bool {
+    match expression {
+        Expression::NamedNode(_)
+        | Expression::Literal(_)
+        | Expression::Variable(_)
+        | Expression::Bound(_) => false,
+        Expression::Or(a, b)
+        | Expression::And(a, b)
+        | Expression::Equal(a, b)
+        | Expression::SameTerm(a, b)
+        | Expression::Greater(a, b)
+        | Expression::GreaterOrEqual(a, b)
+        | Expression::Less(a, b)
+        | Expression::LessOrEqual(a, b)
+        | Expression::Add(a, b)
+        | Expression::Subtract(a, b)
+        | Expression::Multiply(a, b)
+        | Expression::Divide(a, b) => contains_exists(a) || contains_exists(b),
+        Expression::UnaryPlus(e) | Expression::UnaryMinus(e) | Expression::Not(e) => {
+            contains_exists(e)
+        }
+        Expression::In(a, b) => contains_exists(a) || b.iter().any(contains_exists),
+        Expression::Exists(_) => true,
+        Expression::If(a, b, c) => contains_exists(a) || contains_exists(b) || contains_exists(c),
+        Expression::Coalesce(l) | Expression::FunctionCall(_, l) => l.iter().any(contains_exists),
+    }
+}
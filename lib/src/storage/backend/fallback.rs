@@ -1,10 +1,15 @@
 //! TODO: This storage is dramatically naive.
 
+use crate::storage::backend::key_value::{KeyValueStore, SharedMap};
+#[cfg(feature = "stable-memory")]
+use crate::storage::backend::stable::StableTree;
 use crate::storage::StorageError;
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::cell::{Cell, RefCell};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error;
-use std::mem::transmute;
+use std::mem::{take, transmute};
+use std::ops::ControlFlow;
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, RwLock, RwLockWriteGuard};
 
@@ -13,79 +18,249 @@ pub struct ColumnFamilyDefinition {
     pub use_iter: bool,
     pub min_prefix_size: usize,
     pub unordered_writes: bool,
+    /// The stable memory region storing the column family, the heap being used if `None`
+    pub stable_memory: Option<StableMemoryDefinition>,
+    /// The map storing the column family, taking precedence over `stable_memory`
+    pub store: Option<Box<dyn KeyValueStore>>,
 }
 
+/// A stable memory region storing a column family
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct StableMemoryDefinition {
+    /// The identifier of the virtual memory
+    pub memory_id: u8,
+    /// The maximal size of the region in 64KiB pages
+    pub max_pages: Option<u64>,
+}
+
+type Trees = HashMap<ColumnFamily, Tree>;
+
 #[derive(Clone)]
-pub struct Db(Arc<RwLock<HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>);
+pub struct Db {
+    trees: Arc<RwLock<Trees>>,
+    history: Arc<RwLock<Option<History>>>,
+}
 
 impl Db {
-    #[allow(clippy::unnecessary_wraps)]
     pub fn new(column_families: Vec<ColumnFamilyDefinition>) -> Result<Self, StorageError> {
         let mut trees = HashMap::new();
         for cf in column_families {
-            trees.insert(ColumnFamily(cf.name), BTreeMap::default());
+            let tree = if let Some(store) = cf.store {
+                store
+            } else {
+                new_tree(cf.stable_memory)?
+            };
+            trees.insert(ColumnFamily(cf.name), tree);
         }
-        trees.entry(ColumnFamily("default")).or_default(); // We make sure that "default" key exists.
-        Ok(Self(Arc::new(RwLock::new(trees))))
+        trees
+            .entry(ColumnFamily("default"))
+            .or_insert_with(heap_tree); // We make sure that "default" key exists.
+        Ok(Self {
+            trees: Arc::new(RwLock::new(trees)),
+            history: Arc::default(),
+        })
+    }
+
+    /// Starts to keep the previous values of the written keys to allow to read the past versions.
+    ///
+    /// The current version is the `u64` written in big endian at `key` in `column_family`, `0` if there is none.
+    /// The writes done while the current version is `v` are recorded as the ones of the version `v + 1`.
+    /// Only the `retained_versions` versions before the current one are kept.
+    #[allow(clippy::unwrap_in_result)]
+    pub fn enable_history(
+        &self,
+        column_family: ColumnFamily,
+        key: &'static [u8],
+        retained_versions: u64,
+    ) -> Result<(), StorageError> {
+        let trees = self.trees.read().unwrap();
+        let mut history = History {
+            version_column_family: column_family,
+            version_key: key,
+            retained_versions,
+            oldest_version: 0,
+            previous_values: HashMap::new(),
+            written_keys: BTreeMap::new(),
+        };
+        history.oldest_version = history.current_version(&trees)?;
+        *self.history.write().unwrap() = Some(history);
+        Ok(())
     }
 
     #[allow(clippy::unwrap_in_result)]
     pub fn column_family(&self, name: &'static str) -> Option<ColumnFamily> {
         let name = ColumnFamily(name);
-        (self.0.read().unwrap().contains_key(&name)).then(|| name)
+        (self.trees.read().unwrap().contains_key(&name)).then(|| name)
     }
 
     #[must_use]
     pub fn snapshot(&self) -> Reader {
-        Reader(InnerReader::Simple(Arc::clone(&self.0)))
+        Reader(InnerReader::Simple(Arc::clone(&self.trees)), Rc::default())
+    }
+
+    /// Returns a reader of the content of the database at a past version.
+    ///
+    /// Fails with [`StorageError::VersionNotAvailable`] if the history is not enabled
+    /// or does not contain the version anymore.
+    #[allow(clippy::unwrap_in_result)]
+    pub fn snapshot_at(&self, version: u64) -> Result<Reader, StorageError> {
+        let reader = VersionedReader {
+            trees: Arc::clone(&self.trees),
+            history: Arc::clone(&self.history),
+            version,
+        };
+        reader.with_history(|trees, history| {
+            if version <= history.current_version(trees)? {
+                Ok(())
+            } else {
+                Err(StorageError::VersionNotAvailable { version })
+            }
+        })??;
+        Ok(Reader(InnerReader::Versioned(reader), Rc::default()))
     }
 
+    /// Reads the current version and drops from the history the versions not retained anymore.
+    ///
+    /// Returns the version of the writes of a new transaction, `None` if the history is disabled.
+    #[allow(clippy::unwrap_in_result)]
+    fn start_versioned_writes(&self, trees: &Trees) -> Result<Option<u64>, StorageError> {
+        Ok(if let Some(history) = &mut *self.history.write().unwrap() {
+            let version = history.current_version(trees)?;
+            history.prune(version);
+            Some(version + 1)
+        } else {
+            None
+        })
+    }
+
+    /// Executes a transaction.
+    ///
+    /// If `log` is set, the writes done by the transaction are appended to it.
     #[allow(clippy::unwrap_in_result)]
     pub fn transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
         &'b self,
+        log: Option<WriteLog>,
         f: impl Fn(Transaction<'a>) -> Result<T, E>,
     ) -> Result<T, E> {
-        f(Transaction(Rc::new(RefCell::new(self.0.write().unwrap()))))
+        let trees = self.trees.write().unwrap();
+        let version = self.start_versioned_writes(&trees)?;
+        f(Transaction {
+            inner: InnerTransaction::Locked(Rc::new(RefCell::new(trees))),
+            log,
+            history: Arc::clone(&self.history),
+            version,
+            savepoints: Rc::default(),
+            written: Rc::default(),
+        })
+    }
+
+    /// Calls `f` on each entry of a column family in key order.
+    ///
+    /// The database is locked for reads during the whole call.
+    #[allow(clippy::unwrap_in_result)]
+    pub fn for_each_entry<E>(
+        &self,
+        column_family: &ColumnFamily,
+        mut f: impl FnMut(&[u8], &[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut result = Ok(());
+        if let Some(tree) = self.trees.read().unwrap().get(column_family) {
+            tree.for_each(&mut |key, value| {
+                result = f(key, value);
+                if result.is_ok() {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+        }
+        result
+    }
+
+    /// Replaces atomically the content of some column families.
+    ///
+    /// The history is cleared, the previous versions are not readable anymore.
+    #[allow(clippy::unwrap_in_result)]
+    pub fn replace_column_families(
+        &self,
+        content: Vec<(ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>)>,
+    ) -> Result<(), StorageError> {
+        let mut trees = self.trees.write().unwrap();
+        for (column_family, content) in content {
+            trees
+                .entry(column_family)
+                .or_insert_with(heap_tree)
+                .replace(content)?;
+        }
+        if let Some(history) = &mut *self.history.write().unwrap() {
+            history.previous_values.clear();
+            history.written_keys.clear();
+            history.oldest_version = history.current_version(&trees)?;
+        }
+        Ok(())
+    }
+
+    /// Executes a transaction without locking the database for its whole duration.
+    ///
+    /// Each read sees the latest written data and each write is applied immediately.
+    pub fn transaction_without_lock<'a, T, E: Error + 'static + From<StorageError>>(
+        &'a self,
+        log: Option<WriteLog>,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let version = self.start_versioned_writes(&self.trees.read().unwrap())?;
+        f(Transaction {
+            inner: InnerTransaction::Unlocked(Arc::clone(&self.trees)),
+            log,
+            history: Arc::clone(&self.history),
+            version,
+            savepoints: Rc::default(),
+            written: Rc::default(),
+        })
     }
 }
 
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct ColumnFamily(&'static str);
 
-pub struct Reader(InnerReader);
+/// A reader of the column families, its clones sharing its [`ScanStats`]
+#[derive(Clone)]
+pub struct Reader(InnerReader, Rc<ScanStats>);
 
+#[derive(Clone)]
 enum InnerReader {
-    Simple(Arc<RwLock<HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>),
-    Transaction(
-        Weak<RefCell<RwLockWriteGuard<'static, HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>>,
-    ),
+    Simple(Arc<RwLock<HashMap<ColumnFamily, Tree>>>),
+    Transaction(Weak<RefCell<RwLockWriteGuard<'static, HashMap<ColumnFamily, Tree>>>>),
+    Versioned(VersionedReader),
 }
 
 impl Reader {
     #[allow(clippy::unwrap_in_result)]
+    /// Returns the value of `key`, shared with the column family if its map allows it
     pub fn get(
         &self,
         column_family: &ColumnFamily,
         key: &[u8],
-    ) -> Result<Option<Vec<u8>>, StorageError> {
+    ) -> Result<Option<Arc<[u8]>>, StorageError> {
         match &self.0 {
             InnerReader::Simple(reader) => Ok(reader
                 .read()
                 .unwrap()
                 .get(column_family)
-                .and_then(|cf| cf.get(key).cloned())),
+                .and_then(|cf| cf.get_shared(key))),
             InnerReader::Transaction(reader) => {
                 if let Some(reader) = reader.upgrade() {
                     Ok((*reader)
                         .borrow()
                         .get(column_family)
-                        .and_then(|cf| cf.get(key).cloned()))
+                        .and_then(|cf| cf.get_shared(key)))
                 } else {
                     Err(StorageError::Other(
                         "The transaction is already ended".into(),
                     ))
                 }
             }
+            InnerReader::Versioned(reader) => Ok(reader.get(column_family, key)?.map(Into::into)),
         }
     }
 
@@ -113,6 +288,7 @@ impl Reader {
                     ))
                 }
             }
+            InnerReader::Versioned(reader) => Ok(reader.get(column_family, key)?.is_some()),
         }
     }
 
@@ -120,61 +296,138 @@ impl Reader {
         self.scan_prefix(column_family, &[])
     }
 
-    #[allow(clippy::unwrap_in_result)]
+    /// Iterates on the keys starting with `prefix` in key order
+    ///
+    /// The scans of transactions and of past versions read the keys in batches of growing size,
+    /// so a scan stopped after its first entries does not read the other ones.
+    /// The scans of the current content read all the keys at once:
+    /// the content might be written before the end of the scan and its later batches would see the writes.
     pub fn scan_prefix(
         &self,
         column_family: &ColumnFamily,
         prefix: &[u8],
     ) -> Result<Iter, StorageError> {
-        let data: Vec<_> = match &self.0 {
-            InnerReader::Simple(reader) => {
-                let trees = reader.read().unwrap();
-                let tree = if let Some(tree) = trees.get(column_family) {
-                    tree
-                } else {
-                    return Ok(Iter {
-                        iter: Vec::new().into_iter(),
-                        current: None,
-                    });
-                };
-                if prefix.is_empty() {
-                    tree.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        let mut scan = PrefixScan {
+            reader: self.clone(),
+            column_family: column_family.clone(),
+            prefix: prefix.to_vec(),
+            batch_size: if matches!(self.0, InnerReader::Simple(_)) {
+                usize::MAX
+            } else {
+                FIRST_BATCH_SIZE
+            },
+            done: false,
+        };
+        let entries = scan.read(prefix)?;
+        Ok(Iter {
+            iter: entries.into_iter(),
+            current: None,
+            scan: (!scan.done).then(|| scan),
+            error: Cell::new(None),
+        }
+        .started())
+    }
+
+    /// Returns the first key starting with `prefix` and greater than or equal to `start`
+    pub fn first_key_from(
+        &self,
+        column_family: &ColumnFamily,
+        prefix: &[u8],
+        start: &[u8],
+    ) -> Result<Option<Arc<[u8]>>, StorageError> {
+        let entries = self.entries(
+            column_family,
+            |tree| tree.scan_prefix_from_shared(prefix, start, 1),
+            |reader| reader.scan_prefix_from(column_family, prefix, start, 1),
+        )?;
+        self.1.read(entries.len());
+        Ok(entries.into_iter().next().map(|(key, _)| key))
+    }
+
+    /// The work done by the scans of the reader and of its clones
+    pub fn scan_stats(&self) -> Rc<ScanStats> {
+        Rc::clone(&self.1)
+    }
+
+    /// Iterates on the keys greater or equal to `start` and lower than `end` if set, in key order
+    pub fn scan_range(
+        &self,
+        column_family: &ColumnFamily,
+        start: &[u8],
+        end: Option<&[u8]>,
+    ) -> Result<Iter, StorageError> {
+        let entries = self.entries(
+            column_family,
+            |tree| shared_entries(tree.scan_range(start, end)),
+            |reader| {
+                Ok(shared_entries(reader.scan_range(
+                    column_family,
+                    start,
+                    end,
+                )?))
+            },
+        )?;
+        self.1.read(entries.len());
+        Ok(Iter::new(entries))
+    }
+
+    #[allow(clippy::unwrap_in_result)]
+    fn entries(
+        &self,
+        column_family: &ColumnFamily,
+        scan: impl FnOnce(&Tree) -> Vec<(Arc<[u8]>, Arc<[u8]>)>,
+        versioned_scan: impl FnOnce(
+            &VersionedReader,
+        ) -> Result<Vec<(Arc<[u8]>, Arc<[u8]>)>, StorageError>,
+    ) -> Result<Vec<(Arc<[u8]>, Arc<[u8]>)>, StorageError> {
+        match &self.0 {
+            InnerReader::Simple(reader) => Ok(reader
+                .read()
+                .unwrap()
+                .get(column_family)
+                .map_or_else(Vec::new, scan)),
+            InnerReader::Transaction(reader) => {
+                if let Some(reader) = reader.upgrade() {
+                    Ok((*reader)
+                        .borrow()
+                        .get(column_family)
+                        .map_or_else(Vec::new, scan))
                 } else {
-                    tree.range(prefix.to_vec()..)
-                        .take_while(|(k, _)| k.starts_with(prefix))
-                        .map(|(k, v)| (k.clone(), v.clone()))
-                        .collect()
+                    Err(StorageError::Other(
+                        "The transaction is already ended".into(),
+                    ))
                 }
             }
+            InnerReader::Versioned(reader) => versioned_scan(reader),
+        }
+    }
+
+    /// Returns at most `limit` keys greater or equal to `from` in key order
+    #[allow(clippy::unwrap_in_result)]
+    pub fn keys_from(
+        &self,
+        column_family: &ColumnFamily,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<Vec<u8>>, StorageError> {
+        let keys = |trees: &HashMap<ColumnFamily, Tree>| {
+            trees
+                .get(column_family)
+                .map_or_else(Vec::new, |tree| tree.keys_from(from, limit))
+        };
+        match &self.0 {
+            InnerReader::Simple(reader) => Ok(keys(&reader.read().unwrap())),
             InnerReader::Transaction(reader) => {
                 if let Some(reader) = reader.upgrade() {
-                    let trees = (*reader).borrow();
-                    let tree = if let Some(tree) = trees.get(column_family) {
-                        tree
-                    } else {
-                        return Ok(Iter {
-                            iter: Vec::new().into_iter(),
-                            current: None,
-                        });
-                    };
-                    if prefix.is_empty() {
-                        tree.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
-                    } else {
-                        tree.range(prefix.to_vec()..)
-                            .take_while(|(k, _)| k.starts_with(prefix))
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect()
-                    }
+                    Ok(keys(&(*reader).borrow()))
                 } else {
-                    return Err(StorageError::Other(
+                    Err(StorageError::Other(
                         "The transaction is already ended".into(),
-                    ));
+                    ))
                 }
             }
-        };
-        let mut iter = data.into_iter();
-        let current = iter.next();
-        Ok(Iter { iter, current })
+            InnerReader::Versioned(reader) => reader.keys_from(column_family, from, limit),
+        }
     }
 
     #[allow(clippy::unwrap_in_result)]
@@ -184,19 +437,20 @@ impl Reader {
                 .read()
                 .unwrap()
                 .get(column_family)
-                .map_or(0, BTreeMap::len)),
+                .map_or(0, |tree| tree.len())),
             InnerReader::Transaction(reader) => {
                 if let Some(reader) = reader.upgrade() {
                     Ok((*reader)
                         .borrow()
                         .get(column_family)
-                        .map_or(0, BTreeMap::len))
+                        .map_or(0, |tree| tree.len()))
                 } else {
                     Err(StorageError::Other(
                         "The transaction is already ended".into(),
                     ))
                 }
             }
+            InnerReader::Versioned(reader) => reader.len(column_family),
         }
     }
 
@@ -207,46 +461,200 @@ impl Reader {
                 .read()
                 .unwrap()
                 .get(column_family)
-                .map_or(true, BTreeMap::is_empty)),
+                .map_or(true, |tree| tree.is_empty())),
             InnerReader::Transaction(reader) => {
                 if let Some(reader) = reader.upgrade() {
                     Ok((*reader)
                         .borrow()
                         .get(column_family)
-                        .map_or(true, BTreeMap::is_empty))
+                        .map_or(true, |tree| tree.is_empty()))
                 } else {
                     Err(StorageError::Other(
                         "The transaction is already ended".into(),
                     ))
                 }
             }
+            InnerReader::Versioned(reader) => Ok(reader.len(column_family)? == 0),
+        }
+    }
+}
+
+/// The writes done by a transaction, in their order.
+pub type WriteLog = Rc<RefCell<Vec<LoggedWrite>>>;
+
+/// A write recorded in a [`WriteLog`].
+pub enum LoggedWrite {
+    Insert {
+        column_family: ColumnFamily,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Remove {
+        column_family: ColumnFamily,
+        key: Vec<u8>,
+    },
+}
+
+pub struct Transaction<'a> {
+    inner: InnerTransaction<'a>,
+    log: Option<WriteLog>,
+    history: Arc<RwLock<Option<History>>>,
+    /// The version under which the previous values are recorded in the history, `None` if it is disabled
+    version: Option<u64>,
+    savepoints: Rc<RefCell<Savepoints>>,
+    written: Rc<Cell<bool>>,
+}
+
+/// The open savepoints of a transaction
+#[derive(Default)]
+struct Savepoints {
+    /// A layer per open savepoint, the innermost one last, with the values overwritten since its creation
+    layers: Vec<(u64, PreviousValues)>,
+    next_id: u64,
+}
+
+/// The values of the written keys before each write, `None` if the key was absent
+type PreviousValues = Vec<(ColumnFamily, Vec<u8>, Option<Vec<u8>>)>;
+
+/// The clones write into the same transaction
+impl Clone for Transaction<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: match &self.inner {
+                InnerTransaction::Locked(guard) => InnerTransaction::Locked(Rc::clone(guard)),
+                InnerTransaction::Unlocked(db) => InnerTransaction::Unlocked(Arc::clone(db)),
+            },
+            log: self.log.clone(),
+            history: Arc::clone(&self.history),
+            version: self.version,
+            savepoints: Rc::clone(&self.savepoints),
+            written: Rc::clone(&self.written),
         }
     }
 }
 
-pub struct Transaction<'a>(
-    Rc<RefCell<RwLockWriteGuard<'a, HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>>,
-);
+enum InnerTransaction<'a> {
+    Locked(Rc<RefCell<RwLockWriteGuard<'a, HashMap<ColumnFamily, Tree>>>>),
+    Unlocked(Arc<RwLock<HashMap<ColumnFamily, Tree>>>),
+}
 
 impl Transaction<'_> {
     #[allow(unsafe_code, clippy::useless_transmute)]
     pub fn reader(&self) -> Reader {
-        // This transmute is safe because we take a weak reference and the only Rc reference used is guarded by the lifetime.
-        Reader(InnerReader::Transaction(Rc::downgrade(unsafe {
-            transmute(&self.0)
-        })))
+        match &self.inner {
+            InnerTransaction::Locked(guard) => {
+                // This transmute is safe because we take a weak reference and the only Rc reference used is guarded by the lifetime.
+                Reader(
+                    InnerReader::Transaction(Rc::downgrade(unsafe { transmute(guard) })),
+                    Rc::default(),
+                )
+            }
+            InnerTransaction::Unlocked(db) => {
+                Reader(InnerReader::Simple(Arc::clone(db)), Rc::default())
+            }
+        }
+    }
+
+    /// Records in the history the value of the key before its first write by the transaction version
+    /// and in the innermost savepoint its value before this write
+    #[allow(clippy::unwrap_in_result)]
+    fn record_previous_value(&self, column_family: &ColumnFamily, key: &[u8]) {
+        let mut savepoints = self.savepoints.borrow_mut();
+        let layer = savepoints.layers.last_mut();
+        if self.version.is_none() && layer.is_none() {
+            return;
+        }
+        let previous = |trees: &Trees| trees.get(column_family).and_then(|tree| tree.get(key));
+        let previous = match &self.inner {
+            InnerTransaction::Locked(guard) => previous(&(**guard).borrow()),
+            InnerTransaction::Unlocked(db) => previous(&db.read().unwrap()),
+        };
+        if let Some((_, previous_values)) = layer {
+            previous_values.push((column_family.clone(), key.to_vec(), previous.clone()));
+        }
+        if let Some(version) = self.version {
+            if let Some(history) = &mut *self.history.write().unwrap() {
+                history.record(version, column_family, key, previous);
+            }
+        }
+    }
+
+    /// Opens a savepoint nested in the already open ones and returns its identifier
+    pub fn savepoint(&self) -> u64 {
+        let mut savepoints = self.savepoints.borrow_mut();
+        let id = savepoints.next_id;
+        savepoints.next_id += 1;
+        savepoints.layers.push((id, Vec::new()));
+        id
+    }
+
+    /// Restores the values the keys had when the savepoint was opened and closes it with the savepoints nested in it
+    ///
+    /// Fails if the savepoint has already been closed.
+    pub fn rollback_to_savepoint(&mut self, savepoint: u64) -> Result<(), StorageError> {
+        let layers = {
+            let mut savepoints = self.savepoints.borrow_mut();
+            let position = savepoints
+                .layers
+                .iter()
+                .position(|(id, _)| *id == savepoint)
+                .ok_or_else(|| StorageError::Other("The savepoint is already closed".into()))?;
+            savepoints.layers.split_off(position)
+        };
+        // The restoring writes must not be recorded in the outer savepoints that have not seen the undone writes
+        let outer_layers = take(&mut self.savepoints.borrow_mut().layers);
+        let result = layers
+            .into_iter()
+            .rev()
+            .flat_map(|(_, previous_values)| previous_values.into_iter().rev())
+            .try_for_each(|(column_family, key, previous)| {
+                if let Some(previous) = previous {
+                    self.insert(&column_family, &key, &previous)
+                } else {
+                    self.remove(&column_family, &key)
+                }
+            });
+        self.savepoints.borrow_mut().layers = outer_layers;
+        result
     }
 
-    #[allow(clippy::unnecessary_wraps)]
+    /// Closes the savepoint keeping its writes, the savepoints nested in it being also closed
+    ///
+    /// Its writes are then undone by a rollback to an outer savepoint.
+    pub fn release_savepoint(&self, savepoint: u64) {
+        let mut savepoints = self.savepoints.borrow_mut();
+        if let Some(position) = savepoints
+            .layers
+            .iter()
+            .position(|(id, _)| *id == savepoint)
+        {
+            let released = savepoints.layers.split_off(position);
+            if let Some((_, previous_values)) = savepoints.layers.last_mut() {
+                previous_values.extend(released.into_iter().flat_map(|(_, values)| values));
+            }
+        }
+    }
+
+    /// Returns if the transaction has written into a column family
+    pub fn has_written(&self) -> bool {
+        self.written.get()
+    }
+
+    #[allow(clippy::unnecessary_wraps, clippy::unwrap_in_result)]
     pub fn contains_key_for_update(
         &self,
         column_family: &ColumnFamily,
         key: &[u8],
     ) -> Result<bool, StorageError> {
-        Ok((*self.0)
-            .borrow()
-            .get(column_family)
-            .map_or(false, |cf| cf.contains_key(key)))
+        let contains = |trees: &HashMap<ColumnFamily, Tree>| {
+            trees
+                .get(column_family)
+                .map_or(false, |cf| cf.contains_key(key))
+        };
+        Ok(match &self.inner {
+            InnerTransaction::Locked(guard) => contains(&(**guard).borrow()),
+            InnerTransaction::Unlocked(db) => contains(&db.read().unwrap()),
+        })
     }
 
     #[allow(clippy::unnecessary_wraps, clippy::unwrap_in_result)]
@@ -256,11 +664,15 @@ impl Transaction<'_> {
         key: &[u8],
         value: &[u8],
     ) -> Result<(), StorageError> {
-        self.0
-            .borrow_mut()
-            .get_mut(column_family)
-            .unwrap()
-            .insert(key.into(), value.into());
+        self.record_previous_value(column_family, key);
+        self.update_tree(column_family, |tree| tree.insert(key, value))?;
+        if let Some(log) = &self.log {
+            log.borrow_mut().push(LoggedWrite::Insert {
+                column_family: column_family.clone(),
+                key: key.into(),
+                value: value.into(),
+            });
+        }
         Ok(())
     }
 
@@ -272,38 +684,459 @@ impl Transaction<'_> {
         self.insert(column_family, key, &[])
     }
 
+    /// Inserts entries sorted by key, merging them at once into the column family when they are many.
+    pub fn insert_sorted(
+        &mut self,
+        column_family: &ColumnFamily,
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<(), StorageError> {
+        if let Some(log) = &self.log {
+            log.borrow_mut()
+                .extend(entries.iter().map(|(key, value)| LoggedWrite::Insert {
+                    column_family: column_family.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                }));
+        }
+        for (key, _) in &entries {
+            self.record_previous_value(column_family, key);
+        }
+        self.update_tree(column_family, |tree| tree.insert_sorted(entries))
+    }
+
     #[allow(clippy::unnecessary_wraps, clippy::unwrap_in_result)]
     pub fn remove(&mut self, column_family: &ColumnFamily, key: &[u8]) -> Result<(), StorageError> {
-        self.0
-            .borrow_mut()
-            .get_mut(column_family)
-            .unwrap()
-            .remove(key);
+        self.record_previous_value(column_family, key);
+        self.update_tree(column_family, |tree| tree.remove(key))?;
+        if let Some(log) = &self.log {
+            log.borrow_mut().push(LoggedWrite::Remove {
+                column_family: column_family.clone(),
+                key: key.into(),
+            });
+        }
         Ok(())
     }
+
+    #[allow(clippy::unwrap_in_result)]
+    fn update_tree(
+        &mut self,
+        column_family: &ColumnFamily,
+        f: impl FnOnce(&mut Tree) -> Result<(), StorageError>,
+    ) -> Result<(), StorageError> {
+        self.written.set(true);
+        match &self.inner {
+            InnerTransaction::Locked(guard) => {
+                f(guard.borrow_mut().get_mut(column_family).unwrap())
+            }
+            InnerTransaction::Unlocked(db) => {
+                f(db.write().unwrap().get_mut(column_family).unwrap())
+            }
+        }
+    }
+}
+
+/// The number of entries read by the first batch of a prefix scan, the next batches doubling it
+const FIRST_BATCH_SIZE: usize = 1;
+
+/// The work done by the scans of a [`Reader`] and of its clones
+#[derive(Default)]
+pub struct ScanStats {
+    /// The number of entries read
+    pub read_entries: Cell<usize>,
+    /// The number of prefix scans dropped while some of their entries might not have been read
+    pub stopped_scans: Cell<usize>,
+}
+
+impl ScanStats {
+    fn read(&self, entries: usize) {
+        self.read_entries.set(self.read_entries.get() + entries);
+    }
+}
+
+/// A prefix scan reading its entries in batches of growing size
+struct PrefixScan {
+    reader: Reader,
+    column_family: ColumnFamily,
+    prefix: Vec<u8>,
+    batch_size: usize,
+    /// If the last batch has reached the end of the prefix
+    done: bool,
+}
+
+impl PrefixScan {
+    /// Reads the next batch of entries, starting at `start`
+    fn read(&mut self, start: &[u8]) -> Result<Vec<(Arc<[u8]>, Arc<[u8]>)>, StorageError> {
+        let limit = self.batch_size;
+        self.batch_size = self.batch_size.saturating_mul(2);
+        let entries = self.reader.entries(
+            &self.column_family,
+            |tree| tree.scan_prefix_from_shared(&self.prefix, start, limit),
+            |reader| reader.scan_prefix_from(&self.column_family, &self.prefix, start, limit),
+        )?;
+        self.reader.1.read(entries.len());
+        self.done = entries.len() < limit;
+        Ok(entries)
+    }
 }
 
+/// An iterator on entries, sharing their buffers with the column family if its map allows it
 pub struct Iter {
-    iter: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
-    current: Option<(Vec<u8>, Vec<u8>)>,
+    iter: std::vec::IntoIter<(Arc<[u8]>, Arc<[u8]>)>,
+    current: Option<(Arc<[u8]>, Arc<[u8]>)>,
+    /// The scan to read the entries after `iter` from, `None` if there are none
+    scan: Option<PrefixScan>,
+    /// The error of the last read of `scan`, returned once by `status`
+    error: Cell<Option<StorageError>>,
 }
 
 impl Iter {
+    pub fn new(entries: Vec<(Arc<[u8]>, Arc<[u8]>)>) -> Self {
+        Self {
+            iter: entries.into_iter(),
+            current: None,
+            scan: None,
+            error: Cell::new(None),
+        }
+        .started()
+    }
+
+    fn started(mut self) -> Self {
+        self.current = self.iter.next();
+        self
+    }
+
+    /// Returns an iterator on the remaining entries in the reverse order
+    #[must_use]
+    pub fn rev(mut self) -> Self {
+        let mut entries = Vec::new();
+        while let Some(entry) = self.current.clone() {
+            entries.push(entry);
+            self.next();
+        }
+        entries.reverse();
+        let iter = Self::new(entries);
+        iter.error.set(self.error.take());
+        iter
+    }
+
     pub fn key(&self) -> Option<&[u8]> {
-        Some(&self.current.as_ref()?.0)
+        self.current.as_ref().map(|(key, _)| &**key)
     }
 
-    #[allow(dead_code)]
     pub fn value(&self) -> Option<&[u8]> {
-        Some(&self.current.as_ref()?.1)
+        self.current.as_ref().map(|(_, value)| &**value)
     }
 
     pub fn next(&mut self) {
+        let previous = self.current.take();
         self.current = self.iter.next();
+        if self.current.is_some() {
+            return;
+        }
+        if let (Some(scan), Some((last_key, _))) = (&mut self.scan, previous) {
+            // The smallest key after the last read one
+            let mut start = last_key.to_vec();
+            start.push(0);
+            match scan.read(&start) {
+                Ok(entries) => {
+                    self.iter = entries.into_iter();
+                    self.current = self.iter.next();
+                }
+                Err(error) => self.error.set(Some(error)),
+            }
+        }
+        if self.current.is_none() || self.scan.as_ref().map_or(false, |scan| scan.done) {
+            self.scan = None;
+        }
     }
 
-    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
     pub fn status(&self) -> Result<(), StorageError> {
-        Ok(())
+        self.error.take().map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for Iter {
+    fn drop(&mut self) {
+        if let Some(scan) = &self.scan {
+            let stats = &scan.reader.1;
+            stats.stopped_scans.set(stats.stopped_scans.get() + 1);
+        }
+    }
+}
+
+/// The previous values of the keys written by the last versions
+struct History {
+    version_column_family: ColumnFamily,
+    version_key: &'static [u8],
+    retained_versions: u64,
+    /// The oldest version that can be read
+    oldest_version: u64,
+    /// For each column family and key, the value before each version that has written it, `None` if the key was absent
+    previous_values: HashMap<ColumnFamily, BTreeMap<Vec<u8>, BTreeMap<u64, Option<Vec<u8>>>>>,
+    /// The keys written by each version, used to drop the versions not retained anymore
+    written_keys: BTreeMap<u64, Vec<(ColumnFamily, Vec<u8>)>>,
+}
+
+impl History {
+    fn current_version(&self, trees: &Trees) -> Result<u64, StorageError> {
+        Ok(
+            if let Some(value) = trees
+                .get(&self.version_column_family)
+                .and_then(|tree| tree.get(self.version_key))
+            {
+                u64::from_be_bytes(value.as_slice().try_into().map_err(|_| {
+                    StorageError::Other("Invalid encoding of the history version".into())
+                })?)
+            } else {
+                0
+            },
+        )
+    }
+
+    /// Records the value of the key before its first write by `version`
+    fn record(
+        &mut self,
+        version: u64,
+        column_family: &ColumnFamily,
+        key: &[u8],
+        previous: Option<Vec<u8>>,
+    ) {
+        if let Entry::Vacant(entry) = self
+            .previous_values
+            .entry(column_family.clone())
+            .or_default()
+            .entry(key.into())
+            .or_default()
+            .entry(version)
+        {
+            entry.insert(previous);
+            self.written_keys
+                .entry(version)
+                .or_default()
+                .push((column_family.clone(), key.into()));
+        }
+    }
+
+    /// Drops the versions not retained anymore once `current_version` is the current version
+    fn prune(&mut self, current_version: u64) {
+        let oldest_version = current_version.saturating_sub(self.retained_versions);
+        let pruned = self
+            .written_keys
+            .range(..=oldest_version)
+            .map(|(version, _)| *version)
+            .collect::<Vec<_>>();
+        for version in pruned {
+            for (column_family, key) in self.written_keys.remove(&version).unwrap_or_default() {
+                if let Some(keys) = self.previous_values.get_mut(&column_family) {
+                    if let Some(versions) = keys.get_mut(&key) {
+                        versions.remove(&version);
+                        if versions.is_empty() {
+                            keys.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+        self.oldest_version = self.oldest_version.max(oldest_version);
+    }
+
+    /// The value of the key at `version`, `None` if the key has not been written since
+    fn value_at(
+        &self,
+        version: u64,
+        column_family: &ColumnFamily,
+        key: &[u8],
+    ) -> Option<Option<&Vec<u8>>> {
+        let (_, value) = self
+            .previous_values
+            .get(column_family)?
+            .get(key)?
+            .range(version + 1..)
+            .next()?;
+        Some(value.as_ref())
+    }
+
+    /// The keys of the column family greater or equal to `from` that have been written after `version`
+    /// with their value at `version`
+    fn changes_after<'a>(
+        &'a self,
+        version: u64,
+        column_family: &ColumnFamily,
+        from: &[u8],
+    ) -> impl Iterator<Item = (&'a Vec<u8>, Option<&'a Vec<u8>>)> + 'a {
+        self.previous_values
+            .get(column_family)
+            .into_iter()
+            .flat_map(move |keys| keys.range(from.to_vec()..))
+            .filter_map(move |(key, versions)| {
+                let (_, value) = versions.range(version + 1..).next()?;
+                Some((key, value.as_ref()))
+            })
+    }
+}
+
+/// A reader of the database at a past version, the current content being patched with the history
+#[derive(Clone)]
+struct VersionedReader {
+    trees: Arc<RwLock<Trees>>,
+    history: Arc<RwLock<Option<History>>>,
+    version: u64,
+}
+
+impl VersionedReader {
+    #[allow(clippy::unwrap_in_result)]
+    fn with_history<T>(&self, f: impl FnOnce(&Trees, &History) -> T) -> Result<T, StorageError> {
+        let trees = self.trees.read().unwrap();
+        match &*self.history.read().unwrap() {
+            Some(history) if history.oldest_version <= self.version => Ok(f(&trees, history)),
+            _ => Err(StorageError::VersionNotAvailable {
+                version: self.version,
+            }),
+        }
+    }
+
+    fn get(
+        &self,
+        column_family: &ColumnFamily,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        self.with_history(|trees, history| {
+            if let Some(value) = history.value_at(self.version, column_family, key) {
+                value.cloned()
+            } else {
+                trees.get(column_family).and_then(|tree| tree.get(key))
+            }
+        })
+    }
+
+    fn scan_prefix_from(
+        &self,
+        column_family: &ColumnFamily,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Arc<[u8]>, Arc<[u8]>)>, StorageError> {
+        self.with_history(|trees, history| {
+            let changes = history
+                .changes_after(self.version, column_family, start)
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .collect::<Vec<_>>();
+            // Each change removes at most one entry so we read enough current entries to still have `limit` of them
+            let mut entries = trees
+                .get(column_family)
+                .map_or_else(Vec::new, |tree| {
+                    tree.scan_prefix_from_shared(prefix, start, limit.saturating_add(changes.len()))
+                })
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            for (key, value) in changes {
+                if let Some(value) = value {
+                    entries.insert(key.as_slice().into(), value.as_slice().into());
+                } else {
+                    entries.remove(key.as_slice());
+                }
+            }
+            entries.into_iter().take(limit).collect()
+        })
+    }
+
+    fn scan_range(
+        &self,
+        column_family: &ColumnFamily,
+        start: &[u8],
+        end: Option<&[u8]>,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        self.with_history(|trees, history| {
+            let in_range = |key: &[u8]| end.map_or(true, |end| key < end);
+            let mut entries = trees
+                .get(column_family)
+                .map_or_else(Vec::new, |tree| tree.scan_range(start, end))
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            for (key, value) in history
+                .changes_after(self.version, column_family, start)
+                .take_while(|(key, _)| in_range(key.as_slice()))
+            {
+                if let Some(value) = value {
+                    entries.insert(key.clone(), value.clone());
+                } else {
+                    entries.remove(key);
+                }
+            }
+            entries.into_iter().collect()
+        })
+    }
+
+    fn keys_from(
+        &self,
+        column_family: &ColumnFamily,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<Vec<u8>>, StorageError> {
+        self.with_history(|trees, history| {
+            let changes = history
+                .changes_after(self.version, column_family, from)
+                .collect::<Vec<_>>();
+            // Each change removes at most one key so we read enough current keys to still have `limit` of them
+            let mut keys = trees
+                .get(column_family)
+                .map_or_else(Vec::new, |tree| tree.keys_from(from, limit + changes.len()))
+                .into_iter()
+                .collect::<BTreeSet<_>>();
+            for (key, value) in changes {
+                if value.is_some() {
+                    keys.insert(key.clone());
+                } else {
+                    keys.remove(key);
+                }
+            }
+            keys.into_iter().take(limit).collect()
+        })
+    }
+
+    fn len(&self, column_family: &ColumnFamily) -> Result<usize, StorageError> {
+        self.with_history(|trees, history| {
+            let tree = trees.get(column_family);
+            let mut len = tree.map_or(0, |tree| tree.len());
+            for (key, value) in history.changes_after(self.version, column_family, &[]) {
+                match (
+                    tree.map_or(false, |tree| tree.contains_key(key)),
+                    value.is_some(),
+                ) {
+                    (true, false) => len -= 1,
+                    (false, true) => len += 1,
+                    _ => (),
+                }
+            }
+            len
+        })
+    }
+}
+
+/// The content of a column family
+type Tree = Box<dyn KeyValueStore>;
+
+fn heap_tree() -> Tree {
+    Box::new(SharedMap::default())
+}
+
+fn shared_entries(entries: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<(Arc<[u8]>, Arc<[u8]>)> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect()
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn new_tree(stable_memory: Option<StableMemoryDefinition>) -> Result<Tree, StorageError> {
+    match stable_memory {
+        None => Ok(heap_tree()),
+        #[cfg(feature = "stable-memory")]
+        Some(definition) => Ok(Box::new(StableTree::open(definition))),
+        #[cfg(not(feature = "stable-memory"))]
+        Some(_) => Err(StorageError::Other(
+            "The stable-memory feature is required to store column families in stable memory"
+                .into(),
+        )),
     }
 }
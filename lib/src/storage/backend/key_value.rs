@@ -0,0 +1,427 @@
+//! The ordered key-value maps storing the column families.
+
+use crate::storage::StorageError;
+use std::collections::BTreeMap;
+use std::ops::{Bound, ControlFlow};
+use std::sync::Arc;
+
+/// An ordered key-value map storing the content of a column family.
+///
+/// The keys are ordered as byte strings.
+/// The transactions, the history and the write-ahead log are handled by the storage on top of it,
+/// so an implementation only has to apply the writes it receives in order.
+///
+/// The column families are kept on the heap by default, implement this trait to store them elsewhere
+/// (a stable structure, a trie, a test double...) and give it to
+/// [`StorageConfig::with_column_family_store`](crate::store::StorageConfig::with_column_family_store).
+///
+/// Usage example:
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::store::{KeyValueStore, StorageConfig, StorageError, Store};
+/// use std::collections::BTreeMap;
+/// use std::ops::ControlFlow;
+///
+/// /// A map counting its insertions
+/// #[derive(Default)]
+/// struct CountingStore {
+///     map: BTreeMap<Vec<u8>, Vec<u8>>,
+///     insertions: usize,
+/// }
+///
+/// impl KeyValueStore for CountingStore {
+///     fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+///         self.map.get(key).cloned()
+///     }
+///
+///     fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+///         self.map
+///             .range(prefix.to_vec()..)
+///             .take_while(|(k, _)| k.starts_with(prefix))
+///             .map(|(k, v)| (k.clone(), v.clone()))
+///             .collect()
+///     }
+///
+///     fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>> {
+///         self.map.range(from.to_vec()..).take(limit).map(|(k, _)| k.clone()).collect()
+///     }
+///
+///     fn len(&self) -> usize {
+///         self.map.len()
+///     }
+///
+///     fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>) {
+///         for (key, value) in &self.map {
+///             if f(key, value).is_break() {
+///                 return;
+///             }
+///         }
+///     }
+///
+///     fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+///         self.insertions += 1;
+///         self.map.insert(key.to_vec(), value.to_vec());
+///         Ok(())
+///     }
+///
+///     fn remove(&mut self, key: &[u8]) -> Result<(), StorageError> {
+///         self.map.remove(key);
+///         Ok(())
+///     }
+/// }
+///
+/// let config = StorageConfig::new().with_column_family_store("spog", || Box::new(CountingStore::default()));
+/// let store = Store::new_with_config(&config)?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+/// assert_eq!(store.len()?, 1);
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+pub trait KeyValueStore: Send + Sync {
+    /// Returns the value of `key`, if any.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Returns the value of `key` in a buffer that can be shared without copy, if any.
+    ///
+    /// By default the value returned by [`get`](Self::get) is copied into a new buffer,
+    /// the maps keeping their values in shared buffers should override it.
+    fn get_shared(&self, key: &[u8]) -> Option<Arc<[u8]>> {
+        self.get(key).map(Into::into)
+    }
+
+    /// Returns if `key` has a value.
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the entries whose key starts with `prefix`, in key order.
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)>;
+
+    /// Same as [`scan_prefix`](Self::scan_prefix) but with the keys and values in buffers that can be shared without copy.
+    ///
+    /// Like for [`get_shared`](Self::get_shared), the entries are copied by default.
+    fn scan_prefix_shared(&self, prefix: &[u8]) -> Vec<(Arc<[u8]>, Arc<[u8]>)> {
+        self.scan_prefix(prefix)
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect()
+    }
+
+    /// Returns at most `limit` entries whose key starts with `prefix` and is greater than or equal to `start`, in key order.
+    ///
+    /// It allows to read a prefix in batches and to stop before its end.
+    /// By default the keys are listed with [`keys_from`](Self::keys_from) and their values are read one by one.
+    fn scan_prefix_from(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.keys_from(start, limit)
+            .into_iter()
+            .take_while(|key| key.starts_with(prefix))
+            .filter_map(|key| {
+                let value = self.get(&key)?;
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    /// Same as [`scan_prefix_from`](Self::scan_prefix_from) but with the keys and values in buffers that can be shared without copy.
+    ///
+    /// Like for [`get_shared`](Self::get_shared), the entries are copied by default.
+    fn scan_prefix_from_shared(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Arc<[u8]>, Arc<[u8]>)> {
+        self.scan_prefix_from(prefix, start, limit)
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect()
+    }
+
+    /// Returns at most `limit` keys greater than or equal to `from`, in key order.
+    fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>>;
+
+    /// Returns the entries whose key is greater than or equal to `start` and lower than `end` if set, in key order.
+    fn scan_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.keys_from(start, usize::MAX)
+            .into_iter()
+            .take_while(|key| end.map_or(true, |end| key.as_slice() < end))
+            .filter_map(|key| {
+                let value = self.get(&key)?;
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    /// Returns the number of entries.
+    fn len(&self) -> usize;
+
+    /// Returns if there are no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `f` on each entry in key order until it returns [`ControlFlow::Break`].
+    fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>);
+
+    /// Sets the value of `key`.
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError>;
+
+    /// Sets the values of many keys, `entries` being sorted by key without duplicates.
+    fn insert_sorted(&mut self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        for (key, value) in entries {
+            self.insert(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the value of `key`, if any.
+    fn remove(&mut self, key: &[u8]) -> Result<(), StorageError>;
+
+    /// Replaces all the entries with `content`.
+    fn replace(&mut self, content: BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), StorageError> {
+        for key in self.keys_from(&[], usize::MAX) {
+            self.remove(&key)?;
+        }
+        self.insert_sorted(content.into_iter().collect())
+    }
+}
+
+/// The column families kept on the heap
+impl KeyValueStore for BTreeMap<Vec<u8>, Vec<u8>> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        BTreeMap::get(self, key).cloned()
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        BTreeMap::contains_key(self, key)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        if prefix.is_empty() {
+            self.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        } else {
+            self.range(prefix.to_vec()..)
+                .take_while(|(k, _)| k.starts_with(prefix))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        }
+    }
+
+    fn scan_prefix_from(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.range(start.to_vec()..)
+            .take(limit)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>> {
+        self.range(from.to_vec()..)
+            .take(limit)
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    fn scan_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.range(start.to_vec()..)
+            .take_while(|(k, _)| end.map_or(true, |end| k.as_slice() < end))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>) {
+        for (key, value) in self {
+            if f(key, value).is_break() {
+                return;
+            }
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        BTreeMap::insert(self, key.into(), value.into());
+        Ok(())
+    }
+
+    fn insert_sorted(&mut self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        // Merging two trees is linear in their total size, so it is only done if the entries are many
+        if entries.len() >= BTreeMap::len(self) / 8 {
+            self.append(&mut entries.into_iter().collect());
+        } else {
+            self.extend(entries);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        BTreeMap::remove(self, key);
+        Ok(())
+    }
+
+    fn replace(&mut self, content: BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), StorageError> {
+        *self = content;
+        Ok(())
+    }
+}
+
+/// The map keeping the column families on the heap, with its keys and values in shared buffers
+///
+/// The reads return the buffers without copying them.
+/// The empty values, like the ones of the quad indexes, all share the same buffer.
+pub struct SharedMap {
+    entries: BTreeMap<Arc<[u8]>, Arc<[u8]>>,
+    empty: Arc<[u8]>,
+}
+
+impl Default for SharedMap {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            empty: Arc::new([]),
+        }
+    }
+}
+
+impl SharedMap {
+    fn value(&self, value: &[u8]) -> Arc<[u8]> {
+        if value.is_empty() {
+            Arc::clone(&self.empty)
+        } else {
+            value.into()
+        }
+    }
+
+    fn range_from<'a>(
+        &'a self,
+        from: &[u8],
+    ) -> impl Iterator<Item = (&'a Arc<[u8]>, &'a Arc<[u8]>)> + 'a {
+        self.entries
+            .range::<[u8], _>((Bound::Included(from), Bound::Unbounded))
+    }
+}
+
+impl KeyValueStore for SharedMap {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.get(key).map(|value| value.to_vec())
+    }
+
+    fn get_shared(&self, key: &[u8]) -> Option<Arc<[u8]>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.range_from(prefix)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+
+    fn scan_prefix_shared(&self, prefix: &[u8]) -> Vec<(Arc<[u8]>, Arc<[u8]>)> {
+        self.range_from(prefix)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (Arc::clone(k), Arc::clone(v)))
+            .collect()
+    }
+
+    fn scan_prefix_from(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.range_from(start)
+            .take(limit)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+
+    fn scan_prefix_from_shared(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Arc<[u8]>, Arc<[u8]>)> {
+        self.range_from(start)
+            .take(limit)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (Arc::clone(k), Arc::clone(v)))
+            .collect()
+    }
+
+    fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>> {
+        self.range_from(from)
+            .take(limit)
+            .map(|(k, _)| k.to_vec())
+            .collect()
+    }
+
+    fn scan_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.range_from(start)
+            .take_while(|(k, _)| end.map_or(true, |end| &***k < end))
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>) {
+        for (key, value) in &self.entries {
+            if f(key, value).is_break() {
+                return;
+            }
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        let value = self.value(value);
+        self.entries.insert(key.into(), value);
+        Ok(())
+    }
+
+    fn insert_sorted(&mut self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.into(), self.value(&value)))
+            .collect::<Vec<_>>();
+        // Merging two trees is linear in their total size, so it is only done if the entries are many
+        if entries.len() >= self.entries.len() / 8 {
+            self.entries.append(&mut entries.into_iter().collect());
+        } else {
+            self.entries.extend(entries);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn replace(&mut self, content: BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), StorageError> {
+        self.entries = content
+            .into_iter()
+            .map(|(key, value)| (key.into(), self.value(&value)))
+            .collect();
+        Ok(())
+    }
+}
@@ -1,5 +1,14 @@
 //! A storage backend
 
-pub use fallback::{ColumnFamily, ColumnFamilyDefinition, Db, Iter, Reader, Transaction};
+pub use fallback::{
+    ColumnFamily, ColumnFamilyDefinition, Db, Iter, LoggedWrite, Reader, ScanStats,
+    StableMemoryDefinition, Transaction, WriteLog,
+};
+pub use key_value::KeyValueStore;
+pub use prefix_compressed::PrefixCompressedMap;
 
 mod fallback;
+mod key_value;
+mod prefix_compressed;
+#[cfg(feature = "stable-memory")]
+mod stable;
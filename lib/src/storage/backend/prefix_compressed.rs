@@ -0,0 +1,290 @@
+//! An ordered map storing its keys with their shared prefixes factorized.
+
+use crate::storage::backend::key_value::KeyValueStore;
+use crate::storage::StorageError;
+use std::collections::BTreeMap;
+use std::ops::{Bound, ControlFlow};
+
+/// The maximal number of entries of a block, a full block being split in two
+const MAX_BLOCK_LEN: usize = 32;
+
+/// An ordered map storing its entries in blocks of consecutive keys.
+///
+/// Inside of a block, each key is only stored as the length of the prefix it shares with the previous key
+/// followed by the rest of its bytes.
+/// The quad index keys starting with the same subject, predicate or graph name share long prefixes,
+/// so this divides the memory used by the keys at the cost of decoding a block on each access.
+#[derive(Default)]
+pub struct PrefixCompressedMap {
+    /// The blocks by their first key
+    blocks: BTreeMap<Vec<u8>, Block>,
+    len: usize,
+}
+
+/// Entries encoded one after the other as the varint length of the prefix shared with the previous key,
+/// the varint length of the rest of the key, the rest of the key, the varint length of the value and the value
+struct Block(Vec<u8>);
+
+impl Block {
+    fn encode(entries: &[(Vec<u8>, Vec<u8>)]) -> Self {
+        let mut buffer = Vec::new();
+        let mut previous: &[u8] = &[];
+        for (key, value) in entries {
+            let shared = previous.iter().zip(key).take_while(|(a, b)| a == b).count();
+            write_varint(&mut buffer, shared);
+            write_varint(&mut buffer, key.len() - shared);
+            buffer.extend_from_slice(&key[shared..]);
+            write_varint(&mut buffer, value.len());
+            buffer.extend_from_slice(value);
+            previous = key;
+        }
+        Self(buffer)
+    }
+
+    fn decode(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::<(Vec<u8>, Vec<u8>)>::new();
+        let mut buffer = self.0.as_slice();
+        while !buffer.is_empty() {
+            let shared = read_varint(&mut buffer);
+            let suffix_len = read_varint(&mut buffer);
+            let mut key = entries
+                .last()
+                .map_or_else(Vec::new, |(previous, _)| previous[..shared].to_vec());
+            key.extend_from_slice(&buffer[..suffix_len]);
+            buffer = &buffer[suffix_len..];
+            let value_len = read_varint(&mut buffer);
+            entries.push((key, buffer[..value_len].to_vec()));
+            buffer = &buffer[value_len..];
+        }
+        entries
+    }
+}
+
+impl PrefixCompressedMap {
+    /// Returns the first key of the block that contains `key` if it is present
+    fn block_key(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        self.blocks
+            .range::<[u8], _>((Bound::Unbounded, Bound::Included(key)))
+            .next_back()
+            .or_else(|| self.blocks.iter().next())
+            .map(|(first, _)| first)
+    }
+
+    /// Calls `f` on the entries in key order starting from the block that may contain `from`
+    fn scan_from(&self, from: &[u8], mut f: impl FnMut(Vec<u8>, Vec<u8>) -> ControlFlow<()>) {
+        let start = self
+            .blocks
+            .range::<[u8], _>((Bound::Unbounded, Bound::Included(from)))
+            .next_back()
+            .map_or_else(Vec::new, |(first, _)| first.clone());
+        for (_, block) in self.blocks.range(start..) {
+            for (key, value) in block.decode() {
+                if key.as_slice() >= from && f(key, value).is_break() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Replaces the block starting with `first` by blocks of half the maximal size storing `entries`
+    fn write_block(&mut self, first: Option<Vec<u8>>, entries: &[(Vec<u8>, Vec<u8>)]) {
+        if let Some(first) = first {
+            self.blocks.remove(&first);
+        }
+        for chunk in entries.chunks(MAX_BLOCK_LEN / 2) {
+            self.blocks.insert(chunk[0].0.clone(), Block::encode(chunk));
+        }
+    }
+
+    fn block_entries(&self, first: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.blocks.get(first).map_or_else(Vec::new, Block::decode)
+    }
+}
+
+impl KeyValueStore for PrefixCompressedMap {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let first = self.block_key(key)?;
+        self.block_entries(first)
+            .into_iter()
+            .find_map(|(k, v)| (k == key).then(|| v))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        self.scan_from(prefix, |key, value| {
+            if !key.starts_with(prefix) {
+                return ControlFlow::Break(());
+            }
+            entries.push((key, value));
+            ControlFlow::Continue(())
+        });
+        entries
+    }
+
+    fn scan_prefix_from(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        if limit > 0 {
+            self.scan_from(start, |key, value| {
+                if !key.starts_with(prefix) {
+                    return ControlFlow::Break(());
+                }
+                entries.push((key, value));
+                if entries.len() < limit {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+        }
+        entries
+    }
+
+    fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>> {
+        let mut keys = Vec::new();
+        if limit > 0 {
+            self.scan_from(from, |key, _| {
+                keys.push(key);
+                if keys.len() < limit {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+        }
+        keys
+    }
+
+    fn scan_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = Vec::new();
+        self.scan_from(start, |key, value| {
+            if end.map_or(false, |end| key.as_slice() >= end) {
+                return ControlFlow::Break(());
+            }
+            entries.push((key, value));
+            ControlFlow::Continue(())
+        });
+        entries
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>) {
+        self.scan_from(&[], |key, value| f(&key, &value));
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        let first = self.block_key(key).cloned();
+        let mut entries = first
+            .as_deref()
+            .map_or_else(Vec::new, |first| self.block_entries(first));
+        match entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+            Ok(position) => entries[position].1 = value.to_vec(),
+            Err(position) => {
+                entries.insert(position, (key.to_vec(), value.to_vec()));
+                self.len += 1;
+            }
+        }
+        match first {
+            Some(first) if entries.len() <= MAX_BLOCK_LEN && entries[0].0 == first => {
+                self.blocks.insert(first, Block::encode(&entries));
+            }
+            first => self.write_block(first, &entries),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        let first = if let Some(first) = self.block_key(key) {
+            first.clone()
+        } else {
+            return Ok(());
+        };
+        let mut entries = self.block_entries(&first);
+        if let Ok(position) = entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+            entries.remove(position);
+            self.len -= 1;
+            if entries.first().map_or(true, |(k, _)| *k != first) {
+                self.write_block(Some(first), &entries);
+            } else {
+                self.blocks.insert(first, Block::encode(&entries));
+            }
+        }
+        Ok(())
+    }
+
+    fn replace(&mut self, content: BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), StorageError> {
+        self.blocks.clear();
+        self.len = content.len();
+        self.write_block(None, &content.into_iter().collect::<Vec<_>>());
+        Ok(())
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_varint(buffer: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn read_varint(buffer: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((byte, rest)) = buffer.split_first() {
+        *buffer = rest;
+        value |= usize::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_content_as_btree_map() -> Result<(), StorageError> {
+        let mut map = PrefixCompressedMap::default();
+        let mut expected = BTreeMap::new();
+        for i in 0..500_u32 {
+            let key = format!("http://example.com/{}/{}", i % 7, i * 31 % 101).into_bytes();
+            if i % 5 == 0 {
+                map.remove(&key)?;
+                expected.remove(&key);
+            } else {
+                map.insert(&key, &i.to_be_bytes())?;
+                expected.insert(key, i.to_be_bytes().to_vec());
+            }
+        }
+        assert_eq!(map.len(), expected.len());
+        assert_eq!(
+            map.scan_prefix(&[]),
+            expected.clone().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            map.scan_prefix(b"http://example.com/3/"),
+            expected.scan_prefix(b"http://example.com/3/")
+        );
+        assert_eq!(
+            map.keys_from(b"http://example.com/4", 10),
+            expected.keys_from(b"http://example.com/4", 10)
+        );
+        for key in expected.keys() {
+            assert_eq!(map.get(key), expected.get(key).cloned());
+        }
+        assert_eq!(map.get(b"http://example.com/0/0"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! Column families stored in stable memory regions.
+//!
+//! The regions are allocated by a single [`MemoryManager`] per canister,
+//! so their content survives canister upgrades.
+
+use crate::storage::backend::fallback::StableMemoryDefinition;
+use crate::storage::backend::key_value::KeyValueStore;
+use crate::storage::StorageError;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory, StableBTreeMap};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::ControlFlow;
+
+type StableMap = StableBTreeMap<Vec<u8>, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>;
+
+thread_local! {
+    static MEMORY_MANAGER: MemoryManager<DefaultMemoryImpl> = MemoryManager::init(DefaultMemoryImpl::default());
+    static TREES: RefCell<HashMap<u8, StableMap>> = RefCell::new(HashMap::new());
+}
+
+/// A column family stored in a stable memory region.
+///
+/// The map itself lives in a thread local registry keyed by the memory id
+/// because the virtual memories are neither `Send` nor `Sync`.
+pub struct StableTree {
+    memory_id: u8,
+    max_pages: Option<u64>,
+}
+
+impl StableTree {
+    /// Opens the column family stored in the given region, keeping its current content.
+    pub fn open(definition: StableMemoryDefinition) -> Self {
+        TREES.with(|trees| {
+            trees
+                .borrow_mut()
+                .entry(definition.memory_id)
+                .or_insert_with(|| StableBTreeMap::init(memory(definition.memory_id)));
+        });
+        Self {
+            memory_id: definition.memory_id,
+            max_pages: definition.max_pages,
+        }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut StableMap) -> T) -> T {
+        TREES.with(|trees| f(trees.borrow_mut().get_mut(&self.memory_id).unwrap()))
+    }
+}
+
+impl KeyValueStore for StableTree {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.with(|tree| tree.get(&key.to_vec()))
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.with(|tree| tree.contains_key(&key.to_vec()))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.with(|tree| {
+            tree.range(prefix.to_vec()..)
+                .take_while(|(k, _)| k.starts_with(prefix))
+                .collect()
+        })
+    }
+
+    fn scan_prefix_from(
+        &self,
+        prefix: &[u8],
+        start: &[u8],
+        limit: usize,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.with(|tree| {
+            tree.range(start.to_vec()..)
+                .take(limit)
+                .take_while(|(k, _)| k.starts_with(prefix))
+                .collect()
+        })
+    }
+
+    fn keys_from(&self, from: &[u8], limit: usize) -> Vec<Vec<u8>> {
+        self.with(|tree| {
+            tree.range(from.to_vec()..)
+                .take(limit)
+                .map(|(k, _)| k)
+                .collect()
+        })
+    }
+
+    fn scan_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.with(|tree| {
+            tree.range(start.to_vec()..)
+                .take_while(|(k, _)| end.map_or(true, |end| k.as_slice() < end))
+                .collect()
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.with(|tree| usize::try_from(tree.len()).unwrap_or(usize::MAX))
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&[u8], &[u8]) -> ControlFlow<()>) {
+        self.with(|tree| {
+            for (key, value) in tree.iter() {
+                if f(&key, &value).is_break() {
+                    return;
+                }
+            }
+        });
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        if let Some(max_pages) = self.max_pages {
+            if memory(self.memory_id).size() >= max_pages {
+                return Err(StorageError::Other(
+                    format!(
+                        "The stable memory {} has reached its limit of {max_pages} pages",
+                        self.memory_id
+                    )
+                    .into(),
+                ));
+            }
+        }
+        self.with(|tree| tree.insert(key.to_vec(), value.to_vec()));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.with(|tree| tree.remove(&key.to_vec()));
+        Ok(())
+    }
+
+    fn replace(&mut self, content: BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), StorageError> {
+        self.with(|tree| {
+            let keys = tree.iter().map(|(k, _)| k).collect::<Vec<_>>();
+            for key in keys {
+                tree.remove(&key);
+            }
+        });
+        for (key, value) in content {
+            self.insert(&key, &value)?;
+        }
+        Ok(())
+    }
+}
+
+fn memory(memory_id: u8) -> VirtualMemory<DefaultMemoryImpl> {
+    MEMORY_MANAGER.with(|manager| manager.get(MemoryId::new(memory_id)))
+}
@@ -5,7 +5,8 @@ use oxsdatatypes::*;
 use std::io::{Cursor, Read};
 use std::mem::size_of;
 
-pub const WRITTEN_TERM_MAX_SIZE: usize = size_of::<u8>() + 2 * size_of::<StrHash>();
+pub const WRITTEN_TERM_MAX_SIZE: usize =
+    size_of::<u8>() + SmallString::MAX_LEN + size_of::<u8>() + size_of::<StrHash>();
 
 // Encoded term type blocks
 // 1-7: usual named nodes (except prefixes c.f. later)
@@ -19,14 +20,19 @@ const TYPE_NAMED_NODE_ID: u8 = 1;
 const TYPE_NUMERICAL_BLANK_NODE_ID: u8 = 8;
 const TYPE_SMALL_BLANK_NODE_ID: u8 = 9;
 const TYPE_BIG_BLANK_NODE_ID: u8 = 10;
+const TYPE_MEDIUM_BLANK_NODE_ID: u8 = 11;
 const TYPE_SMALL_STRING_LITERAL: u8 = 16;
 const TYPE_BIG_STRING_LITERAL: u8 = 17;
+const TYPE_MEDIUM_STRING_LITERAL: u8 = 18;
+const TYPE_MEDIUM_TYPED_LITERAL: u8 = 19;
 const TYPE_SMALL_SMALL_LANG_STRING_LITERAL: u8 = 20;
 const TYPE_SMALL_BIG_LANG_STRING_LITERAL: u8 = 21;
 const TYPE_BIG_SMALL_LANG_STRING_LITERAL: u8 = 22;
 const TYPE_BIG_BIG_LANG_STRING_LITERAL: u8 = 23;
 const TYPE_SMALL_TYPED_LITERAL: u8 = 24;
 const TYPE_BIG_TYPED_LITERAL: u8 = 25;
+const TYPE_MEDIUM_SMALL_LANG_STRING_LITERAL: u8 = 26;
+const TYPE_MEDIUM_BIG_LANG_STRING_LITERAL: u8 = 27;
 const TYPE_BOOLEAN_LITERAL_TRUE: u8 = 28;
 const TYPE_BOOLEAN_LITERAL_FALSE: u8 = 29;
 const TYPE_FLOAT_LITERAL: u8 = 30;
@@ -44,6 +50,9 @@ const TYPE_G_MONTH_LITERAL: u8 = 41;
 const TYPE_DURATION_LITERAL: u8 = 42;
 const TYPE_YEAR_MONTH_DURATION_LITERAL: u8 = 43;
 const TYPE_DAY_TIME_DURATION_LITERAL: u8 = 44;
+const TYPE_SMALL_MEDIUM_LANG_STRING_LITERAL: u8 = 45;
+const TYPE_MEDIUM_MEDIUM_LANG_STRING_LITERAL: u8 = 46;
+const TYPE_BIG_MEDIUM_LANG_STRING_LITERAL: u8 = 47;
 const TYPE_TRIPLE: u8 = 48;
 
 #[derive(Clone, Copy)]
@@ -74,12 +83,37 @@ impl QuadEncoding {
             Self::Dosp => cursor.read_dosp_quad(),
         }
     }
+
+    /// The positions of the key terms in the subject, predicate, object and graph name order
+    pub fn term_positions(self) -> &'static [usize] {
+        match self {
+            Self::Spog => &[0, 1, 2, 3],
+            Self::Posg => &[1, 2, 0, 3],
+            Self::Ospg => &[2, 0, 1, 3],
+            Self::Gspo => &[3, 0, 1, 2],
+            Self::Gpos => &[3, 1, 2, 0],
+            Self::Gosp => &[3, 2, 0, 1],
+            Self::Dspo => &[0, 1, 2],
+            Self::Dpos => &[1, 2, 0],
+            Self::Dosp => &[2, 0, 1],
+        }
+    }
 }
 
 pub fn decode_term(buffer: &[u8]) -> Result<EncodedTerm, StorageError> {
     Cursor::new(&buffer).read_term()
 }
 
+/// Decodes a sequence of terms like a key prefix
+pub fn decode_terms(buffer: &[u8]) -> Result<Vec<EncodedTerm>, StorageError> {
+    let mut cursor = Cursor::new(&buffer);
+    let mut terms = Vec::new();
+    while usize::try_from(cursor.position()).map_or(false, |position| position < buffer.len()) {
+        terms.push(cursor.read_term()?);
+    }
+    Ok(terms)
+}
+
 pub trait TermReader {
     fn read_term(&mut self) -> Result<EncodedTerm, StorageError>;
 
@@ -217,12 +251,11 @@ impl<R: Read> TermReader for R {
                     id: u128::from_be_bytes(buffer),
                 })
             }
-            TYPE_SMALL_BLANK_NODE_ID => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::SmallBlankNode(
-                    SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
-                ))
+            TYPE_SMALL_BLANK_NODE_ID | TYPE_MEDIUM_BLANK_NODE_ID => {
+                Ok(EncodedTerm::SmallBlankNode(read_small_string(
+                    self,
+                    type_buffer[0] == TYPE_MEDIUM_BLANK_NODE_ID,
+                )?))
             }
             TYPE_BIG_BLANK_NODE_ID => {
                 let mut buffer = [0; 16];
@@ -231,38 +264,47 @@ impl<R: Read> TermReader for R {
                     id_id: StrHash::from_be_bytes(buffer),
                 })
             }
-            TYPE_SMALL_SMALL_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::SmallSmallLangStringLiteral {
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
-                    language: SmallString::from_be_bytes(language_buffer)
-                        .map_err(CorruptionError::new)?,
-                })
+            TYPE_SMALL_SMALL_LANG_STRING_LITERAL
+            | TYPE_SMALL_MEDIUM_LANG_STRING_LITERAL
+            | TYPE_MEDIUM_SMALL_LANG_STRING_LITERAL
+            | TYPE_MEDIUM_MEDIUM_LANG_STRING_LITERAL => {
+                let language = read_small_string(
+                    self,
+                    matches!(
+                        type_buffer[0],
+                        TYPE_SMALL_MEDIUM_LANG_STRING_LITERAL
+                            | TYPE_MEDIUM_MEDIUM_LANG_STRING_LITERAL
+                    ),
+                )?;
+                let value = read_small_string(
+                    self,
+                    matches!(
+                        type_buffer[0],
+                        TYPE_MEDIUM_SMALL_LANG_STRING_LITERAL
+                            | TYPE_MEDIUM_MEDIUM_LANG_STRING_LITERAL
+                    ),
+                )?;
+                Ok(EncodedTerm::SmallSmallLangStringLiteral { value, language })
             }
-            TYPE_SMALL_BIG_LANG_STRING_LITERAL => {
+            TYPE_SMALL_BIG_LANG_STRING_LITERAL | TYPE_MEDIUM_BIG_LANG_STRING_LITERAL => {
                 let mut language_buffer = [0; 16];
                 self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::SmallBigLangStringLiteral {
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
+                    value: read_small_string(
+                        self,
+                        type_buffer[0] == TYPE_MEDIUM_BIG_LANG_STRING_LITERAL,
+                    )?,
                     language_id: StrHash::from_be_bytes(language_buffer),
                 })
             }
-            TYPE_BIG_SMALL_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
+            TYPE_BIG_SMALL_LANG_STRING_LITERAL | TYPE_BIG_MEDIUM_LANG_STRING_LITERAL => {
+                let language =
+                    read_small_string(self, type_buffer[0] == TYPE_BIG_MEDIUM_LANG_STRING_LITERAL)?;
                 let mut value_buffer = [0; 16];
                 self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::BigSmallLangStringLiteral {
                     value_id: StrHash::from_be_bytes(value_buffer),
-                    language: SmallString::from_be_bytes(language_buffer)
-                        .map_err(CorruptionError::new)?,
+                    language,
                 })
             }
             TYPE_BIG_BIG_LANG_STRING_LITERAL => {
@@ -275,15 +317,12 @@ impl<R: Read> TermReader for R {
                     language_id: StrHash::from_be_bytes(language_buffer),
                 })
             }
-            TYPE_SMALL_TYPED_LITERAL => {
+            TYPE_SMALL_TYPED_LITERAL | TYPE_MEDIUM_TYPED_LITERAL => {
                 let mut datatype_buffer = [0; 16];
                 self.read_exact(&mut datatype_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
                 Ok(EncodedTerm::SmallTypedLiteral {
                     datatype_id: StrHash::from_be_bytes(datatype_buffer),
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
+                    value: read_small_string(self, type_buffer[0] == TYPE_MEDIUM_TYPED_LITERAL)?,
                 })
             }
             TYPE_BIG_TYPED_LITERAL => {
@@ -296,12 +335,11 @@ impl<R: Read> TermReader for R {
                     value_id: StrHash::from_be_bytes(value_buffer),
                 })
             }
-            TYPE_SMALL_STRING_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::SmallStringLiteral(
-                    SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
-                ))
+            TYPE_SMALL_STRING_LITERAL | TYPE_MEDIUM_STRING_LITERAL => {
+                Ok(EncodedTerm::SmallStringLiteral(read_small_string(
+                    self,
+                    type_buffer[0] == TYPE_MEDIUM_STRING_LITERAL,
+                )?))
             }
             TYPE_BIG_STRING_LITERAL => {
                 let mut buffer = [0; 16];
@@ -493,6 +531,28 @@ pub fn encode_term_quad(
     vec
 }
 
+/// Reads a small string written in 33 bytes if `long`, in 16 bytes otherwise
+fn read_small_string(reader: &mut impl Read, long: bool) -> Result<SmallString, StorageError> {
+    Ok(if long {
+        let mut buffer = [0; 33];
+        reader.read_exact(&mut buffer)?;
+        SmallString::from_long_be_bytes(buffer)
+    } else {
+        let mut buffer = [0; 16];
+        reader.read_exact(&mut buffer)?;
+        SmallString::from_be_bytes(buffer)
+    }
+    .map_err(CorruptionError::new)?)
+}
+
+fn write_small_string(sink: &mut Vec<u8>, value: &SmallString) {
+    if value.is_short() {
+        sink.extend_from_slice(&value.to_be_bytes());
+    } else {
+        sink.extend_from_slice(&value.to_long_be_bytes());
+    }
+}
+
 pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
     match term {
         EncodedTerm::DefaultGraph => (),
@@ -505,34 +565,55 @@ pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
             sink.extend_from_slice(&id.to_be_bytes())
         }
         EncodedTerm::SmallBlankNode(id) => {
-            sink.push(TYPE_SMALL_BLANK_NODE_ID);
-            sink.extend_from_slice(&id.to_be_bytes())
+            sink.push(if id.is_short() {
+                TYPE_SMALL_BLANK_NODE_ID
+            } else {
+                TYPE_MEDIUM_BLANK_NODE_ID
+            });
+            write_small_string(sink, id);
         }
         EncodedTerm::BigBlankNode { id_id } => {
             sink.push(TYPE_BIG_BLANK_NODE_ID);
             sink.extend_from_slice(&id_id.to_be_bytes());
         }
         EncodedTerm::SmallStringLiteral(value) => {
-            sink.push(TYPE_SMALL_STRING_LITERAL);
-            sink.extend_from_slice(&value.to_be_bytes())
+            sink.push(if value.is_short() {
+                TYPE_SMALL_STRING_LITERAL
+            } else {
+                TYPE_MEDIUM_STRING_LITERAL
+            });
+            write_small_string(sink, value);
         }
         EncodedTerm::BigStringLiteral { value_id } => {
             sink.push(TYPE_BIG_STRING_LITERAL);
             sink.extend_from_slice(&value_id.to_be_bytes());
         }
         EncodedTerm::SmallSmallLangStringLiteral { value, language } => {
-            sink.push(TYPE_SMALL_SMALL_LANG_STRING_LITERAL);
-            sink.extend_from_slice(&language.to_be_bytes());
-            sink.extend_from_slice(&value.to_be_bytes());
+            sink.push(match (value.is_short(), language.is_short()) {
+                (true, true) => TYPE_SMALL_SMALL_LANG_STRING_LITERAL,
+                (true, false) => TYPE_SMALL_MEDIUM_LANG_STRING_LITERAL,
+                (false, true) => TYPE_MEDIUM_SMALL_LANG_STRING_LITERAL,
+                (false, false) => TYPE_MEDIUM_MEDIUM_LANG_STRING_LITERAL,
+            });
+            write_small_string(sink, language);
+            write_small_string(sink, value);
         }
         EncodedTerm::SmallBigLangStringLiteral { value, language_id } => {
-            sink.push(TYPE_SMALL_BIG_LANG_STRING_LITERAL);
+            sink.push(if value.is_short() {
+                TYPE_SMALL_BIG_LANG_STRING_LITERAL
+            } else {
+                TYPE_MEDIUM_BIG_LANG_STRING_LITERAL
+            });
             sink.extend_from_slice(&language_id.to_be_bytes());
-            sink.extend_from_slice(&value.to_be_bytes());
+            write_small_string(sink, value);
         }
         EncodedTerm::BigSmallLangStringLiteral { value_id, language } => {
-            sink.push(TYPE_BIG_SMALL_LANG_STRING_LITERAL);
-            sink.extend_from_slice(&language.to_be_bytes());
+            sink.push(if language.is_short() {
+                TYPE_BIG_SMALL_LANG_STRING_LITERAL
+            } else {
+                TYPE_BIG_MEDIUM_LANG_STRING_LITERAL
+            });
+            write_small_string(sink, language);
             sink.extend_from_slice(&value_id.to_be_bytes());
         }
         EncodedTerm::BigBigLangStringLiteral {
@@ -544,9 +625,13 @@ pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
             sink.extend_from_slice(&value_id.to_be_bytes());
         }
         EncodedTerm::SmallTypedLiteral { value, datatype_id } => {
-            sink.push(TYPE_SMALL_TYPED_LITERAL);
+            sink.push(if value.is_short() {
+                TYPE_SMALL_TYPED_LITERAL
+            } else {
+                TYPE_MEDIUM_TYPED_LITERAL
+            });
             sink.extend_from_slice(&datatype_id.to_be_bytes());
-            sink.extend_from_slice(&value.to_be_bytes());
+            write_small_string(sink, value);
         }
         EncodedTerm::BigTypedLiteral {
             value_id,
@@ -705,6 +790,30 @@ mod tests {
                 "fr-FR-Latn-x-foo-bar-baz-bat-aaaa-bbbb-cccc",
             )
             .into(),
+            BlankNode::new_unchecked("foo-bnode-mediumsized").into(),
+            Literal::new_simple_literal("foo-medium-sized-literal").into(),
+            Literal::new_language_tagged_literal_unchecked("foo-medium-sized-literal", "fr").into(),
+            Literal::new_language_tagged_literal_unchecked("foo", "fr-FR-x-medium-size").into(),
+            Literal::new_language_tagged_literal_unchecked(
+                "foo-medium-sized-literal",
+                "fr-FR-x-medium-size",
+            )
+            .into(),
+            Literal::new_language_tagged_literal_unchecked(
+                "foo-big-literal-thisisaverylargelanguagetaggedstringliteral",
+                "fr-FR-x-medium-size",
+            )
+            .into(),
+            Literal::new_language_tagged_literal_unchecked(
+                "foo-medium-sized-literal",
+                "fr-FR-Latn-x-foo-bar-baz-bat-aaaa-bbbb-cccc",
+            )
+            .into(),
+            Literal::new_typed_literal(
+                "-foo-medium-typed-literal",
+                NamedNode::new_unchecked("http://foo.com"),
+            )
+            .into(),
             Literal::new_typed_literal("-1.32", xsd::DECIMAL).into(),
             Literal::new_typed_literal("2020-01-01T01:01:01Z", xsd::DATE_TIME).into(),
             Literal::new_typed_literal("2020-01-01", xsd::DATE).into(),
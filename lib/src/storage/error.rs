@@ -2,6 +2,7 @@ use crate::io::read::ParseError;
 use std::error::Error;
 use std::fmt;
 use std::io;
+use std::time::Duration;
 
 /// An error related to storage operations (reads, writes...).
 #[derive(Debug)]
@@ -11,6 +12,24 @@ pub enum StorageError {
     Io(io::Error),
     /// Error related to data corruption.
     Corruption(CorruptionError),
+    /// The store is in read-only mode and does not accept writes.
+    ReadOnly,
+    /// The store is in maintenance mode and rejects writes.
+    ///
+    /// The write should be retried after `retry_after`.
+    UnderMaintenance { retry_after: Duration },
+    /// The written graph is locked by a long running update.
+    GraphLocked,
+    /// The caller is not allowed to write into the graph by the [access control list](crate::acl).
+    AccessDenied,
+    /// The requested past dataset version is not kept in the history.
+    VersionNotAvailable { version: u64 },
+    /// The write would make the store exceed one of its [quotas](crate::store::StorageConfig::with_max_quads).
+    QuotaExceeded { quota: Quota, limit: u64 },
+    /// Other writes have been committed since the expected [commit version](crate::store::Store::commit_version).
+    Conflict { expected: u64, actual: u64 },
+    /// An inserted term is rejected by the [term validation](crate::store::StorageConfig::with_term_validation).
+    InvalidTerm { term: String, reason: String },
     #[doc(hidden)]
     Other(Box<dyn Error + Send + Sync + 'static>),
 }
@@ -21,6 +40,26 @@ impl fmt::Display for StorageError {
         match self {
             Self::Io(e) => e.fmt(f),
             Self::Corruption(e) => e.fmt(f),
+            Self::ReadOnly => write!(f, "The store is in read-only mode"),
+            Self::UnderMaintenance { retry_after } => write!(
+                f,
+                "The store is in maintenance mode, retry in {}s",
+                retry_after.as_secs_f64()
+            ),
+            Self::GraphLocked => write!(f, "The graph is locked by a running update"),
+            Self::AccessDenied => write!(f, "The write into the graph is not allowed"),
+            Self::VersionNotAvailable { version } => write!(
+                f,
+                "The dataset version {version} is not available in the history"
+            ),
+            Self::QuotaExceeded { quota, limit } => {
+                write!(f, "The store quota of {limit} {quota} is exceeded")
+            }
+            Self::Conflict { expected, actual } => write!(
+                f,
+                "The store is at the commit version {actual} instead of the expected {expected}"
+            ),
+            Self::InvalidTerm { term, reason } => write!(f, "The term {term} is invalid: {reason}"),
             Self::Other(e) => e.fmt(f),
         }
     }
@@ -32,6 +71,14 @@ impl Error for StorageError {
         match self {
             Self::Io(e) => Some(e),
             Self::Corruption(e) => Some(e),
+            Self::ReadOnly
+            | Self::UnderMaintenance { .. }
+            | Self::GraphLocked
+            | Self::AccessDenied
+            | Self::VersionNotAvailable { .. }
+            | Self::QuotaExceeded { .. }
+            | Self::Conflict { .. }
+            | Self::InvalidTerm { .. } => None,
             Self::Other(e) => Some(e.as_ref()),
         }
     }
@@ -50,11 +97,50 @@ impl From<StorageError> for io::Error {
         match error {
             StorageError::Io(error) => error,
             StorageError::Corruption(error) => error.into(),
+            StorageError::ReadOnly | StorageError::AccessDenied => {
+                Self::new(io::ErrorKind::PermissionDenied, error.to_string())
+            }
+            StorageError::UnderMaintenance { .. } | StorageError::GraphLocked => {
+                Self::new(io::ErrorKind::WouldBlock, error.to_string())
+            }
+            StorageError::VersionNotAvailable { .. } => {
+                Self::new(io::ErrorKind::NotFound, error.to_string())
+            }
+            StorageError::QuotaExceeded { .. } => {
+                Self::new(io::ErrorKind::Other, error.to_string())
+            }
+            StorageError::Conflict { .. } => Self::new(io::ErrorKind::Other, error.to_string()),
+            StorageError::InvalidTerm { .. } => {
+                Self::new(io::ErrorKind::InvalidInput, error.to_string())
+            }
             StorageError::Other(error) => Self::new(io::ErrorKind::Other, error),
         }
     }
 }
 
+/// A limit on the size of a store, see [`StorageError::QuotaExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Quota {
+    /// The number of quads in the store.
+    Quads,
+    /// The number of bytes of the strings in the dictionary.
+    DictionaryBytes,
+    /// The number of quads in a named graph.
+    GraphQuads,
+}
+
+impl fmt::Display for Quota {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Quads => "quads",
+            Self::DictionaryBytes => "dictionary bytes",
+            Self::GraphQuads => "quads per named graph",
+        })
+    }
+}
+
 /// An error return if some content in the database is corrupted.
 #[derive(Debug)]
 pub struct CorruptionError {
@@ -119,6 +205,72 @@ impl From<CorruptionError> for io::Error {
     }
 }
 
+/// The inconsistencies between the column families found by [`Store::check_consistency`](crate::store::Store::check_consistency).
+///
+/// It is also the source of the [`CorruptionError`] returned by [`Store::validate`](crate::store::Store::validate).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CorruptionReport {
+    quads_checked: u64,
+    problem_count: u64,
+    problems: Vec<String>,
+}
+
+impl CorruptionReport {
+    /// Maximal number of problems described in a report, the next ones are only counted.
+    const MAX_DESCRIBED_PROBLEMS: usize = 100;
+
+    pub(crate) fn add(&mut self, problem: impl FnOnce() -> String) {
+        self.problem_count += 1;
+        if self.problems.len() < Self::MAX_DESCRIBED_PROBLEMS {
+            self.problems.push(problem());
+        }
+    }
+
+    pub(crate) fn set_quads_checked(&mut self, quads_checked: u64) {
+        self.quads_checked = quads_checked;
+    }
+
+    /// Returns `true` if no inconsistency has been found.
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        self.problem_count == 0
+    }
+
+    /// The number of quads of the primary indexes that have been checked.
+    #[inline]
+    pub fn quads_checked(&self) -> u64 {
+        self.quads_checked
+    }
+
+    /// The number of inconsistencies found.
+    #[inline]
+    pub fn problem_count(&self) -> u64 {
+        self.problem_count
+    }
+
+    /// The descriptions of the first 100 inconsistencies found.
+    #[inline]
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+}
+
+impl fmt::Display for CorruptionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inconsistencies found while checking {} quads",
+            self.problem_count, self.quads_checked
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CorruptionReport {}
+
 /// An error raised while loading a file into a [`Store`](crate::store::Store).
 #[derive(Debug)]
 pub enum LoaderError {
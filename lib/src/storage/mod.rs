@@ -1,23 +1,53 @@
 #![allow(clippy::same_name_method)]
-use crate::model::{GraphNameRef, NamedOrBlankNodeRef, QuadRef, TermRef};
-use crate::storage::backend::{Reader, Transaction};
+use crate::model::vocab::{rdf, xsd};
+use crate::model::{
+    GraphNameRef, LiteralRef, NamedOrBlankNode, NamedOrBlankNodeRef, Quad, QuadRef, TermRef,
+};
+use crate::storage::backend::{LoggedWrite, Reader, Transaction, WriteLog};
 use crate::storage::binary_encoder::{
-    decode_term, encode_term, encode_term_pair, encode_term_quad, encode_term_triple,
+    decode_term, decode_terms, encode_term, encode_term_pair, encode_term_quad, encode_term_triple,
     write_gosp_quad, write_gpos_quad, write_gspo_quad, write_osp_quad, write_ospg_quad,
     write_pos_quad, write_posg_quad, write_spo_quad, write_spog_quad, write_term, QuadEncoding,
     WRITTEN_TERM_MAX_SIZE,
 };
-pub use crate::storage::error::{CorruptionError, LoaderError, SerializerError, StorageError};
-use crate::storage::numeric_encoder::{insert_term, EncodedQuad, EncodedTerm, StrHash, StrLookup};
-use backend::{ColumnFamily, ColumnFamilyDefinition, Db, Iter};
+pub use crate::storage::error::{
+    CorruptionError, CorruptionReport, LoaderError, Quota, SerializerError, StorageError,
+};
+use crate::storage::numeric_encoder::{
+    insert_term, Decoder, EncodedQuad, EncodedTerm, StrHash, StrLookup,
+};
+#[cfg(feature = "stable-memory")]
+use backend::StableMemoryDefinition;
+use backend::{ColumnFamily, ColumnFamilyDefinition, Db, Iter, PrefixCompressedMap};
+pub use backend::{KeyValueStore, ScanStats};
+use ic_cdk::api::time;
+use lz4_flex::block::{compress_prepend_size, decompress_size_prepended};
+use oxsdatatypes::{Decimal, Double, Float};
+use siphasher::sip128::{Hasher128, SipHasher24};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{empty, BufRead, BufReader, BufWriter, Read, Write};
+use std::iter::Peekable;
+use std::ops::Bound;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
 
 mod backend;
 mod binary_encoder;
 mod error;
 pub mod numeric_encoder;
 pub mod small_string;
+mod validation;
+
+use validation::canonicalize_quad;
+pub use validation::TermValidation;
 
+const DEFAULT_CF: &str = "default";
 const ID2STR_CF: &str = "id2str";
 const SPOG_CF: &str = "spog";
 const POSG_CF: &str = "posg";
@@ -29,11 +59,604 @@ const DSPO_CF: &str = "dspo";
 const DPOS_CF: &str = "dpos";
 const DOSP_CF: &str = "dosp";
 const GRAPHS_CF: &str = "graphs";
+const META_CF: &str = "meta";
+const STATS_CF: &str = "stats";
+const LITERALS_CF: &str = "literals";
+const RANGES_CF: &str = "ranges";
+const EXPIRATIONS_CF: &str = "expirations";
+const CHANGES_CF: &str = "changes";
+const READ_ONLY_KEY: &[u8] = b"read_only";
+const GRAPH_DIGEST_PREFIX: &[u8] = b"graph_digest/";
+const DATASET_VERSION_KEY: &[u8] = b"dataset_version";
+/// The key of the number of transactions that have written into the store
+const COMMIT_VERSION_KEY: &[u8] = b"commit_version";
+const DATASET_DIGEST_KEY: &[u8] = b"dataset_digest";
+/// The key of the total number of bytes of the dictionary strings
+const DICTIONARY_SIZE_KEY: &[u8] = b"dictionary_size";
+/// The key of the sequence number of the next entry of the change log
+const NEXT_CHANGE_SEQUENCE_KEY: &[u8] = b"next_change_sequence";
+/// The first byte of the LZ4 compressed dictionary strings, it is never the first byte of an UTF-8 string
+const COMPRESSED_STR_MARKER: u8 = 0xFF;
+/// Approximate memory used by the dictionary and the index entries of an inserted quad
+const RESERVED_BYTES_PER_QUAD: usize = 512;
+/// Header of the binary snapshots, followed by the format version
+const SNAPSHOT_MAGIC: &[u8] = b"oxigraph-snapshot";
+const SNAPSHOT_VERSION: u8 = 1;
+const STORAGE_VERSION_KEY: &[u8] = b"storage_version";
+/// The names of the disabled secondary indexes, separated by commas
+const DISABLED_INDEXES_KEY: &[u8] = b"disabled_indexes";
+/// The kind of the values of the range index, written after the predicate in the keys
+const NUMERIC_RANGE_KIND: u8 = 0;
+const DATE_TIME_RANGE_KIND: u8 = 1;
+/// The range index keys of the `xsd:dateTime` without timezone are compared with the others with a margin of 14 hours
+const DATE_TIME_WITHOUT_TIMEZONE_MARGIN: f64 = 50_400.;
+/// Number of keys read at once during a scan of the range index or of the expirations
+const RANGE_SCAN_BATCH_SIZE: usize = 1000;
+/// The first byte of the keys of the expirations column family
+///
+/// The expiration time of a quad is stored after the quad key
+/// and the quad is also stored after its expiration time to find the expired quads in time order.
+const EXPIRATION_BY_QUAD_PREFIX: u8 = 0;
+const EXPIRATION_BY_TIME_PREFIX: u8 = 1;
+/// The first byte of the keys of the statistics column family, followed by the encoded terms
+const PREDICATE_COUNT_PREFIX: u8 = 0;
+const GRAPH_COUNT_PREFIX: u8 = 1;
+const OBJECT_CLASS_COUNT_PREFIX: u8 = 2;
+/// The key of the total number of quads, without any term
+const QUAD_COUNT_PREFIX: u8 = 3;
+/// The first byte of the keys of the literal index, followed by the datatype or the language and by the literal
+const DATATYPE_ENTRY_PREFIX: u8 = 0;
+const LANGUAGE_ENTRY_PREFIX: u8 = 1;
+/// The kind of a change log entry, written in its key after its big endian sequence number and followed by the quad or the graph name
+const INSERTED_QUAD_CHANGE: u8 = 0;
+const REMOVED_QUAD_CHANGE: u8 = 1;
+const CREATED_GRAPH_CHANGE: u8 = 2;
+const DROPPED_GRAPH_CHANGE: u8 = 3;
+/// The migrations of the stored data, the i-th one going from the storage version `i + 1` to `i + 2`
+///
+/// A migration must be added each time the layout of the keys or the values changes
+const MIGRATIONS: &[fn(&Storage) -> Result<(), StorageError>] = &[
+    compute_dataset_digest,
+    compute_statistics,
+    compute_quad_count,
+    compute_dictionary_size,
+    inline_medium_strings,
+];
+/// The version of the layout written by this version of the library
+const LATEST_STORAGE_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+
+/// A callback notified after each commit of the quads inserted (`true`) or removed (`false`) by the commit
+pub type ChangeObserver = Arc<dyn Fn(&StorageReader, &[(EncodedQuad, bool)]) + Send + Sync>;
+
+/// A callback called at the end of each write transaction, before its commit, with the quads inserted (`true`) or removed (`false`) by it
+///
+/// The writes done by the hook are part of the transaction but are not given to the hook again.
+pub type CommitHook = Arc<
+    dyn Fn(StorageWriter<'_>, &[(EncodedQuad, bool)]) -> Result<(), StorageError> + Send + Sync,
+>;
+
+/// A filter hiding quads from the reads, see [`Storage::set_read_filter`]
+#[derive(Clone)]
+pub struct EncodedReadFilter {
+    /// The subject, predicate, object and graph name the quads to check must be equal to if set
+    patterns: Vec<[Option<EncodedTerm>; 4]>,
+    /// Returns `true` if the quad matching one of the patterns must be hidden
+    hide: Arc<dyn Fn(&StorageReader, &EncodedQuad) -> bool + Send + Sync>,
+}
+
+impl EncodedReadFilter {
+    pub fn new(
+        patterns: Vec<[Option<EncodedTerm>; 4]>,
+        hide: Arc<dyn Fn(&StorageReader, &EncodedQuad) -> bool + Send + Sync>,
+    ) -> Self {
+        Self { patterns, hide }
+    }
+}
+
+/// A secondary index of the quads that could be disabled with [`StorageConfig::with_indexes`].
+///
+/// The SPOG index is always kept, it is the one the others are rebuilt from.
+/// The quad patterns that would use a disabled index are evaluated with a scan of SPOG filtered afterwards.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub enum QuadIndex {
+    /// Lookups by subject, predicate and object, always built
+    Spog,
+    /// Lookups by predicate and object
+    Posg,
+    /// Lookups by object and subject
+    Ospg,
+    /// Lookups by graph name, subject and predicate
+    Gspo,
+    /// Lookups by graph name, predicate and object
+    Gpos,
+    /// Lookups by graph name, object and subject
+    Gosp,
+}
+
+/// The kinds of objects the statistics count the quads of each predicate by.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+#[repr(u8)]
+pub enum ObjectClass {
+    /// An IRI
+    NamedNode = 0,
+    /// A blank node
+    BlankNode = 1,
+    /// A literal of any datatype
+    Literal = 2,
+    /// A quoted triple
+    Triple = 3,
+}
+
+impl ObjectClass {
+    pub(crate) fn of(term: &EncodedTerm) -> Self {
+        if term.is_named_node() {
+            Self::NamedNode
+        } else if term.is_blank_node() {
+            Self::BlankNode
+        } else if term.is_triple() {
+            Self::Triple
+        } else {
+            Self::Literal
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, StorageError> {
+        Ok(match byte {
+            0 => Self::NamedNode,
+            1 => Self::BlankNode,
+            2 => Self::Literal,
+            3 => Self::Triple,
+            _ => return Err(CorruptionError::msg(format!("Invalid object class {byte}")).into()),
+        })
+    }
+}
+
+/// The counts of the statistics column family returned by [`StorageReader::statistics`]
+#[derive(Default)]
+pub struct EncodedStatistics {
+    pub quads: u64,
+    pub predicates: Vec<(EncodedTerm, u64)>,
+    pub graphs: Vec<(EncodedTerm, u64)>,
+    pub object_classes: Vec<(EncodedTerm, ObjectClass, u64)>,
+}
+
+/// Configures the column families of a [`Storage`].
+///
+/// By default all the indexes are built and all column families are kept on the heap.
+#[derive(Default, Clone, Debug)]
+pub struct StorageConfig {
+    #[cfg(feature = "stable-memory")]
+    stable_column_families: Vec<(String, StableMemoryDefinition)>,
+    column_family_stores: Vec<(String, StoreFactory)>,
+    indexes: Option<Vec<QuadIndex>>,
+    literal_index: bool,
+    range_index: bool,
+    retained_versions: Option<u64>,
+    quotas: Quotas,
+    compression_threshold: Option<usize>,
+    prefix_compression: bool,
+    change_log: bool,
+    term_validation: Option<TermValidation>,
+    literal_canonicalization: bool,
+    clock: Option<Clock>,
+}
+
+/// Builds the map storing a column family when the storage is opened
+#[derive(Clone)]
+struct StoreFactory(Arc<dyn Fn() -> Box<dyn KeyValueStore> + Send + Sync>);
+
+impl fmt::Debug for StoreFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreFactory").finish_non_exhaustive()
+    }
+}
+
+/// Returns the current time in nanoseconds since the epoch, the canister time by default
+#[derive(Clone)]
+struct Clock(Arc<dyn Fn() -> u64 + Send + Sync>);
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
+}
+
+/// The limits on the size of the stored data checked by each insertion
+#[derive(Default, Clone, Copy, Debug)]
+struct Quotas {
+    max_quads: Option<u64>,
+    max_dictionary_bytes: Option<u64>,
+    max_graph_quads: Option<u64>,
+}
+
+impl StorageConfig {
+    /// Builds a configuration keeping all column families on the heap and building all indexes.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the column family `name` (`id2str`, `spog`, `posg`, `ospg`, `gspo`, `gpos`, `gosp`, `dspo`, `dpos`, `dosp`, `graphs`, `meta`, `stats`, `literals`, `ranges`, `expirations` or `changes`)
+    /// in the stable memory region `memory_id`.
+    ///
+    /// The `meta` column family records the version of the storage layout,
+    /// so it should be kept in stable memory along with the other ones to migrate them after an upgrade.
+    ///
+    /// The region is allowed to grow up to `max_pages` 64KiB pages if set.
+    ///
+    /// This method is only available with the `stable-memory` feature and must be used inside a canister.
+    ///
+    /// Usage example:
+    /// ```no_run
+    /// use oxigraph::store::{StorageConfig, Store};
+    ///
+    /// // The dictionary and the main index live in stable memory, the other indexes stay on the heap
+    /// let config = StorageConfig::new()
+    ///     .with_stable_column_family("id2str", 0, Some(65_536))
+    ///     .with_stable_column_family("spog", 1, None);
+    /// let store = Store::new_with_config(&config)?;
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[cfg(feature = "stable-memory")]
+    #[inline]
+    #[must_use]
+    pub fn with_stable_column_family(
+        mut self,
+        name: impl Into<String>,
+        memory_id: u8,
+        max_pages: Option<u64>,
+    ) -> Self {
+        self.stable_column_families.push((
+            name.into(),
+            StableMemoryDefinition {
+                memory_id,
+                max_pages,
+            },
+        ));
+        self
+    }
+
+    /// Stores the column family `name` (see [`with_stable_column_family`](Self::with_stable_column_family) for the names)
+    /// in the map returned by `open`, called each time a storage is opened with this configuration.
+    ///
+    /// It allows to keep a column family in a custom ordered key-value map, see [`KeyValueStore`] for an example.
+    /// It takes precedence over a stable memory region given for the same column family.
+    #[inline]
+    #[must_use]
+    pub fn with_column_family_store(
+        mut self,
+        name: impl Into<String>,
+        open: impl Fn() -> Box<dyn KeyValueStore> + Send + Sync + 'static,
+    ) -> Self {
+        self.column_family_stores
+            .push((name.into(), StoreFactory(Arc::new(open))));
+        self
+    }
+
+    /// Only builds the given secondary indexes, in addition to SPOG which is always built.
+    ///
+    /// Each disabled index saves around a sixth of the memory used by the quads
+    /// but the quad patterns it would serve are evaluated with a scan of SPOG.
+    /// The index of the default graph quads matching the disabled index is disabled too.
+    ///
+    /// If the store content has been written with other indexes, for example before a canister upgrade,
+    /// the indexes are rebuilt when the store is opened.
+    #[inline]
+    #[must_use]
+    pub fn with_indexes(mut self, indexes: impl IntoIterator<Item = QuadIndex>) -> Self {
+        self.indexes = Some(indexes.into_iter().collect());
+        self
+    }
+
+    /// Builds an index of the literals by datatype and by language tag.
+    ///
+    /// The SPARQL filters like `FILTER(datatype(?o) = xsd:dateTime)` or `FILTER(lang(?o) = "en")`
+    /// are then evaluated by looking up the matching literals in the index instead of scanning all the objects.
+    /// It is disabled by default because it costs an index entry per literal and two per language-tagged string.
+    ///
+    /// Like for [`with_indexes`](Self::with_indexes), the indexes are rebuilt when the store is opened
+    /// if its content has been written with or without the literal index.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageConfig, Store};
+    /// use oxigraph::sparql::QueryResults;
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_literal_index())?;
+    /// store.update("INSERT DATA { <http://example.com> <http://example.com/p> \"a\"@en , \"b\"@fr , 1 }")?;
+    /// if let QueryResults::Solutions(solutions) = store.query("SELECT ?o WHERE { ?s ?p ?o FILTER(lang(?o) = \"en\") }")? {
+    ///     assert_eq!(solutions.count(), 1);
+    /// }
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_literal_index(mut self) -> Self {
+        self.literal_index = true;
+        self
+    }
+
+    /// Builds an index of the numeric and `xsd:dateTime` objects of each predicate sorted by value.
+    ///
+    /// The SPARQL filters like `FILTER(?o > 10)` on the object of a triple pattern with a fixed predicate
+    /// are then evaluated by a scan of the matching range of values instead of all the objects of the predicate.
+    /// It also speeds up [`Store::quads_in_range`](crate::store::Store::quads_in_range).
+    /// It is disabled by default because it costs an index entry per quad with a numeric or `xsd:dateTime` object.
+    ///
+    /// Like for [`with_indexes`](Self::with_indexes), the indexes are rebuilt when the store is opened
+    /// if its content has been written with or without the range index.
+    #[inline]
+    #[must_use]
+    pub fn with_range_index(mut self) -> Self {
+        self.range_index = true;
+        self
+    }
+
+    /// Keeps the changes of the `retained_versions` last [dataset versions](crate::store::Store::dataset_version)
+    /// to allow to read the store as it was at one of them with [`Store::snapshot_at`](crate::store::Store::snapshot_at).
+    ///
+    /// Each write keeps in memory the previous value of the written key until its version is dropped.
+    /// The history is not persisted, it starts again empty when the store is opened or restored.
+    #[inline]
+    #[must_use]
+    pub fn with_history(mut self, retained_versions: u64) -> Self {
+        self.retained_versions = Some(retained_versions);
+        self
+    }
+
+    /// Compresses with LZ4 the dictionary strings longer than `threshold` bytes, like long IRIs and literals.
+    ///
+    /// The strings are decompressed when they are read.
+    /// The strings already stored are not compressed again and the compressed strings stay readable
+    /// if the store is opened later without this option.
+    #[inline]
+    #[must_use]
+    pub fn with_dictionary_compression(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// Stores the keys of the quad indexes in blocks where each key only keeps the bytes not shared with the previous one.
+    ///
+    /// The keys of the quads sharing their subject, predicate or graph name start with the same bytes,
+    /// so it saves a large part of the memory used by the indexes of datasets with many quads per subject or graph,
+    /// at the cost of decoding a block of keys on each read or write.
+    /// The indexes kept in stable memory or in a [custom map](Self::with_column_family_store) are not affected.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{StorageConfig, Store};
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_prefix_compression())?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_prefix_compression(mut self) -> Self {
+        self.prefix_compression = true;
+        self
+    }
+
+    /// Appends each committed change to a log stored in the `changes` column family,
+    /// to be read with [`Store::changes_since`](crate::store::Store::changes_since).
+    ///
+    /// The entries are numbered from 1 in commit order: the quad insertions and removals,
+    /// including the ones done by clearing a graph, and the creations and drops of the named graphs.
+    /// The entries are kept until they are removed with [`Store::truncate_changes`](crate::store::Store::truncate_changes),
+    /// and so are the dictionary strings of their terms.
+    /// The option is not persisted: the changes done while the store is opened without it are not logged.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{LoggedChange, StorageConfig, Store};
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_change_log())?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.remove(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///
+    /// let changes = store.changes_since(1)?.collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(changes.len(), 1);
+    /// assert_eq!(changes[0].sequence(), 2);
+    /// assert!(matches!(changes[0].change(), LoggedChange::Removed(_)));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_change_log(mut self) -> Self {
+        self.change_log = true;
+        self
+    }
+
+    /// Validates the terms of the inserted quads and named graphs, `validation` telling if the bad terms are rejected or repaired.
+    ///
+    /// The IRIs must be valid and normalized following [RFC 3987](https://www.rfc-editor.org/rfc/rfc3987#section-5.3)
+    /// and the lexical forms of the literals with a supported XSD datatype must be valid,
+    /// so that the stored values do not break later the comparisons and the ordering.
+    /// The invalid insertions fail with [`StorageError::InvalidTerm`].
+    /// The quads copied from another graph or store are not validated again.
+    /// With [`TermValidation::Repair`], the quads and graph names given to the removals and to the containment checks
+    /// are repaired the same way, so that they match the stored ones.
+    /// The option is not persisted.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{StorageConfig, StorageError, Store, TermValidation};
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_term_validation(TermValidation::Repair))?;
+    /// let ex = NamedNodeRef::new("HTTP://Example.com/a/./b/../c")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// let normalized = NamedNodeRef::new("http://example.com/a/c")?;
+    /// assert!(store.contains(QuadRef::new(normalized, normalized, normalized, GraphNameRef::DefaultGraph))?);
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// store.update("DELETE DATA { <HTTP://Example.com/a/./b/../c> <http://example.com/a/c> <http://example.com/a/c> }")?;
+    /// assert!(store.is_empty()?);
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_term_validation(TermValidation::Reject))?;
+    /// let ex = NamedNodeRef::new("http://example.com/")?;
+    /// assert!(matches!(
+    ///     store.insert(QuadRef::new(ex, ex, &Literal::new_typed_literal("one", vocab::xsd::INTEGER), GraphNameRef::DefaultGraph)),
+    ///     Err(StorageError::InvalidTerm { .. })
+    /// ));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_term_validation(mut self, validation: TermValidation) -> Self {
+        self.term_validation = Some(validation);
+        self
+    }
+
+    /// Replaces the lexical forms of the inserted typed literals by their canonical forms.
+    ///
+    /// The values of the numeric, boolean, date and duration XSD datatypes are always stored in a binary form
+    /// and so are returned in their canonical form (`"01"^^xsd:integer` is returned as `"1"^^xsd:integer`).
+    /// This option also collapses the whitespaces around their lexical forms before they are encoded,
+    /// so that the value-equal literals are stored once and are joined by simple equality,
+    /// and canonicalizes the lexical forms of the string-based XSD datatypes (`xsd:token`, `xsd:hexBinary`...).
+    /// It is applied before the [term validation](Self::with_term_validation).
+    /// The quads given to the removals and to the containment checks are canonicalized the same way.
+    /// The option is not persisted.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::xsd;
+    /// use oxigraph::store::{StorageConfig, Store};
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_literal_canonicalization())?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, &Literal::new_typed_literal(" 01 ", xsd::INTEGER), GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, &Literal::new_typed_literal("0a1b", xsd::HEX_BINARY), GraphNameRef::DefaultGraph))?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, &Literal::from(1), GraphNameRef::DefaultGraph))?);
+    /// assert!(store.contains(QuadRef::new(ex, ex, &Literal::new_typed_literal("0A1B", xsd::HEX_BINARY), GraphNameRef::DefaultGraph))?);
+    /// assert!(store.remove(QuadRef::new(ex, ex, &Literal::new_typed_literal("0a1b", xsd::HEX_BINARY), GraphNameRef::DefaultGraph))?);
+    /// store.update("DELETE DATA { <http://example.com> <http://example.com> \" 01 \"^^<http://www.w3.org/2001/XMLSchema#integer> }")?;
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_literal_canonicalization(mut self) -> Self {
+        self.literal_canonicalization = true;
+        self
+    }
+
+    /// Rejects the insertions that would make the store contain more than `max_quads` quads.
+    ///
+    /// The write then fails with [`StorageError::QuotaExceeded`] and its transaction is rolled back with the writes it has already done.
+    /// The quotas are not persisted and are only checked by the insertions, so a store opened with lower quotas keeps its content.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{Quota, StorageConfig, StorageError, Store};
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new_with_config(&StorageConfig::default().with_max_quads(1))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// assert!(matches!(
+    ///     store.insert(QuadRef::new(ex, ex, ex, ex)),
+    ///     Err(StorageError::QuotaExceeded { quota: Quota::Quads, limit: 1 })
+    /// ));
+    /// assert_eq!(store.len()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_max_quads(mut self, max_quads: u64) -> Self {
+        self.quotas.max_quads = Some(max_quads);
+        self
+    }
+
+    /// Rejects the insertions that would make the strings of the dictionary use more than `max_dictionary_bytes` bytes.
+    ///
+    /// Only the long IRIs, blank node ids and literals are stored in the dictionary, the short ones are inlined in the index keys.
+    /// See [`with_max_quads`](Self::with_max_quads) for the behavior of the quotas.
+    #[inline]
+    #[must_use]
+    pub fn with_max_dictionary_bytes(mut self, max_dictionary_bytes: u64) -> Self {
+        self.quotas.max_dictionary_bytes = Some(max_dictionary_bytes);
+        self
+    }
+
+    /// Rejects the insertions that would make a named graph contain more than `max_graph_quads` quads.
+    ///
+    /// The default graph is not limited by this quota.
+    /// See [`with_max_quads`](Self::with_max_quads) for the behavior of the quotas.
+    #[inline]
+    #[must_use]
+    pub fn with_max_graph_quads(mut self, max_graph_quads: u64) -> Self {
+        self.quotas.max_graph_quads = Some(max_graph_quads);
+        self
+    }
+
+    /// Reads the current time used by the quad expirations from `clock`, in nanoseconds since the epoch,
+    /// instead of the canister time.
+    ///
+    /// It allows to use [`Transaction::insert_with_ttl`](crate::store::Transaction::insert_with_ttl) outside of a canister, for example in tests.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{StorageConfig, Store};
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let now = Arc::new(AtomicU64::new(0));
+    /// let clock = Arc::clone(&now);
+    /// let store = Store::new_with_config(
+    ///     &StorageConfig::default().with_clock(move || clock.load(Ordering::Relaxed)),
+    /// )?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.transaction(|mut t| t.insert_with_ttl(QuadRef::new(ex, ex, ex, ex), Duration::from_secs(1)))?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    ///
+    /// now.store(1_000_000_000, Ordering::Relaxed);
+    /// assert!(!store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Fn() -> u64 + Send + Sync + 'static) -> Self {
+        self.clock = Some(Clock(Arc::new(clock)));
+        self
+    }
+
+    /// The identifiers of the stable memory regions storing column families
+    pub(crate) fn stable_memory_ids(&self) -> Vec<u8> {
+        #[cfg(feature = "stable-memory")]
+        {
+            self.stable_column_families
+                .iter()
+                .map(|(_, definition)| definition.memory_id)
+                .collect()
+        }
+        #[cfg(not(feature = "stable-memory"))]
+        {
+            Vec::new()
+        }
+    }
+}
 
 /// Low level storage primitives
 #[derive(Clone)]
 pub struct Storage {
     db: Db,
+    change_observer: Arc<RwLock<Option<ChangeObserver>>>,
+    commit_hook: Arc<RwLock<Option<CommitHook>>>,
+    read_filter: Arc<RwLock<Option<EncodedReadFilter>>>,
+    graph_locks: Arc<RwLock<GraphLocks>>,
+    reservation: Arc<RwLock<Option<Reservation>>>,
+    write_ahead_log: Arc<Mutex<Option<Box<dyn Write + Send>>>>,
+    default_cf: ColumnFamily,
     id2str_cf: ColumnFamily,
     spog_cf: ColumnFamily,
     posg_cf: ColumnFamily,
@@ -45,11 +668,81 @@ pub struct Storage {
     dpos_cf: ColumnFamily,
     dosp_cf: ColumnFamily,
     graphs_cf: ColumnFamily,
+    meta_cf: ColumnFamily,
+    stats_cf: ColumnFamily,
+    literals_cf: ColumnFamily,
+    ranges_cf: ColumnFamily,
+    expirations_cf: ColumnFamily,
+    changes_cf: ColumnFamily,
+    disabled_indexes: Vec<ColumnFamily>,
+    quotas: Quotas,
+    compression_threshold: Option<usize>,
+    change_log: bool,
+    term_validation: Option<TermValidation>,
+    literal_canonicalization: bool,
+    clock: Clock,
+    /// The number of entries written in each secondary index by the last transaction writing into them
+    index_batch_sizes: Arc<RwLock<Vec<(&'static str, usize)>>>,
 }
 
 impl Storage {
     pub fn new() -> Result<Self, StorageError> {
-        Self::setup(Db::new(Self::column_families())?)
+        Self::setup(Db::new(Self::column_families())?, &StorageConfig::default())
+    }
+
+    pub fn new_with_config(config: &StorageConfig) -> Result<Self, StorageError> {
+        let mut column_families = Self::column_families();
+        #[cfg(feature = "stable-memory")]
+        for (i, (name, definition)) in config.stable_column_families.iter().enumerate() {
+            if config.stable_column_families[..i]
+                .iter()
+                .any(|(_, other)| other.memory_id == definition.memory_id)
+            {
+                return Err(StorageError::Other(
+                    format!("The stable memory {} is used twice", definition.memory_id).into(),
+                ));
+            }
+            let column_family = column_families
+                .iter_mut()
+                .find(|cf| cf.name == name)
+                .ok_or_else(|| {
+                    StorageError::Other(
+                        format!(
+                            "{name} is not a column family that could be stored in stable memory"
+                        )
+                        .into(),
+                    )
+                })?;
+            column_family.stable_memory = Some(*definition);
+        }
+        for (name, open) in &config.column_family_stores {
+            let column_family = column_families
+                .iter_mut()
+                .find(|cf| cf.name == name)
+                .ok_or_else(|| {
+                    StorageError::Other(
+                        format!(
+                            "{name} is not a column family that could be stored in a custom map"
+                        )
+                        .into(),
+                    )
+                })?;
+            column_family.store = Some((open.0)());
+        }
+        if config.prefix_compression {
+            for column_family in &mut column_families {
+                if [
+                    SPOG_CF, POSG_CF, OSPG_CF, GSPO_CF, GPOS_CF, GOSP_CF, DSPO_CF, DPOS_CF, DOSP_CF,
+                ]
+                .contains(&column_family.name)
+                    && column_family.store.is_none()
+                    && column_family.stable_memory.is_none()
+                {
+                    column_family.store = Some(Box::new(PrefixCompressedMap::default()));
+                }
+            }
+        }
+        Self::setup(Db::new(column_families)?, config)
     }
 
     fn column_families() -> Vec<ColumnFamilyDefinition> {
@@ -59,73 +752,166 @@ impl Storage {
                 use_iter: false,
                 min_prefix_size: 0,
                 unordered_writes: true,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: SPOG_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: POSG_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: OSPG_CF,
                 use_iter: true,
                 min_prefix_size: 0, // There are small literals...
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: GSPO_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: GPOS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: GOSP_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: DSPO_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: DPOS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: DOSP_CF,
                 use_iter: true,
                 min_prefix_size: 0, // There are small literals...
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
             ColumnFamilyDefinition {
                 name: GRAPHS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: META_CF,
+                use_iter: false,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: STATS_CF,
+                use_iter: true,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: LITERALS_CF,
+                use_iter: true,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: RANGES_CF,
+                use_iter: true,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: EXPIRATIONS_CF,
+                use_iter: true,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
+            },
+            ColumnFamilyDefinition {
+                name: CHANGES_CF,
+                use_iter: true,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                stable_memory: None,
+                store: None,
             },
         ]
     }
 
-    #[allow(clippy::unnecessary_wraps, clippy::unwrap_in_result)]
-    fn setup(db: Db) -> Result<Self, StorageError> {
+    #[allow(clippy::unwrap_in_result)]
+    fn setup(db: Db, config: &StorageConfig) -> Result<Self, StorageError> {
+        let mut disabled_indexes = Vec::new();
+        if let Some(indexes) = &config.indexes {
+            for (index, names) in [
+                (QuadIndex::Posg, &[POSG_CF, DPOS_CF][..]),
+                (QuadIndex::Ospg, &[OSPG_CF, DOSP_CF][..]),
+                (QuadIndex::Gspo, &[GSPO_CF][..]),
+                (QuadIndex::Gpos, &[GPOS_CF][..]),
+                (QuadIndex::Gosp, &[GOSP_CF][..]),
+            ] {
+                if !indexes.contains(&index) {
+                    for name in names {
+                        disabled_indexes.push(db.column_family(*name).unwrap());
+                    }
+                }
+            }
+        }
+        if !config.literal_index {
+            disabled_indexes.push(db.column_family(LITERALS_CF).unwrap());
+        }
+        if !config.range_index {
+            disabled_indexes.push(db.column_family(RANGES_CF).unwrap());
+        }
         let this = Self {
+            default_cf: db.column_family(DEFAULT_CF).unwrap(),
             id2str_cf: db.column_family(ID2STR_CF).unwrap(),
             spog_cf: db.column_family(SPOG_CF).unwrap(),
             posg_cf: db.column_family(POSG_CF).unwrap(),
@@ -137,66 +923,1755 @@ impl Storage {
             dpos_cf: db.column_family(DPOS_CF).unwrap(),
             dosp_cf: db.column_family(DOSP_CF).unwrap(),
             graphs_cf: db.column_family(GRAPHS_CF).unwrap(),
+            meta_cf: db.column_family(META_CF).unwrap(),
+            stats_cf: db.column_family(STATS_CF).unwrap(),
+            literals_cf: db.column_family(LITERALS_CF).unwrap(),
+            ranges_cf: db.column_family(RANGES_CF).unwrap(),
+            expirations_cf: db.column_family(EXPIRATIONS_CF).unwrap(),
+            changes_cf: db.column_family(CHANGES_CF).unwrap(),
+            disabled_indexes,
+            quotas: config.quotas,
+            compression_threshold: config.compression_threshold,
+            change_log: config.change_log,
+            term_validation: config.term_validation,
+            literal_canonicalization: config.literal_canonicalization,
+            clock: config
+                .clock
+                .clone()
+                .unwrap_or_else(|| Clock(Arc::new(time))),
             db,
+            change_observer: Arc::default(),
+            commit_hook: Arc::default(),
+            read_filter: Arc::default(),
+            graph_locks: Arc::default(),
+            reservation: Arc::default(),
+            write_ahead_log: Arc::default(),
+            index_batch_sizes: Arc::default(),
         };
+        this.migrate()?; // The column families in stable memory might have been written by an older version
+        this.apply_index_layout()?;
+        if let Some(retained_versions) = config.retained_versions {
+            this.db.enable_history(
+                this.default_cf.clone(),
+                DATASET_VERSION_KEY,
+                retained_versions,
+            )?;
+        }
         Ok(this)
     }
 
+    /// The version of the layout of the stored data
+    ///
+    /// The data written before the introduction of the versioning has the version 1.
+    pub fn storage_version(&self) -> Result<u64, StorageError> {
+        let version = match self.db.snapshot().get(&self.meta_cf, STORAGE_VERSION_KEY)? {
+            Some(version) => {
+                Some(u64::from_be_bytes(version[..].try_into().map_err(
+                    |_| CorruptionError::msg("Invalid storage version encoding"),
+                )?))
+            }
+            None => None,
+        };
+        Ok(if let Some(version) = version {
+            version
+        } else if self.is_empty_db()? {
+            LATEST_STORAGE_VERSION
+        } else {
+            1
+        })
+    }
+
+    /// Migrates the stored data to the layout of this version of the library
+    ///
+    /// Fails if the data has been written by a newer version.
+    pub fn migrate(&self) -> Result<(), StorageError> {
+        let stored = self.db.snapshot().get(&self.meta_cf, STORAGE_VERSION_KEY)?;
+        if stored.as_deref() == Some(LATEST_STORAGE_VERSION.to_be_bytes().as_slice()) {
+            return Ok(());
+        }
+        let version = self.storage_version()?;
+        if version > LATEST_STORAGE_VERSION {
+            return Err(CorruptionError::msg(format!(
+                "The storage version {version} is newer than the supported version {LATEST_STORAGE_VERSION}"
+            ))
+            .into());
+        }
+        for migration in MIGRATIONS
+            .iter()
+            .skip(usize::try_from(version.saturating_sub(1)).unwrap_or(usize::MAX))
+        {
+            migration(self)?;
+        }
+        self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+            transaction.insert(
+                &self.meta_cf,
+                STORAGE_VERSION_KEY,
+                &LATEST_STORAGE_VERSION.to_be_bytes(),
+            )
+        })
+    }
+
+    /// Makes the secondary indexes match the enabled ones
+    ///
+    /// The secondary indexes are rebuilt if the stored data has been written with other indexes.
+    fn apply_index_layout(&self) -> Result<(), StorageError> {
+        let layout = self
+            .all_cfs()
+            .iter()
+            .filter(|(_, cf)| !self.is_index_enabled(cf))
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(",");
+        let stored = self
+            .db
+            .snapshot()
+            .get(&self.meta_cf, DISABLED_INDEXES_KEY)?;
+        // The stores written before the introduction of the literal index have no layout and no literal or range index
+        if stored
+            .as_deref()
+            .unwrap_or(format!("{LITERALS_CF},{RANGES_CF}").as_bytes())
+            == layout.as_bytes()
+        {
+            return Ok(());
+        }
+        if !self.is_empty_db()? {
+            let mut rebuild = IndexRebuildState::default();
+            while !self.rebuild_indexes_step(&mut rebuild, 10_000)? {}
+        }
+        self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+            transaction.insert(&self.meta_cf, DISABLED_INDEXES_KEY, layout.as_bytes())
+        })
+    }
+
+    /// Returns if the secondary index stored in the column family `cf` is maintained
+    fn is_index_enabled(&self, cf: &ColumnFamily) -> bool {
+        !self.disabled_indexes.contains(cf)
+    }
+
+    /// The key of the range index entry of the quad, `None` if the index is disabled or the object is not a numeric or `xsd:dateTime` literal
+    fn range_index_key(&self, quad: &EncodedQuad) -> Option<Vec<u8>> {
+        if !self.is_index_enabled(&self.ranges_cf) {
+            return None;
+        }
+        let (kind, value) = range_value(&quad.object)?;
+        let mut key = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE + 9);
+        write_term(&mut key, &quad.predicate);
+        key.push(kind);
+        key.extend_from_slice(&sortable_f64_bytes(value));
+        write_term(&mut key, &quad.object);
+        write_term(&mut key, &quad.subject);
+        write_term(&mut key, &quad.graph_name);
+        Some(key)
+    }
+
+    /// The keys of the literal index entries of the object, empty if the index is disabled
+    fn literal_index_keys(&self, object: &EncodedTerm) -> Vec<Vec<u8>> {
+        let mut keys = Vec::new();
+        if self.is_index_enabled(&self.literals_cf) {
+            for (prefix, tag) in [
+                (DATATYPE_ENTRY_PREFIX, literal_datatype(object)),
+                (LANGUAGE_ENTRY_PREFIX, literal_language(object)),
+            ] {
+                if let Some(tag) = tag {
+                    let mut key = vec![prefix];
+                    write_term(&mut key, &tag);
+                    write_term(&mut key, object);
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    fn is_empty_db(&self) -> Result<bool, StorageError> {
+        let reader = self.db.snapshot();
+        for cf in [&self.spog_cf, &self.dspo_cf, &self.graphs_cf] {
+            if reader.len(cf)? > 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the current time used by the quad expirations, in nanoseconds since the epoch
+    pub fn now(&self) -> u64 {
+        (self.clock.0)()
+    }
+
     pub fn snapshot(&self) -> StorageReader {
         StorageReader {
             reader: self.db.snapshot(),
             storage: self.clone(),
+            index_batch: None,
         }
     }
 
+    /// Returns a reader of the store as it was at the given dataset version
+    ///
+    /// Fails with [`StorageError::VersionNotAvailable`] if the version is not kept in the history.
+    pub fn snapshot_at(&self, version: u64) -> Result<StorageReader, StorageError> {
+        // The versions given by the expirations of the quads not removed yet have the same stored content as the current one
+        let current = self.snapshot();
+        let stored_version = current.stored_dataset_version()?;
+        let version = if version > stored_version && version <= current.dataset_version()? {
+            stored_version
+        } else {
+            version
+        };
+        Ok(StorageReader {
+            reader: self.db.snapshot_at(version)?,
+            storage: self.clone(),
+            index_batch: None,
+        })
+    }
+
     pub fn transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
         &'b self,
         f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
     ) -> Result<T, E> {
-        self.db.transaction(|transaction| {
-            f(StorageWriter {
+        self.transaction_with_isolation(IsolationLevel::RepeatableRead, f)
+    }
+
+    pub fn transaction_with_isolation<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        isolation: IsolationLevel,
+        f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.transaction_with_options(isolation, None, f)
+    }
+
+    /// Executes a transaction allowed to write into the graphs locked by `lock_owner`
+    pub fn transaction_with_lock_owner<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        lock_owner: u64,
+        f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.transaction_with_options(IsolationLevel::RepeatableRead, Some(lock_owner), f)
+    }
+
+    /// Executes a transaction only if the commit version is still `expected`
+    ///
+    /// Fails with [`StorageError::Conflict`] otherwise, the version being checked while the other writes are blocked.
+    pub fn transaction_if_commit_version<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        expected: u64,
+        f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.transaction_with_options(IsolationLevel::RepeatableRead, None, |writer| {
+            let actual = writer.reader().commit_version()?;
+            if actual != expected {
+                return Err(StorageError::Conflict { expected, actual }.into());
+            }
+            f(writer)
+        })
+    }
+
+    fn transaction_with_options<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        isolation: IsolationLevel,
+        lock_owner: Option<u64>,
+        f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        if self.is_read_only()? {
+            return Err(StorageError::ReadOnly.into());
+        }
+        let reservation = self.reservation.write().unwrap().take();
+        let reserved_quads = if let Some(Reservation { quads, memory }) = reservation {
+            drop(memory); // The memory is released to the allocator just before the writes
+            quads
+        } else {
+            0
+        };
+        let observer = self.change_observer.read().unwrap().clone();
+        let hook = self.commit_hook.read().unwrap().clone();
+        let changes = (observer.is_some() || hook.is_some())
+            .then(|| Rc::new(RefCell::new(Vec::with_capacity(reserved_quads))));
+        let f = |mut transaction: Transaction<'a>| {
+            // The writes of a failed transaction are undone by a rollback to this savepoint
+            let savepoint = transaction.savepoint();
+            let index_batch = Rc::new(RefCell::new(IndexWriteBatch::default()));
+            let new_writer = |version_updated| StorageWriter {
                 buffer: Vec::new(),
-                transaction,
+                transaction: transaction.clone(),
                 storage: self,
-            })
-        })
+                index_batch: Rc::clone(&index_batch),
+                changes: changes.clone(),
+                version_updated,
+                lock_owner,
+                denied_graphs: HashSet::new(),
+            };
+            let result = f(new_writer(false)).and_then(|value| {
+                if let (Some(hook), Some(changes)) = (&hook, &changes) {
+                    // The hook only sees the changes done before it, the dataset version being already bumped by them
+                    let done = changes.borrow().clone();
+                    if !done.is_empty() {
+                        hook(new_writer(true), &done)?;
+                    }
+                }
+                Ok(value)
+            });
+            // The index writes are applied even if the transaction has failed so that the rollback undoes them with the other writes
+            let applied = self.apply_index_batch(&mut transaction, index_batch.take());
+            let result = match (result, applied) {
+                (Ok(_), Err(e)) => Err(e.into()),
+                (result, _) => result,
+            };
+            if result.is_err() {
+                transaction.rollback_to_savepoint(savepoint)?;
+            } else {
+                transaction.release_savepoint(savepoint);
+            }
+            result
+        };
+        let result = self.logged_transaction(isolation, f);
+        if let (Ok(_), Some(observer), Some(changes)) = (&result, observer, changes) {
+            let changes = changes.take();
+            if !changes.is_empty() {
+                observer(&self.snapshot(), &changes);
+            }
+        }
+        result
     }
-}
 
-pub struct StorageReader {
-    reader: Reader,
-    storage: Storage,
-}
+    /// Locks graphs, the writes to them being rejected except for the transactions of the returned lock owner
+    ///
+    /// Fails if one of the graphs is already locked.
+    pub fn lock_graphs(&self, graphs: LockedGraphs) -> Result<u64, StorageError> {
+        let mut locks = self.graph_locks.write().unwrap();
+        if locks.owners.values().any(|locked| locked.overlaps(&graphs)) {
+            return Err(StorageError::GraphLocked);
+        }
+        let owner = locks.next_owner;
+        locks.next_owner += 1;
+        locks.owners.insert(owner, graphs);
+        Ok(owner)
+    }
 
-impl StorageReader {
-    pub fn len(&self) -> Result<usize, StorageError> {
-        Ok(self.reader.len(&self.storage.gspo_cf)? + self.reader.len(&self.storage.dspo_cf)?)
+    pub fn unlock_graphs(&self, owner: u64) {
+        self.graph_locks.write().unwrap().owners.remove(&owner);
     }
 
-    pub fn is_empty(&self) -> Result<bool, StorageError> {
-        Ok(self.reader.is_empty(&self.storage.gspo_cf)?
-            && self.reader.is_empty(&self.storage.dspo_cf)?)
+    /// Prepares the next write transaction for the insertion of `quads` quads with `dictionary_bytes` bytes of new strings
+    ///
+    /// The memory is allocated at once and released just before the transaction,
+    /// so that the allocator reuses it instead of growing the heap many times during the writes.
+    pub fn reserve(&self, quads: usize, dictionary_bytes: usize) {
+        let mut reservation = self.reservation.write().unwrap();
+        *reservation = None; // We release the previous reservation first
+        *reservation = Some(Reservation {
+            quads,
+            memory: Vec::with_capacity(
+                quads
+                    .saturating_mul(RESERVED_BYTES_PER_QUAD)
+                    .saturating_add(dictionary_bytes),
+            ),
+        });
     }
 
-    pub fn contains(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
-        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
-        if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut buffer, quad);
-            Ok(self.reader.contains_key(&self.storage.dspo_cf, &buffer)?)
+    /// The number of quads expected by the next write transaction
+    pub fn reserved_quads(&self) -> usize {
+        self.reservation
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |reservation| reservation.quads)
+    }
+
+    /// Sets the callback notified of the changes done by each commit
+    pub fn set_change_observer(&self, observer: Option<ChangeObserver>) {
+        *self.change_observer.write().unwrap() = observer;
+    }
+
+    /// Sets the hook called at the end of each write transaction with its changes
+    pub fn set_commit_hook(&self, hook: Option<CommitHook>) {
+        *self.commit_hook.write().unwrap() = hook;
+    }
+
+    /// Sets the filter hiding quads from the pattern reads and the containment checks of all the readers
+    ///
+    /// The full scans done by the writes and the migrations are not filtered.
+    ///
+    /// The visible quads change, so the dataset version is incremented like by a write,
+    /// bypassing the read-only check done by [`transaction`](Self::transaction) like [`set_read_only`](Self::set_read_only).
+    pub fn set_read_filter(&self, filter: Option<EncodedReadFilter>) -> Result<(), StorageError> {
+        *self.read_filter.write().unwrap() = filter;
+        self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+            let version = StorageReader {
+                reader: transaction.reader(),
+                storage: self.clone(),
+                index_batch: None,
+            }
+            .stored_dataset_version()?;
+            transaction.insert(
+                &self.default_cf,
+                DATASET_VERSION_KEY,
+                &(version + 1).to_be_bytes(),
+            )
+        })
+    }
+
+    /// Encodes the quad as an insertion would store it, with its literals canonicalized and its terms repaired if enabled
+    ///
+    /// The removals and the lookups use it to find the quads changed by their insertion.
+    /// The quads an insertion would reject are encoded as they are, they can not be in the storage anyway.
+    pub fn encode_stored_quad(&self, quad: QuadRef<'_>) -> EncodedQuad {
+        let canonical = if self.literal_canonicalization {
+            canonicalize_quad(quad)
         } else {
-            write_gspo_quad(&mut buffer, quad);
-            Ok(self.reader.contains_key(&self.storage.gspo_cf, &buffer)?)
+            None
+        };
+        let repaired = self.term_validation.and_then(|validation| {
+            validation
+                .validate_quad(canonical.as_ref().map_or(quad, Quad::as_ref))
+                .ok()
+                .flatten()
+        });
+        repaired
+            .or(canonical)
+            .as_ref()
+            .map_or(quad, Quad::as_ref)
+            .into()
+    }
+
+    /// Encodes the graph name as an insertion would store it, see [`encode_stored_quad`](Self::encode_stored_quad)
+    pub fn encode_stored_graph_name(&self, graph_name: NamedOrBlankNodeRef<'_>) -> EncodedTerm {
+        let repaired = self
+            .term_validation
+            .and_then(|validation| validation.validate_graph_name(graph_name).ok().flatten());
+        repaired
+            .as_ref()
+            .map_or(graph_name, NamedOrBlankNode::as_ref)
+            .into()
+    }
+
+    pub fn is_read_only(&self) -> Result<bool, StorageError> {
+        self.db
+            .snapshot()
+            .contains_key(&self.default_cf, READ_ONLY_KEY)
+    }
+
+    /// Sets the persisted read-only flag
+    ///
+    /// It bypasses the read-only check done by [`transaction`](Self::transaction) to allow leaving the read-only mode.
+    pub fn set_read_only(&self, read_only: bool) -> Result<(), StorageError> {
+        self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+            if read_only {
+                transaction.insert_empty(&self.default_cf, READ_ONLY_KEY)
+            } else {
+                transaction.remove(&self.default_cf, READ_ONLY_KEY)
+            }
+        })
+    }
+
+    /// Writes a metadata entry, removing it if `value` is `None`
+    ///
+    /// Like [`set_read_only`](Self::set_read_only), it bypasses the read-only check done by [`transaction`](Self::transaction),
+    /// the metadata like the state of the jobs not being part of the dataset.
+    pub fn set_metadata(&self, key: &[u8], value: Option<&[u8]>) -> Result<(), StorageError> {
+        self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+            if let Some(value) = value {
+                transaction.insert(&self.default_cf, key, value)
+            } else {
+                transaction.remove(&self.default_cf, key)
+            }
+        })
+    }
+
+    /// Executes a backend transaction, appending its writes to the write-ahead log if there is one
+    fn logged_transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        isolation: IsolationLevel,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let log = self
+            .write_ahead_log
+            .lock()
+            .unwrap()
+            .is_some()
+            .then(WriteLog::default);
+        let f = |transaction: Transaction<'a>| {
+            let mut counter = transaction.clone();
+            // The writes of a failed transaction are undone by a rollback to this savepoint,
+            // including the ones of a transaction whose writes could not be appended to the write-ahead log
+            let savepoint = counter.savepoint();
+            let result = f(transaction).and_then(|value| {
+                if counter.has_written() {
+                    let version = decode_commit_version(
+                        counter
+                            .reader()
+                            .get(&self.default_cf, COMMIT_VERSION_KEY)?
+                            .as_deref(),
+                    )?;
+                    counter.insert(
+                        &self.default_cf,
+                        COMMIT_VERSION_KEY,
+                        &(version + 1).to_be_bytes(),
+                    )?;
+                }
+                // The writes are logged before the end of the transaction so that they are only kept if they are logged
+                if let Some(log) = &log {
+                    let writes = log.take();
+                    if !writes.is_empty() {
+                        self.append_to_log(&writes)?;
+                    }
+                }
+                Ok(value)
+            });
+            if result.is_err() {
+                counter.rollback_to_savepoint(savepoint)?;
+            } else {
+                counter.release_savepoint(savepoint);
+            }
+            result
+        };
+        match isolation {
+            IsolationLevel::RepeatableRead => self.db.transaction(log.clone(), f),
+            IsolationLevel::ReadUncommitted => self.db.transaction_without_lock(log.clone(), f),
         }
     }
 
-    pub fn quads_for_pattern(
+    /// Writes the buffered secondary index writes of a transaction, each index in key order
+    #[allow(clippy::unwrap_in_result)]
+    fn apply_index_batch(
         &self,
-        subject: Option<&EncodedTerm>,
-        predicate: Option<&EncodedTerm>,
-        object: Option<&EncodedTerm>,
-        graph_name: Option<&EncodedTerm>,
-    ) -> ChainedDecodingQuadIterator {
-        match subject {
+        transaction: &mut Transaction<'_>,
+        mut batch: IndexWriteBatch,
+    ) -> Result<(), StorageError> {
+        if batch.writes.is_empty() {
+            return Ok(());
+        }
+        let mut sizes = Vec::new();
+        for (name, column_family) in self.all_cfs() {
+            if let Some(writes) = batch.writes.remove(column_family) {
+                sizes.push((name, writes.len()));
+                let mut insertions = Vec::with_capacity(writes.len());
+                for (key, inserted) in writes {
+                    if inserted {
+                        insertions.push((key, Vec::new()));
+                    } else {
+                        transaction.remove(column_family, &key)?;
+                    }
+                }
+                transaction.insert_sorted(column_family, insertions)?;
+            }
+        }
+        *self.index_batch_sizes.write().unwrap() = sizes;
+        Ok(())
+    }
+
+    /// Returns the number of entries written in each secondary index by the last transaction writing into them
+    pub fn index_batch_sizes(&self) -> Vec<(&'static str, usize)> {
+        self.index_batch_sizes.read().unwrap().clone()
+    }
+
+    fn append_to_log(&self, writes: &[LoggedWrite]) -> Result<(), StorageError> {
+        let cfs = self.all_cfs();
+        let cf_index = |column_family: &ColumnFamily| {
+            cfs.iter()
+                .position(|(_, cf)| *cf == column_family)
+                .and_then(|i| u8::try_from(i).ok())
+                .ok_or_else(|| StorageError::Other("Unknown column family".into()))
+        };
+        // Each record starts with the storage version its writes are encoded with
+        let mut record = Vec::new();
+        write_varint(&mut record, LATEST_STORAGE_VERSION)?;
+        write_varint(&mut record, writes.len() as u64)?;
+        for write in writes {
+            match write {
+                LoggedWrite::Insert {
+                    column_family,
+                    key,
+                    value,
+                } => {
+                    record.push(0);
+                    record.push(cf_index(column_family)?);
+                    write_bytes(&mut record, key)?;
+                    write_bytes(&mut record, value)?;
+                }
+                LoggedWrite::Remove { column_family, key } => {
+                    record.push(1);
+                    record.push(cf_index(column_family)?);
+                    write_bytes(&mut record, key)?;
+                }
+            }
+        }
+        let mut log = self.write_ahead_log.lock().unwrap();
+        if let Some(log) = log.as_mut() {
+            write_bytes(log, &record)?;
+            log.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Sets the sink the writes of each transaction are appended to, replacing the previous one
+    pub fn set_write_ahead_log(
+        &self,
+        log: Option<Box<dyn Write + Send>>,
+    ) -> Result<(), StorageError> {
+        let previous = std::mem::replace(&mut *self.write_ahead_log.lock().unwrap(), log);
+        if let Some(mut previous) = previous {
+            previous.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Applies the transactions of a write-ahead log
+    ///
+    /// A truncated last transaction is ignored.
+    /// Fails if a transaction has been written with another storage version than the current one.
+    /// Returns the number of applied transactions.
+    pub fn replay_log(&self, reader: impl Read) -> Result<usize, StorageError> {
+        let cfs = self.all_cfs();
+        let version = self.storage_version()?;
+        let mut reader = BufReader::new(reader);
+        let mut count = 0;
+        while !reader.fill_buf()?.is_empty() {
+            let record = match read_bytes(&mut reader) {
+                Ok(record) => record,
+                Err(StorageError::Corruption(_)) => break, // Truncated record
+                Err(e) => return Err(e),
+            };
+            let mut data = record.as_slice();
+            let record_version = read_varint(&mut data)?;
+            if record_version != version {
+                return Err(CorruptionError::msg(format!(
+                    "The write-ahead log has been written with the storage version {record_version} but the storage has the version {version}"
+                ))
+                .into());
+            }
+            let mut writes = Vec::new();
+            for _ in 0..read_varint(&mut data)? {
+                let mut header = [0; 2];
+                data.read_exact(&mut header)?;
+                let column_family = cfs
+                    .get(usize::from(header[1]))
+                    .map(|(_, cf)| (*cf).clone())
+                    .ok_or_else(|| CorruptionError::msg("Unknown column family in the log"))?;
+                let key = read_bytes(&mut data)?;
+                writes.push(match header[0] {
+                    0 => LoggedWrite::Insert {
+                        column_family,
+                        key,
+                        value: read_bytes(&mut data)?,
+                    },
+                    1 => LoggedWrite::Remove { column_family, key },
+                    _ => return Err(CorruptionError::msg("Invalid write kind in the log").into()),
+                });
+            }
+            self.db.transaction(None, |mut transaction| {
+                for write in &writes {
+                    match write {
+                        LoggedWrite::Insert {
+                            column_family,
+                            key,
+                            value,
+                        } => transaction.insert(column_family, key, value)?,
+                        LoggedWrite::Remove { column_family, key } => {
+                            transaction.remove(column_family, key)?;
+                        }
+                    }
+                }
+                Ok::<_, StorageError>(())
+            })?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The value stored in the dictionary for `value`, compressed if it is longer than the compression threshold
+    fn encode_str<'b>(&self, value: &'b str) -> Cow<'b, [u8]> {
+        if let Some(threshold) = self.compression_threshold {
+            if value.len() > threshold {
+                let mut compressed = vec![COMPRESSED_STR_MARKER];
+                compressed.extend_from_slice(&compress_prepend_size(value.as_bytes()));
+                if compressed.len() < value.len() {
+                    return compressed.into();
+                }
+            }
+        }
+        value.as_bytes().into()
+    }
+
+    /// Adds `delta` bytes to the size of the dictionary if `added`, removes them otherwise
+    fn add_to_dictionary_size(
+        &self,
+        transaction: &mut Transaction<'_>,
+        delta: u64,
+        added: bool,
+    ) -> Result<(), StorageError> {
+        if delta == 0 {
+            return Ok(());
+        }
+        let size = decode_count(
+            transaction
+                .reader()
+                .get(&self.default_cf, DICTIONARY_SIZE_KEY)?
+                .as_deref(),
+        )?;
+        let size = if added {
+            size.saturating_add(delta)
+        } else {
+            size.saturating_sub(delta)
+        };
+        transaction.insert(&self.default_cf, DICTIONARY_SIZE_KEY, &size.to_be_bytes())
+    }
+
+    fn all_cfs(&self) -> [(&'static str, &ColumnFamily); 18] {
+        [
+            (DEFAULT_CF, &self.default_cf),
+            (ID2STR_CF, &self.id2str_cf),
+            (SPOG_CF, &self.spog_cf),
+            (POSG_CF, &self.posg_cf),
+            (OSPG_CF, &self.ospg_cf),
+            (GSPO_CF, &self.gspo_cf),
+            (GPOS_CF, &self.gpos_cf),
+            (GOSP_CF, &self.gosp_cf),
+            (DSPO_CF, &self.dspo_cf),
+            (DPOS_CF, &self.dpos_cf),
+            (DOSP_CF, &self.dosp_cf),
+            (GRAPHS_CF, &self.graphs_cf),
+            (META_CF, &self.meta_cf),
+            (STATS_CF, &self.stats_cf),
+            (LITERALS_CF, &self.literals_cf),
+            (RANGES_CF, &self.ranges_cf),
+            (EXPIRATIONS_CF, &self.expirations_cf),
+            (CHANGES_CF, &self.changes_cf),
+        ]
+    }
+
+    /// Writes the content of all the column families in a compact binary form
+    pub fn save(&self, writer: impl Write) -> Result<(), StorageError> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&[SNAPSHOT_VERSION])?;
+        let cfs = self.all_cfs();
+        write_varint(&mut writer, cfs.len() as u64)?;
+        for (name, cf) in cfs {
+            write_varint(&mut writer, name.len() as u64)?;
+            writer.write_all(name.as_bytes())?;
+            write_varint(&mut writer, self.db.snapshot().len(cf)? as u64)?;
+            self.db.for_each_entry(cf, |key, value| {
+                write_varint(&mut writer, key.len() as u64)?;
+                writer.write_all(key)?;
+                write_varint(&mut writer, value.len() as u64)?;
+                writer.write_all(value)?;
+                Ok::<_, StorageError>(())
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Replaces the content of all the column families by the one written by [`save`](Self::save)
+    ///
+    /// The content is replaced atomically once the snapshot is fully read.
+    pub fn load(&self, reader: impl Read) -> Result<(), StorageError> {
+        self.load_with_log(reader, empty())?;
+        Ok(())
+    }
+
+    /// Same as [`load`](Self::load) but also replays a write-ahead log appended after the snapshot was written
+    ///
+    /// The log is replayed before the migration of the loaded data, its writes having the layout of the snapshot.
+    /// Returns the number of replayed transactions.
+    ///
+    /// Like the transactions, it fails with [`StorageError::ReadOnly`] if the storage is read-only.
+    pub fn load_with_log(
+        &self,
+        snapshot: impl Read,
+        log: impl Read,
+    ) -> Result<usize, StorageError> {
+        if self.is_read_only()? {
+            return Err(StorageError::ReadOnly);
+        }
+        self.replace_content(snapshot)?;
+        let count = self.replay_log(log)?;
+        self.migrate()?;
+        self.apply_index_layout()?;
+        Ok(count)
+    }
+
+    /// Replaces the content of all the column families by the one of a snapshot, without migrating it
+    fn replace_content(&self, reader: impl Read) -> Result<(), StorageError> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0; SNAPSHOT_MAGIC.len() + 1];
+        reader.read_exact(&mut magic)?;
+        if &magic[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err(CorruptionError::msg("The data is not an Oxigraph snapshot").into());
+        }
+        if magic[SNAPSHOT_MAGIC.len()] != SNAPSHOT_VERSION {
+            return Err(CorruptionError::msg(format!(
+                "Unsupported snapshot version {}",
+                magic[SNAPSHOT_MAGIC.len()]
+            ))
+            .into());
+        }
+        let cfs = self.all_cfs();
+        let mut content = Vec::with_capacity(cfs.len());
+        for _ in 0..read_varint(&mut reader)? {
+            let name = read_bytes(&mut reader)?;
+            let cf = cfs
+                .iter()
+                .find(|(n, _)| n.as_bytes() == name)
+                .map(|(_, cf)| (*cf).clone())
+                .ok_or_else(|| {
+                    CorruptionError::msg(format!(
+                        "Unknown column family {} in the snapshot",
+                        String::from_utf8_lossy(&name)
+                    ))
+                })?;
+            let len = read_varint(&mut reader)?;
+            let mut entries = Vec::with_capacity(usize::try_from(len).unwrap_or(0).min(1 << 20));
+            for _ in 0..len {
+                let key = read_bytes(&mut reader)?;
+                let value = read_bytes(&mut reader)?;
+                entries.push((key, value));
+            }
+            content.push((cf, entries.into_iter().collect()));
+        }
+        // The column families missing from the snapshot are emptied
+        for (_, cf) in cfs {
+            if !content.iter().any(|(c, _)| c == cf) {
+                content.push((cf.clone(), BTreeMap::new()));
+            }
+        }
+        self.db.replace_column_families(content)
+    }
+
+    /// The column families derived from SPOG and DSPO
+    fn secondary_cfs(&self) -> [&ColumnFamily; 9] {
+        [
+            &self.posg_cf,
+            &self.ospg_cf,
+            &self.gspo_cf,
+            &self.gpos_cf,
+            &self.gosp_cf,
+            &self.dpos_cf,
+            &self.dosp_cf,
+            &self.literals_cf,
+            &self.ranges_cf,
+        ]
+    }
+
+    /// Does a step of a rebuild of the secondary indexes, touching at most `batch_size` keys
+    ///
+    /// The secondary column families are first cleared and then the enabled ones are filled from a scan of SPOG and DSPO.
+    /// The named graphs are kept, even the empty ones.
+    /// Returns `true` when the rebuild is finished.
+    pub fn rebuild_indexes_step(
+        &self,
+        rebuild: &mut IndexRebuildState,
+        batch_size: usize,
+    ) -> Result<bool, StorageError> {
+        let batch_size = batch_size.max(1);
+        rebuild.phase = match &rebuild.phase {
+            RebuildPhase::Clearing(i) => {
+                let cf = self.secondary_cfs()[*i];
+                let keys = self.db.snapshot().keys_from(cf, &[], batch_size)?;
+                self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+                    for key in &keys {
+                        transaction.remove(cf, key)?;
+                    }
+                    Ok::<_, StorageError>(())
+                })?;
+                if keys.len() == batch_size {
+                    RebuildPhase::Clearing(*i)
+                } else if *i + 1 < self.secondary_cfs().len() {
+                    RebuildPhase::Clearing(*i + 1)
+                } else {
+                    RebuildPhase::Named(Vec::new())
+                }
+            }
+            RebuildPhase::Named(from) => {
+                let keys = self
+                    .db
+                    .snapshot()
+                    .keys_from(&self.spog_cf, from, batch_size)?;
+                self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+                    let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+                    for key in &keys {
+                        let quad = QuadEncoding::Spog.decode(key)?;
+                        for (cf, write) in [
+                            (
+                                &self.posg_cf,
+                                write_posg_quad as fn(&mut Vec<u8>, &EncodedQuad),
+                            ),
+                            (&self.ospg_cf, write_ospg_quad),
+                            (&self.gspo_cf, write_gspo_quad),
+                            (&self.gpos_cf, write_gpos_quad),
+                            (&self.gosp_cf, write_gosp_quad),
+                        ] {
+                            if !self.is_index_enabled(cf) {
+                                continue;
+                            }
+                            buffer.clear();
+                            write(&mut buffer, &quad);
+                            transaction.insert_empty(cf, &buffer)?;
+                        }
+                        buffer.clear();
+                        write_term(&mut buffer, &quad.graph_name);
+                        transaction.insert_empty(&self.graphs_cf, &buffer)?;
+                        for key in self.literal_index_keys(&quad.object) {
+                            transaction.insert_empty(&self.literals_cf, &key)?;
+                        }
+                        if let Some(key) = self.range_index_key(&quad) {
+                            transaction.insert_empty(&self.ranges_cf, &key)?;
+                        }
+                    }
+                    Ok::<_, StorageError>(())
+                })?;
+                rebuild.quads_processed += keys.len() as u64;
+                next_scan_phase(keys, batch_size, RebuildPhase::Named, || {
+                    RebuildPhase::Default(Vec::new())
+                })
+            }
+            RebuildPhase::Default(from) => {
+                let keys = self
+                    .db
+                    .snapshot()
+                    .keys_from(&self.dspo_cf, from, batch_size)?;
+                self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+                    let mut buffer = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
+                    for key in &keys {
+                        let quad = QuadEncoding::Dspo.decode(key)?;
+                        for (cf, write) in [
+                            (
+                                &self.dpos_cf,
+                                write_pos_quad as fn(&mut Vec<u8>, &EncodedQuad),
+                            ),
+                            (&self.dosp_cf, write_osp_quad),
+                        ] {
+                            if self.is_index_enabled(cf) {
+                                buffer.clear();
+                                write(&mut buffer, &quad);
+                                transaction.insert_empty(cf, &buffer)?;
+                            }
+                        }
+                        for key in self.literal_index_keys(&quad.object) {
+                            transaction.insert_empty(&self.literals_cf, &key)?;
+                        }
+                        if let Some(key) = self.range_index_key(&quad) {
+                            transaction.insert_empty(&self.ranges_cf, &key)?;
+                        }
+                    }
+                    Ok::<_, StorageError>(())
+                })?;
+                rebuild.quads_processed += keys.len() as u64;
+                next_scan_phase(keys, batch_size, RebuildPhase::Default, || {
+                    RebuildPhase::Finished
+                })
+            }
+            RebuildPhase::Finished => RebuildPhase::Finished,
+        };
+        Ok(rebuild.is_finished())
+    }
+
+    /// Does a step of a mark and sweep collection of the strings not used anymore, touching at most `batch_size` keys
+    ///
+    /// The strings used by the quads, the named graphs and the change log are first marked and then the other strings are removed.
+    /// The collection restarts if the quads are changed before it is finished.
+    /// Returns `true` when the collection is finished.
+    pub fn collect_strings_step(
+        &self,
+        collection: &mut StringCollectionState,
+        batch_size: usize,
+    ) -> Result<bool, StorageError> {
+        let batch_size = batch_size.max(1);
+        let version = self.snapshot().stored_dataset_version()?;
+        if collection.version != Some(version) {
+            if collection.is_finished() {
+                return Ok(true);
+            }
+            // The quads have changed, the marked strings might not be the used ones anymore
+            collection.version = Some(version);
+            collection.phase = CollectionPhase::Marking(0, Vec::new());
+            collection.used.clear();
+        }
+        collection.phase = match &collection.phase {
+            CollectionPhase::Marking(i, from) => {
+                let (cf, encoding) = [
+                    (&self.spog_cf, Some(QuadEncoding::Spog)),
+                    (&self.dspo_cf, Some(QuadEncoding::Dspo)),
+                    (&self.graphs_cf, None),
+                    (&self.changes_cf, None),
+                ][*i];
+                let keys = self.db.snapshot().keys_from(cf, from, batch_size)?;
+                for key in &keys {
+                    if *i == 3 {
+                        for term in decode_change_log_key(key)?.1.terms() {
+                            add_str_hashes(term, &mut collection.used);
+                        }
+                    } else if let Some(encoding) = encoding {
+                        let quad = encoding.decode(key)?;
+                        for term in [
+                            &quad.subject,
+                            &quad.predicate,
+                            &quad.object,
+                            &quad.graph_name,
+                        ] {
+                            add_str_hashes(term, &mut collection.used);
+                        }
+                    } else {
+                        add_str_hashes(&decode_term(key)?, &mut collection.used);
+                    }
+                }
+                let i = *i;
+                next_scan_phase(
+                    keys,
+                    batch_size,
+                    |from| CollectionPhase::Marking(i, from),
+                    || {
+                        if i < 3 {
+                            CollectionPhase::Marking(i + 1, Vec::new())
+                        } else {
+                            CollectionPhase::Sweeping(Vec::new())
+                        }
+                    },
+                )
+            }
+            CollectionPhase::Sweeping(from) => {
+                let keys = self
+                    .db
+                    .snapshot()
+                    .keys_from(&self.id2str_cf, from, batch_size)?;
+                let removed =
+                    self.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+                        let reader = StorageReader {
+                            reader: transaction.reader(),
+                            storage: self.clone(),
+                            index_batch: None,
+                        };
+                        if reader.stored_dataset_version()? != version {
+                            return Ok(None);
+                        }
+                        let mut removed = 0;
+                        let mut removed_bytes = 0;
+                        for key in &keys {
+                            let hash = StrHash::from_be_bytes(key.as_slice().try_into().map_err(
+                                |_| {
+                                    CorruptionError::msg(
+                                        "Invalid string hash in the id2str column family",
+                                    )
+                                },
+                            )?);
+                            // The named graphs might have been inserted after their marking
+                            if collection.used.contains(&hash)
+                                || reader.contains_named_graph(&EncodedTerm::NamedNode {
+                                    iri_id: hash,
+                                })?
+                                || reader.contains_named_graph(&EncodedTerm::BigBlankNode {
+                                    id_id: hash,
+                                })?
+                            {
+                                continue;
+                            }
+                            if let Some(value) = transaction.reader().get(&self.id2str_cf, key)? {
+                                removed_bytes += value.len() as u64;
+                            }
+                            transaction.remove(&self.id2str_cf, key)?;
+                            removed += 1;
+                        }
+                        self.add_to_dictionary_size(&mut transaction, removed_bytes, false)?;
+                        Ok::<_, StorageError>(Some(removed))
+                    })?;
+                if let Some(removed) = removed {
+                    collection.strings_removed += removed;
+                    next_scan_phase(keys, batch_size, CollectionPhase::Sweeping, || {
+                        CollectionPhase::Finished
+                    })
+                } else {
+                    collection.version = None; // Restarts at the next step
+                    CollectionPhase::Marking(0, Vec::new())
+                }
+            }
+            CollectionPhase::Finished => CollectionPhase::Finished,
+        };
+        if collection.is_finished() {
+            collection.used = HashSet::new();
+        }
+        Ok(collection.is_finished())
+    }
+
+    /// Reads the next at most `batch_size` quads of a scan of DSPO and then of GSPO
+    ///
+    /// The quads are grouped by graph and subject.
+    /// If GSPO is disabled, SPOG is scanned instead and the quads are only grouped by subject.
+    /// The quads written during the scan are returned if they are after the scan position.
+    /// Returns an empty vector when the scan is finished.
+    pub fn scan_quads_step(
+        &self,
+        scan: &mut QuadScanState,
+        batch_size: usize,
+    ) -> Result<Vec<EncodedQuad>, StorageError> {
+        let batch_size = batch_size.max(1);
+        loop {
+            let (cf, encoding, from) = match &scan.phase {
+                QuadScanPhase::Default(from) => (&self.dspo_cf, QuadEncoding::Dspo, from),
+                QuadScanPhase::Named(from) => {
+                    if self.is_index_enabled(&self.gspo_cf) {
+                        (&self.gspo_cf, QuadEncoding::Gspo, from)
+                    } else {
+                        (&self.spog_cf, QuadEncoding::Spog, from)
+                    }
+                }
+                QuadScanPhase::Finished => return Ok(Vec::new()),
+            };
+            let keys = self.db.snapshot().keys_from(cf, from, batch_size)?;
+            let quads = keys
+                .iter()
+                .map(|key| encoding.decode(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            scan.phase = match &scan.phase {
+                QuadScanPhase::Default(_) => {
+                    next_scan_phase(keys, batch_size, QuadScanPhase::Default, || {
+                        QuadScanPhase::Named(Vec::new())
+                    })
+                }
+                _ => next_scan_phase(keys, batch_size, QuadScanPhase::Named, || {
+                    QuadScanPhase::Finished
+                }),
+            };
+            if !quads.is_empty() {
+                return Ok(quads);
+            }
+        }
+    }
+
+    /// Does a step of a compaction of the write-ahead log, appending to it at most `batch_size` entries
+    ///
+    /// The current content of all the column families is appended to the log as insertions,
+    /// so that once the compaction is finished replaying the transactions logged since its start into an empty storage
+    /// gives back the storage content.
+    /// The log should have been replaced by an empty one before the first step.
+    /// Returns `true` when the compaction is finished.
+    pub fn compact_log_step(
+        &self,
+        compaction: &mut LogCompactionState,
+        batch_size: usize,
+    ) -> Result<bool, StorageError> {
+        let batch_size = batch_size.max(1);
+        let cfs = self.all_cfs();
+        let cf = if let Some((_, cf)) = cfs.get(compaction.column_family) {
+            *cf
+        } else {
+            return Ok(true);
+        };
+        // The entries are read and logged while the writes are blocked so that they are logged in the right order
+        let keys = self.db.transaction(None, |transaction| {
+            let reader = transaction.reader();
+            let keys = reader.keys_from(cf, &compaction.from, batch_size)?;
+            let mut writes = Vec::with_capacity(keys.len());
+            for key in &keys {
+                if let Some(value) = reader.get(cf, key)? {
+                    writes.push(LoggedWrite::Insert {
+                        column_family: cf.clone(),
+                        key: key.clone(),
+                        value: value.to_vec(),
+                    });
+                }
+            }
+            if !writes.is_empty() {
+                self.append_to_log(&writes)?;
+            }
+            Ok::<_, StorageError>(keys)
+        })?;
+        compaction.entries_logged += keys.len() as u64;
+        match keys.last() {
+            Some(last) if keys.len() == batch_size => {
+                compaction.from = last.clone();
+                compaction.from.push(0); // The smallest key after the last one
+            }
+            _ => {
+                compaction.column_family += 1;
+                compaction.from.clear();
+                compaction.finished = compaction.column_family >= cfs.len();
+            }
+        }
+        Ok(compaction.is_finished())
+    }
+}
+
+fn add_str_hashes(term: &EncodedTerm, hashes: &mut HashSet<StrHash>) {
+    match term {
+        EncodedTerm::NamedNode { iri_id } => {
+            hashes.insert(*iri_id);
+        }
+        EncodedTerm::BigBlankNode { id_id } => {
+            hashes.insert(*id_id);
+        }
+        EncodedTerm::BigStringLiteral { value_id }
+        | EncodedTerm::BigSmallLangStringLiteral { value_id, .. } => {
+            hashes.insert(*value_id);
+        }
+        EncodedTerm::SmallBigLangStringLiteral { language_id, .. } => {
+            hashes.insert(*language_id);
+        }
+        EncodedTerm::BigBigLangStringLiteral {
+            value_id,
+            language_id,
+        } => {
+            hashes.insert(*value_id);
+            hashes.insert(*language_id);
+        }
+        EncodedTerm::SmallTypedLiteral { datatype_id, .. } => {
+            hashes.insert(*datatype_id);
+        }
+        EncodedTerm::BigTypedLiteral {
+            value_id,
+            datatype_id,
+        } => {
+            hashes.insert(*value_id);
+            hashes.insert(*datatype_id);
+        }
+        EncodedTerm::Triple(triple) => {
+            add_str_hashes(&triple.subject, hashes);
+            add_str_hashes(&triple.predicate, hashes);
+            add_str_hashes(&triple.object, hashes);
+        }
+        _ => (),
+    }
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> Result<(), StorageError> {
+    loop {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(());
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn write_bytes(writer: &mut impl Write, value: &[u8]) -> Result<(), StorageError> {
+    write_varint(writer, value.len() as u64)?;
+    writer.write_all(value)?;
+    Ok(())
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, StorageError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CorruptionError::msg("Too long variable length integer in the snapshot").into())
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>, StorageError> {
+    let len = usize::try_from(read_varint(reader)?)
+        .map_err(|_| CorruptionError::msg("Too long byte string in the snapshot"))?;
+    let mut buffer = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut buffer)?;
+    if buffer.len() != len {
+        return Err(CorruptionError::msg("Unexpected end of the snapshot").into());
+    }
+    Ok(buffer)
+}
+
+/// The state of a rebuild of the secondary indexes done by [`Storage::rebuild_indexes_step`]
+pub struct IndexRebuildState {
+    phase: RebuildPhase,
+    quads_processed: u64,
+}
+
+impl Default for IndexRebuildState {
+    fn default() -> Self {
+        Self {
+            phase: RebuildPhase::Clearing(0),
+            quads_processed: 0,
+        }
+    }
+}
+
+impl IndexRebuildState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase, RebuildPhase::Finished)
+    }
+
+    /// Number of quads of the primary indexes already indexed again
+    pub fn quads_processed(&self) -> u64 {
+        self.quads_processed
+    }
+}
+
+enum RebuildPhase {
+    /// Clearing the secondary column family with the given index
+    Clearing(usize),
+    /// Scanning SPOG from the given key
+    Named(Vec<u8>),
+    /// Scanning DSPO from the given key
+    Default(Vec<u8>),
+    Finished,
+}
+
+fn next_scan_phase<P>(
+    mut keys: Vec<Vec<u8>>,
+    batch_size: usize,
+    same: impl FnOnce(Vec<u8>) -> P,
+    next: impl FnOnce() -> P,
+) -> P {
+    match keys.pop() {
+        Some(mut last) if keys.len() + 1 == batch_size => {
+            last.push(0); // The smallest key after the last one
+            same(last)
+        }
+        _ => next(),
+    }
+}
+
+/// The state of a collection of the unused strings done by [`Storage::collect_strings_step`]
+#[derive(Default)]
+pub struct StringCollectionState {
+    phase: CollectionPhase,
+    /// The dataset version the marking has started at
+    version: Option<u64>,
+    used: HashSet<StrHash>,
+    strings_removed: u64,
+}
+
+impl StringCollectionState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase, CollectionPhase::Finished)
+    }
+
+    /// Number of strings removed so far
+    pub fn strings_removed(&self) -> u64 {
+        self.strings_removed
+    }
+}
+
+enum CollectionPhase {
+    /// Marking the strings used by SPOG, DSPO, the named graphs or the change log from the given key
+    Marking(usize, Vec<u8>),
+    /// Removing the unmarked strings from the given key
+    Sweeping(Vec<u8>),
+    Finished,
+}
+
+impl Default for CollectionPhase {
+    fn default() -> Self {
+        Self::Marking(0, Vec::new())
+    }
+}
+
+/// The state of a scan done by [`Storage::scan_quads_step`]
+#[derive(Default)]
+pub struct QuadScanState {
+    phase: QuadScanPhase,
+}
+
+impl QuadScanState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.phase, QuadScanPhase::Finished)
+    }
+}
+
+enum QuadScanPhase {
+    /// Scanning DSPO from the given key
+    Default(Vec<u8>),
+    /// Scanning GSPO, or SPOG if GSPO is disabled, from the given key
+    Named(Vec<u8>),
+    Finished,
+}
+
+impl Default for QuadScanPhase {
+    fn default() -> Self {
+        Self::Default(Vec::new())
+    }
+}
+
+/// The state of a compaction of the write-ahead log done by [`Storage::compact_log_step`]
+#[derive(Default)]
+pub struct LogCompactionState {
+    /// The index of the column family being logged in [`Storage::all_cfs`]
+    column_family: usize,
+    from: Vec<u8>,
+    entries_logged: u64,
+    finished: bool,
+}
+
+impl LogCompactionState {
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Number of entries appended to the log so far
+    pub fn entries_logged(&self) -> u64 {
+        self.entries_logged
+    }
+}
+
+/// Memory kept for the next write transaction
+struct Reservation {
+    quads: usize,
+    memory: Vec<u8>,
+}
+
+#[derive(Default)]
+struct GraphLocks {
+    next_owner: u64,
+    owners: HashMap<u64, LockedGraphs>,
+}
+
+/// Graphs locked by [`Storage::lock_graphs`]
+#[derive(Debug, Clone)]
+pub enum LockedGraphs {
+    All,
+    Graphs(Vec<EncodedTerm>),
+}
+
+impl LockedGraphs {
+    fn contains(&self, graph_name: &EncodedTerm) -> bool {
+        match self {
+            Self::All => true,
+            Self::Graphs(graphs) => graphs.contains(graph_name),
+        }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        match other {
+            Self::All => true,
+            Self::Graphs(graphs) => graphs.iter().any(|graph_name| self.contains(graph_name)),
+        }
+    }
+}
+
+/// The isolation level of a transaction.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub enum IsolationLevel {
+    /// The transaction only sees the data committed before it started and its own writes
+    /// and the other writes are blocked until it ends.
+    ///
+    /// This is the default.
+    RepeatableRead,
+    /// The transaction does not hold the write lock for its whole duration: each read sees the latest written data,
+    /// including the writes of the other transactions of this level that are not finished yet,
+    /// and each write takes the write lock to be applied and visible immediately.
+    ///
+    /// It is not cheaper than [`RepeatableRead`](Self::RepeatableRead), each write locking the data again,
+    /// but the other transactions are not blocked between its writes.
+    /// If it fails, its writes are rolled back but they may have already been seen by the other readers.
+    ReadUncommitted,
+}
+
+impl Default for IsolationLevel {
+    #[inline]
+    fn default() -> Self {
+        Self::RepeatableRead
+    }
+}
+
+#[derive(Clone)]
+pub struct StorageReader {
+    reader: Reader,
+    storage: Storage,
+    /// The index writes not applied yet of the transaction the reader belongs to
+    index_batch: Option<Rc<RefCell<IndexWriteBatch>>>,
+}
+
+impl StorageReader {
+    pub fn len(&self) -> Result<usize, StorageError> {
+        Ok(self
+            .stored_len()?
+            .saturating_sub(self.expired_len(|_| true)?))
+    }
+
+    /// Returns the number of stored quads, the expired ones not removed yet included
+    fn stored_len(&self) -> Result<usize, StorageError> {
+        usize::try_from(decode_count(
+            self.reader
+                .get(&self.storage.stats_cf, &[QUAD_COUNT_PREFIX])?
+                .as_deref(),
+        )?)
+        .map_err(|_| CorruptionError::msg("The number of quads does not fit in memory").into())
+    }
+
+    /// Returns the number of quads in the given graph, the default graph included
+    pub fn graph_len(&self, graph_name: &EncodedTerm) -> Result<usize, StorageError> {
+        let len = usize::try_from(self.stored_graph_len(graph_name)?)
+            .map_err(|_| CorruptionError::msg("The number of quads does not fit in memory"))?;
+        Ok(len.saturating_sub(self.expired_len(|quad| quad.graph_name == *graph_name)?))
+    }
+
+    /// Returns the number of stored quads in the given graph, the expired ones not removed yet included
+    fn stored_graph_len(&self, graph_name: &EncodedTerm) -> Result<u64, StorageError> {
+        let mut key = vec![GRAPH_COUNT_PREFIX];
+        write_term(&mut key, graph_name);
+        decode_count(self.reader.get(&self.storage.stats_cf, &key)?.as_deref())
+    }
+
+    /// Returns the number of bytes of the strings stored in the dictionary
+    pub fn dictionary_size(&self) -> Result<u64, StorageError> {
+        decode_count(
+            self.reader
+                .get(&self.storage.default_cf, DICTIONARY_SIZE_KEY)?
+                .as_deref(),
+        )
+    }
+
+    pub fn is_empty(&self) -> Result<bool, StorageError> {
+        if !self.reader.is_empty(&self.storage.expirations_cf)? {
+            return Ok(self.len()? == 0);
+        }
+        Ok(self.reader.is_empty(&self.storage.spog_cf)?
+            && self.reader.is_empty(&self.storage.dspo_cf)?)
+    }
+
+    pub fn contains(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
+        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        let contains = if quad.graph_name.is_default_graph() {
+            write_spo_quad(&mut buffer, quad);
+            self.reader.contains_key(&self.storage.dspo_cf, &buffer)?
+        } else {
+            write_spog_quad(&mut buffer, quad);
+            self.reader.contains_key(&self.storage.spog_cf, &buffer)?
+        };
+        Ok(contains
+            && !self.is_expired(quad)?
+            && !self
+                .redaction()
+                .map_or(false, |redaction| redaction.hides(quad)))
+    }
+
+    /// Checks if a [read filter](Storage::set_read_filter) is set
+    pub fn has_read_filter(&self) -> bool {
+        self.storage.read_filter.read().unwrap().is_some()
+    }
+
+    fn redaction(&self) -> Option<Redaction> {
+        let filter = self.storage.read_filter.read().unwrap().clone()?;
+        Some(Redaction {
+            reader: self.clone(),
+            filter,
+        })
+    }
+
+    /// Returns the expiration time of the quad in nanoseconds since the epoch, `None` if it does not expire
+    pub fn expiration(&self, quad: &EncodedQuad) -> Result<Option<u64>, StorageError> {
+        self.reader
+            .get(&self.storage.expirations_cf, &expiration_by_quad_key(quad))?
+            .map(|value| decode_expiration(&value))
+            .transpose()
+    }
+
+    fn is_expired(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
+        Ok(if let Some(expires_at) = self.expiration(quad)? {
+            expires_at <= self.storage.now()
+        } else {
+            false
+        })
+    }
+
+    /// Returns the check of the expiration of the quads if some quads have expired but are not removed yet
+    ///
+    /// The current time is only read if some quads have an expiration.
+    fn expiry(&self) -> Result<Option<Expiry>, StorageError> {
+        if self.reader.is_empty(&self.storage.expirations_cf)? {
+            return Ok(None);
+        }
+        let now = self.storage.now();
+        Ok(if self.expired_quads_until(now, 1)?.is_empty() {
+            None
+        } else {
+            Some(Expiry {
+                reader: self.clone(),
+                now,
+            })
+        })
+    }
+
+    /// Returns the number of quads matching `filter` that have expired but are not removed yet
+    fn expired_len(&self, filter: impl Fn(&EncodedQuad) -> bool) -> Result<usize, StorageError> {
+        if self.reader.is_empty(&self.storage.expirations_cf)? {
+            return Ok(0);
+        }
+        let mut len = 0;
+        self.for_each_expired_quad(self.storage.now(), usize::MAX, |quad| {
+            if filter(&quad) {
+                len += 1;
+            }
+        })?;
+        Ok(len)
+    }
+
+    /// Returns at most `limit` quads expired at `now` in nanoseconds since the epoch, the oldest expirations first
+    pub fn expired_quads_until(
+        &self,
+        now: u64,
+        limit: usize,
+    ) -> Result<Vec<EncodedQuad>, StorageError> {
+        let mut quads = Vec::new();
+        self.for_each_expired_quad(now, limit, |quad| quads.push(quad))?;
+        Ok(quads)
+    }
+
+    /// Calls `f` on at most `limit` quads expired at `now`, the oldest expirations first, and returns their number
+    fn for_each_expired_quad(
+        &self,
+        now: u64,
+        limit: usize,
+        mut f: impl FnMut(EncodedQuad),
+    ) -> Result<usize, StorageError> {
+        let mut count = 0;
+        let mut from = vec![EXPIRATION_BY_TIME_PREFIX];
+        loop {
+            let keys = self.reader.keys_from(
+                &self.storage.expirations_cf,
+                &from,
+                RANGE_SCAN_BATCH_SIZE.min(limit - count),
+            )?;
+            for key in &keys {
+                if key.first() != Some(&EXPIRATION_BY_TIME_PREFIX) {
+                    return Ok(count);
+                }
+                let (expires_at, quad) = decode_expiration_by_time_key(key)?;
+                if expires_at > now {
+                    return Ok(count);
+                }
+                f(quad);
+                count += 1;
+            }
+            match keys.last() {
+                Some(last) if count < limit && keys.len() == RANGE_SCAN_BATCH_SIZE => {
+                    from.clone_from(last);
+                    from.push(0);
+                }
+                _ => return Ok(count),
+            }
+        }
+    }
+
+    /// Returns the quads matching the pattern, the expired quads not removed yet and the quads hidden by the read filter being skipped
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> ChainedDecodingQuadIterator {
+        let mut iter = self.all_quads_for_pattern(subject, predicate, object, graph_name);
+        match self.expiry() {
+            Ok(expiry) => iter.expiry = expiry,
+            Err(error) => iter.error = Some(error),
+        }
+        iter.redaction = self.redaction();
+        iter
+    }
+
+    /// Returns at most `limit` quads of `graph_name`, of all the graphs if `None`, in the order of the primary indexes,
+    /// the first one being at `position` or after it, the expired quads and the ones hidden by the read filter being skipped
+    ///
+    /// Each quad is returned with its position, a tag of the index followed by its key in the index,
+    /// so a scan can be resumed with a range lookup from the position following the last returned quad.
+    pub fn quads_from_position(
+        &self,
+        graph_name: Option<&EncodedTerm>,
+        position: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, EncodedQuad)>, StorageError> {
+        let default_graph = (0, &self.storage.dspo_cf, QuadEncoding::Dspo, Vec::new());
+        let named_graphs = (1, &self.storage.spog_cf, QuadEncoding::Spog, Vec::new());
+        let sources = match graph_name {
+            None => vec![default_graph, named_graphs],
+            Some(graph_name) if graph_name.is_default_graph() => vec![default_graph],
+            Some(graph_name) if self.storage.is_index_enabled(&self.storage.gspo_cf) => vec![(
+                1,
+                &self.storage.gspo_cf,
+                QuadEncoding::Gspo,
+                encode_term(graph_name),
+            )],
+            Some(_) => vec![named_graphs],
+        };
+        let expiry = self.expiry()?;
+        let redaction = self.redaction();
+        let (start_tag, start_key) = position.split_first().unwrap_or((&0, &[][..]));
+        let mut quads = Vec::new();
+        for (tag, cf, encoding, prefix) in sources {
+            if tag < *start_tag {
+                continue;
+            }
+            let mut from = if tag == *start_tag && start_key > prefix.as_slice() {
+                start_key.to_vec()
+            } else {
+                prefix.clone()
+            };
+            loop {
+                let keys = self.reader.keys_from(cf, &from, RANGE_SCAN_BATCH_SIZE)?;
+                for key in &keys {
+                    if !key.starts_with(&prefix) {
+                        break;
+                    }
+                    let quad = encoding.decode(key)?;
+                    if graph_name.map_or(false, |graph_name| quad.graph_name != *graph_name)
+                        || redaction
+                            .as_ref()
+                            .map_or(false, |redaction| redaction.hides(&quad))
+                    {
+                        continue;
+                    }
+                    if let Some(expiry) = &expiry {
+                        if expiry.hides(&quad)? {
+                            continue;
+                        }
+                    }
+                    let mut position = vec![tag];
+                    position.extend_from_slice(key);
+                    quads.push((position, quad));
+                    if quads.len() == limit {
+                        return Ok(quads);
+                    }
+                }
+                match keys.last() {
+                    Some(last)
+                        if keys.len() == RANGE_SCAN_BATCH_SIZE && last.starts_with(&prefix) =>
+                    {
+                        from.clone_from(last);
+                        from.push(0);
+                    }
+                    _ => break,
+                }
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Returns the number of quads matching the pattern, the expired quads not removed yet being skipped
+    ///
+    /// The counts of the statistics are used if only the graph name is set and there is no read filter.
+    /// Otherwise, the index keys are counted without decoding them.
+    pub fn count_quads_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<usize, StorageError> {
+        match (subject, predicate, object, graph_name) {
+            (None, None, None, None) if !self.has_read_filter() => self.len(),
+            (None, None, None, Some(graph_name)) if !self.has_read_filter() => {
+                self.graph_len(graph_name)
+            }
+            _ => self
+                .quads_for_pattern(subject, predicate, object, graph_name)
+                .count_quads(),
+        }
+    }
+
+    fn all_quads_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> ChainedDecodingQuadIterator {
+        match subject {
             Some(subject) => match predicate {
                 Some(predicate) => match object {
                     Some(object) => match graph_name {
@@ -429,6 +2904,74 @@ impl StorageReader {
         }
     }
 
+    /// Returns the distinct terms at `position` (`0` for the subject, `1` for the predicate and `2` for the object)
+    /// of the quads of `graph_name` matching the pattern
+    ///
+    /// They are read from an index starting with the set terms followed by the one at `position`,
+    /// seeking past the entries of each term instead of scanning them.
+    /// Returns `None` if there is no such enabled index or if some quads, expired or written by the transaction
+    /// but not indexed yet, prevent to use it.
+    pub fn distinct_terms_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: &EncodedTerm,
+        position: usize,
+    ) -> Option<DistinctTermIterator> {
+        if self.has_read_filter() || self.expiry().ok()?.is_some() {
+            return None;
+        }
+        let terms = [subject, predicate, object, Some(graph_name)];
+        if terms.get(position)?.is_some() {
+            return None;
+        }
+        let indexes = if graph_name.is_default_graph() {
+            [
+                (QuadEncoding::Dspo, &self.storage.dspo_cf),
+                (QuadEncoding::Dpos, &self.storage.dpos_cf),
+                (QuadEncoding::Dosp, &self.storage.dosp_cf),
+            ]
+        } else {
+            [
+                (QuadEncoding::Gspo, &self.storage.gspo_cf),
+                (QuadEncoding::Gpos, &self.storage.gpos_cf),
+                (QuadEncoding::Gosp, &self.storage.gosp_cf),
+            ]
+        };
+        indexes.into_iter().find_map(|(encoding, column_family)| {
+            let positions = encoding.term_positions();
+            let known = positions
+                .iter()
+                .take_while(|p| terms[**p].is_some())
+                .count();
+            if positions.get(known) != Some(&position)
+                || positions[known..].iter().any(|p| terms[*p].is_some())
+                || !self.storage.is_index_enabled(column_family)
+                || self.index_batch.as_ref().map_or(false, |index_batch| {
+                    index_batch.borrow().writes.contains_key(column_family)
+                })
+            {
+                return None;
+            }
+            let mut prefix = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
+            for p in &positions[..known] {
+                write_term(&mut prefix, terms[*p]?);
+            }
+            Some(DistinctTermIterator {
+                reader: self.reader.clone(),
+                column_family: column_family.clone(),
+                start: Some(prefix.clone()),
+                prefix,
+            })
+        })
+    }
+
+    /// The work done by the index scans of the reader
+    pub fn scan_stats(&self) -> Rc<ScanStats> {
+        self.reader.scan_stats()
+    }
+
     pub fn contains_named_graph(&self, graph_name: &EncodedTerm) -> Result<bool, StorageError> {
         self.reader
             .contains_key(&self.storage.graphs_cf, &encode_term(graph_name))
@@ -446,114 +2989,895 @@ impl StorageReader {
         self.inner_quads(&self.storage.ospg_cf, prefix, QuadEncoding::Ospg)
     }
 
-    fn gspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gspo_cf, prefix, QuadEncoding::Gspo)
-    }
+    fn gspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gspo_cf, prefix, QuadEncoding::Gspo)
+    }
+
+    fn gpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gpos_cf, prefix, QuadEncoding::Gpos)
+    }
+
+    fn gosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gosp_cf, prefix, QuadEncoding::Gosp)
+    }
+
+    fn dspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.dspo_cf, prefix, QuadEncoding::Dspo)
+    }
+
+    fn dpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.dpos_cf, prefix, QuadEncoding::Dpos)
+    }
+
+    fn dosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.dosp_cf, prefix, QuadEncoding::Dosp)
+    }
+
+    fn inner_quads(
+        &self,
+        column_family: &ColumnFamily,
+        prefix: &[u8],
+        encoding: QuadEncoding,
+    ) -> DecodingQuadIterator {
+        if !self.storage.is_index_enabled(column_family) {
+            return self.filtered_quads(prefix, encoding);
+        }
+        let iter = self.reader.scan_prefix(column_family, prefix).unwrap(); // TODO: propagate error?
+        let writes = self
+            .index_batch
+            .as_ref()
+            .map_or_else(Vec::new, |index_batch| {
+                index_batch
+                    .borrow()
+                    .writes_with_prefix(column_family, prefix)
+            });
+        DecodingQuadIterator {
+            iter: PatchedIter::new(iter, writes),
+            encoding,
+            filter: None,
+            returned: false,
+        }
+    }
+
+    /// Scans the primary index instead of a disabled one and filters out the quads not matching the prefix
+    fn filtered_quads(&self, prefix: &[u8], encoding: QuadEncoding) -> DecodingQuadIterator {
+        let mut filter: [Option<EncodedTerm>; 4] = Default::default();
+        for (term, position) in decode_terms(prefix)
+            .unwrap() // The prefixes are always built from valid terms
+            .into_iter()
+            .zip(encoding.term_positions())
+        {
+            filter[*position] = Some(term);
+        }
+        let (column_family, encoding) =
+            if matches!(encoding, QuadEncoding::Dpos | QuadEncoding::Dosp) {
+                (&self.storage.dspo_cf, QuadEncoding::Dspo)
+            } else {
+                (&self.storage.spog_cf, QuadEncoding::Spog)
+            };
+        // We scan the longest prefix of the primary index made of the known terms
+        let mut scan_prefix = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        for term in filter.iter().map_while(Option::as_ref) {
+            write_term(&mut scan_prefix, term);
+        }
+        DecodingQuadIterator {
+            iter: PatchedIter::new(
+                self.reader
+                    .scan_prefix(column_family, &scan_prefix)
+                    .unwrap(), // TODO: propagate error?
+                Vec::new(),
+            ),
+            encoding,
+            filter: Some(filter),
+            returned: false,
+        }
+    }
+
+    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+        Ok(self
+            .reader
+            .get(&self.storage.id2str_cf, &key.to_be_bytes())?
+            .map(|value| decode_str(&value))
+            .transpose()?)
+    }
+
+    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
+        self.reader
+            .contains_key(&self.storage.id2str_cf, &key.to_be_bytes())
+    }
+
+    /// The dictionary entry of a string as stored, possibly compressed
+    pub fn get_encoded_str(&self, key: &StrHash) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self
+            .reader
+            .get(&self.storage.id2str_cf, &key.to_be_bytes())?
+            .map(|value| value.to_vec()))
+    }
+
+    /// Validates that all the storage invariants held in the data
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    pub fn graph_digest(&self, graph_name: &EncodedTerm) -> Result<u128, StorageError> {
+        decode_graph_digest(
+            self.reader
+                .get(&self.storage.default_cf, &graph_digest_key(graph_name))?
+                .as_deref(),
+        )
+    }
+
+    /// Returns the number of quads with the given predicate and, if set, an object of the given class
+    pub fn predicate_count(
+        &self,
+        predicate: &EncodedTerm,
+        object_class: Option<ObjectClass>,
+    ) -> Result<u64, StorageError> {
+        let key = if let Some(object_class) = object_class {
+            object_class_count_key(predicate, object_class)
+        } else {
+            let mut key = vec![PREDICATE_COUNT_PREFIX];
+            write_term(&mut key, predicate);
+            key
+        };
+        decode_count(self.reader.get(&self.storage.stats_cf, &key)?.as_deref())
+    }
+
+    /// Returns the literals with the given datatype used as object, `None` if the literal index is disabled
+    pub fn literals_with_datatype(
+        &self,
+        datatype: &EncodedTerm,
+    ) -> Result<Option<Vec<EncodedTerm>>, StorageError> {
+        self.literal_index_lookup(DATATYPE_ENTRY_PREFIX, datatype)
+    }
+
+    /// Returns the literals with the given language tag used as object, `None` if the literal index is disabled
+    pub fn literals_with_language(
+        &self,
+        language: &str,
+    ) -> Result<Option<Vec<EncodedTerm>>, StorageError> {
+        self.literal_index_lookup(
+            LANGUAGE_ENTRY_PREFIX,
+            &LiteralRef::new_simple_literal(language).into(),
+        )
+    }
+
+    fn literal_index_lookup(
+        &self,
+        prefix: u8,
+        tag: &EncodedTerm,
+    ) -> Result<Option<Vec<EncodedTerm>>, StorageError> {
+        if !self.storage.is_index_enabled(&self.storage.literals_cf) {
+            return Ok(None);
+        }
+        let mut key_prefix = vec![prefix];
+        write_term(&mut key_prefix, tag);
+        let mut literals = Vec::new();
+        let mut iter = self
+            .reader
+            .scan_prefix(&self.storage.literals_cf, &key_prefix)?;
+        while let Some(key) = iter.key() {
+            literals.push(decode_term(&key[key_prefix.len()..])?);
+            iter.next();
+        }
+        iter.status()?;
+        Ok(Some(literals))
+    }
+
+    /// Returns the quads with the given predicate and a numeric or `xsd:dateTime` object in the given range
+    ///
+    /// The bounds are compared with the objects following the SPARQL `<` operator.
+    /// The range index is used if enabled, else all the quads with the predicate are scanned.
+    pub fn quads_in_range(
+        &self,
+        predicate: &EncodedTerm,
+        start: Bound<&EncodedTerm>,
+        end: Bound<&EncodedTerm>,
+    ) -> Result<Vec<EncodedQuad>, StorageError> {
+        let mut quads = Vec::new();
+        if self.storage.is_index_enabled(&self.storage.ranges_cf) {
+            self.scan_range_index(predicate, start, end, |object, subject, graph_name| {
+                quads.push(EncodedQuad::new(
+                    subject,
+                    predicate.clone(),
+                    object,
+                    graph_name,
+                ));
+                true
+            })?;
+        } else {
+            for quad in self.quads_for_predicate(predicate) {
+                let quad = quad?;
+                if is_in_range(&quad.object, start, end) {
+                    quads.push(quad);
+                }
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Returns the distinct objects of the given predicate in the given range
+    ///
+    /// Returns `None` if the range index is disabled or if there are more than `limit` such objects.
+    pub fn objects_in_range(
+        &self,
+        predicate: &EncodedTerm,
+        start: Bound<&EncodedTerm>,
+        end: Bound<&EncodedTerm>,
+        limit: usize,
+    ) -> Result<Option<Vec<EncodedTerm>>, StorageError> {
+        if !self.storage.is_index_enabled(&self.storage.ranges_cf) {
+            return Ok(None);
+        }
+        let mut objects = Vec::<EncodedTerm>::new();
+        let complete = self.scan_range_index(predicate, start, end, |object, _, _| {
+            if objects.last() != Some(&object) {
+                objects.push(object);
+            }
+            objects.len() <= limit
+        })?;
+        Ok(complete.then(|| objects))
+    }
+
+    /// Calls `f` with the object, subject and graph name of the range index entries of `predicate` in the range
+    /// until it returns `false`
+    ///
+    /// Returns `false` if the scan has been stopped by `f`.
+    fn scan_range_index(
+        &self,
+        predicate: &EncodedTerm,
+        start: Bound<&EncodedTerm>,
+        end: Bound<&EncodedTerm>,
+        mut f: impl FnMut(EncodedTerm, EncodedTerm, EncodedTerm) -> bool,
+    ) -> Result<bool, StorageError> {
+        let predicate = encode_term(predicate);
+        for (kind, low, high) in range_scan_bounds(start, end) {
+            let mut prefix = predicate.clone();
+            prefix.push(kind);
+            let mut from = prefix.clone();
+            from.extend_from_slice(&low);
+            loop {
+                let keys =
+                    self.reader
+                        .keys_from(&self.storage.ranges_cf, &from, RANGE_SCAN_BATCH_SIZE)?;
+                for key in &keys {
+                    if !key.starts_with(&prefix) || key[prefix.len()..prefix.len() + 8] > high[..] {
+                        return Ok(true);
+                    }
+                    let mut terms = decode_terms(&key[prefix.len() + 8..])?.into_iter();
+                    if let (Some(object), Some(subject), Some(graph_name)) =
+                        (terms.next(), terms.next(), terms.next())
+                    {
+                        if is_in_range(&object, start, end) && !f(object, subject, graph_name) {
+                            return Ok(false);
+                        }
+                    } else {
+                        return Err(CorruptionError::msg("Invalid range index key").into());
+                    }
+                }
+                match keys.last() {
+                    Some(last) if keys.len() == RANGE_SCAN_BATCH_SIZE => {
+                        from.clone_from(last);
+                        from.push(0);
+                    }
+                    _ => break,
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns all the counts maintained in the statistics column family
+    pub fn statistics(&self) -> Result<EncodedStatistics, StorageError> {
+        let mut statistics = EncodedStatistics::default();
+        let mut iter = self.reader.scan_prefix(&self.storage.stats_cf, &[])?;
+        while let Some(key) = iter.key() {
+            let count = decode_count(iter.value())?;
+            match key.split_first() {
+                Some((&PREDICATE_COUNT_PREFIX, predicate)) => {
+                    statistics.predicates.push((decode_term(predicate)?, count));
+                }
+                Some((&GRAPH_COUNT_PREFIX, graph_name)) => {
+                    statistics.graphs.push((decode_term(graph_name)?, count));
+                }
+                Some((&QUAD_COUNT_PREFIX, [])) => statistics.quads = count,
+                Some((&OBJECT_CLASS_COUNT_PREFIX, [predicate @ .., object_class])) => {
+                    statistics.object_classes.push((
+                        decode_term(predicate)?,
+                        ObjectClass::from_byte(*object_class)?,
+                        count,
+                    ));
+                }
+                _ => return Err(CorruptionError::msg("Invalid statistics key").into()),
+            }
+            iter.next();
+        }
+        iter.status()?;
+        Ok(statistics)
+    }
+
+    /// Returns the sum of the hashes of all the quads
+    pub fn dataset_digest(&self) -> Result<u128, StorageError> {
+        decode_graph_digest(
+            self.reader
+                .get(&self.storage.default_cf, DATASET_DIGEST_KEY)?
+                .as_deref(),
+        )
+    }
+
+    /// Returns the number of transactions that have written into the store
+    pub fn commit_version(&self) -> Result<u64, StorageError> {
+        decode_commit_version(
+            self.reader
+                .get(&self.storage.default_cf, COMMIT_VERSION_KEY)?
+                .as_deref(),
+        )
+    }
+
+    /// Returns the number of transactions that have changed the quads of the store plus the number of expired quads not removed yet
+    ///
+    /// An expiration changes the visible quads, so it is counted as a new version.
+    /// The removal of an expired quad increments the stored version to keep the sum growing.
+    pub fn dataset_version(&self) -> Result<u64, StorageError> {
+        Ok(self.stored_dataset_version()? + self.expired_len(|_| true)? as u64)
+    }
+
+    /// Returns the number of transactions that have changed the quads of the store, the version kept in the history
+    fn stored_dataset_version(&self) -> Result<u64, StorageError> {
+        Ok(
+            if let Some(value) = self
+                .reader
+                .get(&self.storage.default_cf, DATASET_VERSION_KEY)?
+            {
+                u64::from_be_bytes(
+                    value[..]
+                        .try_into()
+                        .map_err(|_| CorruptionError::msg("Invalid dataset version encoding"))?,
+                )
+            } else {
+                0
+            },
+        )
+    }
+
+    /// Returns the entries of the change log with a sequence number greater than `sequence`, in sequence order
+    pub fn changes_since(&self, sequence: u64) -> Result<ChangeLogIterator, StorageError> {
+        Ok(ChangeLogIterator {
+            iter: self.reader.scan_range(
+                &self.storage.changes_cf,
+                &sequence.saturating_add(1).to_be_bytes(),
+                None,
+            )?,
+        })
+    }
+
+    fn next_change_sequence(&self) -> Result<u64, StorageError> {
+        Ok(
+            if let Some(value) = self
+                .reader
+                .get(&self.storage.default_cf, NEXT_CHANGE_SEQUENCE_KEY)?
+            {
+                u64::from_be_bytes(value[..].try_into().map_err(|_| {
+                    CorruptionError::msg("Invalid change log sequence number encoding")
+                })?)
+            } else {
+                1
+            },
+        )
+    }
+
+    pub fn get_metadata(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self
+            .reader
+            .get(&self.storage.default_cf, key)?
+            .map(|value| value.to_vec()))
+    }
+
+    /// Returns the metadata entries whose key starts with `prefix`, sorted by key
+    pub fn metadata_with_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        let mut iter = self.reader.scan_prefix(&self.storage.default_cf, prefix)?;
+        let mut entries = Vec::new();
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            entries.push((key.to_vec(), value.to_vec()));
+            iter.next();
+        }
+        iter.status()?;
+        Ok(entries)
+    }
+
+    pub fn validate(&self) -> Result<(), StorageError> {
+        let report = self.check_consistency()?;
+        if report.is_consistent() {
+            Ok(())
+        } else {
+            Err(CorruptionError::new(report).into())
+        }
+    }
+
+    /// Checks that the secondary indexes contain exactly the quads of the primary ones,
+    /// that the strings of the terms are in the dictionary, that the graphs of the quads are in the named graph list
+    /// and that the quad counters are exact.
+    pub fn check_consistency(&self) -> Result<CorruptionReport, StorageError> {
+        let mut report = CorruptionReport::default();
+        let mut checked_strings = HashSet::new();
+        let named_quads = self.check_primary_index(
+            SPOG_CF,
+            &self.storage.spog_cf,
+            QuadEncoding::Spog,
+            &[
+                (
+                    POSG_CF,
+                    &self.storage.posg_cf,
+                    write_posg_quad as fn(&mut Vec<u8>, &EncodedQuad),
+                ),
+                (OSPG_CF, &self.storage.ospg_cf, write_ospg_quad),
+                (GSPO_CF, &self.storage.gspo_cf, write_gspo_quad),
+                (GPOS_CF, &self.storage.gpos_cf, write_gpos_quad),
+                (GOSP_CF, &self.storage.gosp_cf, write_gosp_quad),
+            ],
+            &mut checked_strings,
+            &mut report,
+        )?;
+        let default_quads = self.check_primary_index(
+            DSPO_CF,
+            &self.storage.dspo_cf,
+            QuadEncoding::Dspo,
+            &[
+                (
+                    DPOS_CF,
+                    &self.storage.dpos_cf,
+                    write_pos_quad as fn(&mut Vec<u8>, &EncodedQuad),
+                ),
+                (DOSP_CF, &self.storage.dosp_cf, write_osp_quad),
+            ],
+            &mut checked_strings,
+            &mut report,
+        )?;
+        for graph_name in self.named_graphs() {
+            match graph_name {
+                Ok(graph_name) => {
+                    self.check_strings(&graph_name, &mut checked_strings, &mut report)?
+                }
+                Err(e) => report.add(|| format!("Invalid key in {GRAPHS_CF}: {e}")),
+            }
+        }
+        let quads = named_quads + default_quads;
+        let counted = self.stored_len()?;
+        if counted != quads {
+            report.add(|| {
+                format!("The quad counter is {counted} but the store contains {quads} quads")
+            });
+        }
+        report.set_quads_checked(quads as u64);
+        Ok(report)
+    }
+
+    /// Checks the quads of a primary index and returns their number
+    fn check_primary_index(
+        &self,
+        name: &str,
+        column_family: &ColumnFamily,
+        encoding: QuadEncoding,
+        secondaries: &[(&str, &ColumnFamily, fn(&mut Vec<u8>, &EncodedQuad))],
+        checked_strings: &mut HashSet<StrHash>,
+        report: &mut CorruptionReport,
+    ) -> Result<usize, StorageError> {
+        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        let mut len = 0;
+        let mut iter = self.reader.scan_prefix(column_family, &[])?;
+        while let Some(key) = iter.key() {
+            len += 1;
+            let quad = match encoding.decode(key) {
+                Ok(quad) => quad,
+                Err(e) => {
+                    report.add(|| format!("Invalid key in {name}: {e}"));
+                    iter.next();
+                    continue;
+                }
+            };
+            iter.next();
+            for (secondary_name, secondary, write) in secondaries {
+                if self.storage.is_index_enabled(secondary) {
+                    buffer.clear();
+                    write(&mut buffer, &quad);
+                    if !self.reader.contains_key(secondary, &buffer)? {
+                        report.add(|| {
+                            format!("The quad {quad:?} of {name} is missing from {secondary_name}")
+                        });
+                    }
+                }
+            }
+            if !quad.graph_name.is_default_graph() {
+                buffer.clear();
+                write_term(&mut buffer, &quad.graph_name);
+                if !self.reader.contains_key(&self.storage.graphs_cf, &buffer)? {
+                    report.add(|| {
+                        format!("The graph of the quad {quad:?} is missing from {GRAPHS_CF}")
+                    });
+                }
+            }
+            for term in [
+                &quad.subject,
+                &quad.predicate,
+                &quad.object,
+                &quad.graph_name,
+            ] {
+                self.check_strings(term, checked_strings, report)?;
+            }
+        }
+        iter.status()?;
+        // All the quads of the primary index are in the secondary ones, so they contain extra entries if they are bigger
+        for (secondary_name, secondary, _) in secondaries {
+            let expected = if self.storage.is_index_enabled(secondary) {
+                len
+            } else {
+                0
+            };
+            let actual = self.reader.len(secondary)?;
+            if actual != expected {
+                report.add(|| format!("{secondary_name} contains {actual} entries but {name} contains {expected} quads"));
+            }
+        }
+        Ok(len)
+    }
+
+    /// Checks that the strings of the term are in the dictionary
+    fn check_strings(
+        &self,
+        term: &EncodedTerm,
+        checked_strings: &mut HashSet<StrHash>,
+        report: &mut CorruptionReport,
+    ) -> Result<(), StorageError> {
+        let mut hashes = HashSet::new();
+        add_str_hashes(term, &mut hashes);
+        for hash in hashes {
+            if checked_strings.insert(hash) && !self.contains_str(&hash)? {
+                report.add(|| {
+                    format!("The string {hash:?} of the term {term:?} is missing from {ID2STR_CF}")
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct ChainedDecodingQuadIterator {
+    first: DecodingQuadIterator,
+    second: Option<DecodingQuadIterator>,
+    /// The check of the quads to skip because they have expired
+    expiry: Option<Expiry>,
+    /// The read filter of the quads to skip
+    redaction: Option<Redaction>,
+    /// An error to return first
+    error: Option<StorageError>,
+}
+
+impl ChainedDecodingQuadIterator {
+    fn new(first: DecodingQuadIterator) -> Self {
+        Self {
+            first,
+            second: None,
+            expiry: None,
+            redaction: None,
+            error: None,
+        }
+    }
+
+    fn pair(first: DecodingQuadIterator, second: DecodingQuadIterator) -> Self {
+        Self {
+            first,
+            second: Some(second),
+            expiry: None,
+            redaction: None,
+            error: None,
+        }
+    }
+
+    /// Returns an iterator on the remaining quads in the reverse order
+    #[must_use]
+    pub fn rev(self) -> Self {
+        let (first, second) = match self.second {
+            Some(second) => (second.rev(), Some(self.first.rev())),
+            None => (self.first.rev(), None),
+        };
+        Self {
+            first,
+            second,
+            expiry: self.expiry,
+            redaction: self.redaction,
+            error: self.error,
+        }
+    }
+
+    /// Returns the number of remaining quads, only decoding them if some of them must be skipped
+    pub fn count_quads(mut self) -> Result<usize, StorageError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        if self.redaction.is_some() || self.expiry.is_some() {
+            return self.try_fold(0, |count, quad| quad.map(|_| count + 1));
+        }
+        let mut count = self.first.count_quads()?;
+        if let Some(second) = self.second {
+            count += second.count_quads()?;
+        }
+        Ok(count)
+    }
+}
+
+impl Iterator for ChainedDecodingQuadIterator {
+    type Item = Result<EncodedQuad, StorageError>;
+
+    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        loop {
+            let result = if let Some(result) = self.first.next() {
+                result
+            } else {
+                self.second.as_mut()?.next()?
+            };
+            if let (Ok(quad), Some(expiry)) = (&result, &self.expiry) {
+                match expiry.hides(quad) {
+                    Ok(true) => continue,
+                    Ok(false) => (),
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+            if let (Ok(quad), Some(redaction)) = (&result, &self.redaction) {
+                if redaction.hides(quad) {
+                    continue;
+                }
+            }
+            return Some(result);
+        }
+    }
+}
+
+/// A change recorded in the change log, see [`StorageConfig::with_change_log`]
+#[derive(Debug, Clone)]
+pub enum EncodedChange {
+    InsertedQuad(EncodedQuad),
+    RemovedQuad(EncodedQuad),
+    CreatedGraph(EncodedTerm),
+    DroppedGraph(EncodedTerm),
+}
 
-    fn gpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gpos_cf, prefix, QuadEncoding::Gpos)
+impl EncodedChange {
+    fn terms(&self) -> Vec<&EncodedTerm> {
+        match self {
+            Self::InsertedQuad(quad) | Self::RemovedQuad(quad) => vec![
+                &quad.subject,
+                &quad.predicate,
+                &quad.object,
+                &quad.graph_name,
+            ],
+            Self::CreatedGraph(graph_name) | Self::DroppedGraph(graph_name) => vec![graph_name],
+        }
     }
+}
 
-    fn gosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gosp_cf, prefix, QuadEncoding::Gosp)
+fn encode_change_log_key(sequence: u64, change: &EncodedChange) -> Vec<u8> {
+    let mut key = Vec::with_capacity(9 + 4 * WRITTEN_TERM_MAX_SIZE);
+    key.extend_from_slice(&sequence.to_be_bytes());
+    match change {
+        EncodedChange::InsertedQuad(quad) => {
+            key.push(INSERTED_QUAD_CHANGE);
+            write_spog_quad(&mut key, quad);
+        }
+        EncodedChange::RemovedQuad(quad) => {
+            key.push(REMOVED_QUAD_CHANGE);
+            write_spog_quad(&mut key, quad);
+        }
+        EncodedChange::CreatedGraph(graph_name) => {
+            key.push(CREATED_GRAPH_CHANGE);
+            write_term(&mut key, graph_name);
+        }
+        EncodedChange::DroppedGraph(graph_name) => {
+            key.push(DROPPED_GRAPH_CHANGE);
+            write_term(&mut key, graph_name);
+        }
     }
+    key
+}
 
-    fn dspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.dspo_cf, prefix, QuadEncoding::Dspo)
+fn decode_change_log_key(key: &[u8]) -> Result<(u64, EncodedChange), StorageError> {
+    if key.len() < 9 {
+        return Err(CorruptionError::msg("Too short change log entry").into());
     }
+    let (sequence, key) = key.split_at(8);
+    let sequence = u64::from_be_bytes(
+        sequence
+            .try_into()
+            .map_err(|_| CorruptionError::msg("Invalid change log sequence number encoding"))?,
+    );
+    let change = match key[0] {
+        INSERTED_QUAD_CHANGE => EncodedChange::InsertedQuad(QuadEncoding::Spog.decode(&key[1..])?),
+        REMOVED_QUAD_CHANGE => EncodedChange::RemovedQuad(QuadEncoding::Spog.decode(&key[1..])?),
+        CREATED_GRAPH_CHANGE => EncodedChange::CreatedGraph(decode_term(&key[1..])?),
+        DROPPED_GRAPH_CHANGE => EncodedChange::DroppedGraph(decode_term(&key[1..])?),
+        kind => {
+            return Err(
+                CorruptionError::msg(format!("Invalid change log entry kind {kind}")).into(),
+            )
+        }
+    };
+    Ok((sequence, change))
+}
 
-    fn dpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.dpos_cf, prefix, QuadEncoding::Dpos)
-    }
+/// The entries of the change log read by [`StorageReader::changes_since`]
+pub struct ChangeLogIterator {
+    iter: Iter,
+}
 
-    fn dosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.dosp_cf, prefix, QuadEncoding::Dosp)
-    }
+impl Iterator for ChangeLogIterator {
+    type Item = Result<(u64, EncodedChange), StorageError>;
 
-    fn inner_quads(
-        &self,
-        column_family: &ColumnFamily,
-        prefix: &[u8],
-        encoding: QuadEncoding,
-    ) -> DecodingQuadIterator {
-        DecodingQuadIterator {
-            iter: self.reader.scan_prefix(column_family, prefix).unwrap(), // TODO: propagate error?
-            encoding,
+    fn next(&mut self) -> Option<Result<(u64, EncodedChange), StorageError>> {
+        if let Err(e) = self.iter.status() {
+            return Some(Err(e));
         }
+        let entry = decode_change_log_key(self.iter.key()?);
+        self.iter.next();
+        Some(entry)
     }
+}
 
-    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+/// The [read filter](Storage::set_read_filter) with the reader the filtered quads are read from
+#[derive(Clone)]
+struct Redaction {
+    reader: StorageReader,
+    filter: EncodedReadFilter,
+}
+
+/// Hides the quads expired at `now` but not removed yet, looking up the expiration of each quad
+struct Expiry {
+    reader: StorageReader,
+    now: u64,
+}
+
+impl Expiry {
+    fn hides(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
         Ok(self
             .reader
-            .get(&self.storage.id2str_cf, &key.to_be_bytes())?
-            .map(String::from_utf8)
-            .transpose()
-            .map_err(CorruptionError::new)?)
-    }
-
-    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
-        self.reader
-            .contains_key(&self.storage.id2str_cf, &key.to_be_bytes())
+            .expiration(quad)?
+            .map_or(false, |expires_at| expires_at <= self.now))
     }
+}
 
-    /// Validates that all the storage invariants held in the data
-    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
-    pub fn validate(&self) -> Result<(), StorageError> {
-        Ok(()) //TODO
+impl Redaction {
+    fn hides(&self, quad: &EncodedQuad) -> bool {
+        self.filter.patterns.iter().any(|pattern| {
+            pattern
+                .iter()
+                .zip([
+                    &quad.subject,
+                    &quad.predicate,
+                    &quad.object,
+                    &quad.graph_name,
+                ])
+                .all(|(expected, term)| expected.as_ref().map_or(true, |expected| expected == term))
+        }) && (self.filter.hide)(&self.reader, quad)
     }
 }
 
-pub struct ChainedDecodingQuadIterator {
-    first: DecodingQuadIterator,
-    second: Option<DecodingQuadIterator>,
+pub struct DecodingQuadIterator {
+    iter: PatchedIter,
+    encoding: QuadEncoding,
+    /// The terms the subject, predicate, object and graph name must be equal to if set
+    filter: Option<[Option<EncodedTerm>; 4]>,
+    /// If the current entry of `iter` has already been returned
+    ///
+    /// The scan only moves to the next entry when the next quad is asked for,
+    /// so a consumer stopping after a quad does not make it read more entries.
+    returned: bool,
 }
 
-impl ChainedDecodingQuadIterator {
-    fn new(first: DecodingQuadIterator) -> Self {
+impl DecodingQuadIterator {
+    fn rev(mut self) -> Self {
+        self.skip_returned();
         Self {
-            first,
-            second: None,
+            iter: self.iter.rev(),
+            encoding: self.encoding,
+            filter: self.filter,
+            returned: false,
         }
     }
 
-    fn pair(first: DecodingQuadIterator, second: DecodingQuadIterator) -> Self {
-        Self {
-            first,
-            second: Some(second),
+    fn skip_returned(&mut self) {
+        if self.returned {
+            self.iter.next();
+            self.returned = false;
+        }
+    }
+
+    fn count_quads(mut self) -> Result<usize, StorageError> {
+        if self.filter.is_some() {
+            return self.try_fold(0, |count, quad| quad.map(|_| count + 1));
         }
+        self.skip_returned();
+        let mut count = 0;
+        while self.iter.key().is_some() {
+            count += 1;
+            self.iter.next();
+        }
+        self.iter.status()?;
+        Ok(count)
     }
 }
 
-impl Iterator for ChainedDecodingQuadIterator {
+impl Iterator for DecodingQuadIterator {
     type Item = Result<EncodedQuad, StorageError>;
 
     fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {
-        if let Some(result) = self.first.next() {
-            Some(result)
-        } else if let Some(second) = self.second.as_mut() {
-            second.next()
-        } else {
-            None
+        loop {
+            self.skip_returned();
+            if let Err(e) = self.iter.status() {
+                return Some(Err(e));
+            }
+            let quad = self.encoding.decode(self.iter.key()?);
+            self.returned = true;
+            if let (Ok(quad), Some(filter)) = (&quad, &self.filter) {
+                if !filter
+                    .iter()
+                    .zip([
+                        &quad.subject,
+                        &quad.predicate,
+                        &quad.object,
+                        &quad.graph_name,
+                    ])
+                    .all(|(expected, term)| expected.as_ref().map_or(true, |e| e == term))
+                {
+                    continue;
+                }
+            }
+            return Some(quad);
         }
     }
 }
 
-pub struct DecodingQuadIterator {
-    iter: Iter,
-    encoding: QuadEncoding,
+/// An iterator on the distinct terms following a prefix in an index, seeking past the keys of each of them
+pub struct DistinctTermIterator {
+    reader: Reader,
+    column_family: ColumnFamily,
+    prefix: Vec<u8>,
+    /// The key to look for the next term from, `None` if the end of the prefix has been reached
+    start: Option<Vec<u8>>,
 }
 
-impl Iterator for DecodingQuadIterator {
-    type Item = Result<EncodedQuad, StorageError>;
+impl Iterator for DistinctTermIterator {
+    type Item = Result<EncodedTerm, StorageError>;
 
-    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {
-        if let Err(e) = self.iter.status() {
-            return Some(Err(e));
+    fn next(&mut self) -> Option<Result<EncodedTerm, StorageError>> {
+        let start = self.start.take()?;
+        let key = match self
+            .reader
+            .first_key_from(&self.column_family, &self.prefix, &start)
+        {
+            Ok(key) => key?,
+            Err(error) => return Some(Err(error)),
+        };
+        let term = match decode_term(&key[self.prefix.len()..]) {
+            Ok(term) => term,
+            Err(error) => return Some(Err(error)),
+        };
+        let mut term_prefix = self.prefix.clone();
+        write_term(&mut term_prefix, &term);
+        self.start = prefix_end(term_prefix);
+        Some(Ok(term))
+    }
+}
+
+/// Returns the smallest key greater than all the keys starting with `prefix`, `None` if there is none
+fn prefix_end(mut prefix: Vec<u8>) -> Option<Vec<u8>> {
+    while let Some(last) = prefix.pop() {
+        if last < u8::MAX {
+            prefix.push(last + 1);
+            return Some(prefix);
         }
-        let term = self.encoding.decode(self.iter.key()?);
-        self.iter.next();
-        Some(term)
     }
+    None
 }
 
 pub struct DecodingGraphIterator {
@@ -583,10 +3907,124 @@ impl StrLookup for StorageReader {
     }
 }
 
+/// The writes to the secondary quad indexes of a transaction, applied in key order when the transaction ends
+///
+/// The primary indexes SPOG and DSPO are written immediately because the insertions and removals look up the quads in them.
+#[derive(Default)]
+struct IndexWriteBatch {
+    /// For each column family and key, `true` if the key is inserted and `false` if it is removed
+    writes: HashMap<ColumnFamily, BTreeMap<Vec<u8>, bool>>,
+}
+
+impl IndexWriteBatch {
+    /// Returns the buffered writes to the keys with the given prefix in key order, `true` for the insertions
+    fn writes_with_prefix(
+        &self,
+        column_family: &ColumnFamily,
+        prefix: &[u8],
+    ) -> Vec<(Vec<u8>, bool)> {
+        self.writes
+            .get(column_family)
+            .map_or_else(Vec::new, |writes| {
+                writes
+                    .range(prefix.to_vec()..)
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(|(key, inserted)| (key.clone(), *inserted))
+                    .collect()
+            })
+    }
+}
+
+/// A scan of an index merged on the fly with the writes of the transaction not applied to the index yet
+struct PatchedIter {
+    iter: Iter,
+    /// The writes to the keys of the scanned prefix that are not reached yet, in key order
+    writes: Peekable<std::vec::IntoIter<(Vec<u8>, bool)>>,
+    /// An inserted key returned before the current key of `iter`
+    inserted: Option<Vec<u8>>,
+}
+
+impl PatchedIter {
+    fn new(iter: Iter, writes: Vec<(Vec<u8>, bool)>) -> Self {
+        let mut iter = Self {
+            iter,
+            writes: writes.into_iter().peekable(),
+            inserted: None,
+        };
+        iter.merge_writes();
+        iter
+    }
+
+    fn key(&self) -> Option<&[u8]> {
+        self.inserted.as_deref().or_else(|| self.iter.key())
+    }
+
+    fn next(&mut self) {
+        if self.inserted.take().is_none() {
+            self.iter.next();
+        }
+        self.merge_writes();
+    }
+
+    fn status(&self) -> Result<(), StorageError> {
+        self.iter.status()
+    }
+
+    /// Returns an iterator on the remaining keys in the reverse order
+    fn rev(mut self) -> Self {
+        if self.inserted.is_none() && self.writes.peek().is_none() {
+            return Self::new(self.iter.rev(), Vec::new());
+        }
+        let empty: Arc<[u8]> = Arc::new([]);
+        let mut entries = Vec::new();
+        while let Some(key) = self.key() {
+            entries.push((key.into(), Arc::clone(&empty)));
+            self.next();
+        }
+        entries.reverse();
+        Self::new(Iter::new(entries), Vec::new())
+    }
+
+    /// Consumes the writes up to the current key of `iter`, skipping the removed keys
+    fn merge_writes(&mut self) {
+        while let Some((key, inserted)) = self.writes.peek() {
+            match self.iter.key().map(|current| current.cmp(key)) {
+                Some(Ordering::Less) => return,
+                Some(Ordering::Equal) => {
+                    if !*inserted {
+                        self.iter.next();
+                    }
+                    self.writes.next();
+                }
+                Some(Ordering::Greater) | None => {
+                    let (key, inserted) = self.writes.next().unwrap();
+                    if inserted {
+                        self.inserted = Some(key);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A savepoint of a [`StorageWriter`] with the writer state to restore on rollback
+pub struct StorageSavepoint {
+    id: u64,
+    version_updated: bool,
+    /// The number of changes recorded for the change observer when the savepoint was opened
+    changes: usize,
+}
+
 pub struct StorageWriter<'a> {
     buffer: Vec<u8>,
     transaction: Transaction<'a>,
     storage: &'a Storage,
+    index_batch: Rc<RefCell<IndexWriteBatch>>,
+    changes: Option<Rc<RefCell<Vec<(EncodedQuad, bool)>>>>,
+    version_updated: bool,
+    lock_owner: Option<u64>,
+    denied_graphs: HashSet<EncodedTerm>,
 }
 
 impl<'a> StorageWriter<'a> {
@@ -594,77 +4032,222 @@ impl<'a> StorageWriter<'a> {
         StorageReader {
             reader: self.transaction.reader(),
             storage: self.storage.clone(),
+            index_batch: Some(Rc::clone(&self.index_batch)),
+        }
+    }
+
+    /// The storage the writer writes into
+    pub fn storage(&self) -> &'a Storage {
+        self.storage
+    }
+
+    /// Opens a savepoint, the buffered index writes being applied first so that a rollback restores them
+    pub fn savepoint(&mut self) -> Result<StorageSavepoint, StorageError> {
+        self.flush_index_batch()?;
+        Ok(StorageSavepoint {
+            id: self.transaction.savepoint(),
+            version_updated: self.version_updated,
+            changes: self
+                .changes
+                .as_ref()
+                .map_or(0, |changes| changes.borrow().len()),
+        })
+    }
+
+    /// Undoes the writes done since the savepoint was opened and closes it with the savepoints opened after it
+    pub fn rollback_to_savepoint(
+        &mut self,
+        savepoint: &StorageSavepoint,
+    ) -> Result<(), StorageError> {
+        self.flush_index_batch()?;
+        self.transaction.rollback_to_savepoint(savepoint.id)?;
+        self.version_updated = savepoint.version_updated;
+        if let Some(changes) = &self.changes {
+            changes.borrow_mut().truncate(savepoint.changes);
         }
+        Ok(())
+    }
+
+    /// Closes the savepoint keeping the writes done since it was opened
+    pub fn release_savepoint(&self, savepoint: &StorageSavepoint) {
+        self.transaction.release_savepoint(savepoint.id);
+    }
+
+    /// Rejects the writes into the given graphs for the rest of the transaction with [`StorageError::AccessDenied`]
+    ///
+    /// The operations on all the graphs, like [`clear`](Self::clear), skip them instead.
+    pub fn deny_writes(&mut self, graph_names: impl IntoIterator<Item = EncodedTerm>) {
+        self.denied_graphs.extend(graph_names);
+    }
+
+    fn flush_index_batch(&mut self) -> Result<(), StorageError> {
+        let batch = self.index_batch.take();
+        self.storage.apply_index_batch(&mut self.transaction, batch)
     }
 
     pub fn insert(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
-        let encoded = quad.into();
+        match self.prepare_quad(quad)? {
+            Some(prepared) => self.insert_valid(prepared.as_ref()),
+            None => self.insert_valid(quad),
+        }
+    }
+
+    /// Canonicalizes and validates the quad according to the storage options, returning its new version if it has changed
+    fn prepare_quad(&self, quad: QuadRef<'_>) -> Result<Option<Quad>, StorageError> {
+        let canonical = if self.storage.literal_canonicalization {
+            canonicalize_quad(quad)
+        } else {
+            None
+        };
+        match self.storage.term_validation {
+            Some(validation) => Ok(validation
+                .validate_quad(canonical.as_ref().map_or(quad, Quad::as_ref))?
+                .or(canonical)),
+            None => Ok(canonical),
+        }
+    }
+
+    fn insert_valid(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
+        let encoded: EncodedQuad = quad.into();
+        self.insert_encoded(&encoded, |writer| {
+            writer.check_quotas(&[&encoded], || {
+                writer.quad_strings_sizes([(quad, &encoded)])
+            })?;
+            insert_quad_strings(quad, &encoded, &mut |key, value| {
+                writer.insert_str(key, value)
+            })
+        })
+    }
+
+    /// Inserts a quad of another storage, copying from it the dictionary entries of its strings
+    ///
+    /// The entries are copied as they are, without decoding and encoding the terms again.
+    pub fn insert_from(
+        &mut self,
+        quad: &EncodedQuad,
+        source: &StorageReader,
+    ) -> Result<bool, StorageError> {
+        self.insert_encoded(quad, |writer| {
+            let mut hashes = HashSet::new();
+            add_str_hashes(&quad.subject, &mut hashes);
+            add_str_hashes(&quad.predicate, &mut hashes);
+            add_str_hashes(&quad.object, &mut hashes);
+            add_str_hashes(&quad.graph_name, &mut hashes);
+            let mut entries = Vec::with_capacity(hashes.len());
+            for hash in hashes {
+                if !writer.reader().contains_str(&hash)? {
+                    let value = source.get_encoded_str(&hash)?.ok_or_else(|| {
+                        CorruptionError::msg(
+                            "The dictionary entry of a string of the source quad is missing",
+                        )
+                    })?;
+                    entries.push((hash, value));
+                }
+            }
+            writer.check_quotas(&[quad], || {
+                Ok(entries
+                    .iter()
+                    .map(|(hash, value)| (*hash, value.len() as u64))
+                    .collect())
+            })?;
+            for (hash, value) in entries {
+                writer.transaction.insert(
+                    &writer.storage.id2str_cf,
+                    &hash.to_be_bytes(),
+                    &value,
+                )?;
+                writer.storage.add_to_dictionary_size(
+                    &mut writer.transaction,
+                    value.len() as u64,
+                    true,
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Inserts an encoded quad, `write_strings` checking the quotas and writing the dictionary entries of a new quad before its index entries
+    fn insert_encoded(
+        &mut self,
+        encoded: &EncodedQuad,
+        write_strings: impl FnOnce(&mut Self) -> Result<(), StorageError>,
+    ) -> Result<bool, StorageError> {
+        self.check_writable(&encoded.graph_name)?;
         self.buffer.clear();
-        let result = if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut self.buffer, &encoded);
+        let result = if encoded.graph_name.is_default_graph() {
+            write_spo_quad(&mut self.buffer, encoded);
             if self
                 .transaction
                 .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?
             {
+                self.remove_expiration(encoded)?;
                 false
             } else {
+                write_strings(self)?;
                 self.transaction
                     .insert_empty(&self.storage.dspo_cf, &self.buffer)?;
 
-                self.buffer.clear();
-                write_pos_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.dpos_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.dpos_cf) {
+                    self.buffer.clear();
+                    write_pos_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.dpos_cf, &self.buffer, true);
+                }
 
-                self.buffer.clear();
-                write_osp_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.dosp_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.dosp_cf) {
+                    self.buffer.clear();
+                    write_osp_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.dosp_cf, &self.buffer, true);
+                }
 
-                self.insert_term(quad.subject.into(), &encoded.subject)?;
-                self.insert_term(quad.predicate.into(), &encoded.predicate)?;
-                self.insert_term(quad.object, &encoded.object)?;
+                self.update_digests(encoded, true)?;
+                self.update_statistics(encoded, true)?;
+                self.update_literal_index(encoded, true)?;
+                self.update_range_index(encoded, true)?;
+                self.record_change(encoded, true)?;
                 true
             }
         } else {
-            write_spog_quad(&mut self.buffer, &encoded);
+            write_spog_quad(&mut self.buffer, encoded);
             if self
                 .transaction
                 .contains_key_for_update(&self.storage.spog_cf, &self.buffer)?
             {
+                self.remove_expiration(encoded)?;
                 false
             } else {
+                write_strings(self)?;
                 self.transaction
                     .insert_empty(&self.storage.spog_cf, &self.buffer)?;
 
-                self.buffer.clear();
-                write_posg_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.posg_cf, &self.buffer)?;
-
-                self.buffer.clear();
-                write_ospg_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.ospg_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.posg_cf) {
+                    self.buffer.clear();
+                    write_posg_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.posg_cf, &self.buffer, true);
+                }
 
-                self.buffer.clear();
-                write_gspo_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gspo_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.ospg_cf) {
+                    self.buffer.clear();
+                    write_ospg_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.ospg_cf, &self.buffer, true);
+                }
 
-                self.buffer.clear();
-                write_gpos_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gpos_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.gspo_cf) {
+                    self.buffer.clear();
+                    write_gspo_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.gspo_cf, &self.buffer, true);
+                }
 
-                self.buffer.clear();
-                write_gosp_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gosp_cf, &self.buffer)?;
+                if self.storage.is_index_enabled(&self.storage.gpos_cf) {
+                    self.buffer.clear();
+                    write_gpos_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.gpos_cf, &self.buffer, true);
+                }
 
-                self.insert_term(quad.subject.into(), &encoded.subject)?;
-                self.insert_term(quad.predicate.into(), &encoded.predicate)?;
-                self.insert_term(quad.object, &encoded.object)?;
+                if self.storage.is_index_enabled(&self.storage.gosp_cf) {
+                    self.buffer.clear();
+                    write_gosp_quad(&mut self.buffer, encoded);
+                    self.write_index(&self.storage.gosp_cf, &self.buffer, true);
+                }
 
                 self.buffer.clear();
                 write_term(&mut self.buffer, &encoded.graph_name);
@@ -674,8 +4257,12 @@ impl<'a> StorageWriter<'a> {
                 {
                     self.transaction
                         .insert_empty(&self.storage.graphs_cf, &self.buffer)?;
-                    self.insert_graph_name(quad.graph_name, &encoded.graph_name)?;
                 }
+                self.update_digests(encoded, true)?;
+                self.update_statistics(encoded, true)?;
+                self.update_literal_index(encoded, true)?;
+                self.update_range_index(encoded, true)?;
+                self.record_change(encoded, true)?;
                 true
             }
         };
@@ -686,7 +4273,15 @@ impl<'a> StorageWriter<'a> {
         &mut self,
         graph_name: NamedOrBlankNodeRef<'_>,
     ) -> Result<bool, StorageError> {
+        let repaired = match self.storage.term_validation {
+            Some(validation) => validation.validate_graph_name(graph_name)?,
+            None => None,
+        };
+        let graph_name = repaired
+            .as_ref()
+            .map_or(graph_name, NamedOrBlankNode::as_ref);
         let encoded_graph_name = graph_name.into();
+        self.check_writable(&encoded_graph_name)?;
 
         self.buffer.clear();
         write_term(&mut self.buffer, &encoded_graph_name);
@@ -699,11 +4294,201 @@ impl<'a> StorageWriter<'a> {
             self.transaction
                 .insert_empty(&self.storage.graphs_cf, &self.buffer)?;
             self.insert_term(graph_name.into(), &encoded_graph_name)?;
+            if self.storage.change_log {
+                self.log_change(&EncodedChange::CreatedGraph(encoded_graph_name))?;
+            }
             true
         };
         Ok(result)
     }
 
+    /// Inserts a batch of quads, writing the entries of each column family in key order
+    ///
+    /// It is faster than inserting the quads one by one because each dictionary and index entry is written once
+    /// and the digests are updated once per graph.
+    /// Returns the number of quads that were not already in the storage.
+    pub fn insert_batch(&mut self, quads: &[QuadRef<'_>]) -> Result<usize, StorageError> {
+        let prepared = quads
+            .iter()
+            .map(|quad| self.prepare_quad(*quad))
+            .collect::<Result<Vec<_>, _>>()?;
+        let quads = quads
+            .iter()
+            .zip(&prepared)
+            .map(|(quad, prepared)| prepared.as_ref().map_or(*quad, Quad::as_ref))
+            .collect::<Vec<_>>();
+        let mut new_quads = Vec::new();
+        let mut seen = HashSet::new();
+        for quad in &quads {
+            let encoded = EncodedQuad::from(*quad);
+            self.check_writable(&encoded.graph_name)?;
+            self.buffer.clear();
+            let cf = if encoded.graph_name.is_default_graph() {
+                write_spo_quad(&mut self.buffer, &encoded);
+                &self.storage.dspo_cf
+            } else {
+                write_spog_quad(&mut self.buffer, &encoded);
+                &self.storage.spog_cf
+            };
+            if self.transaction.contains_key_for_update(cf, &self.buffer)? {
+                self.remove_expiration(&encoded)?;
+            } else if seen.insert(self.buffer.clone()) {
+                new_quads.push((*quad, encoded));
+            }
+        }
+        if new_quads.is_empty() {
+            return Ok(0);
+        }
+        self.check_quotas(
+            &new_quads
+                .iter()
+                .map(|(_, encoded)| encoded)
+                .collect::<Vec<_>>(),
+            || self.quad_strings_sizes(new_quads.iter().map(|(quad, encoded)| (*quad, encoded))),
+        )?;
+
+        let storage = self.storage;
+        let mut strings = Vec::new();
+        for (quad, encoded) in &new_quads {
+            insert_quad_strings(*quad, encoded, &mut |key, value| {
+                strings.push((
+                    key.to_be_bytes().to_vec(),
+                    storage.encode_str(value).into_owned(),
+                ));
+                Ok(())
+            })?;
+        }
+        let strings = sorted_entries(strings);
+        let mut new_strings_bytes = 0;
+        for (key, value) in &strings {
+            if !self
+                .transaction
+                .reader()
+                .contains_key(&self.storage.id2str_cf, key)?
+            {
+                new_strings_bytes += value.len() as u64;
+            }
+        }
+        self.storage
+            .add_to_dictionary_size(&mut self.transaction, new_strings_bytes, true)?;
+        self.transaction
+            .insert_sorted(&self.storage.id2str_cf, strings)?;
+
+        let (default_quads, named_quads): (Vec<_>, Vec<_>) = new_quads
+            .iter()
+            .map(|(_, encoded)| encoded)
+            .partition(|encoded| encoded.graph_name.is_default_graph());
+        for (cf, write, quads) in [
+            (
+                &self.storage.dspo_cf,
+                write_spo_quad as fn(&mut Vec<u8>, &EncodedQuad),
+                &default_quads,
+            ),
+            (&self.storage.dpos_cf, write_pos_quad, &default_quads),
+            (&self.storage.dosp_cf, write_osp_quad, &default_quads),
+            (&self.storage.spog_cf, write_spog_quad, &named_quads),
+            (&self.storage.posg_cf, write_posg_quad, &named_quads),
+            (&self.storage.ospg_cf, write_ospg_quad, &named_quads),
+            (&self.storage.gspo_cf, write_gspo_quad, &named_quads),
+            (&self.storage.gpos_cf, write_gpos_quad, &named_quads),
+            (&self.storage.gosp_cf, write_gosp_quad, &named_quads),
+        ] {
+            if !self.storage.is_index_enabled(cf) {
+                continue;
+            }
+            if *cf != self.storage.dspo_cf && *cf != self.storage.spog_cf {
+                let mut index_batch = self.index_batch.borrow_mut();
+                let writes = index_batch.writes.entry(cf.clone()).or_default();
+                for quad in quads {
+                    let mut key = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+                    write(&mut key, quad);
+                    writes.insert(key, true);
+                }
+                continue;
+            }
+            let entries = quads
+                .iter()
+                .map(|quad| {
+                    let mut key = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+                    write(&mut key, quad);
+                    (key, Vec::new())
+                })
+                .collect();
+            self.transaction
+                .insert_sorted(cf, sorted_entries(entries))?;
+        }
+        let graphs = named_quads
+            .iter()
+            .map(|quad| (encode_term(&quad.graph_name), Vec::new()))
+            .collect();
+        self.transaction
+            .insert_sorted(&self.storage.graphs_cf, sorted_entries(graphs))?;
+
+        for (_, encoded) in &new_quads {
+            self.record_change(encoded, true)?;
+        }
+        self.add_batch_to_digests(default_quads.iter().chain(&named_quads).copied())?;
+        self.add_batch_to_statistics(default_quads.into_iter().chain(named_quads))?;
+        let literals = new_quads
+            .iter()
+            .flat_map(|(_, encoded)| self.storage.literal_index_keys(&encoded.object))
+            .map(|key| (key, Vec::new()))
+            .collect::<Vec<_>>();
+        if !literals.is_empty() {
+            self.transaction
+                .insert_sorted(&self.storage.literals_cf, sorted_entries(literals))?;
+        }
+        let ranges = new_quads
+            .iter()
+            .filter_map(|(_, encoded)| self.storage.range_index_key(encoded))
+            .map(|key| (key, Vec::new()))
+            .collect::<Vec<_>>();
+        if !ranges.is_empty() {
+            self.transaction
+                .insert_sorted(&self.storage.ranges_cf, sorted_entries(ranges))?;
+        }
+        Ok(new_quads.len())
+    }
+
+    /// Increments the statistics counts of new quads, writing each count once
+    fn add_batch_to_statistics<'b>(
+        &mut self,
+        quads: impl IntoIterator<Item = &'b EncodedQuad>,
+    ) -> Result<(), StorageError> {
+        let mut counts = HashMap::<_, u64>::new();
+        for encoded in quads {
+            for key in statistics_keys(encoded) {
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+        for (key, count) in counts {
+            self.add_to_count(&key, count, true)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the hashes of new quads to the digests, writing each digest once
+    fn add_batch_to_digests<'b>(
+        &mut self,
+        quads: impl IntoIterator<Item = &'b EncodedQuad>,
+    ) -> Result<(), StorageError> {
+        let mut graph_hashes = HashMap::<_, u128>::new();
+        let mut dataset_hash = 0_u128;
+        for encoded in quads {
+            let (graph_hash, quad_hash) = digest_hashes(encoded, &mut self.buffer);
+            let graph_digest = graph_hashes
+                .entry(graph_digest_key(&encoded.graph_name))
+                .or_default();
+            *graph_digest = graph_digest.wrapping_add(graph_hash);
+            dataset_hash = dataset_hash.wrapping_add(quad_hash);
+        }
+        for (key, graph_hash) in graph_hashes {
+            self.add_to_digest(&key, graph_hash)?;
+        }
+        self.add_to_digest(DATASET_DIGEST_KEY, dataset_hash)?;
+        Ok(())
+    }
+
     fn insert_term(
         &mut self,
         term: TermRef<'_>,
@@ -712,31 +4497,96 @@ impl<'a> StorageWriter<'a> {
         insert_term(term, encoded, &mut |key, value| self.insert_str(key, value))
     }
 
-    fn insert_graph_name(
-        &mut self,
-        graph_name: GraphNameRef<'_>,
-        encoded: &EncodedTerm,
+    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
+        let key = key.to_be_bytes();
+        if self
+            .transaction
+            .reader()
+            .contains_key(&self.storage.id2str_cf, &key)?
+        {
+            return Ok(());
+        }
+        let value = self.storage.encode_str(value);
+        self.transaction
+            .insert(&self.storage.id2str_cf, &key, &value)?;
+        self.storage
+            .add_to_dictionary_size(&mut self.transaction, value.len() as u64, true)
+    }
+
+    /// Fails if the insertion of the new quads would exceed one of the storage quotas
+    ///
+    /// It is checked before any write so that the rejected insertions do not write anything to roll back.
+    /// `new_strings` returns the encoded size of each string of the new quads, it is only called if the dictionary size is limited.
+    fn check_quotas(
+        &self,
+        new_quads: &[&EncodedQuad],
+        new_strings: impl FnOnce() -> Result<HashMap<StrHash, u64>, StorageError>,
     ) -> Result<(), StorageError> {
-        match graph_name {
-            GraphNameRef::NamedNode(graph_name) => self.insert_term(graph_name.into(), encoded),
-            GraphNameRef::BlankNode(graph_name) => self.insert_term(graph_name.into(), encoded),
-            GraphNameRef::DefaultGraph => Ok(()),
+        let quotas = self.storage.quotas;
+        let reader = self.reader();
+        if let Some(limit) = quotas.max_quads {
+            if reader.stored_len()? as u64 + new_quads.len() as u64 > limit {
+                return Err(StorageError::QuotaExceeded {
+                    quota: Quota::Quads,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = quotas.max_graph_quads {
+            let mut graph_quads = HashMap::<_, u64>::new();
+            for encoded in new_quads {
+                if !encoded.graph_name.is_default_graph() {
+                    *graph_quads.entry(&encoded.graph_name).or_default() += 1;
+                }
+            }
+            for (graph_name, count) in graph_quads {
+                if reader.stored_graph_len(graph_name)? + count > limit {
+                    return Err(StorageError::QuotaExceeded {
+                        quota: Quota::GraphQuads,
+                        limit,
+                    });
+                }
+            }
+        }
+        if let Some(limit) = quotas.max_dictionary_bytes {
+            let mut size = reader.dictionary_size()?;
+            for (key, len) in new_strings()? {
+                if !reader.contains_str(&key)? {
+                    size += len;
+                }
+            }
+            if size > limit {
+                return Err(StorageError::QuotaExceeded {
+                    quota: Quota::DictionaryBytes,
+                    limit,
+                });
+            }
         }
+        Ok(())
     }
 
-    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
-        self.transaction.insert(
-            &self.storage.id2str_cf,
-            &key.to_be_bytes(),
-            value.as_bytes(),
-        )
+    /// The encoded size of each string of the quads
+    fn quad_strings_sizes<'b>(
+        &self,
+        quads: impl IntoIterator<Item = (QuadRef<'b>, &'b EncodedQuad)>,
+    ) -> Result<HashMap<StrHash, u64>, StorageError> {
+        let mut sizes = HashMap::new();
+        for (quad, encoded) in quads {
+            insert_quad_strings(quad, encoded, &mut |key, value| {
+                sizes.insert(*key, self.storage.encode_str(value).len() as u64);
+                Ok(())
+            })?;
+        }
+        Ok(sizes)
     }
 
     pub fn remove(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
-        self.remove_encoded(&quad.into())
+        let encoded = self.storage.encode_stored_quad(quad);
+        self.remove_encoded(&encoded)
     }
 
     fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<bool, StorageError> {
+        self.check_writable(&quad.graph_name)?;
         self.buffer.clear();
         let result = if quad.graph_name.is_default_graph() {
             write_spo_quad(&mut self.buffer, quad);
@@ -750,13 +4600,17 @@ impl<'a> StorageWriter<'a> {
 
                 self.buffer.clear();
                 write_pos_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.dpos_cf, &self.buffer)?;
+                self.write_index(&self.storage.dpos_cf, &self.buffer, false);
 
                 self.buffer.clear();
                 write_osp_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.dosp_cf, &self.buffer)?;
+                self.write_index(&self.storage.dosp_cf, &self.buffer, false);
+                self.update_digests(quad, false)?;
+                self.update_statistics(quad, false)?;
+                self.update_literal_index(quad, false)?;
+                self.update_range_index(quad, false)?;
+                self.remove_expiration(quad)?;
+                self.record_change(quad, false)?;
                 true
             } else {
                 false
@@ -773,28 +4627,29 @@ impl<'a> StorageWriter<'a> {
 
                 self.buffer.clear();
                 write_posg_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.posg_cf, &self.buffer)?;
+                self.write_index(&self.storage.posg_cf, &self.buffer, false);
 
                 self.buffer.clear();
                 write_ospg_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.ospg_cf, &self.buffer)?;
+                self.write_index(&self.storage.ospg_cf, &self.buffer, false);
 
                 self.buffer.clear();
                 write_gspo_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gspo_cf, &self.buffer)?;
+                self.write_index(&self.storage.gspo_cf, &self.buffer, false);
 
                 self.buffer.clear();
                 write_gpos_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gpos_cf, &self.buffer)?;
+                self.write_index(&self.storage.gpos_cf, &self.buffer, false);
 
                 self.buffer.clear();
                 write_gosp_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gosp_cf, &self.buffer)?;
+                self.write_index(&self.storage.gosp_cf, &self.buffer, false);
+                self.update_digests(quad, false)?;
+                self.update_statistics(quad, false)?;
+                self.update_literal_index(quad, false)?;
+                self.update_range_index(quad, false)?;
+                self.remove_expiration(quad)?;
+                self.record_change(quad, false)?;
                 true
             } else {
                 false
@@ -803,37 +4658,368 @@ impl<'a> StorageWriter<'a> {
         Ok(result)
     }
 
-    pub fn clear_graph(&mut self, graph_name: GraphNameRef<'_>) -> Result<(), StorageError> {
-        if graph_name.is_default_graph() {
-            for quad in self.reader().quads_for_graph(&EncodedTerm::DefaultGraph) {
-                self.remove_encoded(&quad?)?;
+    /// Adds (or subtracts) the hash of the quad to the rolling digests of its graph and of the dataset
+    fn update_digests(&mut self, quad: &EncodedQuad, inserted: bool) -> Result<(), StorageError> {
+        let (graph_hash, dataset_hash) = digest_hashes(quad, &mut self.buffer);
+        let (graph_hash, dataset_hash) = if inserted {
+            (graph_hash, dataset_hash)
+        } else {
+            (graph_hash.wrapping_neg(), dataset_hash.wrapping_neg())
+        };
+        self.add_to_digest(&graph_digest_key(&quad.graph_name), graph_hash)?;
+        self.add_to_digest(DATASET_DIGEST_KEY, dataset_hash)
+    }
+
+    /// Adds `delta` to the digest stored at `key`, an absent digest being `0`
+    fn add_to_digest(&mut self, key: &[u8], delta: u128) -> Result<(), StorageError> {
+        let digest = decode_graph_digest(
+            self.transaction
+                .reader()
+                .get(&self.storage.default_cf, key)?
+                .as_deref(),
+        )?
+        .wrapping_add(delta);
+        if digest == 0 {
+            self.transaction.remove(&self.storage.default_cf, key)
+        } else {
+            self.transaction
+                .insert(&self.storage.default_cf, key, &digest.to_be_bytes())
+        }
+    }
+
+    /// Increments (or decrements) the statistics counts of the quad
+    fn update_statistics(
+        &mut self,
+        quad: &EncodedQuad,
+        inserted: bool,
+    ) -> Result<(), StorageError> {
+        for key in statistics_keys(quad) {
+            self.add_to_count(&key, 1, inserted)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the object of an inserted quad to the literal index, or removes it if no quad uses it anymore
+    fn update_literal_index(
+        &mut self,
+        quad: &EncodedQuad,
+        inserted: bool,
+    ) -> Result<(), StorageError> {
+        let keys = self.storage.literal_index_keys(&quad.object);
+        if keys.is_empty() {
+            return Ok(());
+        }
+        if inserted {
+            for key in keys {
+                self.transaction
+                    .insert_empty(&self.storage.literals_cf, &key)?;
+            }
+        } else if self
+            .reader()
+            .quads_for_object(&quad.object)
+            .next()
+            .is_none()
+        {
+            for key in keys {
+                self.transaction.remove(&self.storage.literals_cf, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the current time used by the quad expirations, in nanoseconds since the epoch
+    pub fn now(&self) -> u64 {
+        self.storage.now()
+    }
+
+    /// Inserts a quad that expires at `expires_at` in nanoseconds since the epoch
+    ///
+    /// If the quad is already in the store, its expiration is replaced.
+    pub fn insert_with_expiration(
+        &mut self,
+        quad: QuadRef<'_>,
+        expires_at: u64,
+    ) -> Result<bool, StorageError> {
+        let prepared = self.prepare_quad(quad)?;
+        let quad = prepared.as_ref().map_or(quad, Quad::as_ref);
+        let inserted = self.insert_valid(quad)?;
+        let encoded = EncodedQuad::from(quad);
+        self.remove_expiration(&encoded)?;
+        self.transaction.insert(
+            &self.storage.expirations_cf,
+            &expiration_by_quad_key(&encoded),
+            &expires_at.to_be_bytes(),
+        )?;
+        self.transaction.insert_empty(
+            &self.storage.expirations_cf,
+            &expiration_by_time_key(expires_at, &encoded),
+        )?;
+        Ok(inserted)
+    }
+
+    /// Removes at most `limit` quads expired at `now` in nanoseconds since the epoch and returns their number
+    pub fn remove_expired_quads(&mut self, now: u64, limit: usize) -> Result<usize, StorageError> {
+        let quads = self.reader().expired_quads_until(now, limit)?;
+        // The expired quads are already hidden, only their expirations are moved into the stored dataset version
+        let version_updated = std::mem::replace(&mut self.version_updated, true);
+        for quad in &quads {
+            self.remove_encoded(quad)?;
+        }
+        self.version_updated = version_updated;
+        Ok(quads.len())
+    }
+
+    /// Removes the expiration of the quad if it has one
+    fn remove_expiration(&mut self, quad: &EncodedQuad) -> Result<(), StorageError> {
+        let key = expiration_by_quad_key(quad);
+        if let Some(value) = self
+            .transaction
+            .reader()
+            .get(&self.storage.expirations_cf, &key)?
+        {
+            let expires_at = decode_expiration(&value)?;
+            self.transaction
+                .remove(&self.storage.expirations_cf, &key)?;
+            self.transaction.remove(
+                &self.storage.expirations_cf,
+                &expiration_by_time_key(expires_at, quad),
+            )?;
+            if expires_at <= self.storage.now() {
+                // The expiration was counted in the dataset version, it is moved into the stored one,
+                // and the quad is visible again if it is inserted back
+                self.increment_dataset_version()?;
+                self.update_dataset_version()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds or removes the range index entry of the quad
+    fn update_range_index(
+        &mut self,
+        quad: &EncodedQuad,
+        inserted: bool,
+    ) -> Result<(), StorageError> {
+        if let Some(key) = self.storage.range_index_key(quad) {
+            if inserted {
+                self.transaction
+                    .insert_empty(&self.storage.ranges_cf, &key)?;
+            } else {
+                self.transaction.remove(&self.storage.ranges_cf, &key)?;
             }
+        }
+        Ok(())
+    }
+
+    /// Adds (or subtracts) `delta` to the count stored at `key`, an absent count being `0`
+    fn add_to_count(&mut self, key: &[u8], delta: u64, inserted: bool) -> Result<(), StorageError> {
+        let count = decode_count(
+            self.transaction
+                .reader()
+                .get(&self.storage.stats_cf, key)?
+                .as_deref(),
+        )?;
+        let count = if inserted {
+            count.saturating_add(delta)
         } else {
-            self.buffer.clear();
-            write_term(&mut self.buffer, &graph_name.into());
-            if self
-                .transaction
-                .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
-            {
-                // The condition is useful to lock the graph itself and ensure no quad is inserted at the same time
-                for quad in self.reader().quads_for_graph(&graph_name.into()) {
+            count.saturating_sub(delta)
+        };
+        if count == 0 {
+            self.transaction.remove(&self.storage.stats_cf, key)
+        } else {
+            self.transaction
+                .insert(&self.storage.stats_cf, key, &count.to_be_bytes())
+        }
+    }
+
+    fn check_writable(&self, graph_name: &EncodedTerm) -> Result<(), StorageError> {
+        if self.denied_graphs.contains(graph_name) {
+            return Err(StorageError::AccessDenied);
+        }
+        let locks = self.storage.graph_locks.read().unwrap();
+        if locks
+            .owners
+            .iter()
+            .any(|(owner, locked)| Some(*owner) != self.lock_owner && locked.contains(graph_name))
+        {
+            return Err(StorageError::GraphLocked);
+        }
+        Ok(())
+    }
+
+    /// Buffers the insertion or the removal of a secondary index key, the batch being applied when the transaction ends
+    fn write_index(&self, column_family: &ColumnFamily, key: &[u8], inserted: bool) {
+        self.index_batch
+            .borrow_mut()
+            .writes
+            .entry(column_family.clone())
+            .or_default()
+            .insert(key.to_vec(), inserted);
+    }
+
+    /// Records the change for the change observer and increments the dataset version once per transaction
+    fn record_change(&mut self, quad: &EncodedQuad, inserted: bool) -> Result<(), StorageError> {
+        if let Some(changes) = &self.changes {
+            changes.borrow_mut().push((quad.clone(), inserted));
+        }
+        if self.storage.change_log {
+            self.log_change(&if inserted {
+                EncodedChange::InsertedQuad(quad.clone())
+            } else {
+                EncodedChange::RemovedQuad(quad.clone())
+            })?;
+        }
+        self.update_dataset_version()
+    }
+
+    /// Increments the dataset version if the transaction has not done it yet
+    fn update_dataset_version(&mut self) -> Result<(), StorageError> {
+        if !self.version_updated {
+            self.increment_dataset_version()?;
+            self.version_updated = true;
+        }
+        Ok(())
+    }
+
+    fn increment_dataset_version(&mut self) -> Result<(), StorageError> {
+        let version = self.reader().stored_dataset_version()?;
+        self.transaction.insert(
+            &self.storage.default_cf,
+            DATASET_VERSION_KEY,
+            &(version + 1).to_be_bytes(),
+        )
+    }
+
+    pub fn clear_graph(&mut self, graph_name: GraphNameRef<'_>) -> Result<(), StorageError> {
+        let graph_name = match graph_name {
+            GraphNameRef::NamedNode(graph_name) => {
+                self.storage.encode_stored_graph_name(graph_name.into())
+            }
+            GraphNameRef::BlankNode(graph_name) => graph_name.into(),
+            GraphNameRef::DefaultGraph => {
+                for quad in self.reader().quads_for_graph(&EncodedTerm::DefaultGraph) {
                     self.remove_encoded(&quad?)?;
                 }
+                return Ok(());
+            }
+        };
+        self.buffer.clear();
+        write_term(&mut self.buffer, &graph_name);
+        if self
+            .transaction
+            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+        {
+            // The condition is useful to lock the graph itself and ensure no quad is inserted at the same time
+            for quad in self.reader().quads_for_graph(&graph_name) {
+                self.remove_encoded(&quad?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts the quads of the graph `from` into the graph `to`, returning the number of quads that were not already in `to`
+    ///
+    /// The quads are copied in their encoded form, only the strings of `to` being added to the dictionary.
+    pub fn add_graph(
+        &mut self,
+        from: GraphNameRef<'_>,
+        to: GraphNameRef<'_>,
+    ) -> Result<usize, StorageError> {
+        if from == to {
+            return Ok(0);
+        }
+        let encoded_to = EncodedTerm::from(to);
+        let to_term = match to {
+            GraphNameRef::NamedNode(to) => Some(TermRef::from(to)),
+            GraphNameRef::BlankNode(to) => Some(to.into()),
+            GraphNameRef::DefaultGraph => None,
+        };
+        let mut added = 0;
+        for quad in self.reader().quads_for_graph(&from.into()) {
+            let quad = quad?;
+            let quad = EncodedQuad::new(
+                quad.subject,
+                quad.predicate,
+                quad.object,
+                encoded_to.clone(),
+            );
+            let inserted = self.insert_encoded(&quad, |writer| {
+                writer.check_quotas(&[&quad], || {
+                    let mut sizes = HashMap::new();
+                    if let Some(to_term) = to_term {
+                        insert_term(to_term, &encoded_to, &mut |key, value| {
+                            sizes.insert(*key, writer.storage.encode_str(value).len() as u64);
+                            Ok(())
+                        })?;
+                    }
+                    Ok(sizes)
+                })?;
+                if let Some(to_term) = to_term {
+                    writer.insert_term(to_term, &encoded_to)?;
+                }
+                Ok(())
+            })?;
+            if inserted {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from`
+    pub fn copy_graph(
+        &mut self,
+        from: GraphNameRef<'_>,
+        to: GraphNameRef<'_>,
+    ) -> Result<(), StorageError> {
+        if from != to {
+            self.clear_graph(to)?;
+            self.add_graph(from, to)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from` and removes `from`
+    ///
+    /// The default graph is cleared instead of being removed.
+    pub fn move_graph(
+        &mut self,
+        from: GraphNameRef<'_>,
+        to: GraphNameRef<'_>,
+    ) -> Result<(), StorageError> {
+        if from != to {
+            self.copy_graph(from, to)?;
+            match from {
+                GraphNameRef::NamedNode(from) => {
+                    self.remove_named_graph(from.into())?;
+                }
+                GraphNameRef::BlankNode(from) => {
+                    self.remove_named_graph(from.into())?;
+                }
+                GraphNameRef::DefaultGraph => self.clear_graph(from)?,
             }
         }
         Ok(())
     }
 
+    /// Clears the named graphs, except the ones the writes are [denied](Self::deny_writes) into
     pub fn clear_all_named_graphs(&mut self) -> Result<(), StorageError> {
         for quad in self.reader().quads_in_named_graph() {
-            self.remove_encoded(&quad?)?;
+            let quad = quad?;
+            if !self.denied_graphs.contains(&quad.graph_name) {
+                self.remove_encoded(&quad)?;
+            }
         }
         Ok(())
     }
 
+    /// Clears all the graphs, except the ones the writes are [denied](Self::deny_writes) into
     pub fn clear_all_graphs(&mut self) -> Result<(), StorageError> {
         for quad in self.reader().quads() {
-            self.remove_encoded(&quad?)?;
+            let quad = quad?;
+            if !self.denied_graphs.contains(&quad.graph_name) {
+                self.remove_encoded(&quad)?;
+            }
         }
         Ok(())
     }
@@ -842,13 +5028,15 @@ impl<'a> StorageWriter<'a> {
         &mut self,
         graph_name: NamedOrBlankNodeRef<'_>,
     ) -> Result<bool, StorageError> {
-        self.remove_encoded_named_graph(&graph_name.into())
+        let encoded = self.storage.encode_stored_graph_name(graph_name);
+        self.remove_encoded_named_graph(&encoded)
     }
 
     fn remove_encoded_named_graph(
         &mut self,
         graph_name: &EncodedTerm,
     ) -> Result<bool, StorageError> {
+        self.check_writable(graph_name)?;
         self.buffer.clear();
         write_term(&mut self.buffer, graph_name);
         let result = if self
@@ -863,6 +5051,9 @@ impl<'a> StorageWriter<'a> {
             write_term(&mut self.buffer, graph_name);
             self.transaction
                 .remove(&self.storage.graphs_cf, &self.buffer)?;
+            if self.storage.change_log {
+                self.log_change(&EncodedChange::DroppedGraph(graph_name.clone()))?;
+            }
             true
         } else {
             false
@@ -870,20 +5061,527 @@ impl<'a> StorageWriter<'a> {
         Ok(result)
     }
 
+    /// Removes the named graphs, except the ones the writes are [denied](Self::deny_writes) into
     pub fn remove_all_named_graphs(&mut self) -> Result<(), StorageError> {
         for graph_name in self.reader().named_graphs() {
-            self.remove_encoded_named_graph(&graph_name?)?;
+            let graph_name = graph_name?;
+            if !self.denied_graphs.contains(&graph_name) {
+                self.remove_encoded_named_graph(&graph_name)?;
+            }
         }
         Ok(())
     }
 
+    /// Appends the change to the change log with the next sequence number
+    fn log_change(&mut self, change: &EncodedChange) -> Result<(), StorageError> {
+        let sequence = self.reader().next_change_sequence()?;
+        self.transaction.insert(
+            &self.storage.default_cf,
+            NEXT_CHANGE_SEQUENCE_KEY,
+            &(sequence + 1).to_be_bytes(),
+        )?;
+        self.transaction.insert_empty(
+            &self.storage.changes_cf,
+            &encode_change_log_key(sequence, change),
+        )
+    }
+
+    /// Removes the entries of the change log with a sequence number lower than `sequence`
+    ///
+    /// Returns the number of removed entries.
+    pub fn truncate_change_log(&mut self, sequence: u64) -> Result<usize, StorageError> {
+        let mut iter = self.transaction.reader().scan_range(
+            &self.storage.changes_cf,
+            &[],
+            Some(&sequence.to_be_bytes()),
+        )?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.key() {
+            keys.push(key.to_vec());
+            iter.next();
+        }
+        iter.status()?;
+        for key in &keys {
+            self.transaction.remove(&self.storage.changes_cf, key)?;
+        }
+        Ok(keys.len())
+    }
+
+    pub fn insert_metadata(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        self.transaction
+            .insert(&self.storage.default_cf, key, value)
+    }
+
+    pub fn remove_metadata(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.transaction.remove(&self.storage.default_cf, key)
+    }
+
+    /// Removes the named graphs and clears the default graph, except the graphs the writes are [denied](Self::deny_writes) into
     pub fn clear(&mut self) -> Result<(), StorageError> {
-        for graph_name in self.reader().named_graphs() {
-            self.remove_encoded_named_graph(&graph_name?)?;
+        self.remove_all_named_graphs()?;
+        self.clear_all_graphs()
+    }
+}
+
+/// Sorts entries by key, keeping only the first entry of each key
+fn sorted_entries(mut entries: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+    entries
+}
+
+/// The hashes of the quad added to the digest of its graph and to the dataset digest
+///
+/// The graph name is hashed in the latter, so moving a quad to another graph changes the dataset digest.
+fn digest_hashes(quad: &EncodedQuad, buffer: &mut Vec<u8>) -> (u128, u128) {
+    buffer.clear();
+    write_spo_quad(buffer, quad);
+    let graph_hash = quad_hash(buffer);
+    buffer.clear();
+    write_spog_quad(buffer, quad);
+    (graph_hash, quad_hash(buffer))
+}
+
+fn graph_digest_key(graph_name: &EncodedTerm) -> Vec<u8> {
+    let mut key = GRAPH_DIGEST_PREFIX.to_vec();
+    write_term(&mut key, graph_name);
+    key
+}
+
+fn quad_hash(encoded: &[u8]) -> u128 {
+    let mut hasher = SipHasher24::new();
+    hasher.write(encoded);
+    hasher.finish128().into()
+}
+
+/// Migration from the storage version 1, that had no dataset digest
+fn compute_dataset_digest(storage: &Storage) -> Result<(), StorageError> {
+    let mut digest = 0_u128;
+    let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+    for quad in storage.snapshot().quads() {
+        buffer.clear();
+        write_spog_quad(&mut buffer, &quad?);
+        digest = digest.wrapping_add(quad_hash(&buffer));
+    }
+    storage.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+        transaction.insert(
+            &storage.default_cf,
+            DATASET_DIGEST_KEY,
+            &digest.to_be_bytes(),
+        )
+    })
+}
+
+/// Migration from the storage version 2, that had no statistics
+fn compute_statistics(storage: &Storage) -> Result<(), StorageError> {
+    let mut counts = HashMap::<_, u64>::new();
+    for quad in storage.snapshot().quads() {
+        for key in statistics_keys(&quad?) {
+            *counts.entry(key).or_default() += 1;
         }
-        for quad in self.reader().quads() {
-            self.remove_encoded(&quad?)?;
+    }
+    storage.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+        for (key, count) in &counts {
+            transaction.insert(&storage.stats_cf, key, &count.to_be_bytes())?;
+        }
+        Ok(())
+    })
+}
+
+/// Migration from the storage version 3, that had no total number of quads
+fn compute_quad_count(storage: &Storage) -> Result<(), StorageError> {
+    let count = storage
+        .snapshot()
+        .statistics()?
+        .graphs
+        .iter()
+        .map(|(_, count)| count)
+        .sum::<u64>();
+    storage.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+        transaction.insert(
+            &storage.stats_cf,
+            &[QUAD_COUNT_PREFIX],
+            &count.to_be_bytes(),
+        )
+    })
+}
+
+/// Migration from the storage version 4, that had no total size of the dictionary
+fn compute_dictionary_size(storage: &Storage) -> Result<(), StorageError> {
+    let mut size = 0_u64;
+    let mut iter = storage.db.snapshot().iter(&storage.id2str_cf)?;
+    while let Some(value) = iter.value() {
+        size += value.len() as u64;
+        iter.next();
+    }
+    iter.status()?;
+    storage.logged_transaction(IsolationLevel::RepeatableRead, |mut transaction| {
+        transaction.insert(
+            &storage.default_cf,
+            DICTIONARY_SIZE_KEY,
+            &size.to_be_bytes(),
+        )
+    })
+}
+
+/// Migration from the storage version 5, that stored the strings of 16 to 32 bytes in the dictionary instead of inlining them
+fn inline_medium_strings(storage: &Storage) -> Result<(), StorageError> {
+    let reader = storage.snapshot();
+    let mut reencoded = Vec::new();
+    for quad in reader.quads() {
+        let quad = quad?;
+        let decoded = reader.decode_quad(&quad)?;
+        if EncodedQuad::from(decoded.as_ref()) != quad {
+            let expiration = reader.expiration(&quad)?;
+            reencoded.push((quad, decoded, expiration));
+        }
+    }
+    if reencoded.is_empty() {
+        return Ok(());
+    }
+    storage.transaction(IsolationLevel::RepeatableRead, |mut writer| {
+        for (quad, decoded, expiration) in &reencoded {
+            writer.remove_encoded(quad)?;
+            if let Some(expires_at) = expiration {
+                writer.insert_with_expiration(decoded.as_ref(), *expires_at)?;
+            } else {
+                writer.insert(decoded.as_ref())?;
+            }
         }
         Ok(())
+    })
+}
+
+/// Calls `insert_str` on the strings of the quad terms that are stored in the dictionary
+fn insert_quad_strings(
+    quad: QuadRef<'_>,
+    encoded: &EncodedQuad,
+    insert_str: &mut impl FnMut(&StrHash, &str) -> Result<(), StorageError>,
+) -> Result<(), StorageError> {
+    insert_term(quad.subject.into(), &encoded.subject, insert_str)?;
+    insert_term(quad.predicate.into(), &encoded.predicate, insert_str)?;
+    insert_term(quad.object, &encoded.object, insert_str)?;
+    match quad.graph_name {
+        GraphNameRef::NamedNode(graph_name) => {
+            insert_term(graph_name.into(), &encoded.graph_name, insert_str)
+        }
+        GraphNameRef::BlankNode(graph_name) => {
+            insert_term(graph_name.into(), &encoded.graph_name, insert_str)
+        }
+        GraphNameRef::DefaultGraph => Ok(()),
     }
 }
+
+/// The keys of the statistics counts incremented by the quad
+fn statistics_keys(quad: &EncodedQuad) -> [Vec<u8>; 4] {
+    let mut predicate = vec![PREDICATE_COUNT_PREFIX];
+    write_term(&mut predicate, &quad.predicate);
+    let mut graph = vec![GRAPH_COUNT_PREFIX];
+    write_term(&mut graph, &quad.graph_name);
+    [
+        vec![QUAD_COUNT_PREFIX],
+        predicate,
+        graph,
+        object_class_count_key(&quad.predicate, ObjectClass::of(&quad.object)),
+    ]
+}
+
+fn expiration_by_quad_key(quad: &EncodedQuad) -> Vec<u8> {
+    let mut key = vec![EXPIRATION_BY_QUAD_PREFIX];
+    key.extend_from_slice(&encode_term_quad(
+        &quad.subject,
+        &quad.predicate,
+        &quad.object,
+        &quad.graph_name,
+    ));
+    key
+}
+
+fn expiration_by_time_key(expires_at: u64, quad: &EncodedQuad) -> Vec<u8> {
+    let mut key = vec![EXPIRATION_BY_TIME_PREFIX];
+    key.extend_from_slice(&expires_at.to_be_bytes());
+    key.extend_from_slice(&encode_term_quad(
+        &quad.subject,
+        &quad.predicate,
+        &quad.object,
+        &quad.graph_name,
+    ));
+    key
+}
+
+/// The smallest expiration by time key after the ones of the quads expired at `now`
+fn expiration_by_time_range_end(now: u64) -> Vec<u8> {
+    if let Some(next) = now.checked_add(1) {
+        let mut key = vec![EXPIRATION_BY_TIME_PREFIX];
+        key.extend_from_slice(&next.to_be_bytes());
+        key
+    } else {
+        vec![EXPIRATION_BY_TIME_PREFIX + 1]
+    }
+}
+
+fn decode_expiration_by_time_key(key: &[u8]) -> Result<(u64, EncodedQuad), StorageError> {
+    if key.len() < 9 {
+        return Err(CorruptionError::msg("Invalid expiration key").into());
+    }
+    let expires_at = decode_expiration(&key[1..9])?;
+    if let [subject, predicate, object, graph_name] = decode_terms(&key[9..])?.as_slice() {
+        Ok((
+            expires_at,
+            EncodedQuad::new(
+                subject.clone(),
+                predicate.clone(),
+                object.clone(),
+                graph_name.clone(),
+            ),
+        ))
+    } else {
+        Err(CorruptionError::msg("Invalid expiration key").into())
+    }
+}
+
+fn decode_expiration(value: &[u8]) -> Result<u64, StorageError> {
+    Ok(u64::from_be_bytes(value.try_into().map_err(|_| {
+        CorruptionError::msg("Invalid expiration time encoding")
+    })?))
+}
+
+/// The kind and the approximate value used in the range index keys, `None` if the term is not a numeric or `xsd:dateTime` literal
+fn range_value(term: &EncodedTerm) -> Option<(u8, f64)> {
+    let (kind, value) = match term {
+        EncodedTerm::IntegerLiteral(value) => (NUMERIC_RANGE_KIND, Double::from(*value)),
+        EncodedTerm::DecimalLiteral(value) => (NUMERIC_RANGE_KIND, Double::from(*value)),
+        EncodedTerm::FloatLiteral(value) => (NUMERIC_RANGE_KIND, Double::from(*value)),
+        EncodedTerm::DoubleLiteral(value) => (NUMERIC_RANGE_KIND, *value),
+        EncodedTerm::DateTimeLiteral(value) => {
+            // The first 16 bytes are the number of seconds on the timeline
+            let mut seconds = [0; 16];
+            seconds.copy_from_slice(&value.to_be_bytes()[..16]);
+            (
+                DATE_TIME_RANGE_KIND,
+                Double::from(Decimal::from_be_bytes(seconds)),
+            )
+        }
+        _ => return None,
+    };
+    let value = f64::from(value);
+    (!value.is_nan()).then(|| (kind, value))
+}
+
+/// Encodes the number such that the byte order is the numeric order, `-0` and `0` being encoded the same way
+fn sortable_f64_bytes(value: f64) -> [u8; 8] {
+    let bits = (value + 0.).to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | 1 << 63
+    }
+    .to_be_bytes()
+}
+
+/// The kinds and inclusive sortable value bounds of the range index keys to scan to find all the objects in the range
+///
+/// The bounds are widened to not miss any value because of the rounding to `f64` and of the timezones.
+fn range_scan_bounds(
+    start: Bound<&EncodedTerm>,
+    end: Bound<&EncodedTerm>,
+) -> Vec<(u8, [u8; 8], [u8; 8])> {
+    let bound_value = |bound: Bound<&EncodedTerm>| match bound {
+        Bound::Included(term) | Bound::Excluded(term) => range_value(term).map(Some),
+        Bound::Unbounded => Some(None),
+    };
+    let (start, end) = if let (Some(start), Some(end)) = (bound_value(start), bound_value(end)) {
+        (start, end)
+    } else {
+        return Vec::new(); // A bound that is not a number or a date time: no object can be in the range
+    };
+    [NUMERIC_RANGE_KIND, DATE_TIME_RANGE_KIND]
+        .into_iter()
+        .filter_map(|kind| {
+            let margin = if kind == DATE_TIME_RANGE_KIND {
+                DATE_TIME_WITHOUT_TIMEZONE_MARGIN
+            } else {
+                0.
+            };
+            let low = match start {
+                Some((k, _)) if k != kind => return None,
+                Some((_, value)) => value - margin,
+                None => f64::NEG_INFINITY,
+            };
+            let high = match end {
+                Some((k, _)) if k != kind => return None,
+                Some((_, value)) => value + margin,
+                None => f64::INFINITY,
+            };
+            Some((kind, sortable_f64_bytes(low), sortable_f64_bytes(high)))
+        })
+        .collect()
+}
+
+fn is_in_range(term: &EncodedTerm, start: Bound<&EncodedTerm>, end: Bound<&EncodedTerm>) -> bool {
+    let after_start = match start {
+        Bound::Included(start) => matches!(
+            compare_range_values(term, start),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        Bound::Excluded(start) => compare_range_values(term, start) == Some(Ordering::Greater),
+        Bound::Unbounded => range_value(term).is_some(),
+    };
+    after_start
+        && match end {
+            Bound::Included(end) => matches!(
+                compare_range_values(term, end),
+                Some(Ordering::Less | Ordering::Equal)
+            ),
+            Bound::Excluded(end) => compare_range_values(term, end) == Some(Ordering::Less),
+            Bound::Unbounded => true,
+        }
+}
+
+/// Compares two numeric or `xsd:dateTime` literals like the SPARQL `<` operator, with the numeric type promotion
+fn compare_range_values(a: &EncodedTerm, b: &EncodedTerm) -> Option<Ordering> {
+    match (a, b) {
+        (EncodedTerm::DateTimeLiteral(a), EncodedTerm::DateTimeLiteral(b)) => a.partial_cmp(b),
+        (EncodedTerm::IntegerLiteral(a), EncodedTerm::IntegerLiteral(b)) => a.partial_cmp(b),
+        (
+            EncodedTerm::IntegerLiteral(_) | EncodedTerm::DecimalLiteral(_),
+            EncodedTerm::IntegerLiteral(_) | EncodedTerm::DecimalLiteral(_),
+        ) => to_decimal(a)?.partial_cmp(&to_decimal(b)?),
+        (EncodedTerm::DoubleLiteral(_), _) | (_, EncodedTerm::DoubleLiteral(_)) => {
+            to_double(a)?.partial_cmp(&to_double(b)?)
+        }
+        _ => to_float(a)?.partial_cmp(&to_float(b)?),
+    }
+}
+
+fn to_decimal(term: &EncodedTerm) -> Option<Decimal> {
+    match term {
+        EncodedTerm::IntegerLiteral(value) => Some((*value).into()),
+        EncodedTerm::DecimalLiteral(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn to_float(term: &EncodedTerm) -> Option<Float> {
+    match term {
+        EncodedTerm::IntegerLiteral(value) => Some((*value).into()),
+        EncodedTerm::DecimalLiteral(value) => Some((*value).into()),
+        EncodedTerm::FloatLiteral(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn to_double(term: &EncodedTerm) -> Option<Double> {
+    match term {
+        EncodedTerm::DoubleLiteral(value) => Some(*value),
+        term => to_float(term).map(Into::into),
+    }
+}
+
+/// The datatype of the term, `None` if it is not a literal
+fn literal_datatype(term: &EncodedTerm) -> Option<EncodedTerm> {
+    Some(match term {
+        EncodedTerm::SmallStringLiteral(_) | EncodedTerm::BigStringLiteral { .. } => {
+            xsd::STRING.into()
+        }
+        EncodedTerm::SmallSmallLangStringLiteral { .. }
+        | EncodedTerm::SmallBigLangStringLiteral { .. }
+        | EncodedTerm::BigSmallLangStringLiteral { .. }
+        | EncodedTerm::BigBigLangStringLiteral { .. } => rdf::LANG_STRING.into(),
+        EncodedTerm::SmallTypedLiteral { datatype_id, .. }
+        | EncodedTerm::BigTypedLiteral { datatype_id, .. } => EncodedTerm::NamedNode {
+            iri_id: *datatype_id,
+        },
+        EncodedTerm::BooleanLiteral(_) => xsd::BOOLEAN.into(),
+        EncodedTerm::FloatLiteral(_) => xsd::FLOAT.into(),
+        EncodedTerm::DoubleLiteral(_) => xsd::DOUBLE.into(),
+        EncodedTerm::IntegerLiteral(_) => xsd::INTEGER.into(),
+        EncodedTerm::DecimalLiteral(_) => xsd::DECIMAL.into(),
+        EncodedTerm::DateTimeLiteral(_) => xsd::DATE_TIME.into(),
+        EncodedTerm::TimeLiteral(_) => xsd::TIME.into(),
+        EncodedTerm::DateLiteral(_) => xsd::DATE.into(),
+        EncodedTerm::GYearMonthLiteral(_) => xsd::G_YEAR_MONTH.into(),
+        EncodedTerm::GYearLiteral(_) => xsd::G_YEAR.into(),
+        EncodedTerm::GMonthDayLiteral(_) => xsd::G_MONTH_DAY.into(),
+        EncodedTerm::GDayLiteral(_) => xsd::G_DAY.into(),
+        EncodedTerm::GMonthLiteral(_) => xsd::G_MONTH.into(),
+        EncodedTerm::DurationLiteral(_) => xsd::DURATION.into(),
+        EncodedTerm::YearMonthDurationLiteral(_) => xsd::YEAR_MONTH_DURATION.into(),
+        EncodedTerm::DayTimeDurationLiteral(_) => xsd::DAY_TIME_DURATION.into(),
+        _ => return None,
+    })
+}
+
+/// The language tag of the term encoded as a simple literal, `None` if it is not a language-tagged string
+fn literal_language(term: &EncodedTerm) -> Option<EncodedTerm> {
+    match term {
+        EncodedTerm::SmallSmallLangStringLiteral { language, .. }
+        | EncodedTerm::BigSmallLangStringLiteral { language, .. } => {
+            Some(EncodedTerm::SmallStringLiteral(*language))
+        }
+        EncodedTerm::SmallBigLangStringLiteral { language_id, .. }
+        | EncodedTerm::BigBigLangStringLiteral { language_id, .. } => {
+            Some(EncodedTerm::BigStringLiteral {
+                value_id: *language_id,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn object_class_count_key(predicate: &EncodedTerm, object_class: ObjectClass) -> Vec<u8> {
+    let mut key = vec![OBJECT_CLASS_COUNT_PREFIX];
+    write_term(&mut key, predicate);
+    key.push(object_class as u8);
+    key
+}
+
+/// Decodes a dictionary value, decompressing it if needed
+fn decode_str(value: &[u8]) -> Result<String, StorageError> {
+    Ok(
+        if let Some((&COMPRESSED_STR_MARKER, compressed)) = value.split_first() {
+            String::from_utf8(decompress_size_prepended(compressed).map_err(CorruptionError::new)?)
+                .map_err(CorruptionError::new)?
+        } else {
+            std::str::from_utf8(value)
+                .map_err(CorruptionError::new)?
+                .to_owned()
+        },
+    )
+}
+
+fn decode_count(value: Option<&[u8]>) -> Result<u64, StorageError> {
+    Ok(if let Some(value) = value {
+        u64::from_be_bytes(
+            value
+                .try_into()
+                .map_err(|_| CorruptionError::msg("Invalid statistics count encoding"))?,
+        )
+    } else {
+        0
+    })
+}
+
+fn decode_commit_version(value: Option<&[u8]>) -> Result<u64, StorageError> {
+    Ok(if let Some(value) = value {
+        u64::from_be_bytes(
+            value
+                .try_into()
+                .map_err(|_| CorruptionError::msg("Invalid commit version encoding"))?,
+        )
+    } else {
+        0
+    })
+}
+
+fn decode_graph_digest(value: Option<&[u8]>) -> Result<u128, StorageError> {
+    Ok(if let Some(value) = value {
+        u128::from_be_bytes(
+            value
+                .try_into()
+                .map_err(|_| CorruptionError::msg("Invalid graph digest encoding"))?,
+        )
+    } else {
+        0
+    })
+}
@@ -8,16 +8,32 @@ use std::str;
 use std::str::{FromStr, Utf8Error};
 
 /// A small inline string
-#[derive(Clone, Copy, Default)]
+///
+/// The strings of at most [`SHORT_MAX_LEN`](Self::SHORT_MAX_LEN) bytes are written in 16 bytes,
+/// the ones of at most [`MAX_LEN`](Self::MAX_LEN) bytes in 33 bytes.
+/// The last byte is the length of the string.
+#[derive(Clone, Copy)]
 #[repr(transparent)]
 pub struct SmallString {
-    inner: [u8; 16],
+    inner: [u8; 33],
+}
+
+impl Default for SmallString {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SmallString {
+    /// The maximal length of the strings written in 16 bytes
+    pub const SHORT_MAX_LEN: usize = 15;
+    /// The maximal length of the strings
+    pub const MAX_LEN: usize = 32;
+
     #[inline]
     pub const fn new() -> Self {
-        Self { inner: [0; 16] }
+        Self { inner: [0; 33] }
     }
 
     #[inline]
@@ -25,17 +41,35 @@ impl SmallString {
         Self::from_str(str::from_utf8(bytes).map_err(BadSmallStringError::BadUtf8)?)
     }
 
+    /// Reads a string written by [`to_be_bytes`](Self::to_be_bytes)
     #[inline]
     pub fn from_be_bytes(bytes: [u8; 16]) -> Result<Self, BadSmallStringError> {
-        // We check that it is valid UTF-8
-        str::from_utf8(&bytes.as_ref()[..bytes[15].into()])
-            .map_err(BadSmallStringError::BadUtf8)?;
-        Ok(Self { inner: bytes })
+        let len = bytes[15].into();
+        if len > Self::SHORT_MAX_LEN {
+            return Err(BadSmallStringError::TooLong(len));
+        }
+        Self::from_utf8(&bytes[..len])
+    }
+
+    /// Reads a string written by [`to_long_be_bytes`](Self::to_long_be_bytes)
+    #[inline]
+    pub fn from_long_be_bytes(bytes: [u8; 33]) -> Result<Self, BadSmallStringError> {
+        let len = bytes[32].into();
+        if len > Self::MAX_LEN {
+            return Err(BadSmallStringError::TooLong(len));
+        }
+        Self::from_utf8(&bytes[..len])
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.inner[15].into()
+        self.inner[32].into()
+    }
+
+    /// Returns if the string is written in 16 bytes by [`to_be_bytes`](Self::to_be_bytes)
+    #[inline]
+    pub fn is_short(&self) -> bool {
+        self.len() <= Self::SHORT_MAX_LEN
     }
 
     #[inline]
@@ -57,8 +91,19 @@ impl SmallString {
         &self.inner[..self.len()]
     }
 
+    /// Writes a [short](Self::is_short) string in 16 bytes
     #[inline]
     pub fn to_be_bytes(self) -> [u8; 16] {
+        debug_assert!(self.is_short());
+        let mut bytes = [0; 16];
+        bytes[..Self::SHORT_MAX_LEN].copy_from_slice(&self.inner[..Self::SHORT_MAX_LEN]);
+        bytes[15] = self.inner[32];
+        bytes
+    }
+
+    /// Writes the string in 33 bytes
+    #[inline]
+    pub fn to_long_be_bytes(self) -> [u8; 33] {
         self.inner
     }
 }
@@ -149,10 +194,10 @@ impl FromStr for SmallString {
 
     #[inline]
     fn from_str(value: &str) -> Result<Self, BadSmallStringError> {
-        if value.len() <= 15 {
-            let mut inner = [0; 16];
+        if value.len() <= Self::MAX_LEN {
+            let mut inner = [0; 33];
             inner[..value.len()].copy_from_slice(value.as_bytes());
-            inner[15] = value
+            inner[32] = value
                 .len()
                 .try_into()
                 .map_err(|_| BadSmallStringError::TooLong(value.len()))?;
@@ -184,7 +229,8 @@ impl fmt::Display for BadSmallStringError {
         match self {
             Self::TooLong(v) => write!(
                 f,
-                "small strings could only contain at most 15 characters, found {v}"
+                "small strings could only contain at most {} bytes, found {v}",
+                SmallString::MAX_LEN
             ),
             Self::BadUtf8(e) => e.fmt(f),
         }
@@ -0,0 +1,440 @@
+//! Validation and normalization of the terms of the inserted quads, see [`StorageConfig::with_term_validation`](super::StorageConfig::with_term_validation)
+//! and [`StorageConfig::with_literal_canonicalization`](super::StorageConfig::with_literal_canonicalization)
+
+use crate::model::*;
+use crate::storage::StorageError;
+use oxilangtag::LanguageTag;
+use oxiri::Iri;
+use oxsdatatypes::*;
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+/// How the invalid terms of the inserted quads are handled, see [`StorageConfig::with_term_validation`](super::StorageConfig::with_term_validation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TermValidation {
+    /// The insertions of quads with an invalid or not normalized term fail with [`StorageError::InvalidTerm`].
+    Reject,
+    /// The IRIs are normalized and the whitespaces around the literal lexical forms are removed,
+    /// the insertions of quads with a term that is still invalid fail with [`StorageError::InvalidTerm`].
+    Repair,
+}
+
+impl TermValidation {
+    /// Validates the quad, returning its repaired version if one of its terms has been repaired
+    pub fn validate_quad(self, quad: QuadRef<'_>) -> Result<Option<Quad>, StorageError> {
+        let subject = self.validate_subject(quad.subject)?;
+        let predicate = self.validate_named_node(quad.predicate)?;
+        let object = self.validate_term(quad.object)?;
+        let graph_name = match quad.graph_name {
+            GraphNameRef::NamedNode(graph_name) => {
+                self.validate_named_node(graph_name)?.map(GraphName::from)
+            }
+            GraphNameRef::BlankNode(_) | GraphNameRef::DefaultGraph => None,
+        };
+        if subject.is_none() && predicate.is_none() && object.is_none() && graph_name.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Quad::new(
+            subject.unwrap_or_else(|| quad.subject.into_owned()),
+            predicate.unwrap_or_else(|| quad.predicate.into_owned()),
+            object.unwrap_or_else(|| quad.object.into_owned()),
+            graph_name.unwrap_or_else(|| quad.graph_name.into_owned()),
+        )))
+    }
+
+    /// Validates the graph name, returning its repaired version if it has been repaired
+    pub fn validate_graph_name(
+        self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<Option<NamedOrBlankNode>, StorageError> {
+        Ok(match graph_name {
+            NamedOrBlankNodeRef::NamedNode(graph_name) => {
+                self.validate_named_node(graph_name)?.map(Into::into)
+            }
+            NamedOrBlankNodeRef::BlankNode(_) => None,
+        })
+    }
+
+    fn validate_subject(self, subject: SubjectRef<'_>) -> Result<Option<Subject>, StorageError> {
+        Ok(match subject {
+            SubjectRef::NamedNode(node) => self.validate_named_node(node)?.map(Into::into),
+            SubjectRef::BlankNode(_) => None,
+            SubjectRef::Triple(triple) => self.validate_triple(triple)?.map(Into::into),
+        })
+    }
+
+    fn validate_term(self, term: TermRef<'_>) -> Result<Option<Term>, StorageError> {
+        Ok(match term {
+            TermRef::NamedNode(node) => self.validate_named_node(node)?.map(Into::into),
+            TermRef::BlankNode(_) => None,
+            TermRef::Literal(literal) => self.validate_literal(literal)?.map(Into::into),
+            TermRef::Triple(triple) => self.validate_triple(triple)?.map(Into::into),
+        })
+    }
+
+    fn validate_triple(self, triple: &Triple) -> Result<Option<Triple>, StorageError> {
+        let subject = self.validate_subject(triple.subject.as_ref())?;
+        let predicate = self.validate_named_node(triple.predicate.as_ref())?;
+        let object = self.validate_term(triple.object.as_ref())?;
+        if subject.is_none() && predicate.is_none() && object.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Triple::new(
+            subject.unwrap_or_else(|| triple.subject.clone()),
+            predicate.unwrap_or_else(|| triple.predicate.clone()),
+            object.unwrap_or_else(|| triple.object.clone()),
+        )))
+    }
+
+    fn validate_named_node(
+        self,
+        node: NamedNodeRef<'_>,
+    ) -> Result<Option<NamedNode>, StorageError> {
+        let normalized =
+            normalize_iri(node.as_str()).map_err(|reason| invalid_term(node, reason))?;
+        if normalized == node.as_str() {
+            Ok(None)
+        } else if self == Self::Repair {
+            Ok(Some(NamedNode::new_unchecked(normalized)))
+        } else {
+            Err(invalid_term(
+                node,
+                format!("the IRI is not normalized, its normalized form is <{normalized}>"),
+            ))
+        }
+    }
+
+    fn validate_literal(self, literal: LiteralRef<'_>) -> Result<Option<Literal>, StorageError> {
+        if let Some(language) = literal.language() {
+            if let Err(e) = LanguageTag::parse(language) {
+                return Err(invalid_term(literal, e.to_string()));
+            }
+            return Ok(None);
+        }
+        let datatype = self.validate_named_node(literal.datatype())?;
+        let datatype_ref = datatype
+            .as_ref()
+            .map_or(literal.datatype(), NamedNode::as_ref);
+        let value = literal.value();
+        let value = match is_valid_lexical_form(value, datatype_ref.as_str()) {
+            None | Some(true) => None,
+            Some(false) => {
+                // The whitespace facet of the non-string datatypes is "collapse"
+                let trimmed = value.trim_matches(|c: char| matches!(c, ' ' | '\t' | '\n' | '\r'));
+                if self == Self::Repair
+                    && is_valid_lexical_form(trimmed, datatype_ref.as_str()) == Some(true)
+                {
+                    Some(trimmed)
+                } else {
+                    return Err(invalid_term(
+                        literal,
+                        format!("the lexical form is not a valid {datatype_ref}"),
+                    ));
+                }
+            }
+        };
+        if datatype.is_none() && value.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Literal::new_typed_literal(
+            value.unwrap_or_else(|| literal.value()),
+            datatype_ref,
+        )))
+    }
+}
+
+/// Canonicalizes the lexical forms of the typed literals of the quad, returning its new version if one of them has changed
+pub fn canonicalize_quad(quad: QuadRef<'_>) -> Option<Quad> {
+    let subject = if let SubjectRef::Triple(triple) = quad.subject {
+        canonicalize_triple(triple).map(Subject::from)
+    } else {
+        None
+    };
+    let object = canonicalize_term(quad.object);
+    if subject.is_none() && object.is_none() {
+        return None;
+    }
+    Some(Quad::new(
+        subject.unwrap_or_else(|| quad.subject.into_owned()),
+        quad.predicate,
+        object.unwrap_or_else(|| quad.object.into_owned()),
+        quad.graph_name,
+    ))
+}
+
+fn canonicalize_triple(triple: &Triple) -> Option<Triple> {
+    let subject = if let Subject::Triple(triple) = &triple.subject {
+        canonicalize_triple(triple).map(Subject::from)
+    } else {
+        None
+    };
+    let object = canonicalize_term(triple.object.as_ref());
+    if subject.is_none() && object.is_none() {
+        return None;
+    }
+    Some(Triple::new(
+        subject.unwrap_or_else(|| triple.subject.clone()),
+        triple.predicate.clone(),
+        object.unwrap_or_else(|| triple.object.clone()),
+    ))
+}
+
+fn canonicalize_term(term: TermRef<'_>) -> Option<Term> {
+    match term {
+        TermRef::Literal(literal) if literal.language().is_none() => {
+            canonical_lexical_form(literal.value(), literal.datatype().as_str())
+                .map(|value| Literal::new_typed_literal(value, literal.datatype()).into())
+        }
+        TermRef::Triple(triple) => canonicalize_triple(triple).map(Into::into),
+        _ => None,
+    }
+}
+
+/// The canonical lexical form of the value, `None` if it is already canonical or if it is not a valid value of a supported XSD datatype
+fn canonical_lexical_form(value: &str, datatype: &str) -> Option<String> {
+    let is_whitespace = |c: char| matches!(c, ' ' | '\t' | '\n' | '\r');
+    let collapsed = value
+        .split(is_whitespace)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let canonical = match datatype {
+        "http://www.w3.org/2001/XMLSchema#normalizedString" => value.replace(is_whitespace, " "),
+        "http://www.w3.org/2001/XMLSchema#token"
+        | "http://www.w3.org/2001/XMLSchema#Name"
+        | "http://www.w3.org/2001/XMLSchema#NCName"
+        | "http://www.w3.org/2001/XMLSchema#NMTOKEN"
+        | "http://www.w3.org/2001/XMLSchema#ID"
+        | "http://www.w3.org/2001/XMLSchema#IDREF"
+        | "http://www.w3.org/2001/XMLSchema#ENTITY"
+        | "http://www.w3.org/2001/XMLSchema#anyURI" => collapsed,
+        "http://www.w3.org/2001/XMLSchema#language" => collapsed.to_ascii_lowercase(),
+        "http://www.w3.org/2001/XMLSchema#hexBinary" => collapsed.to_ascii_uppercase(),
+        "http://www.w3.org/2001/XMLSchema#base64Binary" => {
+            value.chars().filter(|c| !is_whitespace(*c)).collect()
+        }
+        _ => {
+            if is_valid_lexical_form(&collapsed, datatype) != Some(true) {
+                return None;
+            }
+            match datatype {
+                "http://www.w3.org/2001/XMLSchema#boolean" => canonical::<Boolean>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#float" => canonical::<Float>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#double" => canonical::<Double>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#decimal" => canonical::<Decimal>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#dateTime"
+                | "http://www.w3.org/2001/XMLSchema#dateTimeStamp" => {
+                    canonical::<DateTime>(&collapsed)?
+                }
+                "http://www.w3.org/2001/XMLSchema#time" => canonical::<Time>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#date" => canonical::<Date>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#gYearMonth" => {
+                    canonical::<GYearMonth>(&collapsed)?
+                }
+                "http://www.w3.org/2001/XMLSchema#gYear" => canonical::<GYear>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#gMonthDay" => canonical::<GMonthDay>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#gDay" => canonical::<GDay>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#gMonth" => canonical::<GMonth>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#duration" => canonical::<Duration>(&collapsed)?,
+                "http://www.w3.org/2001/XMLSchema#yearMonthDuration" => {
+                    canonical::<YearMonthDuration>(&collapsed)?
+                }
+                "http://www.w3.org/2001/XMLSchema#dayTimeDuration" => {
+                    canonical::<DayTimeDuration>(&collapsed)?
+                }
+                // The integer and its derived datatypes
+                _ => canonical::<Integer>(&collapsed)?,
+            }
+        }
+    };
+    if canonical == value {
+        None
+    } else {
+        Some(canonical)
+    }
+}
+
+fn canonical<T: FromStr + Display>(value: &str) -> Option<String> {
+    Some(T::from_str(value).ok()?.to_string())
+}
+
+fn invalid_term(term: impl Into<TermRef<'_>>, reason: impl Into<String>) -> StorageError {
+    StorageError::InvalidTerm {
+        term: term.into().to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Checks the lexical form against the XSD datatype, `None` if the datatype is not supported
+fn is_valid_lexical_form(value: &str, datatype: &str) -> Option<bool> {
+    Some(match datatype {
+        "http://www.w3.org/2001/XMLSchema#boolean" => Boolean::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#float" => Float::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#double" => Double::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#decimal" => Decimal::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#integer" => Integer::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#byte" => is_integer_in(value, i8::MIN, i8::MAX),
+        "http://www.w3.org/2001/XMLSchema#short" => is_integer_in(value, i16::MIN, i16::MAX),
+        "http://www.w3.org/2001/XMLSchema#int" => is_integer_in(value, i32::MIN, i32::MAX),
+        "http://www.w3.org/2001/XMLSchema#long" => is_integer_in(value, i64::MIN, i64::MAX),
+        "http://www.w3.org/2001/XMLSchema#unsignedByte" => is_integer_in(value, 0, u8::MAX),
+        "http://www.w3.org/2001/XMLSchema#unsignedShort" => is_integer_in(value, 0, u16::MAX),
+        "http://www.w3.org/2001/XMLSchema#unsignedInt" => is_integer_in(value, 0, u32::MAX),
+        "http://www.w3.org/2001/XMLSchema#unsignedLong"
+        | "http://www.w3.org/2001/XMLSchema#nonNegativeInteger" => {
+            is_integer_in(value, 0, i64::MAX)
+        }
+        "http://www.w3.org/2001/XMLSchema#positiveInteger" => is_integer_in(value, 1, i64::MAX),
+        "http://www.w3.org/2001/XMLSchema#negativeInteger" => is_integer_in(value, i64::MIN, -1),
+        "http://www.w3.org/2001/XMLSchema#nonPositiveInteger" => is_integer_in(value, i64::MIN, 0),
+        "http://www.w3.org/2001/XMLSchema#dateTime" => DateTime::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#dateTimeStamp" => {
+            DateTime::from_str(value).map_or(false, |value| value.timezone_offset().is_some())
+        }
+        "http://www.w3.org/2001/XMLSchema#time" => Time::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#date" => Date::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#gYearMonth" => GYearMonth::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#gYear" => GYear::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#gMonthDay" => GMonthDay::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#gDay" => GDay::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#gMonth" => GMonth::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#duration" => Duration::from_str(value).is_ok(),
+        "http://www.w3.org/2001/XMLSchema#yearMonthDuration" => {
+            YearMonthDuration::from_str(value).is_ok()
+        }
+        "http://www.w3.org/2001/XMLSchema#dayTimeDuration" => {
+            DayTimeDuration::from_str(value).is_ok()
+        }
+        _ => return None,
+    })
+}
+
+fn is_integer_in(value: &str, min: impl Into<Integer>, max: impl Into<Integer>) -> bool {
+    Integer::from_str(value).map_or(false, |value| min.into() <= value && value <= max.into())
+}
+
+/// Normalizes the IRI following the syntax-based and scheme-based normalizations of [RFC 3987](https://www.rfc-editor.org/rfc/rfc3987#section-5.3):
+/// the scheme and the host are lowercased, the percent-encodings are uppercased or decoded for the unreserved characters,
+/// the dot segments are removed from the path, the default ports are removed and the empty HTTP paths are replaced by `/`.
+fn normalize_iri(iri: &str) -> Result<String, String> {
+    let iri = Iri::parse(iri).map_err(|e| e.to_string())?;
+    let iri = normalize_percent_encoding(iri.as_str());
+    let iri = Iri::parse(iri.as_str()).map_err(|e| e.to_string())?;
+    let scheme = iri.scheme().to_ascii_lowercase();
+    let mut normalized = String::with_capacity(iri.as_str().len());
+    normalized.push_str(&scheme);
+    normalized.push(':');
+    if let Some(authority) = iri.authority() {
+        normalized.push_str("//");
+        let (user_info, host_port) = match authority.rfind('@') {
+            Some(end) => authority.split_at(end + 1),
+            None => ("", authority),
+        };
+        normalized.push_str(user_info);
+        let port_start = if host_port.starts_with('[') {
+            host_port.find(']').map_or(host_port.len(), |end| end + 1)
+        } else {
+            host_port.rfind(':').unwrap_or(host_port.len())
+        };
+        let (host, port) = host_port.split_at(port_start);
+        normalized.push_str(&lowercase_outside_escapes(host));
+        let default_port = match scheme.as_str() {
+            "http" | "ws" => ":80",
+            "https" | "wss" => ":443",
+            "ftp" => ":21",
+            _ => ":",
+        };
+        if port != ":" && port != default_port {
+            normalized.push_str(port);
+        }
+        if iri.path().is_empty() && matches!(scheme.as_str(), "http" | "https") {
+            normalized.push('/');
+        }
+    }
+    normalized.push_str(&remove_dot_segments(iri.path()));
+    if let Some(query) = iri.query() {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    if let Some(fragment) = iri.fragment() {
+        normalized.push('#');
+        normalized.push_str(fragment);
+    }
+    Ok(normalized)
+}
+
+/// Uppercases the hexadecimal digits of the percent-encodings and decodes the ones of the unreserved characters
+fn normalize_percent_encoding(iri: &str) -> String {
+    let mut normalized = String::with_capacity(iri.len());
+    let mut chars = iri.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '%' {
+            if let Some(byte) = iri
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                chars.nth(1);
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                    normalized.push(char::from(byte));
+                } else {
+                    write!(normalized, "%{byte:02X}").unwrap();
+                }
+                continue;
+            }
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+fn lowercase_outside_escapes(value: &str) -> String {
+    let mut lowercased = String::with_capacity(value.len());
+    let mut escape = 0;
+    for c in value.chars() {
+        if c == '%' {
+            escape = 2;
+            lowercased.push(c);
+        } else if escape > 0 {
+            escape -= 1;
+            lowercased.push(c);
+        } else {
+            lowercased.push(c.to_ascii_lowercase());
+        }
+    }
+    lowercased
+}
+
+/// The `remove_dot_segments` algorithm of [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986#section-5.2.4)
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if let Some(rest) = input
+            .strip_prefix("../")
+            .or_else(|| input.strip_prefix("./"))
+        {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.truncate(output.rfind('/').unwrap_or(0));
+        } else if input == "/.." {
+            input = "/";
+            output.truncate(output.rfind('/').unwrap_or(0));
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..]
+                .find('/')
+                .map_or(input.len(), |end| end + start);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
@@ -23,24 +23,96 @@
 //! };
 //! # Result::<_, Box<dyn std::error::Error>>::Ok(())
 //! ```
+//!
+//! [RDF-star](https://w3c.github.io/rdf-star/cg-spec/) quoted triples are supported in the storage,
+//! in SPARQL-star queries and updates and in the Turtle-star, TriG-star, N-Triples-star and N-Quads-star formats.
+//! They allow to annotate a statement without reification:
+//! ```
+//! use oxigraph::io::GraphFormat;
+//! use oxigraph::model::*;
+//! use oxigraph::sparql::QueryResults;
+//! use oxigraph::store::Store;
+//!
+//! let store = Store::new()?;
+//!
+//! // Turtle-star loading
+//! store.load_graph(
+//!     b"@prefix ex: <http://example.com/> .\nex:alice ex:knows ex:bob .\n<< ex:alice ex:knows ex:bob >> ex:since 2010 .".as_ref(),
+//!     GraphFormat::Turtle,
+//!     GraphNameRef::DefaultGraph,
+//!     None,
+//! )?;
+//!
+//! // SPARQL-star update with the annotation syntax
+//! store.update("PREFIX ex: <http://example.com/> INSERT DATA { ex:bob ex:knows ex:carol {| ex:since 2015 |} }")?;
+//!
+//! // SPARQL-star query
+//! if let QueryResults::Solutions(mut solutions) = store.query("PREFIX ex: <http://example.com/> SELECT ?since WHERE { << ex:alice ex:knows ex:bob >> ex:since ?since }")? {
+//!     assert_eq!(solutions.next().unwrap()?.get("since"), Some(&Literal::from(2010).into()));
+//! };
+//!
+//! // N-Triples-star serialization
+//! let mut buffer = Vec::new();
+//! store.dump_graph(&mut buffer, GraphFormat::NTriples, GraphNameRef::DefaultGraph)?;
+//! assert!(String::from_utf8(buffer)?.contains("<< <http://example.com/bob> <http://example.com/knows> <http://example.com/carol> >> <http://example.com/since> \"2015\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n"));
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::acl::GraphAccess;
+#[cfg(feature = "credentials")]
+use crate::credentials::{verify_credential, CredentialError};
+use crate::events::{notify_canister, ChangeNotification};
 use crate::io::read::ParseError;
 use crate::io::{
-    DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser, GraphSerializer,
+    Compression, DatasetFormat, DatasetParser, DatasetSerializer, GraphFormat, GraphParser,
+    GraphSerializer,
 };
+use crate::model::vocab::skos;
 use crate::model::*;
+use crate::reasoner::validate_rule;
 use crate::sparql::{
-    evaluate_query, evaluate_update, EvaluationError, Query, QueryExplanation, QueryOptions,
-    QueryResults, Update, UpdateOptions,
+    estimate_query_cost, evaluate_query, evaluate_update, BatchedUpdate, CostEstimate,
+    EvaluationError, PropertyPathExpression, Query, QueryExplanation, QueryOptions, QueryResults,
+    QuerySolution, Update, UpdateOptions, Variable,
 };
 use crate::storage::numeric_encoder::{Decoder, EncodedQuad, EncodedTerm};
 use crate::storage::{
-    ChainedDecodingQuadIterator, DecodingGraphIterator, Storage, StorageReader, StorageWriter,
+    ChainedDecodingQuadIterator, ChangeLogIterator, DecodingGraphIterator, EncodedChange,
+    EncodedReadFilter, IndexRebuildState, LockedGraphs, LogCompactionState, QuadScanState, Storage,
+    StorageReader, StorageSavepoint, StorageWriter, StringCollectionState,
 };
-pub use crate::storage::{CorruptionError, LoaderError, SerializerError, StorageError};
+pub use crate::storage::{
+    CorruptionError, CorruptionReport, IsolationLevel, LoaderError, Quota, SerializerError,
+    StorageError,
+};
+pub use crate::storage::{KeyValueStore, ObjectClass, QuadIndex, StorageConfig, TermValidation};
+use crate::summary::{summarize, DatasetSummary, SummaryBuilder};
+use ic_cdk::api::{performance_counter, set_certified_data, time};
+use ic_cdk::export::candid::Principal;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::io::{BufRead, Write};
+use std::io::{self, BufRead, Read, Write};
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use std::{fmt, str};
 
+const VIEW_GRAPH_PREFIX: &[u8] = b"view_graph/";
+const PREFIX_KEY_PREFIX: &[u8] = b"prefix/";
+const RULE_KEY_PREFIX: &[u8] = b"rule/";
+/// Maximal number of materialized query results kept for [`Store::query_paged`]
+const MAX_OPEN_CURSORS: usize = 16;
+/// The maximal number of bulk load sessions open at the same time.
+const MAX_BULK_LOAD_SESSIONS: usize = 4;
+/// The maximal number of bytes received by a bulk load session.
+const MAX_BULK_LOAD_SESSION_SIZE: usize = 256 * 1024 * 1024;
+/// The default number of quads inserted by each transaction of a [`BulkLoader`].
+const DEFAULT_BULK_LOAD_BATCH_SIZE: usize = 10_000;
+/// Number of keys touched by each step of the collection done by [`Store::gc_strings`]
+const GC_STRINGS_BATCH_SIZE: usize = 10_000;
+
 /// An on-disk [RDF dataset](https://www.w3.org/TR/rdf11-concepts/#dfn-rdf-dataset).
 /// Allows to query and update it using SPARQL.
 /// It is based on the [RocksDB](https://rocksdb.org/) key-value store.
@@ -80,6 +152,11 @@ use std::{fmt, str};
 #[derive(Clone)]
 pub struct Store {
     storage: Storage,
+    maintenance: Arc<RwLock<Option<Maintenance>>>,
+    subscriptions: Arc<RwLock<Subscriptions>>,
+    triggers: Arc<RwLock<Triggers>>,
+    cursors: Arc<RwLock<Cursors>>,
+    bulk_loads: Arc<RwLock<BulkLoads>>,
 }
 
 impl Store {
@@ -87,6 +164,46 @@ impl Store {
     pub fn new() -> Result<Self, StorageError> {
         Ok(Self {
             storage: Storage::new()?,
+            maintenance: Arc::default(),
+            subscriptions: Arc::default(),
+            triggers: Arc::default(),
+            cursors: Arc::default(),
+            bulk_loads: Arc::default(),
+        })
+    }
+
+    /// Creates a [`Store`] with a custom configuration of its column families.
+    ///
+    /// The configuration allows to disable some secondary indexes to save memory
+    /// and, with the `stable-memory` feature, to store some column families in stable memory regions
+    /// (see [`StorageConfig::with_stable_column_family`]).
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{QuadIndex, StorageConfig, Store};
+    ///
+    /// // Only the SPOG, POSG and GSPO indexes are built
+    /// let config = StorageConfig::new().with_indexes([QuadIndex::Spog, QuadIndex::Posg, QuadIndex::Gspo]);
+    /// let store = Store::new_with_config(&config)?;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// store.insert(quad)?;
+    ///
+    /// // The lookups by object scan SPOG as OSPG is disabled
+    /// let results = store.quads_for_pattern(None, None, Some(ex.into()), None).collect::<Result<Vec<_>,_>>()?;
+    /// assert_eq!(vec![quad.into_owned()], results);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn new_with_config(config: &StorageConfig) -> Result<Self, StorageError> {
+        Ok(Self {
+            storage: Storage::new_with_config(config)?,
+            maintenance: Arc::default(),
+            subscriptions: Arc::default(),
+            triggers: Arc::default(),
+            cursors: Arc::default(),
+            bulk_loads: Arc::default(),
         })
     }
 
@@ -146,11 +263,59 @@ impl Store {
         results
     }
 
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) on behalf of a caller.
+    ///
+    /// The graphs the [access control list](crate::acl) does not allow the principal to read are hidden from the evaluation,
+    /// even if the query dataset explicitly lists them.
+    ///
+    /// Usage example:
+    /// ```
+    /// use ic_cdk::export::candid::Principal;
+    /// use oxigraph::acl::{grant, principal_iri, Access};
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::QueryResults;
+    /// use oxigraph::store::Store;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// grant(&store, ex.into(), principal_iri(Principal::management_canister()).as_ref(), Access::Read)?;
+    ///
+    /// if let QueryResults::Boolean(found) = store.query_as(Principal::management_canister(), "ASK { GRAPH <http://example.com> { ?s ?p ?o } }")? {
+    ///     assert!(found);
+    /// }
+    /// if let QueryResults::Boolean(found) = store.query_as(Principal::anonymous(), "ASK FROM <http://example.com> { ?s ?p ?o }")? {
+    ///     assert!(!found);
+    /// }
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn query_as(
+        &self,
+        principal: Principal,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError> {
+        let access = GraphAccess::for_principal(self, principal)?;
+        self.query_opt(
+            query,
+            QueryOptions::default().with_hidden_graphs(access.hidden_graphs().cloned().collect()),
+        )
+    }
+
     /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options and
     /// returns a query explanation with some statistics (if enabled with the `with_stats` parameter).
     ///
+    /// The statistics give for each plan operator its number of results, its duration
+    /// and the number of instructions it executed, measured with `ic_cdk::api::performance_counter`.
+    /// The property path operators also give the number of nodes visited by their transitive closures.
+    /// Each operator also gives the number of index entries read by it and its children and the number of index scans
+    /// they have stopped before their end, like the ones of an `ASK` query or of an `EXISTS` filter stopping at their first solution.
+    /// The scans are only read lazily inside [transactions](Self::transaction) and on [past versions](Self::snapshot_at),
+    /// the scans of the current content reading all their entries at once to not see the writes done while they are consumed.
+    ///
     /// Beware: if you want to compute statistics you need to exhaust the results iterator before having a look at them.
     ///
+    /// The plan can be traversed with [`QueryExplanation::plan`] or serialized in JSON.
+    ///
     /// Usage example serialising the explanation with statistics in JSON:
     /// ```
     /// use oxigraph::store::Store;
@@ -172,7 +337,243 @@ impl Store {
         options: QueryOptions,
         with_stats: bool,
     ) -> Result<(Result<QueryResults, EvaluationError>, QueryExplanation), EvaluationError> {
-        evaluate_query(self.storage.snapshot(), query, options, with_stats)
+        let reader = if let Some(version) = options.as_of() {
+            self.storage.snapshot_at(version)?
+        } else {
+            self.storage.snapshot()
+        };
+        evaluate_query(reader, query, options, with_stats)
+    }
+
+    /// Predicts the number of results of a query and the instructions needed to evaluate it, without evaluating it.
+    ///
+    /// The prediction is built from the query plan and the store [statistics](Self::statistics):
+    /// it is only an order of magnitude, but it allows to refuse the queries that would obviously exceed a cycle budget.
+    /// It fails if the query calls a custom function other than the XSD casts.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::Query;
+    /// use oxigraph::store::Store;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// for i in 0..3 {
+    ///     store.insert(QuadRef::new(ex, ex, &Literal::from(i), GraphNameRef::DefaultGraph))?;
+    /// }
+    /// let query = Query::parse("SELECT ?s WHERE { ?s <http://example.com> ?o }", None)?;
+    /// let estimate = store.estimate_query_cost(&query)?;
+    /// assert_eq!(estimate.results(), 3);
+    /// if estimate.instructions() < 1_000_000_000 {
+    ///     store.query(query)?;
+    /// }
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn estimate_query_cost(&self, query: &Query) -> Result<CostEstimate, EvaluationError> {
+        estimate_query_cost(self.storage.snapshot(), query)
+    }
+
+    /// Executes a [SPARQL 1.1 SELECT query](https://www.w3.org/TR/sparql11-query/#select) and returns a page of at most `limit` solutions.
+    ///
+    /// The first call, with [`PageToken::default()`], evaluates the query and materializes its solutions in the store.
+    /// The next pages are read from the materialized solutions using the token returned by [`QueryPage::next_token`],
+    /// so they are stable even if the store is modified in between.
+    /// The materialized solutions are dropped when the last page is returned.
+    /// At most 16 materialized results are kept, the oldest ones being dropped first.
+    ///
+    /// The state changes made during canister query calls are discarded,
+    /// so the first page must be requested using an update call for the next pages to be available.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{PageToken, Store};
+    ///
+    /// let store = Store::new()?;
+    /// let query = "SELECT ?o WHERE { VALUES ?o { 1 2 3 } }";
+    ///
+    /// let page = store.query_paged(query, PageToken::default(), 2)?;
+    /// assert_eq!(page.solutions().len(), 2);
+    /// let token = page.next_token().unwrap();
+    ///
+    /// // The token can be sent to the client and parsed back
+    /// let page = store.query_paged(query, token.to_string().parse()?, 2)?;
+    /// assert_eq!(page.solutions()[0].get("o"), Some(&Literal::from(3).into()));
+    /// assert_eq!(page.next_token(), None);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn query_paged(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        token: PageToken,
+        limit: usize,
+    ) -> Result<QueryPage, EvaluationError> {
+        let query = query.try_into().map_err(Into::into)?;
+        let query_string = query.to_string();
+        let cursor_id = if token.cursor == 0 {
+            let solutions = match self.query(query)? {
+                QueryResults::Solutions(solutions) => solutions,
+                _ => return Err(EvaluationError::msg("Only SELECT queries can be paged")),
+            };
+            let variables = solutions.variables().to_vec();
+            let solutions = solutions
+                .map(|solution| solution.map(|solution| solution.values().to_vec()))
+                .collect::<Result<_, _>>()?;
+            let mut cursors = self.cursors.write().unwrap();
+            cursors.next_id += 1;
+            let id = cursors.next_id;
+            cursors.entries.insert(
+                id,
+                Cursor {
+                    query: query_string.clone(),
+                    variables,
+                    solutions,
+                },
+            );
+            while cursors.entries.len() > MAX_OPEN_CURSORS {
+                let oldest = *cursors.entries.keys().next().unwrap();
+                cursors.entries.remove(&oldest);
+            }
+            id
+        } else {
+            token.cursor
+        };
+        let mut cursors = self.cursors.write().unwrap();
+        let cursor = cursors
+            .entries
+            .get(&cursor_id)
+            .ok_or_else(|| EvaluationError::msg("The page token has expired"))?;
+        if cursor.query != query_string {
+            return Err(EvaluationError::msg(
+                "The page token has been built for an other query",
+            ));
+        }
+        let start = usize::try_from(token.offset)
+            .unwrap_or(usize::MAX)
+            .min(cursor.solutions.len());
+        let end = start
+            .saturating_add(limit.max(1))
+            .min(cursor.solutions.len());
+        let variables = Rc::new(cursor.variables.clone());
+        let solutions = cursor.solutions[start..end]
+            .iter()
+            .map(|values| QuerySolution::from((Rc::clone(&variables), values.clone())))
+            .collect();
+        let next = if end < cursor.solutions.len() {
+            Some(PageToken {
+                cursor: cursor_id,
+                offset: end.try_into().unwrap_or(u64::MAX),
+            })
+        } else {
+            cursors.entries.remove(&cursor_id);
+            None
+        };
+        Ok(QueryPage {
+            variables: variables.to_vec(),
+            solutions,
+            next,
+        })
+    }
+
+    /// Executes a [SPARQL 1.1 SELECT query](https://www.w3.org/TR/sparql11-query/#select)
+    /// until the current message has executed `instruction_budget` instructions.
+    ///
+    /// The number of executed instructions is checked with [`performance_counter`] after each solution.
+    /// If the budget is exhausted before the end of the query, the solutions found so far are returned
+    /// with a [`QueryContinuation`] to give to [`resume_query`](Self::resume_query) in a later call, for example from a timer.
+    /// Each call returns at least one solution, except if the query has no more solutions.
+    ///
+    /// The operations materializing their input (`ORDER BY`, `GROUP BY`...) are not interrupted,
+    /// so the budget should keep enough margin below the canister instruction limit.
+    ///
+    /// This method must be used inside a canister.
+    ///
+    /// Usage example:
+    /// ```no_run
+    /// use oxigraph::store::{QueryContinuation, Store};
+    ///
+    /// let store = Store::new()?;
+    /// let mut results = store.query_with_budget("SELECT * WHERE { ?s ?p ?o }", 1_000_000_000)?;
+    /// let mut solutions = results.solutions().len();
+    /// while let Some(continuation) = results.continuation() {
+    ///     // In a real canister, the continuation would be stored and resumed by a later message
+    ///     results = store.resume_query(&QueryContinuation::from_bytes(&continuation.to_bytes())?, 1_000_000_000)?;
+    ///     solutions += results.solutions().len();
+    /// }
+    /// # let _ = solutions;
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn query_with_budget(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        instruction_budget: u64,
+    ) -> Result<BudgetedSolutions, EvaluationError> {
+        let query = query.try_into().map_err(Into::into)?;
+        let continuation = QueryContinuation {
+            query: query.to_string(),
+            dataset_version: self.dataset_version()?,
+            skip: 0,
+        };
+        self.evaluate_with_budget(query, continuation, instruction_budget)
+    }
+
+    /// Resumes a query suspended by [`query_with_budget`](Self::query_with_budget).
+    ///
+    /// The query is evaluated again and the solutions already returned are skipped.
+    /// It fails if the store has been modified since the query has been started.
+    ///
+    /// This method must be used inside a canister.
+    pub fn resume_query(
+        &self,
+        continuation: &QueryContinuation,
+        instruction_budget: u64,
+    ) -> Result<BudgetedSolutions, EvaluationError> {
+        if continuation.dataset_version != self.dataset_version()? {
+            return Err(EvaluationError::msg(
+                "The store has been modified since the query has been suspended",
+            ));
+        }
+        let query = Query::parse(&continuation.query, None)?;
+        self.evaluate_with_budget(query, continuation.clone(), instruction_budget)
+    }
+
+    fn evaluate_with_budget(
+        &self,
+        query: Query,
+        mut continuation: QueryContinuation,
+        instruction_budget: u64,
+    ) -> Result<BudgetedSolutions, EvaluationError> {
+        let mut solutions = match self.query(query)? {
+            QueryResults::Solutions(solutions) => solutions,
+            _ => return Err(EvaluationError::msg("Only SELECT queries can be suspended")),
+        };
+        let variables = solutions.variables().to_vec();
+        for _ in 0..continuation.skip {
+            if solutions.next().transpose()?.is_none() {
+                break;
+            }
+        }
+        let mut found = Vec::new();
+        let mut exhausted = true;
+        for solution in solutions {
+            found.push(solution?);
+            if performance_counter(0) >= instruction_budget {
+                exhausted = false;
+                break;
+            }
+        }
+        let continuation = if exhausted {
+            None
+        } else {
+            continuation.skip += u64::try_from(found.len()).unwrap_or(u64::MAX);
+            Some(continuation)
+        };
+        Ok(BudgetedSolutions {
+            variables,
+            solutions: found,
+            continuation,
+        })
     }
 
     /// Retrieves quads with a filter on each quad component
@@ -213,6 +614,101 @@ impl Store {
         }
     }
 
+    /// Retrieves quads with a filter on each quad component, in the given [`Order`].
+    ///
+    /// The quads are sorted by their keys in the storage index used to evaluate the pattern.
+    /// This order is stable but is not the SPARQL `ORDER BY` one: the terms are compared by their encoding.
+    /// With [`Order::Desc`], the last quads of [`quads_for_pattern`](Self::quads_for_pattern) are returned first,
+    /// allowing to get them without going through the other ones.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{Order, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// for value in [1, 2, 3] {
+    ///     store.insert(QuadRef::new(ex, ex, Literal::from(value).as_ref(), GraphNameRef::DefaultGraph))?;
+    /// }
+    ///
+    /// let ascending = store.quads_for_pattern_ordered(Some(ex.into()), None, None, None, Order::Asc).collect::<Result<Vec<_>,_>>()?;
+    /// let mut descending = store.quads_for_pattern_ordered(Some(ex.into()), None, None, None, Order::Desc).collect::<Result<Vec<_>,_>>()?;
+    /// descending.reverse();
+    /// assert_eq!(ascending, descending);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn quads_for_pattern_ordered(
+        &self,
+        subject: Option<SubjectRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+        order: Order,
+    ) -> QuadIter {
+        let reader = self.storage.snapshot();
+        let iter = reader.quads_for_pattern(
+            subject.map(EncodedTerm::from).as_ref(),
+            predicate.map(EncodedTerm::from).as_ref(),
+            object.map(EncodedTerm::from).as_ref(),
+            graph_name.map(EncodedTerm::from).as_ref(),
+        );
+        QuadIter {
+            iter: match order {
+                Order::Asc => iter,
+                Order::Desc => iter.rev(),
+            },
+            reader,
+        }
+    }
+
+    /// Retrieves the quads with the given predicate and a numeric or `xsd:dateTime` object in the given range.
+    ///
+    /// The objects are compared with the bounds like with the SPARQL `<` operator,
+    /// so `xsd:integer` and `xsd:double` values are compared together.
+    /// The [range index](StorageConfig::with_range_index) is used if enabled, else all the quads with the predicate are scanned.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageConfig, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_range_index())?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// for value in [1, 5, 10] {
+    ///     store.insert(QuadRef::new(ex, ex, Literal::from(value).as_ref(), GraphNameRef::DefaultGraph))?;
+    /// }
+    /// store.insert(QuadRef::new(ex, ex, Literal::from(7.5).as_ref(), GraphNameRef::DefaultGraph))?;
+    ///
+    /// let results = store.quads_in_range(ex, Literal::from(5)..Literal::from(10))?;
+    /// assert_eq!(2, results.len());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn quads_in_range<'a>(
+        &self,
+        predicate: impl Into<NamedNodeRef<'a>>,
+        range: impl RangeBounds<Literal>,
+    ) -> Result<Vec<Quad>, StorageError> {
+        let encode_bound = |bound: Bound<&Literal>| match bound {
+            Bound::Included(value) => Bound::Included(EncodedTerm::from(value.as_ref())),
+            Bound::Excluded(value) => Bound::Excluded(EncodedTerm::from(value.as_ref())),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let as_ref = |bound: &Bound<EncodedTerm>| match bound {
+            Bound::Included(value) => Bound::Included(value),
+            Bound::Excluded(value) => Bound::Excluded(value),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let start = encode_bound(range.start_bound());
+        let end = encode_bound(range.end_bound());
+        let reader = self.storage.snapshot();
+        reader
+            .quads_in_range(&predicate.into().into(), as_ref(&start), as_ref(&end))?
+            .iter()
+            .map(|quad| reader.decode_quad(quad))
+            .collect()
+    }
+
     /// Returns all the quads contained in the store.
     ///
     /// Usage example:
@@ -232,6 +728,31 @@ impl Store {
     /// assert_eq!(vec![quad], results);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
+    ///
+    /// The iterator does not see the writes done while it is consumed:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// for i in 0..3 {
+    ///     let ex = NamedNode::new(format!("http://example.com/{i}"))?;
+    ///     store.insert(QuadRef::new(&ex, &ex, &ex, GraphNameRef::DefaultGraph))?;
+    /// }
+    ///
+    /// let mut count = 0;
+    /// for quad in store.iter() {
+    ///     if let Subject::NamedNode(s) = quad?.subject {
+    ///         // Sorted after the current quad
+    ///         let next = NamedNode::new(format!("{}/next", s.as_str()))?;
+    ///         store.insert(QuadRef::new(&next, &next, &next, GraphNameRef::DefaultGraph))?;
+    ///     }
+    ///     count += 1;
+    /// }
+    /// assert_eq!(count, 3);
+    /// assert_eq!(store.len()?, 6);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
     pub fn iter(&self) -> QuadIter {
         self.quads_for_pattern(None, None, None, None)
     }
@@ -254,13 +775,13 @@ impl Store {
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
     pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
-        let quad = EncodedQuad::from(quad.into());
+        let quad = self.storage.encode_stored_quad(quad.into());
         self.storage.snapshot().contains(&quad)
     }
 
     /// Returns the number of quads in the store.
     ///
-    /// Warning: this function executes a full scan.
+    /// The number is maintained on every insertion and removal, so getting it does not require a scan of the store.
     ///
     /// Usage example:
     /// ```
@@ -278,6 +799,33 @@ impl Store {
         self.storage.snapshot().len()
     }
 
+    /// Returns the number of quads in the given graph.
+    ///
+    /// Like [`len`](Self::len), the number is maintained on every insertion and removal.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, LiteralRef::new_simple_literal("foo"), GraphNameRef::DefaultGraph))?;
+    /// assert_eq!(1, store.graph_len(ex)?);
+    /// assert_eq!(2, store.graph_len(GraphNameRef::DefaultGraph)?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn graph_len<'a>(
+        &self,
+        graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<usize, StorageError> {
+        self.storage
+            .snapshot()
+            .graph_len(&EncodedTerm::from(graph_name.into()))
+    }
+
     /// Returns if the store is empty.
     ///
     /// Usage example:
@@ -326,7 +874,78 @@ impl Store {
         &'b self,
         f: impl Fn(Transaction<'a>) -> Result<T, E>,
     ) -> Result<T, E> {
-        self.storage.transaction(|writer| f(Transaction { writer }))
+        self.transaction_with_isolation(IsolationLevel::RepeatableRead, f)
+    }
+
+    /// Starts a transaction whose writes are buffered until it is explicitly [committed](OpenTransaction::commit).
+    ///
+    /// Unlike [`transaction`](Self::transaction), the transaction is not bound to a closure,
+    /// so it can be passed between helper functions and kept across the `await` points of inter-canister calls.
+    /// Dropping it or calling [`rollback`](OpenTransaction::rollback) discards its writes.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    ///
+    /// let mut transaction = store.begin();
+    /// transaction.insert(quad)?;
+    /// assert!(transaction.contains(quad)?);
+    /// assert!(!store.contains(quad)?); // Not committed yet
+    /// transaction.commit()?;
+    /// assert!(store.contains(quad)?);
+    ///
+    /// let mut transaction = store.begin();
+    /// transaction.remove(quad)?;
+    /// transaction.rollback();
+    /// assert!(store.contains(quad)?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn begin(&self) -> OpenTransaction {
+        OpenTransaction {
+            store: self.clone(),
+            writes: Vec::new(),
+            positions: HashMap::new(),
+            reads: RefCell::default(),
+            read_version: Cell::new(None),
+        }
+    }
+
+    /// Executes a transaction with a given [isolation level](IsolationLevel).
+    ///
+    /// Unlike the default [`IsolationLevel::RepeatableRead`], [`IsolationLevel::ReadUncommitted`] does not block the other transactions
+    /// between its writes, each write taking the write lock on its own and being visible immediately,
+    /// before the transaction ends.
+    /// It is not cheaper and fits long maintenance jobs that do not need isolation.
+    /// With both levels the writes of a failed transaction are rolled back,
+    /// but with [`IsolationLevel::ReadUncommitted`] they may have been read before.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{IsolationLevel, StorageError, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.transaction_with_isolation(IsolationLevel::ReadUncommitted, |mut transaction| {
+    ///     transaction.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///     Result::<_, StorageError>::Ok(())
+    /// })?;
+    /// assert_eq!(store.len()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn transaction_with_isolation<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        isolation: IsolationLevel,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.check_not_in_maintenance()?;
+        self.storage
+            .transaction_with_isolation(isolation, |writer| f(Transaction { writer }))
     }
 
     /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
@@ -350,6 +969,10 @@ impl Store {
         &self,
         update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
     ) -> Result<(), EvaluationError> {
+        let update = update.try_into().map_err(Into::into)?;
+        if self.queue_write(|| QueuedWrite::Update(update.clone()))? {
+            return Ok(());
+        }
         self.update_opt(update, UpdateOptions::default())
     }
 
@@ -377,13 +1000,60 @@ impl Store {
     ) -> Result<(), EvaluationError> {
         let update = update.try_into().map_err(Into::into)?;
         let options = options.into();
+        self.check_not_in_maintenance()?;
         self.storage
             .transaction(|mut t| evaluate_update(&mut t, &update, &options))
     }
 
+    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/) on behalf of a caller.
+    ///
+    /// The graphs the [access control list](crate::acl) does not allow the principal to read are hidden
+    /// from the `WHERE` clauses and the writes into the graphs it is not allowed to write
+    /// make the whole update fail with [`StorageError::AccessDenied`].
+    /// The `ALL` and `NAMED` targets of `CLEAR` and `DROP` only cover the graphs the principal is allowed to write.
+    ///
+    /// Usage example:
+    /// ```
+    /// use ic_cdk::export::candid::Principal;
+    /// use oxigraph::acl::{grant, principal_iri, Access};
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// grant(&store, ex.into(), principal_iri(Principal::anonymous()).as_ref(), Access::Read)?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///
+    /// assert!(store.update_as(Principal::anonymous(), "INSERT DATA { GRAPH <http://example.com> { <http://example.com> <http://example.com> 1 } }").is_err());
+    /// store.update_as(Principal::anonymous(), "INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }")?;
+    /// assert_eq!(store.len()?, 3);
+    ///
+    /// // The read-only graph and the access control list are kept
+    /// store.update_as(Principal::anonymous(), "DROP ALL")?;
+    /// assert_eq!(store.len()?, 2);
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn update_as(
+        &self,
+        principal: Principal,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+    ) -> Result<(), EvaluationError> {
+        let update = update.try_into().map_err(Into::into)?;
+        let access = GraphAccess::for_principal(self, principal)?;
+        let options = UpdateOptions::from(
+            QueryOptions::default().with_hidden_graphs(access.hidden_graphs().cloned().collect()),
+        );
+        self.check_not_in_maintenance()?;
+        self.storage.transaction(|mut t| {
+            t.deny_writes(access.denied_graphs().map(|g| g.as_ref().into()));
+            evaluate_update(&mut t, &update, &options)
+        })
+    }
+
     /// Loads a graph file (i.e. triples) into the store.
     ///
-    /// This function is atomic, quite slow and memory hungry. To get much better performances you might want to use the [`bulk_loader`](Store::bulk_loader).
+    /// This function is atomic and quite slow. To get much better performances you might want to use the [`bulk_loader`](Store::bulk_loader).
     ///
     /// Usage example:
     /// ```
@@ -409,27 +1079,80 @@ impl Store {
         to_graph_name: impl Into<GraphNameRef<'a>>,
         base_iri: Option<&str>,
     ) -> Result<(), LoaderError> {
-        let mut parser = GraphParser::from_format(format);
-        if let Some(base_iri) = base_iri {
-            parser = parser
-                .with_base_iri(base_iri)
-                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        self.load_graph_opt(
+            reader,
+            format,
+            to_graph_name,
+            base_iri,
+            LoadOptions::default(),
+        )
+    }
+
+    /// Loads a graph file (i.e. triples) into the store with some options.
+    ///
+    /// The triples are inserted while the file is parsed, by batches of [`LoadOptions::with_batch_size`] triples,
+    /// so the whole file is never kept in memory.
+    /// The gzip and Zstandard files are decompressed on the fly, see [`LoadOptions::with_compression`].
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{LoadOptions, LoaderError, StorageConfig, StorageError, Store};
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    ///
+    /// // an invalid file: nothing is inserted by an atomic load
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> .\n<http://example.com> .";
+    /// let options = LoadOptions::default().with_batch_size(1);
+    /// assert!(store.load_graph_opt(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None, options).is_err());
+    /// assert!(store.is_empty()?);
+    ///
+    /// // the first batch is kept by a non atomic load
+    /// let options = options.without_atomicity();
+    /// assert!(store.load_graph_opt(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None, options).is_err());
+    /// assert_eq!(store.len()?, 1);
+    ///
+    /// // a storage error also rolls back an atomic load
+    /// let store = Store::new_with_config(&StorageConfig::default().with_max_quads(1))?;
+    /// let file = b"<http://example.com/a> <http://example.com> <http://example.com> .\n<http://example.com/b> <http://example.com> <http://example.com> .";
+    /// let options = LoadOptions::default().with_batch_size(1);
+    /// assert!(matches!(
+    ///     store.load_graph_opt(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None, options),
+    ///     Err(LoaderError::Storage(StorageError::QuotaExceeded { .. }))
+    /// ));
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn load_graph_opt<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+        options: LoadOptions,
+    ) -> Result<(), LoaderError> {
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
         }
-        let quads = parser
-            .read_triples(reader)?
-            .collect::<Result<Vec<_>, _>>()?;
-        let to_graph_name = to_graph_name.into();
-        self.storage.transaction(move |mut t| {
-            for quad in &quads {
-                t.insert(quad.as_ref().in_graph(to_graph_name))?;
-            }
-            Ok(())
-        })
+        if let Some(compression) = options.compression {
+            parser = parser.with_compression(compression);
+        }
+        let to_graph_name = to_graph_name.into().into_owned();
+        self.load_parsed_quads(
+            parser
+                .read_triples(reader)?
+                .map(|triple| triple.map(|triple| triple.in_graph(to_graph_name.clone()))),
+            options,
+        )
     }
 
     /// Loads a dataset file (i.e. quads) into the store.
     ///
-    /// This function is atomic, quite slow and memory hungry. To get much better performances you might want to use the [`bulk_loader`](Store::bulk_loader).
+    /// This function is atomic and quite slow. To get much better performances you might want to use the [`bulk_loader`](Store::bulk_loader).
     ///
     /// Usage example:
     /// ```
@@ -453,6 +1176,23 @@ impl Store {
         reader: impl BufRead,
         format: DatasetFormat,
         base_iri: Option<&str>,
+    ) -> Result<(), LoaderError> {
+        self.load_dataset_opt(reader, format, base_iri, LoadOptions::default())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store with some options.
+    ///
+    /// The quads are inserted while the file is parsed, by batches of [`LoadOptions::with_batch_size`] quads,
+    /// so the whole file is never kept in memory.
+    /// The gzip and Zstandard files are decompressed on the fly, see [`LoadOptions::with_compression`].
+    ///
+    /// See [`load_graph_opt`](Self::load_graph_opt) for an example.
+    pub fn load_dataset_opt(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+        options: LoadOptions,
     ) -> Result<(), LoaderError> {
         let mut parser = DatasetParser::from_format(format);
         if let Some(base_iri) = base_iri {
@@ -460,18 +1200,200 @@ impl Store {
                 .with_base_iri(base_iri)
                 .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
         }
-        let quads = parser.read_quads(reader)?.collect::<Result<Vec<_>, _>>()?;
-        self.storage.transaction(move |mut t| {
-            for quad in &quads {
-                t.insert(quad.into())?;
+        if let Some(compression) = options.compression {
+            parser = parser.with_compression(compression);
+        }
+        self.load_parsed_quads(parser.read_quads(reader)?, options)
+    }
+
+    /// Inserts the quads by batches, in a single transaction rolled back on any parsing or storage error if the load is atomic.
+    fn load_parsed_quads(
+        &self,
+        quads: impl Iterator<Item = Result<Quad, ParseError>>,
+        options: LoadOptions,
+    ) -> Result<(), LoaderError> {
+        if !options.atomic {
+            return self
+                .bulk_loader()
+                .with_batch_size(options.batch_size)
+                .load_ok_quads(quads);
+        }
+        self.check_not_in_maintenance()?;
+        let quads = RefCell::new(quads);
+        self.storage.transaction(|mut t| {
+            let mut quads = quads.borrow_mut();
+            let mut batch = Vec::with_capacity(options.batch_size);
+            loop {
+                batch.clear();
+                for quad in quads.by_ref().take(options.batch_size) {
+                    batch.push(quad?);
+                }
+                if batch.is_empty() {
+                    return Ok(());
+                }
+                t.insert_batch(&batch.iter().map(Quad::as_ref).collect::<Vec<_>>())?;
             }
-            Ok(())
         })
     }
 
+    /// Creates a bulk loader allowing to load a lot of data quickly into the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    ///
+    /// // quads file insertion
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
+    /// store.bulk_loader().load_dataset(file.as_ref(), DatasetFormat::NQuads, None)?;
+    ///
+    /// // we inspect the store contents
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn bulk_loader(&self) -> BulkLoader {
+        BulkLoader {
+            store: self.clone(),
+            batch_size: DEFAULT_BULK_LOAD_BATCH_SIZE,
+            on_progress: None,
+            on_parse_error: None,
+        }
+    }
+
+    /// Starts loading a dataset file sent in chunks, for example across several update calls
+    /// to go beyond the size limit of the ingress messages.
+    ///
+    /// The chunks are appended with [`BulkLoadSession::append`] and loaded atomically
+    /// by [`BulkLoadSession::commit`] as [`load_dataset`](Self::load_dataset) would do.
+    /// Turtle files can be loaded with [`DatasetFormat::TriG`] and N-Triples files with [`DatasetFormat::NQuads`].
+    ///
+    /// At most 4 sessions can be open at the same time and each of them can receive at most 256MiB.
+    /// The sessions are only kept on the heap: they are lost on canister upgrades and the files must then be sent again.
+    /// A session that is not committed is kept open until it is [aborted](BulkLoadSession::abort),
+    /// for example with [`abort_bulk_load`](Self::abort_bulk_load) if the client has disappeared.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let id = store.start_bulk_load(DatasetFormat::NQuads, None)?.id();
+    ///
+    /// // Each chunk might be received by a different call
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
+    /// for chunk in file.chunks(10) {
+    ///     store.bulk_load_session(id).unwrap().append(chunk)?;
+    /// }
+    /// assert!(store.is_empty()?);
+    ///
+    /// store.bulk_load_session(id).unwrap().commit()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// assert!(store.bulk_load_session(id).is_none());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn start_bulk_load(
+        &self,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<BulkLoadSession, LoaderError> {
+        if let Some(base_iri) = base_iri {
+            DatasetParser::from_format(format)
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        }
+        let mut bulk_loads = self.bulk_loads.write().unwrap();
+        if bulk_loads.entries.len() >= MAX_BULK_LOAD_SESSIONS {
+            return Err(StorageError::Other(
+                format!("At most {MAX_BULK_LOAD_SESSIONS} bulk load sessions can be open").into(),
+            )
+            .into());
+        }
+        bulk_loads.next_id += 1;
+        let id = bulk_loads.next_id;
+        bulk_loads.entries.insert(
+            id,
+            BulkLoad {
+                format,
+                base_iri: base_iri.map(ToOwned::to_owned),
+                data: Vec::new(),
+            },
+        );
+        Ok(BulkLoadSession {
+            store: self.clone(),
+            id,
+        })
+    }
+
+    /// Returns the open bulk load session with the given id.
+    ///
+    /// See [`start_bulk_load`](Self::start_bulk_load).
+    pub fn bulk_load_session(&self, id: u64) -> Option<BulkLoadSession> {
+        self.bulk_loads
+            .read()
+            .unwrap()
+            .entries
+            .contains_key(&id)
+            .then(|| BulkLoadSession {
+                store: self.clone(),
+                id,
+            })
+    }
+
+    /// Returns the ids of the open bulk load sessions with the number of bytes they have received.
+    ///
+    /// See [`start_bulk_load`](Self::start_bulk_load).
+    pub fn bulk_load_sessions(&self) -> Vec<(u64, usize)> {
+        self.bulk_loads
+            .read()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|(id, bulk_load)| (*id, bulk_load.data.len()))
+            .collect()
+    }
+
+    /// Closes the bulk load session with the given id without loading anything, for example from an administration method.
+    ///
+    /// Returns `true` if the session was open.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::DatasetFormat;
+    ///
+    /// let store = Store::new()?;
+    /// for _ in 0..4 {
+    ///     store.start_bulk_load(DatasetFormat::NQuads, None)?.append(b"<http://example.com> ")?;
+    /// }
+    /// assert!(store.start_bulk_load(DatasetFormat::NQuads, None).is_err()); // Too many open sessions
+    ///
+    /// for (id, _) in store.bulk_load_sessions() {
+    ///     assert!(store.abort_bulk_load(id));
+    /// }
+    /// assert!(store.start_bulk_load(DatasetFormat::NQuads, None).is_ok());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn abort_bulk_load(&self, id: u64) -> bool {
+        self.bulk_loads
+            .write()
+            .unwrap()
+            .entries
+            .remove(&id)
+            .is_some()
+    }
+
     /// Adds a quad to this store.
     ///
     /// Returns `true` if the quad was not already in the store.
+    /// It returns `false` if the insertion is queued because the store is in [maintenance mode](Self::begin_maintenance),
+    /// it is not known yet if it will insert the quad.
     ///
     /// Usage example:
     /// ```
@@ -490,6 +1412,9 @@ impl Store {
     /// ```
     pub fn insert<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
         let quad = quad.into();
+        if self.queue_write(|| QueuedWrite::Insert(quad.into_owned()))? {
+            return Ok(false);
+        }
         self.transaction(|mut t| t.insert(quad))
     }
 
@@ -500,13 +1425,99 @@ impl Store {
         &self,
         quads: impl IntoIterator<Item = impl Into<Quad>>,
     ) -> Result<(), StorageError> {
-        let quads = quads.into_iter().map(Into::into).collect::<Vec<_>>();
-        self.transaction(move |mut t| t.extend(&quads))
+        let mut buffer = Vec::with_capacity(self.storage.reserved_quads());
+        buffer.extend(quads.into_iter().map(Into::into));
+        self.transaction(move |mut t| t.extend(&buffer))
+    }
+
+    /// Adds atomically to this store the quads of another store, only the ones of the given graphs if `graph_filter` is set.
+    ///
+    /// The quads are copied in their encoded form and the dictionary entries of their strings are copied as they are,
+    /// avoiding to decode and to encode again the terms.
+    /// It allows for example to merge into the live store a staging store built during a bulk session.
+    /// The quads hidden by the [read filter](Self::set_read_filter) of the other store are not copied
+    /// and neither are its empty named graphs.
+    ///
+    /// Returns the number of quads that were not already in this store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let staging = Store::new()?;
+    /// staging.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// staging.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///
+    /// let store = Store::new()?;
+    /// assert_eq!(store.load_from_store(&staging, Some(&[ex.into()]))?, 1);
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// assert_eq!(store.load_from_store(&staging, None)?, 1);
+    /// assert_eq!(store.len()?, 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn load_from_store(
+        &self,
+        other: &Self,
+        graph_filter: Option<&[GraphName]>,
+    ) -> Result<usize, StorageError> {
+        let source = other.storage.snapshot();
+        let graph_names = graph_filter.map(|graph_names| {
+            graph_names
+                .iter()
+                .map(|graph_name| EncodedTerm::from(graph_name.as_ref()))
+                .collect::<Vec<_>>()
+        });
+        self.transaction(|mut t| {
+            let mut loaded = 0;
+            let quads: Box<dyn Iterator<Item = _>> = if let Some(graph_names) = &graph_names {
+                Box::new(graph_names.iter().flat_map(|graph_name| {
+                    source.quads_for_pattern(None, None, None, Some(graph_name))
+                }))
+            } else {
+                Box::new(source.quads_for_pattern(None, None, None, None))
+            };
+            for quad in quads {
+                if t.writer.insert_from(&quad?, &source)? {
+                    loaded += 1;
+                }
+            }
+            Ok(loaded)
+        })
+    }
+
+    /// Prepares the store for a bulk load of about `expected_quads` quads
+    /// whose new strings take about `expected_dictionary_bytes` bytes.
+    ///
+    /// The memory needed is allocated at once and the buffers of the next write are pre-sized,
+    /// avoiding the many reallocations that inflate the instruction count of large imports.
+    /// The reservation is released by the next write, even if it is smaller than expected.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// store.reserve(1000, 64_000);
+    /// let p = NamedNodeRef::new("http://example.com/p")?;
+    /// store.extend((0..1000).map(|i| {
+    ///     Quad::new(p, p, Literal::from(i), GraphName::DefaultGraph)
+    /// }))?;
+    /// assert_eq!(store.len()?, 1000);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn reserve(&self, expected_quads: usize, expected_dictionary_bytes: usize) {
+        self.storage
+            .reserve(expected_quads, expected_dictionary_bytes);
     }
 
     /// Removes a quad from this store.
     ///
     /// Returns `true` if the quad was in the store and has been removed.
+    /// It returns `false` if the removal is queued because the store is in [maintenance mode](Self::begin_maintenance),
+    /// it is not known yet if it will remove the quad.
     ///
     /// Usage example:
     /// ```
@@ -526,11 +1537,50 @@ impl Store {
     /// ```
     pub fn remove<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
         let quad = quad.into();
+        if self.queue_write(|| QueuedWrite::Remove(quad.into_owned()))? {
+            return Ok(false);
+        }
         self.transaction(move |mut t| t.remove(quad))
     }
 
+    /// Removes atomically a set of quads from this store.
+    ///
+    /// Returns the number of quads that were in the store and have been removed.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let a = NamedNodeRef::new("http://example.com/a")?;
+    /// let b = NamedNodeRef::new("http://example.com/b")?;
+    ///
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(a, a, a, GraphNameRef::DefaultGraph))?;
+    /// assert_eq!(
+    ///     store.remove_all([
+    ///         QuadRef::new(a, a, a, GraphNameRef::DefaultGraph),
+    ///         QuadRef::new(b, b, b, GraphNameRef::DefaultGraph),
+    ///     ])?,
+    ///     1
+    /// );
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn remove_all(
+        &self,
+        quads: impl IntoIterator<Item = impl Into<Quad>>,
+    ) -> Result<usize, StorageError> {
+        let quads = quads.into_iter().map(Into::into).collect::<Vec<_>>();
+        self.transaction(move |mut t| t.remove_all(&quads))
+    }
+
     /// Dumps a store graph into a file.
-    ///    
+    ///
+    /// The prefixes registered with [`register_prefix`](Self::register_prefix) are used in Turtle.
+    /// A [`GraphSerializer`] might be given instead of the format, for example to compress the dump
+    /// (see [`GraphSerializer::with_compression`]).
+    ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
@@ -550,10 +1600,13 @@ impl Store {
     pub fn dump_graph<'a>(
         &self,
         writer: impl Write,
-        format: GraphFormat,
+        format: impl Into<GraphSerializer>,
         from_graph_name: impl Into<GraphNameRef<'a>>,
     ) -> Result<(), SerializerError> {
-        let mut writer = GraphSerializer::from_format(format).triple_writer(writer)?;
+        let mut writer = format
+            .into()
+            .with_prefixes(self.prefixes()?)
+            .triple_writer(writer)?;
         for quad in self.quads_for_pattern(None, None, None, Some(from_graph_name.into())) {
             writer.write(quad?.as_ref())?;
         }
@@ -562,7 +1615,11 @@ impl Store {
     }
 
     /// Dumps the store into a file.
-    ///    
+    ///
+    /// The prefixes registered with [`register_prefix`](Self::register_prefix) are used in TriG.
+    /// A [`DatasetSerializer`] might be given instead of the format, for example to compress the dump
+    /// (see [`DatasetSerializer::with_compression`]).
+    ///
     /// ```
     /// use oxigraph::store::Store;
     /// use oxigraph::io::DatasetFormat;
@@ -575,14 +1632,31 @@ impl Store {
     /// let mut buffer = Vec::new();
     /// store.dump_dataset(&mut buffer, DatasetFormat::NQuads)?;
     /// assert_eq!(file, buffer.as_slice());
-    /// # std::io::Result::Ok(())
+    ///
+    /// // a compressed dump is loaded back transparently
+    /// # #[cfg(feature = "compression")] {
+    /// use oxigraph::io::{Compression, DatasetSerializer};
+    ///
+    /// let mut compressed = Vec::new();
+    /// store.dump_dataset(
+    ///     &mut compressed,
+    ///     DatasetSerializer::from_format(DatasetFormat::NQuads).with_compression(Compression::Zstd),
+    /// )?;
+    /// let restored = Store::new()?;
+    /// restored.load_dataset(compressed.as_slice(), DatasetFormat::NQuads, None)?;
+    /// assert_eq!(restored.len()?, 1);
+    /// # }
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
     pub fn dump_dataset(
         &self,
         writer: impl Write,
-        format: DatasetFormat,
+        format: impl Into<DatasetSerializer>,
     ) -> Result<(), SerializerError> {
-        let mut writer = DatasetSerializer::from_format(format).quad_writer(writer)?;
+        let mut writer = format
+            .into()
+            .with_prefixes(self.prefixes()?)
+            .quad_writer(writer)?;
         for quad in self.iter() {
             writer.write(&quad?)?;
         }
@@ -590,610 +1664,4685 @@ impl Store {
         Ok(())
     }
 
-    /// Returns all the store named graphs.
+    /// Dumps the store content in the [N-Quads](https://www.w3.org/TR/n-quads/) format in chunks of at most `max_bytes` bytes,
+    /// for example to stream a backup out of the canister over several query calls.
+    ///
+    /// The export starts with the default [`DumpCursor`] and continues with the cursor returned with each chunk.
+    /// The chunks contain whole statements, so their concatenation is the full dump.
+    /// A chunk exceeds `max_bytes` only if a single statement does.
+    ///
+    /// The export fails if the store is modified between two chunks.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
+    /// use oxigraph::store::{DumpCursor, Store};
+    /// use oxigraph::io::DatasetFormat;
     /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNode::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.insert(QuadRef::new(&ex, &ex, &ex, &ex))?;
-    /// store.insert(QuadRef::new(&ex, &ex, &ex, GraphNameRef::DefaultGraph))?;
-    /// assert_eq!(vec![NamedOrBlankNode::from(ex)], store.named_graphs().collect::<Result<Vec<_>,_>>()?);
+    /// for i in 0..10 {
+    ///     let ex = NamedNode::new(format!("http://example.com/{i}"))?;
+    ///     store.insert(QuadRef::new(&ex, &ex, &ex, GraphNameRef::DefaultGraph))?;
+    /// }
+    ///
+    /// let mut dump = Vec::new();
+    /// let mut cursor = Some(DumpCursor::default());
+    /// while let Some(current) = cursor {
+    ///     // The cursor might be sent to the client and parsed back
+    ///     let chunk = store.dump_dataset_chunked(current.to_string().parse()?, 200)?;
+    ///     assert!(chunk.data().len() <= 200);
+    ///     dump.extend_from_slice(chunk.data());
+    ///     cursor = chunk.next_cursor();
+    /// }
+    ///
+    /// let restored = Store::new()?;
+    /// restored.load_dataset(dump.as_slice(), DatasetFormat::NQuads, None)?;
+    /// assert_eq!(restored.len()?, 10);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn named_graphs(&self) -> GraphNameIter {
-        let reader = self.storage.snapshot();
-        GraphNameIter {
-            iter: reader.named_graphs(),
-            reader,
+    pub fn dump_dataset_chunked(
+        &self,
+        cursor: DumpCursor,
+        max_bytes: usize,
+    ) -> Result<DatasetChunk, StorageError> {
+        let dataset_version = self.dataset_version()?;
+        if cursor.offset > 0 && cursor.dataset_version != dataset_version {
+            return Err(StorageError::Other(
+                "The store has been modified since the export started".into(),
+            ));
+        }
+        let mut data = Vec::new();
+        let mut offset = cursor.offset;
+        let mut next = None;
+        for quad in self
+            .iter()
+            .skip(usize::try_from(cursor.offset).unwrap_or(usize::MAX))
+        {
+            let statement = format!("{} .\n", quad?);
+            if !data.is_empty() && data.len() + statement.len() > max_bytes {
+                next = Some(DumpCursor {
+                    dataset_version,
+                    offset,
+                });
+                break;
+            }
+            data.extend_from_slice(statement.as_bytes());
+            offset += 1;
         }
+        Ok(DatasetChunk { data, next })
     }
 
-    /// Checks if the store contains a given graph
+    /// Writes the whole store content in a compact binary form, for example to stable memory in `pre_upgrade`.
+    ///
+    /// The encoded column families are written as is, without decoding the terms,
+    /// so it is much faster than a dump in a RDF format and keeps the store metadata.
+    /// The format starts with a version number, and the backups written by older versions of the library
+    /// are migrated when they are [restored](Self::restore).
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
-    /// use oxigraph::model::{NamedNode, QuadRef};
+    /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNode::new("http://example.com")?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.insert(QuadRef::new(&ex, &ex, &ex, &ex))?;
-    /// assert!(store.contains_named_graph(&ex)?);
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///
+    /// let mut snapshot = Vec::new();
+    /// store.backup(&mut snapshot)?;
+    ///
+    /// let restored = Store::new()?;
+    /// restored.restore(snapshot.as_slice())?;
+    /// assert!(restored.contains(QuadRef::new(ex, ex, ex, ex))?);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn contains_named_graph<'a>(
-        &self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
-    ) -> Result<bool, StorageError> {
-        let graph_name = EncodedTerm::from(graph_name.into());
-        self.storage.snapshot().contains_named_graph(&graph_name)
+    pub fn backup(&self, writer: impl Write) -> Result<(), StorageError> {
+        self.storage.save(writer)
     }
 
-    /// Inserts a graph into this store.
+    /// Replaces the whole store content by a snapshot written by [`backup`](Self::backup),
+    /// for example from stable memory in `post_upgrade`.
     ///
-    /// Returns `true` if the graph was not already in the store.
+    /// The content is replaced atomically once the snapshot is fully read.
+    /// The [subscriptions](Self::subscribe) are not notified of the changes.
+    ///
+    /// Like the other writes, it fails with [`StorageError::ReadOnly`] if the store is [read-only](Self::set_read_only)
+    /// and with [`StorageError::UnderMaintenance`] if it is in [maintenance mode](Self::begin_maintenance).
+    /// The read-only flag of the snapshot is restored with its content.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
-    /// use oxigraph::model::NamedNodeRef;
+    /// use oxigraph::store::{StorageError, Store};
     ///
-    /// let ex = NamedNodeRef::new("http://example.com")?;
-    /// let store = Store::new()?;
-    /// store.insert_named_graph(ex)?;
+    /// let mut snapshot = Vec::new();
+    /// Store::new()?.backup(&mut snapshot)?;
     ///
-    /// assert_eq!(store.named_graphs().collect::<Result<Vec<_>,_>>()?, vec![ex.into_owned().into()]);
+    /// let store = Store::new()?;
+    /// store.set_read_only(true)?;
+    /// assert!(matches!(store.restore(snapshot.as_slice()), Err(StorageError::ReadOnly)));
+    /// assert!(store.is_read_only()?);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn insert_named_graph<'a>(
+    pub fn restore(&self, reader: impl Read) -> Result<(), StorageError> {
+        self.check_not_in_maintenance()?;
+        self.storage.load(reader)
+    }
+
+    /// Replaces the whole store content by a [checkpoint](Self::checkpoint) and replays on it the write-ahead log appended after it.
+    ///
+    /// The log is replayed before the data is migrated to the storage layout of this version of the library,
+    /// so the checkpoint and the log can have been written by a previous version.
+    /// Returns the number of replayed transactions, see [`enable_write_ahead_log`](Self::enable_write_ahead_log) for an example.
+    ///
+    /// It fails like [`restore`](Self::restore) if the store is read-only or in maintenance mode.
+    pub fn restore_with_write_ahead_log(
         &self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
-    ) -> Result<bool, StorageError> {
-        let graph_name = graph_name.into();
-        self.transaction(|mut t| t.insert_named_graph(graph_name))
+        checkpoint: impl Read,
+        log: impl Read,
+    ) -> Result<usize, StorageError> {
+        self.check_not_in_maintenance()?;
+        self.storage.load_with_log(checkpoint, log)
     }
 
-    /// Clears a graph from this store.
+    /// Writes the whole store content in a compact binary form.
+    #[deprecated(note = "Use backup instead")]
+    pub fn save_to_stable_writer(&self, writer: impl Write) -> Result<(), StorageError> {
+        self.backup(writer)
+    }
+
+    /// Replaces the whole store content by a snapshot written by [`backup`](Self::backup).
+    #[deprecated(note = "Use restore instead")]
+    pub fn load_from_stable_reader(&self, reader: impl Read) -> Result<(), StorageError> {
+        self.restore(reader)
+    }
+
+    /// Enables the write-ahead log mode: the writes of each committed transaction are appended to `log`,
+    /// for example a writer to a stable memory region.
+    ///
+    /// After an upgrade, the store content is rebuilt by loading the last [checkpoint](Self::checkpoint)
+    /// and then replaying the log with [`restore_with_write_ahead_log`](Self::restore_with_write_ahead_log),
+    /// avoiding a monolithic dump in `pre_upgrade`.
+    /// Each logged transaction records the storage layout version it has been written with,
+    /// so that a log written by a previous version of the library is replayed before the data is migrated.
+    ///
+    /// If a write to the log fails, the transaction is rolled back and the error is returned,
+    /// so the store content stays consistent with the log.
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
-    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    /// use oxigraph::model::*;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::io::{Result, Write};
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for SharedBuffer {
+    ///     fn write(&mut self, buf: &[u8]) -> Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
     ///
     /// let ex = NamedNodeRef::new("http://example.com")?;
-    /// let quad = QuadRef::new(ex, ex, ex, ex);
     /// let store = Store::new()?;
-    /// store.insert(quad)?;
-    /// assert_eq!(1, store.len()?);
+    /// let mut checkpoint = Vec::new();
+    /// let log = SharedBuffer::default();
+    /// store.checkpoint(&mut checkpoint, log.clone())?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
     ///
-    /// store.clear_graph(ex)?;
-    /// assert!(store.is_empty()?);
-    /// assert_eq!(1, store.named_graphs().count());
-    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// let restored = Store::new()?;
+    /// assert_eq!(restored.restore_with_write_ahead_log(checkpoint.as_slice(), log.0.lock().unwrap().as_slice())?, 1);
+    /// assert!(restored.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # std::result::Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn clear_graph<'a>(
+    pub fn enable_write_ahead_log(
         &self,
-        graph_name: impl Into<GraphNameRef<'a>>,
+        log: impl Write + Send + 'static,
     ) -> Result<(), StorageError> {
-        let graph_name = graph_name.into();
-        self.transaction(|mut t| t.clear_graph(graph_name))
+        self.storage.set_write_ahead_log(Some(Box::new(log)))
     }
 
-    /// Removes a graph from this store.
+    /// Disables the write-ahead log mode.
+    pub fn disable_write_ahead_log(&self) -> Result<(), StorageError> {
+        self.storage.set_write_ahead_log(None)
+    }
+
+    /// Compacts the write-ahead log: writes a snapshot of the store to `snapshot`
+    /// and then appends the next transactions to the new empty `log`.
     ///
-    /// Returns `true` if the graph was in the store and has been removed.
+    /// The previous log is not needed anymore once the snapshot is saved.
+    /// See [`enable_write_ahead_log`](Self::enable_write_ahead_log) for an example.
+    pub fn checkpoint(
+        &self,
+        snapshot: impl Write,
+        log: impl Write + Send + 'static,
+    ) -> Result<(), StorageError> {
+        self.storage.save(snapshot)?;
+        self.enable_write_ahead_log(log)
+    }
+
+    /// Applies the transactions recorded in a write-ahead log and returns their number.
+    ///
+    /// A last transaction that was not fully written is ignored.
+    /// Fails if a transaction has been written with another storage layout version,
+    /// use [`restore_with_write_ahead_log`](Self::restore_with_write_ahead_log) to replay the log of a previous version.
+    /// The replayed transactions are not appended to the current write-ahead log
+    /// and the [subscriptions](Self::subscribe) are not notified.
+    pub fn replay_write_ahead_log(&self, log: impl Read) -> Result<usize, StorageError> {
+        self.storage.replay_log(log)
+    }
+
+    /// Starts an incremental compaction of the write-ahead log into the new empty `log`.
+    ///
+    /// Contrary to [`checkpoint`](Self::checkpoint), no snapshot is written in a single call:
+    /// the next transactions are appended to `log` and [`LogCompaction::step`] appends to it the current store content by batches,
+    /// for example from a canister timer.
+    /// Once the compaction is finished, replaying `log` into an empty store gives back the store content,
+    /// so the previous log and checkpoint are not needed anymore.
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
-    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    /// use oxigraph::model::*;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::io::{Result, Write};
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for SharedBuffer {
+    ///     fn write(&mut self, buf: &[u8]) -> Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
     ///
     /// let ex = NamedNodeRef::new("http://example.com")?;
-    /// let quad = QuadRef::new(ex, ex, ex, ex);
     /// let store = Store::new()?;
-    /// store.insert(quad)?;
-    /// assert_eq!(1, store.len()?);
+    /// store.enable_write_ahead_log(SharedBuffer::default())?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
     ///
-    /// assert!(store.remove_named_graph(ex)?);
-    /// assert!(store.is_empty()?);
-    /// assert_eq!(0, store.named_graphs().count());
-    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// let log = SharedBuffer::default();
+    /// let mut compaction = store.compact_write_ahead_log(log.clone())?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// while !compaction.step(10)? {}
+    ///
+    /// let restored = Store::new()?;
+    /// restored.replay_write_ahead_log(log.0.lock().unwrap().as_slice())?;
+    /// assert_eq!(restored.len()?, 2);
+    /// # std::result::Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn remove_named_graph<'a>(
+    pub fn compact_write_ahead_log(
         &self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
-    ) -> Result<bool, StorageError> {
-        let graph_name = graph_name.into();
-        self.transaction(|mut t| t.remove_named_graph(graph_name))
+        log: impl Write + Send + 'static,
+    ) -> Result<LogCompaction, StorageError> {
+        self.enable_write_ahead_log(log)?;
+        Ok(LogCompaction {
+            store: self.clone(),
+            state: LogCompactionState::default(),
+        })
     }
 
-    /// Clears the store.
+    /// Returns all the store named graphs.
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let ex = NamedNode::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
-    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;    
-    /// assert_eq!(2, store.len()?);
-    ///
-    /// store.clear()?;
-    /// assert!(store.is_empty()?);
+    /// store.insert(QuadRef::new(&ex, &ex, &ex, &ex))?;
+    /// store.insert(QuadRef::new(&ex, &ex, &ex, GraphNameRef::DefaultGraph))?;
+    /// assert_eq!(vec![NamedOrBlankNode::from(ex)], store.named_graphs().collect::<Result<Vec<_>,_>>()?);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn clear(&self) -> Result<(), StorageError> {
-        self.transaction(|mut t| t.clear())
-    }
-
-    /// Validates that all the store invariants held in the data
-    #[doc(hidden)]
-    pub fn validate(&self) -> Result<(), StorageError> {
-        self.storage.snapshot().validate()
-    }
-}
-
-impl fmt::Display for Store {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for t in self.iter() {
-            writeln!(f, "{} .", t.map_err(|_| fmt::Error)?)?;
+    pub fn named_graphs(&self) -> GraphNameIter {
+        let reader = self.storage.snapshot();
+        GraphNameIter {
+            iter: reader.named_graphs(),
+            reader,
         }
-        Ok(())
     }
-}
 
-/// An object to do operations during a transaction.
-///
-/// See [`Store::transaction`] for a more detailed description.
-pub struct Transaction<'a> {
-    writer: StorageWriter<'a>,
-}
-
-impl<'a> Transaction<'a> {
-    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
+    /// Checks if the store contains a given graph
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
-    /// use oxigraph::model::*;
-    /// use oxigraph::sparql::{EvaluationError, QueryResults};
+    /// use oxigraph::model::{NamedNode, QuadRef};
     ///
+    /// let ex = NamedNode::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     if let QueryResults::Solutions(solutions) = transaction.query("SELECT ?s WHERE { ?s ?p ?o }")? {
-    ///         for solution in solutions {
-    ///             if let Some(Term::NamedNode(s)) =  solution?.get("s") {
-    ///                 transaction.insert(QuadRef::new(s, vocab::rdf::TYPE, NamedNodeRef::new_unchecked("http://example.com"), GraphNameRef::DefaultGraph))?;
-    ///             }
-    ///         }
-    ///     }
-    ///     Result::<_, EvaluationError>::Ok(())
-    /// })?;
-    /// # Result::<_, EvaluationError>::Ok(())
+    /// store.insert(QuadRef::new(&ex, &ex, &ex, &ex))?;
+    /// assert!(store.contains_named_graph(&ex)?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn query(
+    pub fn contains_named_graph<'a>(
         &self,
-        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
-    ) -> Result<QueryResults, EvaluationError> {
-        self.query_opt(query, QueryOptions::default())
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, StorageError> {
+        let graph_name = self.storage.encode_stored_graph_name(graph_name.into());
+        self.storage.snapshot().contains_named_graph(&graph_name)
     }
 
-    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    /// Checks if two graphs of the store are [isomorphic](https://www.w3.org/TR/rdf11-concepts/#dfn-graph-isomorphism),
+    /// i.e. if they are equal up to a renaming of their blank nodes.
     ///
-    /// Usage example with a custom function serializing terms to N-Triples:
+    /// Both graphs are loaded in memory and compared with [`Graph::is_isomorphic`].
+    ///
+    /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
-    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
     ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let g1 = NamedNodeRef::new("http://example.com/g1")?;
+    /// let g2 = NamedNodeRef::new("http://example.com/g2")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     if let QueryResults::Solutions(solutions) = transaction.query_opt(
-    ///         "SELECT ?s (<http://www.w3.org/ns/formats/N-Triples>(?s) AS ?nt) WHERE { ?s ?p ?o }",
-    ///         QueryOptions::default().with_custom_function(
-    ///             NamedNode::new_unchecked("http://www.w3.org/ns/formats/N-Triples"),
-    ///             |args| args.get(0).map(|t| Literal::from(t.to_string()).into())
-    ///         )
-    ///     )? {
-    ///         for solution in solutions {
-    ///             let solution = solution?;
-    ///             if let (Some(Term::NamedNode(s)), Some(nt)) = (solution.get("s"), solution.get("nt")) {
-    ///                 transaction.insert(QuadRef::new(s, NamedNodeRef::new_unchecked("http://example.com/n-triples-representation"), nt, GraphNameRef::DefaultGraph))?;
-    ///             }
-    ///         }
-    ///     }
-    ///     Result::<_, EvaluationError>::Ok(())
-    /// })?;
-    /// # Result::<_, EvaluationError>::Ok(())
+    /// store.insert(QuadRef::new(ex, ex, &BlankNode::default(), g1))?;
+    /// store.insert(QuadRef::new(ex, ex, &BlankNode::default(), g2))?;
+    /// assert!(store.graph_isomorphic(g1, g2)?);
+    ///
+    /// store.insert(QuadRef::new(ex, ex, ex, g2))?;
+    /// assert!(!store.graph_isomorphic(g1, g2)?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn query_opt(
+    pub fn graph_isomorphic<'a, 'b>(
         &self,
-        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
-        options: QueryOptions,
-    ) -> Result<QueryResults, EvaluationError> {
-        let (results, _) = evaluate_query(self.writer.reader(), query, options, false)?;
-        results
+        graph_a: impl Into<GraphNameRef<'a>>,
+        graph_b: impl Into<GraphNameRef<'b>>,
+    ) -> Result<bool, StorageError> {
+        let graph_a = self.graph(graph_a.into())?;
+        let graph_b = self.graph(graph_b.into())?;
+        Ok(graph_a.is_isomorphic(&graph_b))
     }
 
-    /// Retrieves quads with a filter on each quad component.
+    /// Loads the triples of a graph in memory.
+    fn graph(&self, graph_name: GraphNameRef<'_>) -> Result<Graph, StorageError> {
+        self.quads_for_pattern(None, None, None, Some(graph_name))
+            .map(|quad| quad.map(Triple::from))
+            .collect()
+    }
+
+    /// Returns an order-independent digest of the content of a graph.
+    ///
+    /// The digest is the sum of the hashes of the graph quads and is maintained on every insertion and removal,
+    /// so getting it is cheap. It allows to quickly detect changes but it is not a cryptographic hash
+    /// and the same graph content may get a different digest if it contains blank nodes.
+    ///
+    /// The digest of an empty graph is `0`.
     ///
-    /// Usage example:
     /// Usage example:
     /// ```
-    /// use oxigraph::store::{StorageError, Store};
+    /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
     ///
-    /// let store = Store::new()?;
     /// let a = NamedNodeRef::new("http://example.com/a")?;
     /// let b = NamedNodeRef::new("http://example.com/b")?;
+    /// let store1 = Store::new()?;
+    /// store1.insert(QuadRef::new(a, a, a, a))?;
+    /// store1.insert(QuadRef::new(b, b, b, a))?;
+    /// let store2 = Store::new()?;
+    /// store2.insert(QuadRef::new(b, b, b, a))?;
+    /// store2.insert(QuadRef::new(a, a, a, a))?;
+    /// assert_eq!(store1.quick_graph_digest(a)?, store2.quick_graph_digest(a)?);
     ///
-    /// // Copy all triples about ex:a to triples about ex:b
-    /// store.transaction(|mut transaction| {
-    ///     for q in transaction.quads_for_pattern(Some(a.into()), None, None, None) {
-    ///         let q = q?;
-    ///         transaction.insert(QuadRef::new(b, &q.predicate, &q.object, &q.graph_name))?;
-    ///     }
-    ///     Result::<_, StorageError>::Ok(())
-    /// })?;
+    /// store1.clear_graph(a)?;
+    /// assert_eq!(store1.quick_graph_digest(a)?, 0);
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn quads_for_pattern(
+    pub fn quick_graph_digest<'a>(
         &self,
-        subject: Option<SubjectRef<'_>>,
-        predicate: Option<NamedNodeRef<'_>>,
-        object: Option<TermRef<'_>>,
-        graph_name: Option<GraphNameRef<'_>>,
-    ) -> QuadIter {
-        let reader = self.writer.reader();
-        QuadIter {
-            iter: reader.quads_for_pattern(
-                subject.map(EncodedTerm::from).as_ref(),
-                predicate.map(EncodedTerm::from).as_ref(),
-                object.map(EncodedTerm::from).as_ref(),
-                graph_name.map(EncodedTerm::from).as_ref(),
-            ),
-            reader,
-        }
-    }
-
-    /// Returns all the quads contained in the store.
-    pub fn iter(&self) -> QuadIter {
-        self.quads_for_pattern(None, None, None, None)
-    }
-
-    /// Checks if this store contains a given quad.
-    pub fn contains<'b>(&self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
-        let quad = EncodedQuad::from(quad.into());
-        self.writer.reader().contains(&quad)
+        graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<u128, StorageError> {
+        let graph_name = EncodedTerm::from(graph_name.into());
+        self.storage.snapshot().graph_digest(&graph_name)
     }
 
-    /// Returns the number of quads in the store.
+    /// Returns an order-independent digest of the whole store content.
     ///
-    /// Warning: this function executes a full scan.
-    pub fn len(&self) -> Result<usize, StorageError> {
-        self.writer.reader().len()
-    }
-
-    /// Returns if the store is empty.
-    pub fn is_empty(&self) -> Result<bool, StorageError> {
-        self.writer.reader().is_empty()
-    }
-
-    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
+    /// Like [`quick_graph_digest`](Self::quick_graph_digest), it is the sum of the hashes of the quads
+    /// (including their graph names) and is maintained on every insertion and removal, so getting it is cheap.
+    /// The digest of an empty store is `0`.
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
-    /// use oxigraph::sparql::EvaluationError;
     ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     // insertion
-    ///     transaction.update("INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// let digest = store.dataset_digest()?;
+    /// assert_ne!(digest, 0);
     ///
-    ///     // we inspect the store contents
-    ///     let ex = NamedNodeRef::new_unchecked("http://example.com");
-    ///     assert!(transaction.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
-    ///     Result::<_, EvaluationError>::Ok(())
-    /// })?;
-    /// # Result::<_, EvaluationError>::Ok(())
+    /// store.remove(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// assert_ne!(store.dataset_digest()?, digest);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn update(
-        &mut self,
-        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
-    ) -> Result<(), EvaluationError> {
-        self.update_opt(update, UpdateOptions::default())
+    pub fn dataset_digest(&self) -> Result<u128, StorageError> {
+        self.storage.snapshot().dataset_digest()
     }
 
-    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/) with some options.
-    pub fn update_opt(
-        &mut self,
-        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
-        options: impl Into<UpdateOptions>,
-    ) -> Result<(), EvaluationError> {
-        evaluate_update(
-            &mut self.writer,
-            &update.try_into().map_err(Into::into)?,
-            &options.into(),
-        )
+    /// Sets the [dataset digest](Self::dataset_digest) as the canister certified data and returns it.
+    ///
+    /// The certified data can only be set in update calls, so it should be called at the end of each update method writing the store
+    /// and in `post_upgrade`.
+    /// The query methods can then return the certificate of `ic_cdk::api::data_certificate()`
+    /// with the digest encoded in big endian, so that the off-chain consumers can check if the dataset has changed between two reads.
+    ///
+    /// It must be used inside a canister.
+    pub fn certify_dataset_digest(&self) -> Result<u128, StorageError> {
+        let digest = self.dataset_digest()?;
+        set_certified_data(&digest.to_be_bytes());
+        Ok(digest)
     }
 
-    /// Loads a graph file (i.e. triples) into the store.
+    /// Returns the number of quads of each predicate, of each graph and of each predicate and object class.
+    ///
+    /// The counts are maintained on every insertion and removal, so getting them does not require a scan of the store.
+    /// They are also used by the SPARQL query optimizer to order the triple patterns.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
-    /// use oxigraph::io::GraphFormat;
     /// use oxigraph::model::*;
+    /// use oxigraph::store::{ObjectClass, Store};
     ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, LiteralRef::new_simple_literal("foo"), ex))?;
     ///
-    /// // insertion
-    /// let file = b"<http://example.com> <http://example.com> <http://example.com> .";
-    /// store.transaction(|mut transaction| {
-    ///     transaction.load_graph(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None)
-    /// })?;
-    ///
-    /// // we inspect the store contents
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
-    /// # Result::<_,oxigraph::store::LoaderError>::Ok(())
+    /// let statistics = store.statistics()?;
+    /// assert_eq!(statistics.quads, 2);
+    /// assert_eq!(statistics.predicates[&ex.into_owned()], 2);
+    /// assert_eq!(statistics.graphs[&GraphName::DefaultGraph], 1);
+    /// assert_eq!(statistics.object_classes[&(ex.into_owned(), ObjectClass::Literal)], 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn load_graph<'b>(
-        &mut self,
-        reader: impl BufRead,
-        format: GraphFormat,
-        to_graph_name: impl Into<GraphNameRef<'b>>,
-        base_iri: Option<&str>,
-    ) -> Result<(), LoaderError> {
-        let mut parser = GraphParser::from_format(format);
-        if let Some(base_iri) = base_iri {
-            parser = parser
-                .with_base_iri(base_iri)
-                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+    pub fn statistics(&self) -> Result<Statistics, StorageError> {
+        let reader = self.storage.snapshot();
+        let encoded = reader.statistics()?;
+        let mut statistics = Statistics {
+            quads: encoded.quads,
+            ..Statistics::default()
+        };
+        for (predicate, count) in encoded.predicates {
+            statistics
+                .predicates
+                .insert(reader.decode_named_node(&predicate)?, count);
         }
-        let to_graph_name = to_graph_name.into();
-        for triple in parser.read_triples(reader)? {
-            self.writer
-                .insert(triple?.as_ref().in_graph(to_graph_name))?;
+        for (graph_name, count) in encoded.graphs {
+            let graph_name = if graph_name.is_default_graph() {
+                GraphName::DefaultGraph
+            } else {
+                reader.decode_named_or_blank_node(&graph_name)?.into()
+            };
+            statistics.graphs.insert(graph_name, count);
         }
-        Ok(())
+        for (predicate, object_class, count) in encoded.object_classes {
+            statistics
+                .object_classes
+                .insert((reader.decode_named_node(&predicate)?, object_class), count);
+        }
+        Ok(statistics)
     }
 
-    /// Loads a dataset file (i.e. quads) into the store.
+    /// Returns the number of keys inserted or removed in each secondary index by the last transaction that wrote into them.
+    ///
+    /// The writes to the secondary indexes (all except `spog` and `dspo`) are buffered during a transaction
+    /// and applied in key order when it ends, so each index is written by a single sorted batch.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
-    /// use oxigraph::io::DatasetFormat;
     /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
     ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let other = NamedNodeRef::new("http://example.com/other")?;
     /// let store = Store::new()?;
-    ///
-    /// // insertion
-    /// let file = b"<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
-    /// store.transaction(|mut transaction| {
-    ///     transaction.load_dataset(file.as_ref(), DatasetFormat::NQuads, None)
-    /// })?;
-    ///
-    /// // we inspect the store contents
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
-    /// # Result::<_,oxigraph::store::LoaderError>::Ok(())
+    /// store.extend([QuadRef::new(ex, ex, ex, ex), QuadRef::new(ex, ex, other, ex)])?;
+    /// assert!(store.index_batch_sizes().contains(&("posg", 2)));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn load_dataset(
-        &mut self,
-        reader: impl BufRead,
-        format: DatasetFormat,
-        base_iri: Option<&str>,
-    ) -> Result<(), LoaderError> {
-        let mut parser = DatasetParser::from_format(format);
-        if let Some(base_iri) = base_iri {
-            parser = parser
-                .with_base_iri(base_iri)
-                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
-        }
-        for quad in parser.read_quads(reader)? {
-            self.writer.insert(quad?.as_ref())?;
-        }
-        Ok(())
+    pub fn index_batch_sizes(&self) -> Vec<(&'static str, usize)> {
+        self.storage.index_batch_sizes()
     }
 
-    /// Adds a quad to this store.
+    /// Returns the version of the store content.
     ///
-    /// Returns `true` if the quad was not already in the store.
+    /// It is incremented by each transaction inserting or removing quads
+    /// and by each expiration of a quad inserted with [`Transaction::insert_with_ttl`].
     ///
     /// Usage example:
     /// ```
     /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
-    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert(quad)
-    /// })?;
-    /// assert!(store.contains(quad)?);
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// assert_eq!(store.dataset_version()?, 0);
+    /// store.extend([QuadRef::new(ex, ex, ex, ex), QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph)])?;
+    /// assert_eq!(store.dataset_version()?, 1);
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?; // Already there
+    /// assert_eq!(store.dataset_version()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn insert<'b>(&mut self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
-        self.writer.insert(quad.into())
+    pub fn dataset_version(&self) -> Result<u64, StorageError> {
+        self.storage.snapshot().dataset_version()
     }
 
-    /// Adds a set of quads to this store.
-    pub fn extend<'b>(
-        &mut self,
-        quads: impl IntoIterator<Item = impl Into<QuadRef<'b>>>,
-    ) -> Result<(), StorageError> {
-        for quad in quads {
-            self.writer.insert(quad.into())?;
-        }
-        Ok(())
+    /// Returns the number of committed transactions that have written into the store.
+    ///
+    /// Unlike the [dataset version](Self::dataset_version), it is also incremented by the writes not changing the quads,
+    /// like the metadata updates.
+    /// It is meant to be given to [`transaction_if_version`](Self::transaction_if_version).
+    pub fn commit_version(&self) -> Result<u64, StorageError> {
+        self.storage.snapshot().commit_version()
     }
 
-    /// Removes a quad from this store.
+    /// Returns the entries of the [change log](StorageConfig::with_change_log) with a sequence number greater than `sequence`, in sequence order.
     ///
-    /// Returns `true` if the quad was in the store and has been removed.
+    /// A replica or an indexer keeps the sequence number of the last entry it has applied and asks for the following ones.
+    /// The log is empty if the store has not been opened with [`StorageConfig::with_change_log`].
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
+    /// use oxigraph::store::{LoggedChange, StorageConfig, Store};
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
-    /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert(quad)?;
-    ///     transaction.remove(quad)
-    /// })?;
-    /// assert!(!store.contains(quad)?);
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// let store = Store::new_with_config(&StorageConfig::default().with_change_log())?;
+    /// store.update("CREATE GRAPH <http://example.com/g> ; INSERT DATA { GRAPH <http://example.com/g> { <http://example.com/s> <http://example.com/p> 1 } }")?;
+    ///
+    /// let changes = store.changes_since(0)?.collect::<Result<Vec<_>, _>>()?;
+    /// assert!(matches!(changes[0].change(), LoggedChange::GraphCreated(_)));
+    /// assert!(matches!(changes[1].change(), LoggedChange::Inserted(_)));
+    /// assert_eq!(store.changes_since(changes[1].sequence())?.count(), 0);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn remove<'b>(&mut self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
-        self.writer.remove(quad.into())
-    }
-
-    /// Returns all the store named graphs.
-    pub fn named_graphs(&self) -> GraphNameIter {
-        let reader = self.writer.reader();
-        GraphNameIter {
-            iter: reader.named_graphs(),
+    pub fn changes_since(&self, sequence: u64) -> Result<ChangeLogIter, StorageError> {
+        let reader = self.storage.snapshot();
+        Ok(ChangeLogIter {
+            iter: reader.changes_since(sequence)?,
             reader,
-        }
+        })
     }
 
-    /// Checks if the store contains a given graph.
-    pub fn contains_named_graph<'b>(
-        &self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
-    ) -> Result<bool, StorageError> {
-        self.writer
-            .reader()
-            .contains_named_graph(&EncodedTerm::from(graph_name.into()))
+    /// Removes the entries of the [change log](StorageConfig::with_change_log) with a sequence number lower than `sequence`,
+    /// for example once all the replicas have applied them.
+    ///
+    /// The following entries keep their sequence numbers.
+    /// Returns the number of removed entries.
+    pub fn truncate_changes(&self, sequence: u64) -> Result<usize, StorageError> {
+        self.storage
+            .transaction(|mut t| t.truncate_change_log(sequence))
     }
 
-    /// Inserts a graph into this store.
+    /// Executes a transaction only if no other write has been committed since the [commit version](Self::commit_version) `expected`.
     ///
-    /// Returns `true` if the graph was not already in the store.
+    /// It fails with [`StorageError::Conflict`] otherwise.
+    /// It allows compare-and-swap workflows where the store is read in a call,
+    /// the writes are computed across `await` points and are then applied only if the read data has not changed.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
-    /// use oxigraph::model::NamedNodeRef;
+    /// use oxigraph::store::{StorageError, Store};
+    /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert_named_graph(ex)
+    /// let version = store.commit_version()?;
+    /// store.transaction_if_version(version, |mut transaction| {
+    ///     transaction.insert(QuadRef::new(ex, ex, ex, ex))
     /// })?;
-    /// assert_eq!(store.named_graphs().collect::<Result<Vec<_>,_>>()?, vec![ex.into_owned().into()]);
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    ///
+    /// // The store has been written since `version`
+    /// let result = store.transaction_if_version(version, |mut transaction| {
+    ///     transaction.remove(QuadRef::new(ex, ex, ex, ex))
+    /// });
+    /// assert!(matches!(result, Err(StorageError::Conflict { .. })));
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn insert_named_graph<'b>(
-        &mut self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
-    ) -> Result<bool, StorageError> {
-        self.writer.insert_named_graph(graph_name.into())
+    pub fn transaction_if_version<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        expected: u64,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.check_not_in_maintenance()?;
+        self.storage
+            .transaction_if_commit_version(expected, |writer| f(Transaction { writer }))
     }
 
-    /// Clears a graph from this store.
+    /// Returns a read-only view of the store as it was just after the commit of the given [dataset version](Self::dataset_version).
+    ///
+    /// The store must keep the version in its history (see [`StorageConfig::with_history`]),
+    /// it fails with [`StorageError::VersionNotAvailable`] otherwise.
+    /// See also [`QueryOptions::with_as_of`] to evaluate a single query at a past version.
     ///
     /// Usage example:
     /// ```
-    /// use oxigraph::store::Store;
-    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{StorageConfig, Store};
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// let quad = QuadRef::new(ex, ex, ex, ex);
-    /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert(quad)?;
-    ///     transaction.clear_graph(ex)
-    /// })?;
+    /// let store = Store::new_with_config(&StorageConfig::default().with_history(10))?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.clear()?;
+    ///
+    /// assert_eq!(store.snapshot_at(1)?.len()?, 1);
+    /// assert_eq!(store.snapshot_at(2)?.len()?, 2);
+    /// assert!(store.snapshot_at(2)?.contains(QuadRef::new(ex, ex, ex, ex))?);
     /// assert!(store.is_empty()?);
-    /// assert_eq!(1, store.named_graphs().count());
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn clear_graph<'b>(
-        &mut self,
-        graph_name: impl Into<GraphNameRef<'b>>,
-    ) -> Result<(), StorageError> {
-        self.writer.clear_graph(graph_name.into())
+    pub fn snapshot_at(&self, version: u64) -> Result<StoreSnapshot, StorageError> {
+        self.storage.snapshot_at(version)?;
+        Ok(StoreSnapshot {
+            store: self.clone(),
+            version,
+        })
     }
 
-    /// Removes a graph from this store.
+    /// Computes a compact statistical [summary](DatasetSummary) of the store content,
+    /// to exchange with the federation peers.
     ///
-    /// Returns `true` if the graph was in the store and has been removed.
+    /// Warning: this function executes a full scan.
+    ///
+    /// See the [`summary`](crate::summary) module for an example.
+    pub fn export_summary(&self) -> Result<DatasetSummary, StorageError> {
+        summarize(self.iter())
+    }
+
+    /// Starts an incremental computation of a [summary](DatasetSummary) of the store content.
+    ///
+    /// Contrary to [`export_summary`](Self::export_summary), the store is scanned by batches
+    /// with [`SummaryExport::step`], for example from a canister timer.
+    /// The quads written during the computation are only counted if the scan has not reached them yet,
+    /// so the summary is approximate if the store changes meanwhile.
     ///
     /// Usage example:
     /// ```
+    /// use oxigraph::model::*;
     /// use oxigraph::store::Store;
-    /// use oxigraph::model::{NamedNodeRef, QuadRef};
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
-    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// let ex = NamedNodeRef::new("http://example.com")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert(quad)?;
-    ///     transaction.remove_named_graph(ex)
-    /// })?;
-    /// assert!(store.is_empty()?);
-    /// assert_eq!(0, store.named_graphs().count());
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///
+    /// let mut export = store.export_summary_incrementally();
+    /// while !export.step(1)? {}
+    /// assert_eq!(export.summary().unwrap().quad_count(), 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn remove_named_graph<'b>(
-        &mut self,
-        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
-    ) -> Result<bool, StorageError> {
-        self.writer.remove_named_graph(graph_name.into())
+    pub fn export_summary_incrementally(&self) -> SummaryExport {
+        SummaryExport {
+            store: self.clone(),
+            state: QuadScanState::default(),
+            builder: Some(SummaryBuilder::default()),
+            summary: None,
+        }
     }
 
-    /// Clears the store.
+    /// Verifies the [Data Integrity](https://www.w3.org/TR/vc-data-integrity/) proofs of the Verifiable Credential stored in the graph `graph`.
+    ///
+    /// Returns `false` if one of the signatures does not match.
+    ///
+    /// See the [`credentials`](crate::credentials) module for the supported proofs and an example.
+    #[cfg(feature = "credentials")]
+    pub fn verify_credential<'a>(
+        &self,
+        graph: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, CredentialError> {
+        verify_credential(self, graph.into())
+    }
+
+    /// Defines a view graph: a `CONSTRUCT` query whose results replace the content of the graph `graph_name`
+    /// each time [`refresh_view_graph`](Self::refresh_view_graph) is called.
+    ///
+    /// The definition is persisted in the store and replaces the view graph with the same name if any.
+    /// The graph content is not changed until the first refresh.
+    ///
+    /// Contrary to the [materialized views](crate::views), nothing is done when the store changes,
+    /// so view graphs are cheap to keep.
+    pub fn define_view_graph<'a>(
+        &self,
+        name: &str,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<(), EvaluationError> {
+        let query = query.try_into().map_err(Into::into)?;
+        if !query.is_construct() {
+            return Err(EvaluationError::msg(
+                "A view graph must be defined by a CONSTRUCT query",
+            ));
+        }
+        let view = ViewGraph {
+            query: query.to_string(),
+            graph_name: graph_name.into().into_owned(),
+            refreshed_at: None,
+            dataset_version: None,
+        };
+        let key = view_graph_key(name);
+        let value = encode_view_graph(&view);
+        self.transaction(|mut t| t.insert_metadata(&key, &value))?;
+        Ok(())
+    }
+
+    /// Runs the query of a view graph defined with [`define_view_graph`](Self::define_view_graph)
+    /// and atomically replaces the content of its graph with the query results.
+    ///
+    /// The refresh time and the [dataset version](Self::dataset_version) the results are computed from are recorded.
+    ///
+    /// Returns `None` if the view graph does not exist.
+    /// It must be called inside a canister, the refresh time being read from the system time.
     ///
     /// Usage example:
-    /// ```
+    /// ```no_run
     /// use oxigraph::store::Store;
     /// use oxigraph::model::*;
     ///
-    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let view = NamedNodeRef::new("http://example.com/view")?;
     /// let store = Store::new()?;
-    /// store.transaction(|mut transaction| {
-    ///     transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
-    ///     transaction.clear()
-    /// })?;
-    /// assert!(store.is_empty()?);
-    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.define_view_graph("inverse", "CONSTRUCT { ?o ?p ?s } WHERE { ?s ?p ?o }", view)?;
+    /// assert_eq!(store.quads_for_pattern(None, None, None, Some(view.into())).count(), 0);
+    ///
+    /// let refreshed = store.refresh_view_graph("inverse")?.unwrap();
+    /// assert_eq!(refreshed.dataset_version(), Some(store.dataset_version()?));
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, view))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
-    pub fn clear(&mut self) -> Result<(), StorageError> {
-        self.writer.clear()
+    pub fn refresh_view_graph(&self, name: &str) -> Result<Option<ViewGraph>, EvaluationError> {
+        let key = view_graph_key(name);
+        let refreshed_at = time();
+        self.transaction(|mut t| {
+            let mut view = if let Some(value) = t.metadata(&key)? {
+                decode_view_graph(&value)?
+            } else {
+                return Ok(None);
+            };
+            let triples = if let QueryResults::Graph(triples) = t.query(view.query.as_str())? {
+                triples.collect::<Result<Vec<_>, _>>()?
+            } else {
+                return Err(CorruptionError::msg("Invalid view graph query").into());
+            };
+            t.clear_graph(&view.graph_name)?;
+            for triple in &triples {
+                t.insert(triple.as_ref().in_graph(&view.graph_name))?;
+            }
+            // The version is read after the writes so that it stays the same until the next change
+            view.refreshed_at = Some(refreshed_at);
+            view.dataset_version = Some(t.writer.reader().dataset_version()?);
+            t.insert_metadata(&key, &encode_view_graph(&view))?;
+            Ok(Some(view))
+        })
     }
-}
-
-/// An iterator returning the quads contained in a [`Store`].
-pub struct QuadIter {
-    iter: ChainedDecodingQuadIterator,
-    reader: StorageReader,
-}
 
-impl Iterator for QuadIter {
-    type Item = Result<Quad, StorageError>;
+    /// Returns a view graph defined with [`define_view_graph`](Self::define_view_graph).
+    pub fn view_graph(&self, name: &str) -> Result<Option<ViewGraph>, StorageError> {
+        self.metadata(&view_graph_key(name))?
+            .map(|value| decode_view_graph(&value))
+            .transpose()
+    }
 
-    fn next(&mut self) -> Option<Result<Quad, StorageError>> {
-        Some(match self.iter.next()? {
-            Ok(quad) => self.reader.decode_quad(&quad),
-            Err(error) => Err(error),
+    /// Removes a view graph definition. The content of its graph is kept.
+    ///
+    /// Returns `true` if the view graph existed.
+    pub fn remove_view_graph(&self, name: &str) -> Result<bool, StorageError> {
+        let key = view_graph_key(name);
+        self.transaction(|mut t| {
+            let exists = t.metadata(&key)?.is_some();
+            if exists {
+                t.remove_metadata(&key)?;
+            }
+            Ok(exists)
         })
     }
+
+    /// Defines a rule: a `CONSTRUCT` query whose `WHERE` clause is a basic graph pattern,
+    /// deriving the triples of its template from each of its solutions.
+    ///
+    /// The definition is persisted in the store and replaces the rule with the same name if any.
+    /// The rules are applied with a semi-naive forward chaining by the [reasoners](crate::reasoner::Reasoner)
+    /// built or [refreshed](crate::reasoner::Reasoner::refresh) after their definition.
+    /// The quads derived by a rule are stored in its graph `graph_name`, which records their provenance.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::reasoner::Reasoner;
+    /// use oxigraph::store::Store;
+    ///
+    /// let alice = NamedNodeRef::new("http://example.com/alice")?;
+    /// let order = NamedNodeRef::new("http://example.com/order")?;
+    /// let placed_by = NamedNodeRef::new("http://example.com/placedBy")?;
+    /// let customer = NamedNodeRef::new("http://example.com/Customer")?;
+    /// let customers = NamedNodeRef::new("http://example.com/rules/customers")?;
+    /// let store = Store::new()?;
+    /// store.define_rule(
+    ///     "customers",
+    ///     "CONSTRUCT { ?c a <http://example.com/Customer> } WHERE { ?o <http://example.com/placedBy> ?c }",
+    ///     customers,
+    /// )?;
+    /// let _reasoner = Reasoner::with_defined_rules(store.clone())?;
+    ///
+    /// store.insert(QuadRef::new(order, placed_by, alice, GraphNameRef::DefaultGraph))?;
+    /// assert!(store.contains(QuadRef::new(alice, vocab::rdf::TYPE, customer, customers))?);
+    ///
+    /// store.remove(QuadRef::new(order, placed_by, alice, GraphNameRef::DefaultGraph))?;
+    /// assert!(!store.contains(QuadRef::new(alice, vocab::rdf::TYPE, customer, customers))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn define_rule<'a>(
+        &self,
+        name: &str,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<(), EvaluationError> {
+        let query = query.try_into().map_err(Into::into)?;
+        validate_rule(&query)?;
+        let rule = RuleDefinition {
+            query: query.to_string(),
+            graph_name: graph_name.into().into_owned(),
+        };
+        let key = rule_key(name);
+        let value = encode_rule(&rule);
+        self.transaction(|mut t| t.insert_metadata(&key, &value))?;
+        Ok(())
+    }
+
+    /// Returns a rule defined with [`define_rule`](Self::define_rule).
+    pub fn rule(&self, name: &str) -> Result<Option<RuleDefinition>, StorageError> {
+        self.metadata(&rule_key(name))?
+            .map(|value| decode_rule(&value))
+            .transpose()
+    }
+
+    /// Returns the rules defined with [`define_rule`](Self::define_rule), sorted by name.
+    pub fn rules(&self) -> Result<Vec<(String, RuleDefinition)>, StorageError> {
+        self.metadata_with_prefix(RULE_KEY_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| -> Result<_, StorageError> {
+                let name = String::from_utf8(key[RULE_KEY_PREFIX.len()..].to_vec())
+                    .map_err(|_| CorruptionError::msg("Invalid rule name"))?;
+                Ok((name, decode_rule(&value)?))
+            })
+            .collect()
+    }
+
+    /// Removes a rule definition. The quads already derived by the rule are kept.
+    ///
+    /// Returns `true` if the rule existed.
+    pub fn remove_rule(&self, name: &str) -> Result<bool, StorageError> {
+        let key = rule_key(name);
+        self.transaction(|mut t| {
+            let exists = t.metadata(&key)?.is_some();
+            if exists {
+                t.remove_metadata(&key)?;
+            }
+            Ok(exists)
+        })
+    }
+
+    /// Registers a prefix for the IRIs starting with `prefix_iri`, replacing the prefix with the same name if any.
+    ///
+    /// The prefixes are persisted in the store and returned by [`prefixes`](Self::prefixes).
+    /// They are used to abbreviate the IRIs by [`dump_graph`](Self::dump_graph) and [`dump_dataset`](Self::dump_dataset)
+    /// and could be declared implicitly in SPARQL with [`parse_query`](Self::parse_query) and [`parse_update`](Self::parse_update).
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com/s")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.register_prefix("ex", NamedNodeRef::new("http://example.com/")?)?;
+    ///
+    /// let mut buffer = Vec::new();
+    /// store.dump_graph(&mut buffer, GraphFormat::Turtle, GraphNameRef::DefaultGraph)?;
+    /// assert_eq!(
+    ///     String::from_utf8(buffer)?,
+    ///     "@prefix ex: <http://example.com/> .\n\nex:s ex:s ex:s .\n"
+    /// );
+    ///
+    /// assert!(store.remove_prefix("ex")?);
+    /// assert!(store.prefixes()?.is_empty());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn register_prefix<'a>(
+        &self,
+        prefix_name: &str,
+        prefix_iri: impl Into<NamedNodeRef<'a>>,
+    ) -> Result<(), StorageError> {
+        let key = prefix_key(prefix_name);
+        let value = prefix_iri.into().as_str().as_bytes().to_vec();
+        self.transaction(|mut t| t.insert_metadata(&key, &value))
+    }
+
+    /// Returns the prefixes registered with [`register_prefix`](Self::register_prefix), sorted by name.
+    pub fn prefixes(&self) -> Result<Vec<(String, NamedNode)>, StorageError> {
+        self.metadata_with_prefix(PREFIX_KEY_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| -> Result<_, StorageError> {
+                let name = String::from_utf8(key[PREFIX_KEY_PREFIX.len()..].to_vec())
+                    .map_err(|_| CorruptionError::msg("Invalid prefix name"))?;
+                let iri = String::from_utf8(value)
+                    .map_err(|_| CorruptionError::msg("Invalid prefix IRI"))?;
+                Ok((name, NamedNode::new_unchecked(iri)))
+            })
+            .collect()
+    }
+
+    /// Removes a prefix registered with [`register_prefix`](Self::register_prefix).
+    ///
+    /// Returns `true` if the prefix existed.
+    pub fn remove_prefix(&self, prefix_name: &str) -> Result<bool, StorageError> {
+        let key = prefix_key(prefix_name);
+        self.transaction(|mut t| {
+            let exists = t.metadata(&key)?.is_some();
+            if exists {
+                t.remove_metadata(&key)?;
+            }
+            Ok(exists)
+        })
+    }
+
+    /// Parses a SPARQL query with the prefixes registered with [`register_prefix`](Self::register_prefix) declared implicitly.
+    ///
+    /// The `PREFIX` declarations of the query override them.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::QueryResults;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com/s")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// store.register_prefix("ex", NamedNodeRef::new("http://example.com/")?)?;
+    ///
+    /// let query = store.parse_query("ASK { ex:s ex:s ex:s }", None)?;
+    /// assert!(matches!(store.query(query)?, QueryResults::Boolean(true)));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn parse_query(
+        &self,
+        query: &str,
+        base_iri: Option<&str>,
+    ) -> Result<Query, EvaluationError> {
+        Ok(Query::parse_with_prefixes(
+            query,
+            base_iri,
+            self.registered_prefixes()?,
+        )?)
+    }
+
+    /// Parses a SPARQL update with the prefixes registered with [`register_prefix`](Self::register_prefix) declared implicitly.
+    ///
+    /// The `PREFIX` declarations of the update override them.
+    pub fn parse_update(
+        &self,
+        update: &str,
+        base_iri: Option<&str>,
+    ) -> Result<Update, EvaluationError> {
+        Ok(Update::parse_with_prefixes(
+            update,
+            base_iri,
+            self.registered_prefixes()?,
+        )?)
+    }
+
+    fn registered_prefixes(&self) -> Result<Vec<(String, String)>, StorageError> {
+        Ok(self
+            .prefixes()?
+            .into_iter()
+            .map(|(prefix_name, prefix_iri)| (prefix_name, prefix_iri.into_string()))
+            .collect())
+    }
+
+    /// Inserts a graph into this store.
+    ///
+    /// Returns `true` if the graph was not already in the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::NamedNodeRef;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert_named_graph(ex)?;
+    ///
+    /// assert_eq!(store.named_graphs().collect::<Result<Vec<_>,_>>()?, vec![ex.into_owned().into()]);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn insert_named_graph<'a>(
+        &self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, StorageError> {
+        let graph_name = graph_name.into();
+        self.transaction(|mut t| t.insert_named_graph(graph_name))
+    }
+
+    /// Clears a graph from this store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// let store = Store::new()?;
+    /// store.insert(quad)?;
+    /// assert_eq!(1, store.len()?);
+    ///
+    /// store.clear_graph(ex)?;
+    /// assert!(store.is_empty()?);
+    /// assert_eq!(1, store.named_graphs().count());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn clear_graph<'a>(
+        &self,
+        graph_name: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), StorageError> {
+        let graph_name = graph_name.into();
+        self.transaction(|mut t| t.clear_graph(graph_name))
+    }
+
+    /// Removes a graph from this store.
+    ///
+    /// Returns `true` if the graph was in the store and has been removed.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// let store = Store::new()?;
+    /// store.insert(quad)?;
+    /// assert_eq!(1, store.len()?);
+    ///
+    /// assert!(store.remove_named_graph(ex)?);
+    /// assert!(store.is_empty()?);
+    /// assert_eq!(0, store.named_graphs().count());
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn remove_named_graph<'a>(
+        &self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<bool, StorageError> {
+        let graph_name = graph_name.into();
+        self.transaction(|mut t| t.remove_named_graph(graph_name))
+    }
+
+    /// Adds the quads of the graph `from` to the graph `to`, like the SPARQL [`ADD`](https://www.w3.org/TR/sparql11-update/#add) operation.
+    ///
+    /// The quads are copied in their encoded form, without decoding and encoding again their terms.
+    /// Returns the number of quads that were not already in `to`.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///
+    /// assert_eq!(store.add_graph(ex, GraphNameRef::DefaultGraph)?, 1);
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// assert_eq!(store.len()?, 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn add_graph<'a>(
+        &self,
+        from: impl Into<GraphNameRef<'a>>,
+        to: impl Into<GraphNameRef<'a>>,
+    ) -> Result<usize, StorageError> {
+        let (from, to) = (from.into(), to.into());
+        self.transaction(|mut t| t.add_graph(from, to))
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from`, like the SPARQL [`COPY`](https://www.w3.org/TR/sparql11-update/#copy) operation.
+    ///
+    /// The quads are copied in their encoded form, without decoding and encoding again their terms.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let g = NamedNodeRef::new("http://example.com/g")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(g, g, g, g))?;
+    ///
+    /// store.copy_graph(ex, g)?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, g))?);
+    /// assert!(!store.contains(QuadRef::new(g, g, g, g))?);
+    /// assert_eq!(store.len()?, 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn copy_graph<'a>(
+        &self,
+        from: impl Into<GraphNameRef<'a>>,
+        to: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), StorageError> {
+        let (from, to) = (from.into(), to.into());
+        self.transaction(|mut t| t.copy_graph(from, to))
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from` and removes `from`, like the SPARQL [`MOVE`](https://www.w3.org/TR/sparql11-update/#move) operation.
+    ///
+    /// The quads are moved in their encoded form, without decoding and encoding again their terms.
+    /// The default graph is cleared instead of being removed.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let g = NamedNodeRef::new("http://example.com/g")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///
+    /// store.move_graph(ex, g)?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, g))?);
+    /// assert_eq!(store.named_graphs().count(), 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn move_graph<'a>(
+        &self,
+        from: impl Into<GraphNameRef<'a>>,
+        to: impl Into<GraphNameRef<'a>>,
+    ) -> Result<(), StorageError> {
+        let (from, to) = (from.into(), to.into());
+        self.transaction(|mut t| t.move_graph(from, to))
+    }
+
+    /// Clears the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;    
+    /// assert_eq!(2, store.len()?);
+    ///
+    /// store.clear()?;
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn clear(&self) -> Result<(), StorageError> {
+        self.transaction(|mut t| t.clear())
+    }
+
+    /// Removes at most `limit` quads whose time to live has elapsed.
+    ///
+    /// Returns the number of removed quads, lower than `limit` if all the expired quads have been removed.
+    /// The expired quads are already hidden from the queries and the pattern lookups before their removal.
+    ///
+    /// See [`Transaction::insert_with_ttl`] to insert quads with a time to live.
+    ///
+    /// The expiration of a quad is counted as a change of the [`dataset_version`](Self::dataset_version)
+    /// and its removal does not change the version again.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageConfig, Store};
+    /// use oxigraph::model::*;
+    /// use std::time::Duration;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new_with_config(&StorageConfig::default().with_clock(|| 0))?;
+    /// store.transaction(|mut t| t.insert_with_ttl(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph), Duration::ZERO))?;
+    /// assert!(store.is_empty()?);
+    /// let version = store.dataset_version()?;
+    ///
+    /// assert_eq!(1, store.remove_expired_quads(100)?);
+    /// assert_eq!(version, store.dataset_version()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn remove_expired_quads(&self, limit: usize) -> Result<usize, StorageError> {
+        let now = self.storage.now();
+        self.transaction(|mut t| t.writer.remove_expired_quads(now, limit))
+    }
+
+    /// Removes the subgraph reachable from `root` following a [property path](https://www.w3.org/TR/sparql11-query/#propertypaths).
+    ///
+    /// All the quads whose subject is `root` or one of the nodes reachable from it using `path` are removed.
+    /// Cycles in the data are only traversed once.
+    ///
+    /// If `dry_run` is `true` nothing is removed.
+    ///
+    /// Returns the quads that have been removed (or would have been removed in dry-run mode).
+    ///
+    /// Usage example removing a document and all its parts:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::PropertyPathExpression;
+    ///
+    /// let doc = NamedNodeRef::new("http://example.com/doc")?;
+    /// let part = NamedNodeRef::new("http://example.com/part")?;
+    /// let has_part = NamedNode::new("http://purl.org/dc/terms/hasPart")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(doc, &has_part, part, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(part, &has_part, doc, GraphNameRef::DefaultGraph))?;
+    ///
+    /// let path = PropertyPathExpression::OneOrMore(Box::new(has_part.into()));
+    /// assert_eq!(2, store.remove_closure(doc, &path, true)?.len());
+    /// assert_eq!(2, store.len()?);
+    ///
+    /// assert_eq!(2, store.remove_closure(doc, &path, false)?.len());
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn remove_closure<'a>(
+        &self,
+        root: impl Into<SubjectRef<'a>>,
+        path: &PropertyPathExpression,
+        dry_run: bool,
+    ) -> Result<Vec<Quad>, StorageError> {
+        let root = root.into();
+        self.transaction(|mut t| t.remove_closure(root, path, dry_run))
+    }
+
+    /// Rewrites atomically the objects of the quads matching a subject and predicate filter.
+    ///
+    /// `transform` is called on each matching object and returns the new object or `None` to keep the quad unchanged.
+    ///
+    /// Returns the number of rewritten quads.
+    ///
+    /// Usage example renaming an IRI used as object:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let old = NamedNodeRef::new("http://example.com/old")?;
+    /// let new = NamedNodeRef::new("http://example.com/new")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, old, GraphNameRef::DefaultGraph))?;
+    ///
+    /// assert_eq!(1, store.replace_objects(None, Some(ex), |o| (*o == Term::from(old)).then(|| new.into()))?);
+    /// assert!(store.contains(QuadRef::new(ex, ex, new, GraphNameRef::DefaultGraph))?);
+    /// assert_eq!(1, store.len()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn replace_objects<'a>(
+        &self,
+        subject: Option<SubjectRef<'a>>,
+        predicate: Option<NamedNodeRef<'a>>,
+        transform: impl Fn(&Term) -> Option<Term>,
+    ) -> Result<usize, StorageError> {
+        self.transaction(|mut t| t.replace_objects(subject, predicate, &transform))
+    }
+
+    /// Returns the concepts that are narrower than `concept` following transitively
+    /// [`skos:narrower`](vocab::skos::NARROWER) and the inverse of [`skos:broader`](vocab::skos::BROADER).
+    ///
+    /// Each concept is returned only once, even if the hierarchy contains cycles, and `concept` itself is not returned.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::skos;
+    ///
+    /// let animal = NamedNodeRef::new("http://example.com/animal")?;
+    /// let mammal = NamedNodeRef::new("http://example.com/mammal")?;
+    /// let cat = NamedNodeRef::new("http://example.com/cat")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(animal, skos::NARROWER, mammal, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(cat, skos::BROADER, mammal, GraphNameRef::DefaultGraph))?;
+    ///
+    /// assert_eq!(store.narrower_transitive(animal)?, vec![NamedOrBlankNode::from(mammal), cat.into()]);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn narrower_transitive<'a>(
+        &self,
+        concept: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<Vec<NamedOrBlankNode>, StorageError> {
+        self.skos_hierarchy_closure(concept.into(), skos::NARROWER, skos::BROADER)
+    }
+
+    /// Returns the concepts that are broader than `concept` following transitively
+    /// [`skos:broader`](vocab::skos::BROADER) and the inverse of [`skos:narrower`](vocab::skos::NARROWER).
+    ///
+    /// Each concept is returned only once, even if the hierarchy contains cycles, and `concept` itself is not returned.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::skos;
+    ///
+    /// let animal = NamedNodeRef::new("http://example.com/animal")?;
+    /// let mammal = NamedNodeRef::new("http://example.com/mammal")?;
+    /// let cat = NamedNodeRef::new("http://example.com/cat")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(animal, skos::NARROWER, mammal, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(cat, skos::BROADER, mammal, GraphNameRef::DefaultGraph))?;
+    ///
+    /// assert_eq!(store.broader_transitive(cat)?, vec![NamedOrBlankNode::from(mammal), animal.into()]);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn broader_transitive<'a>(
+        &self,
+        concept: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<Vec<NamedOrBlankNode>, StorageError> {
+        self.skos_hierarchy_closure(concept.into(), skos::BROADER, skos::NARROWER)
+    }
+
+    /// Returns the concept hierarchy of a SKOS concept scheme.
+    ///
+    /// The roots are the scheme [top concepts](vocab::skos::HAS_TOP_CONCEPT) and the children of each concept
+    /// are its narrower concepts. Each concept appears only once in the returned forest, even if the hierarchy contains cycles.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::skos;
+    ///
+    /// let scheme = NamedNodeRef::new("http://example.com/scheme")?;
+    /// let animal = NamedNodeRef::new("http://example.com/animal")?;
+    /// let cat = NamedNodeRef::new("http://example.com/cat")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(scheme, skos::HAS_TOP_CONCEPT, animal, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(cat, skos::BROADER, animal, GraphNameRef::DefaultGraph))?;
+    ///
+    /// let tree = store.concept_scheme_tree(scheme)?;
+    /// assert_eq!(tree.len(), 1);
+    /// assert_eq!(tree[0].concept, NamedOrBlankNode::from(animal));
+    /// assert_eq!(tree[0].children[0].concept, NamedOrBlankNode::from(cat));
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn concept_scheme_tree<'a>(
+        &self,
+        scheme: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<Vec<ConceptTree>, StorageError> {
+        let mut visited = HashSet::new();
+        let mut roots = Vec::new();
+        for concept in
+            self.skos_related(scheme.into(), skos::HAS_TOP_CONCEPT, skos::TOP_CONCEPT_OF)?
+        {
+            if visited.insert(concept.clone()) {
+                roots.push(concept);
+            }
+        }
+        roots
+            .into_iter()
+            .map(|concept| self.concept_tree(concept, &mut visited))
+            .collect()
+    }
+
+    /// Builds depth first the tree of the narrower concepts of `concept` not visited yet.
+    ///
+    /// The concepts being built are kept in an explicit stack to not overflow the call stack on deep hierarchies.
+    fn concept_tree(
+        &self,
+        concept: NamedOrBlankNode,
+        visited: &mut HashSet<NamedOrBlankNode>,
+    ) -> Result<ConceptTree, StorageError> {
+        // The ancestors of the current concept, with their narrower concepts not explored yet
+        let mut stack = Vec::new();
+        let mut narrower = self
+            .skos_related(concept.as_ref(), skos::NARROWER, skos::BROADER)?
+            .into_iter();
+        let mut current = ConceptTree {
+            concept,
+            children: Vec::new(),
+        };
+        loop {
+            if let Some(child) = narrower.find(|child| visited.insert(child.clone())) {
+                let child_narrower = self
+                    .skos_related(child.as_ref(), skos::NARROWER, skos::BROADER)?
+                    .into_iter();
+                stack.push((current, narrower));
+                current = ConceptTree {
+                    concept: child,
+                    children: Vec::new(),
+                };
+                narrower = child_narrower;
+            } else if let Some((mut parent, parent_narrower)) = stack.pop() {
+                parent.children.push(current);
+                current = parent;
+                narrower = parent_narrower;
+            } else {
+                return Ok(current);
+            }
+        }
+    }
+
+    fn skos_hierarchy_closure(
+        &self,
+        concept: NamedOrBlankNodeRef<'_>,
+        relation: NamedNodeRef<'_>,
+        inverse_relation: NamedNodeRef<'_>,
+    ) -> Result<Vec<NamedOrBlankNode>, StorageError> {
+        let mut visited = HashSet::new();
+        visited.insert(concept.into_owned());
+        let mut output = Vec::new();
+        let mut todo = VecDeque::new();
+        todo.push_back(concept.into_owned());
+        while let Some(current) = todo.pop_front() {
+            for related in self.skos_related(current.as_ref(), relation, inverse_relation)? {
+                if visited.insert(related.clone()) {
+                    output.push(related.clone());
+                    todo.push_back(related);
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Returns the resources linked from `concept` with `relation` or to `concept` with `inverse_relation`.
+    fn skos_related(
+        &self,
+        concept: NamedOrBlankNodeRef<'_>,
+        relation: NamedNodeRef<'_>,
+        inverse_relation: NamedNodeRef<'_>,
+    ) -> Result<Vec<NamedOrBlankNode>, StorageError> {
+        let mut output = Vec::new();
+        for quad in self.quads_for_pattern(Some(concept.into()), Some(relation), None, None) {
+            match quad?.object {
+                Term::NamedNode(node) => output.push(node.into()),
+                Term::BlankNode(node) => output.push(node.into()),
+                Term::Literal(_) | Term::Triple(_) => (),
+            }
+        }
+        for quad in self.quads_for_pattern(None, Some(inverse_relation), Some(concept.into()), None)
+        {
+            match quad?.subject {
+                Subject::NamedNode(node) => output.push(node.into()),
+                Subject::BlankNode(node) => output.push(node.into()),
+                Subject::Triple(_) => (),
+            }
+        }
+        Ok(output)
+    }
+
+    /// Enables or disables the read-only mode.
+    ///
+    /// In read-only mode all the write operations (insertions, removals, SPARQL updates, loads...)
+    /// fail with [`StorageError::ReadOnly`].
+    /// The flag is persisted with the store data.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageError, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.set_read_only(true)?;
+    /// assert!(store.is_read_only()?);
+    /// assert!(matches!(store.insert(QuadRef::new(ex, ex, ex, ex)), Err(StorageError::ReadOnly)));
+    ///
+    /// store.set_read_only(false)?;
+    /// assert!(store.insert(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn set_read_only(&self, read_only: bool) -> Result<(), StorageError> {
+        self.storage.set_read_only(read_only)
+    }
+
+    /// Returns if the store is in read-only mode.
+    ///
+    /// See [`Store::set_read_only`].
+    pub fn is_read_only(&self) -> Result<bool, StorageError> {
+        self.storage.is_read_only()
+    }
+
+    /// Sets the [`ReadFilter`] hiding quads from all the reads, `None` removing the current one.
+    ///
+    /// The hidden quads are skipped by [`quads_for_pattern`](Self::quads_for_pattern), [`contains`](Self::contains),
+    /// the SPARQL queries, the `WHERE` clauses of the SPARQL updates and the dumps,
+    /// in addition to the graphs hidden by the [access control list](crate::acl).
+    /// They are still counted by [`len`](Self::len) and saved by [`backup`](Self::backup).
+    ///
+    /// The filter is only kept in memory and must be set again after each canister upgrade.
+    ///
+    /// The reads done before and after the change differ, so the [`dataset_version`](Self::dataset_version) is incremented
+    /// and the query continuations and dump cursors taken before the change are rejected.
+    /// The version is incremented even if the store is read-only.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::QueryResults;
+    /// use oxigraph::store::{QuadPattern, ReadFilter, Store};
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com/alice")?;
+    /// let password = NamedNodeRef::new("http://example.com/password")?;
+    /// store.insert(QuadRef::new(ex, password, LiteralRef::new_simple_literal("secret"), GraphNameRef::DefaultGraph))?;
+    /// let version = store.dataset_version()?;
+    /// store.set_read_filter(Some(ReadFilter::hiding([QuadPattern::new(None, Some(password), None, None)])))?;
+    ///
+    /// assert!(store.dataset_version()? > version);
+    /// assert_eq!(store.iter().count(), 0);
+    /// if let QueryResults::Boolean(found) = store.query("ASK { ?s ?p ?o }")? {
+    ///     assert!(!found);
+    /// }
+    /// assert_eq!(store.len()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn set_read_filter(&self, filter: Option<ReadFilter>) -> Result<(), StorageError> {
+        self.storage.set_read_filter(filter.map(|filter| {
+            let patterns = filter
+                .patterns
+                .iter()
+                .map(|pattern| {
+                    [
+                        pattern.subject.as_ref().map(|t| t.as_ref().into()),
+                        pattern.predicate.as_ref().map(|t| t.as_ref().into()),
+                        pattern.object.as_ref().map(|t| t.as_ref().into()),
+                        pattern.graph_name.as_ref().map(|t| t.as_ref().into()),
+                    ]
+                })
+                .collect();
+            let hide = filter.hide;
+            EncodedReadFilter::new(
+                patterns,
+                Arc::new(move |reader: &StorageReader, quad: &EncodedQuad| {
+                    // A quad that can not be decoded is hidden
+                    reader
+                        .decode_quad(quad)
+                        .map_or(true, |quad| hide(quad.as_ref()))
+                }),
+            )
+        }));
+    }
+
+    /// Registers a standing subscription: after each commit, `callback` is called with the inserted and removed quads matching `pattern`.
+    ///
+    /// The callback is called after the end of the transaction, so it is allowed to write into the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{QuadChange, QuadPattern, Store};
+    /// use oxigraph::model::*;
+    /// use oxigraph::model::vocab::rdf;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let order = NamedNodeRef::new("http://example.com/Order")?;
+    /// let ex = NamedNodeRef::new("http://example.com/o1")?;
+    /// let store = Store::new()?;
+    /// let new_orders = Arc::new(Mutex::new(Vec::new()));
+    /// let seen = Arc::clone(&new_orders);
+    /// store.subscribe(
+    ///     QuadPattern::new(None, Some(rdf::TYPE), Some(order.into()), None),
+    ///     move |changes| {
+    ///         for change in changes {
+    ///             if let QuadChange::Inserted(quad) = change {
+    ///                 seen.lock().unwrap().push(quad.subject.clone());
+    ///             }
+    ///         }
+    ///     },
+    /// );
+    /// store.insert(QuadRef::new(ex, rdf::TYPE, order, GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// assert_eq!(*new_orders.lock().unwrap(), vec![Subject::from(ex.into_owned())]);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn subscribe(
+        &self,
+        pattern: QuadPattern,
+        callback: impl Fn(&[QuadChange]) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.add_subscription(Subscription {
+            pattern,
+            target: SubscriptionTarget::Callback(Arc::new(callback)),
+        })
+    }
+
+    /// Registers a standing subscription whose matching changes are queued
+    /// until they are retrieved with [`take_subscription_changes`](Self::take_subscription_changes),
+    /// for example by a subscriber canister.
+    ///
+    /// At most `capacity` changes are kept, the oldest ones being dropped first.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{QuadChange, QuadPattern, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    /// let store = Store::new()?;
+    /// let subscription = store.subscribe_queued(QuadPattern::default(), 100);
+    /// store.insert(quad)?;
+    /// store.remove(quad)?;
+    /// assert_eq!(
+    ///     store.take_subscription_changes(subscription),
+    ///     Some(vec![QuadChange::Inserted(quad.into_owned()), QuadChange::Removed(quad.into_owned())])
+    /// );
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn subscribe_queued(&self, pattern: QuadPattern, capacity: usize) -> SubscriptionId {
+        self.add_subscription(Subscription {
+            pattern,
+            target: SubscriptionTarget::Queue {
+                changes: VecDeque::new(),
+                capacity: capacity.max(1),
+            },
+        })
+    }
+
+    /// Registers a standing subscription notifying the canister `canister` of the matching changes.
+    ///
+    /// After each commit, a one-way call to its method `method` is sent with a [`ChangeNotification`](crate::events::ChangeNotification)
+    /// of the matching changes.
+    /// See the [`events`](crate::events) module for the details.
+    ///
+    /// This method must be used inside a canister.
+    pub fn subscribe_canister(
+        &self,
+        pattern: QuadPattern,
+        canister: Principal,
+        method: impl Into<String>,
+    ) -> SubscriptionId {
+        self.add_subscription(Subscription {
+            pattern,
+            target: SubscriptionTarget::Canister {
+                canister,
+                method: method.into(),
+            },
+        })
+    }
+
+    /// Returns the canister notified by a subscription registered with [`subscribe_canister`](Self::subscribe_canister).
+    ///
+    /// It allows to check that a canister only removes its own subscriptions.
+    pub fn subscription_canister(&self, id: SubscriptionId) -> Option<Principal> {
+        if let SubscriptionTarget::Canister { canister, .. } =
+            self.subscriptions.read().unwrap().entries.get(&id)?.target
+        {
+            Some(canister)
+        } else {
+            None
+        }
+    }
+
+    /// Returns and removes the changes queued for a subscription registered with [`subscribe_queued`](Self::subscribe_queued).
+    ///
+    /// Returns `None` if the subscription does not exist or is not a queued one.
+    pub fn take_subscription_changes(&self, id: SubscriptionId) -> Option<Vec<QuadChange>> {
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        if let SubscriptionTarget::Queue { changes, .. } =
+            &mut subscriptions.entries.get_mut(&id)?.target
+        {
+            Some(changes.drain(..).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Removes a subscription.
+    ///
+    /// Returns `true` if the subscription existed.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        let removed = subscriptions.entries.remove(&id).is_some();
+        if subscriptions.entries.is_empty() {
+            self.storage.set_change_observer(None);
+        }
+        removed
+    }
+
+    fn add_subscription(&self, subscription: Subscription) -> SubscriptionId {
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        let id = SubscriptionId(subscriptions.next_id);
+        subscriptions.next_id += 1;
+        subscriptions.entries.insert(id, subscription);
+        if subscriptions.entries.len() == 1 {
+            let subscriptions = Arc::clone(&self.subscriptions);
+            self.storage.set_change_observer(Some(Arc::new(
+                move |reader: &StorageReader, changes: &[(EncodedQuad, bool)]| {
+                    notify_subscriptions(&subscriptions, reader, changes)
+                },
+            )));
+        }
+        id
+    }
+
+    /// Registers a trigger running `action` inside each write transaction changing quads matching `pattern`.
+    ///
+    /// The action is run once per transaction, at its end and before its commit, with all its matching changes.
+    /// Its writes are part of the transaction: if it fails the whole transaction fails.
+    /// The writes done by the triggers do not fire the triggers again.
+    ///
+    /// The triggers are kept in memory and have to be registered again after an upgrade of the canister.
+    ///
+    /// Usage example stamping the resources whose price changes with `dcterms:modified`:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::Update;
+    /// use oxigraph::store::{QuadPattern, Store, TriggerAction};
+    ///
+    /// let store = Store::new()?;
+    /// let price = NamedNodeRef::new("http://example.com/price")?;
+    /// let modified = NamedNodeRef::new("http://purl.org/dc/terms/modified")?;
+    /// store.create_trigger(
+    ///     QuadPattern::new(None, Some(price), None, None),
+    ///     TriggerAction::Update(Update::parse(
+    ///         "DELETE { ?subject <http://purl.org/dc/terms/modified> ?old }
+    ///          INSERT { ?subject <http://purl.org/dc/terms/modified> \"2022-01-01T00:00:00Z\"^^<http://www.w3.org/2001/XMLSchema#dateTime> }
+    ///          WHERE { OPTIONAL { ?subject <http://purl.org/dc/terms/modified> ?old } }",
+    ///         None,
+    ///     )?),
+    /// );
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com/product")?;
+    /// store.insert(QuadRef::new(ex, price, Literal::from(10).as_ref(), GraphNameRef::DefaultGraph))?;
+    /// assert_eq!(store.quads_for_pattern(Some(ex.into()), Some(modified), None, None).count(), 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn create_trigger(&self, pattern: QuadPattern, action: TriggerAction) -> TriggerId {
+        let mut triggers = self.triggers.write().unwrap();
+        let id = TriggerId(triggers.next_id);
+        triggers.next_id += 1;
+        triggers.entries.insert(id, Trigger { pattern, action });
+        if triggers.entries.len() == 1 {
+            let triggers = Arc::clone(&self.triggers);
+            self.storage.set_commit_hook(Some(Arc::new(
+                move |writer: StorageWriter<'_>, changes: &[(EncodedQuad, bool)]| {
+                    fire_triggers(&triggers, writer, changes)
+                },
+            )));
+        }
+        id
+    }
+
+    /// Removes a trigger registered with [`create_trigger`](Self::create_trigger).
+    ///
+    /// Returns `true` if the trigger existed.
+    pub fn drop_trigger(&self, id: TriggerId) -> bool {
+        let mut triggers = self.triggers.write().unwrap();
+        let removed = triggers.entries.remove(&id).is_some();
+        if triggers.entries.is_empty() {
+            self.storage.set_commit_hook(None);
+        }
+        removed
+    }
+
+    /// Puts the store in maintenance mode.
+    ///
+    /// While in maintenance mode the writes done through the regular methods are handled according to `mode`
+    /// and the background work (migrations, compaction...) is done with [`maintenance_transaction`](Self::maintenance_transaction),
+    /// so that it does not interleave with user mutations.
+    ///
+    /// If the store is already in maintenance mode, only the mode is changed and the already queued writes are kept.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{MaintenanceMode, StorageError, Store};
+    /// use oxigraph::model::*;
+    /// use std::time::Duration;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.begin_maintenance(MaintenanceMode::Queue {
+    ///     capacity: 1,
+    ///     retry_after: Duration::from_secs(10),
+    /// });
+    ///
+    /// // The first write is queued, the second one rejected
+    /// assert!(!store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// assert!(!store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// assert!(matches!(
+    ///     store.insert(QuadRef::new(ex, ex, ex, ex)),
+    ///     Err(StorageError::UnderMaintenance { .. })
+    /// ));
+    ///
+    /// // The background work is still allowed
+    /// store.maintenance_transaction(|mut t| t.insert(QuadRef::new(ex, ex, ex, ex)))?;
+    ///
+    /// // The queued write is applied when the maintenance ends
+    /// assert_eq!(store.end_maintenance()?, 1);
+    /// assert_eq!(store.len()?, 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn begin_maintenance(&self, mode: MaintenanceMode) {
+        let mut maintenance = self.maintenance.write().unwrap();
+        if let Some(maintenance) = maintenance.as_mut() {
+            maintenance.mode = mode;
+        } else {
+            *maintenance = Some(Maintenance {
+                mode,
+                queue: Vec::new(),
+            });
+        }
+    }
+
+    /// Leaves the maintenance mode and applies the queued writes in their arrival order.
+    ///
+    /// Returns the number of applied writes.
+    /// If some queued writes fail, the other ones are still applied and the first error is returned.
+    pub fn end_maintenance(&self) -> Result<usize, EvaluationError> {
+        let maintenance = if let Some(maintenance) = self.maintenance.write().unwrap().take() {
+            maintenance
+        } else {
+            return Ok(0);
+        };
+        let mut count = 0;
+        let mut first_error = None;
+        for write in maintenance.queue {
+            let result = match write {
+                QueuedWrite::Insert(quad) => self.insert(&quad).map(|_| ()).map_err(Into::into),
+                QueuedWrite::Remove(quad) => self.remove(&quad).map(|_| ()).map_err(Into::into),
+                QueuedWrite::Update(update) => self.update(update),
+            };
+            match result {
+                Ok(()) => count += 1,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        if let Some(error) = first_error {
+            Err(error)
+        } else {
+            Ok(count)
+        }
+    }
+
+    /// Returns if the store is in maintenance mode.
+    pub fn is_in_maintenance(&self) -> bool {
+        self.maintenance.read().unwrap().is_some()
+    }
+
+    /// Executes a transaction even if the store is in maintenance mode.
+    ///
+    /// It should only be used by the background work done during the maintenance.
+    /// See [`begin_maintenance`](Self::begin_maintenance).
+    pub fn maintenance_transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.storage.transaction(|writer| f(Transaction { writer }))
+    }
+
+    fn check_not_in_maintenance(&self) -> Result<(), StorageError> {
+        if let Some(maintenance) = &*self.maintenance.read().unwrap() {
+            return Err(StorageError::UnderMaintenance {
+                retry_after: maintenance.mode.retry_after(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Queues the write if the store is in maintenance mode.
+    ///
+    /// Returns `true` if the write has been queued and fails if it is rejected,
+    /// the writes into a read-only store being rejected before they are queued.
+    fn queue_write(&self, write: impl FnOnce() -> QueuedWrite) -> Result<bool, StorageError> {
+        let mut maintenance = self.maintenance.write().unwrap();
+        let maintenance = if let Some(maintenance) = maintenance.as_mut() {
+            maintenance
+        } else {
+            return Ok(false);
+        };
+        if self.storage.is_read_only()? {
+            return Err(StorageError::ReadOnly);
+        }
+        match maintenance.mode {
+            MaintenanceMode::Queue { capacity, .. } if maintenance.queue.len() < capacity => {
+                maintenance.queue.push(write());
+                Ok(true)
+            }
+            mode => Err(StorageError::UnderMaintenance {
+                retry_after: mode.retry_after(),
+            }),
+        }
+    }
+
+    /// Validates that all the store invariants held in the data.
+    ///
+    /// It returns a [`CorruptionError`] whose source is the [`CorruptionReport`] if an inconsistency is found.
+    ///
+    /// Warning: this function executes a full scan.
+    /// See [`check_consistency`](Self::check_consistency) for the checks done.
+    pub fn validate(&self) -> Result<(), StorageError> {
+        self.storage.snapshot().validate()
+    }
+
+    /// Checks the consistency of the store content and returns the found inconsistencies.
+    ///
+    /// It checks that:
+    /// * the secondary indexes contain exactly the quads of SPOG and DSPO.
+    /// * the strings of all the terms are in the dictionary.
+    /// * the graphs of the quads are in the list of the named graphs.
+    /// * the counter returned by [`len`](Self::len) is exact.
+    ///
+    /// Warning: this function executes a full scan.
+    /// If an inconsistency is found, the secondary indexes could be repaired with [`rebuild_indexes`](Self::rebuild_indexes).
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///
+    /// let report = store.check_consistency()?;
+    /// assert!(report.is_consistent());
+    /// assert_eq!(report.quads_checked(), 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn check_consistency(&self) -> Result<CorruptionReport, StorageError> {
+        self.storage.snapshot().check_consistency()
+    }
+
+    /// Starts a rebuild of the secondary indexes from the primary ones (SPOG and DSPO).
+    ///
+    /// It is the recovery path when the indexes are inconsistent.
+    /// The rebuild is done incrementally by calling [`IndexRebuild::step`], for example from a canister timer.
+    /// The queries may return incomplete results until it is finished,
+    /// so the store should be put in [maintenance mode](Self::begin_maintenance) during the rebuild.
+    /// The writes done during the rebuild are kept.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{MaintenanceMode, Store};
+    /// use std::time::Duration;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let store = Store::new()?;
+    /// store.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///
+    /// store.begin_maintenance(MaintenanceMode::Reject {
+    ///     retry_after: Duration::from_secs(60),
+    /// });
+    /// let mut rebuild = store.rebuild_indexes();
+    /// while !rebuild.step(1)? {}
+    /// assert_eq!(rebuild.quads_processed(), 2);
+    /// store.end_maintenance()?;
+    ///
+    /// assert_eq!(store.quads_for_pattern(None, None, Some(ex.into()), None).count(), 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn rebuild_indexes(&self) -> IndexRebuild {
+        IndexRebuild {
+            store: self.clone(),
+            state: IndexRebuildState::default(),
+        }
+    }
+
+    /// Starts a collection of the strings of the dictionary that are not used by any quad or named graph anymore.
+    ///
+    /// Removing a quad does not remove its strings from the dictionary, so the memory used by the store
+    /// keeps growing if the data changes often.
+    /// The collection is done incrementally by calling [`StringCollection::step`], for example from a canister timer.
+    /// It restarts each time the quads change before it is finished, so it should be run when the store is not written much.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, LiteralRef::new_simple_literal("a long literal stored in the dictionary"), GraphNameRef::DefaultGraph);
+    /// let store = Store::new()?;
+    /// store.insert(quad)?;
+    /// store.remove(quad)?;
+    ///
+    /// let mut collection = store.collect_strings();
+    /// while !collection.step(10)? {}
+    /// assert_eq!(collection.strings_removed(), 2);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn collect_strings(&self) -> StringCollection {
+        StringCollection {
+            store: self.clone(),
+            state: StringCollectionState::default(),
+        }
+    }
+
+    /// Removes the strings of the dictionary that are not used by any quad or named graph anymore
+    /// and returns the number of removed strings.
+    ///
+    /// Warning: this function executes a full scan of the store and of the dictionary.
+    /// Use [`collect_strings`](Self::collect_strings) to spread the collection on several messages
+    /// if the store is too big to be scanned within the instruction limit.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::Store;
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, LiteralRef::new_simple_literal("a long literal stored in the dictionary"), ex);
+    /// let store = Store::new()?;
+    /// store.insert(quad)?;
+    /// assert_eq!(store.gc_strings()?, 0);
+    ///
+    /// store.remove(quad)?;
+    /// // The IRI is kept because it is the name of a named graph that still exists
+    /// assert_eq!(store.gc_strings()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn gc_strings(&self) -> Result<u64, StorageError> {
+        let mut collection = self.collect_strings();
+        while !collection.step(GC_STRINGS_BATCH_SIZE)? {}
+        Ok(collection.strings_removed())
+    }
+
+    pub(crate) fn metadata(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        self.storage.snapshot().get_metadata(key)
+    }
+
+    pub(crate) fn metadata_with_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        self.storage.snapshot().metadata_with_prefix(prefix)
+    }
+
+    /// Writes a metadata entry, removing it if `value` is `None`, even if the store is read-only or in maintenance mode
+    pub(crate) fn set_metadata(
+        &self,
+        key: &[u8],
+        value: Option<&[u8]>,
+    ) -> Result<(), StorageError> {
+        self.storage.set_metadata(key, value)
+    }
+
+    /// Returns at most `limit` quads of the graph, of all the graphs if `None`, starting at `position` in the storage order,
+    /// each with the position to give to resume the scan from it
+    pub(crate) fn quads_from_position(
+        &self,
+        graph_name: Option<GraphNameRef<'_>>,
+        position: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Quad)>, StorageError> {
+        let reader = self.storage.snapshot();
+        reader
+            .quads_from_position(graph_name.map(EncodedTerm::from).as_ref(), position, limit)?
+            .into_iter()
+            .map(|(position, quad)| Ok((position, reader.decode_quad(&quad)?)))
+            .collect()
+    }
+
+    pub(crate) fn lock_graphs(&self, graphs: LockedGraphs) -> Result<u64, StorageError> {
+        self.storage.lock_graphs(graphs)
+    }
+
+    pub(crate) fn unlock_graphs(&self, lock_owner: u64) {
+        self.storage.unlock_graphs(lock_owner);
+    }
+
+    pub(crate) fn step_batched_update(
+        &self,
+        update: &mut BatchedUpdate,
+        lock_owner: u64,
+        batch_size: usize,
+    ) -> Result<(), EvaluationError> {
+        self.check_not_in_maintenance()?;
+        update.step(&self.storage, lock_owner, batch_size)
+    }
+}
+
+impl fmt::Display for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in self.iter() {
+            writeln!(f, "{} .", t.map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// An object to do operations during a transaction.
+///
+/// See [`Store::transaction`] for a more detailed description.
+pub struct Transaction<'a> {
+    writer: StorageWriter<'a>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/).
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{EvaluationError, QueryResults};
+    ///
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     if let QueryResults::Solutions(solutions) = transaction.query("SELECT ?s WHERE { ?s ?p ?o }")? {
+    ///         for solution in solutions {
+    ///             if let Some(Term::NamedNode(s)) =  solution?.get("s") {
+    ///                 transaction.insert(QuadRef::new(s, vocab::rdf::TYPE, NamedNodeRef::new_unchecked("http://example.com"), GraphNameRef::DefaultGraph))?;
+    ///             }
+    ///         }
+    ///     }
+    ///     Result::<_, EvaluationError>::Ok(())
+    /// })?;
+    /// # Result::<_, EvaluationError>::Ok(())
+    /// ```
+    pub fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.query_opt(query, QueryOptions::default())
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options.
+    ///
+    /// Usage example with a custom function serializing terms to N-Triples:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::{EvaluationError, QueryOptions, QueryResults};
+    ///
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     if let QueryResults::Solutions(solutions) = transaction.query_opt(
+    ///         "SELECT ?s (<http://www.w3.org/ns/formats/N-Triples>(?s) AS ?nt) WHERE { ?s ?p ?o }",
+    ///         QueryOptions::default().with_custom_function(
+    ///             NamedNode::new_unchecked("http://www.w3.org/ns/formats/N-Triples"),
+    ///             |args| args.get(0).map(|t| Literal::from(t.to_string()).into())
+    ///         )
+    ///     )? {
+    ///         for solution in solutions {
+    ///             let solution = solution?;
+    ///             if let (Some(Term::NamedNode(s)), Some(nt)) = (solution.get("s"), solution.get("nt")) {
+    ///                 transaction.insert(QuadRef::new(s, NamedNodeRef::new_unchecked("http://example.com/n-triples-representation"), nt, GraphNameRef::DefaultGraph))?;
+    ///             }
+    ///         }
+    ///     }
+    ///     Result::<_, EvaluationError>::Ok(())
+    /// })?;
+    /// # Result::<_, EvaluationError>::Ok(())
+    /// ```
+    pub fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        if options.as_of().is_some() {
+            return Err(StorageError::Other(
+                "Queries on a past version are not supported inside transactions".into(),
+            )
+            .into());
+        }
+        let (results, _) = evaluate_query(self.writer.reader(), query, options, false)?;
+        results
+    }
+
+    /// Retrieves quads with a filter on each quad component.
+    ///
+    /// Usage example:
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageError, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let a = NamedNodeRef::new("http://example.com/a")?;
+    /// let b = NamedNodeRef::new("http://example.com/b")?;
+    ///
+    /// // Copy all triples about ex:a to triples about ex:b
+    /// store.transaction(|mut transaction| {
+    ///     for q in transaction.quads_for_pattern(Some(a.into()), None, None, None) {
+    ///         let q = q?;
+    ///         transaction.insert(QuadRef::new(b, &q.predicate, &q.object, &q.graph_name))?;
+    ///     }
+    ///     Result::<_, StorageError>::Ok(())
+    /// })?;
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<SubjectRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> QuadIter {
+        let reader = self.writer.reader();
+        QuadIter {
+            iter: reader.quads_for_pattern(
+                subject.map(EncodedTerm::from).as_ref(),
+                predicate.map(EncodedTerm::from).as_ref(),
+                object.map(EncodedTerm::from).as_ref(),
+                graph_name.map(EncodedTerm::from).as_ref(),
+            ),
+            reader,
+        }
+    }
+
+    /// Returns all the quads contained in the store.
+    pub fn iter(&self) -> QuadIter {
+        self.quads_for_pattern(None, None, None, None)
+    }
+
+    /// Checks if this store contains a given quad.
+    pub fn contains<'b>(&self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
+        let quad = self.writer.storage().encode_stored_quad(quad.into());
+        self.writer.reader().contains(&quad)
+    }
+
+    /// Returns the number of quads in the store.
+    pub fn len(&self) -> Result<usize, StorageError> {
+        self.writer.reader().len()
+    }
+
+    /// Returns if the store is empty.
+    pub fn is_empty(&self) -> Result<bool, StorageError> {
+        self.writer.reader().is_empty()
+    }
+
+    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/).
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    /// use oxigraph::sparql::EvaluationError;
+    ///
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     // insertion
+    ///     transaction.update("INSERT DATA { <http://example.com> <http://example.com> <http://example.com> }")?;
+    ///
+    ///     // we inspect the store contents
+    ///     let ex = NamedNodeRef::new_unchecked("http://example.com");
+    ///     assert!(transaction.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    ///     Result::<_, EvaluationError>::Ok(())
+    /// })?;
+    /// # Result::<_, EvaluationError>::Ok(())
+    /// ```
+    pub fn update(
+        &mut self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+    ) -> Result<(), EvaluationError> {
+        self.update_opt(update, UpdateOptions::default())
+    }
+
+    /// Executes a [SPARQL 1.1 update](https://www.w3.org/TR/sparql11-update/) with some options.
+    pub fn update_opt(
+        &mut self,
+        update: impl TryInto<Update, Error = impl Into<EvaluationError>>,
+        options: impl Into<UpdateOptions>,
+    ) -> Result<(), EvaluationError> {
+        evaluate_update(
+            &mut self.writer,
+            &update.try_into().map_err(Into::into)?,
+            &options.into(),
+        )
+    }
+
+    /// Loads a graph file (i.e. triples) into the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    ///
+    /// // insertion
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> .";
+    /// store.transaction(|mut transaction| {
+    ///     transaction.load_graph(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None)
+    /// })?;
+    ///
+    /// // we inspect the store contents
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// # Result::<_,oxigraph::store::LoaderError>::Ok(())
+    /// ```
+    pub fn load_graph<'b>(
+        &mut self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'b>>,
+        base_iri: Option<&str>,
+    ) -> Result<(), LoaderError> {
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        }
+        let to_graph_name = to_graph_name.into();
+        for triple in parser.read_triples(reader)? {
+            self.writer
+                .insert(triple?.as_ref().in_graph(to_graph_name))?;
+        }
+        Ok(())
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::DatasetFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    ///
+    /// // insertion
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> <http://example.com> .";
+    /// store.transaction(|mut transaction| {
+    ///     transaction.load_dataset(file.as_ref(), DatasetFormat::NQuads, None)
+    /// })?;
+    ///
+    /// // we inspect the store contents
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_,oxigraph::store::LoaderError>::Ok(())
+    /// ```
+    pub fn load_dataset(
+        &mut self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), LoaderError> {
+        let mut parser = DatasetParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        }
+        for quad in parser.read_quads(reader)? {
+            self.writer.insert(quad?.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Adds a quad to this store.
+    ///
+    /// Returns `true` if the quad was not already in the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    ///
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(quad)
+    /// })?;
+    /// assert!(store.contains(quad)?);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn insert<'b>(&mut self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
+        self.writer.insert(quad.into())
+    }
+
+    /// Adds a quad to this store that expires once `ttl` has elapsed.
+    ///
+    /// The expired quads are not returned anymore by the queries and the lookups
+    /// and are removed by [`Store::remove_expired_quads`].
+    /// Inserting again the quad replaces its expiration and inserting it with [`insert`](Self::insert) makes it permanent.
+    ///
+    /// Returns `true` if the quad was not already in the store.
+    ///
+    /// The time is read from the canister or from the clock set with [`StorageConfig::with_clock`].
+    /// The [`dataset_version`](Store::dataset_version) is incremented when the quad expires.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageConfig, Store};
+    /// use oxigraph::model::*;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    ///
+    /// let now = Arc::new(AtomicU64::new(0));
+    /// let clock = Arc::clone(&now);
+    /// let store = Store::new_with_config(
+    ///     &StorageConfig::default().with_clock(move || clock.load(Ordering::Relaxed)),
+    /// )?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert_with_ttl(quad, Duration::from_secs(3600))
+    /// })?;
+    /// assert!(store.contains(quad)?);
+    /// let version = store.dataset_version()?;
+    ///
+    /// now.store(3_600_000_000_000, Ordering::Relaxed);
+    /// assert!(!store.contains(quad)?);
+    /// assert_eq!(store.dataset_version()?, version + 1);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn insert_with_ttl<'b>(
+        &mut self,
+        quad: impl Into<QuadRef<'b>>,
+        ttl: Duration,
+    ) -> Result<bool, StorageError> {
+        let expires_at = self
+            .writer
+            .now()
+            .saturating_add(u64::try_from(ttl.as_nanos()).unwrap_or(u64::MAX));
+        self.writer.insert_with_expiration(quad.into(), expires_at)
+    }
+
+    /// Adds a set of quads to this store.
+    pub fn extend<'b>(
+        &mut self,
+        quads: impl IntoIterator<Item = impl Into<QuadRef<'b>>>,
+    ) -> Result<(), StorageError> {
+        for quad in quads {
+            self.writer.insert(quad.into())?;
+        }
+        Ok(())
+    }
+
+    /// Removes a quad from this store.
+    ///
+    /// Returns `true` if the quad was in the store and has been removed.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(quad)?;
+    ///     transaction.remove(quad)
+    /// })?;
+    /// assert!(!store.contains(quad)?);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn remove<'b>(&mut self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
+        self.writer.remove(quad.into())
+    }
+
+    /// Removes a set of quads from this store.
+    ///
+    /// Returns the number of quads that were in the store and have been removed.
+    pub fn remove_all<'b>(
+        &mut self,
+        quads: impl IntoIterator<Item = impl Into<QuadRef<'b>>>,
+    ) -> Result<usize, StorageError> {
+        let mut count = 0;
+        for quad in quads {
+            if self.writer.remove(quad.into())? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Opens a savepoint the transaction writes can be rolled back to without aborting the transaction.
+    ///
+    /// Savepoints can be nested: rolling back to a savepoint or releasing it also closes the savepoints opened after it.
+    /// A savepoint dropped without being rolled back or released keeps its writes.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let ex1 = NamedNodeRef::new_unchecked("http://example.com/1");
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///     let savepoint = transaction.savepoint()?;
+    ///     transaction.insert(QuadRef::new(ex1, ex, ex, GraphNameRef::DefaultGraph))?;
+    ///     transaction.rollback_to_savepoint(savepoint)
+    /// })?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// assert!(!store.contains(QuadRef::new(ex1, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn savepoint(&mut self) -> Result<Savepoint, StorageError> {
+        Ok(Savepoint {
+            inner: self.writer.savepoint()?,
+        })
+    }
+
+    /// Undoes the writes done since the savepoint was opened and closes it.
+    ///
+    /// Fails if the savepoint has already been closed by a rollback to or the release of an outer savepoint.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn rollback_to_savepoint(&mut self, savepoint: Savepoint) -> Result<(), StorageError> {
+        self.writer.rollback_to_savepoint(&savepoint.inner)
+    }
+
+    /// Closes the savepoint keeping the writes done since it was opened.
+    ///
+    /// They are still undone by a rollback to an outer savepoint.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn release_savepoint(&mut self, savepoint: Savepoint) {
+        self.writer.release_savepoint(&savepoint.inner);
+    }
+
+    /// Returns all the store named graphs.
+    pub fn named_graphs(&self) -> GraphNameIter {
+        let reader = self.writer.reader();
+        GraphNameIter {
+            iter: reader.named_graphs(),
+            reader,
+        }
+    }
+
+    /// Checks if the store contains a given graph.
+    pub fn contains_named_graph<'b>(
+        &self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
+    ) -> Result<bool, StorageError> {
+        let graph_name = self
+            .writer
+            .storage()
+            .encode_stored_graph_name(graph_name.into());
+        self.writer.reader().contains_named_graph(&graph_name)
+    }
+
+    /// Inserts a graph into this store.
+    ///
+    /// Returns `true` if the graph was not already in the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::NamedNodeRef;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert_named_graph(ex)
+    /// })?;
+    /// assert_eq!(store.named_graphs().collect::<Result<Vec<_>,_>>()?, vec![ex.into_owned().into()]);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn insert_named_graph<'b>(
+        &mut self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
+    ) -> Result<bool, StorageError> {
+        self.writer.insert_named_graph(graph_name.into())
+    }
+
+    /// Clears a graph from this store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(quad)?;
+    ///     transaction.clear_graph(ex)
+    /// })?;
+    /// assert!(store.is_empty()?);
+    /// assert_eq!(1, store.named_graphs().count());
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn clear_graph<'b>(
+        &mut self,
+        graph_name: impl Into<GraphNameRef<'b>>,
+    ) -> Result<(), StorageError> {
+        self.writer.clear_graph(graph_name.into())
+    }
+
+    /// Removes a graph from this store.
+    ///
+    /// Returns `true` if the graph was in the store and has been removed.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::{NamedNodeRef, QuadRef};
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let quad = QuadRef::new(ex, ex, ex, ex);
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(quad)?;
+    ///     transaction.remove_named_graph(ex)
+    /// })?;
+    /// assert!(store.is_empty()?);
+    /// assert_eq!(0, store.named_graphs().count());
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn remove_named_graph<'b>(
+        &mut self,
+        graph_name: impl Into<NamedOrBlankNodeRef<'b>>,
+    ) -> Result<bool, StorageError> {
+        self.writer.remove_named_graph(graph_name.into())
+    }
+
+    /// Adds the quads of the graph `from` to the graph `to`.
+    ///
+    /// Returns the number of quads that were not already in `to`.
+    /// See [`Store::add_graph`].
+    pub fn add_graph<'b>(
+        &mut self,
+        from: impl Into<GraphNameRef<'b>>,
+        to: impl Into<GraphNameRef<'b>>,
+    ) -> Result<usize, StorageError> {
+        self.writer.add_graph(from.into(), to.into())
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from`.
+    ///
+    /// See [`Store::copy_graph`].
+    pub fn copy_graph<'b>(
+        &mut self,
+        from: impl Into<GraphNameRef<'b>>,
+        to: impl Into<GraphNameRef<'b>>,
+    ) -> Result<(), StorageError> {
+        self.writer.copy_graph(from.into(), to.into())
+    }
+
+    /// Replaces the content of the graph `to` by the quads of the graph `from` and removes `from`.
+    ///
+    /// See [`Store::move_graph`].
+    pub fn move_graph<'b>(
+        &mut self,
+        from: impl Into<GraphNameRef<'b>>,
+        to: impl Into<GraphNameRef<'b>>,
+    ) -> Result<(), StorageError> {
+        self.writer.move_graph(from.into(), to.into())
+    }
+
+    /// Clears the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::model::*;
+    ///
+    /// let ex = NamedNodeRef::new_unchecked("http://example.com");
+    /// let store = Store::new()?;
+    /// store.transaction(|mut transaction| {
+    ///     transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
+    ///     transaction.clear()
+    /// })?;
+    /// assert!(store.is_empty()?);
+    /// # Result::<_,oxigraph::store::StorageError>::Ok(())
+    /// ```
+    pub fn clear(&mut self) -> Result<(), StorageError> {
+        self.writer.clear()
+    }
+
+    /// Removes the subgraph reachable from `root` following a property path.
+    ///
+    /// See [`Store::remove_closure`] for a more detailed description.
+    pub fn remove_closure<'b>(
+        &mut self,
+        root: impl Into<SubjectRef<'b>>,
+        path: &PropertyPathExpression,
+        dry_run: bool,
+    ) -> Result<Vec<Quad>, StorageError> {
+        let root = Term::from(root.into());
+        let mut nodes = self.path_targets(&root, path, false)?;
+        nodes.insert(root);
+        let mut quads = Vec::new();
+        for node in &nodes {
+            if let Some(subject) = term_as_subject(node) {
+                for quad in self.quads_for_pattern(Some(subject), None, None, None) {
+                    quads.push(quad?);
+                }
+            }
+        }
+        if !dry_run {
+            for quad in &quads {
+                self.writer.remove(quad.as_ref())?;
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Rewrites the objects of the quads matching a subject and predicate filter.
+    ///
+    /// See [`Store::replace_objects`] for a more detailed description.
+    pub fn replace_objects<'b>(
+        &mut self,
+        subject: Option<SubjectRef<'b>>,
+        predicate: Option<NamedNodeRef<'b>>,
+        transform: impl Fn(&Term) -> Option<Term>,
+    ) -> Result<usize, StorageError> {
+        let mut replacements = Vec::new();
+        for quad in self.quads_for_pattern(subject, predicate, None, None) {
+            let quad = quad?;
+            if let Some(object) = transform(&quad.object) {
+                if object != quad.object {
+                    replacements.push((quad, object));
+                }
+            }
+        }
+        for (quad, object) in &replacements {
+            self.writer.remove(quad.as_ref())?;
+            self.writer.insert(QuadRef::new(
+                &quad.subject,
+                &quad.predicate,
+                object,
+                &quad.graph_name,
+            ))?;
+        }
+        Ok(replacements.len())
+    }
+
+    /// Returns the terms reachable from `start` using `path` (or its inverse if `reverse` is set).
+    fn path_targets(
+        &self,
+        start: &Term,
+        path: &PropertyPathExpression,
+        reverse: bool,
+    ) -> Result<HashSet<Term>, StorageError> {
+        let mut output = HashSet::new();
+        self.add_path_targets(start, path, reverse, &mut output)?;
+        Ok(output)
+    }
+
+    fn add_path_targets(
+        &self,
+        start: &Term,
+        path: &PropertyPathExpression,
+        reverse: bool,
+        output: &mut HashSet<Term>,
+    ) -> Result<(), StorageError> {
+        match path {
+            PropertyPathExpression::NamedNode(p) => {
+                if reverse {
+                    for quad in
+                        self.quads_for_pattern(None, Some(p.as_ref()), Some(start.as_ref()), None)
+                    {
+                        output.insert(quad?.subject.into());
+                    }
+                } else if let Some(subject) = term_as_subject(start) {
+                    for quad in self.quads_for_pattern(Some(subject), Some(p.as_ref()), None, None)
+                    {
+                        output.insert(quad?.object);
+                    }
+                }
+            }
+            PropertyPathExpression::Reverse(p) => {
+                self.add_path_targets(start, p, !reverse, output)?;
+            }
+            PropertyPathExpression::Sequence(a, b) => {
+                let (first, second) = if reverse { (b, a) } else { (a, b) };
+                for middle in self.path_targets(start, first, reverse)? {
+                    self.add_path_targets(&middle, second, reverse, output)?;
+                }
+            }
+            PropertyPathExpression::Alternative(a, b) => {
+                self.add_path_targets(start, a, reverse, output)?;
+                self.add_path_targets(start, b, reverse, output)?;
+            }
+            PropertyPathExpression::ZeroOrMore(p) => {
+                output.insert(start.clone());
+                output.extend(self.transitive_path_targets(start, p, reverse)?);
+            }
+            PropertyPathExpression::OneOrMore(p) => {
+                output.extend(self.transitive_path_targets(start, p, reverse)?);
+            }
+            PropertyPathExpression::ZeroOrOne(p) => {
+                output.insert(start.clone());
+                self.add_path_targets(start, p, reverse, output)?;
+            }
+            PropertyPathExpression::NegatedPropertySet(ps) => {
+                if reverse {
+                    for quad in self.quads_for_pattern(None, None, Some(start.as_ref()), None) {
+                        let quad = quad?;
+                        if !ps.contains(&quad.predicate) {
+                            output.insert(quad.subject.into());
+                        }
+                    }
+                } else if let Some(subject) = term_as_subject(start) {
+                    for quad in self.quads_for_pattern(Some(subject), None, None, None) {
+                        let quad = quad?;
+                        if !ps.contains(&quad.predicate) {
+                            output.insert(quad.object);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the terms reachable with one or more steps of `path`, visiting each node only once.
+    fn transitive_path_targets(
+        &self,
+        start: &Term,
+        path: &PropertyPathExpression,
+        reverse: bool,
+    ) -> Result<HashSet<Term>, StorageError> {
+        let mut visited = HashSet::new();
+        let mut todo = vec![start.clone()];
+        while let Some(current) = todo.pop() {
+            for next in self.path_targets(&current, path, reverse)? {
+                if visited.insert(next.clone()) {
+                    todo.push(next);
+                }
+            }
+        }
+        Ok(visited)
+    }
+
+    pub(crate) fn metadata(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        self.writer.reader().get_metadata(key)
+    }
+
+    pub(crate) fn insert_metadata(&mut self, key: &[u8], value: &[u8]) -> Result<(), StorageError> {
+        self.writer.insert_metadata(key, value)
+    }
+
+    pub(crate) fn remove_metadata(&mut self, key: &[u8]) -> Result<(), StorageError> {
+        self.writer.remove_metadata(key)
+    }
+}
+
+fn term_as_subject(term: &Term) -> Option<SubjectRef<'_>> {
+    match term {
+        Term::NamedNode(node) => Some(node.into()),
+        Term::BlankNode(node) => Some(node.into()),
+        Term::Literal(_) => None,
+        Term::Triple(triple) => Some(SubjectRef::Triple(triple)),
+    }
+}
+
+/// A savepoint of a [`Transaction`] opened by [`Transaction::savepoint`].
+#[must_use]
+pub struct Savepoint {
+    inner: StorageSavepoint,
+}
+
+/// A transaction started by [`Store::begin`], its writes being only applied to the store by [`commit`](Self::commit).
+///
+/// The writes are buffered in memory.
+/// The reads see the current content of the store with the buffered writes applied:
+/// unlike in a [`Transaction`], the writes committed by other calls while the transaction is open are visible.
+/// The quads read from the store are recorded and [`commit`](Self::commit) fails with [`StorageError::Conflict`]
+/// if another commit has changed one of them, so the transaction can be retried.
+/// Dropping the transaction without committing it discards its writes.
+#[must_use]
+pub struct OpenTransaction {
+    store: Store,
+    /// The buffered writes in the order of the first write of each quad, `true` for the insertions and `false` for the removals
+    writes: Vec<(Quad, bool)>,
+    /// The position of each quad in `writes`
+    positions: HashMap<Quad, usize>,
+    /// The quads read from the store, with their presence when they have been read first
+    reads: RefCell<HashMap<Quad, bool>>,
+    /// The commit version of the store at the first read
+    read_version: Cell<Option<u64>>,
+}
+
+impl OpenTransaction {
+    /// Buffers the insertion of a quad.
+    ///
+    /// Returns `true` if the quad was not already in the store or inserted by the transaction.
+    pub fn insert<'a>(&mut self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
+        let quad = quad.into();
+        let present = self.contains(quad)?;
+        self.buffer(quad.into_owned(), true);
+        Ok(!present)
+    }
+
+    /// Buffers the insertion of a set of quads.
+    pub fn extend<'a>(
+        &mut self,
+        quads: impl IntoIterator<Item = impl Into<QuadRef<'a>>>,
+    ) -> Result<(), StorageError> {
+        for quad in quads {
+            self.insert(quad)?;
+        }
+        Ok(())
+    }
+
+    /// Buffers the removal of a quad.
+    ///
+    /// Returns `true` if the quad was in the store or inserted by the transaction.
+    pub fn remove<'a>(&mut self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
+        let quad = quad.into();
+        let present = self.contains(quad)?;
+        self.buffer(quad.into_owned(), false);
+        Ok(present)
+    }
+
+    /// Checks if the store contains the quad once the buffered writes are applied.
+    pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
+        let quad = quad.into();
+        if let Some(position) = self.positions.get(&quad.into_owned()) {
+            Ok(self.writes[*position].1)
+        } else {
+            if self.read_version.get().is_none() {
+                self.read_version.set(Some(self.store.commit_version()?));
+            }
+            let present = self.store.contains(quad)?;
+            self.reads
+                .borrow_mut()
+                .entry(quad.into_owned())
+                .or_insert(present);
+            Ok(present)
+        }
+    }
+
+    /// The number of buffered writes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns `true` if no write is buffered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Applies the buffered writes to the store in a single [transaction](Store::transaction).
+    ///
+    /// The quads are written in the order of their first buffered write.
+    /// If one of the writes fails, the transaction is rolled back and none of them is kept.
+    ///
+    /// It fails with [`StorageError::Conflict`] without writing anything if a quad read by the transaction
+    /// has been inserted or removed by another commit since it was read:
+    /// ```
+    /// use oxigraph::store::{StorageError, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let quad = QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph);
+    ///
+    /// let mut transaction = store.begin();
+    /// if !transaction.contains(quad)? {
+    ///     transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// }
+    /// store.insert(quad)?; // Concurrent write
+    /// assert!(matches!(transaction.commit(), Err(StorageError::Conflict { .. })));
+    /// assert!(!store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{StorageConfig, StorageError, Store};
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_max_quads(1))?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let mut transaction = store.begin();
+    /// transaction.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+    /// transaction.insert(QuadRef::new(ex, ex, ex, ex))?;
+    /// assert!(matches!(transaction.commit(), Err(StorageError::QuotaExceeded { .. })));
+    /// assert!(store.is_empty()?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn commit(self) -> Result<(), StorageError> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        let reads = self.reads.borrow();
+        self.store.transaction(|mut transaction| {
+            for (quad, present) in reads.iter() {
+                if transaction.contains(quad)? != *present {
+                    return Err(StorageError::Conflict {
+                        expected: self.read_version.get().unwrap_or_default(),
+                        actual: transaction.writer.reader().commit_version()?,
+                    });
+                }
+            }
+            for (quad, inserted) in &self.writes {
+                if *inserted {
+                    transaction.insert(quad)?;
+                } else {
+                    transaction.remove(quad)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Discards the buffered writes.
+    #[inline]
+    pub fn rollback(self) {
+        drop(self);
+    }
+
+    fn buffer(&mut self, quad: Quad, inserted: bool) {
+        if let Some(position) = self.positions.get(&quad) {
+            self.writes[*position].1 = inserted;
+        } else {
+            self.positions.insert(quad.clone(), self.writes.len());
+            self.writes.push((quad, inserted));
+        }
+    }
+}
+
+/// A read-only view of a [`Store`] as it was at a past [dataset version](Store::dataset_version).
+///
+/// It is returned by [`Store::snapshot_at`].
+/// Its methods fail with [`StorageError::VersionNotAvailable`] once the version is dropped from the history.
+#[derive(Clone)]
+pub struct StoreSnapshot {
+    store: Store,
+    version: u64,
+}
+
+impl StoreSnapshot {
+    /// The dataset version of the snapshot.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) on the snapshot.
+    pub fn query(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.query_opt(query, QueryOptions::default())
+    }
+
+    /// Executes a [SPARQL 1.1 query](https://www.w3.org/TR/sparql11-query/) with some options on the snapshot.
+    pub fn query_opt(
+        &self,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        options: QueryOptions,
+    ) -> Result<QueryResults, EvaluationError> {
+        self.store
+            .query_opt(query, options.with_as_of(self.version))
+    }
+
+    /// Retrieves the quads of the snapshot with a filter on each quad component.
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<SubjectRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Result<QuadIter, StorageError> {
+        let reader = self.store.storage.snapshot_at(self.version)?;
+        Ok(QuadIter {
+            iter: reader.quads_for_pattern(
+                subject.map(EncodedTerm::from).as_ref(),
+                predicate.map(EncodedTerm::from).as_ref(),
+                object.map(EncodedTerm::from).as_ref(),
+                graph_name.map(EncodedTerm::from).as_ref(),
+            ),
+            reader,
+        })
+    }
+
+    /// Returns all the quads of the snapshot.
+    pub fn iter(&self) -> Result<QuadIter, StorageError> {
+        self.quads_for_pattern(None, None, None, None)
+    }
+
+    /// Checks if the snapshot contains the given quad.
+    pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
+        let quad = self.store.storage.encode_stored_quad(quad.into());
+        self.store
+            .storage
+            .snapshot_at(self.version)?
+            .contains(&quad)
+    }
+
+    /// Returns the number of quads in the snapshot.
+    pub fn len(&self) -> Result<usize, StorageError> {
+        self.store.storage.snapshot_at(self.version)?.len()
+    }
+
+    /// Returns if the snapshot is empty.
+    pub fn is_empty(&self) -> Result<bool, StorageError> {
+        self.store.storage.snapshot_at(self.version)?.is_empty()
+    }
+}
+
+/// An iterator returning the quads contained in a [`Store`].
+pub struct QuadIter {
+    iter: ChainedDecodingQuadIterator,
+    reader: StorageReader,
+}
+
+impl Iterator for QuadIter {
+    type Item = Result<Quad, StorageError>;
+
+    fn next(&mut self) -> Option<Result<Quad, StorageError>> {
+        Some(match self.iter.next()? {
+            Ok(quad) => self.reader.decode_quad(&quad),
+            Err(error) => Err(error),
+        })
+    }
+}
+
+/// An iterator returning the entries of the change log.
+///
+/// See [`Store::changes_since`].
+pub struct ChangeLogIter {
+    iter: ChangeLogIterator,
+    reader: StorageReader,
+}
+
+impl Iterator for ChangeLogIter {
+    type Item = Result<ChangeLogEntry, StorageError>;
+
+    fn next(&mut self) -> Option<Result<ChangeLogEntry, StorageError>> {
+        Some(self.iter.next()?.and_then(|(sequence, change)| {
+            Ok(ChangeLogEntry {
+                sequence,
+                change: match change {
+                    EncodedChange::InsertedQuad(quad) => {
+                        LoggedChange::Inserted(self.reader.decode_quad(&quad)?)
+                    }
+                    EncodedChange::RemovedQuad(quad) => {
+                        LoggedChange::Removed(self.reader.decode_quad(&quad)?)
+                    }
+                    EncodedChange::CreatedGraph(graph_name) => LoggedChange::GraphCreated(
+                        self.reader.decode_named_or_blank_node(&graph_name)?,
+                    ),
+                    EncodedChange::DroppedGraph(graph_name) => LoggedChange::GraphDropped(
+                        self.reader.decode_named_or_blank_node(&graph_name)?,
+                    ),
+                },
+            })
+        }))
+    }
+}
+
+/// An entry of the change log.
+///
+/// See [`Store::changes_since`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub struct ChangeLogEntry {
+    sequence: u64,
+    change: LoggedChange,
+}
+
+impl ChangeLogEntry {
+    /// The sequence number of the entry, the first entry having the number 1.
+    #[inline]
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The logged change.
+    #[inline]
+    pub fn change(&self) -> &LoggedChange {
+        &self.change
+    }
+}
+
+/// A change recorded in the change log.
+///
+/// See [`Store::changes_since`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub enum LoggedChange {
+    /// The quad has been inserted.
+    Inserted(Quad),
+    /// The quad has been removed.
+    Removed(Quad),
+    /// The named graph has been created.
+    GraphCreated(NamedOrBlankNode),
+    /// The named graph has been dropped, after the removal of its quads.
+    GraphDropped(NamedOrBlankNode),
+}
+
+/// How the writes are handled while the store is in maintenance mode.
+///
+/// See [`Store::begin_maintenance`].
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum MaintenanceMode {
+    /// All the writes fail with [`StorageError::UnderMaintenance`].
+    Reject {
+        /// The delay after which the writes should be retried.
+        retry_after: Duration,
+    },
+    /// The quad insertions and removals and the SPARQL updates without options are queued
+    /// and applied when the maintenance ends.
+    ///
+    /// They always succeed when queued, the insertions and removals returning `false` because their outcome is not known yet.
+    /// The other writes and the writes arriving when the queue is full fail with [`StorageError::UnderMaintenance`]
+    /// and the writes into a [read-only](Store::set_read_only) store fail with [`StorageError::ReadOnly`] without being queued.
+    Queue {
+        /// The maximal number of queued writes.
+        capacity: usize,
+        /// The delay after which the rejected writes should be retried.
+        retry_after: Duration,
+    },
+}
+
+impl MaintenanceMode {
+    fn retry_after(self) -> Duration {
+        match self {
+            Self::Reject { retry_after } | Self::Queue { retry_after, .. } => retry_after,
+        }
+    }
+}
+
+/// The order in which [`Store::quads_for_pattern_ordered`] returns the quads.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
+pub enum Order {
+    /// The storage key order.
+    Asc,
+    /// The reverse of the storage key order.
+    Desc,
+}
+
+/// A quad pattern, each `None` component matching any term.
+///
+/// See [`Store::subscribe`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash, Default)]
+pub struct QuadPattern {
+    subject: Option<Subject>,
+    predicate: Option<NamedNode>,
+    object: Option<Term>,
+    graph_name: Option<GraphName>,
+}
+
+impl QuadPattern {
+    /// Builds a new pattern.
+    #[inline]
+    pub fn new(
+        subject: Option<SubjectRef<'_>>,
+        predicate: Option<NamedNodeRef<'_>>,
+        object: Option<TermRef<'_>>,
+        graph_name: Option<GraphNameRef<'_>>,
+    ) -> Self {
+        Self {
+            subject: subject.map(SubjectRef::into_owned),
+            predicate: predicate.map(NamedNodeRef::into_owned),
+            object: object.map(TermRef::into_owned),
+            graph_name: graph_name.map(GraphNameRef::into_owned),
+        }
+    }
+
+    /// Checks if a quad matches the pattern.
+    #[inline]
+    pub fn matches<'a>(&self, quad: impl Into<QuadRef<'a>>) -> bool {
+        let quad = quad.into();
+        self.subject
+            .as_ref()
+            .map_or(true, |s| s.as_ref() == quad.subject)
+            && self
+                .predicate
+                .as_ref()
+                .map_or(true, |p| p.as_ref() == quad.predicate)
+            && self
+                .object
+                .as_ref()
+                .map_or(true, |o| o.as_ref() == quad.object)
+            && self
+                .graph_name
+                .as_ref()
+                .map_or(true, |g| g.as_ref() == quad.graph_name)
+    }
+}
+
+/// A redaction layer hiding quads from the reads of a [`Store`], see [`Store::set_read_filter`].
+///
+/// The quads matching one of the patterns are given to the callback and are hidden if it returns `true`.
+#[derive(Clone)]
+pub struct ReadFilter {
+    patterns: Vec<QuadPattern>,
+    hide: Arc<dyn Fn(QuadRef<'_>) -> bool + Send + Sync>,
+}
+
+impl ReadFilter {
+    /// Builds a filter hiding the quads matching one of the patterns for which `hide` returns `true`.
+    ///
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{QuadPattern, ReadFilter, Store};
+    ///
+    /// let store = Store::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// store.insert(QuadRef::new(ex, ex, LiteralRef::new_simple_literal("public"), GraphNameRef::DefaultGraph))?;
+    /// store.insert(QuadRef::new(ex, ex, LiteralRef::new_simple_literal("private:key"), GraphNameRef::DefaultGraph))?;
+    /// store.set_read_filter(Some(ReadFilter::new(
+    ///     [QuadPattern::new(None, Some(ex), None, None)],
+    ///     |quad| matches!(quad.object, TermRef::Literal(value) if value.value().starts_with("private:")),
+    /// )))?;
+    /// assert_eq!(store.iter().count(), 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    pub fn new(
+        patterns: impl IntoIterator<Item = QuadPattern>,
+        hide: impl Fn(QuadRef<'_>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            patterns: patterns.into_iter().collect(),
+            hide: Arc::new(hide),
+        }
+    }
+
+    /// Builds a filter hiding all the quads matching one of the patterns.
+    #[inline]
+    pub fn hiding(patterns: impl IntoIterator<Item = QuadPattern>) -> Self {
+        Self::new(patterns, |_| true)
+    }
+}
+
+/// A change committed into the store.
+///
+/// See [`Store::subscribe`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub enum QuadChange {
+    /// The quad has been inserted.
+    Inserted(Quad),
+    /// The quad has been removed.
+    Removed(Quad),
+}
+
+impl QuadChange {
+    /// The inserted or removed quad.
+    #[inline]
+    pub fn quad(&self) -> &Quad {
+        match self {
+            Self::Inserted(quad) | Self::Removed(quad) => quad,
+        }
+    }
+}
+
+/// The identifier of a subscription registered with [`Store::subscribe`], [`Store::subscribe_queued`] or [`Store::subscribe_canister`].
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct SubscriptionId(u64);
+
+impl From<SubscriptionId> for u64 {
+    #[inline]
+    fn from(id: SubscriptionId) -> Self {
+        id.0
+    }
+}
+
+impl From<u64> for SubscriptionId {
+    #[inline]
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    next_id: u64,
+    entries: BTreeMap<SubscriptionId, Subscription>,
+}
+
+struct Subscription {
+    pattern: QuadPattern,
+    target: SubscriptionTarget,
+}
+
+enum SubscriptionTarget {
+    Callback(Arc<dyn Fn(&[QuadChange]) + Send + Sync>),
+    Queue {
+        changes: VecDeque<QuadChange>,
+        capacity: usize,
+    },
+    Canister {
+        canister: Principal,
+        method: String,
+    },
+}
+
+fn notify_subscriptions(
+    subscriptions: &RwLock<Subscriptions>,
+    reader: &StorageReader,
+    changes: &[(EncodedQuad, bool)],
+) {
+    let changes = changes
+        .iter()
+        .filter_map(|(quad, inserted)| {
+            // The strings of the removed quads are kept, so decoding only fails on corruption
+            let quad = reader.decode_quad(quad).ok()?;
+            Some(if *inserted {
+                QuadChange::Inserted(quad)
+            } else {
+                QuadChange::Removed(quad)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut callbacks = Vec::new();
+    let mut notifications = Vec::new();
+    for (id, subscription) in &mut subscriptions.write().unwrap().entries {
+        let matching = changes
+            .iter()
+            .filter(|change| subscription.pattern.matches(change.quad()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            continue;
+        }
+        match &mut subscription.target {
+            SubscriptionTarget::Callback(callback) => {
+                callbacks.push((Arc::clone(callback), matching));
+            }
+            SubscriptionTarget::Queue { changes, capacity } => {
+                for change in matching {
+                    if changes.len() >= *capacity {
+                        changes.pop_front();
+                    }
+                    changes.push_back(change);
+                }
+            }
+            SubscriptionTarget::Canister { canister, method } => {
+                notifications.push((
+                    *canister,
+                    method.clone(),
+                    ChangeNotification::new(*id, &matching),
+                ));
+            }
+        }
+    }
+    for (canister, method, notification) in notifications {
+        notify_canister(canister, &method, notification);
+    }
+    // The callbacks are called without holding the lock to allow them to use the store
+    for (callback, changes) in callbacks {
+        callback(&changes);
+    }
+}
+
+/// The identifier of a trigger registered with [`Store::create_trigger`].
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Hash)]
+pub struct TriggerId(u64);
+
+impl From<TriggerId> for u64 {
+    #[inline]
+    fn from(id: TriggerId) -> Self {
+        id.0
+    }
+}
+
+impl From<u64> for TriggerId {
+    #[inline]
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+/// The action run by a trigger registered with [`Store::create_trigger`].
+#[derive(Clone)]
+pub enum TriggerAction {
+    /// Runs a SPARQL update.
+    ///
+    /// The `WHERE` clauses of its DELETE/INSERT operations are joined with a `VALUES` clause
+    /// binding `?subject`, `?predicate`, `?object`, `?graph` and `?inserted` to each matching change.
+    /// `?graph` is unbound for the default graph and the changes containing blank nodes are not bound.
+    Update(Update),
+    /// Inserts the quads built from the templates for each matching change.
+    ///
+    /// The instances that are not valid quads (e.g. a literal as subject) are skipped.
+    Insert(Vec<QuadTemplate>),
+    /// Calls a function with the matching changes and the committing transaction.
+    ///
+    /// The function must write through the given transaction and not through the [`Store`].
+    Callback(
+        Arc<
+            dyn Fn(&[QuadChange], &mut Transaction<'_>) -> Result<(), EvaluationError>
+                + Send
+                + Sync,
+        >,
+    ),
+}
+
+/// A quad to insert by [`TriggerAction::Insert`] built from the terms of a changed quad.
+///
+/// ```
+/// use oxigraph::model::*;
+/// use oxigraph::store::{QuadPattern, QuadTemplate, Store, TemplateTerm, TriggerAction};
+///
+/// let store = Store::new()?;
+/// let ex = NamedNodeRef::new("http://example.com")?;
+/// let dirty = NamedNodeRef::new("http://example.com/dirty")?;
+/// store.create_trigger(
+///     QuadPattern::default(),
+///     TriggerAction::Insert(vec![QuadTemplate::new(
+///         TemplateTerm::Subject,
+///         TemplateTerm::Constant(dirty.into()),
+///         TemplateTerm::Constant(Literal::from(true).into()),
+///         TemplateTerm::GraphName,
+///     )]),
+/// );
+/// store.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?;
+/// assert!(store.contains(QuadRef::new(ex, dirty, &Literal::from(true), GraphNameRef::DefaultGraph))?);
+/// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+/// ```
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub struct QuadTemplate {
+    subject: TemplateTerm,
+    predicate: TemplateTerm,
+    object: TemplateTerm,
+    graph_name: TemplateTerm,
+}
+
+impl QuadTemplate {
+    /// Builds a new template.
+    #[inline]
+    pub fn new(
+        subject: TemplateTerm,
+        predicate: TemplateTerm,
+        object: TemplateTerm,
+        graph_name: TemplateTerm,
+    ) -> Self {
+        Self {
+            subject,
+            predicate,
+            object,
+            graph_name,
+        }
+    }
+
+    fn instantiate(&self, quad: &Quad) -> Option<Quad> {
+        Some(Quad::new(
+            match self.subject.instantiate(quad)? {
+                Term::NamedNode(node) => Subject::from(node),
+                Term::BlankNode(node) => node.into(),
+                Term::Triple(triple) => Subject::Triple(triple),
+                Term::Literal(_) => return None,
+            },
+            match self.predicate.instantiate(quad)? {
+                Term::NamedNode(node) => node,
+                _ => return None,
+            },
+            self.object.instantiate(quad)?,
+            match self.graph_name.instantiate(quad) {
+                Some(Term::NamedNode(node)) => GraphName::from(node),
+                Some(Term::BlankNode(node)) => node.into(),
+                Some(Term::Literal(_) | Term::Triple(_)) => return None,
+                None => GraphName::DefaultGraph,
+            },
+        ))
+    }
+}
+
+/// A term of a [`QuadTemplate`].
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub enum TemplateTerm {
+    /// A fixed term.
+    Constant(Term),
+    /// The default graph, only valid as graph name.
+    DefaultGraph,
+    /// The subject of the changed quad.
+    Subject,
+    /// The predicate of the changed quad.
+    Predicate,
+    /// The object of the changed quad.
+    Object,
+    /// The graph name of the changed quad.
+    GraphName,
+}
+
+impl TemplateTerm {
+    /// The term, `None` being the default graph
+    fn instantiate(&self, quad: &Quad) -> Option<Term> {
+        match self {
+            Self::Constant(term) => Some(term.clone()),
+            Self::DefaultGraph => None,
+            Self::Subject => Some(quad.subject.clone().into()),
+            Self::Predicate => Some(quad.predicate.clone().into()),
+            Self::Object => Some(quad.object.clone()),
+            Self::GraphName => match &quad.graph_name {
+                GraphName::NamedNode(node) => Some(node.clone().into()),
+                GraphName::BlankNode(node) => Some(node.clone().into()),
+                GraphName::DefaultGraph => None,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+struct Triggers {
+    next_id: u64,
+    entries: BTreeMap<TriggerId, Trigger>,
+}
+
+#[derive(Clone)]
+struct Trigger {
+    pattern: QuadPattern,
+    action: TriggerAction,
+}
+
+fn fire_triggers(
+    triggers: &RwLock<Triggers>,
+    writer: StorageWriter<'_>,
+    changes: &[(EncodedQuad, bool)],
+) -> Result<(), StorageError> {
+    let reader = writer.reader();
+    let changes = changes
+        .iter()
+        .map(|(quad, inserted)| {
+            let quad = reader.decode_quad(quad)?;
+            Ok(if *inserted {
+                QuadChange::Inserted(quad)
+            } else {
+                QuadChange::Removed(quad)
+            })
+        })
+        .collect::<Result<Vec<_>, StorageError>>()?;
+    // The actions are run without holding the lock to allow them to manage the triggers
+    let triggers = triggers
+        .read()
+        .unwrap()
+        .entries
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut transaction = Transaction { writer };
+    for trigger in triggers {
+        let matching = changes
+            .iter()
+            .filter(|change| trigger.pattern.matches(change.quad()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            continue;
+        }
+        match trigger.action {
+            TriggerAction::Update(update) => {
+                let variables = ["subject", "predicate", "object", "graph", "inserted"]
+                    .map(Variable::new_unchecked);
+                let rows = matching
+                    .iter()
+                    .map(|change| {
+                        let quad = change.quad();
+                        vec![
+                            Some(quad.subject.clone().into()),
+                            Some(quad.predicate.clone().into()),
+                            Some(quad.object.clone()),
+                            match &quad.graph_name {
+                                GraphName::NamedNode(node) => Some(node.clone().into()),
+                                GraphName::BlankNode(node) => Some(node.clone().into()),
+                                GraphName::DefaultGraph => None,
+                            },
+                            Some(Literal::from(matches!(change, QuadChange::Inserted(_))).into()),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                transaction
+                    .update(update.with_values(&variables, &rows))
+                    .map_err(trigger_error)?;
+            }
+            TriggerAction::Insert(templates) => {
+                for change in &matching {
+                    for template in &templates {
+                        if let Some(quad) = template.instantiate(change.quad()) {
+                            transaction.insert(&quad)?;
+                        }
+                    }
+                }
+            }
+            TriggerAction::Callback(callback) => {
+                callback(&matching, &mut transaction).map_err(trigger_error)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn trigger_error(error: EvaluationError) -> StorageError {
+    match error {
+        EvaluationError::Storage(error) => error,
+        error => StorageError::Other(Box::new(error)),
+    }
+}
+
+/// An incremental rebuild of the secondary indexes of a [`Store`].
+///
+/// See [`Store::rebuild_indexes`].
+pub struct IndexRebuild {
+    store: Store,
+    state: IndexRebuildState,
+}
+
+impl IndexRebuild {
+    /// Rebuilds a bounded part of the indexes, touching at most `batch_size` index entries.
+    ///
+    /// It works even if the store is in maintenance mode.
+    /// Returns `true` when the rebuild is finished.
+    pub fn step(&mut self, batch_size: usize) -> Result<bool, StorageError> {
+        self.store
+            .storage
+            .rebuild_indexes_step(&mut self.state, batch_size)
+    }
+
+    /// Returns if the rebuild is finished.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// The number of quads indexed again so far.
+    #[inline]
+    pub fn quads_processed(&self) -> u64 {
+        self.state.quads_processed()
+    }
+}
+
+/// An incremental collection of the unused strings of a [`Store`] dictionary.
+///
+/// See [`Store::collect_strings`].
+pub struct StringCollection {
+    store: Store,
+    state: StringCollectionState,
+}
+
+impl StringCollection {
+    /// Does a bounded part of the collection, touching at most `batch_size` keys.
+    ///
+    /// Returns `true` when the collection is finished.
+    pub fn step(&mut self, batch_size: usize) -> Result<bool, StorageError> {
+        self.store
+            .storage
+            .collect_strings_step(&mut self.state, batch_size)
+    }
+
+    /// Returns if the collection is finished.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// The number of strings removed so far.
+    #[inline]
+    pub fn strings_removed(&self) -> u64 {
+        self.state.strings_removed()
+    }
+}
+
+/// An incremental computation of a [`DatasetSummary`] of a [`Store`].
+///
+/// See [`Store::export_summary_incrementally`].
+pub struct SummaryExport {
+    store: Store,
+    state: QuadScanState,
+    builder: Option<SummaryBuilder>,
+    summary: Option<DatasetSummary>,
+}
+
+impl SummaryExport {
+    /// Adds at most `batch_size` quads to the summary.
+    ///
+    /// Returns `true` when the summary is finished.
+    pub fn step(&mut self, batch_size: usize) -> Result<bool, StorageError> {
+        let builder = if let Some(builder) = &mut self.builder {
+            builder
+        } else {
+            return Ok(true);
+        };
+        let storage = &self.store.storage;
+        let quads = storage.scan_quads_step(&mut self.state, batch_size)?;
+        let reader = storage.snapshot();
+        for quad in &quads {
+            builder.add(reader.decode_quad(quad)?);
+        }
+        if self.state.is_finished() {
+            self.summary = self.builder.take().map(SummaryBuilder::finish);
+        }
+        Ok(self.is_finished())
+    }
+
+    /// Returns if the summary is finished.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.summary.is_some()
+    }
+
+    /// The summary, once it is finished.
+    #[inline]
+    pub fn summary(&self) -> Option<&DatasetSummary> {
+        self.summary.as_ref()
+    }
+
+    /// Returns the summary, once it is finished.
+    #[inline]
+    pub fn into_summary(self) -> Option<DatasetSummary> {
+        self.summary
+    }
+}
+
+/// Options of [`Store::load_graph_opt`] and [`Store::load_dataset_opt`].
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct LoadOptions {
+    batch_size: usize,
+    atomic: bool,
+    compression: Option<Compression>,
+}
+
+impl Default for LoadOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BULK_LOAD_BATCH_SIZE,
+            atomic: true,
+            compression: None,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Sets the number of quads parsed before being inserted at once, 10000 by default.
+    ///
+    /// The quads of a batch are kept in memory until they are inserted.
+    #[inline]
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Inserts each batch in its own transaction, like the [`BulkLoader`] does.
+    ///
+    /// By default all the batches are inserted in a single transaction and none is kept if the parsing fails.
+    /// Without atomicity the batches inserted before an error are kept.
+    #[inline]
+    #[must_use]
+    pub fn without_atomicity(mut self) -> Self {
+        self.atomic = false;
+        self
+    }
+
+    /// Decompresses the file with the given compression format.
+    ///
+    /// By default the gzip and Zstandard files are detected from their first bytes.
+    /// The decompression requires the `compression` feature.
+    ///
+    /// ```
+    /// # #[cfg(feature = "compression")] {
+    /// use oxigraph::io::{Compression, DatasetFormat, DatasetSerializer};
+    /// use oxigraph::model::*;
+    /// use oxigraph::store::{LoadOptions, Store};
+    ///
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let mut file = Vec::new();
+    /// let mut writer = DatasetSerializer::from_format(DatasetFormat::NQuads)
+    ///     .with_compression(Compression::Gzip)
+    ///     .quad_writer(&mut file)?;
+    /// writer.write(QuadRef::new(ex, ex, ex, ex))?;
+    /// writer.finish()?;
+    ///
+    /// let store = Store::new()?;
+    /// let options = LoadOptions::default().with_compression(Compression::Gzip);
+    /// store.load_dataset_opt(file.as_slice(), DatasetFormat::NQuads, None, options)?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, ex))?);
+    /// # }
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+}
+
+/// A bulk loader allowing to load a lot of data quickly into the store.
+///
+/// The quads are inserted by batches: the dictionary and index entries of each batch are sorted
+/// and merged at once into the store, which is much faster than inserting the quads one by one.
+///
+/// Warning: the load is not atomic, each batch is inserted by its own transaction.
+/// If the load fails, the batches already inserted are kept.
+///
+/// See [`Store::bulk_loader`] for an example.
+pub struct BulkLoader {
+    store: Store,
+    batch_size: usize,
+    on_progress: Option<Box<dyn Fn(u64)>>,
+    #[allow(clippy::type_complexity)]
+    on_parse_error: Option<Box<dyn Fn(ParseError) -> Result<(), ParseError>>>,
+}
+
+impl BulkLoader {
+    /// Sets the number of quads inserted by each transaction, 10000 by default.
+    ///
+    /// The quads of a batch are kept in memory until they are inserted.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Adds a `callback` evaluated after each inserted batch with the number of quads read so far.
+    #[must_use]
+    pub fn on_progress(mut self, callback: impl Fn(u64) + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Adds a `callback` catching all parse errors and choosing if the parsing should continue
+    /// by returning `Ok` or fail by returning `Err`.
+    ///
+    /// By default the parsing fails.
+    #[must_use]
+    pub fn on_parse_error(
+        mut self,
+        callback: impl Fn(ParseError) -> Result<(), ParseError> + 'static,
+    ) -> Self {
+        self.on_parse_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Loads a dataset file (i.e. quads) into the store.
+    ///
+    /// See [`Store::bulk_loader`] for an example.
+    pub fn load_dataset(
+        &self,
+        reader: impl BufRead,
+        format: DatasetFormat,
+        base_iri: Option<&str>,
+    ) -> Result<(), LoaderError> {
+        let mut parser = DatasetParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        }
+        self.load_ok_quads(
+            parser
+                .read_quads(reader)?
+                .filter_map(|r| self.handle_parse_error(r)),
+        )
+    }
+
+    /// Loads a graph file (i.e. triples) into the store.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::Store;
+    /// use oxigraph::io::GraphFormat;
+    /// use oxigraph::model::*;
+    ///
+    /// let store = Store::new()?;
+    ///
+    /// // insertion
+    /// let file = b"<http://example.com> <http://example.com> <http://example.com> .";
+    /// store.bulk_loader().load_graph(file.as_ref(), GraphFormat::NTriples, GraphNameRef::DefaultGraph, None)?;
+    ///
+    /// // we inspect the store contents
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// assert!(store.contains(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph))?);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn load_graph<'a>(
+        &self,
+        reader: impl BufRead,
+        format: GraphFormat,
+        to_graph_name: impl Into<GraphNameRef<'a>>,
+        base_iri: Option<&str>,
+    ) -> Result<(), LoaderError> {
+        let mut parser = GraphParser::from_format(format);
+        if let Some(base_iri) = base_iri {
+            parser = parser
+                .with_base_iri(base_iri)
+                .map_err(|e| ParseError::invalid_base_iri(base_iri, e))?;
+        }
+        let to_graph_name = to_graph_name.into();
+        self.load_ok_quads(
+            parser
+                .read_triples(reader)?
+                .filter_map(|r| self.handle_parse_error(r))
+                .map(|r| r.map(|triple| triple.in_graph(to_graph_name.into_owned()))),
+        )
+    }
+
+    /// Adds a set of quads to the store.
+    pub fn load_quads(
+        &self,
+        quads: impl IntoIterator<Item = impl Into<Quad>>,
+    ) -> Result<(), StorageError> {
+        self.load_ok_quads(quads.into_iter().map(Ok::<_, StorageError>))
+    }
+
+    /// Adds a set of quads to the store, stopping at the first error.
+    pub fn load_ok_quads<EI, EO: From<StorageError> + From<EI>>(
+        &self,
+        quads: impl IntoIterator<Item = Result<impl Into<Quad>, EI>>,
+    ) -> Result<(), EO> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut count = 0;
+        for quad in quads {
+            batch.push(quad?.into());
+            if batch.len() >= self.batch_size {
+                self.insert_batch(&mut batch, &mut count)?;
+            }
+        }
+        if !batch.is_empty() {
+            self.insert_batch(&mut batch, &mut count)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts and empties a batch, `count` being the number of quads read so far.
+    fn insert_batch(&self, batch: &mut Vec<Quad>, count: &mut u64) -> Result<(), StorageError> {
+        self.store.check_not_in_maintenance()?;
+        let quads = batch.iter().map(Quad::as_ref).collect::<Vec<_>>();
+        self.store
+            .storage
+            .transaction(|mut t| t.insert_batch(&quads))?;
+        *count += batch.len() as u64;
+        batch.clear();
+        if let Some(callback) = &self.on_progress {
+            callback(*count);
+        }
+        Ok(())
+    }
+
+    fn handle_parse_error<T>(
+        &self,
+        result: Result<T, ParseError>,
+    ) -> Option<Result<T, ParseError>> {
+        match result {
+            Ok(value) => Some(Ok(value)),
+            Err(error) => {
+                if let Some(callback) = &self.on_parse_error {
+                    callback(error).err().map(Err)
+                } else {
+                    Some(Err(error))
+                }
+            }
+        }
+    }
+}
+
+/// An incremental compaction of the write-ahead log of a [`Store`].
+///
+/// See [`Store::compact_write_ahead_log`].
+pub struct LogCompaction {
+    store: Store,
+    state: LogCompactionState,
+}
+
+impl LogCompaction {
+    /// Appends at most `batch_size` entries of the store content to the log.
+    ///
+    /// Returns `true` when the compaction is finished.
+    pub fn step(&mut self, batch_size: usize) -> Result<bool, StorageError> {
+        self.store
+            .storage
+            .compact_log_step(&mut self.state, batch_size)
+    }
+
+    /// Returns if the compaction is finished.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// The number of entries appended to the log so far.
+    #[inline]
+    pub fn entries_logged(&self) -> u64 {
+        self.state.entries_logged()
+    }
+}
+
+/// Solutions returned by [`Store::query_with_budget`] or [`Store::resume_query`].
+#[derive(Debug)]
+pub struct BudgetedSolutions {
+    variables: Vec<Variable>,
+    solutions: Vec<QuerySolution>,
+    continuation: Option<QueryContinuation>,
+}
+
+impl BudgetedSolutions {
+    /// The variables of the solutions.
+    #[inline]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// The solutions found before the budget has been exhausted.
+    #[inline]
+    pub fn solutions(&self) -> &[QuerySolution] {
+        &self.solutions
+    }
+
+    /// Returns the solutions found before the budget has been exhausted.
+    #[inline]
+    pub fn into_solutions(self) -> Vec<QuerySolution> {
+        self.solutions
+    }
+
+    /// The continuation to resume the query, `None` if all the solutions have been returned.
+    #[inline]
+    pub fn continuation(&self) -> Option<&QueryContinuation> {
+        self.continuation.as_ref()
+    }
+}
+
+/// The state of a query suspended by [`Store::query_with_budget`].
+///
+/// It can be serialized with [`to_bytes`](Self::to_bytes), for example to be kept in stable memory until it is resumed.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QueryContinuation {
+    query: String,
+    dataset_version: u64,
+    skip: u64,
+}
+
+impl QueryContinuation {
+    /// The number of solutions already returned.
+    #[inline]
+    pub fn solutions_returned(&self) -> u64 {
+        self.skip
+    }
+
+    /// Serializes the continuation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(16 + self.query.len());
+        buffer.extend_from_slice(&self.dataset_version.to_be_bytes());
+        buffer.extend_from_slice(&self.skip.to_be_bytes());
+        buffer.extend_from_slice(self.query.as_bytes());
+        buffer
+    }
+
+    /// Parses a continuation serialized with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+        if data.len() < 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The query continuation is truncated",
+            ));
+        }
+        let (dataset_version, data) = data.split_at(8);
+        let (skip, query) = data.split_at(8);
+        Ok(Self {
+            query: str::from_utf8(query)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                .into(),
+            dataset_version: u64::from_be_bytes(dataset_version.try_into().unwrap()),
+            skip: u64::from_be_bytes(skip.try_into().unwrap()),
+        })
+    }
+}
+
+/// The position of a page of solutions returned by [`Store::query_paged`].
+///
+/// The default token points to the first page.
+/// The tokens can be serialized using [`Display`](fmt::Display) and parsed back using [`FromStr`].
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Default)]
+pub struct PageToken {
+    cursor: u64,
+    offset: u64,
+}
+
+impl fmt::Display for PageToken {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}-{:x}", self.cursor, self.offset)
+    }
+}
+
+impl FromStr for PageToken {
+    type Err = PageTokenParseError;
+
+    fn from_str(s: &str) -> Result<Self, PageTokenParseError> {
+        let (cursor, offset) = s.split_once('-').ok_or(PageTokenParseError {})?;
+        Ok(Self {
+            cursor: u64::from_str_radix(cursor, 16).map_err(|_| PageTokenParseError {})?,
+            offset: u64::from_str_radix(offset, 16).map_err(|_| PageTokenParseError {})?,
+        })
+    }
+}
+
+/// An error raised while parsing a [`PageToken`].
+#[derive(Debug)]
+pub struct PageTokenParseError {}
+
+impl fmt::Display for PageTokenParseError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The page token is invalid")
+    }
+}
+
+impl Error for PageTokenParseError {}
+
+/// A page of solutions returned by [`Store::query_paged`].
+#[derive(Debug)]
+pub struct QueryPage {
+    variables: Vec<Variable>,
+    solutions: Vec<QuerySolution>,
+    next: Option<PageToken>,
+}
+
+impl QueryPage {
+    /// The variables of the solutions.
+    #[inline]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// The solutions of the page.
+    #[inline]
+    pub fn solutions(&self) -> &[QuerySolution] {
+        &self.solutions
+    }
+
+    /// Returns the solutions of the page.
+    #[inline]
+    pub fn into_solutions(self) -> Vec<QuerySolution> {
+        self.solutions
+    }
+
+    /// The token to get the next page, `None` if it is the last page.
+    #[inline]
+    pub fn next_token(&self) -> Option<PageToken> {
+        self.next
+    }
+}
+
+/// The position of a chunked export, see [`Store::dump_dataset_chunked`].
+///
+/// The default cursor starts the export.
+/// The cursors can be serialized using [`Display`](fmt::Display) and parsed back using [`FromStr`].
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Default)]
+pub struct DumpCursor {
+    dataset_version: u64,
+    offset: u64,
+}
+
+impl fmt::Display for DumpCursor {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}-{:x}", self.dataset_version, self.offset)
+    }
+}
+
+impl FromStr for DumpCursor {
+    type Err = DumpCursorParseError;
+
+    fn from_str(s: &str) -> Result<Self, DumpCursorParseError> {
+        let (dataset_version, offset) = s.split_once('-').ok_or(DumpCursorParseError {})?;
+        Ok(Self {
+            dataset_version: u64::from_str_radix(dataset_version, 16)
+                .map_err(|_| DumpCursorParseError {})?,
+            offset: u64::from_str_radix(offset, 16).map_err(|_| DumpCursorParseError {})?,
+        })
+    }
+}
+
+/// An error raised while parsing a [`DumpCursor`].
+#[derive(Debug)]
+pub struct DumpCursorParseError {}
+
+impl fmt::Display for DumpCursorParseError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The dump cursor is invalid")
+    }
+}
+
+impl Error for DumpCursorParseError {}
+
+/// A chunk of N-Quads returned by [`Store::dump_dataset_chunked`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct DatasetChunk {
+    data: Vec<u8>,
+    next: Option<DumpCursor>,
+}
+
+impl DatasetChunk {
+    /// The serialized statements.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the serialized statements.
+    #[inline]
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The cursor to get the next chunk, `None` if it is the last chunk.
+    #[inline]
+    pub fn next_cursor(&self) -> Option<DumpCursor> {
+        self.next
+    }
+}
+
+/// A dataset file loaded in chunks, started with [`Store::start_bulk_load`].
+#[derive(Clone)]
+pub struct BulkLoadSession {
+    store: Store,
+    id: u64,
+}
+
+impl BulkLoadSession {
+    /// The session id, allowing to get the session back with [`Store::bulk_load_session`].
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The number of bytes received so far.
+    pub fn len(&self) -> usize {
+        self.store
+            .bulk_loads
+            .read()
+            .unwrap()
+            .entries
+            .get(&self.id)
+            .map_or(0, |bulk_load| bulk_load.data.len())
+    }
+
+    /// Returns if no bytes have been received yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends the next chunk of the file.
+    ///
+    /// The chunks do not need to be aligned with the file statements.
+    /// Fails without appending the chunk if the session would receive more than 256MiB.
+    pub fn append(&self, chunk: &[u8]) -> Result<(), StorageError> {
+        let mut bulk_loads = self.store.bulk_loads.write().unwrap();
+        let data = &mut bulk_loads
+            .entries
+            .get_mut(&self.id)
+            .ok_or_else(|| self.closed_error())?
+            .data;
+        if data.len() + chunk.len() > MAX_BULK_LOAD_SESSION_SIZE {
+            return Err(StorageError::Other(
+                format!(
+                    "The bulk load session {} can not receive more than {MAX_BULK_LOAD_SESSION_SIZE} bytes",
+                    self.id
+                )
+                .into(),
+            ));
+        }
+        data.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Parses the whole file and loads it atomically into the store: nothing is inserted if it fails.
+    ///
+    /// The session is closed once the file is loaded or if the file is invalid.
+    /// It is kept open if the insertion fails with a [`LoaderError::Storage`] error,
+    /// for example if the store is in maintenance, so that the commit can be retried later.
+    ///
+    /// Usage example:
+    /// ```
+    /// use oxigraph::store::{LoaderError, StorageConfig, Store};
+    /// use oxigraph::io::DatasetFormat;
+    ///
+    /// let store = Store::new_with_config(&StorageConfig::default().with_max_quads(1))?;
+    /// let session = store.start_bulk_load(DatasetFormat::NQuads, None)?;
+    /// session.append(b"<http://example.com/a> <http://example.com> <http://example.com> .\n")?;
+    /// session.append(b"<http://example.com/b> <http://example.com> <http://example.com> .\n")?;
+    /// assert!(matches!(session.commit(), Err(LoaderError::Storage(_))));
+    /// assert!(store.is_empty()?);
+    /// assert!(!session.is_empty()); // The session is still open
+    /// session.abort();
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn commit(&self) -> Result<(), LoaderError> {
+        let bulk_load = self
+            .store
+            .bulk_loads
+            .write()
+            .unwrap()
+            .entries
+            .remove(&self.id)
+            .ok_or_else(|| self.closed_error())?;
+        let result = self.store.load_dataset(
+            bulk_load.data.as_slice(),
+            bulk_load.format,
+            bulk_load.base_iri.as_deref(),
+        );
+        if let Err(LoaderError::Storage(_)) = result {
+            self.store
+                .bulk_loads
+                .write()
+                .unwrap()
+                .entries
+                .insert(self.id, bulk_load);
+        }
+        result
+    }
+
+    /// Closes the session without loading anything.
+    pub fn abort(self) {
+        self.store
+            .bulk_loads
+            .write()
+            .unwrap()
+            .entries
+            .remove(&self.id);
+    }
+
+    fn closed_error(&self) -> StorageError {
+        StorageError::Other(format!("The bulk load session {} is not open", self.id).into())
+    }
+}
+
+#[derive(Default)]
+struct BulkLoads {
+    next_id: u64,
+    entries: BTreeMap<u64, BulkLoad>,
+}
+
+struct BulkLoad {
+    format: DatasetFormat,
+    base_iri: Option<String>,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Cursors {
+    next_id: u64,
+    entries: BTreeMap<u64, Cursor>,
+}
+
+struct Cursor {
+    query: String,
+    variables: Vec<Variable>,
+    solutions: Vec<Vec<Option<Term>>>,
+}
+
+struct Maintenance {
+    mode: MaintenanceMode,
+    queue: Vec<QueuedWrite>,
+}
+
+enum QueuedWrite {
+    Insert(Quad),
+    Remove(Quad),
+    Update(Update),
+}
+
+/// A view graph defined with [`Store::define_view_graph`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ViewGraph {
+    query: String,
+    graph_name: NamedOrBlankNode,
+    refreshed_at: Option<u64>,
+    dataset_version: Option<u64>,
+}
+
+impl ViewGraph {
+    /// The `CONSTRUCT` query computing the graph content.
+    #[inline]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The graph containing the query results.
+    #[inline]
+    pub fn graph_name(&self) -> NamedOrBlankNodeRef<'_> {
+        self.graph_name.as_ref()
+    }
+
+    /// The time of the last refresh in nanoseconds since the epoch, `None` if the graph has never been refreshed.
+    #[inline]
+    pub fn refreshed_at(&self) -> Option<u64> {
+        self.refreshed_at
+    }
+
+    /// The [dataset version](Store::dataset_version) just after the last refresh.
+    ///
+    /// The graph content is up to date as long as the store version is still the same.
+    #[inline]
+    pub fn dataset_version(&self) -> Option<u64> {
+        self.dataset_version
+    }
+}
+
+fn view_graph_key(name: &str) -> Vec<u8> {
+    let mut key = VIEW_GRAPH_PREFIX.to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// A rule defined with [`Store::define_rule`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct RuleDefinition {
+    query: String,
+    graph_name: NamedOrBlankNode,
+}
+
+impl RuleDefinition {
+    /// The `CONSTRUCT` query of the rule.
+    #[inline]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The graph containing the quads derived by the rule.
+    #[inline]
+    pub fn graph_name(&self) -> NamedOrBlankNodeRef<'_> {
+        self.graph_name.as_ref()
+    }
+}
+
+fn rule_key(name: &str) -> Vec<u8> {
+    let mut key = RULE_KEY_PREFIX.to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+fn encode_rule(rule: &RuleDefinition) -> Vec<u8> {
+    let graph_name = rule.graph_name.to_string();
+    let mut buffer = (graph_name.len() as u64).to_be_bytes().to_vec();
+    buffer.extend_from_slice(graph_name.as_bytes());
+    buffer.extend_from_slice(rule.query.as_bytes());
+    buffer
+}
+
+fn decode_rule(data: &[u8]) -> Result<RuleDefinition, StorageError> {
+    if data.len() < 8 {
+        return Err(CorruptionError::msg("Unexpected end of a rule encoding").into());
+    }
+    let (len, data) = data.split_at(8);
+    let len = len
+        .try_into()
+        .ok()
+        .and_then(|len| usize::try_from(u64::from_be_bytes(len)).ok())
+        .ok_or_else(|| CorruptionError::msg("Invalid rule encoding"))?;
+    if data.len() < len {
+        return Err(CorruptionError::msg("Unexpected end of a rule encoding").into());
+    }
+    let (graph_name, query) = data.split_at(len);
+    let graph_name = match str::from_utf8(graph_name)
+        .ok()
+        .and_then(|graph_name| Term::from_str(graph_name).ok())
+    {
+        Some(Term::NamedNode(node)) => node.into(),
+        Some(Term::BlankNode(node)) => node.into(),
+        _ => return Err(CorruptionError::msg("Invalid rule graph name").into()),
+    };
+    Ok(RuleDefinition {
+        query: str::from_utf8(query)
+            .map_err(|_| CorruptionError::msg("Invalid rule query encoding"))?
+            .to_owned(),
+        graph_name,
+    })
+}
+
+fn prefix_key(name: &str) -> Vec<u8> {
+    let mut key = PREFIX_KEY_PREFIX.to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+fn encode_view_graph(view: &ViewGraph) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let (Some(refreshed_at), Some(dataset_version)) = (view.refreshed_at, view.dataset_version) {
+        buffer.push(1);
+        buffer.extend_from_slice(&refreshed_at.to_be_bytes());
+        buffer.extend_from_slice(&dataset_version.to_be_bytes());
+    } else {
+        buffer.push(0);
+    }
+    let graph_name = view.graph_name.to_string();
+    buffer.extend_from_slice(&(graph_name.len() as u64).to_be_bytes());
+    buffer.extend_from_slice(graph_name.as_bytes());
+    buffer.extend_from_slice(view.query.as_bytes());
+    buffer
+}
+
+fn decode_view_graph(data: &[u8]) -> Result<ViewGraph, StorageError> {
+    fn read_u64(data: &mut &[u8]) -> Result<u64, StorageError> {
+        if data.len() < 8 {
+            return Err(CorruptionError::msg("Unexpected end of a view graph encoding").into());
+        }
+        let (value, rest) = data.split_at(8);
+        *data = rest;
+        Ok(u64::from_be_bytes(value.try_into().map_err(|_| {
+            CorruptionError::msg("Invalid view graph encoding")
+        })?))
+    }
+
+    let (flag, mut data) = data
+        .split_first()
+        .ok_or_else(|| CorruptionError::msg("Empty view graph encoding"))?;
+    let (refreshed_at, dataset_version) = if *flag == 1 {
+        (Some(read_u64(&mut data)?), Some(read_u64(&mut data)?))
+    } else {
+        (None, None)
+    };
+    let len = usize::try_from(read_u64(&mut data)?)
+        .map_err(|_| CorruptionError::msg("Invalid view graph encoding"))?;
+    if data.len() < len {
+        return Err(CorruptionError::msg("Unexpected end of a view graph encoding").into());
+    }
+    let (graph_name, query) = data.split_at(len);
+    let graph_name = match str::from_utf8(graph_name)
+        .ok()
+        .and_then(|graph_name| Term::from_str(graph_name).ok())
+    {
+        Some(Term::NamedNode(node)) => node.into(),
+        Some(Term::BlankNode(node)) => node.into(),
+        _ => return Err(CorruptionError::msg("Invalid view graph name").into()),
+    };
+    Ok(ViewGraph {
+        query: str::from_utf8(query)
+            .map_err(|_| CorruptionError::msg("Invalid view graph query encoding"))?
+            .to_owned(),
+        graph_name,
+        refreshed_at,
+        dataset_version,
+    })
+}
+
+/// The number of quads of the parts of a [`Store`] returned by [`Store::statistics`].
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Statistics {
+    /// The number of quads of the store.
+    pub quads: u64,
+    /// The number of quads of each predicate.
+    pub predicates: HashMap<NamedNode, u64>,
+    /// The number of quads of each graph, including the default graph.
+    pub graphs: HashMap<GraphName, u64>,
+    /// The number of quads of each predicate and class of object.
+    pub object_classes: HashMap<(NamedNode, ObjectClass), u64>,
+}
+
+/// A node of a SKOS concept hierarchy returned by [`Store::concept_scheme_tree`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ConceptTree {
+    /// The concept.
+    pub concept: NamedOrBlankNode,
+    /// The trees of the concepts directly narrower than [`concept`](Self::concept).
+    pub children: Vec<ConceptTree>,
 }
 
 /// An iterator returning the graph names contained in a [`Store`].
@@ -0,0 +1,444 @@
+//! Compact statistical summaries of a dataset, to exchange with federation peers.
+//!
+//! A [`DatasetSummary`] is built by [`Store::export_summary`](crate::store::Store::export_summary) with a full scan of the store.
+//! It contains:
+//! * the number of quads and subjects of each predicate.
+//! * the ranges of the literal values of each predicate, grouped by datatype.
+//! * the most frequent characteristic sets, i.e. the sets of predicates used together to describe a subject.
+//!
+//! It is serialized in a compact binary form with [`DatasetSummary::to_bytes`], that a peer canister
+//! reads back with [`DatasetSummary::from_bytes`] to select the relevant sources of a query without probing them.
+//!
+//! The subjects are counted per graph: a subject described in two graphs is counted twice.
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::model::*;
+//! use oxigraph::store::Store;
+//! use oxigraph::summary::DatasetSummary;
+//!
+//! let store = Store::new()?;
+//! let alice = NamedNodeRef::new("http://example.com/alice")?;
+//! let bob = NamedNodeRef::new("http://example.com/bob")?;
+//! let name = NamedNodeRef::new("http://example.com/name")?;
+//! let age = NamedNodeRef::new("http://example.com/age")?;
+//! store.insert(QuadRef::new(alice, name, LiteralRef::new_simple_literal("Alice"), GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(alice, age, &Literal::from(31), GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(bob, name, LiteralRef::new_simple_literal("Bob"), GraphNameRef::DefaultGraph))?;
+//! store.insert(QuadRef::new(bob, age, &Literal::from(7), GraphNameRef::DefaultGraph))?;
+//!
+//! let summary = DatasetSummary::from_bytes(&store.export_summary()?.to_bytes())?;
+//! assert_eq!(summary.quad_count(), 4);
+//! let age_summary = summary.predicate(age).unwrap();
+//! assert_eq!(age_summary.subject_count(), 2);
+//! assert_eq!(age_summary.ranges()[0].min(), &Literal::from(7));
+//! assert_eq!(age_summary.ranges()[0].max(), &Literal::from(31));
+//! assert_eq!(summary.estimate_subjects(&[name, age]), 2);
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::model::vocab::xsd;
+use crate::model::*;
+use crate::store::StorageError;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::mem::take;
+use std::str::{self, FromStr};
+
+/// Maximal number of characteristic sets kept in a summary, the most frequent ones being kept.
+const MAX_CHARACTERISTIC_SETS: usize = 256;
+
+/// A statistical summary of a dataset.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct DatasetSummary {
+    quad_count: u64,
+    subject_count: u64,
+    predicates: BTreeMap<NamedNode, PredicateSummary>,
+    characteristic_sets: Vec<CharacteristicSet>,
+}
+
+impl DatasetSummary {
+    /// The number of quads of the dataset.
+    #[inline]
+    pub fn quad_count(&self) -> u64 {
+        self.quad_count
+    }
+
+    /// The number of distinct subjects of each graph, summed over the graphs.
+    #[inline]
+    pub fn subject_count(&self) -> u64 {
+        self.subject_count
+    }
+
+    /// The summaries of the predicates, ordered by IRI.
+    pub fn predicates(&self) -> impl Iterator<Item = (&NamedNode, &PredicateSummary)> {
+        self.predicates.iter()
+    }
+
+    /// The summary of a predicate, `None` if the predicate is not used.
+    pub fn predicate<'a>(
+        &self,
+        predicate: impl Into<NamedNodeRef<'a>>,
+    ) -> Option<&PredicateSummary> {
+        self.predicates.get(&predicate.into().into_owned())
+    }
+
+    /// The most frequent characteristic sets, ordered by decreasing number of subjects.
+    #[inline]
+    pub fn characteristic_sets(&self) -> &[CharacteristicSet] {
+        &self.characteristic_sets
+    }
+
+    /// Estimates the number of subjects described with all the given predicates.
+    ///
+    /// The estimation is a lower bound, the least frequent characteristic sets being dropped from the summary.
+    pub fn estimate_subjects(&self, predicates: &[NamedNodeRef<'_>]) -> u64 {
+        self.characteristic_sets
+            .iter()
+            .filter(|set| predicates.iter().all(|p| set.contains(*p)))
+            .map(|set| set.subject_count)
+            .sum()
+    }
+
+    /// Serializes the summary in a compact binary form.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_u64(&mut buffer, self.quad_count);
+        write_u64(&mut buffer, self.subject_count);
+        write_u64(&mut buffer, self.predicates.len() as u64);
+        for (predicate, summary) in &self.predicates {
+            write_str(&mut buffer, predicate.as_str());
+            write_u64(&mut buffer, summary.quad_count);
+            write_u64(&mut buffer, summary.subject_count);
+            write_u64(&mut buffer, summary.ranges.len() as u64);
+            for range in &summary.ranges {
+                write_u64(&mut buffer, range.count);
+                write_str(&mut buffer, &range.min.to_string());
+                write_str(&mut buffer, &range.max.to_string());
+            }
+        }
+        // The predicates of the characteristic sets are written as indexes in the predicate list
+        let indexes = self
+            .predicates
+            .keys()
+            .enumerate()
+            .map(|(i, predicate)| (predicate, i as u64))
+            .collect::<HashMap<_, _>>();
+        write_u64(&mut buffer, self.characteristic_sets.len() as u64);
+        for set in &self.characteristic_sets {
+            write_u64(&mut buffer, set.subject_count);
+            write_u64(&mut buffer, set.predicates.len() as u64);
+            for predicate in &set.predicates {
+                write_u64(&mut buffer, indexes[predicate]);
+            }
+        }
+        buffer
+    }
+
+    /// Reads a summary serialized with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, io::Error> {
+        let data = &mut data;
+        let quad_count = read_u64(data)?;
+        let subject_count = read_u64(data)?;
+        let mut predicates = BTreeMap::new();
+        for _ in 0..read_u64(data)? {
+            let predicate = NamedNode::new(read_str(data)?).map_err(invalid_data)?;
+            let predicate_quad_count = read_u64(data)?;
+            let predicate_subject_count = read_u64(data)?;
+            let mut ranges = Vec::new();
+            for _ in 0..read_u64(data)? {
+                let count = read_u64(data)?;
+                let min = Literal::from_str(read_str(data)?).map_err(invalid_data)?;
+                let max = Literal::from_str(read_str(data)?).map_err(invalid_data)?;
+                if min.datatype() != max.datatype() {
+                    return Err(invalid_data(
+                        "The bounds of a value range have different datatypes",
+                    ));
+                }
+                ranges.push(ValueRange { min, max, count });
+            }
+            predicates.insert(
+                predicate,
+                PredicateSummary {
+                    quad_count: predicate_quad_count,
+                    subject_count: predicate_subject_count,
+                    ranges,
+                },
+            );
+        }
+        let predicate_list = predicates.keys().cloned().collect::<Vec<_>>();
+        let mut characteristic_sets = Vec::new();
+        for _ in 0..read_u64(data)? {
+            let set_subject_count = read_u64(data)?;
+            let mut set_predicates = Vec::new();
+            for _ in 0..read_u64(data)? {
+                let predicate = usize::try_from(read_u64(data)?)
+                    .ok()
+                    .and_then(|i| predicate_list.get(i))
+                    .ok_or_else(|| {
+                        invalid_data("Invalid predicate index in a characteristic set")
+                    })?;
+                set_predicates.push(predicate.clone());
+            }
+            set_predicates.sort();
+            characteristic_sets.push(CharacteristicSet {
+                predicates: set_predicates,
+                subject_count: set_subject_count,
+            });
+        }
+        if !data.is_empty() {
+            return Err(invalid_data("Unexpected trailing bytes after the summary"));
+        }
+        Ok(Self {
+            quad_count,
+            subject_count,
+            predicates,
+            characteristic_sets,
+        })
+    }
+}
+
+/// The statistics of a predicate in a [`DatasetSummary`].
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct PredicateSummary {
+    quad_count: u64,
+    subject_count: u64,
+    ranges: Vec<ValueRange>,
+}
+
+impl PredicateSummary {
+    /// The number of quads with this predicate.
+    #[inline]
+    pub fn quad_count(&self) -> u64 {
+        self.quad_count
+    }
+
+    /// The number of subjects described with this predicate.
+    #[inline]
+    pub fn subject_count(&self) -> u64 {
+        self.subject_count
+    }
+
+    /// The ranges of the literal objects, one per datatype.
+    #[inline]
+    pub fn ranges(&self) -> &[ValueRange] {
+        &self.ranges
+    }
+
+    fn add_literal(&mut self, literal: &Literal) {
+        if let Some(range) = self
+            .ranges
+            .iter_mut()
+            .find(|range| range.min.datatype() == literal.datatype())
+        {
+            range.count += 1;
+            if compare_literals(literal, &range.min) == Ordering::Less {
+                range.min = literal.clone();
+            }
+            if compare_literals(literal, &range.max) == Ordering::Greater {
+                range.max = literal.clone();
+            }
+        } else {
+            self.ranges.push(ValueRange {
+                min: literal.clone(),
+                max: literal.clone(),
+                count: 1,
+            });
+        }
+    }
+}
+
+/// The smallest and the greatest literal objects of a predicate with a given datatype.
+///
+/// The numeric literals are compared by value and the other ones by lexical form.
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub struct ValueRange {
+    min: Literal,
+    max: Literal,
+    count: u64,
+}
+
+impl ValueRange {
+    /// The datatype of the literals.
+    #[inline]
+    pub fn datatype(&self) -> NamedNodeRef<'_> {
+        self.min.datatype()
+    }
+
+    /// The smallest literal.
+    #[inline]
+    pub fn min(&self) -> &Literal {
+        &self.min
+    }
+
+    /// The greatest literal.
+    #[inline]
+    pub fn max(&self) -> &Literal {
+        &self.max
+    }
+
+    /// The number of literals with this datatype.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// A set of predicates used together to describe some subjects.
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub struct CharacteristicSet {
+    predicates: Vec<NamedNode>,
+    subject_count: u64,
+}
+
+impl CharacteristicSet {
+    /// The predicates, ordered by IRI.
+    #[inline]
+    pub fn predicates(&self) -> &[NamedNode] {
+        &self.predicates
+    }
+
+    /// The number of subjects described with exactly these predicates.
+    #[inline]
+    pub fn subject_count(&self) -> u64 {
+        self.subject_count
+    }
+
+    /// Checks if the set contains a predicate.
+    pub fn contains<'a>(&self, predicate: impl Into<NamedNodeRef<'a>>) -> bool {
+        let predicate = predicate.into();
+        self.predicates
+            .binary_search_by(|p| p.as_ref().cmp(&predicate))
+            .is_ok()
+    }
+}
+
+/// Builds a summary from quads grouped by graph and subject, like the ones returned by a full scan of the store.
+pub(crate) fn summarize(
+    quads: impl IntoIterator<Item = Result<Quad, StorageError>>,
+) -> Result<DatasetSummary, StorageError> {
+    let mut builder = SummaryBuilder::default();
+    for quad in quads {
+        builder.add(quad?);
+    }
+    Ok(builder.finish())
+}
+
+/// Builds a summary incrementally from quads grouped by graph and subject.
+#[derive(Default)]
+pub(crate) struct SummaryBuilder {
+    summary: DatasetSummary,
+    current: Option<(GraphName, Subject)>,
+    current_predicates: BTreeSet<NamedNode>,
+    sets: HashMap<Vec<NamedNode>, u64>,
+}
+
+impl SummaryBuilder {
+    pub(crate) fn add(&mut self, quad: Quad) {
+        let key = (quad.graph_name, quad.subject);
+        if self.current.as_ref() != Some(&key) {
+            add_characteristic_set(
+                &mut self.summary,
+                &mut self.sets,
+                take(&mut self.current_predicates),
+            );
+            self.current = Some(key);
+        }
+        self.summary.quad_count += 1;
+        let predicate = self
+            .summary
+            .predicates
+            .entry(quad.predicate.clone())
+            .or_default();
+        predicate.quad_count += 1;
+        if let Term::Literal(literal) = &quad.object {
+            predicate.add_literal(literal);
+        }
+        if self.current_predicates.insert(quad.predicate) {
+            predicate.subject_count += 1;
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> DatasetSummary {
+        add_characteristic_set(&mut self.summary, &mut self.sets, self.current_predicates);
+        let mut sets = self
+            .sets
+            .into_iter()
+            .map(|(predicates, subject_count)| CharacteristicSet {
+                predicates,
+                subject_count,
+            })
+            .collect::<Vec<_>>();
+        sets.sort_by(|a, b| {
+            b.subject_count
+                .cmp(&a.subject_count)
+                .then_with(|| a.predicates.cmp(&b.predicates))
+        });
+        sets.truncate(MAX_CHARACTERISTIC_SETS);
+        self.summary.characteristic_sets = sets;
+        self.summary
+    }
+}
+
+fn add_characteristic_set(
+    summary: &mut DatasetSummary,
+    sets: &mut HashMap<Vec<NamedNode>, u64>,
+    predicates: BTreeSet<NamedNode>,
+) {
+    if !predicates.is_empty() {
+        summary.subject_count += 1;
+        *sets.entry(predicates.into_iter().collect()).or_default() += 1;
+    }
+}
+
+fn compare_literals(a: &Literal, b: &Literal) -> Ordering {
+    let datatype = a.datatype();
+    if datatype == xsd::INTEGER
+        || datatype == xsd::DECIMAL
+        || datatype == xsd::DOUBLE
+        || datatype == xsd::FLOAT
+    {
+        if let (Ok(a), Ok(b)) = (f64::from_str(a.value()), f64::from_str(b.value())) {
+            if let Some(ordering) = a.partial_cmp(&b) {
+                return ordering;
+            }
+        }
+    }
+    a.value().cmp(b.value())
+}
+
+fn write_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_str(buffer: &mut Vec<u8>, value: &str) {
+    write_u64(buffer, value.len() as u64);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+fn read_u64(data: &mut &[u8]) -> Result<u64, io::Error> {
+    if data.len() < 8 {
+        return Err(invalid_data("Unexpected end of the summary"));
+    }
+    let (value, rest) = data.split_at(8);
+    *data = rest;
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(value);
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_str<'a>(data: &mut &'a [u8]) -> Result<&'a str, io::Error> {
+    let len = usize::try_from(read_u64(data)?).map_err(invalid_data)?;
+    if data.len() < len {
+        return Err(invalid_data("Unexpected end of the summary"));
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    str::from_utf8(value).map_err(invalid_data)
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
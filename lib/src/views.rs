@@ -0,0 +1,380 @@
+//! Materialized views: SPARQL query results stored and kept up to date when the store changes.
+//!
+//! Expensive queries, like the ones behind dashboards, can be registered as views
+//! whose results are computed once and then maintained from the [change feed](Store::subscribe) of the store:
+//! * the results of a `CONSTRUCT` view are stored in a named graph of the store.
+//! * the results of a `SELECT` view are stored in a solution table kept in memory.
+//!
+//! The quad insertions are applied incrementally to the `CONSTRUCT` views only made of basic graph patterns,
+//! `UNION`, `FILTER` and `BIND` with a template without blank nodes.
+//! The other changes of the data read by a view mark it as stale.
+//! The stale views are fully recomputed by [`MaterializedViews::refresh_stale`],
+//! which should be called periodically, for example from a canister timer.
+//!
+//! The changes of the view graphs themselves are ignored.
+//!
+//! Usage example:
+//! ```
+//! use oxigraph::model::*;
+//! use oxigraph::store::Store;
+//! use oxigraph::views::MaterializedViews;
+//!
+//! let store = Store::new()?;
+//! let views = MaterializedViews::new(store.clone());
+//! let a = NamedNodeRef::new("http://example.com/a")?;
+//! let b = NamedNodeRef::new("http://example.com/b")?;
+//! let knows = NamedNodeRef::new("http://example.com/knows")?;
+//! let known_by = NamedNodeRef::new("http://example.com/knownBy")?;
+//! let view = NamedNodeRef::new("http://example.com/view")?;
+//! views.create_graph_view(
+//!     "known_by",
+//!     "CONSTRUCT { ?b <http://example.com/knownBy> ?a } WHERE { ?a <http://example.com/knows> ?b }",
+//!     view,
+//! )?;
+//! views.create_table_view("count", "SELECT (COUNT(*) AS ?c) WHERE { ?s ?p ?o }")?;
+//!
+//! // The insertion is applied incrementally to the graph view
+//! store.insert(QuadRef::new(a, knows, b, GraphNameRef::DefaultGraph))?;
+//! assert!(store.contains(QuadRef::new(b, known_by, a, view))?);
+//! assert_eq!(views.is_stale("known_by"), Some(false));
+//!
+//! // The other changes require a full refresh
+//! assert_eq!(views.is_stale("count"), Some(true));
+//! store.remove(QuadRef::new(a, knows, b, GraphNameRef::DefaultGraph))?;
+//! assert_eq!(views.is_stale("known_by"), Some(true));
+//! assert_eq!(views.refresh_stale()?, 2);
+//! assert!(!store.contains(QuadRef::new(b, known_by, a, view))?);
+//! assert_eq!(
+//!     views.table("count").unwrap().rows(),
+//!     [vec![Some(Literal::from(0).into())]]
+//! );
+//! # Result::<_, Box<dyn std::error::Error>>::Ok(())
+//! ```
+use crate::model::*;
+use crate::sparql::{
+    ChangeEffect, EvaluationError, IncrementalQuery, Query, QueryResults, Variable,
+};
+use crate::store::{QuadChange, QuadPattern, StorageError, Store, SubscriptionId};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// A set of materialized views over a [`Store`].
+///
+/// The views are maintained as long as this object is alive.
+/// Their definitions are not persisted and should be registered again after a canister upgrade.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct MaterializedViews {
+    inner: Arc<ViewsInner>,
+}
+
+impl MaterializedViews {
+    /// Starts to track the changes of the store to maintain the views.
+    pub fn new(store: Store) -> Self {
+        let inner = Arc::new_cyclic(|inner| {
+            let inner = inner.clone();
+            let subscription = store.subscribe(QuadPattern::default(), move |changes| {
+                if let Some(inner) = inner.upgrade() {
+                    inner.apply_changes(changes);
+                }
+            });
+            ViewsInner {
+                store,
+                views: RwLock::default(),
+                subscription,
+            }
+        });
+        Self { inner }
+    }
+
+    /// Registers a `CONSTRUCT` query as a view whose results are stored in the graph `graph_name`.
+    ///
+    /// The current content of the graph is replaced by the query results.
+    /// A view with the same name is replaced.
+    pub fn create_graph_view<'a>(
+        &self,
+        name: impl Into<String>,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+        graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
+    ) -> Result<(), EvaluationError> {
+        let query = IncrementalQuery::new(query.try_into().map_err(Into::into)?);
+        if !query.is_construct() {
+            return Err(EvaluationError::msg(
+                "A graph view must be defined by a CONSTRUCT query",
+            ));
+        }
+        self.create_view(
+            name.into(),
+            query,
+            ViewTarget::Graph(graph_name.into().into_owned()),
+        )
+    }
+
+    /// Registers a `SELECT` query as a view whose results are stored in a [solution table](ViewTable).
+    ///
+    /// A view with the same name is replaced.
+    pub fn create_table_view(
+        &self,
+        name: impl Into<String>,
+        query: impl TryInto<Query, Error = impl Into<EvaluationError>>,
+    ) -> Result<(), EvaluationError> {
+        let query = IncrementalQuery::new(query.try_into().map_err(Into::into)?);
+        if !query.is_select() {
+            return Err(EvaluationError::msg(
+                "A table view must be defined by a SELECT query",
+            ));
+        }
+        self.create_view(name.into(), query, ViewTarget::Table(None))
+    }
+
+    fn create_view(
+        &self,
+        name: String,
+        query: IncrementalQuery,
+        target: ViewTarget,
+    ) -> Result<(), EvaluationError> {
+        self.inner.views.write().unwrap().insert(
+            name.clone(),
+            View {
+                query: Arc::new(query),
+                target,
+                stale: true,
+            },
+        );
+        if let Err(e) = self.inner.refresh(&name) {
+            self.inner.views.write().unwrap().remove(&name);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Removes a view.
+    ///
+    /// The content of the graph of a `CONSTRUCT` view is kept.
+    ///
+    /// Returns `true` if the view existed.
+    pub fn remove_view(&self, name: &str) -> bool {
+        self.inner.views.write().unwrap().remove(name).is_some()
+    }
+
+    /// Returns the names of the views.
+    pub fn names(&self) -> Vec<String> {
+        self.inner.views.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Returns if the view results are outdated, `None` if the view does not exist.
+    pub fn is_stale(&self, name: &str) -> Option<bool> {
+        Some(self.inner.views.read().unwrap().get(name)?.stale)
+    }
+
+    /// Returns the solution table of a `SELECT` view.
+    ///
+    /// The table may be outdated if the view [is stale](Self::is_stale).
+    pub fn table(&self, name: &str) -> Option<ViewTable> {
+        if let ViewTarget::Table(table) = &self.inner.views.read().unwrap().get(name)?.target {
+            table.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Recomputes the results of a view from scratch.
+    ///
+    /// Returns `false` if the view does not exist.
+    pub fn refresh(&self, name: &str) -> Result<bool, EvaluationError> {
+        self.inner.refresh(name)
+    }
+
+    /// Recomputes the results of all the stale views and returns their number.
+    pub fn refresh_stale(&self) -> Result<usize, EvaluationError> {
+        let stale = self
+            .inner
+            .views
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, view)| view.stale)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for name in stale {
+            if self.inner.refresh(&name)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// The results of a `SELECT` view.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ViewTable {
+    variables: Vec<Variable>,
+    rows: Vec<Vec<Option<Term>>>,
+}
+
+impl ViewTable {
+    /// The variables of the table columns.
+    #[inline]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// The solutions, each value being the one of the variable with the same index or `None` if unbound.
+    #[inline]
+    pub fn rows(&self) -> &[Vec<Option<Term>>] {
+        &self.rows
+    }
+}
+
+struct ViewsInner {
+    store: Store,
+    views: RwLock<BTreeMap<String, View>>,
+    subscription: SubscriptionId,
+}
+
+struct View {
+    query: Arc<IncrementalQuery>,
+    target: ViewTarget,
+    stale: bool,
+}
+
+enum ViewTarget {
+    Graph(NamedOrBlankNode),
+    /// `None` before the first evaluation.
+    Table(Option<ViewTable>),
+}
+
+impl ViewTarget {
+    fn graph_name(&self) -> Option<&NamedOrBlankNode> {
+        match self {
+            Self::Graph(graph_name) => Some(graph_name),
+            Self::Table(_) => None,
+        }
+    }
+}
+
+impl ViewsInner {
+    fn refresh(&self, name: &str) -> Result<bool, EvaluationError> {
+        let (query, graph_name) = {
+            let views = self.views.read().unwrap();
+            let view = if let Some(view) = views.get(name) {
+                view
+            } else {
+                return Ok(false);
+            };
+            (Arc::clone(&view.query), view.target.graph_name().cloned())
+        };
+        let table = match self.store.query(query.query().clone())? {
+            QueryResults::Graph(triples) => {
+                let triples = triples.collect::<Result<Vec<_>, _>>()?;
+                if let Some(graph_name) = graph_name {
+                    self.store.transaction(|mut transaction| {
+                        transaction.clear_graph(&graph_name)?;
+                        for triple in &triples {
+                            transaction.insert(triple.as_ref().in_graph(&graph_name))?;
+                        }
+                        Result::<_, StorageError>::Ok(())
+                    })?;
+                }
+                None
+            }
+            QueryResults::Solutions(solutions) => {
+                let variables = solutions.variables().to_vec();
+                let rows = solutions
+                    .map(|solution| {
+                        let solution = solution?;
+                        Ok(variables.iter().map(|v| solution.get(v).cloned()).collect())
+                    })
+                    .collect::<Result<_, EvaluationError>>()?;
+                Some(ViewTable { variables, rows })
+            }
+            QueryResults::Boolean(_) => None,
+        };
+        if let Some(view) = self.views.write().unwrap().get_mut(name) {
+            if let ViewTarget::Table(current) = &mut view.target {
+                *current = table;
+            }
+            view.stale = false;
+        }
+        Ok(true)
+    }
+
+    fn apply_changes(&self, changes: &[QuadChange]) {
+        let (views, graph_names) = {
+            let views = self.views.read().unwrap();
+            (
+                views
+                    .iter()
+                    .filter(|(_, view)| !view.stale)
+                    .map(|(name, view)| {
+                        (
+                            name.clone(),
+                            Arc::clone(&view.query),
+                            view.target.graph_name().cloned(),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+                views
+                    .values()
+                    .filter_map(|view| view.target.graph_name().cloned())
+                    .map(GraphName::from)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        for (name, query, graph_name) in views {
+            let mut additions = Vec::new();
+            let mut stale = false;
+            for change in changes {
+                let quad = change.quad();
+                if graph_names.contains(&quad.graph_name) {
+                    continue;
+                }
+                match query.effect(quad.as_ref(), matches!(change, QuadChange::Inserted(_))) {
+                    ChangeEffect::Unchanged => (),
+                    ChangeEffect::Additions(queries) => additions.extend(queries),
+                    ChangeEffect::Unknown => {
+                        stale = true;
+                        break;
+                    }
+                }
+            }
+            if !stale && !additions.is_empty() {
+                stale = if let Some(graph_name) = graph_name {
+                    self.insert_results(additions, &graph_name).is_err()
+                } else {
+                    true
+                };
+            }
+            if stale {
+                // The view is fully refreshed later, the error is then returned if it is still there
+                if let Some(view) = self.views.write().unwrap().get_mut(&name) {
+                    view.stale = true;
+                }
+            }
+        }
+    }
+
+    fn insert_results(
+        &self,
+        queries: Vec<Query>,
+        graph_name: &NamedOrBlankNode,
+    ) -> Result<(), EvaluationError> {
+        let mut quads = Vec::new();
+        for query in queries {
+            if let QueryResults::Graph(triples) = self.store.query(query)? {
+                for triple in triples {
+                    quads.push(triple?.in_graph(graph_name.clone()));
+                }
+            }
+        }
+        Ok(self.store.extend(quads)?)
+    }
+}
+
+impl Drop for ViewsInner {
+    fn drop(&mut self) {
+        self.store.unsubscribe(self.subscription);
+    }
+}